@@ -5,6 +5,7 @@
 
 void put_rng_init();
 void put_rng_reseed(const uint8_t *buffer, size_t length);
+size_t put_rng_state(uint8_t *buffer, size_t length);
 
 #ifndef thread_local
 // since C11 the standard include _Thread_local
@@ -20,6 +21,8 @@ void put_rng_reseed(const uint8_t *buffer, size_t length);
 #endif
 #endif
 
+#define UNUSED(x) (void)(x)
+
 #ifndef USE_CUSTOM_PRNG // use OpenSSL's default PRNG
 
 void put_rng_init()
@@ -32,14 +35,20 @@ void put_rng_reseed(const uint8_t *buffer, size_t length)
     RAND_seed(buffer, length);
 }
 
+size_t put_rng_state(uint8_t *buffer, size_t length)
+{
+    UNUSED(buffer);
+    UNUSED(length);
+    // the state of the default PRNG can not be restored
+    return 0;
+}
+
 #else // use our custom PRNG
 
 #define DEFAULT_RNG_SEED 42
 
 static thread_local uint64_t seed = DEFAULT_RNG_SEED;
 
-#define UNUSED(x) (void)(x)
-
 static int stdlib_rand_seed(const void *buf, int num)
 {
     put_rng_reseed(buf, num);
@@ -98,4 +107,15 @@ void put_rng_reseed(const uint8_t *buffer, size_t length)
     seed = *((uint64_t *)buffer);
 }
 
+size_t put_rng_state(uint8_t *buffer, size_t length)
+{
+    if (buffer == NULL || length < sizeof(uint64_t))
+    {
+        return 0;
+    }
+
+    *((uint64_t *)buffer) = seed;
+    return sizeof(uint64_t);
+}
+
 #endif // USE_CUSTOM_PRNG
@@ -16,7 +16,7 @@ use wolfssl_sys as wolf;
 
 use crate::callbacks::{ssl_msg_callback, ExtraUserDataRegistry};
 use crate::error::{ErrorCode, ErrorStack, InnerError, SslError};
-use crate::util::{cvt, cvt_p};
+use crate::util::{cvt, cvt_n, cvt_p};
 use crate::x509::X509Ref;
 use crate::{bio, TLSVersion};
 
@@ -288,6 +288,13 @@ impl SslContextRef {
     pub fn set_num_tickets(&mut self, n: u64) -> Result<(), ErrorStack> {
         unsafe { cvt(wolf::wolfSSL_CTX_set_num_tickets(self.as_ptr(), n)).map(|_| ()) }
     }
+
+    /// Sets the maximum amount of early data which a TLS 1.3 server accepts.
+    ///
+    /// This corresponds to `wolfSSL_CTX_set_max_early_data`.
+    pub fn set_max_early_data(&mut self, bytes: u32) -> Result<(), ErrorStack> {
+        unsafe { cvt_n(wolf::wolfSSL_CTX_set_max_early_data(self.as_ptr(), bytes)).map(|_| ()) }
+    }
 }
 
 /// WolfSSL library initialization (done only once statically)
@@ -678,6 +685,30 @@ impl<S: Read + Write> SslStream<S> {
         }
     }
 
+    /// Reads the early data which a client sent before the handshake finished. Returns `Ok(0)`
+    /// if there is no early data to read.
+    ///
+    /// As long as the server reads early data, the handshake is continued by this function
+    /// instead of [`SslStream::do_handshake`].
+    ///
+    /// This corresponds to `wolfSSL_read_early_data`.
+    pub fn read_early_data(&mut self, buf: &mut [u8]) -> Result<usize, SslError> {
+        let mut read: c_int = 0;
+        let ret = unsafe {
+            wolf::wolfSSL_read_early_data(
+                self.ssl.as_ptr(),
+                buf.as_mut_ptr() as *mut c_void,
+                buf.len() as c_int,
+                &mut read,
+            )
+        };
+        if ret >= 0 {
+            Ok(read as usize)
+        } else {
+            Err(self.make_error(ret))
+        }
+    }
+
     fn get_raw_rbio(&self) -> *mut bio::BIO {
         unsafe { wolf::wolfSSL_SSL_get_rbio(self.ssl.as_ptr()) }
     }
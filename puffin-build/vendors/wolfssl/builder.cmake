@@ -42,6 +42,7 @@ autotools_builder(
     --enable-keygen  # support for RSA certs
     --enable-certgen # support x509 decoding
     --enable-tls13
+    --enable-earlydata # accepted if the PUT option max_early_data is set
     --enable-dtls
     --enable-sp
     --disable-sha3
@@ -6,10 +6,12 @@
 //! Each [`Agent`] has an *inbound* and an *outbound* channel (see [`crate::stream`])
 
 use core::fmt;
+use std::mem::ManuallyDrop;
 
 use serde::{Deserialize, Serialize};
 
 use crate::error::Error;
+use crate::fuzzer::coverage::with_namespace;
 use crate::protocol::ProtocolBehavior;
 use crate::put::Put;
 use crate::stream::Stream;
@@ -37,6 +39,11 @@ impl AgentName {
     pub const fn first() -> Self {
         AgentName::new()
     }
+
+    /// The agent with the number `index`, as it is displayed.
+    pub const fn from_index(index: u8) -> Self {
+        AgentName(index)
+    }
 }
 
 impl Default for AgentName {
@@ -64,8 +71,9 @@ pub struct AgentDescriptor {
     pub tls_version: TLSVersion,
     /// Whether the agent which holds this descriptor is a server.
     pub typ: AgentType,
-    /// Whether we want to try to reuse a previous agent. This is needed for TLS session resumption
-    /// as openssl agents rotate ticket keys if they are recreated.
+    /// Whether we want to try to reuse an agent of a prior trace, see [`crate::trace::Trace`].
+    /// This is needed for TLS session resumption as openssl agents rotate ticket keys if they
+    /// are recreated.
     pub try_reuse: bool,
     /// If agent is a server:
     ///   Make client auth. a requirement.
@@ -143,16 +151,20 @@ pub enum TLSVersion {
 }
 
 /// An [`Agent`] holds a non-cloneable reference to a Stream.
+///
+/// All calls into the PUT, including its drop, are run in the coverage name space of the PUT, see
+/// [`with_namespace`].
 pub struct Agent<PB: ProtocolBehavior> {
     descriptor: AgentDescriptor,
-    put: Box<dyn Put<PB>>,
+    namespace: usize,
+    put: ManuallyDrop<Box<dyn Put<PB>>>,
 }
 
 impl<PB: ProtocolBehavior> fmt::Debug for Agent<PB> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Agent")
             .field("descriptor", &self.descriptor)
-            .field("put", &self.put.describe_state())
+            .field("put", &self.describe_state())
             .finish()
     }
 }
@@ -160,49 +172,76 @@ impl<PB: ProtocolBehavior> fmt::Debug for Agent<PB> {
 impl<PB: ProtocolBehavior> PartialEq for Agent<PB> {
     fn eq(&self, other: &Self) -> bool {
         self.descriptor.name.eq(&other.descriptor.name)
-            && self.put.describe_state() == other.put.describe_state()
+            && self.describe_state() == other.describe_state()
+    }
+}
+
+impl<PB: ProtocolBehavior> Drop for Agent<PB> {
+    fn drop(&mut self) {
+        // SAFETY: the PUT is not used after it has been dropped
+        with_namespace(self.namespace, || unsafe {
+            ManuallyDrop::drop(&mut self.put)
+        })
     }
 }
 
 impl<PB: ProtocolBehavior> Agent<PB> {
-    pub fn new(descriptor: AgentDescriptor, put: Box<dyn Put<PB>>) -> Self {
-        Self { descriptor, put }
+    /// Creates an agent whose PUT tracks its coverage in the name space `namespace`, see
+    /// [`namespace`](crate::fuzzer::coverage::namespace).
+    pub fn new(descriptor: AgentDescriptor, namespace: usize, put: Box<dyn Put<PB>>) -> Self {
+        Self {
+            descriptor,
+            namespace,
+            put: ManuallyDrop::new(put),
+        }
     }
 
     pub fn progress(&mut self) -> Result<(), Error> {
-        self.put.progress()
+        with_namespace(self.namespace, || self.put.progress())
     }
 
     pub fn reset(&mut self, new_name: AgentName) -> Result<(), Error> {
         self.descriptor.name = new_name;
-        self.put.reset(new_name)
+        with_namespace(self.namespace, || self.put.reset(new_name))
+    }
+
+    pub fn renegotiate(&mut self) -> Result<(), Error> {
+        with_namespace(self.namespace, || self.put.renegotiate())
+    }
+
+    pub fn key_update(&mut self, update_requested: bool) -> Result<(), Error> {
+        with_namespace(self.namespace, || self.put.key_update(update_requested))
     }
 
     /// Shut down the agent by consuming it and returning a string that summarizes the execution.
     pub fn shutdown(&mut self) -> String {
-        self.put.shutdown()
+        with_namespace(self.namespace, || self.put.shutdown())
     }
 
     /// Checks whether the agent is in a good state.
     pub fn is_state_successful(&self) -> bool {
-        self.put.is_state_successful()
+        with_namespace(self.namespace, || self.put.is_state_successful())
+    }
+
+    fn describe_state(&self) -> String {
+        with_namespace(self.namespace, || self.put.describe_state().to_string())
     }
 
-    /// Checks whether the agent is reusable with the descriptor.
+    /// Checks whether the agent is reusable with the descriptor, i.e. whether its PUT has been
+    /// created with the same configuration.
     pub fn is_reusable_with(&self, other: &AgentDescriptor) -> bool {
-        self.descriptor.typ == other.typ && self.descriptor.tls_version == other.tls_version
+        self.descriptor.typ == other.typ
+            && self.descriptor.tls_version == other.tls_version
+            && self.descriptor.client_authentication == other.client_authentication
+            && self.descriptor.server_authentication == other.server_authentication
     }
 
     pub fn name(&self) -> AgentName {
         self.descriptor.name
     }
 
-    pub fn put(&self) -> &dyn Put<PB> {
-        self.put.as_ref()
-    }
-
-    pub fn put_mut(&mut self) -> &mut dyn Put<PB> {
-        self.put.as_mut()
+    pub fn descriptor(&self) -> &AgentDescriptor {
+        &self.descriptor
     }
 }
 
@@ -214,13 +253,19 @@ impl<PB: ProtocolBehavior>
         PB::OpaqueProtocolMessageFlight,
     > for Agent<PB>
 {
+    fn add_inbound_flight(&mut self, encoded_flight: &[u8]) {
+        with_namespace(self.namespace, || {
+            self.put.add_inbound_flight(encoded_flight)
+        })
+    }
+
     fn add_to_inbound(&mut self, message_flight: &PB::OpaqueProtocolMessageFlight) {
-        self.put.add_to_inbound(message_flight)
+        with_namespace(self.namespace, || self.put.add_to_inbound(message_flight))
     }
 
     fn take_message_from_outbound(
         &mut self,
     ) -> Result<Option<PB::OpaqueProtocolMessageFlight>, Error> {
-        self.put.take_message_from_outbound()
+        with_namespace(self.namespace, || self.put.take_message_from_outbound())
     }
 }
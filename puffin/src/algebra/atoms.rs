@@ -153,9 +153,10 @@ mod fn_container {
     use serde::ser::SerializeStruct;
     use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
-    use crate::algebra::deserialize_signature;
     use crate::algebra::dynamic_function::{DynamicFunction, DynamicFunctionShape, TypeShape};
+    use crate::algebra::remap::{has_unmapped, resolve_function, serialized_name};
     use crate::algebra::signature::Signature;
+    use crate::algebra::try_deserialize_signature;
 
     const NAME: &str = "name";
     const ARGUMENTS: &str = "arguments";
@@ -188,13 +189,38 @@ mod fn_container {
             S: Serializer,
         {
             let mut state = serializer.serialize_struct("FnContainer", FIELDS.len())?;
-            state.serialize_field(NAME, &self.shape.name)?;
+            state.serialize_field(NAME, &serialized_name(self.shape.name))?;
             state.serialize_field(ARGUMENTS, &self.shape.argument_types)?;
             state.serialize_field(RETURN, &self.shape.return_type)?;
             state.end()
         }
     }
 
+    /// Checks that the types of a deserialized function symbol equal the ones of `shape` in the
+    /// signature.
+    fn check_types<E: de::Error>(
+        shape: &DynamicFunctionShape,
+        argument_types: Vec<TypeShape>,
+        return_type: TypeShape,
+    ) -> Result<(), E> {
+        if has_unmapped()
+            || (return_type == shape.return_type && argument_types == shape.argument_types)
+        {
+            return Ok(());
+        }
+
+        let found = DynamicFunctionShape {
+            name: shape.name,
+            argument_types,
+            return_type,
+        };
+        Err(de::Error::custom(format!(
+            "Return types or argument types do not match! The trace uses {}, but the signature \
+             defines {}",
+            found, shape
+        )))
+    }
+
     struct FnContainerVisitor {
         signature: &'static Signature,
     }
@@ -220,20 +246,10 @@ mod fn_container {
                 .next_element()?
                 .ok_or_else(|| de::Error::invalid_length(2, &self))?;
 
-            let (shape, dynamic_fn) =
-                self.signature.functions_by_name.get(name).ok_or_else(|| {
-                    de::Error::custom(format!("could not find function {}", name))
-                })?;
-
-            if name != shape.name {
-                return Err(de::Error::custom("Function name does not match!"));
-            }
+            let (shape, dynamic_fn) = resolve_function(self.signature, name)
+                .ok_or_else(|| de::Error::custom(format!("could not find function {}", name)))?;
 
-            if return_type != shape.return_type || argument_types != shape.argument_types {
-                return Err(de::Error::custom(
-                    "Return types or argument types do not match!",
-                ));
-            }
+            check_types(shape, argument_types, return_type)?;
 
             Ok(FnContainer {
                 shape: shape.clone(),
@@ -275,26 +291,17 @@ mod fn_container {
             }
 
             let name = name.ok_or_else(|| de::Error::missing_field(NAME))?;
-            let (shape, dynamic_fn) =
-                self.signature.functions_by_name.get(name).ok_or_else(|| {
-                    de::Error::custom(format!(
-                        "Failed to link function symbol: Could not find function {}",
-                        name
-                    ))
-                })?;
+            let (shape, dynamic_fn) = resolve_function(self.signature, name).ok_or_else(|| {
+                de::Error::custom(format!(
+                    "Failed to link function symbol: Could not find function {}",
+                    name
+                ))
+            })?;
 
             let argument_types = arguments.ok_or_else(|| de::Error::missing_field(ARGUMENTS))?;
             let return_type = ret.ok_or_else(|| de::Error::missing_field(RETURN))?;
 
-            if name != shape.name {
-                return Err(de::Error::custom("Function name does not match!"));
-            }
-
-            if return_type != shape.return_type || argument_types != shape.argument_types {
-                return Err(de::Error::custom(
-                    "Return types or argument types do not match!",
-                ));
-            }
+            check_types(shape, argument_types, return_type)?;
 
             Ok(FnContainer {
                 shape: shape.clone(),
@@ -308,13 +315,10 @@ mod fn_container {
         where
             D: Deserializer<'de>,
         {
-            deserializer.deserialize_struct(
-                "FnContainer",
-                FIELDS,
-                FnContainerVisitor {
-                    signature: deserialize_signature(),
-                },
-            )
+            let signature = try_deserialize_signature()
+                .ok_or_else(|| de::Error::custom("current signature needs to be set"))?;
+
+            deserializer.deserialize_struct("FnContainer", FIELDS, FnContainerVisitor { signature })
         }
     }
 }
@@ -50,6 +50,10 @@
 //! ```
 //!
 //! It returns one possibility for the cipher suites which could be sent during a `ClientHello`.
+//!
+//! Functions which need random values must draw them from [`crate::rng::fill_bytes`] instead of
+//! a global source of randomness. During the evaluation of a term, it uses the RNG of the
+//! [`TraceContext`](crate::trace::TraceContext), such that replays of a trace are deterministic.
 use std::any::{type_name, Any, TypeId};
 use std::collections::hash_map::DefaultHasher;
 use std::fmt;
@@ -59,8 +63,9 @@ use itertools::Itertools;
 use serde::de::Visitor;
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::algebra::deserialize_signature;
 use crate::algebra::error::FnError;
+use crate::algebra::remap::{resolve_type, serialized_name};
+use crate::algebra::try_deserialize_signature;
 
 /// Describes the shape of a [`DynamicFunction`]
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -213,14 +218,14 @@ macro_rules! dynamic_fn {
                            if let Some(arg_) = args.get(index)
                                     .ok_or_else(|| {
                                         let shape = Self::shape();
-                                        FnError::Unknown(format!("Missing argument #{} while calling {}.", index + 1, shape.name))
+                                        FnError::Bug(format!("Missing argument #{} while calling {}.", index + 1, shape.name))
                                     })?
                                     .as_ref().downcast_ref::<$arg>() {
                                index += 1;
                                arg_
                            } else {
                                let shape = Self::shape();
-                               return Err(FnError::Unknown(format!(
+                               return Err(FnError::Bug(format!(
                                     "Passed argument #{} of {} did not match the shape {}. Hashes of passed types are {}.",
                                     index + 1,
                                     shape.name,
@@ -301,7 +306,7 @@ impl Serialize for TypeShape {
     where
         S: Serializer,
     {
-        serializer.serialize_str(self.name)
+        serializer.serialize_str(&serialized_name(self.name))
     }
 }
 
@@ -323,11 +328,10 @@ impl<'de> Deserialize<'de> for TypeShape {
             where
                 E: de::Error,
             {
-                let typ = deserialize_signature()
-                    .types_by_name
-                    .get(v)
-                    .ok_or_else(|| de::Error::missing_field("could not find type"))?;
-                Ok(*typ)
+                let signature = try_deserialize_signature()
+                    .ok_or_else(|| de::Error::custom("current signature needs to be set"))?;
+                resolve_type(signature, v)
+                    .ok_or_else(|| de::Error::missing_field("could not find type"))
             }
         }
 
@@ -7,6 +7,28 @@ pub enum FnError {
     Unknown(String),
     /// Error which happened because a cryptographic operation failed.
     Crypto(String),
+    /// Error which can not be caused by the arguments of a well-typed term, like a mismatch of the
+    /// argument types. It indicates a bug in the function symbol or the signature.
+    Bug(String),
+}
+
+/// Whether an [`FnError`] is expected when evaluating mutated terms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FnErrorCategory {
+    /// The arguments are nonsensical, like a key of the wrong length. Mutations cause such errors
+    /// all the time.
+    Malformed,
+    /// The function symbol or the signature has a bug.
+    Bug,
+}
+
+impl FnError {
+    pub fn category(&self) -> FnErrorCategory {
+        match self {
+            FnError::Unknown(_) | FnError::Crypto(_) => FnErrorCategory::Malformed,
+            FnError::Bug(_) => FnErrorCategory::Bug,
+        }
+    }
 }
 
 impl std::error::Error for FnError {}
@@ -22,6 +44,7 @@ impl fmt::Display for FnError {
         match self {
             FnError::Unknown(msg) => write!(f, "error in fn: {}", msg),
             FnError::Crypto(msg) => write!(f, "error in fn from rustls: {}", msg),
+            FnError::Bug(msg) => write!(f, "bug in fn: {}", msg),
         }
     }
 }
@@ -3,6 +3,25 @@
 
 #[macro_export]
 macro_rules! term {
+    //
+    // Nested knowledge selected by a query path, see `puffin::query`
+    //
+    (($agent:expr, $counter:expr) / $path:literal $(>$req_type:expr)?) => {{
+        use $crate::algebra::atoms::Variable;
+        use $crate::algebra::Term;
+        use $crate::query::parse_path;
+        use $crate::trace::{Query, Source};
+
+        let path = parse_path($path).unwrap_or_else(|err| panic!("invalid query path {}: {}", $path, err));
+        let query = Query {
+            source: Some(Source::Agent($agent)),
+            matcher: None,
+            counter: $counter,
+            path,
+        };
+        Term::Variable(Variable::new($($req_type)?, query))
+    }};
+
     //
     // Handshake with QueryMatcher
     // `>$req_type:expr` must be the last part of the arm, even if it is not used.
@@ -43,6 +43,7 @@ pub mod atoms;
 pub mod dynamic_function;
 pub mod error;
 pub mod macros;
+pub mod remap;
 pub mod signature;
 pub mod term;
 
@@ -55,6 +56,11 @@ pub fn deserialize_signature() -> &'static Signature {
         .expect("current signature needs to be set")
 }
 
+/// Returns the current signature which is used during deserialization, if it has been set.
+pub fn try_deserialize_signature() -> Option<&'static Signature> {
+    DESERIALIZATION_SIGNATURE.get().copied()
+}
+
 pub fn set_deserialize_signature(signature: &'static Signature) -> Result<(), ()> {
     DESERIALIZATION_SIGNATURE.set(signature).map_err(|_err| ())
 }
@@ -106,7 +112,7 @@ impl Matcher for AnyMatcher {
 #[cfg(test)]
 #[allow(clippy::ptr_arg)]
 pub mod test_signature {
-    use std::any::{Any, TypeId};
+    use std::any::Any;
     use std::fmt;
     use std::io::Read;
 
@@ -123,8 +129,8 @@ pub mod test_signature {
     };
     use crate::put::{Put, PutOptions};
     use crate::put_registry::{Factory, PutKind};
+    use crate::stream::Stream;
     use crate::trace::{Action, InputAction, Knowledge, Source, Step, Trace};
-    use crate::variable_data::VariableData;
     use crate::{define_signature, term, VERSION_STR};
 
     pub struct HmacKey;
@@ -355,20 +361,8 @@ pub mod test_signature {
 
     pub struct TestClaim;
 
-    impl VariableData for TestClaim {
-        fn boxed(&self) -> Box<dyn VariableData> {
-            panic!("Not implemented for test stub");
-        }
-
-        fn boxed_any(&self) -> Box<dyn Any> {
-            panic!("Not implemented for test stub");
-        }
-
-        fn type_id(&self) -> TypeId {
-            panic!("Not implemented for test stub");
-        }
-
-        fn type_name(&self) -> &'static str {
+    impl Clone for TestClaim {
+        fn clone(&self) -> Self {
             panic!("Not implemented for test stub");
         }
     }
@@ -484,9 +478,9 @@ pub mod test_signature {
     }
 
     pub struct TestSecurityViolationPolicy;
-    impl SecurityViolationPolicy<TestClaim> for TestSecurityViolationPolicy {
+    impl SecurityViolationPolicy<TestClaim, AnyMatcher> for TestSecurityViolationPolicy {
         fn check_violation(_claims: &[TestClaim]) -> Option<&'static str> {
-            panic!("Not implemented for test stub");
+            None
         }
     }
 
@@ -636,6 +630,85 @@ pub mod test_signature {
             Box::new(TestFactory {})
         }
     }
+
+    /// A PUT which accepts all inputs and never sends a message, such that traces which only
+    /// progress and reset agents can be executed
+    pub struct IdleFactory;
+
+    impl Factory<TestProtocolBehavior> for IdleFactory {
+        fn create(
+            &self,
+            agent_descriptor: &AgentDescriptor,
+            _claims: &GlobalClaimList<<TestProtocolBehavior as ProtocolBehavior>::Claim>,
+            _options: &PutOptions,
+        ) -> Result<Box<dyn Put<TestProtocolBehavior>>, Error> {
+            Ok(Box::new(IdlePut {
+                descriptor: agent_descriptor.clone(),
+            }))
+        }
+
+        fn kind(&self) -> PutKind {
+            PutKind::Rust
+        }
+
+        fn name(&self) -> String {
+            String::from("TESTSTUB_IDLE_PUT")
+        }
+
+        fn versions(&self) -> Vec<(String, String)> {
+            vec![(
+                "harness".to_string(),
+                format!("{} ({})", self.name(), VERSION_STR),
+            )]
+        }
+
+        fn clone_factory(&self) -> Box<dyn Factory<TestProtocolBehavior>> {
+            Box::new(IdleFactory)
+        }
+    }
+
+    pub struct IdlePut {
+        descriptor: AgentDescriptor,
+    }
+
+    impl Stream<AnyMatcher, TestMessage, TestOpaqueMessage, TestOpaqueMessageFlight> for IdlePut {
+        fn add_inbound_flight(&mut self, _encoded_flight: &[u8]) {}
+
+        fn take_message_from_outbound(&mut self) -> Result<Option<TestOpaqueMessageFlight>, Error> {
+            Ok(None)
+        }
+    }
+
+    impl Put<TestProtocolBehavior> for IdlePut {
+        fn progress(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn reset(&mut self, new_name: AgentName) -> Result<(), Error> {
+            self.descriptor.name = new_name;
+            Ok(())
+        }
+
+        fn descriptor(&self) -> &AgentDescriptor {
+            &self.descriptor
+        }
+
+        fn describe_state(&self) -> &str {
+            "idle"
+        }
+
+        fn is_state_successful(&self) -> bool {
+            true
+        }
+
+        fn shutdown(&mut self) -> String {
+            String::new()
+        }
+
+        fn version() -> String {
+            VERSION_STR.to_string()
+        }
+    }
 }
 
 #[cfg(test)]
@@ -745,7 +818,8 @@ mod tests {
         }
 
         let registry =
-            PutRegistry::<TestProtocolBehavior>::new([("teststub", dummy_factory())], "teststub");
+            PutRegistry::<TestProtocolBehavior>::new([("teststub", dummy_factory())], "teststub")
+                .unwrap();
 
         let spawner = Spawner::new(registry.clone());
         let mut context = TraceContext::new(spawner);
@@ -759,6 +833,11 @@ mod tests {
             .unwrap()
             .downcast_ref::<Vec<u8>>();
         //println!("{:?}", string);
+
+        // one argument vector per level of the term, which are reused by later evaluations
+        assert_eq!(context.arguments().available(), 2);
+        generated_term.evaluate(&context).unwrap();
+        assert_eq!(context.arguments().available(), 2);
     }
 
     #[test_log::test]
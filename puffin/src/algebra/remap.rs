@@ -0,0 +1,269 @@
+//! Remapping of the symbols of traces which were written with another signature.
+//!
+//! Functions and types are serialized with their full names, which include the module path of
+//! their definition. Traces of other forks, like upstream tlspuffin, or of earlier versions of the
+//! signature therefore fail to deserialize after a function has been renamed or moved.
+//!
+//! While a [`SymbolMap`] is active, see [`with_symbol_map`], names which are not part of the
+//! deserialization signature are resolved as follows:
+//!
+//! 1. The name, or the name without module prefix, is renamed according to the map.
+//! 2. The renamed name is looked up in the signature, either with its full name or without module
+//!    prefix if this is unique.
+//!
+//! Names which can not be resolved are collected instead of failing immediately, such that all
+//! unmapped symbols of a trace are reported at once. During serialization the names of the current
+//! signature are renamed according to the map, which allows to export traces for other forks with
+//! the [reversed](SymbolMap::reversed) map.
+
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::algebra::dynamic_function::TypeShape;
+use crate::algebra::remove_prefix;
+use crate::algebra::signature::{FunctionDefinition, Signature};
+
+/// Renames of functions and types from the names of another signature to the names of the current
+/// one.
+///
+/// The textual format consists of one rename `old -> new` per line. Names may be given with or
+/// without module prefix. Everything after a `#` is a comment:
+///
+/// ```text
+/// # moved to fn_extensions
+/// tlspuffin::tls::fn_impl::fn_utils::fn_key_share_extension -> fn_key_share_extension
+/// fn_cipher_suite13 -> fn_cipher_suite13_aes_128_gcm_sha256
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolMap {
+    renames: HashMap<String, String>,
+}
+
+impl SymbolMap {
+    pub fn insert(&mut self, old: impl Into<String>, new: impl Into<String>) {
+        self.renames.insert(old.into(), new.into());
+    }
+
+    /// The map which renames in the opposite direction.
+    pub fn reversed(&self) -> Self {
+        Self {
+            renames: self
+                .renames
+                .iter()
+                .map(|(old, new)| (new.clone(), old.clone()))
+                .collect(),
+        }
+    }
+
+    fn rename(&self, name: &str) -> Option<&str> {
+        self.renames
+            .get(name)
+            .or_else(|| self.renames.get(&remove_prefix(name)))
+            .map(String::as_str)
+    }
+}
+
+impl FromStr for SymbolMap {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut map = SymbolMap::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            match line.split_once("->") {
+                Some((old, new)) if !old.trim().is_empty() && !new.trim().is_empty() => {
+                    map.insert(old.trim(), new.trim())
+                }
+                _ => return Err(format!("Expected `old -> new` in line {}", number + 1)),
+            }
+        }
+
+        Ok(map)
+    }
+}
+
+impl fmt::Display for SymbolMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut renames = self.renames.iter().collect::<Vec<_>>();
+        renames.sort();
+        for (old, new) in renames {
+            writeln!(f, "{} -> {}", old, new)?;
+        }
+        Ok(())
+    }
+}
+
+struct Remapping {
+    map: SymbolMap,
+    unmapped: BTreeSet<String>,
+}
+
+thread_local! {
+    static REMAPPING: RefCell<Option<Remapping>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` while `map` is used to (de)serialize symbols on this thread. Returns the result of `f`
+/// and the names which could not be resolved in the deserialization signature.
+///
+/// If names could not be resolved, deserialized terms contain arbitrary placeholder symbols and
+/// must be discarded.
+pub fn with_symbol_map<T>(map: &SymbolMap, f: impl FnOnce() -> T) -> (T, Vec<String>) {
+    let previous = REMAPPING.with(|cell| {
+        cell.replace(Some(Remapping {
+            map: map.clone(),
+            unmapped: BTreeSet::new(),
+        }))
+    });
+    let result = f();
+    let remapping = REMAPPING.with(|cell| cell.replace(previous));
+
+    let unmapped = remapping.map_or(vec![], |remapping| remapping.unmapped.into_iter().collect());
+    (result, unmapped)
+}
+
+/// Whether names could not be resolved so far. Type checks of deserialized symbols are skipped in
+/// this case, because placeholders have been used.
+pub(crate) fn has_unmapped() -> bool {
+    REMAPPING.with(|cell| {
+        cell.borrow()
+            .as_ref()
+            .map_or(false, |remapping| !remapping.unmapped.is_empty())
+    })
+}
+
+/// The name under which the symbol `name` is serialized.
+pub(crate) fn serialized_name(name: &'static str) -> String {
+    REMAPPING.with(|cell| {
+        cell.borrow()
+            .as_ref()
+            .and_then(|remapping| remapping.map.rename(name))
+            .map_or(name.to_string(), str::to_string)
+    })
+}
+
+/// Looks up `name` in `names`, either with its full name or without module prefix if this is
+/// unique.
+fn lookup<'a, V>(names: &'a HashMap<&'static str, V>, name: &str) -> Option<&'a V> {
+    names.get(name).or_else(|| {
+        let short = remove_prefix(name);
+        let mut candidates = names
+            .iter()
+            .filter(|(candidate, _)| remove_prefix(candidate) == short);
+        match (candidates.next(), candidates.next()) {
+            (Some((_, value)), None) => Some(value),
+            _ => None,
+        }
+    })
+}
+
+/// Resolves `name` in `names` while a [`SymbolMap`] is active. Returns `placeholder` if the name
+/// can not be resolved. Without an active map, only exact names are resolved.
+fn resolve<'a, V>(
+    names: &'a HashMap<&'static str, V>,
+    name: &str,
+    placeholder: impl FnOnce() -> Option<&'a V>,
+) -> Option<&'a V> {
+    if let Some(value) = names.get(name) {
+        return Some(value);
+    }
+
+    REMAPPING.with(|cell| {
+        let mut remapping = cell.borrow_mut();
+        let remapping = remapping.as_mut()?;
+
+        let renamed = remapping.map.rename(name).unwrap_or(name);
+        lookup(names, renamed).or_else(|| {
+            remapping.unmapped.insert(name.to_string());
+            placeholder()
+        })
+    })
+}
+
+pub(crate) fn resolve_function<'a>(
+    signature: &'a Signature,
+    name: &str,
+) -> Option<&'a FunctionDefinition> {
+    resolve(&signature.functions_by_name, name, || {
+        signature.functions.first()
+    })
+}
+
+pub(crate) fn resolve_type(signature: &Signature, name: &str) -> Option<TypeShape> {
+    resolve(&signature.types_by_name, name, || {
+        signature.types_by_name.values().next()
+    })
+    .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::AgentName;
+    use crate::algebra::test_signature::*;
+    use crate::algebra::{set_deserialize_signature, AnyMatcher, Term};
+    use crate::term;
+    use crate::trace::{Action, InputAction, Trace};
+
+    fn trace() -> TestTrace {
+        Trace {
+            descriptors: vec![],
+            prior_traces: vec![],
+            steps: vec![InputAction::new_step(
+                AgentName::first(),
+                term! { fn_client_extensions_new },
+            )],
+        }
+    }
+
+    fn recipe(trace: &TestTrace) -> &Term<AnyMatcher> {
+        match &trace.steps[0].action {
+            Action::Input(input) => &input.recipe,
+            _ => panic!("expected input"),
+        }
+    }
+
+    #[test_log::test]
+    fn test_parse_symbol_map() {
+        let map: SymbolMap = "# comment\nfn_a -> fn_b\n\nx::Y->Z # moved\n"
+            .parse()
+            .unwrap();
+        assert_eq!(map.rename("fn_a"), Some("fn_b"));
+        assert_eq!(map.rename("x::Y"), Some("Z"));
+        assert_eq!(map.reversed().rename("fn_b"), Some("fn_a"));
+        assert_eq!(map.to_string().parse::<SymbolMap>().unwrap(), map);
+
+        assert!("fn_a fn_b".parse::<SymbolMap>().is_err());
+    }
+
+    #[test_log::test]
+    fn test_remap_trace() {
+        let _ = set_deserialize_signature(&TEST_SIGNATURE);
+        let trace = trace();
+
+        // Export with the names of another fork
+        let mut upstream = SymbolMap::default();
+        upstream.insert("fn_client_extensions_new", "upstream::fn_empty_extensions");
+        let (bytes, _) = with_symbol_map(&upstream, || trace.serialize_postcard().unwrap());
+        assert!(Trace::<AnyMatcher>::deserialize_postcard(&bytes).is_err());
+
+        // Import with the reversed map
+        let (imported, unmapped) = with_symbol_map(&upstream.reversed(), || {
+            Trace::<AnyMatcher>::deserialize_postcard(&bytes)
+        });
+        assert!(unmapped.is_empty());
+        assert_eq!(recipe(&imported.unwrap()), recipe(&trace));
+
+        // Unmapped symbols are reported
+        let (_, unmapped) = with_symbol_map(&SymbolMap::default(), || {
+            Trace::<AnyMatcher>::deserialize_postcard(&bytes)
+        });
+        assert_eq!(unmapped, vec!["upstream::fn_empty_extensions".to_string()]);
+    }
+}
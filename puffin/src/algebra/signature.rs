@@ -1,5 +1,8 @@
-use std::collections::HashMap;
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::Mutex;
 
 use itertools::Itertools;
 use once_cell::sync::Lazy;
@@ -9,17 +12,33 @@ use crate::algebra::atoms::Variable;
 use crate::algebra::dynamic_function::{
     make_dynamic, DescribableFunction, DynamicFunction, DynamicFunctionShape, TypeShape,
 };
-use crate::algebra::Matcher;
+use crate::algebra::{Matcher, Term};
 use crate::trace::{Query, Source};
 
 pub type FunctionDefinition = (DynamicFunctionShape, Box<dyn DynamicFunction>);
 
+/// Maximal depth of the terms which [`Signature::infer_term`] tries
+const INFER_MAX_DEPTH: usize = 3;
+/// Maximal number of terms per type and depth which [`Signature::infer_term`] uses as arguments
+const INFER_MAX_ARGUMENTS: usize = 16;
+/// Maximal number of argument combinations per function symbol and depth which
+/// [`Signature::infer_term`] tries
+const INFER_MAX_COMBINATIONS: usize = 64;
+
+/// The ground terms per type and depth which evaluate without an error
+type GroundTerms<M> = HashMap<(TypeShape, usize), Vec<Term<M>>>;
+
 /// Records a universe of functions.
 /// Signatures are containers for types and function symbols. They hold references to the concrete
 /// implementations of functions and the types of variables.
 pub struct Signature {
     pub functions_by_name: HashMap<&'static str, FunctionDefinition>,
+    /// Functions by their return type
     pub functions_by_typ: HashMap<TypeShape, Vec<FunctionDefinition>>,
+    /// Functions with an argument of the type
+    pub functions_by_argument_typ: HashMap<TypeShape, Vec<FunctionDefinition>>,
+    /// Functions without arguments by their return type
+    pub constants_by_typ: HashMap<TypeShape, Vec<FunctionDefinition>>,
     pub functions: Vec<FunctionDefinition>,
     pub types_by_name: HashMap<&'static str, TypeShape>,
 }
@@ -44,6 +63,24 @@ impl Signature {
             .into_iter()
             .into_group_map_by(|(shape, _dynamic_fn)| shape.return_type);
 
+        let functions_by_argument_typ: HashMap<TypeShape, Vec<FunctionDefinition>> = definitions
+            .iter()
+            .flat_map(|definition| {
+                definition
+                    .0
+                    .argument_types
+                    .iter()
+                    .unique()
+                    .map(move |typ| (*typ, definition.clone()))
+            })
+            .into_group_map();
+
+        let constants_by_typ: HashMap<TypeShape, Vec<FunctionDefinition>> = definitions
+            .iter()
+            .filter(|(shape, _dynamic_fn)| shape.is_constant())
+            .cloned()
+            .into_group_map_by(|(shape, _dynamic_fn)| shape.return_type);
+
         let types_by_name: HashMap<&'static str, TypeShape> = definitions
             .clone()
             .into_iter()
@@ -64,11 +101,158 @@ impl Signature {
         Signature {
             functions_by_name,
             functions_by_typ,
+            functions_by_argument_typ,
+            constants_by_typ,
             functions: definitions,
             types_by_name,
         }
     }
 
+    /// Function symbols which return `typ`.
+    pub fn functions_returning(&self, typ: &TypeShape) -> &[FunctionDefinition] {
+        self.functions_by_typ.get(typ).map_or(&[], Vec::as_slice)
+    }
+
+    /// Function symbols without arguments which return `typ`.
+    pub fn constants_returning(&self, typ: &TypeShape) -> &[FunctionDefinition] {
+        self.constants_by_typ.get(typ).map_or(&[], Vec::as_slice)
+    }
+
+    /// Function symbols which accept `typ` as at least one of their arguments.
+    pub fn functions_accepting(&self, typ: &TypeShape) -> &[FunctionDefinition] {
+        self.functions_by_argument_typ
+            .get(typ)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// The shape of the function symbol `name`.
+    pub fn shape(&self, name: &str) -> Option<&DynamicFunctionShape> {
+        self.functions_by_name.get(name).map(|(shape, _)| shape)
+    }
+
+    /// Whether traces which were written with the signature of `fingerprint` use the same symbols
+    /// as this signature.
+    pub fn is_compatible(&self, fingerprint: u64) -> bool {
+        self.fingerprint() == fingerprint
+    }
+
+    /// Describes each function symbol by its name, argument types and return type. Traces which
+    /// use a symbol with a different description fail to deserialize.
+    pub fn symbols(&self) -> BTreeMap<String, String> {
+        self.functions
+            .iter()
+            .map(|(shape, _dynamic_fn)| (shape.name.to_string(), shape.to_string()))
+            .collect()
+    }
+
+    /// Stable hash over all [symbols](Self::symbols) of the signature.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = ahash::RandomState::with_seeds(0, 0, 0, 0).build_hasher();
+        self.symbols().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Finds a term which evaluates to a value of type `T` for which `matches` holds.
+    ///
+    /// The ground terms are tried by increasing depth, up to a depth of [`INFER_MAX_DEPTH`]. The
+    /// search is bounded: at most [`INFER_MAX_ARGUMENTS`] terms per type and depth are used as
+    /// arguments and at most [`INFER_MAX_COMBINATIONS`] argument combinations are tried per
+    /// function symbol. Symbols which return a fresh value on each evaluation, like random
+    /// numbers, are never found.
+    pub fn infer_term<T: 'static, M: Matcher>(
+        &self,
+        matches: impl Fn(&T) -> bool,
+    ) -> Option<Term<M>> {
+        let typ = TypeShape::of::<T>();
+        let mut ground_terms = GroundTerms::new();
+
+        (1..=INFER_MAX_DEPTH).find_map(|depth| {
+            self.fill_argument_terms(&typ, depth, &mut ground_terms);
+            self.applications(&typ, depth, &ground_terms)
+                .find(|(_, value)| value.downcast_ref::<T>().map_or(false, &matches))
+                .map(|(term, _)| term)
+        })
+    }
+
+    /// Computes the ground terms of the argument types of the functions which return `typ`, such
+    /// that terms of `typ` with `depth` levels can be built from them.
+    fn fill_argument_terms<M: Matcher>(
+        &self,
+        typ: &TypeShape,
+        depth: usize,
+        ground_terms: &mut GroundTerms<M>,
+    ) {
+        for (shape, _) in self.functions_returning(typ) {
+            for argument in &shape.argument_types {
+                for argument_depth in 1..depth {
+                    if ground_terms.contains_key(&(*argument, argument_depth)) {
+                        continue;
+                    }
+                    // Recursive types are cut off at the terms which are already known
+                    ground_terms.insert((*argument, argument_depth), vec![]);
+                    self.fill_argument_terms(argument, argument_depth, ground_terms);
+
+                    let terms = self
+                        .applications(argument, argument_depth, ground_terms)
+                        .map(|(term, _)| term)
+                        .take(INFER_MAX_ARGUMENTS)
+                        .collect();
+                    ground_terms.insert((*argument, argument_depth), terms);
+                }
+            }
+        }
+    }
+
+    /// The ground terms of type `typ` with exactly `depth` levels which evaluate without an error,
+    /// together with their value. The arguments are taken from `ground_terms`.
+    fn applications<'a, M: Matcher>(
+        &'a self,
+        typ: &TypeShape,
+        depth: usize,
+        ground_terms: &'a GroundTerms<M>,
+    ) -> impl Iterator<Item = (Term<M>, Box<dyn Any>)> + 'a {
+        self.functions_returning(typ)
+            .iter()
+            .filter(move |(shape, _)| shape.is_constant() == (depth == 1))
+            .flat_map(move |(shape, dynamic_fn)| {
+                let argument_lists: Vec<Vec<Term<M>>> = if shape.is_constant() {
+                    vec![vec![]]
+                } else {
+                    shape
+                        .argument_types
+                        .iter()
+                        .map(|argument| {
+                            // The deepest arguments first, such that the combinations which
+                            // reach `depth` are tried first
+                            (1..depth)
+                                .rev()
+                                .flat_map(|argument_depth| {
+                                    ground_terms
+                                        .get(&(*argument, argument_depth))
+                                        .into_iter()
+                                        .flatten()
+                                        .map(move |term| (argument_depth, term))
+                                })
+                                .collect::<Vec<_>>()
+                        })
+                        .multi_cartesian_product()
+                        .take(INFER_MAX_COMBINATIONS)
+                        .filter(|arguments| arguments.iter().any(|(d, _)| *d == depth - 1))
+                        .map(|arguments| arguments.into_iter().map(|(_, t)| t.clone()).collect())
+                        .collect()
+                };
+
+                argument_lists.into_iter().filter_map(move |arguments| {
+                    let term = Term::Application(
+                        Function::new(shape.clone(), dynamic_fn.clone()),
+                        arguments,
+                    );
+                    let value = evaluate_ground(&term)?;
+                    Some((term, value))
+                })
+            })
+    }
+
     /// Create a new [`Function`] distinct from all existing [`Function`]s.
     pub fn new_function<F, Types>(f: &'static F) -> Function
     where
@@ -98,11 +282,101 @@ impl Signature {
             source,
             matcher,
             counter,
+            path: vec![],
         };
         Variable::new(type_shape, query)
     }
 }
 
+/// Additional function symbols which are registered at startup, for example by crates which
+/// provide vendor-specific extensions of a protocol.
+///
+/// Extensions are registered with [`register_signature_extension`] and added to the signature
+/// named [`SignatureExtension::signature`] when it is built by [`define_signature`]. The name of a
+/// signature is the name of its static, like `TLS_SIGNATURE`.
+pub trait SignatureExtension: Send {
+    /// Name of the signature which is extended
+    fn signature(&self) -> &'static str;
+
+    fn definitions(&self) -> Vec<FunctionDefinition>;
+}
+
+/// Evaluates a term without variables, or returns `None` if a function symbol fails.
+fn evaluate_ground<M: Matcher>(term: &Term<M>) -> Option<Box<dyn Any>> {
+    match term {
+        Term::Variable(_) => None,
+        Term::Application(function, arguments) => {
+            let arguments = arguments
+                .iter()
+                .map(evaluate_ground)
+                .collect::<Option<Vec<_>>>()?;
+            function.dynamic_fn()(&arguments).ok()
+        }
+    }
+}
+
+#[derive(Default)]
+struct ExtensionRegistry {
+    extensions: Vec<Box<dyn SignatureExtension>>,
+    /// Names of the signatures which have already been built
+    built: Vec<&'static str>,
+}
+
+static EXTENSIONS: Lazy<Mutex<ExtensionRegistry>> = Lazy::new(Mutex::default);
+
+/// Registers `extension`, such that its symbols are added to the extended signature.
+///
+/// Extensions must be registered before the signature is used for the first time. Fails if the
+/// signature has already been built.
+pub fn register_signature_extension(
+    extension: impl SignatureExtension + 'static,
+) -> Result<(), String> {
+    let mut registry = EXTENSIONS.lock().unwrap();
+    let signature = extension.signature();
+    if registry.built.contains(&signature) {
+        return Err(format!(
+            "Signature {} has already been built, extensions must be registered before",
+            signature
+        ));
+    }
+
+    registry.extensions.push(Box::new(extension));
+    Ok(())
+}
+
+impl Signature {
+    /// Construct the `Signature` named `name` from the given [`FunctionDefinition`]s and the ones
+    /// of the registered [`SignatureExtension`]s. Symbols of extensions which have the same name
+    /// as an existing symbol are skipped.
+    pub fn with_extensions(name: &'static str, mut definitions: Vec<FunctionDefinition>) -> Self {
+        let mut registry = EXTENSIONS.lock().unwrap();
+        registry.built.push(name);
+
+        for extension in registry
+            .extensions
+            .iter()
+            .filter(|extension| extension.signature() == name)
+        {
+            for definition in extension.definitions() {
+                if definitions
+                    .iter()
+                    .any(|(shape, _)| shape.name == definition.0.name)
+                {
+                    log::warn!(
+                        "Skipping symbol {} of an extension of {}, it already exists",
+                        definition.0.name,
+                        name
+                    );
+                } else {
+                    definitions.push(definition);
+                }
+            }
+        }
+
+        Signature::new(definitions)
+    }
+}
+
 pub type StaticSignature = Lazy<Signature>;
 
 pub const fn create_static_signature(init: fn() -> Signature) -> StaticSignature {
@@ -111,7 +385,7 @@ pub const fn create_static_signature(init: fn() -> Signature) -> StaticSignature
 
 #[macro_export]
 macro_rules! define_signature {
-    ($name_signature:ident, $($f:path)+) => {
+    ($name_signature:ident, $($f:path)+ $(; $($projections:path)+)?) => {
         use $crate::algebra::signature::create_static_signature;
         use $crate::algebra::signature::StaticSignature;
         use $crate::algebra::signature::Signature;
@@ -124,7 +398,184 @@ macro_rules! define_signature {
             let definitions = vec![
                 $($crate::algebra::dynamic_function::make_dynamic(&$f)),*
             ];
-            Signature::new(definitions)
+            Signature::with_extensions(
+                stringify!($name_signature),
+                definitions
+                    .into_iter()
+                    $($(.chain($projections()))+)?
+                    .collect(),
+            )
         });
     };
 }
+
+/// Defines a function symbol `fn_get_<prefix>_<field>` for each listed field of a struct.
+///
+/// Each symbol returns a clone of the field. The definitions of the symbols are returned by a
+/// generated function `<prefix>_projections`, which can be passed to [`define_signature`] after a
+/// `;`:
+///
+/// ```ignore
+/// define_projections!(client_hello: ClientHelloPayload {
+///     random: Random,
+///     extensions: Vec<ClientExtension>,
+/// });
+///
+/// define_signature!(TLS_SIGNATURE, fn_client_hello fn_finished; client_hello_projections);
+/// ```
+#[macro_export]
+macro_rules! define_projections {
+    ($prefix:ident : $typ:ty { $($field:ident : $field_typ:ty),+ $(,)? }) => {
+        $crate::paste::paste! {
+            $(
+                #[doc = concat!("Returns the field `", stringify!($field), "` of a `", stringify!($typ), "`.")]
+                #[allow(clippy::ptr_arg)]
+                pub fn [<fn_get_ $prefix _ $field>](
+                    value: &$typ,
+                ) -> Result<$field_typ, $crate::algebra::error::FnError> {
+                    Ok(value.$field.clone())
+                }
+            )+
+
+            #[doc = concat!("Definitions of the projections of `", stringify!($typ), "`.")]
+            pub fn [<$prefix _projections>]() -> Vec<$crate::algebra::signature::FunctionDefinition> {
+                vec![
+                    $($crate::algebra::dynamic_function::make_dynamic(&[<fn_get_ $prefix _ $field>])),+
+                ]
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algebra::dynamic_function::{make_dynamic, TypeShape};
+    use crate::algebra::error::FnError;
+    use crate::algebra::signature::{
+        register_signature_extension, FunctionDefinition, Signature, SignatureExtension,
+    };
+    use crate::algebra::test_signature::*;
+    use crate::algebra::{remove_prefix, AnyMatcher, Term};
+    use crate::define_projections;
+
+    #[derive(Debug, Clone)]
+    pub struct Point {
+        x: u8,
+        label: Vec<u8>,
+    }
+
+    define_projections!(point: Point { x: u8, label: Vec<u8> });
+
+    fn names(definitions: &[FunctionDefinition]) -> Vec<String> {
+        definitions
+            .iter()
+            .map(|(shape, _)| remove_prefix(shape.name))
+            .collect()
+    }
+
+    #[test_log::test]
+    fn test_type_compatibility_tables() {
+        let extensions = TypeShape::of::<ClientExtensions>();
+
+        assert_eq!(
+            names(TEST_SIGNATURE.functions_returning(&extensions)),
+            vec!["fn_client_extensions_append", "fn_client_extensions_new"]
+        );
+        assert_eq!(
+            names(TEST_SIGNATURE.constants_returning(&extensions)),
+            vec!["fn_client_extensions_new"]
+        );
+        assert_eq!(
+            names(TEST_SIGNATURE.functions_accepting(&extensions)),
+            vec!["fn_client_hello", "fn_client_extensions_append"]
+        );
+    }
+
+    #[test_log::test]
+    fn test_infer_term() {
+        fn fn_point() -> Result<Point, FnError> {
+            Ok(Point {
+                x: 3,
+                label: vec![1, 2],
+            })
+        }
+
+        fn fn_add(a: &u8, b: &u8) -> Result<u8, FnError> {
+            Ok(a + b)
+        }
+
+        let mut definitions = point_projections();
+        definitions.push(make_dynamic(&fn_point));
+        definitions.push(make_dynamic(&fn_add));
+        let signature = Signature::new(definitions);
+
+        let point: Term<AnyMatcher> = signature
+            .infer_term::<Point, _>(|point| point.x == 3)
+            .unwrap();
+        assert_eq!(remove_prefix(point.name()), "fn_point");
+
+        let x: Term<AnyMatcher> = signature.infer_term::<u8, _>(|x| *x == 3).unwrap();
+        assert_eq!(remove_prefix(x.name()), "fn_get_point_x");
+        assert_eq!(x.size(), 2);
+
+        let sum: Term<AnyMatcher> = signature.infer_term::<u8, _>(|x| *x == 6).unwrap();
+        assert_eq!(remove_prefix(sum.name()), "fn_add");
+        assert_eq!(sum.size(), 5);
+
+        assert!(signature
+            .infer_term::<u8, AnyMatcher>(|x| *x == 4)
+            .is_none());
+    }
+
+    #[test_log::test]
+    fn test_signature_extension() {
+        struct PointExtension;
+
+        impl SignatureExtension for PointExtension {
+            fn signature(&self) -> &'static str {
+                "POINT_SIGNATURE"
+            }
+
+            fn definitions(&self) -> Vec<FunctionDefinition> {
+                // The constant is skipped, because it already exists
+                vec![
+                    make_dynamic(&fn_get_point_x),
+                    make_dynamic(&fn_client_extensions_new),
+                ]
+            }
+        }
+
+        register_signature_extension(PointExtension).unwrap();
+
+        let signature = Signature::with_extensions(
+            "POINT_SIGNATURE",
+            vec![make_dynamic(&fn_client_extensions_new)],
+        );
+        assert_eq!(
+            names(&signature.functions),
+            vec!["fn_client_extensions_new", "fn_get_point_x"]
+        );
+
+        assert!(register_signature_extension(PointExtension).is_err());
+    }
+
+    #[test_log::test]
+    fn test_define_projections() {
+        let point = Point {
+            x: 3,
+            label: vec![1, 2],
+        };
+        assert_eq!(fn_get_point_x(&point).unwrap(), 3);
+        assert_eq!(fn_get_point_label(&point).unwrap(), vec![1, 2]);
+
+        let signature = Signature::new(point_projections());
+        assert_eq!(
+            names(signature.functions_accepting(&TypeShape::of::<Point>())),
+            vec!["fn_get_point_x", "fn_get_point_label"]
+        );
+        assert_eq!(
+            names(signature.functions_returning(&TypeShape::of::<u8>())),
+            vec!["fn_get_point_x"]
+        );
+    }
+}
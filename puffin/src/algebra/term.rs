@@ -1,7 +1,10 @@
 //! This module provides[`Term`]s as well as iterators over them.
 
 use std::any::Any;
+use std::cell::RefCell;
 use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::time::Instant;
 
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
@@ -10,7 +13,8 @@ use super::atoms::{Function, Variable};
 use crate::algebra::dynamic_function::TypeShape;
 use crate::algebra::error::FnError;
 use crate::algebra::Matcher;
-use crate::error::Error;
+use crate::error::{Error, TermError};
+use crate::fuzzer::stats_stage::SYMBOL_EVALUATION;
 use crate::protocol::ProtocolBehavior;
 use crate::trace::{Source, TraceContext};
 
@@ -80,6 +84,20 @@ impl<M: Matcher> Term<M> {
         *self = other;
     }
 
+    /// Increases the counter of the queries of all variables in this term by `offset`.
+    pub fn shift_query_counters(&mut self, offset: u16) {
+        match self {
+            Term::Variable(variable) => {
+                variable.query.counter = variable.query.counter.saturating_add(offset);
+            }
+            Term::Application(_, subterms) => {
+                for subterm in subterms {
+                    subterm.shift_query_counters(offset);
+                }
+            }
+        }
+    }
+
     fn display_at_depth(&self, depth: usize) -> String {
         let tabs = "\t".repeat(depth);
         match self {
@@ -118,9 +136,14 @@ impl<M: Matcher> Term<M> {
                         todo!("Implement querying by label");
                     }
                 })
-                .ok_or_else(|| Error::Term(format!("Unable to find variable {}!", variable))),
+                .ok_or_else(|| {
+                    Error::Term(TermError::VariableNotFound {
+                        typ: variable.typ,
+                        query: variable.query.to_string(),
+                    })
+                }),
             Term::Application(func, args) => {
-                let mut dynamic_args: Vec<Box<dyn Any>> = Vec::new();
+                let mut dynamic_args = context.arguments().take();
                 for term in args {
                     match term.evaluate(context) {
                         Ok(data) => {
@@ -132,13 +155,82 @@ impl<M: Matcher> Term<M> {
                     }
                 }
                 let dynamic_fn = &func.dynamic_fn();
-                let result: Result<Box<dyn Any>, FnError> = dynamic_fn(&dynamic_args);
-                result.map_err(Error::Fn)
+                let result: Result<Box<dyn Any>, FnError> = if SYMBOL_EVALUATION.is_enabled() {
+                    let start = Instant::now();
+                    let result = context.with_rng(|| dynamic_fn(&dynamic_args));
+                    SYMBOL_EVALUATION.record(func.name(), start.elapsed());
+                    result
+                } else {
+                    context.with_rng(|| dynamic_fn(&dynamic_args))
+                };
+                result.map_err(|error| {
+                    Error::Term(TermError::Fn {
+                        symbol: func.name(),
+                        argument_types: func.shape().argument_types.clone(),
+                        error,
+                    })
+                })
             }
         }
     }
 }
 
+/// The argument vectors of the function symbols which are evaluated during an execution.
+///
+/// Evaluating a term takes one vector per application. The vectors are returned to the pool when
+/// the function symbol was called, such that an execution only allocates as many vectors as its
+/// deepest term has levels. The results of the function symbols are allocated by the symbols and
+/// are not pooled. The `term evaluate` benchmarks of tlspuffin compare the evaluation with and
+/// without the pool.
+#[derive(Debug, Default)]
+pub struct ArgumentPool {
+    free: RefCell<Vec<Vec<Box<dyn Any>>>>,
+}
+
+impl ArgumentPool {
+    pub fn take(&self) -> Arguments<'_> {
+        Arguments {
+            values: self.free.borrow_mut().pop().unwrap_or_default(),
+            pool: self,
+        }
+    }
+
+    /// Number of vectors which are ready for reuse
+    pub fn available(&self) -> usize {
+        self.free.borrow().len()
+    }
+}
+
+/// Argument vector which returns to its [`ArgumentPool`] when dropped.
+pub struct Arguments<'a> {
+    values: Vec<Box<dyn Any>>,
+    pool: &'a ArgumentPool,
+}
+
+impl Deref for Arguments<'_> {
+    type Target = Vec<Box<dyn Any>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.values
+    }
+}
+
+impl DerefMut for Arguments<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.values
+    }
+}
+
+impl Drop for Arguments<'_> {
+    fn drop(&mut self) {
+        self.values.clear();
+        self.pool
+            .free
+            .borrow_mut()
+            .push(std::mem::take(&mut self.values));
+    }
+}
+
 fn append<'a, M: Matcher>(term: &'a Term<M>, v: &mut Vec<&'a Term<M>>) {
     match *term {
         Term::Variable(_) => {}
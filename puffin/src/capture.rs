@@ -0,0 +1,558 @@
+//! Capturing of the bytes which are exchanged with the [`Agent`](crate::agent::Agent)s.
+//!
+//! If capturing is enabled on a [`TraceContext`](crate::trace::TraceContext), every flight
+//! which is sent to or received from an agent is recorded. The [`Capture`] can then be written
+//! as a PCAP file. Each agent is represented by a separate TCP connection between the attacker
+//! (`10.0.0.1`) and the agent (`10.0.0.2`, port 443). This allows analyzing crashes with tools
+//! like Wireshark, which dissect the connections as TLS.
+//!
+//! PCAP files of real-world traffic can be read with [`read_pcap`], for example to reconstruct
+//! seeds from captured handshakes.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::agent::AgentName;
+use crate::counters::Direction;
+
+const ATTACKER_ADDRESS: [u8; 4] = [10, 0, 0, 1];
+const AGENT_ADDRESS: [u8; 4] = [10, 0, 0, 2];
+const AGENT_PORT: u16 = 443;
+const ATTACKER_BASE_PORT: u16 = 40000;
+
+/// Raw IPv4 packets without link layer
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_IPV4: u32 = 228;
+const MAX_SEGMENT_SIZE: usize = 1460;
+
+const TCP_SYN: u8 = 0x02;
+const TCP_PSH: u8 = 0x08;
+const TCP_ACK: u8 = 0x10;
+
+/// Bytes which have been sent or received by an agent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedFlight {
+    pub agent: AgentName,
+    pub direction: Direction,
+    /// Time since the unix epoch
+    pub timestamp: Duration,
+    pub data: Vec<u8>,
+}
+
+/// The flights exchanged with all agents, in the order of the execution.
+///
+/// If the capture has been created with [`Capture::with_pcap_file`], each flight is also written
+/// to the file as soon as it is recorded. The file is therefore complete even if the PUT crashes.
+#[derive(Debug, Default)]
+pub struct Capture {
+    flights: Vec<CapturedFlight>,
+    sink: Option<PcapWriter<File>>,
+}
+
+impl Capture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_pcap_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self {
+            flights: vec![],
+            sink: Some(PcapWriter::new(File::create(path)?)?),
+        })
+    }
+
+    pub fn record(&mut self, agent: AgentName, direction: Direction, data: Vec<u8>) {
+        if data.is_empty() {
+            return;
+        }
+
+        let flight = CapturedFlight {
+            agent,
+            direction,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default(),
+            data,
+        };
+
+        if let Some(sink) = &mut self.sink {
+            if let Err(err) = sink.write_flight(&flight) {
+                log::error!("Failed to write captured flight: {}", err);
+            }
+        }
+
+        self.flights.push(flight);
+    }
+
+    pub fn flights(&self) -> &[CapturedFlight] {
+        &self.flights
+    }
+
+    /// Compares the bytes which the agents sent in this and the `other` capture. The timestamps and
+    /// the received flights are ignored. Returns a description of the first difference.
+    pub fn compare_sent(&self, other: &Capture) -> Result<(), String> {
+        let sent = |capture: &'_ Capture| {
+            capture
+                .flights
+                .iter()
+                .filter(|flight| flight.direction == Direction::Sent)
+                .map(|flight| (flight.agent, flight.data.clone()))
+                .collect::<Vec<_>>()
+        };
+        let (sent, other_sent) = (sent(self), sent(other));
+
+        if let Some((index, ((agent, _), _))) = sent
+            .iter()
+            .zip(&other_sent)
+            .enumerate()
+            .find(|(_, (flight, other_flight))| flight != other_flight)
+        {
+            return Err(format!("sent flight #{} of {} differs", index, agent));
+        }
+
+        if sent.len() != other_sent.len() {
+            return Err(format!(
+                "{} flights were sent, then {}",
+                sent.len(),
+                other_sent.len()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Writes the capture in the PCAP format.
+    pub fn write_pcap<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut pcap = PcapWriter::new(writer)?;
+        for flight in &self.flights {
+            pcap.write_flight(flight)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes [`CapturedFlight`]s as packets of TCP connections in the PCAP format.
+#[derive(Debug)]
+pub struct PcapWriter<W: Write> {
+    writer: W,
+    connections: Vec<Connection>,
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// Writes the global header of the PCAP file.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(&0xa1b2c3d4u32.to_le_bytes())?;
+        writer.write_all(&2u16.to_le_bytes())?;
+        writer.write_all(&4u16.to_le_bytes())?;
+        writer.write_all(&0i32.to_le_bytes())?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.write_all(&65535u32.to_le_bytes())?;
+        writer.write_all(&LINKTYPE_RAW.to_le_bytes())?;
+
+        Ok(Self {
+            writer,
+            connections: vec![],
+        })
+    }
+
+    pub fn write_flight(&mut self, flight: &CapturedFlight) -> io::Result<()> {
+        let index = match self
+            .connections
+            .iter()
+            .position(|connection| connection.agent == flight.agent)
+        {
+            Some(index) => index,
+            None => {
+                let attacker_port = ATTACKER_BASE_PORT.wrapping_add(self.connections.len() as u16);
+                let mut connection = Connection::new(flight.agent, attacker_port);
+                for packet in connection.handshake() {
+                    self.write_record(flight.timestamp, &packet)?;
+                }
+                self.connections.push(connection);
+                self.connections.len() - 1
+            }
+        };
+
+        for segment in flight.data.chunks(MAX_SEGMENT_SIZE) {
+            let packet =
+                self.connections[index].segment(flight.direction, TCP_PSH | TCP_ACK, segment);
+            self.write_record(flight.timestamp, &packet)?;
+        }
+
+        self.writer.flush()
+    }
+
+    fn write_record(&mut self, timestamp: Duration, packet: &[u8]) -> io::Result<()> {
+        self.writer
+            .write_all(&(timestamp.as_secs() as u32).to_le_bytes())?;
+        self.writer
+            .write_all(&timestamp.subsec_micros().to_le_bytes())?;
+        self.writer
+            .write_all(&(packet.len() as u32).to_le_bytes())?;
+        self.writer
+            .write_all(&(packet.len() as u32).to_le_bytes())?;
+        self.writer.write_all(packet)
+    }
+}
+
+/// State of the TCP connection between the attacker and an agent.
+#[derive(Debug)]
+struct Connection {
+    agent: AgentName,
+    attacker_port: u16,
+    /// Next sequence number of the attacker
+    attacker_seq: u32,
+    /// Next sequence number of the agent
+    agent_seq: u32,
+}
+
+impl Connection {
+    fn new(agent: AgentName, attacker_port: u16) -> Self {
+        Self {
+            agent,
+            attacker_port,
+            attacker_seq: 0,
+            agent_seq: 0,
+        }
+    }
+
+    fn handshake(&mut self) -> Vec<Vec<u8>> {
+        let syn = self.segment(Direction::Received, TCP_SYN, &[]);
+        self.attacker_seq += 1;
+        let syn_ack = self.segment(Direction::Sent, TCP_SYN | TCP_ACK, &[]);
+        self.agent_seq += 1;
+        let ack = self.segment(Direction::Received, TCP_ACK, &[]);
+
+        vec![syn, syn_ack, ack]
+    }
+
+    /// Creates an IPv4 packet. [`Direction::Received`] packets are sent by the attacker to the
+    /// agent.
+    fn segment(&mut self, direction: Direction, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let (source, destination, source_port, destination_port, seq, ack) = match direction {
+            Direction::Received => (
+                ATTACKER_ADDRESS,
+                AGENT_ADDRESS,
+                self.attacker_port,
+                AGENT_PORT,
+                self.attacker_seq,
+                self.agent_seq,
+            ),
+            Direction::Sent => (
+                AGENT_ADDRESS,
+                ATTACKER_ADDRESS,
+                AGENT_PORT,
+                self.attacker_port,
+                self.agent_seq,
+                self.attacker_seq,
+            ),
+        };
+
+        let mut tcp = Vec::with_capacity(20 + payload.len());
+        tcp.extend_from_slice(&source_port.to_be_bytes());
+        tcp.extend_from_slice(&destination_port.to_be_bytes());
+        tcp.extend_from_slice(&seq.to_be_bytes());
+        tcp.extend_from_slice(&(if flags & TCP_ACK != 0 { ack } else { 0 }).to_be_bytes());
+        tcp.push(5 << 4); // header length of 5 words
+        tcp.push(flags);
+        tcp.extend_from_slice(&65535u16.to_be_bytes()); // window
+        tcp.extend_from_slice(&[0, 0]); // checksum
+        tcp.extend_from_slice(&[0, 0]); // urgent pointer
+        tcp.extend_from_slice(payload);
+
+        let mut pseudo_header = Vec::with_capacity(12 + tcp.len());
+        pseudo_header.extend_from_slice(&source);
+        pseudo_header.extend_from_slice(&destination);
+        pseudo_header.extend_from_slice(&[0, 6]);
+        pseudo_header.extend_from_slice(&(tcp.len() as u16).to_be_bytes());
+        pseudo_header.extend_from_slice(&tcp);
+        let tcp_checksum = checksum(&pseudo_header);
+        tcp[16..18].copy_from_slice(&tcp_checksum.to_be_bytes());
+
+        let mut ip = Vec::with_capacity(20 + tcp.len());
+        ip.push(0x45); // version 4, header length of 5 words
+        ip.push(0);
+        ip.extend_from_slice(&((20 + tcp.len()) as u16).to_be_bytes());
+        ip.extend_from_slice(&[0, 0]); // identification
+        ip.extend_from_slice(&[0x40, 0]); // don't fragment
+        ip.push(64); // ttl
+        ip.push(6); // tcp
+        ip.extend_from_slice(&[0, 0]); // checksum
+        ip.extend_from_slice(&source);
+        ip.extend_from_slice(&destination);
+        let ip_checksum = checksum(&ip);
+        ip[10..12].copy_from_slice(&ip_checksum.to_be_bytes());
+        ip.extend_from_slice(&tcp);
+
+        let advance = payload.len() as u32;
+        match direction {
+            Direction::Received => self.attacker_seq = self.attacker_seq.wrapping_add(advance),
+            Direction::Sent => self.agent_seq = self.agent_seq.wrapping_add(advance),
+        }
+
+        ip
+    }
+}
+
+/// The payload which has been exchanged over a TCP connection in a PCAP file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CapturedConnection {
+    /// Bytes which have been sent by the peer which opened the connection
+    pub client_data: Vec<u8>,
+    /// Bytes which have been sent by the other peer
+    pub server_data: Vec<u8>,
+}
+
+/// State of a connection while reading a PCAP file
+struct ReadConnection {
+    client: ([u8; 4], u16),
+    server: ([u8; 4], u16),
+    /// Next expected sequence numbers of the client and the server
+    next_seq: [Option<u32>; 2],
+    data: CapturedConnection,
+}
+
+/// Reads the TCP connections over IPv4 in a PCAP file with an Ethernet or raw IP link layer.
+///
+/// The segments of each direction are concatenated in the order of the file. Retransmitted
+/// segments are skipped, but segments are not reordered. The peer which sent the first SYN, or
+/// otherwise the first segment, is considered the client.
+pub fn read_pcap(pcap: &[u8]) -> io::Result<Vec<CapturedConnection>> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+    if pcap.len() < 24 {
+        return Err(invalid("PCAP file is too short"));
+    }
+    let big_endian = match pcap[..4] {
+        [0xd4, 0xc3, 0xb2, 0xa1] | [0x4d, 0x3c, 0xb2, 0xa1] => false,
+        [0xa1, 0xb2, 0xc3, 0xd4] | [0xa1, 0xb2, 0x3c, 0x4d] => true,
+        _ => return Err(invalid("Not a PCAP file")),
+    };
+    let read_u32 = |bytes: &[u8]| {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    };
+
+    let link_type = read_u32(&pcap[20..24]);
+    let link_header = match link_type {
+        LINKTYPE_ETHERNET => 14,
+        LINKTYPE_RAW | LINKTYPE_IPV4 => 0,
+        _ => return Err(invalid("Unsupported link type")),
+    };
+
+    let mut connections: Vec<ReadConnection> = vec![];
+    let mut offset = 24;
+    while offset + 16 <= pcap.len() {
+        let length = read_u32(&pcap[offset + 8..offset + 12]) as usize;
+        let packet = pcap
+            .get(offset + 16..offset + 16 + length)
+            .ok_or_else(|| invalid("Truncated packet"))?;
+        offset += 16 + length;
+
+        if link_type == LINKTYPE_ETHERNET && packet.get(12..14) != Some(&[0x08, 0x00]) {
+            continue; // not IPv4
+        }
+        if let Some(segment) = packet.get(link_header..).and_then(parse_segment) {
+            read_segment(&mut connections, segment);
+        }
+    }
+
+    Ok(connections
+        .into_iter()
+        .map(|connection| connection.data)
+        .collect())
+}
+
+struct Segment<'a> {
+    source: ([u8; 4], u16),
+    destination: ([u8; 4], u16),
+    seq: u32,
+    flags: u8,
+    payload: &'a [u8],
+}
+
+fn parse_segment(ip: &[u8]) -> Option<Segment> {
+    if ip.len() < 20 || ip[0] >> 4 != 4 || ip[9] != 6 {
+        return None;
+    }
+    let ip_header = usize::from(ip[0] & 0x0f) * 4;
+    let total_length = usize::from(u16::from_be_bytes([ip[2], ip[3]]));
+    let tcp = ip.get(ip_header..total_length.min(ip.len()))?;
+    if tcp.len() < 20 {
+        return None;
+    }
+    let tcp_header = usize::from(tcp[12] >> 4) * 4;
+
+    Some(Segment {
+        source: (
+            ip[12..16].try_into().ok()?,
+            u16::from_be_bytes([tcp[0], tcp[1]]),
+        ),
+        destination: (
+            ip[16..20].try_into().ok()?,
+            u16::from_be_bytes([tcp[2], tcp[3]]),
+        ),
+        seq: u32::from_be_bytes(tcp[4..8].try_into().ok()?),
+        flags: tcp[13],
+        payload: tcp.get(tcp_header..)?,
+    })
+}
+
+fn read_segment(connections: &mut Vec<ReadConnection>, segment: Segment) {
+    let found = connections.iter().position(|connection| {
+        (connection.client, connection.server) == (segment.source, segment.destination)
+            || (connection.server, connection.client) == (segment.source, segment.destination)
+    });
+    let index = match found {
+        Some(index) => index,
+        None => {
+            connections.push(ReadConnection {
+                client: segment.source,
+                server: segment.destination,
+                next_seq: [None, None],
+                data: CapturedConnection::default(),
+            });
+            connections.len() - 1
+        }
+    };
+    let connection = &mut connections[index];
+    let from_client = connection.client == segment.source;
+    let direction = usize::from(!from_client);
+
+    if segment.flags & TCP_SYN != 0 {
+        connection.next_seq[direction] = Some(segment.seq.wrapping_add(1));
+        return;
+    }
+    if segment.payload.is_empty() {
+        return;
+    }
+    if let Some(next_seq) = connection.next_seq[direction] {
+        // The segment ends before the expected data, i.e. it has been retransmitted
+        let end = segment.seq.wrapping_add(segment.payload.len() as u32);
+        if (end.wrapping_sub(next_seq) as i32) <= 0 {
+            return;
+        }
+    }
+    connection.next_seq[direction] = Some(segment.seq.wrapping_add(segment.payload.len() as u32));
+
+    let data = if from_client {
+        &mut connection.data.client_data
+    } else {
+        &mut connection.data.server_data
+    };
+    data.extend_from_slice(segment.payload);
+}
+
+/// Internet checksum as defined in RFC 1071
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|chunk| u32::from(u16::from_be_bytes([chunk[0], *chunk.get(1).unwrap_or(&0)])))
+        .sum();
+
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{checksum, read_pcap, Capture};
+    use crate::agent::AgentName;
+    use crate::counters::Direction;
+
+    #[test_log::test]
+    fn test_checksum() {
+        // Example IPv4 header from https://en.wikipedia.org/wiki/Internet_checksum
+        let header = [
+            0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7,
+        ];
+
+        assert_eq!(checksum(&header), 0xb861);
+    }
+
+    #[test_log::test]
+    fn test_compare_sent() {
+        let server = AgentName::first();
+
+        let mut capture = Capture::new();
+        capture.record(server, Direction::Received, vec![1]);
+        capture.record(server, Direction::Sent, vec![2]);
+
+        let mut same = Capture::new();
+        same.record(server, Direction::Received, vec![3]);
+        same.record(server, Direction::Sent, vec![2]);
+        assert_eq!(capture.compare_sent(&same), Ok(()));
+
+        let mut different = Capture::new();
+        different.record(server, Direction::Sent, vec![4]);
+        assert!(capture.compare_sent(&different).is_err());
+
+        let mut longer = Capture::new();
+        longer.record(server, Direction::Sent, vec![2]);
+        longer.record(server, Direction::Sent, vec![5]);
+        assert!(capture.compare_sent(&longer).is_err());
+    }
+
+    #[test_log::test]
+    fn test_write_pcap() {
+        let server = AgentName::first();
+
+        let mut capture = Capture::new();
+        capture.record(server, Direction::Received, vec![0x16; 10]);
+        capture.record(server, Direction::Sent, vec![0x16; 2000]);
+        capture.record(server, Direction::Sent, vec![]);
+
+        let mut pcap = vec![];
+        capture.write_pcap(&mut pcap).unwrap();
+
+        let mut offset = 24;
+        let mut packets = vec![];
+        while offset < pcap.len() {
+            let len = u32::from_le_bytes(pcap[offset + 8..offset + 12].try_into().unwrap());
+            packets.push(len as usize);
+            offset += 16 + len as usize;
+        }
+
+        // handshake, 10 bytes, 2000 bytes in two segments
+        assert_eq!(packets, vec![40, 40, 40, 50, 1500, 580]);
+        assert_eq!(offset, pcap.len());
+    }
+
+    #[test_log::test]
+    fn test_read_pcap() {
+        let server = AgentName::first();
+        let other_server = server.next();
+
+        let mut capture = Capture::new();
+        capture.record(server, Direction::Received, vec![1; 10]);
+        capture.record(other_server, Direction::Received, vec![2; 3]);
+        capture.record(server, Direction::Sent, vec![3; 2000]);
+        capture.record(server, Direction::Received, vec![4; 5]);
+
+        let mut pcap = vec![];
+        capture.write_pcap(&mut pcap).unwrap();
+
+        let connections = read_pcap(&pcap).unwrap();
+        assert_eq!(connections.len(), 2);
+        assert_eq!(
+            connections[0].client_data,
+            [vec![1; 10], vec![4; 5]].concat()
+        );
+        assert_eq!(connections[0].server_data, vec![3; 2000]);
+        assert_eq!(connections[1].client_data, vec![2; 3]);
+        assert!(connections[1].server_data.is_empty());
+
+        assert!(read_pcap(&pcap[..10]).is_err());
+    }
+}
@@ -1,29 +1,112 @@
 use std::any::Any;
 use std::cell::{Ref, RefCell, RefMut};
-use std::fmt::Debug;
-use std::ops::Deref;
+use std::collections::{HashSet, VecDeque};
+use std::fmt::{self, Debug};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::ops::RangeBounds;
+use std::path::Path;
 use std::rc::Rc;
 use std::slice::Iter;
+use std::sync::{Arc, Mutex, PoisonError};
 
 use itertools::Itertools;
 
 use crate::agent::AgentName;
 use crate::algebra::dynamic_function::TypeShape;
+use crate::algebra::Matcher;
+use crate::counters::{Direction, TraceCounters};
 use crate::variable_data::VariableData;
 
-pub trait Claim: VariableData + Debug {
+/// Number of the latest claims which are remembered for the fingerprints of crashes
+const RECENT_CLAIMS: usize = 8;
+
+/// The agent and the type of the latest claims of the current execution, see [`recent_claims`]
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// The agents and the types of the latest claims which were made since the last
+/// [`GlobalClaimList`] was created, the oldest first.
+///
+/// The list is still available after the PUT crashed. It is empty if the lock is held, e.g.
+/// because the crash happened while a claim was recorded.
+pub fn recent_claims() -> Vec<String> {
+    RECENT
+        .try_lock()
+        .map(|recent| recent.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+fn remember_claim(claim: String) {
+    let mut recent = RECENT.lock().unwrap_or_else(PoisonError::into_inner);
+    if recent.len() == RECENT_CLAIMS {
+        recent.pop_front();
+    }
+    recent.push_back(claim);
+}
+
+pub trait Claim: VariableData + Debug + Clone {
     fn agent_name(&self) -> AgentName;
     fn id(&self) -> TypeShape;
     fn inner(&self) -> Box<dyn Any>;
+
+    /// Returns entries of the NSS key log format for the secrets which are reported in the claim,
+    /// see <https://firefox-source-docs.mozilla.org/security/nss/legacy/key_log_format/index.html>.
+    /// By default, a claim reports no secrets.
+    fn key_log(&self) -> Vec<String> {
+        vec![]
+    }
 }
 
-pub trait SecurityViolationPolicy<C: Claim> {
+pub trait SecurityViolationPolicy<C: Claim, M: Matcher> {
     fn check_violation(claims: &[C]) -> Option<&'static str>;
+
+    /// Checks the [`TraceCounters`] of the agents. By default, no violation is reported.
+    fn check_counters(_counters: &TraceCounters<M>) -> Option<&'static str> {
+        None
+    }
+
+    /// Checks whether the attacker learned a secret which the agents reported in their `claims`.
+    /// The `knowledge` is the data which the attacker extracted from the messages of the agents.
+    /// By default, no violation is reported.
+    fn check_secrecy(_claims: &[C], _knowledge: &[&dyn VariableData]) -> Option<&'static str> {
+        None
+    }
+
+    /// Checks the `claims` against the flights which the agents sent and received, in the order
+    /// of the execution. The flights are
+    /// [`ProtocolMessageFlight`](crate::protocol::ProtocolBehavior::ProtocolMessageFlight)s. By
+    /// default, no violation is reported.
+    fn check_exchange(
+        _claims: &[C],
+        _exchanged: &[(AgentName, Direction, &dyn VariableData)],
+    ) -> Option<&'static str> {
+        None
+    }
+}
+
+/// When a claim was made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ClaimOrigin {
+    /// Increases with each claim of the list, starting at 0
+    pub sequence: u64,
+    /// Index of the step of the trace which was executed when the claim was made. `None` if the
+    /// claim was made outside of a step, e.g. while spawning the agents.
+    pub step: Option<usize>,
 }
 
-#[derive(Default, Clone, Debug, PartialEq)]
+#[derive(Default, Clone, Debug)]
 pub struct ClaimList<C: Claim> {
     claims: Vec<C>,
+    /// The origin of each claim, in the same order as `claims`
+    origins: Vec<ClaimOrigin>,
+    current_step: Option<usize>,
+    key_log: Option<KeyLog>,
+}
+
+impl<C: Claim + PartialEq> PartialEq for ClaimList<C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.claims == other.claims
+    }
 }
 
 impl<C: Claim> ClaimList<C> {
@@ -46,6 +129,25 @@ impl<C: Claim> ClaimList<C> {
     pub fn slice(&self) -> &[C] {
         &self.claims
     }
+
+    /// The claims together with their origin, in the order in which they were made.
+    pub fn iter_with_origin(&self) -> impl Iterator<Item = (&C, ClaimOrigin)> {
+        self.claims.iter().zip(self.origins.iter().copied())
+    }
+
+    /// The claims which were made during the steps in `steps`, in order.
+    pub fn claims_during_steps(&self, steps: impl RangeBounds<usize>) -> impl Iterator<Item = &C> {
+        self.iter_with_origin()
+            .filter(move |(_, origin)| origin.step.map_or(false, |step| steps.contains(&step)))
+            .map(|(claim, _)| claim)
+    }
+
+    /// The claims of `agent_name`, in order.
+    pub fn claims_by_agent(&self, agent_name: AgentName) -> impl Iterator<Item = &C> {
+        self.claims
+            .iter()
+            .filter(move |claim| claim.agent_name() == agent_name)
+    }
 }
 
 impl<C: Claim> ClaimList<C> {
@@ -67,18 +169,103 @@ impl<C: Claim> ClaimList<C> {
 
 impl<C: Claim> From<Vec<C>> for ClaimList<C> {
     fn from(claims: Vec<C>) -> Self {
-        Self { claims }
+        Self {
+            origins: (0..claims.len() as u64)
+                .map(|sequence| ClaimOrigin {
+                    sequence,
+                    step: None,
+                })
+                .collect(),
+            claims,
+            current_step: None,
+            key_log: None,
+        }
     }
 }
 
 impl<C: Claim> ClaimList<C> {
     pub const fn new() -> Self {
-        Self { claims: vec![] }
+        Self {
+            claims: vec![],
+            origins: vec![],
+            current_step: None,
+            key_log: None,
+        }
     }
 
     pub fn claim_sized(&mut self, claim: C) {
+        if let Some(key_log) = &self.key_log {
+            key_log.write(&claim.key_log());
+        }
+        remember_claim(format!("{}:{}", claim.agent_name(), claim.id()));
+        self.origins.push(ClaimOrigin {
+            sequence: self.claims.len() as u64,
+            step: self.current_step,
+        });
         self.claims.push(claim);
     }
+
+    /// Sets the index of the step which is executed, see [`ClaimOrigin::step`].
+    pub fn set_step(&mut self, step: Option<usize>) {
+        self.current_step = step;
+    }
+
+    /// Writes the [`Claim::key_log`] entries of all claims which are made from now on to
+    /// `key_log`
+    pub fn enable_key_log(&mut self, key_log: KeyLog) {
+        self.key_log = Some(key_log);
+    }
+}
+
+/// Destination of the NSS key log entries of claims, such that captured traffic can be decrypted
+/// with tools like Wireshark. Entries are written as soon as a claim is made and only once.
+#[derive(Clone)]
+pub struct KeyLog {
+    inner: Arc<Mutex<KeyLogWriter>>,
+}
+
+struct KeyLogWriter {
+    writer: Box<dyn Write + Send>,
+    /// Entries which have already been written
+    written: HashSet<String>,
+}
+
+impl KeyLog {
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(KeyLogWriter {
+                writer: Box::new(writer),
+                written: HashSet::new(),
+            })),
+        }
+    }
+
+    /// Appends to the file at `path`, like TLS libraries do with the file in `SSLKEYLOGFILE`
+    pub fn with_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(file))
+    }
+
+    fn write(&self, entries: &[String]) {
+        let mut inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        let KeyLogWriter { writer, written } = &mut *inner;
+
+        for entry in entries {
+            if written.insert(entry.clone()) {
+                if let Err(err) = writeln!(writer, "{}", entry) {
+                    log::warn!("Failed to write key log entry: {}", err);
+                }
+            }
+        }
+
+        let _ = writer.flush();
+    }
+}
+
+impl Debug for KeyLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "KeyLog")
+    }
 }
 
 #[derive(Default, Clone, PartialEq, Debug)]
@@ -88,16 +275,118 @@ pub struct GlobalClaimList<C: Claim> {
 
 impl<C: Claim> GlobalClaimList<C> {
     pub fn new() -> Self {
+        RECENT
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+
         Self {
             claims: Rc::new(RefCell::new(ClaimList::new())),
         }
     }
 
     pub fn deref_borrow(&self) -> Ref<'_, ClaimList<C>> {
-        self.claims.deref().borrow()
+        self.claims.borrow()
     }
 
     pub fn deref_borrow_mut(&self) -> RefMut<'_, ClaimList<C>> {
-        self.claims.deref().borrow_mut()
+        self.claims.borrow_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+
+    use super::{Claim, ClaimList, ClaimOrigin, KeyLog};
+    use crate::agent::AgentName;
+    use crate::algebra::dynamic_function::TypeShape;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct SecretClaim(&'static str);
+
+    impl Claim for SecretClaim {
+        fn agent_name(&self) -> AgentName {
+            if self.0 == "bb" {
+                AgentName::first().next()
+            } else {
+                AgentName::first()
+            }
+        }
+
+        fn id(&self) -> TypeShape {
+            TypeShape::of::<Self>()
+        }
+
+        fn inner(&self) -> Box<dyn Any> {
+            Box::new(self.clone())
+        }
+
+        fn key_log(&self) -> Vec<String> {
+            vec![format!("CLIENT_RANDOM 00 {}", self.0)]
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test_log::test]
+    fn test_key_log() {
+        let buffer = SharedBuffer::default();
+        let mut claims = ClaimList::new();
+
+        claims.claim_sized(SecretClaim("ff"));
+        claims.enable_key_log(KeyLog::new(buffer.clone()));
+        claims.claim_sized(SecretClaim("aa"));
+        claims.claim_sized(SecretClaim("aa"));
+        claims.claim_sized(SecretClaim("bb"));
+
+        assert_eq!(
+            String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap(),
+            "CLIENT_RANDOM 00 aa\nCLIENT_RANDOM 00 bb\n"
+        );
+    }
+
+    #[test_log::test]
+    fn test_claim_order() {
+        let mut claims = ClaimList::new();
+
+        claims.claim_sized(SecretClaim("ff"));
+        claims.set_step(Some(0));
+        claims.claim_sized(SecretClaim("aa"));
+        claims.set_step(Some(2));
+        claims.claim_sized(SecretClaim("bb"));
+        claims.claim_sized(SecretClaim("cc"));
+
+        assert_eq!(
+            claims.iter_with_origin().nth(2).unwrap().1,
+            ClaimOrigin {
+                sequence: 2,
+                step: Some(2)
+            }
+        );
+        assert_eq!(
+            claims.claims_during_steps(1..=2).collect::<Vec<_>>(),
+            vec![&SecretClaim("bb"), &SecretClaim("cc")]
+        );
+        assert_eq!(claims.claims_during_steps(..).count(), 3);
+        assert_eq!(
+            claims
+                .claims_by_agent(AgentName::first())
+                .collect::<Vec<_>>(),
+            vec![&SecretClaim("ff"), &SecretClaim("aa"), &SecretClaim("cc")]
+        );
     }
 }
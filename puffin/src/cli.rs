@@ -1,26 +1,43 @@
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::Write;
+use std::net::{SocketAddr, ToSocketAddrs};
 use std::path::{Path, PathBuf};
 use std::process::ExitCode;
+use std::time::Duration;
 use std::{env, fs};
 
-use clap::parser::ValuesRef;
-use clap::{arg, crate_authors, crate_name, crate_version, value_parser, Command};
+use clap::parser::{ValueSource, ValuesRef};
+use clap::{arg, crate_authors, crate_name, crate_version, value_parser, ArgAction, Command};
 use libafl::inputs::Input;
 
 use crate::agent::AgentName;
+use crate::algebra::remap::SymbolMap;
 use crate::algebra::set_deserialize_signature;
+use crate::capture::{read_pcap, Capture};
+use crate::claims::KeyLog;
 use crate::codec::Codec;
-use crate::execution::{ForkedRunner, Runner, TraceRunner};
+use crate::debugger::Debugger;
+use crate::error::ErrorClass;
+use crate::execution::{
+    run_in_subprocess, ExecutionStatus, ForkedRunner, ParallelRunner, Runner, TraceRunner,
+    SECURITY_VIOLATION_EXIT_CODE,
+};
 use crate::experiment::*;
+use crate::fuzzer::mutations::MutationWeights;
 use crate::fuzzer::sanitizer::asan::{asan_info, setup_asan_env};
-use crate::fuzzer::{start, FuzzerConfig};
+use crate::fuzzer::trace_file::{decode_remapped, encode_foreign};
+use crate::fuzzer::{
+    cmin, coverage, crashes, minimize, start, CampaignConfig, CheckpointConfig, FuzzerConfig,
+    Profile, SyncConfig,
+};
 use crate::graphviz::write_graphviz;
-use crate::log::config_default;
+use crate::log::{config_default, set_log_filter, LogFilter};
 use crate::protocol::{ProtocolBehavior, ProtocolMessage};
 use crate::put::PutDescriptor;
 use crate::put_registry::{PutRegistry, TCP_PUT};
+use crate::rng::set_master_seed;
 use crate::trace::{Action, Spawner, Trace, TraceContext};
+use crate::triage::{print_execution_report, triage};
 
 fn create_app<S>(title: S) -> Command
 where
@@ -33,14 +50,71 @@ where
         .arg(arg!(-c --cores [spec] "Sets the cores to use during fuzzing"))
         .arg(arg!(-s --seed [n] "(experimental) provide a seed for all clients")
             .value_parser(value_parser!(u64)))
+        .arg(arg!(--"rng-seed" [n] "Master seed of the fuzzer, the PUTs and the function symbols to reproduce whole sessions and replays")
+            .value_parser(value_parser!(u64)))
         .arg(arg!(-p --port [n] "Port of the broker")
             .value_parser(value_parser!(u16).range(1..)))
+        .arg(arg!(--"broker-connect" [address] "Connect the broker to the broker at host:port on another machine to share testcases and coverage")
+            .value_parser(|address: &str| address.to_socket_addrs().ok().and_then(|mut addresses| addresses.next()).ok_or(format!("cannot resolve {}", address))))
         .arg(arg!(-i --"max-iters" [i] "Maximum iterations to do")
             .value_parser(value_parser!(u64).range(0..)))
         .arg(arg!(--minimizer "Use a minimizer"))
+        .arg(arg!(--profile [name] "Preset for the fuzzing campaign. Explicitly passed options take precedence")
+            .value_parser(Profile::NAMES))
+        .arg(arg!(--campaign [file] "Campaign configuration in TOML. Explicitly passed options take precedence")
+            .value_parser(value_parser!(PathBuf)))
+        .arg(arg!(--"distill-every" [minutes] "Distill the corpus every n minutes during fuzzing")
+            .value_parser(value_parser!(u64).range(1..)))
+        .arg(arg!(--"sync-dir" [dir] "Exchange corpus entries with other fuzzers through this AFL-style sync directory")
+            .value_parser(value_parser!(PathBuf)))
+        .arg(arg!(--"sync-name" [name] "Name of the subdirectory of the sync directory which this fuzzer owns")
+            .default_value("tlspuffin"))
+        .arg(arg!(--"sync-every" [seconds] "Synchronize with the sync directory every n seconds")
+            .value_parser(value_parser!(u64).range(1..))
+            .default_value("60"))
+        .arg(arg!(--"checkpoint-dir" [dir] "Periodically write the fuzzer state of each client to this directory")
+            .value_parser(value_parser!(PathBuf)))
+        .arg(arg!(--"checkpoint-every" [minutes] "Write a checkpoint every n minutes")
+            .value_parser(value_parser!(u64).range(1..))
+            .default_value("15"))
+        .arg(arg!(--resume [dir] "Continue the campaign from the checkpoints in this directory, and keep checkpointing there")
+            .value_parser(value_parser!(PathBuf)))
+        .arg(arg!(--"mutation-weights" [weights] "Relative weights of the mutations, e.g. swap=2,generate=0. Unlisted mutations have a weight of 1")
+            .value_parser(|weights: &str| weights.parse::<MutationWeights>()))
+        .arg(arg!(--timeout [ms] "Executions which take longer are reported as timeouts")
+            .value_parser(value_parser!(u64).range(1..))
+            .default_value("5000"))
+        .arg(arg!(--"hang-deadline" [ms] "Timed out traces are executed again with this deadline and stored as hangs if they exceed it. Defaults to 6 times the timeout")
+            .value_parser(value_parser!(u64).range(1..)))
         .arg(arg!(--tui "Display fuzzing logs using the interactive terminal UI"))
+        .arg(arg!(--"log-filter" [filter] "Log levels of the root logger and of modules, e.g. info,puffin::trace=debug. Overrides RUST_LOG")
+            .value_parser(|filter: &str| filter.parse::<LogFilter>()))
         .arg(arg!(--"put-use-clear" "Use clearing functionality instead of recreating puts"))
+        .arg(arg!(--"put-option" [option] "Passes an option key=value to the PUT, e.g. cipher_list=AES128-SHA. Use key@n=value to configure only agent n")
+            .action(ArgAction::Append)
+            .value_parser(|option: &str| option.split_once('=').map(|(key, value)| (key.to_string(), value.to_string())).ok_or("expected key=value")))
+        .arg(arg!(--"agent-put" [mapping] "Spawns agent n with another registered PUT, e.g. 1=openssl312, such that traces compare PUT versions")
+            .action(ArgAction::Append)
+            .value_parser(|mapping: &str| mapping.split_once('=').and_then(|(agent, put)| Some((agent.parse::<u8>().ok()?, put.to_string()))).ok_or("expected n=put")))
         .arg(arg!(--"no-launcher" "Do not use the convenient launcher"))
+        .arg(arg!(--fork "Execute each trace in a forked process to survive crashes of the PUT"))
+        .arg(arg!(--incremental [steps] "Only execute the differing steps of traces which share at least this many steps with the previous trace")
+            .value_parser(value_parser!(usize))
+            .requires("fork"))
+        .arg(arg!(--jobs [n] "Execute up to n mutants of a corpus entry at the same time, each in a forked process")
+            .value_parser(value_parser!(usize))
+            .requires("fork"))
+        .arg(arg!(--"objective-errors" [classes] "Report executions which fail with these error classes as objectives, e.g. codec,put")
+            .value_delimiter(',')
+            .value_parser(|class: &str| class.parse::<ErrorClass>()))
+        .arg(arg!(--"memory-limit" [mb] "Report executions which allocate more than this many MiB as out-of-memory objectives")
+            .value_parser(value_parser!(u64).range(1..)))
+        .arg(arg!(--"max-knowledge-per-source" [count] "Evict the least recently used knowledge of an agent which sent more messages, unless later steps refer to it")
+            .value_parser(value_parser!(usize)))
+        .arg(arg!(--statsd [address] "Send the fuzzing metrics to the statsd daemon at host:port"))
+        .arg(arg!(--"prometheus-file" [path] "Write the latest fuzzing metrics to this file in the Prometheus text format")
+            .value_parser(value_parser!(PathBuf)))
+        .arg(arg!(--"symbol-stats" "Measure how often and how long each symbol is evaluated and report the most expensive ones"))
         .subcommands(vec![
             Command::new("quick-experiment").about("Starts a new experiment and writes the results out"),
             Command::new("experiment").about("Starts a new experiment and writes the results out")
@@ -61,13 +135,52 @@ where
                 .arg(arg!(-n --number <n> "Amount of files to execute starting at index.").value_parser(value_parser!(usize)))
                 .arg(arg!(-i --index <i> "Index of file to execute.").value_parser(value_parser!(usize)))
                 .arg(arg!(-s --sort "Sort files in ascending order by the creation date before executing")),
+            Command::new("inspect")
+                .about("Executes a single trace and prints the knowledge, the claims and the result of the execution")
+                .arg(arg!(<input> "The file which stores a trace"))
+                .arg(arg!(--pcap <file> "Write the exchanged bytes to a PCAP file"))
+                .arg(arg!(--keylog <file> "Append the secrets of the agents in the NSS key log format to a file. Defaults to SSLKEYLOGFILE")),
+            Command::new("diff")
+                .about("Prints the steps and subterms which differ between two traces")
+                .arg(arg!(<old> "The file which stores the first trace"))
+                .arg(arg!(<new> "The file which stores the second trace")),
+            Command::new("check-determinism")
+                .about("Executes a trace twice and checks whether the PUTs sent the same flights both times")
+                .arg(arg!(<input> "The file which stores a trace")),
+            Command::new("debug")
+                .about("Replays a trace step by step and pauses before each step or at breakpoints")
+                .arg(arg!(<input> "The file which stores a trace"))
+                .arg(arg!(-b --break <step> "Pause only before the given steps").num_args(1..).value_parser(value_parser!(usize))),
+            Command::new("shrink")
+                .about("Minimizes a trace which crashes the PUT")
+                .arg(arg!(<input> "The file which stores a trace"))
+                .arg(arg!(<output> "The file to which the minimized trace should be written")),
             Command::new("execute-traces")
                 .about("Executes traces stored in files.")
-                .arg(arg!(<inputs> "The file which stores a trace").num_args(1..)),
+                .arg(arg!(<inputs> "The file which stores a trace").num_args(1..))
+                .arg(arg!(-j --jobs <n> "Execute up to n traces at the same time, each in a forked process").value_parser(value_parser!(usize))),
+            Command::new("cmin")
+                .about("Replays a corpus and copies a minimal subset of the traces with the same coverage to a directory")
+                .arg(arg!(<inputs> "The files or directories which store the traces").num_args(1..))
+                .arg(arg!(-o --output <dir> "The directory to which the kept traces should be written").required(true)),
+            Command::new("triage")
+                .about("Replays the crashing traces of an objective directory and writes a report for each")
+                .arg(arg!(<objective_dir> "The directory which stores the crashing traces"))
+                .arg(arg!(-o --output <dir> "The directory to which the reports should be written").default_value("triage")),
             Command::new("binary-attack")
                 .about("Serializes a trace as much as possible and output its")
                 .arg(arg!(<input> "The file which stores a trace"))
                 .arg(arg!(<output> "The file to write serialized data to")),
+            Command::new("import")
+                .about("Reconstructs traces from the connections in a PCAP file")
+                .arg(arg!(<input> "The PCAP file which stores the captured connections"))
+                .arg(arg!(<output> "The directory to which the traces should be written")),
+            Command::new("convert")
+                .about("Converts traces of other forks, like upstream tlspuffin, to the current signature")
+                .arg(arg!(<inputs> "The files which store the traces").num_args(1..))
+                .arg(arg!(-o --output <dir> "The directory to which the converted traces should be written").required(true))
+                .arg(arg!(-m --map <file> "Renames symbols of the other fork to the current signature, one `old -> new` per line"))
+                .arg(arg!(--export "Convert traces of the current signature to the format of the other fork instead")),
             Command::new("tcp")
                 .about("Executes a trace against a TCP client/server")
                 .arg(arg!(<input> "The file which stores a trace"))
@@ -95,15 +208,75 @@ where
 
     let matches = create_app(title).get_matches();
 
-    let first_core = "0".to_string();
-    let core_definition = matches.get_one("cores").unwrap_or(&first_core);
+    if let Some(filter) = matches.get_one::<LogFilter>("log-filter") {
+        let _ = set_log_filter(filter.clone());
+        handle.set_config(config_default());
+    }
+
+    let core_definition: Option<&String> = matches.get_one("cores");
     let port: u16 = *matches.get_one::<u16>("port").unwrap_or(&1337u16);
     let static_seed: Option<u64> = matches.get_one("seed").copied();
+    let rng_seed: Option<u64> = matches.get_one("rng-seed").copied();
+    let remote_broker: Option<SocketAddr> = matches.get_one("broker-connect").copied();
     let max_iters: Option<u64> = matches.get_one("max-iters").copied();
     let minimizer = matches.get_flag("minimizer");
+    let distillation_interval: Option<Duration> = matches
+        .get_one::<u64>("distill-every")
+        .map(|minutes| Duration::from_secs(minutes * 60));
+    let sync: Option<SyncConfig> =
+        matches
+            .get_one::<PathBuf>("sync-dir")
+            .map(|directory| SyncConfig {
+                directory: directory.clone(),
+                name: matches.get_one::<String>("sync-name").unwrap().clone(),
+                interval: Duration::from_secs(*matches.get_one::<u64>("sync-every").unwrap()),
+            });
+    let resume: Option<&PathBuf> = matches.get_one("resume");
+    let checkpoint: Option<CheckpointConfig> = resume
+        .or_else(|| matches.get_one::<PathBuf>("checkpoint-dir"))
+        .map(|directory| CheckpointConfig {
+            directory: directory.clone(),
+            interval: Duration::from_secs(
+                *matches.get_one::<u64>("checkpoint-every").unwrap() * 60,
+            ),
+        });
+    let execution_timeout = Duration::from_millis(*matches.get_one::<u64>("timeout").unwrap());
+    let hang_deadline = matches
+        .get_one::<u64>("hang-deadline")
+        .map_or(execution_timeout * 6, |ms| Duration::from_millis(*ms));
     let tui = matches.get_flag("tui");
     let no_launcher = matches.get_flag("no-launcher");
+    let fork = matches.get_flag("fork");
+    let incremental: Option<usize> = matches.get_one::<usize>("incremental").copied();
+    let jobs = matches.get_one::<usize>("jobs").copied().unwrap_or(1);
+    let objective_errors: Vec<ErrorClass> = matches
+        .get_many::<ErrorClass>("objective-errors")
+        .map_or(vec![], |classes| classes.copied().collect());
+    let memory_limit: Option<u64> = matches.get_one::<u64>("memory-limit").copied();
+    let max_knowledge_per_source: Option<usize> = matches
+        .get_one::<usize>("max-knowledge-per-source")
+        .copied();
+    let symbol_stats = matches.get_flag("symbol-stats");
+    let statsd_address: Option<String> = matches.get_one::<String>("statsd").cloned();
+    let prometheus_file: Option<PathBuf> = matches.get_one::<PathBuf>("prometheus-file").cloned();
     let put_use_clear = matches.get_flag("put-use-clear");
+    let put_options: Vec<(String, String)> = matches
+        .get_many::<(String, String)>("put-option")
+        .map_or(vec![], |options| options.cloned().collect());
+    let agent_puts: Vec<(AgentName, String)> = matches
+        .get_many::<(u8, String)>("agent-put")
+        .map_or(vec![], |mappings| {
+            mappings
+                .map(|(agent, put)| (AgentName::from_index(*agent), put.clone()))
+                .collect()
+        });
+    let profile: Option<Profile> = matches
+        .get_one::<String>("profile")
+        .map(|name| name.parse().unwrap());
+    let campaign: Option<&PathBuf> = matches.get_one("campaign");
+    let mutation_weights: Option<MutationWeights> = matches
+        .get_one::<MutationWeights>("mutation-weights")
+        .copied();
 
     log::info!("Git Version: {}", crate::GIT_REF);
     log::info!("Put Versions:");
@@ -118,19 +291,44 @@ where
     asan_info();
     setup_asan_env();
 
+    if let Some(seed) = rng_seed {
+        log::info!("Using master seed {}", seed);
+        set_master_seed(seed);
+    }
+
     // Initialize global state
 
     if set_deserialize_signature(PB::signature()).is_err() {
         log::error!("Failed to initialize deserialization");
     }
 
-    let mut options: Vec<(String, String)> = Vec::new();
+    coverage::set_namespaces(&put_registry);
+
+    let mut options: Vec<(String, String)> = put_options;
     if put_use_clear {
         options.push(("use_clear".to_string(), put_use_clear.to_string()))
     }
 
     let default_put = PutDescriptor::new(put_registry.default().name(), options);
 
+    if let Some((_, put)) = agent_puts
+        .iter()
+        .find(|(_, put)| put_registry.find_by_id(put).is_none())
+    {
+        log::error!("PUT {} is not in the registry", put);
+        return ExitCode::FAILURE;
+    }
+    let agent_mapping: Vec<(AgentName, PutDescriptor)> = agent_puts
+        .iter()
+        .map(|(agent, put)| (*agent, PutDescriptor::new(put, default_put.options.clone())))
+        .collect();
+
+    // The PUTs of the agents of every command which executes traces
+    let spawner = Spawner::new(put_registry.clone())
+        .with_default(default_put.clone())
+        .with_mapping(&agent_mapping);
+    let runner = Runner::new(put_registry.clone(), spawner.clone());
+
     if let Some(_matches) = matches.subcommand_matches("seed") {
         if let Err(err) = seed(&put_registry) {
             log::error!("Failed to create seeds on disk: {:?}", err);
@@ -202,11 +400,6 @@ where
             lookup_paths.len()
         );
 
-        let runner = Runner::new(
-            put_registry.clone(),
-            Spawner::new(put_registry).with_default(default_put),
-        );
-
         for path in lookup_paths {
             log::info!("Executing: {}", path.display());
             execute(&runner, path);
@@ -260,10 +453,9 @@ where
 
         log::info!("execute: found {} inputs", paths.len());
 
-        let runner = Runner::new(
-            put_registry.clone(),
-            Spawner::new(put_registry).with_default(default_put),
-        );
+        if let Some(jobs) = matches.get_one::<usize>("jobs") {
+            return execute_parallel(ParallelRunner::new(runner, *jobs), &paths);
+        }
 
         for path in paths {
             log::info!("Executing: {}", path.display());
@@ -271,14 +463,144 @@ where
         }
 
         return ExitCode::SUCCESS;
+    } else if let Some(matches) = matches.subcommand_matches("inspect") {
+        let input: &String = matches.get_one("input").unwrap();
+        let pcap: Option<&String> = matches.get_one("pcap");
+        let key_log: Option<String> = matches
+            .get_one::<String>("keylog")
+            .cloned()
+            .or_else(|| env::var("SSLKEYLOGFILE").ok());
+
+        match inspect(&runner, input, pcap, key_log.as_deref()) {
+            Ok(ExecutionStatus::Success) => {}
+            Ok(status) => {
+                log::warn!("Execution finished with status {:?}", status);
+                return ExitCode::FAILURE;
+            }
+            Err(err) => {
+                log::error!("Failed to inspect trace: {:?}", err);
+                return ExitCode::FAILURE;
+            }
+        }
+    } else if let Some(matches) = matches.subcommand_matches("diff") {
+        let mut traces = vec![];
+        for input in ["old", "new"].map(|id| matches.get_one::<String>(id).unwrap()) {
+            match Trace::<PB::Matcher>::from_file(input) {
+                Ok(trace) => traces.push(trace),
+                Err(err) => {
+                    log::error!("Invalid trace file {}: {}", input, err);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+
+        let diff = traces[0].diff(&traces[1]);
+        if diff.is_empty() {
+            println!("The traces are equal");
+        } else {
+            print!("{}", diff);
+        }
+    } else if let Some(matches) = matches.subcommand_matches("check-determinism") {
+        let input: &String = matches.get_one("input").unwrap();
+
+        let trace = match Trace::<PB::Matcher>::from_file(input) {
+            Ok(trace) => trace,
+            Err(err) => {
+                log::error!("Invalid trace file {}: {}", input, err);
+                return ExitCode::FAILURE;
+            }
+        };
+
+        if let Err(err) = trace.execute_deterministic(&runner) {
+            log::error!("{}", err);
+            return ExitCode::FAILURE;
+        }
+
+        log::info!("The PUTs sent the same flights in both executions");
+    } else if let Some(matches) = matches.subcommand_matches("debug") {
+        let input: &String = matches.get_one("input").unwrap();
+        let breakpoints: Vec<usize> = matches
+            .get_many("break")
+            .map(|steps| steps.copied().collect())
+            .unwrap_or_default();
+
+        if let Err(err) = debug(&runner, input, breakpoints) {
+            log::error!("Failed to debug trace: {:?}", err);
+            return ExitCode::FAILURE;
+        }
+    } else if let Some(matches) = matches.subcommand_matches("shrink") {
+        let input: &String = matches.get_one("input").unwrap();
+        let output: &String = matches.get_one("output").unwrap();
+
+        if let Err(err) = shrink(&runner, input, output) {
+            log::error!("Failed to shrink trace: {:?}", err);
+            return ExitCode::FAILURE;
+        }
+    } else if let Some(matches) = matches.subcommand_matches("cmin") {
+        let inputs: ValuesRef<String> = matches.get_many("inputs").unwrap();
+        let output: &String = matches.get_one("output").unwrap();
+
+        let mut paths = vec![];
+        for input in inputs.map(PathBuf::from) {
+            if input.is_dir() {
+                match fs::read_dir(&input) {
+                    Ok(entries) => paths.extend(
+                        entries
+                            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                            .filter(|path| path.is_file()),
+                    ),
+                    Err(err) => {
+                        log::error!("Failed to read {}: {}", input.display(), err);
+                        return ExitCode::FAILURE;
+                    }
+                }
+            } else {
+                paths.push(input);
+            }
+        }
+        paths.sort();
+
+        if let Err(err) = cmin(&runner, &paths, Path::new(output), execution_timeout) {
+            log::error!("Failed to minimize the corpus: {:?}", err);
+            return ExitCode::FAILURE;
+        }
+    } else if let Some(matches) = matches.subcommand_matches("triage") {
+        let objective_dir: &String = matches.get_one("objective_dir").unwrap();
+        let output: &String = matches.get_one("output").unwrap();
+
+        match triage(&runner, Path::new(objective_dir), Path::new(output)) {
+            Ok(reports) => log::info!("Wrote {} reports to {}", reports.len(), output),
+            Err(err) => {
+                log::error!("Failed to triage crashes: {:?}", err);
+                return ExitCode::FAILURE;
+            }
+        }
     } else if let Some(matches) = matches.subcommand_matches("binary-attack") {
         let input: &String = matches.get_one("input").unwrap();
         let output: &String = matches.get_one("output").unwrap();
 
-        if let Err(err) = binary_attack(input, output, &put_registry, default_put) {
+        if let Err(err) = binary_attack(input, output, spawner) {
             log::error!("Failed to create trace output: {:?}", err);
             return ExitCode::FAILURE;
         }
+    } else if let Some(matches) = matches.subcommand_matches("import") {
+        let input: &String = matches.get_one("input").unwrap();
+        let output: &String = matches.get_one("output").unwrap();
+
+        if let Err(err) = import::<PB>(input, output) {
+            log::error!("Failed to import traces: {:?}", err);
+            return ExitCode::FAILURE;
+        }
+    } else if let Some(matches) = matches.subcommand_matches("convert") {
+        let inputs: ValuesRef<String> = matches.get_many("inputs").unwrap();
+        let output: &String = matches.get_one("output").unwrap();
+        let map: Option<&String> = matches.get_one("map");
+        let export = matches.get_flag("export");
+
+        if let Err(err) = convert::<PB>(inputs, output, map, export) {
+            log::error!("Failed to convert traces: {:?}", err);
+            return ExitCode::FAILURE;
+        }
     } else if let Some(matches) = matches.subcommand_matches("tcp") {
         let input: &String = matches.get_one("input").unwrap();
         let prog: Option<&String> = matches.get_one("binary");
@@ -338,7 +660,9 @@ where
             }
 
             experiment_path
-        } else if let Some(_matches) = matches.subcommand_matches("quick-experiment") {
+        } else if matches.subcommand_matches("quick-experiment").is_some()
+            || profile.map_or(false, |profile| profile.is_experiment())
+        {
             let description = "No Description, because this is a quick experiment.";
             let experiments_root = PathBuf::from("experiments");
 
@@ -369,23 +693,117 @@ where
             return ExitCode::FAILURE;
         }
 
-        let config = FuzzerConfig {
+        let mut config = FuzzerConfig {
+            put: default_put.clone(),
+            agent_puts: agent_puts.clone(),
             initial_corpus_dir: PathBuf::from("./seeds"),
             static_seed,
+            rng_seed,
             max_iters,
-            core_definition: core_definition.to_string(),
+            max_duration: None,
+            core_definition: "0".to_string(),
             corpus_dir: experiment_path.join("corpus"),
             objective_dir: experiment_path.join("objective"),
+            hangs_dir: experiment_path.join("hangs"),
+            buckets_dir: experiment_path.join("buckets"),
             broker_port: port,
+            remote_broker,
             stats_file: experiment_path.join("stats.json"),
             log_file: experiment_path.join("tlspuffin.log"),
             minimizer,
+            distillation_interval,
+            check_determinism: false,
+            sync,
+            checkpoint,
+            resume: resume.is_some(),
             mutation_stage_config: Default::default(),
             mutation_config: Default::default(),
             tui,
             no_launcher,
+            fork,
+            incremental,
+            jobs,
+            execution_timeout,
+            hang_deadline,
+            objective_errors,
+            memory_limit,
+            max_knowledge_per_source,
+            symbol_stats,
+            statsd_address,
+            prometheus_file,
         };
 
+        if let Some(profile) = profile {
+            log::info!("Using profile {}", profile);
+            profile.apply(&mut config);
+        }
+
+        if let Some(path) = campaign {
+            log::info!("Using campaign {}", path.display());
+            if let Err(err) =
+                CampaignConfig::from_file(path).and_then(|campaign| campaign.apply(&mut config))
+            {
+                log::error!("Failed to load campaign: {}", err);
+                return ExitCode::FAILURE;
+            }
+        }
+
+        let explicit = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        if let Some(core_definition) = core_definition {
+            config.core_definition = core_definition.clone();
+        }
+
+        if static_seed.is_some() {
+            config.static_seed = static_seed;
+        }
+
+        if rng_seed.is_some() {
+            config.rng_seed = rng_seed;
+        }
+
+        if explicit("put-option") || explicit("put-use-clear") {
+            config.put.options = default_put.options.clone();
+        }
+
+        if explicit("timeout") {
+            config.execution_timeout = execution_timeout;
+        }
+
+        if explicit("hang-deadline") {
+            config.hang_deadline = hang_deadline;
+        }
+
+        if explicit("agent-put") {
+            config.agent_puts = agent_puts;
+        }
+
+        if distillation_interval.is_some() {
+            config.distillation_interval = distillation_interval;
+        }
+
+        if let Some(weights) = mutation_weights {
+            log::info!("Using mutation weights {}", weights);
+            config.mutation_config.weights = weights;
+        }
+
+        if let Some(put) = std::iter::once(&config.put.factory)
+            .chain(config.agent_puts.iter().map(|(_, put)| put))
+            .find(|put| put_registry.find_by_id(put).is_none())
+        {
+            log::error!("Unknown PUT {}", put);
+            return ExitCode::FAILURE;
+        }
+
+        // Passing this file to --campaign reproduces the campaign
+        if let Err(err) = fs::write(
+            experiment_path.join("campaign.toml"),
+            CampaignConfig::resolved(&config).to_toml(),
+        ) {
+            log::error!("Failed to write campaign: {:?}", err);
+            return ExitCode::FAILURE;
+        }
+
         if let Err(err) = start::<PB>(&put_registry, config, handle) {
             match err {
                 libafl::Error::ShuttingDown => {
@@ -408,12 +826,8 @@ fn plot<PB: ProtocolBehavior>(
     is_multiple: bool,
     is_tree: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let mut input_file = File::open(input)?;
-
     // Read trace file
-    let mut buffer = Vec::new();
-    input_file.read_to_end(&mut buffer)?;
-    let trace = postcard::from_bytes::<Trace<PB::Matcher>>(&buffer)?;
+    let trace = Trace::<PB::Matcher>::from_file(input)?;
 
     // All-in-one tree
     write_graphviz(
@@ -451,6 +865,57 @@ fn seed<PB: ProtocolBehavior>(
     Ok(())
 }
 
+fn execute_parallel<PB: ProtocolBehavior>(
+    runner: ParallelRunner<PB>,
+    paths: &[PathBuf],
+) -> ExitCode {
+    let (paths, traces): (Vec<_>, Vec<_>) = paths
+        .iter()
+        .filter_map(|path| match Trace::<PB::Matcher>::from_file(path) {
+            Ok(trace) => Some((path, trace)),
+            Err(_) => {
+                log::error!("Invalid trace file {}", path.display());
+                None
+            }
+        })
+        .unzip();
+
+    log::info!(
+        "Executing {} traces in up to {} processes",
+        traces.len(),
+        runner.workers()
+    );
+
+    let mut violations = 0;
+    let mut crashes = 0;
+    for (path, result) in paths.iter().zip(runner.execute_all(&traces)) {
+        match result {
+            Ok(ExecutionStatus::Success) => log::info!("{}: success", path.display()),
+            Ok(ExecutionStatus::Failure(SECURITY_VIOLATION_EXIT_CODE)) => {
+                log::error!("{}: security claim violated", path.display());
+                violations += 1;
+            }
+            Ok(ExecutionStatus::Crashed) => {
+                log::error!("{}: PUT crashed", path.display());
+                crashes += 1;
+            }
+            Ok(status) => log::info!("{}: execution finished with {:?}", path.display(), status),
+            Err(err) => log::error!("{}: {}", path.display(), err),
+        }
+    }
+
+    if violations > 0 || crashes > 0 {
+        log::error!(
+            "{} traces violated a security claim, {} traces crashed",
+            violations,
+            crashes
+        );
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
+
 fn execute<PB: ProtocolBehavior, P: AsRef<Path>>(runner: &Runner<PB>, input: P) {
     let trace = match Trace::<PB::Matcher>::from_file(input.as_ref()) {
         Ok(t) => t,
@@ -473,13 +938,169 @@ fn execute<PB: ProtocolBehavior, P: AsRef<Path>>(runner: &Runner<PB>, input: P)
     }
 }
 
+fn inspect<PB: ProtocolBehavior>(
+    runner: &Runner<PB>,
+    input: &str,
+    pcap: Option<&String>,
+    key_log: Option<&str>,
+) -> Result<ExecutionStatus, Box<dyn std::error::Error>> {
+    let trace = Trace::<PB::Matcher>::from_file(input)?;
+
+    println!("{}", trace);
+
+    // A crashing PUT would take the whole process down, therefore the trace is executed in a
+    // subprocess which prints the state of the execution
+    let status = run_in_subprocess(
+        || {
+            let mut ctx = runner.new_context();
+            if let Some(path) = pcap {
+                // The flights are written while executing, such that they are available even if
+                // the PUT crashes
+                match Capture::with_pcap_file(path) {
+                    Ok(capture) => ctx.enable_capture(capture),
+                    Err(err) => println!("Failed to create {}: {}", path, err),
+                }
+            }
+            if let Some(path) = key_log {
+                match KeyLog::with_file(path) {
+                    Ok(key_log) => ctx.enable_key_log(key_log),
+                    Err(err) => println!("Failed to open {}: {}", path, err),
+                }
+            }
+            let result = trace.execute(&mut ctx);
+
+            if let (Some(path), Some(capture)) = (pcap, ctx.capture()) {
+                println!("Wrote {} flights to {}", capture.flights().len(), path);
+            }
+
+            std::process::exit(print_execution_report(&ctx, result));
+        },
+        None,
+    )?;
+
+    println!("Status: {:?}", status);
+    Ok(status)
+}
+
+fn debug<PB: ProtocolBehavior>(
+    runner: &Runner<PB>,
+    input: &str,
+    breakpoints: Vec<usize>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut trace = Trace::<PB::Matcher>::from_file(input)?;
+
+    Debugger::new(runner).with_breakpoints(breakpoints).run(
+        &mut trace,
+        std::io::stdin().lock(),
+        std::io::stdout(),
+    )?;
+
+    Ok(())
+}
+
+fn shrink<PB: ProtocolBehavior>(
+    runner: &Runner<PB>,
+    input: &str,
+    output: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let trace = Trace::<PB::Matcher>::from_file(input)?;
+
+    if !crashes(runner, &trace) {
+        return Err(format!("trace {} does not crash", input).into());
+    }
+
+    let minimized = minimize(&trace, |candidate| crashes(runner, candidate));
+    minimized.to_file(output)?;
+
+    log::info!(
+        "Minimized trace from {} to {} steps into {}",
+        trace.steps.len(),
+        minimized.steps.len(),
+        output
+    );
+    Ok(())
+}
+
+fn import<PB: ProtocolBehavior>(
+    input: &str,
+    output: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let connections = read_pcap(&fs::read(input)?)?;
+    fs::create_dir_all(output)?;
+
+    let name = Path::new(input)
+        .file_stem()
+        .map_or("import".into(), |stem| stem.to_string_lossy());
+
+    let mut imported = 0;
+    for (i, connection) in connections.iter().enumerate() {
+        match PB::import_trace(&connection.client_data) {
+            Ok(trace) => {
+                trace.to_file(Path::new(output).join(format!("{}_{}.trace", name, i)))?;
+                imported += 1;
+            }
+            Err(err) => log::warn!("Skipping connection {}: {}", i, err),
+        }
+    }
+
+    log::info!(
+        "Imported {} of {} connections into the directory {}",
+        imported,
+        connections.len(),
+        output
+    );
+    Ok(())
+}
+
+fn convert<PB: ProtocolBehavior>(
+    inputs: ValuesRef<String>,
+    output: &str,
+    map: Option<&String>,
+    export: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let map: SymbolMap = match map {
+        Some(map) => fs::read_to_string(map)?.parse()?,
+        None => SymbolMap::default(),
+    };
+    fs::create_dir_all(output)?;
+
+    let mut converted = 0;
+    let mut failed = 0;
+    for input in inputs {
+        let output = Path::new(output).join(Path::new(input).file_name().unwrap_or_default());
+
+        let result = if export {
+            Trace::<PB::Matcher>::from_file(input)
+                .and_then(|trace| encode_foreign(&trace, &map.reversed()))
+                .and_then(|bytes| Ok(fs::write(&output, bytes)?))
+        } else {
+            decode_remapped::<PB::Matcher>(&fs::read(input)?, &map)
+                .and_then(|decoded| decoded.trace.to_file(&output))
+        };
+
+        match result {
+            Ok(()) => converted += 1,
+            Err(err) => {
+                log::warn!("Failed to convert {}: {}", input, err);
+                failed += 1;
+            }
+        }
+    }
+
+    log::info!(
+        "Converted {} traces into the directory {}, failed {}",
+        converted,
+        output,
+        failed
+    );
+    Ok(())
+}
+
 fn binary_attack<PB: ProtocolBehavior>(
     input: &str,
     output: &str,
-    put_registry: &PutRegistry<PB>,
-    default_put: impl Into<PutDescriptor>,
+    spawner: Spawner<PB>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let spawner = Spawner::new(put_registry.clone()).with_default(default_put);
     let ctx = TraceContext::new(spawner);
     let trace = Trace::<PB::Matcher>::from_file(input)?;
 
@@ -507,7 +1128,11 @@ fn binary_attack<PB: ProtocolBehavior>(
                     }
                 }
             }
-            Action::Output(_) => {}
+            Action::Output(_)
+            | Action::Expect(_)
+            | Action::Reset(_)
+            | Action::Renegotiate(_)
+            | Action::KeyUpdate(_) => {}
         }
     }
     Ok(())
@@ -0,0 +1,275 @@
+//! Conformance of the PUTs to the seed traces of a protocol.
+//!
+//! The [`ConformanceSuite`] executes every seed of [`ProtocolBehavior::create_corpus`] with every
+//! PUT of a [`PutRegistry`] and collects the outcomes in a [`ConformanceMatrix`]. A seed succeeds
+//! if its execution does not fail and all agents reach a successful state. Seeds which a PUT can
+//! not handle, e.g. because the library does not support a feature, are registered with
+//! [`ConformanceSuite::expect_failure`] together with the reason. Any other failure, and any
+//! expected failure which no longer fails, is reported by [`ConformanceMatrix::assert_conforming`]:
+//!
+//! ```ignore
+//! ConformanceSuite::new(tls_registry())
+//!     .skip_put(TCP_PUT)
+//!     .expect_failure("rust-put-wolfssl", "seed_0rtt", "early data is disabled")
+//!     .run()
+//!     .assert_conforming();
+//! ```
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::execution::Runner;
+use crate::protocol::ProtocolBehavior;
+use crate::put_registry::PutRegistry;
+use crate::trace::{Spawner, Trace};
+
+/// Outcome of the execution of one seed with one PUT
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    /// The seed failed as documented by [`ConformanceSuite::expect_failure`]
+    ExpectedFailure(String),
+    /// The seed succeeded although a failure is expected
+    UnexpectedSuccess(String),
+    Failure(String),
+}
+
+impl Outcome {
+    pub fn is_conforming(&self) -> bool {
+        matches!(self, Outcome::Success | Outcome::ExpectedFailure(_))
+    }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            Outcome::Success => "ok",
+            Outcome::ExpectedFailure(_) => "expected",
+            Outcome::UnexpectedSuccess(_) => "UNEXPECTED",
+            Outcome::Failure(_) => "FAIL",
+        }
+    }
+}
+
+pub struct ConformanceSuite<PB: ProtocolBehavior> {
+    registry: PutRegistry<PB>,
+    skipped: BTreeSet<String>,
+    expected_failures: HashMap<(String, String), String>,
+}
+
+impl<PB: ProtocolBehavior> ConformanceSuite<PB> {
+    pub fn new(registry: PutRegistry<PB>) -> Self {
+        Self {
+            registry,
+            skipped: BTreeSet::new(),
+            expected_failures: HashMap::new(),
+        }
+    }
+
+    /// Excludes `put` from the suite, e.g. PUTs which can not run without options
+    pub fn skip_put(mut self, put: impl Into<String>) -> Self {
+        self.skipped.insert(put.into());
+        self
+    }
+
+    /// Documents that the `seed` is expected to fail with `put` because of `reason`
+    pub fn expect_failure(
+        mut self,
+        put: impl Into<String>,
+        seed: impl Into<String>,
+        reason: impl Into<String>,
+    ) -> Self {
+        self.expected_failures
+            .insert((put.into(), seed.into()), reason.into());
+        self
+    }
+
+    /// Executes the seeds of the protocol with all PUTs which are not skipped
+    pub fn run(&self) -> ConformanceMatrix {
+        self.run_seeds(&PB::create_corpus())
+    }
+
+    /// Executes `seeds` with all PUTs which are not skipped
+    pub fn run_seeds(&self, seeds: &[(Trace<PB::Matcher>, &'static str)]) -> ConformanceMatrix {
+        let mut matrix = ConformanceMatrix {
+            puts: vec![],
+            seeds: seeds.iter().map(|(_, name)| name.to_string()).collect(),
+            outcomes: HashMap::new(),
+        };
+
+        for (put, _) in self.registry.puts() {
+            if self.skipped.contains(put) {
+                continue;
+            }
+            matrix.puts.push(put.to_string());
+
+            let spawner = Spawner::new(self.registry.clone()).with_default(put);
+            let runner = Runner::new(self.registry.clone(), spawner);
+
+            for (trace, seed) in seeds {
+                log::info!("Executing seed {} with PUT {}", seed, put);
+                let failure = execute(&runner, trace);
+                let expected = self
+                    .expected_failures
+                    .get(&(put.to_string(), seed.to_string()));
+
+                let outcome = match (failure, expected) {
+                    (None, None) => Outcome::Success,
+                    (None, Some(reason)) => Outcome::UnexpectedSuccess(reason.clone()),
+                    (Some(_), Some(reason)) => Outcome::ExpectedFailure(reason.clone()),
+                    (Some(failure), None) => Outcome::Failure(failure),
+                };
+                matrix
+                    .outcomes
+                    .insert((put.to_string(), seed.to_string()), outcome);
+            }
+        }
+
+        matrix
+    }
+}
+
+/// Returns why the execution of `trace` failed, or `None` if it succeeded
+fn execute<PB: ProtocolBehavior>(
+    runner: &Runner<PB>,
+    trace: &Trace<PB::Matcher>,
+) -> Option<String> {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let (ctx, result) = runner.execute_with_context(trace);
+        match result {
+            Ok(()) if ctx.agents_successful() => None,
+            Ok(()) => Some("not all agents are in a successful state".to_string()),
+            Err(err) => Some(err.to_string()),
+        }
+    }));
+
+    result.unwrap_or_else(|_| Some("PUT panicked".to_string()))
+}
+
+/// Outcomes of a [`ConformanceSuite`] per PUT and seed. The [`Display`](fmt::Display)
+/// implementation prints the matrix as table with one row per seed and one column per PUT.
+#[derive(Debug, Clone)]
+pub struct ConformanceMatrix {
+    puts: Vec<String>,
+    seeds: Vec<String>,
+    outcomes: HashMap<(String, String), Outcome>,
+}
+
+impl ConformanceMatrix {
+    pub fn outcome(&self, put: &str, seed: &str) -> Option<&Outcome> {
+        self.outcomes.get(&(put.to_string(), seed.to_string()))
+    }
+
+    /// Returns the PUT, the seed and the outcome of all non-conforming executions
+    pub fn violations(&self) -> Vec<(&str, &str, &Outcome)> {
+        self.puts
+            .iter()
+            .flat_map(|put| self.seeds.iter().map(move |seed| (put, seed)))
+            .filter_map(|(put, seed)| {
+                self.outcome(put, seed)
+                    .filter(|outcome| !outcome.is_conforming())
+                    .map(|outcome| (put.as_str(), seed.as_str(), outcome))
+            })
+            .collect()
+    }
+
+    /// Panics with the matrix and all non-conforming executions, if there are any
+    pub fn assert_conforming(&self) {
+        let violations = self.violations();
+        if violations.is_empty() {
+            log::info!("Conformance matrix:\n{}", self);
+            return;
+        }
+
+        let details = violations
+            .iter()
+            .map(|(put, seed, outcome)| match outcome {
+                Outcome::UnexpectedSuccess(reason) => {
+                    format!(
+                        "{} with {}: succeeded, expected to fail: {}",
+                        seed, put, reason
+                    )
+                }
+                Outcome::Failure(failure) => format!("{} with {}: {}", seed, put, failure),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        panic!(
+            "{} seed executions do not conform:\n{}\n\n{}",
+            violations.len(),
+            details,
+            self
+        );
+    }
+}
+
+impl fmt::Display for ConformanceMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.seeds.iter().map(String::len).max().unwrap_or(0);
+
+        write!(f, "{:width$}", "", width = width)?;
+        for put in &self.puts {
+            write!(f, " | {}", put)?;
+        }
+        for seed in &self.seeds {
+            write!(f, "\n{:width$}", seed, width = width)?;
+            for put in &self.puts {
+                let symbol = self.outcome(put, seed).map_or("-", Outcome::symbol);
+                write!(f, " | {:w$}", symbol, w = put.len())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConformanceSuite, Outcome};
+    use crate::agent::{AgentDescriptor, AgentName, TLSVersion};
+    use crate::algebra::test_signature::*;
+    use crate::put_registry::PutRegistry;
+    use crate::trace::Trace;
+
+    #[test_log::test]
+    fn test_matrix_reports_unexpected_outcomes() {
+        let registry = PutRegistry::<TestProtocolBehavior>::new(
+            [("TESTSTUB_RUST_PUT", Box::new(TestFactory) as _)],
+            "TESTSTUB_RUST_PUT",
+        )
+        .unwrap();
+        let empty: TestTrace = Trace {
+            descriptors: vec![],
+            prior_traces: vec![],
+            steps: vec![],
+        };
+        // The test factory panics when an agent is spawned
+        let spawning = Trace {
+            descriptors: vec![AgentDescriptor::new_server(
+                AgentName::first(),
+                TLSVersion::V1_3,
+            )],
+            ..empty.clone()
+        };
+        let seeds = [(empty.clone(), "empty"), (spawning, "spawning")];
+
+        let matrix = ConformanceSuite::new(registry.clone()).run_seeds(&seeds);
+        assert_eq!(
+            matrix.outcome("TESTSTUB_RUST_PUT", "empty"),
+            Some(&Outcome::Success)
+        );
+        assert_eq!(matrix.violations().len(), 1);
+
+        let matrix = ConformanceSuite::new(registry.clone())
+            .expect_failure("TESTSTUB_RUST_PUT", "spawning", "no agents")
+            .run_seeds(&seeds);
+        matrix.assert_conforming();
+
+        let matrix = ConformanceSuite::new(registry)
+            .skip_put("TESTSTUB_RUST_PUT")
+            .run_seeds(&seeds);
+        assert!(matrix.to_string().contains("spawning"));
+        assert!(matrix.violations().is_empty());
+    }
+}
@@ -0,0 +1,191 @@
+//! Per-agent counters which are collected while executing a [`Trace`](crate::trace::Trace).
+//!
+//! Claims only capture what a PUT reports about itself. The [`TraceCounters`] are instead derived
+//! from the messages which are exchanged with each [`Agent`](crate::agent::Agent). This allows
+//! [security policies](crate::claims::SecurityViolationPolicy) to detect violations like "a
+//! server must not send more than one HelloRetryRequest".
+
+use std::any::TypeId;
+
+use serde::Serialize;
+
+use crate::agent::AgentName;
+use crate::algebra::Matcher;
+use crate::protocol::{ExtractKnowledge, ProtocolBehavior};
+use crate::trace::Source;
+
+/// Whether a flight has been sent or received by an agent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// Counters of a single agent.
+///
+/// Messages are counted per [`Matcher`], which is determined by the
+/// [`ExtractKnowledge`] implementation of the protocol. Encrypted messages are therefore only
+/// counted with the matcher of their outer record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentCounters<M: Matcher> {
+    /// Number of records (opaque messages) sent by the agent
+    pub records_sent: usize,
+    /// Number of records (opaque messages) received by the agent
+    pub records_received: usize,
+    messages_sent: Vec<(Option<M>, usize)>,
+    messages_received: Vec<(Option<M>, usize)>,
+    history: Vec<(Direction, Option<M>)>,
+}
+
+impl<M: Matcher> Default for AgentCounters<M> {
+    fn default() -> Self {
+        Self {
+            records_sent: 0,
+            records_received: 0,
+            messages_sent: vec![],
+            messages_received: vec![],
+            history: vec![],
+        }
+    }
+}
+
+impl<M: Matcher> AgentCounters<M> {
+    /// Number of structured messages sent by the agent which match `matcher`.
+    pub fn messages_sent(&self, matcher: &Option<M>) -> usize {
+        Self::sum_matching(&self.messages_sent, matcher)
+    }
+
+    /// Number of structured messages received by the agent which match `matcher`.
+    pub fn messages_received(&self, matcher: &Option<M>) -> usize {
+        Self::sum_matching(&self.messages_received, matcher)
+    }
+
+    /// The structured messages sent and received by the agent, in the order of the execution.
+    pub fn history(&self) -> &[(Direction, Option<M>)] {
+        &self.history
+    }
+
+    fn sum_matching(messages: &[(Option<M>, usize)], matcher: &Option<M>) -> usize {
+        messages
+            .iter()
+            .filter(|(message_matcher, _)| message_matcher.matches(matcher))
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    /// Updates the counters with the records and messages contained in `flight`.
+    pub fn count<PB: ProtocolBehavior<Matcher = M>>(
+        &mut self,
+        flight: &dyn ExtractKnowledge<M>,
+        direction: Direction,
+    ) {
+        let source = Source::Label(String::new());
+        let mut knowledges = vec![];
+        let _ = flight.extract_knowledge(&mut knowledges, None, &source);
+
+        for knowledge in knowledges {
+            let type_id = knowledge.data.type_id();
+
+            if type_id == TypeId::of::<PB::OpaqueProtocolMessage>() {
+                match direction {
+                    Direction::Sent => self.records_sent += 1,
+                    Direction::Received => self.records_received += 1,
+                }
+            } else if type_id == TypeId::of::<PB::ProtocolMessage>() {
+                let messages = match direction {
+                    Direction::Sent => &mut self.messages_sent,
+                    Direction::Received => &mut self.messages_received,
+                };
+
+                if let Some((_, count)) = messages
+                    .iter_mut()
+                    .find(|(matcher, _)| *matcher == knowledge.matcher)
+                {
+                    *count += 1;
+                } else {
+                    messages.push((knowledge.matcher.clone(), 1));
+                }
+
+                self.history.push((direction, knowledge.matcher));
+            }
+        }
+    }
+}
+
+/// The [`AgentCounters`] of all agents of a [`TraceContext`](crate::trace::TraceContext).
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceCounters<M: Matcher> {
+    agents: Vec<(AgentName, AgentCounters<M>)>,
+}
+
+impl<M: Matcher> Default for TraceCounters<M> {
+    fn default() -> Self {
+        Self { agents: vec![] }
+    }
+}
+
+impl<M: Matcher> TraceCounters<M> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the counters of `agent_name`, if the agent sent or received anything yet.
+    pub fn get(&self, agent_name: AgentName) -> Option<&AgentCounters<M>> {
+        self.agents
+            .iter()
+            .find(|(name, _)| *name == agent_name)
+            .map(|(_, counters)| counters)
+    }
+
+    pub fn get_mut_or_default(&mut self, agent_name: AgentName) -> &mut AgentCounters<M> {
+        let index = match self.agents.iter().position(|(name, _)| *name == agent_name) {
+            Some(index) => index,
+            None => {
+                self.agents.push((agent_name, AgentCounters::default()));
+                self.agents.len() - 1
+            }
+        };
+
+        &mut self.agents[index].1
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (AgentName, &AgentCounters<M>)> {
+        self.agents.iter().map(|(name, counters)| (*name, counters))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::AnyMatcher;
+
+    #[test_log::test]
+    fn test_counters_are_per_agent() {
+        let client = AgentName::first();
+        let server = client.next();
+
+        let mut counters: TraceCounters<AnyMatcher> = TraceCounters::new();
+        counters.get_mut_or_default(client).records_sent += 2;
+        counters.get_mut_or_default(client).records_sent += 1;
+        counters.get_mut_or_default(server).records_received += 1;
+
+        assert_eq!(counters.get(client).unwrap().records_sent, 3);
+        assert_eq!(counters.get(server).unwrap().records_sent, 0);
+        assert_eq!(counters.get(server).unwrap().records_received, 1);
+        assert!(counters.get(server.next()).is_none());
+    }
+
+    #[test_log::test]
+    fn test_messages_are_summed_by_matcher() {
+        let counters = AgentCounters {
+            records_sent: 0,
+            records_received: 0,
+            messages_sent: vec![(Some(AnyMatcher), 2), (None, 1)],
+            messages_received: vec![],
+            history: vec![],
+        };
+
+        assert_eq!(counters.messages_sent(&None), 3);
+        assert_eq!(counters.messages_received(&None), 0);
+    }
+}
@@ -0,0 +1,295 @@
+//! Interactive step-by-step replay of a [`Trace`].
+//!
+//! The [`Debugger`] pauses before each [`Step`](crate::trace::Step) of a trace, or only before the
+//! steps which have a breakpoint. While the execution is paused, the knowledge, the claims and the
+//! bytes produced by the recipe of the next step can be inspected. The recipe can also be exported
+//! as JSON, edited and loaded again before continuing.
+
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+use crate::algebra::Term;
+use crate::codec::Codec;
+use crate::error::Error;
+use crate::execution::Runner;
+use crate::protocol::ProtocolBehavior;
+use crate::trace::{as_message_flight, Action, Trace, TraceContext};
+
+const HELP: &str = "\
+Commands:
+  s, step          execute the next step and pause again
+  c, continue      execute steps until the next breakpoint
+  b, break <n>     set a breakpoint before step n
+  p, print         print the next step
+  k, knowledge     print the knowledge of the attacker
+  claims           print the claims of the agents
+  e, eval          evaluate the recipe of the next step and print the bytes
+  dump <file>      write the recipe of the next step as JSON to a file
+  load <file>      replace the recipe of the next step with the JSON term in a file
+  q, quit          stop the execution";
+
+/// A command which is entered while the execution is paused.
+#[derive(Debug, PartialEq)]
+enum Command {
+    Step,
+    Continue,
+    Break(usize),
+    Print,
+    Knowledge,
+    Claims,
+    Eval,
+    Dump(PathBuf),
+    Load(PathBuf),
+    Help,
+    Quit,
+}
+
+impl Command {
+    fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("step");
+        let argument = words.next();
+
+        let command = match (command, argument) {
+            ("s" | "step", None) => Command::Step,
+            ("c" | "continue", None) => Command::Continue,
+            ("b" | "break", Some(step)) => Command::Break(
+                step.parse()
+                    .map_err(|_| format!("Invalid step number: {}", step))?,
+            ),
+            ("p" | "print", None) => Command::Print,
+            ("k" | "knowledge", None) => Command::Knowledge,
+            ("claims", None) => Command::Claims,
+            ("e" | "eval", None) => Command::Eval,
+            ("dump", Some(path)) => Command::Dump(PathBuf::from(path)),
+            ("load", Some(path)) => Command::Load(PathBuf::from(path)),
+            ("h" | "help", None) => Command::Help,
+            ("q" | "quit", None) => Command::Quit,
+            _ => return Err(format!("Unknown command: {}\n{}", line.trim(), HELP)),
+        };
+
+        Ok(command)
+    }
+}
+
+/// Replays a [`Trace`] interactively.
+pub struct Debugger<'a, PB: ProtocolBehavior> {
+    runner: &'a Runner<PB>,
+    breakpoints: BTreeSet<usize>,
+}
+
+impl<'a, PB: ProtocolBehavior> Debugger<'a, PB> {
+    pub fn new(runner: &'a Runner<PB>) -> Self {
+        Self {
+            runner,
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    /// Pauses only before the steps with the given indices. Without breakpoints, the debugger
+    /// pauses before each step.
+    pub fn with_breakpoints(mut self, breakpoints: impl IntoIterator<Item = usize>) -> Self {
+        self.breakpoints.extend(breakpoints);
+        self
+    }
+
+    /// Executes `trace` and reads commands from `input` whenever the execution is paused. Edits
+    /// of recipes are applied to `trace`.
+    pub fn run<R: BufRead, W: Write>(
+        &mut self,
+        trace: &mut Trace<PB::Matcher>,
+        input: R,
+        mut output: W,
+    ) -> Result<(), Error> {
+        let mut ctx = self.runner.new_context();
+        trace.prepare(&mut ctx)?;
+
+        let mut lines = input.lines();
+        let mut stepping = self.breakpoints.is_empty();
+
+        for index in 0..trace.steps.len() {
+            if stepping || self.breakpoints.contains(&index) {
+                let step = &trace.steps[index];
+                writeln!(output, "Step #{}: {} -> {}", index, step.agent, step.action)?;
+
+                loop {
+                    write!(output, "(debug) ")?;
+                    output.flush()?;
+
+                    let line = match lines.next() {
+                        Some(line) => line?,
+                        None => return Ok(()),
+                    };
+
+                    let command = match Command::parse(&line) {
+                        Ok(command) => command,
+                        Err(msg) => {
+                            writeln!(output, "{}", msg)?;
+                            continue;
+                        }
+                    };
+
+                    match command {
+                        Command::Step => {
+                            stepping = true;
+                            break;
+                        }
+                        Command::Continue => {
+                            stepping = false;
+                            break;
+                        }
+                        Command::Break(step) => {
+                            self.breakpoints.insert(step);
+                        }
+                        Command::Print => {
+                            let step = &trace.steps[index];
+                            writeln!(output, "{} -> {}", step.agent, step.action)?;
+                        }
+                        Command::Knowledge => writeln!(output, "{}", ctx)?,
+                        Command::Claims => {
+                            for claim in ctx.claims().deref_borrow().iter() {
+                                writeln!(output, "{:?}", claim)?;
+                            }
+                        }
+                        Command::Eval => {
+                            let msg = match &trace.steps[index].action {
+                                Action::Input(input) => evaluate(&input.recipe, &ctx),
+                                Action::Output(_)
+                                | Action::Expect(_)
+                                | Action::Reset(_)
+                                | Action::Renegotiate(_)
+                                | Action::KeyUpdate(_) => {
+                                    "The next step is not an input step".to_string()
+                                }
+                            };
+                            writeln!(output, "{}", msg)?;
+                        }
+                        Command::Dump(path) => {
+                            let msg = match &trace.steps[index].action {
+                                Action::Input(input) => serde_json::to_string_pretty(&input.recipe)
+                                    .map_err(|err| err.to_string())
+                                    .and_then(|json| {
+                                        fs::write(&path, json).map_err(|err| err.to_string())
+                                    })
+                                    .map_or_else(
+                                        |err| format!("Failed to write recipe: {}", err),
+                                        |_| format!("Wrote recipe to {}", path.display()),
+                                    ),
+                                Action::Output(_)
+                                | Action::Expect(_)
+                                | Action::Reset(_)
+                                | Action::Renegotiate(_)
+                                | Action::KeyUpdate(_) => {
+                                    "The next step is not an input step".to_string()
+                                }
+                            };
+                            writeln!(output, "{}", msg)?;
+                        }
+                        Command::Load(path) => {
+                            let msg = match &mut trace.steps[index].action {
+                                Action::Input(input) => match fs::read_to_string(&path)
+                                    .map_err(|err| err.to_string())
+                                    .and_then(|json| {
+                                        serde_json::from_str::<Term<PB::Matcher>>(&json)
+                                            .map_err(|err| err.to_string())
+                                    }) {
+                                    Ok(recipe) => {
+                                        input.recipe = recipe;
+                                        format!("Replaced recipe:\n{}", input.recipe)
+                                    }
+                                    Err(err) => format!("Failed to load recipe: {}", err),
+                                },
+                                Action::Output(_)
+                                | Action::Expect(_)
+                                | Action::Reset(_)
+                                | Action::Renegotiate(_)
+                                | Action::KeyUpdate(_) => {
+                                    "The next step is not an input step".to_string()
+                                }
+                            };
+                            writeln!(output, "{}", msg)?;
+                        }
+                        Command::Help => writeln!(output, "{}", HELP)?,
+                        Command::Quit => return Ok(()),
+                    }
+                }
+            }
+
+            ctx.claims().deref_borrow_mut().set_step(Some(index));
+            if let Err(err) = trace.steps[index]
+                .execute(&mut ctx)
+                .and_then(|_| ctx.verify_security_violations())
+            {
+                writeln!(output, "Step #{} failed: {}", index, err)?;
+                return Ok(());
+            }
+        }
+
+        writeln!(output, "Trace finished")?;
+        Ok(())
+    }
+}
+
+fn evaluate<PB: ProtocolBehavior>(recipe: &Term<PB::Matcher>, ctx: &TraceContext<PB>) -> String {
+    match recipe
+        .evaluate(ctx)
+        .and_then(|evaluated| as_message_flight::<PB>(evaluated))
+    {
+        Ok(flight) => {
+            let mut bytes = vec![];
+            flight.encode(&mut bytes);
+            hexdump(&bytes)
+        }
+        Err(err) => format!("Failed to evaluate recipe: {}", err),
+    }
+}
+
+fn hexdump(bytes: &[u8]) -> String {
+    let mut dump = String::new();
+
+    for (line, chunk) in bytes.chunks(16).enumerate() {
+        let _ = write!(dump, "{:08x} ", line * 16);
+        for byte in chunk {
+            let _ = write!(dump, " {:02x}", byte);
+        }
+        dump.push('\n');
+    }
+
+    let _ = write!(dump, "({} bytes)", bytes.len());
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{hexdump, Command};
+
+    #[test_log::test]
+    fn test_parse_commands() {
+        assert_eq!(Command::parse(""), Ok(Command::Step));
+        assert_eq!(Command::parse("c"), Ok(Command::Continue));
+        assert_eq!(Command::parse("break 3"), Ok(Command::Break(3)));
+        assert_eq!(
+            Command::parse("load recipe.json"),
+            Ok(Command::Load(PathBuf::from("recipe.json")))
+        );
+        assert!(Command::parse("break x").is_err());
+        assert!(Command::parse("dump").is_err());
+    }
+
+    #[test_log::test]
+    fn test_hexdump() {
+        let bytes: Vec<u8> = (0..18).collect();
+
+        assert_eq!(
+            hexdump(&bytes),
+            "00000000  00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f\n\
+             00000010  10 11\n\
+             (18 bytes)"
+        );
+    }
+}
@@ -1,17 +1,24 @@
+use std::str::FromStr;
 use std::{fmt, io};
 
-use crate::algebra::error::FnError;
+use itertools::Itertools;
+
+use crate::algebra::dynamic_function::TypeShape;
+use crate::algebra::error::{FnError, FnErrorCategory};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Error {
-    /// Returned if a concrete function from the protocol fails or term evaluation fails
+    /// Returned if a concrete function from the protocol fails
     Fn(FnError),
-    Term(String),
+    /// Returned if the evaluation of a term fails
+    Term(TermError),
     /// PUT reported an error
     Put(String),
     /// There was an unexpected IO error. Should never happen because we are not fuzzing on a
     /// network which can fail.
-    IO(String),
+    IO(IoError),
+    /// A message could not be encoded or decoded
+    Codec(String),
     /// Some error which was caused because of agents or their names. Like an agent which was not
     /// found.
     Agent(String),
@@ -19,6 +26,130 @@ pub enum Error {
     Stream(String),
     Extraction(),
     SecurityClaim(&'static str),
+    /// A PUT which runs in another process was terminated by a signal
+    PutCrash(String),
+}
+
+/// Errors which occur during the evaluation of a [`Term`](crate::algebra::Term).
+#[derive(Debug, Clone, PartialEq)]
+pub enum TermError {
+    /// No knowledge matches the query of a variable
+    VariableNotFound { typ: TypeShape, query: String },
+    /// The function symbol of an application failed
+    Fn {
+        symbol: &'static str,
+        argument_types: Vec<TypeShape>,
+        error: FnError,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IoError {
+    pub kind: io::ErrorKind,
+    pub message: String,
+}
+
+/// Classes of [`Error`]s, which allow to count or filter errors without matching on their details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorClass {
+    Fn,
+    Term,
+    Put,
+    IO,
+    Codec,
+    Agent,
+    Stream,
+    Extraction,
+    SecurityClaim,
+    PutCrash,
+}
+
+impl ErrorClass {
+    pub const ALL: [ErrorClass; 10] = [
+        ErrorClass::Fn,
+        ErrorClass::Term,
+        ErrorClass::Put,
+        ErrorClass::IO,
+        ErrorClass::Codec,
+        ErrorClass::Agent,
+        ErrorClass::Stream,
+        ErrorClass::Extraction,
+        ErrorClass::SecurityClaim,
+        ErrorClass::PutCrash,
+    ];
+
+    /// Stable code of the class, e.g. for command line options
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorClass::Fn => "fn",
+            ErrorClass::Term => "term",
+            ErrorClass::Put => "put",
+            ErrorClass::IO => "io",
+            ErrorClass::Codec => "codec",
+            ErrorClass::Agent => "agent",
+            ErrorClass::Stream => "stream",
+            ErrorClass::Extraction => "extraction",
+            ErrorClass::SecurityClaim => "security-claim",
+            ErrorClass::PutCrash => "put-crash",
+        }
+    }
+}
+
+impl fmt::Display for ErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+impl FromStr for ErrorClass {
+    type Err = String;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        ErrorClass::ALL
+            .into_iter()
+            .find(|class| class.code() == code)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown error class {}, expected one of {}",
+                    code,
+                    ErrorClass::ALL.iter().join(", ")
+                )
+            })
+    }
+}
+
+impl Error {
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            Error::Fn(_) | Error::Term(TermError::Fn { .. }) => ErrorClass::Fn,
+            Error::Term(TermError::VariableNotFound { .. }) => ErrorClass::Term,
+            Error::Put(_) => ErrorClass::Put,
+            Error::IO(_) => ErrorClass::IO,
+            Error::Codec(_) => ErrorClass::Codec,
+            Error::Agent(_) => ErrorClass::Agent,
+            Error::Stream(_) => ErrorClass::Stream,
+            Error::Extraction() => ErrorClass::Extraction,
+            Error::SecurityClaim(_) => ErrorClass::SecurityClaim,
+            Error::PutCrash(_) => ErrorClass::PutCrash,
+        }
+    }
+
+    /// Whether the error is reported like a crash of the PUT. This is the case for violated
+    /// security claims and for PUTs in other processes which crashed.
+    pub fn is_crash(&self) -> bool {
+        matches!(self, Error::SecurityClaim(_) | Error::PutCrash(_))
+    }
+
+    /// Whether the error indicates a bug in a function symbol instead of a nonsensical term.
+    /// Such errors are reported as objectives.
+    pub fn is_bug(&self) -> bool {
+        match self {
+            Error::Fn(error) | Error::Term(TermError::Fn { error, .. }) => {
+                error.category() == FnErrorCategory::Bug
+            }
+            _ => false,
+        }
+    }
 }
 
 impl std::error::Error for Error {}
@@ -34,6 +165,7 @@ impl fmt::Display for Error {
                 "error in io of openssl (this should not happen): {}",
                 err
             ),
+            Error::Codec(err) => write!(f, "error in the encoding of a message: {}", err),
             Error::Agent(err) => write!(f, "error regarding an agent: {}", err),
             Error::Stream(err) => write!(f, "error in the stream: {}", err),
             Error::Extraction() => write!(f, "error while extracting variable",),
@@ -42,18 +174,114 @@ impl fmt::Display for Error {
                 "error because a security violation occurred. msg: {}",
                 msg
             ),
+            Error::PutCrash(msg) => write!(f, "the PUT crashed: {}", msg),
+        }
+    }
+}
+
+impl fmt::Display for TermError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TermError::VariableNotFound { typ, query } => {
+                write!(f, "Unable to find variable {} of type {}!", query, typ)
+            }
+            TermError::Fn {
+                symbol,
+                argument_types,
+                error,
+            } => write!(
+                f,
+                "{}({}) failed: {}",
+                symbol,
+                argument_types.iter().join(","),
+                error
+            ),
         }
     }
 }
 
+impl fmt::Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({:?})", self.message, self.kind)
+    }
+}
+
 impl From<FnError> for Error {
     fn from(err: FnError) -> Self {
         Error::Fn(err)
     }
 }
 
+impl From<TermError> for Error {
+    fn from(err: TermError) -> Self {
+        Error::Term(err)
+    }
+}
+
+impl From<&io::Error> for IoError {
+    fn from(err: &io::Error) -> Self {
+        IoError {
+            kind: err.kind(),
+            message: err.to_string(),
+        }
+    }
+}
+
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Self {
-        Error::IO(err.to_string())
+        Error::IO(IoError::from(&err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+    use std::io;
+
+    use super::{Error, ErrorClass, IoError, TermError};
+    use crate::algebra::dynamic_function::{make_dynamic, TypeShape};
+    use crate::algebra::error::{FnError, FnErrorCategory};
+
+    fn fn_seq_1_plus(seq: &u32) -> Result<u32, FnError> {
+        Ok(seq + 1)
+    }
+
+    #[test_log::test]
+    fn test_error_classes() {
+        let fn_error = Error::Term(TermError::Fn {
+            symbol: "fn_seq_1",
+            argument_types: vec![TypeShape::of::<u32>()],
+            error: FnError::Crypto("bad key".to_string()),
+        });
+        assert_eq!(fn_error.class(), ErrorClass::Fn);
+        assert!(!fn_error.is_bug());
+        assert!(Error::Fn(FnError::Bug("type mismatch".to_string())).is_bug());
+
+        // Arguments of the wrong type can not be passed by well-typed terms
+        let (_, dynamic_fn) = make_dynamic(&fn_seq_1_plus);
+        let args: Vec<Box<dyn Any>> = vec![Box::new(1u8)];
+        assert_eq!(
+            dynamic_fn(&args).unwrap_err().category(),
+            FnErrorCategory::Bug
+        );
+        assert_eq!(
+            fn_error.to_string(),
+            "error evaluating a term: fn_seq_1(u32) failed: error in fn from rustls: bad key"
+        );
+
+        let io_error = Error::from(io::Error::new(io::ErrorKind::BrokenPipe, "closed"));
+        assert_eq!(io_error.class(), ErrorClass::IO);
+        assert!(matches!(
+            io_error,
+            Error::IO(IoError {
+                kind: io::ErrorKind::BrokenPipe,
+                ..
+            })
+        ));
+
+        for class in ErrorClass::ALL {
+            assert_eq!(class.code().parse::<ErrorClass>(), Ok(class));
+        }
+        assert!("unknown".parse::<ErrorClass>().is_err());
     }
 }
@@ -1,8 +1,11 @@
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
 use std::sync::mpsc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use nix::errno::Errno;
-use nix::sys::signal::{kill, Signal};
+use nix::sys::signal::{kill, signal, SigHandler, Signal};
 use nix::sys::wait::WaitStatus::{self, Exited, Signaled};
 use nix::sys::wait::{waitpid, WaitPidFlag};
 use nix::unistd::{fork, ForkResult, Pid};
@@ -10,7 +13,7 @@ use nix::unistd::{fork, ForkResult, Pid};
 use crate::error::Error;
 use crate::protocol::ProtocolBehavior;
 use crate::put_registry::PutRegistry;
-use crate::trace::{Spawner, Trace, TraceContext};
+use crate::trace::{KnowledgeRetention, Spawner, Step, Trace, TraceContext, TraceHook, TraceHooks};
 
 pub trait TraceRunner {
     type PB: ProtocolBehavior;
@@ -26,6 +29,8 @@ pub trait TraceRunner {
 pub struct Runner<PB: ProtocolBehavior> {
     registry: PutRegistry<PB>,
     spawner: Spawner<PB>,
+    retention: KnowledgeRetention,
+    hooks: TraceHooks<PB>,
 }
 
 impl<PB: ProtocolBehavior> Runner<PB> {
@@ -33,8 +38,134 @@ impl<PB: ProtocolBehavior> Runner<PB> {
         Self {
             registry: registry.into(),
             spawner: spawner.into(),
+            retention: KnowledgeRetention::default(),
+            hooks: TraceHooks::default(),
         }
     }
+
+    /// Bounds the knowledge of the contexts which this runner creates, see [`KnowledgeRetention`].
+    pub fn with_knowledge_retention(mut self, retention: KnowledgeRetention) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Registers `hook` in all contexts which this runner creates, see [`TraceHook`].
+    pub fn with_trace_hook(mut self, hook: impl TraceHook<PB> + 'static) -> Self {
+        self.hooks.push(Rc::new(hook));
+        self
+    }
+
+    /// Creates a [`TraceContext`] for executing a new trace.
+    pub fn new_context(&self) -> TraceContext<PB> {
+        // We reseed all PUTs before executing a trace!
+        self.registry.determinism_reseed_all_factories();
+
+        let mut ctx = TraceContext::new(self.spawner.clone());
+        ctx.knowledge_store.set_retention(self.retention);
+        ctx.set_hooks(self.hooks.clone());
+        ctx
+    }
+
+    /// Executes `trace` and returns the [`TraceContext`] together with the result of the
+    /// execution. In contrast to [`TraceRunner::execute`], the context is also returned if the
+    /// execution failed, e.g. because of a security violation.
+    pub fn execute_with_context(
+        &self,
+        trace: &Trace<PB::Matcher>,
+    ) -> (TraceContext<PB>, Result<(), Error>) {
+        let mut ctx = self.new_context();
+        let result = trace.execute(&mut ctx);
+        (ctx, result)
+    }
+
+    /// Executes `prefix` once, and then each of the `suffixes` in a forked child process which
+    /// continues from the state of the PUTs, the knowledge and the claims after the prefix. This
+    /// amortizes a common prefix, like a handshake, over many continuations.
+    ///
+    /// The prefix is executed in the calling process. Returns an error if the prefix fails,
+    /// otherwise the status of each suffix in the same order.
+    pub fn execute_suffixes(
+        &self,
+        prefix: &Trace<PB::Matcher>,
+        suffixes: &[Vec<Step<PB::Matcher>>],
+        timeout: Option<Duration>,
+    ) -> Result<Vec<ExecutionStatus>, Error> {
+        let mut prefix = self.execute_prefix(prefix)?;
+
+        suffixes
+            .iter()
+            .map(|suffix| {
+                prefix.execute_suffix(suffix, timeout, |_ctx, result| {
+                    let ret = match result {
+                        Ok(_) => 0,
+                        Err(_) => 1,
+                    };
+
+                    unsafe { libc::_exit(ret) }
+                })
+            })
+            .collect()
+    }
+
+    /// Executes `prefix` in the calling process and returns the state after it, from which
+    /// suffixes are executed like by [`Runner::execute_suffixes`].
+    pub fn execute_prefix(&self, prefix: &Trace<PB::Matcher>) -> Result<PrefixContext<PB>, Error> {
+        let mut ctx = self.new_context();
+
+        // The prefix does not know which knowledge the suffixes refer to
+        let retention = ctx.knowledge_store.retention();
+        ctx.knowledge_store
+            .set_retention(KnowledgeRetention::default());
+        prefix.execute(&mut ctx)?;
+        ctx.knowledge_store.set_retention(retention);
+
+        Ok(PrefixContext {
+            prefix: Trace {
+                descriptors: prefix.descriptors.clone(),
+                prior_traces: vec![],
+                steps: prefix.steps.clone(),
+            },
+            ctx: Some(ctx),
+        })
+    }
+}
+
+/// The state of the PUTs, the knowledge and the claims after the execution of a prefix, see
+/// [`Runner::execute_prefix`].
+pub struct PrefixContext<PB: ProtocolBehavior> {
+    prefix: Trace<PB::Matcher>,
+    ctx: Option<TraceContext<PB>>,
+}
+
+impl<PB: ProtocolBehavior> PrefixContext<PB> {
+    /// Executes `suffix` in a forked child which continues from the state after the prefix.
+    /// `child` receives the context and the result of the execution in the child, and is expected
+    /// to exit the process. Returns the status of the child.
+    pub fn execute_suffix<F>(
+        &mut self,
+        suffix: &[Step<PB::Matcher>],
+        timeout: Option<Duration>,
+        child: F,
+    ) -> Result<ExecutionStatus, Error>
+    where
+        F: FnOnce(TraceContext<PB>, Result<(), Error>),
+    {
+        let trace = Trace {
+            steps: self.prefix.steps.iter().chain(suffix).cloned().collect(),
+            ..self.prefix.clone()
+        };
+        let start = self.prefix.steps.len();
+
+        run_in_subprocess(
+            || {
+                let mut ctx = self.ctx.take().expect("the context of the prefix is kept");
+                let result = trace.execute_from(&mut ctx, start);
+                child(ctx, result);
+            },
+            timeout,
+        )
+        .map_err(|err| Error::Put(err.to_string()))
+    }
 }
 
 impl<PB: ProtocolBehavior> TraceRunner for &Runner<PB> {
@@ -46,12 +177,142 @@ impl<PB: ProtocolBehavior> TraceRunner for &Runner<PB> {
     where
         T: AsRef<Trace<<Self::PB as ProtocolBehavior>::Matcher>>,
     {
-        // We reseed all PUTs before executing a trace!
-        self.registry.determinism_reseed_all_factories();
+        let (ctx, result) = self.execute_with_context(trace.as_ref());
+        result.map(|_| ctx)
+    }
+}
 
-        let mut ctx = TraceContext::new(self.spawner.clone());
-        trace.as_ref().execute(&mut ctx)?;
-        Ok(ctx)
+/// Exit code of a child of the [`ParallelRunner`] whose trace violated a security claim
+pub const SECURITY_VIOLATION_EXIT_CODE: i32 = 2;
+
+/// Executes batches of traces in parallel forked processes.
+///
+/// Each trace is executed in its own child process, like by the [`ForkedRunner`], and therefore
+/// with separate PUT instances, knowledge and claims. At most `workers` children run at the same
+/// time. A child exits with 0 if the execution succeeded, with [`SECURITY_VIOLATION_EXIT_CODE`]
+/// if a security claim was violated and with 1 if the execution failed otherwise. A crash or panic
+/// of the PUT only ends the child which executed the trace.
+///
+/// The fuzzer uses it to execute a batch of mutants at the same time, see
+/// [`ParallelHarness`](crate::fuzzer::harness::ParallelHarness).
+#[derive(Debug, Clone)]
+pub struct ParallelRunner<PB: ProtocolBehavior> {
+    runner: Runner<PB>,
+    workers: usize,
+    timeout: Option<Duration>,
+}
+
+impl<PB: ProtocolBehavior> ParallelRunner<PB> {
+    pub fn new(runner: Runner<PB>, workers: usize) -> Self {
+        Self {
+            runner,
+            workers: workers.max(1),
+            timeout: None,
+        }
+    }
+
+    /// Children which run longer are killed and reported as [`ExecutionStatus::Timeout`].
+    pub fn with_timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.timeout = timeout.into();
+        self
+    }
+
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    /// Executes `traces` and returns the status of each execution in the same order.
+    pub fn execute_all(
+        &self,
+        traces: &[Trace<PB::Matcher>],
+    ) -> Vec<Result<ExecutionStatus, ForkError>> {
+        self.execute_all_with(traces, |_, runner, trace| {
+            Self::exit_code(runner.execute_with_context(trace).1)
+        })
+    }
+
+    /// Executes `traces` like [`ParallelRunner::execute_all`], but each child calls `child` with
+    /// the index of its trace, the runner and the trace. The child exits with the returned code.
+    pub fn execute_all_with<F>(
+        &self,
+        traces: &[Trace<PB::Matcher>],
+        mut child: F,
+    ) -> Vec<Result<ExecutionStatus, ForkError>>
+    where
+        F: FnMut(usize, &Runner<PB>, &Trace<PB::Matcher>) -> i32,
+    {
+        let mut results: Vec<Option<Result<ExecutionStatus, ForkError>>> = vec![None; traces.len()];
+        let mut running: HashMap<Pid, (usize, Instant)> = HashMap::new();
+        let mut next = 0;
+
+        while next < traces.len() || !running.is_empty() {
+            if next < traces.len() && running.len() < self.workers {
+                match unsafe { fork() } {
+                    Ok(ForkResult::Parent { child }) => {
+                        running.insert(child, (next, Instant::now()));
+                    }
+                    Ok(ForkResult::Child) => {
+                        reset_crash_handlers();
+                        // A panic is reported like a crash of the PUT
+                        let code = panic::catch_unwind(AssertUnwindSafe(|| {
+                            child(next, &self.runner, &traces[next])
+                        }))
+                        .unwrap_or_else(|_| std::process::abort());
+                        unsafe { libc::_exit(code) }
+                    }
+                    Err(errno) => results[next] = Some(Err(errno.into())),
+                }
+                next += 1;
+                continue;
+            }
+
+            // Only the own children are collected, other children of the process are not touched
+            let mut finished = vec![];
+            for (pid, (_, started)) in &running {
+                match waitpid(*pid, Some(WaitPidFlag::WNOHANG)) {
+                    Ok(WaitStatus::StillAlive)
+                        if self
+                            .timeout
+                            .is_some_and(|timeout| started.elapsed() > timeout) =>
+                    {
+                        let _ = kill(*pid, Signal::SIGKILL);
+                        let _ = waitpid(*pid, None);
+                        finished.push((*pid, Ok(ExecutionStatus::Timeout)));
+                    }
+                    Ok(WaitStatus::StillAlive) => {}
+                    status => finished.push((*pid, ExecutionStatus::try_from(status))),
+                }
+            }
+
+            if finished.is_empty() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            for (pid, status) in finished {
+                if let Some((index, _)) = running.remove(&pid) {
+                    results[index] = Some(status);
+                }
+            }
+        }
+
+        results.into_iter().flatten().collect()
+    }
+
+    fn exit_code(result: Result<(), Error>) -> i32 {
+        match result {
+            Ok(()) => 0,
+            Err(Error::SecurityClaim(message)) => {
+                log::error!("Security claim violated: {}", message);
+                SECURITY_VIOLATION_EXIT_CODE
+            }
+            Err(Error::PutCrash(message)) => {
+                log::error!("PUT crashed: {}", message);
+                std::process::abort()
+            }
+            Err(err) => {
+                log::info!("Execution failed: {}", err);
+                1
+            }
+        }
     }
 }
 
@@ -102,7 +363,7 @@ impl<T: TraceRunner + Clone> TraceRunner for &ForkedRunner<T> {
                     Err(_) => 1,
                 };
 
-                std::process::exit(ret);
+                unsafe { libc::_exit(ret) }
             },
             self.timeout,
         )
@@ -120,6 +381,8 @@ impl std::fmt::Display for ForkError {
     }
 }
 
+impl std::error::Error for ForkError {}
+
 impl From<Errno> for ForkError {
     fn from(e: Errno) -> Self {
         Self {
@@ -128,6 +391,38 @@ impl From<Errno> for ForkError {
     }
 }
 
+/// Signals for which the in-process executor of LibAFL installs its crash handler
+const LIBAFL_SIGNALS: [Signal; 8] = [
+    Signal::SIGALRM,
+    Signal::SIGUSR2,
+    Signal::SIGABRT,
+    Signal::SIGBUS,
+    Signal::SIGFPE,
+    Signal::SIGILL,
+    Signal::SIGSEGV,
+    Signal::SIGTRAP,
+];
+
+/// Restores the default handlers of the crash signals and the default panic hook in a forked
+/// child.
+///
+/// The child inherits the handlers which the executor of the fuzzer client installed, together
+/// with their data. If the PUT crashed in the child, they would fire an objective over the event
+/// manager of the client and overwrite its restart state, while the parent reports the crash of
+/// the child as well. With the default handlers, the child is just terminated by the signal.
+pub(crate) fn reset_crash_handlers() {
+    for crash_signal in LIBAFL_SIGNALS {
+        unsafe {
+            let _ = signal(crash_signal, SigHandler::SigDfl);
+        }
+    }
+    // Like at the start of every Rust program, a closed connection is reported as an error
+    unsafe {
+        let _ = signal(Signal::SIGPIPE, SigHandler::SigIgn);
+    }
+    let _ = panic::take_hook();
+}
+
 pub fn run_in_subprocess<R>(
     func: R,
     timeout: impl Into<Option<Duration>>,
@@ -142,8 +437,12 @@ where
         match unsafe { fork() }? {
             ForkResult::Parent { child, .. } => Ok(child),
             ForkResult::Child => {
-                f();
-                std::process::exit(0);
+                reset_crash_handlers();
+                // A panic is reported like a crash of the PUT
+                if panic::catch_unwind(AssertUnwindSafe(f)).is_err() {
+                    std::process::abort();
+                }
+                unsafe { libc::_exit(0) }
             }
         }
     }
@@ -163,9 +462,8 @@ where
             })
     }
 
-    let executor_pid = do_fork(func)?;
-
-    let mut watchdog = WatchDog::new();
+    // NOTE The handlers are registered before forking, else the SIGCHLD of a child which exits
+    // immediately is missed
     let mut signals = signal_hook::iterator::SignalsInfo::<
         signal_hook::iterator::exfiltrator::WithOrigin,
     >::new([signal_hook::consts::SIGUSR1, signal_hook::consts::SIGCHLD])
@@ -173,6 +471,10 @@ where
         reason: format!("failed to register signal handlers: {e}"),
     })?;
 
+    let executor_pid = do_fork(func)?;
+
+    let mut watchdog = WatchDog::new();
+
     watchdog.start(timeout.into());
 
     let mut result = ExecutionStatus::Timeout;
@@ -216,7 +518,11 @@ impl WatchDog {
         self.channel = Some(send);
 
         std::thread::spawn(move || {
-            std::thread::sleep(duration);
+            // The watchdog is dropped once the child has been collected
+            if let Err(mpsc::RecvTimeoutError::Disconnected) = recv.recv_timeout(duration) {
+                return;
+            }
+
             loop {
                 kill(nix::unistd::Pid::this(), Signal::SIGUSR1).unwrap();
                 if recv.recv_timeout(Duration::from_millis(200)).is_err() {
@@ -258,3 +564,186 @@ impl TryFrom<Result<WaitStatus, Errno>> for ExecutionStatus {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use nix::sys::signal::{signal, SigHandler, Signal};
+
+    use super::{run_in_subprocess, ExecutionStatus, ParallelRunner, Runner};
+    use crate::agent::{AgentDescriptor, AgentName, TLSVersion};
+    use crate::algebra::test_signature::*;
+    use crate::algebra::AnyMatcher;
+    use crate::put_registry::PutRegistry;
+    use crate::trace::{OutputAction, Spawner, Step, Trace, TraceHook};
+
+    #[test_log::test]
+    fn test_parallel_runner_keeps_order_and_isolates_crashes() {
+        let registry = PutRegistry::<TestProtocolBehavior>::new(
+            [("TESTSTUB_RUST_PUT", Box::new(TestFactory) as _)],
+            "TESTSTUB_RUST_PUT",
+        )
+        .unwrap();
+        let runner = ParallelRunner::new(Runner::new(registry.clone(), Spawner::new(registry)), 3);
+
+        let empty: TestTrace = Trace {
+            descriptors: vec![],
+            prior_traces: vec![],
+            steps: vec![],
+        };
+        // The test factory panics when an agent is spawned
+        let spawning = Trace {
+            descriptors: vec![AgentDescriptor::new_server(
+                AgentName::first(),
+                TLSVersion::V1_3,
+            )],
+            ..empty.clone()
+        };
+
+        // There is no agent which could produce an output
+        let failing = Trace {
+            steps: vec![OutputAction::new_step(AgentName::first())],
+            ..empty.clone()
+        };
+
+        let traces = vec![empty.clone(), spawning, empty.clone(), failing, empty];
+        let statuses: Vec<_> = runner
+            .execute_all(&traces)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(
+            statuses,
+            vec![
+                ExecutionStatus::Success,
+                ExecutionStatus::Crashed,
+                ExecutionStatus::Success,
+                ExecutionStatus::Failure(1),
+                ExecutionStatus::Success
+            ]
+        );
+    }
+
+    #[test_log::test]
+    fn test_parallel_runner_kills_children_after_timeout() {
+        let registry = PutRegistry::<TestProtocolBehavior>::new(
+            [("TESTSTUB_RUST_PUT", Box::new(TestFactory) as _)],
+            "TESTSTUB_RUST_PUT",
+        )
+        .unwrap();
+        let runner = ParallelRunner::new(Runner::new(registry.clone(), Spawner::new(registry)), 2)
+            .with_timeout(Duration::from_secs(1));
+
+        let empty: TestTrace = Trace {
+            descriptors: vec![],
+            prior_traces: vec![],
+            steps: vec![],
+        };
+        let statuses: Vec<_> = runner
+            .execute_all_with(&[empty.clone(), empty], |index, _runner, _trace| {
+                if index == 1 {
+                    std::thread::sleep(Duration::from_secs(30));
+                }
+                3
+            })
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(
+            statuses,
+            vec![ExecutionStatus::Failure(3), ExecutionStatus::Timeout]
+        );
+    }
+
+    #[test_log::test]
+    fn test_subprocess_crashes_with_default_handlers() {
+        // Stands in for the crash handler of LibAFL, which would handle the crash in the child
+        extern "C" fn exit_successfully(_: libc::c_int) {
+            unsafe { libc::_exit(0) }
+        }
+
+        unsafe {
+            signal(Signal::SIGSEGV, SigHandler::Handler(exit_successfully)).unwrap();
+        }
+        let status = run_in_subprocess(
+            || unsafe {
+                libc::raise(libc::SIGSEGV);
+            },
+            None,
+        );
+        unsafe {
+            signal(Signal::SIGSEGV, SigHandler::SigDfl).unwrap();
+        }
+
+        assert_eq!(status.unwrap(), ExecutionStatus::Crashed);
+    }
+
+    #[test_log::test]
+    fn test_trace_hooks_see_step_starts() {
+        struct CountSteps(Arc<AtomicUsize>);
+
+        impl TraceHook<TestProtocolBehavior> for CountSteps {
+            fn on_step_start(&self, index: usize, _step: &Step<AnyMatcher>) {
+                assert_eq!(index, self.0.fetch_add(1, Ordering::SeqCst));
+            }
+        }
+
+        let registry = PutRegistry::<TestProtocolBehavior>::new(
+            [("TESTSTUB_RUST_PUT", Box::new(TestFactory) as _)],
+            "TESTSTUB_RUST_PUT",
+        )
+        .unwrap();
+        let steps = Arc::new(AtomicUsize::new(0));
+        let runner = Runner::new(registry.clone(), Spawner::new(registry))
+            .with_trace_hook(CountSteps(steps.clone()));
+
+        // There is no agent which could produce an output, hence the second step is not reached
+        let trace: TestTrace = Trace {
+            descriptors: vec![],
+            prior_traces: vec![],
+            steps: vec![
+                OutputAction::new_step(AgentName::first()),
+                OutputAction::new_step(AgentName::first()),
+            ],
+        };
+
+        assert!(runner.execute_with_context(&trace).1.is_err());
+        assert_eq!(steps.load(Ordering::SeqCst), 1);
+    }
+
+    #[test_log::test]
+    fn test_execute_suffixes_after_prefix() {
+        let registry = PutRegistry::<TestProtocolBehavior>::new(
+            [("TESTSTUB_RUST_PUT", Box::new(TestFactory) as _)],
+            "TESTSTUB_RUST_PUT",
+        )
+        .unwrap();
+        let runner = Runner::new(registry.clone(), Spawner::new(registry));
+
+        let prefix: TestTrace = Trace {
+            descriptors: vec![],
+            prior_traces: vec![],
+            steps: vec![],
+        };
+        // There is no agent which could produce an output
+        let failing = vec![OutputAction::new_step(AgentName::first())];
+
+        let statuses = runner
+            .execute_suffixes(&prefix, &[vec![], failing, vec![]], None)
+            .unwrap();
+
+        assert_eq!(
+            statuses,
+            vec![
+                ExecutionStatus::Success,
+                ExecutionStatus::Failure(1),
+                ExecutionStatus::Success
+            ]
+        );
+    }
+}
@@ -0,0 +1,298 @@
+//! Reproducible configuration of a fuzzing campaign.
+//!
+//! A [`CampaignConfig`] is loaded from a TOML file with `--campaign` and applied to the
+//! [`FuzzerConfig`]. Options which are passed explicitly on the command line take precedence.
+//! The resolved configuration is written to `campaign.toml` in the experiment directory and
+//! attached to every stored testcase by the [`CampaignFeedback`], such that a campaign and its
+//! findings can be reproduced by passing the file again:
+//!
+//! ```toml
+//! put = "openssl111k"
+//! seed = 42
+//! rng_seed = 1234
+//! seeds = "./seeds"
+//! corpus_dir = "./corpus"
+//! objective_dir = "./objective"
+//! timeout_ms = 5000
+//! hang_deadline_ms = 30000
+//! max_trace_length = 15
+//! max_term_size = 300
+//!
+//! [put_options]
+//! cipher_list = "AES128-SHA"
+//!
+//! [agent_puts]
+//! 1 = "openssl312"
+//!
+//! [mutation_weights]
+//! swap = 2
+//! generate = 0
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use libafl::prelude::*;
+use libafl_bolts::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::libafl_setup::FuzzerConfig;
+use super::mutations::MutationWeights;
+use crate::agent::AgentName;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CampaignConfig {
+    /// Name of the PUT factory which all agents use
+    pub put: Option<String>,
+    /// Options which are passed to the PUT, like `--put-option`
+    pub put_options: BTreeMap<String, String>,
+    /// PUT factories of the agents which do not use `put`, by the number of the agent, like
+    /// `--agent-put`
+    pub agent_puts: BTreeMap<String, String>,
+    /// Seed of the random number generator of the fuzzer clients
+    pub seed: Option<u64>,
+    /// Master seed of all random number generators, see [`rng`](crate::rng)
+    pub rng_seed: Option<u64>,
+    /// Directory of the initial corpus
+    pub seeds: Option<PathBuf>,
+    pub corpus_dir: Option<PathBuf>,
+    pub objective_dir: Option<PathBuf>,
+    /// Weights of the mutations, see [`MutationWeights`]. Unlisted mutations have a weight of 1.
+    pub mutation_weights: BTreeMap<String, u32>,
+    pub timeout_ms: Option<u64>,
+    pub hang_deadline_ms: Option<u64>,
+    /// Limits of the mutations, see [`MutationConfig`](super::libafl_setup::MutationConfig)
+    pub fresh_zoo_after: Option<u64>,
+    pub min_trace_length: Option<usize>,
+    pub max_trace_length: Option<usize>,
+    pub max_trace_size: Option<usize>,
+    pub min_term_size: Option<usize>,
+    pub max_term_size: Option<usize>,
+}
+
+libafl_bolts::impl_serdeany!(CampaignConfig);
+
+impl CampaignConfig {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+        toml::from_str(&content).map_err(|err| format!("invalid {}: {}", path.display(), err))
+    }
+
+    /// Describes the campaign which runs with `config`.
+    pub fn resolved(config: &FuzzerConfig) -> Self {
+        Self {
+            put: Some(config.put.factory.clone()),
+            put_options: config
+                .put
+                .options
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            agent_puts: config
+                .agent_puts
+                .iter()
+                .map(|(agent, put)| (agent.to_string(), put.clone()))
+                .collect(),
+            seed: config.static_seed,
+            rng_seed: config.rng_seed,
+            seeds: Some(config.initial_corpus_dir.clone()),
+            corpus_dir: Some(config.corpus_dir.clone()),
+            objective_dir: Some(config.objective_dir.clone()),
+            mutation_weights: MutationWeights::NAMES
+                .iter()
+                .zip(config.mutation_config.weights.as_slice())
+                .map(|(name, weight)| (name.to_string(), *weight))
+                .collect(),
+            timeout_ms: Some(config.execution_timeout.as_millis() as u64),
+            hang_deadline_ms: Some(config.hang_deadline.as_millis() as u64),
+            fresh_zoo_after: Some(config.mutation_config.fresh_zoo_after),
+            min_trace_length: Some(config.mutation_config.min_trace_length),
+            max_trace_length: Some(config.mutation_config.max_trace_length),
+            max_trace_size: Some(config.mutation_config.max_trace_size),
+            min_term_size: Some(config.mutation_config.term_constraints.min_term_size),
+            max_term_size: Some(config.mutation_config.term_constraints.max_term_size),
+        }
+    }
+
+    /// Overwrites the fields of `config` which are set in the campaign.
+    pub fn apply(&self, config: &mut FuzzerConfig) -> Result<(), String> {
+        if let Some(put) = &self.put {
+            config.put.factory = put.clone();
+        }
+        if !self.put_options.is_empty() {
+            config.put.options = self
+                .put_options
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect::<Vec<_>>()
+                .into();
+        }
+        if !self.agent_puts.is_empty() {
+            config.agent_puts = self
+                .agent_puts
+                .iter()
+                .map(|(agent, put)| {
+                    agent
+                        .parse()
+                        .map(|agent| (AgentName::from_index(agent), put.clone()))
+                        .map_err(|_| format!("invalid agent {} in agent_puts", agent))
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        if let Some(seed) = self.seed {
+            config.static_seed = Some(seed);
+        }
+        if let Some(rng_seed) = self.rng_seed {
+            config.rng_seed = Some(rng_seed);
+        }
+        if let Some(seeds) = &self.seeds {
+            config.initial_corpus_dir = seeds.clone();
+        }
+        if let Some(corpus_dir) = &self.corpus_dir {
+            config.corpus_dir = corpus_dir.clone();
+        }
+        if let Some(objective_dir) = &self.objective_dir {
+            config.objective_dir = objective_dir.clone();
+        }
+        if !self.mutation_weights.is_empty() {
+            let spec = self
+                .mutation_weights
+                .iter()
+                .map(|(name, weight)| format!("{}={}", name, weight))
+                .collect::<Vec<_>>()
+                .join(",");
+            config.mutation_config.weights = spec.parse()?;
+        }
+        if let Some(timeout_ms) = self.timeout_ms {
+            // Like --hang-deadline, the deadline defaults to 6 times the timeout
+            config.execution_timeout = Duration::from_millis(timeout_ms);
+            config.hang_deadline = config.execution_timeout * 6;
+        }
+        if let Some(hang_deadline_ms) = self.hang_deadline_ms {
+            config.hang_deadline = Duration::from_millis(hang_deadline_ms);
+        }
+
+        let mutation_config = &mut config.mutation_config;
+        if let Some(fresh_zoo_after) = self.fresh_zoo_after {
+            mutation_config.fresh_zoo_after = fresh_zoo_after;
+        }
+        if let Some(min_trace_length) = self.min_trace_length {
+            mutation_config.min_trace_length = min_trace_length;
+        }
+        if let Some(max_trace_length) = self.max_trace_length {
+            mutation_config.max_trace_length = max_trace_length;
+        }
+        if let Some(max_trace_size) = self.max_trace_size {
+            mutation_config.max_trace_size = max_trace_size;
+        }
+        if let Some(min_term_size) = self.min_term_size {
+            mutation_config.term_constraints.min_term_size = min_term_size;
+        }
+        if let Some(max_term_size) = self.max_term_size {
+            mutation_config.term_constraints.max_term_size = max_term_size;
+        }
+
+        Ok(())
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("campaign config is always representable in TOML")
+    }
+}
+
+/// Reports nothing, but attaches the [`CampaignConfig`] to every stored testcase.
+#[derive(Debug)]
+pub struct CampaignFeedback {
+    campaign: CampaignConfig,
+}
+
+impl CampaignFeedback {
+    pub fn new(campaign: CampaignConfig) -> Self {
+        Self { campaign }
+    }
+}
+
+impl Named for CampaignFeedback {
+    fn name(&self) -> &str {
+        "CampaignFeedback"
+    }
+}
+
+impl<S> Feedback<S> for CampaignFeedback
+where
+    S: State,
+{
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &S::Input,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = S>,
+        OT: ObserversTuple<S>,
+    {
+        Ok(false)
+    }
+
+    fn append_metadata<OT>(
+        &mut self,
+        _state: &mut S,
+        _observers: &OT,
+        testcase: &mut Testcase<S::Input>,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<S>,
+    {
+        testcase.add_metadata(self.campaign.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CampaignConfig;
+
+    #[test_log::test]
+    fn test_parse_campaign() {
+        let campaign: CampaignConfig = toml::from_str(
+            r#"
+            put = "openssl111k"
+            seed = 42
+            timeout_ms = 1000
+            max_trace_length = 20
+
+            [put_options]
+            cipher_list = "AES128-SHA"
+
+            [agent_puts]
+            1 = "openssl312"
+
+            [mutation_weights]
+            swap = 2
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(campaign.put.as_deref(), Some("openssl111k"));
+        assert_eq!(campaign.seed, Some(42));
+        assert_eq!(campaign.mutation_weights.get("swap"), Some(&2));
+        assert_eq!(campaign.max_trace_length, Some(20));
+        assert_eq!(
+            campaign.agent_puts.get("1").map(String::as_str),
+            Some("openssl312")
+        );
+        assert_eq!(
+            toml::from_str::<CampaignConfig>(&campaign.to_toml()).unwrap(),
+            campaign
+        );
+
+        assert!(toml::from_str::<CampaignConfig>("timeout = 1000").is_err());
+    }
+}
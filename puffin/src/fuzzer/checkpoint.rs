@@ -0,0 +1,137 @@
+//! Checkpoints of the fuzzer state, such that a campaign survives reboots and can be moved to
+//! another machine.
+//!
+//! The restart mechanism of LibAFL only keeps the state in shared memory while the broker runs.
+//! Every `interval` the [`CheckpointStage`] additionally writes the whole state of a client to
+//! `<directory>/client-<core>.state`: the corpus and objective entries with their metadata, the
+//! metadata of the feedbacks and the scheduler, and the RNG. A client which is started with
+//! `--resume <directory>` continues from its checkpoint instead of the seeds.
+//!
+//! Entries which are evicted from the cache of the corpus are only referenced by their file in
+//! the corpus directory. The corpus and objective directories therefore need to be moved along
+//! with the checkpoints and keep their paths.
+
+use std::fs;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use libafl::prelude::*;
+use libafl_bolts::fs::write_file_atomic;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Where and how often the state is checkpointed, see [`CheckpointStage`]
+#[derive(Clone, Debug)]
+pub struct CheckpointConfig {
+    pub directory: PathBuf,
+    pub interval: Duration,
+}
+
+impl CheckpointConfig {
+    /// The checkpoint of the client on the core `core`
+    pub fn file(&self, core: usize) -> PathBuf {
+        self.directory.join(format!("client-{}.state", core))
+    }
+}
+
+/// Writes `state` atomically to `path`
+pub fn save<S: Serialize>(state: &S, path: &Path) -> Result<(), Error> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    write_file_atomic(path, &postcard::to_allocvec(state)?)
+}
+
+/// Reads the state at `path`, or returns `None` if there is no checkpoint
+pub fn load<S: DeserializeOwned>(path: &Path) -> Result<Option<S>, Error> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(postcard::from_bytes(&fs::read(path)?)?))
+}
+
+/// A [`Stage`] which periodically writes the fuzzer state to a file.
+#[derive(Clone, Debug)]
+pub struct CheckpointStage<E, EM, Z> {
+    file: Option<PathBuf>,
+    interval: Duration,
+    last_run: Instant,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(E, EM, Z)>,
+}
+
+impl<E, EM, Z> CheckpointStage<E, EM, Z> {
+    /// Checkpoints to `file` every `interval`. `None` disables the checkpoints.
+    pub fn new(file: Option<PathBuf>, interval: Duration) -> Self {
+        Self {
+            file,
+            interval,
+            last_run: Instant::now(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<E, EM, Z> UsesState for CheckpointStage<E, EM, Z>
+where
+    E: UsesState<State = Z::State>,
+    EM: UsesState<State = Z::State>,
+    Z: UsesState,
+{
+    type State = Z::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for CheckpointStage<E, EM, Z>
+where
+    E: UsesState<State = Z::State>,
+    EM: UsesState<State = Z::State>,
+    Z: UsesState,
+    Z::State: Serialize,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut Z::State,
+        _manager: &mut EM,
+        _corpus_idx: CorpusId,
+    ) -> Result<(), Error> {
+        let file = if let Some(file) = &self.file {
+            file
+        } else {
+            return Ok(());
+        };
+
+        if self.last_run.elapsed() < self.interval {
+            return Ok(());
+        }
+        self.last_run = Instant::now();
+
+        save(state, file)?;
+        log::info!("Checkpointed the fuzzer state to {}", file.display());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load, save, CheckpointConfig};
+
+    #[test_log::test]
+    fn test_checkpoint_roundtrip() {
+        let config = CheckpointConfig {
+            directory: std::env::temp_dir()
+                .join(format!("puffin-checkpoint-{}", std::process::id())),
+            interval: Default::default(),
+        };
+        let file = config.file(3);
+
+        assert_eq!(load::<Vec<u64>>(&file).unwrap(), None);
+        save(&vec![1u64, 2, 3], &file).unwrap();
+        assert_eq!(load::<Vec<u64>>(&file).unwrap(), Some(vec![1, 2, 3]));
+
+        let _ = std::fs::remove_dir_all(&config.directory);
+    }
+}
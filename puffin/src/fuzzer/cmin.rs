@@ -0,0 +1,211 @@
+//! Offline corpus distillation of the traces of a campaign.
+//!
+//! Each trace is executed in a forked process, like by the [`fork_harness`], and its coverage is
+//! read from the maps of the edges and the state graph observers. As for the hitcounts observer,
+//! an entry of a map counts as the pair of its index and the bucket of its hit count. The traces
+//! which are kept are selected by [`distill`], i.e. the shortest trace for each covered entry.
+//! Traces which crash or time out are not kept, they belong into the objectives.
+
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use libafl::corpus::CorpusId;
+use libafl::executors::ExitKind;
+use libafl::inputs::Input;
+use libafl::Error;
+use libafl_bolts::shmem::{ShMemProvider, StdShMemProvider};
+use libafl_bolts::AsMutSlice;
+
+use crate::execution::Runner;
+use crate::fuzzer::distillation::distill;
+use crate::fuzzer::harness::{fork_harness, fork_timeout};
+use crate::fuzzer::libafl_setup::edges_map;
+use crate::fuzzer::state_graph::state_graph_map;
+use crate::protocol::ProtocolBehavior;
+use crate::trace::Trace;
+
+/// Number of hit count buckets per map entry, see [`bucket`]
+const BUCKETS: usize = 8;
+
+/// The bucket of a hit count, following the classes of AFL
+fn bucket(count: u8) -> usize {
+    match count {
+        0 | 1 => 0,
+        2 => 1,
+        3 => 2,
+        4..=7 => 3,
+        8..=15 => 4,
+        16..=31 => 5,
+        32..=127 => 6,
+        128..=255 => 7,
+    }
+}
+
+/// The covered entries of `maps`, which are numbered consecutively
+fn covered(maps: &[&[u8]]) -> Vec<usize> {
+    let mut offset = 0;
+    let mut covered = vec![];
+
+    for map in maps {
+        for (index, count) in map.iter().enumerate() {
+            if *count > 0 {
+                covered.push((offset + index) * BUCKETS + bucket(*count));
+            }
+        }
+        offset += map.len();
+    }
+
+    covered
+}
+
+/// The name of the copy of `path` in the output directory. Traces of different input directories
+/// may share their name, in which case a counter is appended to the stem of the later ones.
+fn output_name(path: &Path, taken: &mut HashSet<OsString>) -> OsString {
+    let name = path
+        .file_name()
+        .map_or_else(|| "trace".into(), OsString::from);
+    let stem = Path::new(&name).file_stem().unwrap_or_default().to_owned();
+    let extension = Path::new(&name).extension().map(OsString::from);
+
+    let mut candidate = name;
+    let mut counter = 1;
+    while !taken.insert(candidate.clone()) {
+        candidate = stem.clone();
+        candidate.push(format!("-{}", counter));
+        if let Some(extension) = &extension {
+            candidate.push(".");
+            candidate.push(extension);
+        }
+        counter += 1;
+    }
+
+    candidate
+}
+
+/// Copies a minimal subset of the traces in `inputs`, which achieves the same coverage, to
+/// `output`. Returns the number of traces which were kept.
+pub fn cmin<PB: ProtocolBehavior + 'static>(
+    runner: &Runner<PB>,
+    inputs: &[PathBuf],
+    output: &Path,
+    execution_timeout: Duration,
+) -> Result<usize, Error> {
+    let edges = edges_map();
+    let mut shared_map =
+        StdShMemProvider::new()?.new_shmem((edges.len() + state_graph_map().len()).max(1))?;
+
+    let mut entries = vec![];
+    for path in inputs {
+        let trace = match Trace::<PB::Matcher>::from_file(path) {
+            Ok(trace) => trace,
+            Err(err) => {
+                log::warn!("Skipping {}: {}", path.display(), err);
+                continue;
+            }
+        };
+
+        edges.fill(0);
+        state_graph_map().fill(0);
+        let exit_kind = fork_harness(
+            runner,
+            &trace,
+            &[],
+            edges,
+            shared_map.as_mut_slice(),
+            fork_timeout(execution_timeout),
+            None,
+        )
+        .map_err(|err| Error::unknown(err.to_string()))?;
+        if exit_kind != ExitKind::Ok {
+            log::warn!(
+                "Skipping {}: execution ended with {:?}",
+                path.display(),
+                exit_kind
+            );
+            continue;
+        }
+
+        entries.push((
+            path,
+            trace.steps.len(),
+            covered(&[&*edges, &*state_graph_map()]),
+        ));
+    }
+
+    let kept = distill(
+        entries
+            .iter()
+            .enumerate()
+            .map(|(id, (_, len, covered))| (CorpusId::from(id), *len, covered.as_slice())),
+    );
+
+    fs::create_dir_all(output)?;
+    let mut taken = HashSet::new();
+    for (id, (path, _, _)) in entries.iter().enumerate() {
+        if kept.contains(&CorpusId::from(id)) {
+            fs::copy(path, output.join(output_name(path, &mut taken)))?;
+        }
+    }
+
+    log::info!(
+        "Kept {} of {} traces ({} executed successfully)",
+        kept.len(),
+        inputs.len(),
+        entries.len()
+    );
+
+    Ok(kept.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::path::Path;
+
+    use super::{bucket, covered, output_name, BUCKETS};
+
+    #[test_log::test]
+    fn test_covered_entries_with_buckets() {
+        assert_eq!(bucket(1), 0);
+        assert_eq!(bucket(5), 3);
+        assert_eq!(bucket(255), BUCKETS - 1);
+
+        let edges = [0, 1, 0, 9];
+        let states = [3, 0];
+        assert_eq!(
+            covered(&[&edges, &states]),
+            vec![BUCKETS, 3 * BUCKETS + 4, 4 * BUCKETS + 2]
+        );
+    }
+
+    #[test_log::test]
+    fn test_output_names_do_not_collide() {
+        let mut taken = HashSet::new();
+        let names: Vec<_> = [
+            "a/seed.trace",
+            "b/seed.trace",
+            "c/seed.trace",
+            "a/seed-1.trace",
+            "b/other",
+            "c/other",
+        ]
+        .iter()
+        .map(|path| output_name(Path::new(path), &mut taken))
+        .collect();
+
+        assert_eq!(
+            names,
+            [
+                "seed.trace",
+                "seed-1.trace",
+                "seed-2.trace",
+                "seed-1-1.trace",
+                "other",
+                "other-1"
+            ]
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! Name spaces of the edges map, such that the coverage of each PUT of the registry is tracked
+//! independently.
+//!
+//! All PUT libraries which are linked into the fuzzer share the guards of the sancov
+//! instrumentation, which write into the first `MAX_EDGES_NUM` entries of the edges map. With the
+//! `coverage-namespaces` feature, the edges map holds one region of `MAX_EDGES_NUM` entries per
+//! registered PUT, see [`set_namespaces`]. For the duration of a call into a PUT, e.g. by an
+//! [`Agent`](crate::agent::Agent), [`with_namespace`] swaps the region of the PUT with the first
+//! region. The feedback and the corpus minimizer then see the same edge covered by two PUTs as
+//! different entries of one map.
+//!
+//! The default PUT uses the first region, such that regions are only swapped for agents of other
+//! PUTs. Without the feature, or if the regions do not fit into the edges map, all PUTs share the
+//! first region.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::protocol::ProtocolBehavior;
+use crate::put_registry::PutRegistry;
+
+/// Number of name spaces of the edges map
+static NAMESPACES: AtomicUsize = AtomicUsize::new(1);
+
+/// Tracks the coverage of each PUT of `registry` in its own region of the edges map.
+pub fn set_namespaces<PB: ProtocolBehavior>(registry: &PutRegistry<PB>) {
+    if cfg!(feature = "coverage-namespaces") {
+        NAMESPACES.store(registry.puts().count().max(1), Ordering::SeqCst);
+    }
+}
+
+/// Index of the region in which the coverage of the PUT `id` of `registry` is tracked. The
+/// default PUT uses the first region, the other PUTs follow in the order of their ids.
+pub fn namespace<PB: ProtocolBehavior>(registry: &PutRegistry<PB>, id: &str) -> usize {
+    if id == registry.default_id() {
+        return 0;
+    }
+
+    let mut others: Vec<&str> = registry
+        .puts()
+        .map(|(other, _)| other)
+        .filter(|other| *other != registry.default_id())
+        .collect();
+    others.sort_unstable();
+
+    others
+        .iter()
+        .position(|other| *other == id)
+        .map_or(0, |position| position + 1)
+}
+
+/// Number of regions which the edges map holds, each of `MAX_EDGES_NUM` entries
+pub fn regions(edges: usize, capacity: usize) -> usize {
+    fit(edges, capacity, NAMESPACES.load(Ordering::SeqCst))
+}
+
+fn fit(edges: usize, capacity: usize, namespaces: usize) -> usize {
+    if edges * namespaces <= capacity {
+        namespaces
+    } else {
+        1
+    }
+}
+
+/// Runs `f`, which calls into the PUT whose coverage is tracked in the region `namespace`, see
+/// [`namespace`].
+#[cfg(all(feature = "coverage-namespaces", not(test)))]
+pub fn with_namespace<R>(namespace: usize, f: impl FnOnce() -> R) -> R {
+    /// Swaps the previous region back in, also if `f` panics
+    struct Restore(usize);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            activate(self.0);
+        }
+    }
+
+    let _restore = Restore(ACTIVE.load(Ordering::SeqCst));
+    activate(namespace);
+    f()
+}
+
+/// Runs `f`, which calls into the PUT whose coverage is tracked in the region `namespace`, see
+/// [`namespace`].
+#[cfg(not(all(feature = "coverage-namespaces", not(test))))]
+pub fn with_namespace<R>(_namespace: usize, f: impl FnOnce() -> R) -> R {
+    f()
+}
+
+/// The name space whose region is swapped with the first region
+#[cfg(all(feature = "coverage-namespaces", not(test)))]
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+/// Swaps the region of `namespace` with the first region of the edges map, after restoring the
+/// region of the previously active name space.
+#[cfg(all(feature = "coverage-namespaces", not(test)))]
+fn activate(namespace: usize) {
+    use libafl_targets::{EDGES_MAP, EDGES_MAP_SIZE, MAX_EDGES_NUM};
+
+    let edges = unsafe { MAX_EDGES_NUM };
+    let namespace = if namespace < regions(edges, EDGES_MAP_SIZE) {
+        namespace
+    } else {
+        0
+    };
+
+    let active = ACTIVE.swap(namespace, Ordering::SeqCst);
+    if active == namespace {
+        return;
+    }
+
+    let map = unsafe { &mut *std::ptr::addr_of_mut!(EDGES_MAP) };
+    for region in [active, namespace] {
+        if region != 0 {
+            let (first, rest) = map.split_at_mut(region * edges);
+            first[..edges].swap_with_slice(&mut rest[..edges]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fit, namespace};
+    use crate::algebra::test_signature::{TestFactory, TestProtocolBehavior};
+    use crate::put_registry::PutRegistry;
+
+    #[test_log::test]
+    fn test_regions_fit_into_map() {
+        assert_eq!(fit(100, 300, 3), 3);
+        assert_eq!(fit(101, 300, 3), 1);
+        assert_eq!(fit(100, 100, 1), 1);
+    }
+
+    #[test_log::test]
+    fn test_namespaces_of_puts() {
+        let registry = PutRegistry::<TestProtocolBehavior>::new(
+            [
+                ("c", Box::new(TestFactory) as _),
+                ("default", Box::new(TestFactory) as _),
+                ("b", Box::new(TestFactory) as _),
+            ],
+            "default",
+        )
+        .unwrap();
+
+        assert_eq!(namespace(&registry, "default"), 0);
+        assert_eq!(namespace(&registry, "b"), 1);
+        assert_eq!(namespace(&registry, "c"), 2);
+    }
+}
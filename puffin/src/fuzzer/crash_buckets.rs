@@ -0,0 +1,453 @@
+//! Deduplication of crashes.
+//!
+//! The [`CrashBucketStage`] sorts new objectives into buckets by the top frames of the stack and
+//! the latest claims, see [`recent_claims`]. They are captured by [`capture_crash_context`], which
+//! executes the objective again in a forked process, because a backtrace can not be captured
+//! safely in the signal handler of the fuzzer. ASAN reports take precedence over the captured
+//! backtrace, because they contain the stack of the PUT even if the crash was not caused by a
+//! signal.
+//!
+//! Each bucket is stored in the buckets directory of the campaign as a minimized representative
+//! `<bucket>.trace` together with [`BucketMetadata`] in `<bucket>.json`. A representative is
+//! replaced if an objective of the same bucket is shorter than the trace from which the
+//! representative was minimized.
+
+use std::backtrace::Backtrace;
+use std::fs::{self, File};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::io::Read;
+use std::marker::PhantomData;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::Duration;
+
+use chrono::Utc;
+use libafl::prelude::*;
+use libafl_bolts::prelude::*;
+use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use nix::unistd::pipe;
+use serde::{Deserialize, Serialize};
+
+use super::sanitizer::feedback::AsanReportMetadata;
+use crate::claims::recent_claims;
+use crate::execution::{run_in_subprocess, Runner, TraceRunner};
+use crate::protocol::ProtocolBehavior;
+use crate::trace::Trace;
+
+/// Number of frames of the stack which distinguish buckets
+const STACK_FRAMES: usize = 5;
+
+/// Timeout of the execution which reproduces a crash, see [`capture_crash_context`]
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Signals with which the PUT crashes
+const CRASH_SIGNALS: [Signal; 5] = [
+    Signal::SIGSEGV,
+    Signal::SIGBUS,
+    Signal::SIGILL,
+    Signal::SIGFPE,
+    Signal::SIGABRT,
+];
+
+/// Frames of the C runtime which are part of every stack
+const RUNTIME_FRAMES: [&str; 7] = [
+    "__restore_rt",
+    "__libc_start_main",
+    "__libc_start_call_main",
+    "_start",
+    "main",
+    "start_thread",
+    "clone",
+];
+
+/// The state of the execution when the PUT crashed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashContextMetadata {
+    /// Function names of the PUT on the stack of the crash, the innermost first
+    pub frames: Vec<String>,
+    /// The latest claims before the crash, see [`recent_claims`]
+    pub claims: Vec<String>,
+}
+
+libafl_bolts::impl_serdeany!(CrashContextMetadata);
+
+/// Describes a bucket in the buckets directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BucketMetadata {
+    /// Name of the bucket
+    pub key: String,
+    pub frames: Vec<String>,
+    pub claims: Vec<String>,
+    /// Length of the objective from which the representative was minimized
+    pub source_len: usize,
+    /// When the representative was stored, in RFC 3339 format
+    pub found: String,
+}
+
+/// Captures the context of the crashes, minimizes the objectives and describes their shape if
+/// neither a stack nor claims are known.
+pub struct CrashTriage<I> {
+    pub buckets_dir: PathBuf,
+    pub context: Box<dyn Fn(&I) -> CrashContextMetadata>,
+    pub minimize: Box<dyn Fn(&I) -> I>,
+    pub shape: fn(&I) -> u64,
+}
+
+/// Extracts the function names of the first stack of an ASAN `report`.
+pub fn asan_frames(report: &str) -> Vec<String> {
+    report
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| !line.starts_with('#'))
+        .take_while(|line| line.starts_with('#'))
+        .filter_map(|line| line.split_once(" in ").map(|(_, location)| location))
+        .filter_map(|location| location.split_whitespace().next())
+        .filter(|function| !RUNTIME_FRAMES.contains(function))
+        .take(STACK_FRAMES)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Extracts the function names of the PUT from a `backtrace` which was formatted by the standard
+/// library. Frames of Rust code, like the fuzzer and its signal handler, are skipped.
+pub fn backtrace_frames(backtrace: &str) -> Vec<String> {
+    backtrace
+        .lines()
+        .filter_map(|line| line.trim().split_once(": "))
+        .filter(|(index, _)| index.chars().all(|c| c.is_ascii_digit()))
+        .map(|(_, function)| function.trim())
+        .filter(|function| {
+            !function.contains("::")
+                && !function.starts_with('<')
+                && !RUNTIME_FRAMES.contains(function)
+        })
+        .take(STACK_FRAMES)
+        .map(str::to_string)
+        .collect()
+}
+
+fn bucket_key(frames: &[String], claims: &[String]) -> u64 {
+    let mut hasher = ahash::RandomState::with_seeds(0, 0, 0, 0).build_hasher();
+    frames.hash(&mut hasher);
+    claims.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Write end of the pipe to which [`report_crash_context`] writes the context of a crash
+static CONTEXT_FD: AtomicI32 = AtomicI32::new(-1);
+
+/// Handles the crash signals in the child of [`capture_crash_context`].
+extern "C" fn report_crash_context(_signal: libc::c_int) {
+    // Neither capturing the backtrace nor allocating is async-signal-safe. This only runs in the
+    // forked child, which is killed after the timeout if it deadlocks.
+    let context = CrashContextMetadata {
+        frames: backtrace_frames(&Backtrace::force_capture().to_string()),
+        claims: recent_claims(),
+    };
+    if let Ok(encoded) = serde_json::to_vec(&context) {
+        let fd = CONTEXT_FD.load(Ordering::SeqCst);
+        unsafe { libc::write(fd, encoded.as_ptr().cast(), encoded.len()) };
+    }
+    unsafe { libc::_exit(1) };
+}
+
+/// Captures the stack and the latest claims of a crash of `trace` in a forked process.
+///
+/// Like in the [`minimizer`](super::minimizer), security violations count as crashes. The context
+/// is empty if the trace does not crash again.
+pub fn capture_crash_context<PB: ProtocolBehavior>(
+    runner: &Runner<PB>,
+    trace: &Trace<PB::Matcher>,
+) -> CrashContextMetadata {
+    let (reader, writer) = match pipe() {
+        Ok(pipe) => pipe,
+        Err(err) => {
+            log::error!("Failed to create a pipe for the crash context: {}", err);
+            return CrashContextMetadata::default();
+        }
+    };
+    let writer_fd = writer.as_raw_fd();
+
+    let status = run_in_subprocess(
+        || {
+            CONTEXT_FD.store(writer_fd, Ordering::SeqCst);
+            for signal in CRASH_SIGNALS {
+                unsafe {
+                    let _ = sigaction(
+                        signal,
+                        &SigAction::new(
+                            SigHandler::Handler(report_crash_context),
+                            SaFlags::empty(),
+                            SigSet::empty(),
+                        ),
+                    );
+                }
+            }
+
+            if runner.execute(trace).is_err_and(|err| err.is_crash()) {
+                std::process::abort();
+            }
+        },
+        CAPTURE_TIMEOUT,
+    );
+    // The read end only reaches the end of the file once no process holds the write end
+    drop(writer);
+
+    let mut encoded = vec![];
+    if let Err(err) = File::from(reader).read_to_end(&mut encoded) {
+        log::error!("Failed to read the crash context: {}", err);
+    }
+    if encoded.is_empty() {
+        log::debug!(
+            "Captured no crash context, the execution ended with {:?}",
+            status
+        );
+    }
+
+    serde_json::from_slice(&encoded).unwrap_or_default()
+}
+
+/// Number of objectives which were already sorted into buckets by the [`CrashBucketStage`]. It
+/// is part of the state, such that a restarted client does not sort them again.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CrashBucketsMetadata {
+    pub processed: usize,
+}
+
+libafl_bolts::impl_serdeany!(CrashBucketsMetadata);
+
+/// A [`Stage`] which sorts new objectives into buckets and stores a minimized representative of
+/// each bucket.
+pub struct CrashBucketStage<E, EM, Z>
+where
+    Z: UsesState,
+{
+    triage: Option<CrashTriage<<Z::State as UsesInput>::Input>>,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(E, EM, Z)>,
+}
+
+impl<E, EM, Z> UsesState for CrashBucketStage<E, EM, Z>
+where
+    E: UsesState<State = Z::State>,
+    EM: UsesState<State = Z::State>,
+    Z: UsesState,
+{
+    type State = Z::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for CrashBucketStage<E, EM, Z>
+where
+    E: UsesState<State = Z::State>,
+    EM: UsesState<State = Z::State>,
+    Z: UsesState,
+    Z::State: HasSolutions + HasMetadata,
+    <Z::State as UsesInput>::Input: HasLen,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut Z::State,
+        _manager: &mut EM,
+        _corpus_idx: CorpusId,
+    ) -> Result<(), Error> {
+        let triage = if let Some(triage) = &self.triage {
+            triage
+        } else {
+            return Ok(());
+        };
+
+        let processed = state
+            .metadata_map()
+            .get::<CrashBucketsMetadata>()
+            .map_or(0, |metadata| metadata.processed);
+        let new: Vec<CorpusId> = state.solutions().ids().skip(processed).collect();
+
+        for (index, id) in new.into_iter().enumerate() {
+            let solutions = state.solutions();
+            let input = solutions.cloned_input_for_id(id)?;
+            let asan_frames = solutions
+                .get(id)?
+                .borrow()
+                .metadata_map()
+                .get::<AsanReportMetadata>()
+                .map(|report| asan_frames(&report.report))
+                .unwrap_or_default();
+
+            let mut context = (triage.context)(&input);
+            if !asan_frames.is_empty() {
+                context.frames = asan_frames;
+            }
+
+            state.add_metadata(CrashBucketsMetadata {
+                processed: processed + index + 1,
+            });
+
+            if let Err(err) = sort_into_bucket(triage, &input, context) {
+                log::error!("Failed to sort objective into a bucket: {}", err);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<E, EM, Z> CrashBucketStage<E, EM, Z>
+where
+    Z: UsesState,
+{
+    /// Creates a new stage. If `triage` is `None`, the stage does nothing.
+    pub fn new(triage: Option<CrashTriage<<Z::State as UsesInput>::Input>>) -> Self {
+        Self {
+            triage,
+            phantom: PhantomData,
+        }
+    }
+}
+
+fn sort_into_bucket<I: Input + HasLen>(
+    triage: &CrashTriage<I>,
+    input: &I,
+    context: CrashContextMetadata,
+) -> Result<(), Error> {
+    let key = if context.frames.is_empty() && context.claims.is_empty() {
+        format!("shape-{:016x}", (triage.shape)(input))
+    } else {
+        format!("{:016x}", bucket_key(&context.frames, &context.claims))
+    };
+
+    let metadata_path = triage.buckets_dir.join(&key).with_extension("json");
+    if let Some(existing) = read_bucket(&metadata_path) {
+        if existing.source_len <= input.len() {
+            log::debug!("Objective belongs to known bucket {}", key);
+            return Ok(());
+        }
+    }
+
+    log::warn!(
+        "Storing representative of crash bucket {} (stack: {}, claims: {})",
+        key,
+        context.frames.join(" < "),
+        context.claims.join(", ")
+    );
+
+    let representative = (triage.minimize)(input);
+    let metadata = BucketMetadata {
+        key,
+        frames: context.frames,
+        claims: context.claims,
+        source_len: input.len(),
+        found: Utc::now().to_rfc3339(),
+    };
+
+    fs::create_dir_all(&triage.buckets_dir)?;
+    representative.to_file(metadata_path.with_extension("trace"))?;
+    let metadata =
+        serde_json::to_string_pretty(&metadata).map_err(|err| Error::serialize(err.to_string()))?;
+    write_file_atomic(&metadata_path, metadata.as_bytes())
+}
+
+fn read_bucket(metadata_path: &Path) -> Option<BucketMetadata> {
+    let metadata = fs::read(metadata_path).ok()?;
+    serde_json::from_slice(&metadata).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        asan_frames, backtrace_frames, bucket_key, capture_crash_context, CrashContextMetadata,
+    };
+    use crate::agent::AgentName;
+    use crate::algebra::test_signature::*;
+    use crate::algebra::AnyMatcher;
+    use crate::execution::Runner;
+    use crate::put_registry::PutRegistry;
+    use crate::trace::{OutputAction, Spawner, Step, Trace, TraceHook};
+
+    #[test_log::test]
+    fn test_asan_frames() {
+        let report = "\
+==4711==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000011 at pc 0x55d1 bp 0x7ffc sp 0x7ffc
+READ of size 1 at 0x602000000011 thread T0
+    #0 0x55d1 in tls_process_server_hello ssl/statem/statem_clnt.c:1432
+    #1 0x55d2 in read_state_machine ssl/statem/statem.c:636
+    #2 0x55d3 in main fuzz/main.c:10
+
+0x602000000011 is located 0 bytes to the right of 1-byte region
+allocated by thread T0 here:
+    #0 0x55d4 in malloc
+";
+
+        assert_eq!(
+            asan_frames(report),
+            vec!["tls_process_server_hello", "read_state_machine"]
+        );
+    }
+
+    #[test_log::test]
+    fn test_backtrace_frames() {
+        let backtrace = "\
+   0: std::backtrace::Backtrace::force_capture
+             at /rustc/library/std/src/backtrace.rs:312:9
+   1: libafl::executors::inprocess::unix_signal_handler::inproc_crash_handler
+   2: __restore_rt
+   3: ssl3_read_bytes
+             at ssl/record/rec_layer_s3.c:1234
+   4: SSL_read
+   5: <puffin::put::Put as core::fmt::Debug>::fmt
+";
+
+        assert_eq!(
+            backtrace_frames(backtrace),
+            vec!["ssl3_read_bytes", "SSL_read"]
+        );
+    }
+
+    #[test_log::test]
+    fn test_capture_crash_context_in_child() {
+        struct Abort;
+
+        impl TraceHook<TestProtocolBehavior> for Abort {
+            fn on_step_start(&self, _index: usize, _step: &Step<AnyMatcher>) {
+                std::process::abort();
+            }
+        }
+
+        let registry = PutRegistry::<TestProtocolBehavior>::new(
+            [("TESTSTUB_RUST_PUT", Box::new(TestFactory) as _)],
+            "TESTSTUB_RUST_PUT",
+        )
+        .unwrap();
+        // There is no agent which could produce an output, the execution fails without crashing
+        let trace: TestTrace = Trace {
+            descriptors: vec![],
+            prior_traces: vec![],
+            steps: vec![OutputAction::new_step(AgentName::first())],
+        };
+
+        let runner = Runner::new(registry.clone(), Spawner::new(registry.clone()));
+        assert_eq!(
+            capture_crash_context(&runner, &trace),
+            CrashContextMetadata::default()
+        );
+
+        let aborting = Runner::new(registry.clone(), Spawner::new(registry)).with_trace_hook(Abort);
+        let context = capture_crash_context(&aborting, &trace);
+        assert!(
+            context.frames.iter().any(|frame| frame == "abort"),
+            "{:?}",
+            context.frames
+        );
+    }
+
+    #[test_log::test]
+    fn test_bucket_key() {
+        let frames = vec!["ssl3_read_bytes".to_string()];
+        let claims = vec!["agent_1:ClientHello".to_string()];
+
+        assert_eq!(bucket_key(&frames, &claims), bucket_key(&frames, &claims));
+        assert_ne!(bucket_key(&frames, &claims), bucket_key(&frames, &[]));
+    }
+}
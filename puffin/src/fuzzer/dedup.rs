@@ -0,0 +1,182 @@
+//! Rejection of traces which are already in the corpus.
+//!
+//! Mutations frequently produce a trace which equals an existing entry, e.g. by swapping two equal
+//! subterms. If such a trace is considered interesting again, for example because of a
+//! non-deterministic PUT, it would be stored and scheduled twice. The [`DedupFeedback`] compares
+//! traces by their [`StructuralHash`] and is not
+//! interesting for a trace which was already added to the corpus. As it is combined with the other
+//! feedbacks by a logical and, a duplicate is neither stored nor passed to the scheduler, and no
+//! event is fired for it.
+
+use std::collections::HashSet;
+
+use libafl::prelude::*;
+use libafl_bolts::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::trace::StructuralHash;
+
+/// The structural hashes of the traces which were added to the corpus. It is part of the state,
+/// such that a restarted client still knows its corpus.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DedupMetadata {
+    hashes: HashSet<u64>,
+    /// How many traces were not added because they are already stored
+    pub duplicates: usize,
+}
+
+libafl_bolts::impl_serdeany!(DedupMetadata);
+
+impl DedupMetadata {
+    /// The metadata of `state`, which is added if the state was created without it
+    fn of<S: HasMetadata>(state: &mut S) -> &mut Self {
+        if !state.has_metadata::<Self>() {
+            state.add_metadata(Self::default());
+        }
+        state.metadata_map_mut().get_mut::<Self>().unwrap()
+    }
+}
+
+/// A [`Feedback`] which is not interesting for traces that are structurally equal to a trace in
+/// the corpus. It is meant to be combined with the actual feedback by [`feedback_and_fast`].
+#[derive(Debug, Default)]
+pub struct DedupFeedback;
+
+impl DedupFeedback {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Named for DedupFeedback {
+    fn name(&self) -> &str {
+        "DedupFeedback"
+    }
+}
+
+impl<S> Feedback<S> for DedupFeedback
+where
+    S: State + HasMetadata,
+    S::Input: StructuralHash,
+{
+    fn is_interesting<EM, OT>(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        input: &S::Input,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = S>,
+        OT: ObserversTuple<S>,
+    {
+        let hash = input.structural_hash();
+        let metadata = DedupMetadata::of(state);
+
+        if metadata.hashes.contains(&hash) {
+            metadata.duplicates += 1;
+            log::debug!("Rejected a duplicate of a corpus entry");
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    fn append_metadata<OT>(
+        &mut self,
+        state: &mut S,
+        _observers: &OT,
+        testcase: &mut Testcase<S::Input>,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<S>,
+    {
+        if let Some(input) = testcase.input() {
+            let hash = input.structural_hash();
+            DedupMetadata::of(state).hashes.insert(hash);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use libafl::prelude::*;
+    use libafl_bolts::prelude::*;
+
+    use super::{DedupFeedback, DedupMetadata};
+    use crate::agent::AgentName;
+    use crate::algebra::test_signature::TestTrace;
+    use crate::trace::{OutputAction, Trace};
+
+    /// Executes every input successfully, without installing the signal handlers of the
+    /// [`InProcessExecutor`] into the test process
+    struct OkExecutor<S>(PhantomData<S>);
+
+    impl<S: State> UsesState for OkExecutor<S> {
+        type State = S;
+    }
+
+    impl<EM, S, Z> Executor<EM, Z> for OkExecutor<S>
+    where
+        EM: UsesState<State = S>,
+        S: State,
+        Z: UsesState<State = S>,
+    {
+        fn run_target(
+            &mut self,
+            _fuzzer: &mut Z,
+            _state: &mut S,
+            _mgr: &mut EM,
+            _input: &S::Input,
+        ) -> Result<ExitKind, Error> {
+            Ok(ExitKind::Ok)
+        }
+    }
+
+    #[test_log::test]
+    fn test_duplicates_are_rejected() {
+        let trace: TestTrace = Trace {
+            descriptors: vec![],
+            prior_traces: vec![],
+            steps: vec![OutputAction::new_step(AgentName::first())],
+        };
+        let longer = Trace {
+            steps: vec![trace.steps[0].clone(), trace.steps[0].clone()],
+            ..trace.clone()
+        };
+
+        let mut feedback = feedback_and_fast!(DedupFeedback::new(), ConstFeedback::new(true));
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), feedback, objective);
+        let mut manager = NopEventManager::new();
+        let mut executor = WithObservers::new(OkExecutor(PhantomData), tuple_list!());
+
+        let (result, first) = fuzzer
+            .evaluate_input(&mut state, &mut executor, &mut manager, trace.clone())
+            .unwrap();
+        assert_eq!(result, ExecuteInputResult::Corpus);
+        let (_, other) = fuzzer
+            .evaluate_input(&mut state, &mut executor, &mut manager, longer)
+            .unwrap();
+        assert_ne!(first, other);
+
+        let (result, duplicate) = fuzzer
+            .evaluate_input(&mut state, &mut executor, &mut manager, trace)
+            .unwrap();
+        assert_eq!(result, ExecuteInputResult::None);
+        assert_eq!(duplicate, None);
+        assert_eq!(state.corpus().count(), 2);
+        assert_eq!(state.metadata::<DedupMetadata>().unwrap().duplicates, 1);
+    }
+}
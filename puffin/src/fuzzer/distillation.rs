@@ -0,0 +1,256 @@
+//! Online corpus distillation (cmin) which runs periodically during a fuzzing campaign.
+//!
+//! Every `interval` the [`DistillationStage`] recomputes a minimal subset of the corpus which
+//! preserves the coverage seen so far. For each covered map index, the shortest trace which hits
+//! it is kept. All other entries are marked with [`DemotedMetadata`]. They are not deleted from
+//! the corpus, but the [`DistilledScheduler`] avoids scheduling them.
+
+use std::collections::{HashMap, HashSet};
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+use libafl::prelude::*;
+use libafl_bolts::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How often the [`DistilledScheduler`] asks the inner scheduler for another entry if it returned
+/// a demoted one.
+const MAX_RESCHEDULE_TRIES: usize = 16;
+
+/// Marks a [`Testcase`] as redundant with respect to the coverage of the current corpus.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DemotedMetadata;
+
+libafl_bolts::impl_serdeany!(DemotedMetadata);
+
+/// Computes which entries of the corpus are required to preserve the coverage.
+///
+/// `entries` consists of the id of the entry, the length of the input and the map indexes covered
+/// by the entry. For each map index, the shortest entry is selected. Ties are broken by the
+/// id of the entry.
+pub fn distill<'a, I>(entries: I) -> HashSet<CorpusId>
+where
+    I: IntoIterator<Item = (CorpusId, usize, &'a [usize])>,
+{
+    let mut best: HashMap<usize, (usize, CorpusId)> = HashMap::new();
+
+    for (id, len, indexes) in entries {
+        for index in indexes {
+            best.entry(*index)
+                .and_modify(|current| {
+                    if (len, id) < *current {
+                        *current = (len, id);
+                    }
+                })
+                .or_insert((len, id));
+        }
+    }
+
+    best.into_values().map(|(_len, id)| id).collect()
+}
+
+/// A [`Stage`] which periodically demotes redundant corpus entries.
+#[derive(Clone, Debug)]
+pub struct DistillationStage<E, EM, Z> {
+    interval: Option<Duration>,
+    last_run: Instant,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(E, EM, Z)>,
+}
+
+impl<E, EM, Z> UsesState for DistillationStage<E, EM, Z>
+where
+    E: UsesState<State = Z::State>,
+    EM: UsesState<State = Z::State>,
+    Z: UsesState,
+{
+    type State = Z::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for DistillationStage<E, EM, Z>
+where
+    E: UsesState<State = Z::State>,
+    EM: UsesState<State = Z::State>,
+    Z: UsesState,
+    Z::State: HasCorpus,
+    <Z::State as UsesInput>::Input: HasLen,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut Z::State,
+        _manager: &mut EM,
+        _corpus_idx: CorpusId,
+    ) -> Result<(), Error> {
+        let interval = if let Some(interval) = self.interval {
+            interval
+        } else {
+            return Ok(());
+        };
+
+        if self.last_run.elapsed() < interval {
+            return Ok(());
+        }
+        self.last_run = Instant::now();
+
+        let corpus = state.corpus();
+
+        let mut coverage = Vec::with_capacity(corpus.count());
+        for id in corpus.ids() {
+            let indexes = corpus
+                .get(id)?
+                .borrow()
+                .metadata_map()
+                .get::<MapIndexesMetadata>()
+                .map(|meta| meta.list.clone())
+                .unwrap_or_default();
+            let len = corpus.cloned_input_for_id(id)?.len();
+            coverage.push((id, len, indexes));
+        }
+
+        let kept = distill(
+            coverage
+                .iter()
+                .map(|(id, len, indexes)| (*id, *len, indexes.as_slice())),
+        );
+
+        let mut demoted = 0;
+        for (id, _, _) in &coverage {
+            let mut testcase = corpus.get(*id)?.borrow_mut();
+            if kept.contains(id) {
+                let _ = testcase.metadata_map_mut().remove::<DemotedMetadata>();
+            } else {
+                testcase.add_metadata(DemotedMetadata);
+                demoted += 1;
+            }
+        }
+
+        log::info!(
+            "Distilled corpus: {} of {} entries kept, {} demoted",
+            kept.len(),
+            coverage.len(),
+            demoted
+        );
+
+        Ok(())
+    }
+}
+
+impl<E, EM, Z> DistillationStage<E, EM, Z> {
+    /// Creates a new stage. If `interval` is `None`, the stage does nothing.
+    pub fn new(interval: Option<Duration>) -> Self {
+        Self {
+            interval,
+            last_run: Instant::now(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// A [`Scheduler`] which skips entries marked by the [`DistillationStage`].
+///
+/// If the inner scheduler repeatedly returns demoted entries, the last one is used anyway. This
+/// keeps the fuzzer running even if the whole corpus got demoted.
+#[derive(Debug, Clone)]
+pub struct DistilledScheduler<CS> {
+    inner: CS,
+}
+
+impl<CS> DistilledScheduler<CS> {
+    pub fn new(inner: CS) -> Self {
+        Self { inner }
+    }
+}
+
+impl<CS> UsesState for DistilledScheduler<CS>
+where
+    CS: UsesState,
+{
+    type State = CS::State;
+}
+
+impl<CS> Scheduler for DistilledScheduler<CS>
+where
+    CS: Scheduler,
+    CS::State: HasCorpus,
+{
+    fn on_add(&mut self, state: &mut Self::State, idx: CorpusId) -> Result<(), Error> {
+        self.inner.on_add(state, idx)
+    }
+
+    fn on_evaluation<OT>(
+        &mut self,
+        state: &mut Self::State,
+        input: &<Self::State as UsesInput>::Input,
+        observers: &OT,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<Self::State>,
+    {
+        self.inner.on_evaluation(state, input, observers)
+    }
+
+    fn next(&mut self, state: &mut Self::State) -> Result<CorpusId, Error> {
+        let mut id = self.inner.next(state)?;
+
+        for _ in 0..MAX_RESCHEDULE_TRIES {
+            let is_demoted = state
+                .corpus()
+                .get(id)?
+                .borrow()
+                .has_metadata::<DemotedMetadata>();
+
+            if !is_demoted {
+                break;
+            }
+
+            id = self.inner.next(state)?;
+        }
+
+        Ok(id)
+    }
+
+    fn set_current_scheduled(
+        &mut self,
+        state: &mut Self::State,
+        next_idx: Option<CorpusId>,
+    ) -> Result<(), Error> {
+        self.inner.set_current_scheduled(state, next_idx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl::corpus::CorpusId;
+
+    use super::distill;
+
+    #[test_log::test]
+    fn test_distill_keeps_shortest_per_index() {
+        let a = CorpusId::from(0usize);
+        let b = CorpusId::from(1usize);
+        let c = CorpusId::from(2usize);
+
+        let kept = distill([
+            (a, 5, [1usize, 2, 3].as_slice()),
+            (b, 2, [1usize, 2].as_slice()),
+            (c, 7, [3usize].as_slice()),
+        ]);
+
+        assert!(kept.contains(&b));
+        assert!(kept.contains(&a));
+        assert!(!kept.contains(&c));
+    }
+
+    #[test_log::test]
+    fn test_distill_drops_entries_without_coverage() {
+        let a = CorpusId::from(0usize);
+        let b = CorpusId::from(1usize);
+
+        let kept = distill([(a, 1, [].as_slice()), (b, 1, [4usize].as_slice())]);
+
+        assert_eq!(kept.len(), 1);
+        assert!(kept.contains(&b));
+    }
+}
@@ -0,0 +1,192 @@
+//! Triage of executions which exceeded the execution timeout.
+//!
+//! A timeout is often caused by a slow machine or by a flaky PUT instead of a trace which really
+//! hangs. The [`HangFeedback`] therefore executes each timed out trace again in a forked process
+//! with a longer deadline. Only traces which exceed this deadline as well are stored, together with
+//! [`HangMetadata`], in the hangs directory of the campaign. They are not added to the objective
+//! corpus, which is reserved for crashes.
+//!
+//! Traces which consist of the same steps and the same function symbols at the roots of their
+//! recipes likely hang for the same reason. Only the first of them is re-executed and stored.
+
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{fs, mem};
+
+use chrono::Utc;
+use libafl::prelude::*;
+use libafl_bolts::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::algebra::Matcher;
+use crate::execution::{run_in_subprocess, ExecutionStatus, Runner, TraceRunner};
+use crate::protocol::ProtocolBehavior;
+use crate::trace::{Action, Trace};
+
+/// Describes a trace in the hangs directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HangMetadata {
+    /// Key under which similar hangs are deduplicated
+    pub key: String,
+    /// The execution timeout which was exceeded first
+    pub timeout: Duration,
+    /// The deadline which was exceeded when the trace was executed again
+    pub deadline: Duration,
+    /// When the hang was found, in RFC 3339 format
+    pub found: String,
+}
+
+/// Reports no objective, but stores traces which exceeded the timeout and also a longer deadline
+/// in `hangs_dir`.
+pub struct HangFeedback<PB: ProtocolBehavior> {
+    runner: Runner<PB>,
+    hangs_dir: PathBuf,
+    timeout: Duration,
+    deadline: Duration,
+}
+
+impl<PB: ProtocolBehavior> HangFeedback<PB> {
+    pub fn new(
+        runner: Runner<PB>,
+        hangs_dir: PathBuf,
+        timeout: Duration,
+        deadline: Duration,
+    ) -> Self {
+        Self {
+            runner,
+            hangs_dir,
+            timeout,
+            deadline,
+        }
+    }
+
+    fn triage(&self, trace: &Trace<PB::Matcher>) -> Result<(), Error> {
+        let key = format!("{:016x}", hang_key(trace));
+        let trace_path = self.hangs_dir.join(&key).with_extension("trace");
+
+        if trace_path.exists() {
+            log::debug!("Skipping triage of known hang {}", key);
+            return Ok(());
+        }
+
+        if !self.hangs(trace)? {
+            log::info!(
+                "Trace timed out after {:?}, but finished within {:?}",
+                self.timeout,
+                self.deadline
+            );
+            return Ok(());
+        }
+
+        log::warn!(
+            "Trace did not finish within {:?}, storing it as hang {}",
+            self.deadline,
+            key
+        );
+
+        let metadata = HangMetadata {
+            key,
+            timeout: self.timeout,
+            deadline: self.deadline,
+            found: Utc::now().to_rfc3339(),
+        };
+        write_hang(&trace_path, trace, &metadata)
+    }
+
+    /// Executes `trace` in a forked process and returns whether it exceeded the deadline.
+    fn hangs(&self, trace: &Trace<PB::Matcher>) -> Result<bool, Error> {
+        let status = run_in_subprocess(
+            || {
+                let _ = self.runner.execute(trace);
+            },
+            self.deadline,
+        )
+        .map_err(|err| Error::unknown(err.to_string()))?;
+
+        Ok(matches!(status, ExecutionStatus::Timeout))
+    }
+}
+
+fn write_hang<M: Matcher>(
+    trace_path: &Path,
+    trace: &Trace<M>,
+    metadata: &HangMetadata,
+) -> Result<(), Error> {
+    if let Some(hangs_dir) = trace_path.parent() {
+        fs::create_dir_all(hangs_dir)?;
+    }
+
+    trace.to_file(trace_path)?;
+    let metadata =
+        serde_json::to_string_pretty(metadata).map_err(|err| Error::serialize(err.to_string()))?;
+    write_file_atomic(trace_path.with_extension("json"), metadata.as_bytes())
+}
+
+/// Hashes the agents and the kinds of the steps of `trace`, together with the function symbols at
+/// the roots of the recipes.
+pub(crate) fn hang_key<M: Matcher>(trace: &Trace<M>) -> u64 {
+    let mut hasher = ahash::RandomState::with_seeds(0, 0, 0, 0).build_hasher();
+
+    for step in &trace.steps {
+        step.agent.hash(&mut hasher);
+        mem::discriminant(&step.action).hash(&mut hasher);
+        if let Action::Input(input) = &step.action {
+            input.recipe.name().hash(&mut hasher);
+        }
+    }
+
+    hasher.finish()
+}
+
+impl<PB: ProtocolBehavior> Named for HangFeedback<PB> {
+    fn name(&self) -> &str {
+        "HangFeedback"
+    }
+}
+
+impl<S, PB> Feedback<S> for HangFeedback<PB>
+where
+    S: State + UsesInput<Input = Trace<PB::Matcher>>,
+    PB: ProtocolBehavior,
+{
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        input: &S::Input,
+        _observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = S>,
+        OT: ObserversTuple<S>,
+    {
+        if *exit_kind == ExitKind::Timeout {
+            if let Err(err) = self.triage(input) {
+                log::error!("Failed to triage timeout: {}", err);
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::hang_key;
+    use crate::algebra::test_signature::setup_simple_trace;
+
+    #[test_log::test]
+    fn test_hang_key() {
+        let trace = setup_simple_trace();
+
+        let mut same_steps = trace.clone();
+        same_steps.descriptors.clear();
+        assert_eq!(hang_key(&trace), hang_key(&same_steps));
+
+        let mut fewer_steps = trace.clone();
+        fewer_steps.steps.pop();
+        assert_ne!(hang_key(&trace), hang_key(&fewer_steps));
+    }
+}
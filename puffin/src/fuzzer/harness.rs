@@ -1,45 +1,308 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
 use libafl::executors::ExitKind;
+use libafl_bolts::shmem::ShMem;
 use rand::Rng;
 
-use crate::error::Error;
-use crate::execution::{Runner, TraceRunner};
+use crate::algebra::Matcher;
+use crate::error::{Error, ErrorClass};
+use crate::execution::{run_in_subprocess, ExecutionStatus, ForkError, ParallelRunner, Runner};
+use crate::fuzzer::incremental::{prefix_hashes, PrefixCache};
+use crate::fuzzer::memory::{self, OOM_EXIT_CODE};
+use crate::fuzzer::state_graph::{record_transitions, state_graph_map};
 use crate::fuzzer::stats_stage::*;
 use crate::protocol::ProtocolBehavior;
-use crate::put_registry::PutRegistry;
-use crate::trace::{Action, Spawner, Trace};
+use crate::trace::{Action, StructuralHash, Trace, TraceContext};
+
+/// Timeout of an execution in a forked process.
+///
+/// It is below the `execution_timeout` of the
+/// [`TimeoutExecutor`](libafl::executors::TimeoutExecutor) such that a hanging child is killed
+/// before the fuzzer client itself is restarted.
+pub fn fork_timeout(execution_timeout: Duration) -> Duration {
+    execution_timeout * 4 / 5
+}
 
+/// Executes the trace in the fuzzer client.
+///
+/// Errors of the `objective_errors` classes and bugs of function symbols are reported like
+/// security violations, by aborting the client. Executions which exceed the [`memory`] limit are
+/// reported as [`ExitKind::Oom`].
 pub fn harness<PB: ProtocolBehavior + 'static>(
-    put_registry: &PutRegistry<PB>,
+    runner: &Runner<PB>,
     input: &Trace<PB::Matcher>,
+    objective_errors: &[ErrorClass],
 ) -> ExitKind {
-    let runner = Runner::new(put_registry.clone(), Spawner::new(put_registry.clone()));
+    update_trace_stats(input);
+
+    memory::begin_execution();
+    let exit_kind = execute(runner, input, objective_errors);
+    if memory::exceeded().is_some() {
+        return ExitKind::Oom;
+    }
+
+    exit_kind
+}
+
+/// Executes the trace in a forked process such that a crash of the PUT does not kill the fuzzer
+/// client.
+///
+/// The child copies the coverage of the execution from `edges_map` and the transitions from the
+/// [`state_graph_map`] into `shared_map`, which is in shared memory, before it exits. The parent
+/// then copies them back such that the observers see the coverage of the child. The coverage of a
+/// crashing execution is lost, as well as the error statistics of the child. A child which
+/// exceeded the [`memory`] limit exits with [`OOM_EXIT_CODE`].
+///
+/// With `prefixes`, traces which start with the same steps as the previous trace only execute
+/// their remaining steps, see [`incremental`](crate::fuzzer::incremental).
+///
+/// Returns an error if no child could be forked, in which case the trace was not executed.
+pub fn fork_harness<PB: ProtocolBehavior + 'static>(
+    runner: &Runner<PB>,
+    input: &Trace<PB::Matcher>,
+    objective_errors: &[ErrorClass],
+    edges_map: &mut [u8],
+    shared_map: &mut [u8],
+    timeout: Duration,
+    prefixes: Option<&mut PrefixCache>,
+) -> Result<ExitKind, ForkError> {
+    update_trace_stats(input);
+
+    let edges_len = edges_map.len().min(shared_map.len());
+    let edges_map = &mut edges_map[..edges_len];
+    let (shared_edges, shared_states) = shared_map.split_at_mut(edges_len);
 
-    TRACE_LENGTH.update(input.steps.len());
+    let mut execute_forked = || {
+        run_in_subprocess(
+            || {
+                memory::begin_execution();
+                execute(runner, input, objective_errors);
+                exit_child(edges_map, shared_edges, shared_states)
+            },
+            timeout,
+        )
+    };
+
+    let status = match prefixes {
+        Some(prefixes) => {
+            let hashes = prefix_hashes(input);
+            match prefixes.execute(input, &hashes, timeout) {
+                Some(status) => Ok(status),
+                None => {
+                    let status = execute_forked();
+                    prefixes.update(
+                        runner,
+                        input,
+                        hashes,
+                        objective_errors,
+                        edges_map,
+                        shared_edges,
+                        shared_states,
+                    );
+                    status
+                }
+            }
+        }
+        None => execute_forked(),
+    };
+
+    Ok(exit_kind(status?, edges_map, shared_edges, shared_states))
+}
+
+/// Executes batches of traces at the same time, each in a forked process of a [`ParallelRunner`].
+///
+/// The [`ParallelMutationalStage`](super::stages::ParallelMutationalStage) executes the mutants
+/// of a corpus entry with [`ParallelHarness::execute_batch`] before the fuzzer evaluates them one
+/// by one. Like in [`fork_harness`], each child copies its coverage into `shared_map`, but into
+/// its own slot. When the fuzzer evaluates a mutant of the batch, [`ParallelHarness::take`]
+/// copies the coverage of its slot back instead of executing the trace again.
+pub struct ParallelHarness<PB: ProtocolBehavior, SHM: ShMem> {
+    runner: ParallelRunner<PB>,
+    objective_errors: Vec<ErrorClass>,
+    shared_map: SHM,
+    /// The slot and the status of each trace of the last batch by its [`StructuralHash`]
+    results: HashMap<u64, (usize, ExecutionStatus)>,
+}
+
+impl<PB: ProtocolBehavior + 'static, SHM: ShMem> ParallelHarness<PB, SHM> {
+    /// `shared_map` has room for the edges map and the [`state_graph_map`] of each trace of a
+    /// batch.
+    pub fn new(
+        runner: ParallelRunner<PB>,
+        objective_errors: Vec<ErrorClass>,
+        shared_map: SHM,
+    ) -> Self {
+        Self {
+            runner,
+            objective_errors,
+            shared_map,
+            results: HashMap::new(),
+        }
+    }
+
+    /// Executes `traces` in parallel. The results of the previous batch are discarded. Traces for
+    /// which there is no slot left in the shared map are not executed.
+    pub fn execute_batch(&mut self, traces: &[Trace<PB::Matcher>], edges_map: &mut [u8]) {
+        self.results.clear();
+
+        let slot_len = Self::slot_len(edges_map);
+        let traces = &traces[..traces.len().min(self.shared_map.len() / slot_len)];
+        let objective_errors = &self.objective_errors;
+        let shared_map = self.shared_map.as_mut_slice();
+
+        let statuses = self
+            .runner
+            .execute_all_with(traces, |index, runner, trace| {
+                let slot = &mut shared_map[index * slot_len..(index + 1) * slot_len];
+                let (shared_edges, shared_states) = slot.split_at_mut(edges_map.len());
+
+                // The maps still contain the coverage of the last execution of the fuzzer client
+                edges_map.fill(0);
+                state_graph_map().fill(0);
+                memory::begin_execution();
+                execute(runner, trace, objective_errors);
+                copy_coverage(edges_map, shared_edges, shared_states)
+            });
+
+        for (index, (trace, status)) in traces.iter().zip(statuses).enumerate() {
+            match status {
+                Ok(status) => {
+                    self.results
+                        .insert(trace.structural_hash(), (index, status));
+                }
+                // The trace is executed by the fuzzer like any other trace
+                Err(err) => log::warn!("Failed to fork the execution of a trace: {}", err),
+            }
+        }
+    }
+
+    /// Returns the result of `input` if it was executed in the last batch and copies its coverage
+    /// into the maps. Each result is only returned once.
+    pub fn take(&mut self, input: &Trace<PB::Matcher>, edges_map: &mut [u8]) -> Option<ExitKind> {
+        let (index, status) = self.results.remove(&input.structural_hash())?;
+        update_trace_stats(input);
+
+        let slot_len = Self::slot_len(edges_map);
+        let slot = &self.shared_map.as_slice()[index * slot_len..(index + 1) * slot_len];
+        let (shared_edges, shared_states) = slot.split_at(edges_map.len());
+        Some(exit_kind(status, edges_map, shared_edges, shared_states))
+    }
+
+    fn slot_len(edges_map: &[u8]) -> usize {
+        (edges_map.len() + state_graph_map().len()).max(1)
+    }
+}
+
+/// Copies the coverage of a forked execution into the shared map and exits the child.
+pub(crate) fn exit_child(edges_map: &[u8], shared_edges: &mut [u8], shared_states: &mut [u8]) -> ! {
+    let code = copy_coverage(edges_map, shared_edges, shared_states);
+    unsafe { libc::_exit(code) }
+}
+
+/// Copies the coverage of a forked execution into the shared map and returns the exit code of the
+/// child.
+fn copy_coverage(edges_map: &[u8], shared_edges: &mut [u8], shared_states: &mut [u8]) -> i32 {
+    let states_len = state_graph_map().len().min(shared_states.len());
+    shared_edges.copy_from_slice(edges_map);
+    shared_states[..states_len].copy_from_slice(&state_graph_map()[..states_len]);
+
+    if memory::exceeded().is_some() {
+        return OOM_EXIT_CODE;
+    }
 
-    for step in &input.steps {
+    0
+}
+
+/// Copies the coverage of a forked execution back from the shared map, see [`fork_harness`].
+fn exit_kind(
+    status: ExecutionStatus,
+    edges_map: &mut [u8],
+    shared_edges: &[u8],
+    shared_states: &[u8],
+) -> ExitKind {
+    let states_len = state_graph_map().len().min(shared_states.len());
+
+    match status {
+        ExecutionStatus::Success => {
+            edges_map.copy_from_slice(shared_edges);
+            state_graph_map()[..states_len].copy_from_slice(&shared_states[..states_len]);
+            ExitKind::Ok
+        }
+        ExecutionStatus::Timeout => ExitKind::Timeout,
+        ExecutionStatus::Crashed => ExitKind::Crash,
+        ExecutionStatus::Failure(OOM_EXIT_CODE) => {
+            // The coverage is still copied, because the child finished the execution
+            edges_map.copy_from_slice(shared_edges);
+            state_graph_map()[..states_len].copy_from_slice(&shared_states[..states_len]);
+            ExitKind::Oom
+        }
+        status @ (ExecutionStatus::Interrupted | ExecutionStatus::Failure(_)) => {
+            log::warn!("Forked execution finished with status {:?}", status);
+            ExitKind::Crash
+        }
+    }
+}
+
+fn update_trace_stats<M: Matcher>(trace: &Trace<M>) {
+    TRACE_LENGTH.update(trace.steps.len());
+
+    for step in &trace.steps {
         match &step.action {
             Action::Input(input) => {
                 TERM_SIZE.update(input.recipe.size());
             }
-            Action::Output(_) => {}
+            Action::Output(_)
+            | Action::Expect(_)
+            | Action::Reset(_)
+            | Action::Renegotiate(_)
+            | Action::KeyUpdate(_) => {}
         }
     }
+}
 
-    if let Err(err) = runner.execute(input) {
-        match &err {
-            Error::Fn(_) => FN_ERROR.increment(),
-            Error::Term(_e) => TERM.increment(),
-            Error::Put(_) => PUT.increment(),
-            Error::IO(_) => IO.increment(),
-            Error::Agent(_) => AGENT.increment(),
-            Error::Stream(_) => STREAM.increment(),
-            Error::Extraction() => EXTRACTION.increment(),
-            Error::SecurityClaim(msg) => {
-                log::warn!("{}", msg);
+fn execute<PB: ProtocolBehavior + 'static>(
+    runner: &Runner<PB>,
+    input: &Trace<PB::Matcher>,
+    objective_errors: &[ErrorClass],
+) -> ExitKind {
+    let (ctx, result) = runner.execute_with_context(input);
+    finish(ctx, result, objective_errors)
+}
+
+/// Records the statistics of an execution. Errors of the `objective_errors` classes, bugs and
+/// security violations abort the process.
+pub(crate) fn finish<PB: ProtocolBehavior + 'static>(
+    ctx: TraceContext<PB>,
+    result: Result<(), Error>,
+    objective_errors: &[ErrorClass],
+) -> ExitKind {
+    record_transitions(ctx.counters(), state_graph_map());
+    drop(ctx);
+
+    if let Err(err) = result {
+        match err.class() {
+            ErrorClass::Fn => FN_ERROR.increment(),
+            ErrorClass::Term => TERM.increment(),
+            ErrorClass::Put => PUT.increment(),
+            ErrorClass::IO => IO.increment(),
+            ErrorClass::Codec => CODEC.increment(),
+            ErrorClass::Agent => AGENT.increment(),
+            ErrorClass::Stream => STREAM.increment(),
+            ErrorClass::Extraction => EXTRACTION.increment(),
+            ErrorClass::SecurityClaim | ErrorClass::PutCrash => {
+                log::warn!("{}", err);
                 std::process::abort()
             }
         }
 
+        // Errors of malformed terms are expected during fuzzing, but bugs of function symbols
+        // are not
+        if err.is_bug() || objective_errors.contains(&err.class()) {
+            log::warn!("Objective error: {}", err);
+            std::process::abort()
+        }
+
         log::trace!("{}", err);
     }
 
@@ -57,3 +320,79 @@ pub fn dummy_harness<PB: ProtocolBehavior + 'static>(_input: &Trace<PB::Matcher>
     }
     ExitKind::Ok // Everything other than Ok is recorded in the crash corpus
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use libafl::executors::ExitKind;
+    use libafl_bolts::shmem::{ShMemProvider, StdShMemProvider};
+
+    use super::ParallelHarness;
+    use crate::agent::{AgentDescriptor, AgentName, TLSVersion};
+    use crate::algebra::test_signature::*;
+    use crate::algebra::AnyMatcher;
+    use crate::execution::{ParallelRunner, Runner};
+    use crate::fuzzer::state_graph::state_graph_map;
+    use crate::put_registry::PutRegistry;
+    use crate::trace::{OutputAction, Spawner, Step, Trace, TraceHook};
+
+    /// Crashes before the second step
+    struct CrashAtSecondStep;
+
+    impl TraceHook<TestProtocolBehavior> for CrashAtSecondStep {
+        fn on_step_start(&self, index: usize, _step: &Step<AnyMatcher>) {
+            if index == 1 {
+                unsafe {
+                    libc::raise(libc::SIGSEGV);
+                }
+            }
+        }
+    }
+
+    fn outputs(outputs: usize) -> TestTrace {
+        let server = AgentName::first();
+        Trace {
+            descriptors: vec![AgentDescriptor::new_server(server, TLSVersion::V1_3)],
+            prior_traces: vec![],
+            steps: vec![OutputAction::new_step(server); outputs],
+        }
+    }
+
+    #[test_log::test]
+    fn test_parallel_harness_returns_the_results_of_the_batch() {
+        let registry = PutRegistry::<TestProtocolBehavior>::new(
+            [("TESTSTUB_IDLE_PUT", Box::new(IdleFactory) as _)],
+            "TESTSTUB_IDLE_PUT",
+        )
+        .unwrap();
+        let runner = ParallelRunner::new(
+            Runner::new(registry.clone(), Spawner::new(registry))
+                .with_trace_hook(CrashAtSecondStep),
+            2,
+        )
+        .with_timeout(Duration::from_secs(5));
+
+        let mut edges = vec![1; 8];
+        // Room for two traces
+        let shared_map = StdShMemProvider::new()
+            .unwrap()
+            .new_shmem(2 * (edges.len() + state_graph_map().len()))
+            .unwrap();
+        let mut harness = ParallelHarness::new(runner, vec![], shared_map);
+
+        let (successful, crashing, skipped) = (outputs(1), outputs(2), outputs(3));
+        harness.execute_batch(
+            &[successful.clone(), crashing.clone(), skipped.clone()],
+            &mut edges,
+        );
+
+        assert_eq!(harness.take(&crashing, &mut edges), Some(ExitKind::Crash));
+        assert_eq!(harness.take(&successful, &mut edges), Some(ExitKind::Ok));
+        // The coverage of the child replaced the coverage of the parent
+        assert_eq!(edges, vec![0; 8]);
+
+        assert_eq!(harness.take(&successful, &mut edges), None);
+        assert_eq!(harness.take(&skipped, &mut edges), None);
+    }
+}
@@ -0,0 +1,583 @@
+//! Incremental execution of traces which share their first steps with the previous input.
+//!
+//! A mutational stage mutates the same corpus entry several times in a row, and a mutation often
+//! only changes the later steps of a trace. When two consecutive inputs share a prefix of at least
+//! `min_prefix` steps, the [`PrefixCache`] forks a *prefix process* which executes the prefix once
+//! and then waits for traces. For each following input which starts with the same prefix, the
+//! prefix process executes the remaining steps like [`Runner::execute_suffixes`], i.e. in a child
+//! which snapshots the PUTs, knowledge and claims after the prefix. The prefix process also
+//! enforces the timeout of the child.
+//!
+//! Prefixes are compared through their [`prefix_hashes`]. The children report their coverage
+//! through the shared map like the children of
+//! [`fork_harness`](super::harness::fork_harness). They inherit the coverage of the prefix from
+//! the prefix process, such that the observers see the same coverage as for a full execution.
+
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+use nix::sys::signal::{kill, Signal};
+use nix::sys::wait::waitpid;
+use nix::unistd::{fork, ForkResult, Pid};
+
+use crate::algebra::Matcher;
+use crate::error::ErrorClass;
+use crate::execution::{reset_crash_handlers, ExecutionStatus, Runner};
+use crate::fuzzer::harness::{exit_child, finish};
+use crate::fuzzer::memory;
+use crate::fuzzer::state_graph::state_graph_map;
+use crate::protocol::ProtocolBehavior;
+use crate::trace::Trace;
+
+/// How long the prefix process may take to report a child in addition to its timeout
+const REPLY_GRACE: Duration = Duration::from_secs(1);
+
+/// Hashes of the prefixes of `trace`. The hash at index `i` covers the agents, the prior traces
+/// and the steps up to and including step `i`.
+pub fn prefix_hashes<M: Matcher>(trace: &Trace<M>) -> Vec<u64> {
+    let state = ahash::RandomState::with_seeds(0, 0, 0, 0);
+
+    let mut hasher = state.build_hasher();
+    trace.descriptors.hash(&mut hasher);
+    trace.prior_traces.hash(&mut hasher);
+    let mut previous = hasher.finish();
+
+    trace
+        .steps
+        .iter()
+        .map(|step| {
+            let mut hasher = state.build_hasher();
+            previous.hash(&mut hasher);
+            step.hash(&mut hasher);
+            previous = hasher.finish();
+            previous
+        })
+        .collect()
+}
+
+/// Keeps a prefix process for the prefix which the last inputs had in common.
+pub struct PrefixCache {
+    min_prefix: usize,
+    /// Prefix hashes of the previous input
+    previous: Vec<u64>,
+    process: Option<PrefixProcess>,
+    /// Hash of the last prefix whose process failed
+    failed: Option<u64>,
+}
+
+struct PrefixProcess {
+    pid: Pid,
+    channel: UnixStream,
+    /// Number of steps of the prefix
+    len: usize,
+    hash: u64,
+}
+
+impl PrefixCache {
+    pub fn new(min_prefix: usize) -> Self {
+        Self {
+            min_prefix: min_prefix.max(1),
+            previous: vec![],
+            process: None,
+            failed: None,
+        }
+    }
+
+    /// Number of steps of the prefix which is currently cached
+    pub fn prefix_len(&self) -> Option<usize> {
+        self.process.as_ref().map(|process| process.len)
+    }
+
+    /// Executes the steps of `input` after the cached prefix. Returns `None` if `input` does not
+    /// start with the cached prefix or the prefix process failed. In that case the input needs to
+    /// be executed fully.
+    pub fn execute<M: Matcher>(
+        &mut self,
+        input: &Trace<M>,
+        hashes: &[u64],
+        timeout: Duration,
+    ) -> Option<ExecutionStatus> {
+        let process = self.process.as_mut()?;
+        if hashes.get(process.len - 1) != Some(&process.hash) {
+            return None;
+        }
+
+        match process.execute(input, timeout) {
+            Ok(status) => Some(status),
+            Err(err) => {
+                log::warn!("Prefix process of {} steps failed: {}", process.len, err);
+                self.failed = Some(process.hash);
+                self.stop();
+                None
+            }
+        }
+    }
+
+    /// Remembers the `hashes` of a fully executed `input`. If it shares at least `min_prefix`
+    /// steps with the previous input, a prefix process for the shared steps replaces the current
+    /// one.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update<PB: ProtocolBehavior + 'static>(
+        &mut self,
+        runner: &Runner<PB>,
+        input: &Trace<PB::Matcher>,
+        hashes: Vec<u64>,
+        objective_errors: &[ErrorClass],
+        edges_map: &mut [u8],
+        shared_edges: &mut [u8],
+        shared_states: &mut [u8],
+    ) {
+        let common = self
+            .previous
+            .iter()
+            .zip(&hashes)
+            .take_while(|(previous, hash)| previous == hash)
+            .count();
+        self.previous = hashes;
+
+        if common < self.min_prefix {
+            return;
+        }
+
+        let hash = self.previous[common - 1];
+        if self.failed == Some(hash)
+            || self.process.as_ref().map(|process| process.hash) == Some(hash)
+        {
+            return;
+        }
+
+        self.stop();
+
+        let prefix = Trace {
+            descriptors: input.descriptors.clone(),
+            prior_traces: input.prior_traces.clone(),
+            steps: input.steps[..common].to_vec(),
+        };
+
+        match PrefixProcess::spawn(
+            runner,
+            &prefix,
+            hash,
+            objective_errors,
+            edges_map,
+            shared_edges,
+            shared_states,
+        ) {
+            Ok(process) => self.process = Some(process),
+            Err(err) => log::warn!("Failed to fork prefix process: {}", err),
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(process) = self.process.take() {
+            let _ = kill(process.pid, Signal::SIGKILL);
+            let _ = waitpid(process.pid, None);
+        }
+    }
+}
+
+impl Drop for PrefixCache {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl PrefixProcess {
+    fn spawn<PB: ProtocolBehavior + 'static>(
+        runner: &Runner<PB>,
+        prefix: &Trace<PB::Matcher>,
+        hash: u64,
+        objective_errors: &[ErrorClass],
+        edges_map: &mut [u8],
+        shared_edges: &mut [u8],
+        shared_states: &mut [u8],
+    ) -> io::Result<Self> {
+        let (channel, child_channel) = UnixStream::pair()?;
+
+        match unsafe { fork() }? {
+            ForkResult::Parent { child } => Ok(Self {
+                pid: child,
+                channel,
+                len: prefix.steps.len(),
+                hash,
+            }),
+            ForkResult::Child => {
+                drop(channel);
+                // A crash of the prefix process is reported by the client, not by the handlers
+                // which the prefix process inherited from it
+                reset_crash_handlers();
+                serve(
+                    runner,
+                    prefix,
+                    child_channel,
+                    objective_errors,
+                    edges_map,
+                    shared_edges,
+                    shared_states,
+                )
+            }
+        }
+    }
+
+    /// Sends `input` to the prefix process and waits for the status of its execution.
+    fn execute<M: Matcher>(
+        &mut self,
+        input: &Trace<M>,
+        timeout: Duration,
+    ) -> io::Result<ExecutionStatus> {
+        let encoded = input
+            .serialize_postcard()
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?;
+
+        self.channel.set_read_timeout(Some(timeout + REPLY_GRACE))?;
+        self.channel
+            .write_all(&(encoded.len() as u32).to_le_bytes())?;
+        self.channel.write_all(&encoded)?;
+        self.channel
+            .write_all(&(timeout.as_millis() as u64).to_le_bytes())?;
+
+        read_status(&mut self.channel)
+    }
+}
+
+/// Main loop of the prefix process: executes `prefix`, then the remaining steps of every received
+/// trace, see [`Runner::execute_prefix`].
+fn serve<PB: ProtocolBehavior + 'static>(
+    runner: &Runner<PB>,
+    prefix: &Trace<PB::Matcher>,
+    mut channel: UnixStream,
+    objective_errors: &[ErrorClass],
+    edges_map: &mut [u8],
+    shared_edges: &mut [u8],
+    shared_states: &mut [u8],
+) -> ! {
+    edges_map.fill(0);
+    state_graph_map().fill(0);
+
+    let mut prefix_ctx = match runner.execute_prefix(prefix) {
+        Ok(prefix_ctx) => prefix_ctx,
+        Err(err) => {
+            log::debug!("Prefix failed: {}", err);
+            unsafe { libc::_exit(1) }
+        }
+    };
+
+    loop {
+        let Ok((trace, timeout)) = read_trace::<PB::Matcher>(&mut channel) else {
+            unsafe { libc::_exit(0) }
+        };
+
+        // The peak memory of the child starts from the memory of the prefix process
+        memory::begin_execution();
+        let suffix = trace.steps.get(prefix.steps.len()..).unwrap_or_default();
+        let status = prefix_ctx
+            .execute_suffix(suffix, Some(timeout), |ctx, result| {
+                finish(ctx, result, objective_errors);
+                exit_child(edges_map, shared_edges, shared_states)
+            })
+            .map_err(|err| log::warn!("Failed to execute suffix: {}", err))
+            .ok();
+
+        if write_status(&mut channel, status).is_err() {
+            unsafe { libc::_exit(0) }
+        }
+    }
+}
+
+/// Reads a trace and the timeout of its execution
+fn read_trace<M: Matcher>(channel: &mut UnixStream) -> io::Result<(Trace<M>, Duration)> {
+    let mut len = [0; 4];
+    channel.read_exact(&mut len)?;
+    let mut encoded = vec![0; u32::from_le_bytes(len) as usize];
+    channel.read_exact(&mut encoded)?;
+    let mut timeout = [0; 8];
+    channel.read_exact(&mut timeout)?;
+
+    let trace = Trace::deserialize_postcard(&encoded)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+    Ok((trace, Duration::from_millis(u64::from_le_bytes(timeout))))
+}
+
+fn read_i32(channel: &mut UnixStream) -> io::Result<i32> {
+    let mut bytes = [0; 4];
+    channel.read_exact(&mut bytes)?;
+    Ok(i32::from_le_bytes(bytes))
+}
+
+fn write_status(channel: &mut UnixStream, status: Option<ExecutionStatus>) -> io::Result<()> {
+    let (tag, code) = match status {
+        Some(ExecutionStatus::Success) => (0u8, 0),
+        Some(ExecutionStatus::Timeout) => (1, 0),
+        Some(ExecutionStatus::Crashed) => (2, 0),
+        Some(ExecutionStatus::Interrupted) => (3, 0),
+        Some(ExecutionStatus::Failure(code)) => (4, code),
+        None => (5, 0),
+    };
+
+    channel.write_all(&[tag])?;
+    channel.write_all(&code.to_le_bytes())
+}
+
+fn read_status(channel: &mut UnixStream) -> io::Result<ExecutionStatus> {
+    let mut tag = [0; 1];
+    channel.read_exact(&mut tag)?;
+    let code = read_i32(channel)?;
+
+    match tag[0] {
+        0 => Ok(ExecutionStatus::Success),
+        1 => Ok(ExecutionStatus::Timeout),
+        2 => Ok(ExecutionStatus::Crashed),
+        3 => Ok(ExecutionStatus::Interrupted),
+        4 => Ok(ExecutionStatus::Failure(code)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::Other,
+            "failed to retrieve process status",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Read;
+    use std::os::fd::AsRawFd;
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use std::time::Duration;
+
+    use nix::sys::signal::{kill, signal, SigHandler, Signal};
+    use nix::sys::wait::waitpid;
+    use nix::unistd::pipe;
+
+    use super::{prefix_hashes, PrefixCache};
+    use crate::agent::{AgentDescriptor, AgentName, TLSVersion};
+    use crate::algebra::test_signature::*;
+    use crate::algebra::AnyMatcher;
+    use crate::error::ErrorClass;
+    use crate::execution::{ExecutionStatus, Runner};
+    use crate::put_registry::PutRegistry;
+    use crate::trace::{OutputAction, Spawner, Step, Trace, TraceHook};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Sleeps before the step at the given index
+    struct SleepAt(usize);
+
+    impl TraceHook<TestProtocolBehavior> for SleepAt {
+        fn on_step_start(&self, index: usize, _step: &Step<AnyMatcher>) {
+            if index == self.0 {
+                std::thread::sleep(Duration::from_secs(30));
+            }
+        }
+    }
+
+    /// Crashes before the step at the given index
+    struct CrashAt(usize);
+
+    impl TraceHook<TestProtocolBehavior> for CrashAt {
+        fn on_step_start(&self, index: usize, _step: &Step<AnyMatcher>) {
+            if index == self.0 {
+                unsafe {
+                    libc::raise(libc::SIGSEGV);
+                }
+            }
+        }
+    }
+
+    fn idle_runner() -> Runner<TestProtocolBehavior> {
+        let registry = PutRegistry::<TestProtocolBehavior>::new(
+            [("TESTSTUB_IDLE_PUT", Box::new(IdleFactory) as _)],
+            "TESTSTUB_IDLE_PUT",
+        )
+        .unwrap();
+        Runner::new(registry.clone(), Spawner::new(registry))
+    }
+
+    /// A trace of `outputs` output steps of the only agent, followed by an output of a missing
+    /// agent if `failing`
+    fn outputs(outputs: usize, failing: bool) -> TestTrace {
+        let server = AgentName::first();
+        let mut steps = vec![OutputAction::new_step(server); outputs];
+        if failing {
+            steps.push(OutputAction::new_step(server.next()));
+        }
+
+        Trace {
+            descriptors: vec![AgentDescriptor::new_server(server, TLSVersion::V1_3)],
+            prior_traces: vec![],
+            steps,
+        }
+    }
+
+    /// Caches the prefix of `trace` in `cache`, like for two consecutive full executions
+    fn cache_prefix(
+        cache: &mut PrefixCache,
+        runner: &Runner<TestProtocolBehavior>,
+        trace: &TestTrace,
+        objective_errors: &[ErrorClass],
+    ) {
+        let (mut edges, mut shared_edges, mut shared_states) = (vec![0; 8], vec![0; 8], vec![0]);
+        for _ in 0..2 {
+            cache.update(
+                runner,
+                trace,
+                prefix_hashes(trace),
+                objective_errors,
+                &mut edges,
+                &mut shared_edges,
+                &mut shared_states,
+            );
+        }
+    }
+
+    fn execute(
+        cache: &mut PrefixCache,
+        trace: &TestTrace,
+        timeout: Duration,
+    ) -> Option<ExecutionStatus> {
+        cache.execute(trace, &prefix_hashes(trace), timeout)
+    }
+
+    #[test_log::test]
+    fn test_prefix_hashes_diverge_after_mutated_step() {
+        let trace = setup_simple_trace();
+        let hashes = prefix_hashes(&trace);
+        assert_eq!(hashes.len(), trace.steps.len());
+        assert_eq!(hashes, prefix_hashes(&trace.clone()));
+
+        let mut mutated = trace.clone();
+        mutated.steps.swap(1, 2);
+        let mutated_hashes = prefix_hashes(&mutated);
+        assert_eq!(hashes[0], mutated_hashes[0]);
+        assert!(hashes[1..]
+            .iter()
+            .zip(&mutated_hashes[1..])
+            .all(|(hash, mutated)| hash != mutated));
+
+        let mut truncated = trace;
+        truncated.steps.pop();
+        assert!(hashes.starts_with(&prefix_hashes(&truncated)));
+    }
+
+    #[test_log::test]
+    fn test_prefix_cache_executes_suffixes() {
+        let runner = idle_runner();
+        let mut cache = PrefixCache::new(2);
+        cache_prefix(
+            &mut cache,
+            &runner,
+            &outputs(2, false),
+            &[ErrorClass::Agent],
+        );
+        assert_eq!(cache.prefix_len(), Some(2));
+
+        assert_eq!(
+            execute(&mut cache, &outputs(3, false), TIMEOUT),
+            Some(ExecutionStatus::Success)
+        );
+        // The missing agent is an objective
+        assert_eq!(
+            execute(&mut cache, &outputs(2, true), TIMEOUT),
+            Some(ExecutionStatus::Crashed)
+        );
+        assert_eq!(
+            execute(&mut cache, &outputs(4, false), TIMEOUT),
+            Some(ExecutionStatus::Success)
+        );
+
+        // A trace with another prefix is executed fully
+        let mut other = outputs(3, false);
+        other.descriptors[0].try_reuse = true;
+        assert_eq!(execute(&mut cache, &other, TIMEOUT), None);
+        assert_eq!(cache.prefix_len(), Some(2));
+    }
+
+    #[test_log::test]
+    fn test_prefix_cache_reports_timeouts() {
+        let runner = idle_runner().with_trace_hook(SleepAt(3));
+        let mut cache = PrefixCache::new(2);
+        cache_prefix(&mut cache, &runner, &outputs(2, false), &[]);
+
+        assert_eq!(
+            execute(&mut cache, &outputs(4, false), Duration::from_millis(200)),
+            Some(ExecutionStatus::Timeout)
+        );
+        // The prefix process is still available
+        assert_eq!(
+            execute(&mut cache, &outputs(3, false), TIMEOUT),
+            Some(ExecutionStatus::Success)
+        );
+        assert_eq!(cache.prefix_len(), Some(2));
+    }
+
+    #[test_log::test]
+    fn test_prefix_cache_forgets_failed_prefixes() {
+        let runner = idle_runner();
+        let mut cache = PrefixCache::new(2);
+        let failing = outputs(1, true);
+        cache_prefix(&mut cache, &runner, &failing, &[]);
+        assert_eq!(cache.prefix_len(), Some(2));
+
+        let mut longer = failing.clone();
+        longer
+            .steps
+            .push(OutputAction::new_step(AgentName::first()));
+        assert_eq!(execute(&mut cache, &longer, TIMEOUT), None);
+        assert_eq!(cache.prefix_len(), None);
+
+        // The prefix is not forked again
+        cache_prefix(&mut cache, &runner, &failing, &[]);
+        assert_eq!(cache.prefix_len(), None);
+    }
+
+    #[test_log::test]
+    fn test_prefix_process_crashes_with_default_handlers() {
+        /// Write end of the pipe to which the crash handler of the client writes
+        static HANDLED: AtomicI32 = AtomicI32::new(-1);
+
+        // Stands in for the crash handler of LibAFL, which the prefix process must not run
+        extern "C" fn report_handled(_: libc::c_int) {
+            unsafe {
+                libc::write(HANDLED.load(Ordering::SeqCst), b"!".as_ptr().cast(), 1);
+                libc::_exit(0)
+            }
+        }
+
+        let (reader, writer) = pipe().unwrap();
+        unsafe {
+            libc::fcntl(reader.as_raw_fd(), libc::F_SETFL, libc::O_NONBLOCK);
+        }
+        HANDLED.store(writer.as_raw_fd(), Ordering::SeqCst);
+
+        unsafe {
+            signal(Signal::SIGSEGV, SigHandler::Handler(report_handled)).unwrap();
+        }
+        let runner = idle_runner().with_trace_hook(CrashAt(1));
+        let mut cache = PrefixCache::new(2);
+        cache_prefix(&mut cache, &runner, &outputs(2, false), &[]);
+        let status = execute(&mut cache, &outputs(3, false), TIMEOUT);
+        unsafe {
+            signal(Signal::SIGSEGV, SigHandler::SigDfl).unwrap();
+        }
+
+        assert_eq!(status, None);
+        let mut handled = vec![];
+        let _ = File::from(reader).read_to_end(&mut handled);
+        assert!(handled.is_empty());
+    }
+
+    #[test_log::test]
+    fn test_prefix_cache_stops_after_prefix_process_died() {
+        let runner = idle_runner();
+        let mut cache = PrefixCache::new(2);
+        cache_prefix(&mut cache, &runner, &outputs(2, false), &[]);
+
+        let pid = cache.process.as_ref().unwrap().pid;
+        kill(pid, Signal::SIGKILL).unwrap();
+        waitpid(pid, None).unwrap();
+
+        assert_eq!(execute(&mut cache, &outputs(3, false), TIMEOUT), None);
+        assert_eq!(cache.prefix_len(), None);
+    }
+}
@@ -1,6 +1,11 @@
 use core::time::Duration;
+use std::cell::RefCell;
 use std::fmt;
+use std::hash::Hash;
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Instant;
 
 use libafl::corpus::ondisk::OnDiskMetadataFormat;
 use libafl::prelude::*;
@@ -8,36 +13,122 @@ use libafl_bolts::prelude::*;
 use log4rs::Handle;
 
 use super::harness;
-use crate::fuzzer::mutations::trace_mutations;
+use crate::agent::AgentName;
+use crate::error::ErrorClass;
+use crate::execution::{ParallelRunner, Runner};
+use crate::fuzzer::campaign::{CampaignConfig, CampaignFeedback};
+use crate::fuzzer::checkpoint::{self, CheckpointConfig, CheckpointStage};
+use crate::fuzzer::crash_buckets::{capture_crash_context, CrashBucketStage, CrashTriage};
+use crate::fuzzer::dedup::DedupFeedback;
+use crate::fuzzer::distillation::{DistillationStage, DistilledScheduler};
+use crate::fuzzer::hangs::{hang_key, HangFeedback};
+use crate::fuzzer::incremental::PrefixCache;
+use crate::fuzzer::memory::{self, MemoryLimitFeedback};
 use crate::fuzzer::mutations::util::TermConstraints;
+use crate::fuzzer::mutations::{trace_mutations, MutationWeights};
+use crate::fuzzer::sanitizer::asan::install_report_callback;
+use crate::fuzzer::sanitizer::feedback::AsanFeedback;
+use crate::fuzzer::signature_check::check_corpus_signature;
+use crate::fuzzer::stages::{ParallelBatch, ParallelMutationalStage, PuffinScheduledMutator};
+use crate::fuzzer::state_graph::{
+    state_graph_map, STATE_GRAPH_FEEDBACK_NAME, STATE_GRAPH_OBSERVER_NAME,
+};
 use crate::fuzzer::stats_monitor::StatsMonitor;
+use crate::fuzzer::stats_stage::{StatsStage, SYMBOL_EVALUATION};
+use crate::fuzzer::sync::{SyncConfig, SyncStage};
+use crate::fuzzer::trace_file::{prepare_initial_corpus, MIGRATED_DIR};
+use crate::fuzzer::{crashes, minimize};
 use crate::log::{config_fuzzing, config_fuzzing_client};
 use crate::protocol::ProtocolBehavior;
+use crate::put::PutDescriptor;
 use crate::put_registry::PutRegistry;
-use crate::trace::Trace;
+use crate::rng;
+use crate::trace::{KnowledgeRetention, Spawner, StructuralHash, Trace};
 
 pub const MAP_FEEDBACK_NAME: &str = "edges";
 const EDGES_OBSERVER_NAME: &str = "edges_observer";
 
+/// How often progress is reported to the broker if fuzzing for a limited duration
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(15);
+
 type ConcreteExecutor<'harness, H, OT, S> = TimeoutExecutor<InProcessExecutor<'harness, H, OT, S>>;
 
 type ConcreteState<C, R, SC, I> = StdState<I, C, R, SC>;
 
 #[derive(Clone, Debug)]
 pub struct FuzzerConfig {
+    /// The PUT which all agents use
+    pub put: PutDescriptor,
+    /// Agents which use another factory of the registry than `put`, e.g. to compare two versions
+    /// of a library within one trace. They are configured with the options of `put`.
+    pub agent_puts: Vec<(AgentName, String)>,
     pub initial_corpus_dir: PathBuf,
     pub static_seed: Option<u64>,
+    /// Master seed of all random number generators, see [`rng`](crate::rng). `static_seed`
+    /// takes precedence for the fuzzer clients.
+    pub rng_seed: Option<u64>,
     pub max_iters: Option<u64>,
+    /// Fuzzing stops after this duration. `None` fuzzes until the fuzzer is stopped.
+    pub max_duration: Option<Duration>,
     pub core_definition: String,
     pub stats_file: PathBuf,
     pub corpus_dir: PathBuf,
     pub objective_dir: PathBuf,
+    /// Traces which exceed the `hang_deadline` are stored in this directory, see
+    /// [`HangFeedback`].
+    pub hangs_dir: PathBuf,
+    /// A minimized representative of each bucket of crashes is stored in this directory, see
+    /// [`CrashBucketStage`].
+    pub buckets_dir: PathBuf,
     pub broker_port: u16,
+    /// The broker connects to the broker at this address, which usually runs on another machine.
+    /// Both brokers then exchange interesting testcases and the coverage of their clients.
+    pub remote_broker: Option<SocketAddr>,
     pub minimizer: bool, // FIXME: support this property
+    /// How often the corpus is distilled during the campaign. `None` disables the distillation.
+    pub distillation_interval: Option<Duration>,
+    /// Executes each seed twice before fuzzing and warns about seeds with which the PUT is not
+    /// deterministic, see [`Trace::execute_deterministic`].
+    pub check_determinism: bool,
+    /// Exchanges corpus entries with other fuzzers through a shared directory. `None` disables
+    /// the synchronization.
+    pub sync: Option<SyncConfig>,
+    /// Periodically writes the state of each client to a directory. `None` disables the
+    /// checkpoints, see [`checkpoint`].
+    pub checkpoint: Option<CheckpointConfig>,
+    /// The clients continue from their checkpoint in the directory of `checkpoint` if there is one
+    pub resume: bool,
     pub mutation_stage_config: MutationStageConfig,
     pub mutation_config: MutationConfig,
     pub tui: bool,
     pub no_launcher: bool,
+    /// Executes each trace in a forked process. A crash of the PUT is then reported as objective
+    /// instead of restarting the fuzzer client.
+    pub fork: bool,
+    /// Traces which share at least this many steps with the previous trace only execute their
+    /// remaining steps, see [`incremental`](super::incremental). Requires `fork`.
+    pub incremental: Option<usize>,
+    /// The mutants of a corpus entry are executed in batches of this size, each mutant in its own
+    /// forked process at the same time, see [`ParallelMutationalStage`]. Requires `fork`.
+    pub jobs: usize,
+    /// Executions which take longer are reported as timeouts.
+    pub execution_timeout: Duration,
+    /// Traces which timed out are executed again with this deadline to triage hangs.
+    pub hang_deadline: Duration,
+    /// Executions which fail with an error of these classes are reported as objectives.
+    pub objective_errors: Vec<ErrorClass>,
+    /// Executions which allocate more MiB are reported as out-of-memory objectives, see
+    /// [`memory`].
+    pub memory_limit: Option<u64>,
+    /// Knowledge of the trace context is evicted if a source sent more messages, see
+    /// [`KnowledgeRetention`].
+    pub max_knowledge_per_source: Option<usize>,
+    /// Measures how often and how long each symbol is evaluated, see [`SYMBOL_EVALUATION`].
+    pub symbol_stats: bool,
+    /// The metrics are additionally sent to the statsd daemon at this address.
+    pub statsd_address: Option<String>,
+    /// The latest metrics are additionally written to this file in the Prometheus text format.
+    pub prometheus_file: Option<PathBuf>,
     pub log_file: PathBuf,
 }
 
@@ -64,10 +155,14 @@ pub struct MutationConfig {
     pub fresh_zoo_after: u64,
     pub max_trace_length: usize,
     pub min_trace_length: usize,
+    /// Above this sum of the term sizes of all recipes we no longer grow a trace. Larger traces
+    /// are truncated.
+    pub max_trace_size: usize,
     /// Below this term size we no longer mutate. Note that it is possible to reach
     /// smaller terms by having a mutation which removes all symbols in a single mutation.
     /// Above this term size we no longer mutate.
     pub term_constraints: TermConstraints,
+    pub weights: MutationWeights,
 }
 
 impl Default for MutationConfig {
@@ -77,10 +172,12 @@ impl Default for MutationConfig {
             fresh_zoo_after: 100000,
             max_trace_length: 15,
             min_trace_length: 2,
+            max_trace_size: 3000,
             term_constraints: TermConstraints {
                 min_term_size: 0,
                 max_term_size: 300,
             },
+            weights: MutationWeights::default(),
         }
     }
 }
@@ -102,14 +199,19 @@ where
     feedback: Option<F>,
     objective: Option<OF>,
     initial_inputs: Option<Vec<(I, &'static str)>>,
+    initial_files: Option<Vec<PathBuf>>,
     mutations: Option<MT>,
+    crash_triage: Option<CrashTriage<I>>,
+    parallel_batch: Option<ParallelBatch<I>>,
+    checkpoint_file: Option<PathBuf>,
 }
 
 impl<'harness, H, C, R, SC, EM, F, OF, OT, CS, MT, I>
     RunClientBuilder<'harness, H, C, R, SC, EM, F, OF, OT, CS, MT, I>
 where
     ConcreteState<C, R, SC, I>: UsesInput<Input = I>,
-    I: Input + HasLen,
+    ConcreteState<C, R, SC, I>: serde::Serialize,
+    I: Input + HasLen + Hash,
     C: Corpus + UsesInput<Input = I>,
     R: Rand,
     SC: Corpus + UsesInput<Input = I>,
@@ -147,7 +249,11 @@ where
             feedback: None,
             objective: None,
             initial_inputs: None,
+            initial_files: None,
             mutations: None,
+            crash_triage: None,
+            parallel_batch: None,
+            checkpoint_file: None,
         }
     }
 
@@ -191,11 +297,34 @@ where
         self
     }
 
+    /// Files of the initial corpus which are loaded instead of the `initial_inputs`
+    fn with_initial_files(mut self, initial_files: Option<Vec<PathBuf>>) -> Self {
+        self.initial_files = initial_files;
+        self
+    }
+
     fn with_mutations(mut self, mutations: MT) -> Self {
         self.mutations = Some(mutations);
         self
     }
 
+    fn with_crash_triage(mut self, crash_triage: CrashTriage<I>) -> Self {
+        self.crash_triage = Some(crash_triage);
+        self
+    }
+
+    /// Executes the mutants of the mutational stage in batches, see [`ParallelMutationalStage`]
+    fn with_parallel_batch(mut self, parallel_batch: Option<ParallelBatch<I>>) -> Self {
+        self.parallel_batch = parallel_batch;
+        self
+    }
+
+    /// The file to which the state of this client is checkpointed
+    fn with_checkpoint_file(mut self, checkpoint_file: Option<PathBuf>) -> Self {
+        self.checkpoint_file = checkpoint_file;
+        self
+    }
+
     fn run_client(mut self) -> Result<(), Error> {
         let mut feedback = self.feedback.unwrap();
         let mut objective = self.objective.unwrap();
@@ -215,21 +344,43 @@ where
         let FuzzerConfig {
             initial_corpus_dir,
             max_iters,
+            max_duration,
+            distillation_interval,
+            sync,
+            checkpoint,
+            execution_timeout,
             mutation_stage_config:
                 MutationStageConfig {
                     max_iterations_per_stage: _,
-                    max_mutations_per_iteration: _,
+                    max_mutations_per_iteration,
+                },
+            mutation_config:
+                MutationConfig {
+                    max_trace_size,
+                    weights,
+                    ..
                 },
             ..
         } = self.config;
 
-        // FIXME let mutator = PuffinScheduledMutator::new(self.mutations.unwrap(),
-        // max_mutations_per_iteration);
-        let mutator = StdScheduledMutator::new(self.mutations.unwrap());
+        state.set_max_size(max_trace_size);
+
+        let mutator = PuffinScheduledMutator::new(
+            self.mutations.unwrap(),
+            max_mutations_per_iteration,
+            weights,
+        );
         let mut stages = tuple_list!(
             // FIXMEPuffinMutationalStage::new(mutator, max_iterations_per_stage),
-            StdMutationalStage::new(mutator),
-            // FIXME StatsStage::new()
+            ParallelMutationalStage::new(mutator, self.parallel_batch),
+            DistillationStage::new(distillation_interval),
+            SyncStage::new(sync),
+            CrashBucketStage::new(self.crash_triage),
+            StatsStage::new(),
+            CheckpointStage::new(
+                self.checkpoint_file.clone(),
+                checkpoint.map_or(Duration::MAX, |checkpoint| checkpoint.interval),
+            ),
         );
 
         let mut fuzzer: StdFuzzer<CS, F, OF, OT> =
@@ -245,18 +396,18 @@ where
                 &mut state,
                 &mut self.event_manager,
             )?,
-            Duration::new(5, 0),
+            execution_timeout,
         );
 
         // In case the corpus is empty (on first run), reset
         if state.corpus().is_empty() {
-            if initial_corpus_dir.exists() {
+            if let Some(initial_files) = self.initial_files {
                 state
-                    .load_initial_inputs(
+                    .load_initial_inputs_by_filenames(
                         &mut fuzzer,
                         &mut executor,
                         &mut self.event_manager,
-                        &[initial_corpus_dir.clone()],
+                        &initial_files,
                     )
                     .unwrap_or_else(|err| {
                         panic!(
@@ -277,7 +428,29 @@ where
             }
         }
 
-        if let Some(max_iters) = max_iters {
+        if let Some(max_duration) = max_duration {
+            let started = Instant::now();
+            let mut iters = 0;
+
+            while started.elapsed() < max_duration && max_iters.map_or(true, |max| iters < max) {
+                self.event_manager
+                    .maybe_report_progress(&mut state, PROGRESS_INTERVAL)?;
+                fuzzer.fuzz_one(
+                    &mut stages,
+                    &mut executor,
+                    &mut state,
+                    &mut self.event_manager,
+                )?;
+                iters += 1;
+            }
+
+            self.event_manager.report_progress(&mut state)?;
+            log::info!("Stopped fuzzing after {} iterations", iters);
+
+            if let Some(file) = &self.checkpoint_file {
+                checkpoint::save(&state, file)?;
+            }
+        } else if let Some(max_iters) = max_iters {
             fuzzer.fuzz_loop_for(
                 &mut stages,
                 &mut executor,
@@ -299,21 +472,36 @@ where
 
 type ConcreteMinimizer<S> = IndexesLenTimeMinimizerScheduler<QueueScheduler<S>>;
 
+type ConcreteMapFeedback<'a, S> = MapFeedback<
+    DifferentIsNovel,
+    HitcountsMapObserver<StdMapObserver<'a, u8, false>>,
+    MaxReducer,
+    S,
+    u8,
+>;
+
 type ConcreteObservers<'a> = (
     HitcountsMapObserver<StdMapObserver<'a, u8, false>>,
-    (TimeObserver, ()),
+    (
+        HitcountsMapObserver<StdMapObserver<'a, u8, false>>,
+        (TimeObserver, ()),
+    ),
 );
 
 type ConcreteFeedback<'a, S> = CombinedFeedback<
-    MapFeedback<
-        DifferentIsNovel,
-        HitcountsMapObserver<StdMapObserver<'a, u8, false>>,
-        MaxReducer,
+    DedupFeedback,
+    CombinedFeedback<
+        CampaignFeedback,
+        CombinedFeedback<
+            ConcreteMapFeedback<'a, S>,
+            CombinedFeedback<ConcreteMapFeedback<'a, S>, TimeFeedback, LogicEagerOr, S>,
+            LogicEagerOr,
+            S,
+        >,
+        LogicEagerOr,
         S,
-        u8,
     >,
-    TimeFeedback,
-    LogicEagerOr,
+    LogicFastAnd,
     S,
 >;
 
@@ -334,7 +522,7 @@ impl<'harness, 'a, H, SC, C, R, EM, OF, CS, MT, I>
     >
 where
     ConcreteState<C, R, SC, I>: UsesInput<Input = I>,
-    I: Input + HasLen,
+    I: Input + HasLen + Hash + StructuralHash,
     C: Corpus + UsesInput<Input = I> + fmt::Debug,
     R: Rand,
     SC: Corpus + UsesInput<Input = I> + fmt::Debug,
@@ -362,20 +550,7 @@ where
         ConcreteFeedback<'a, ConcreteState<C, R, SC, I>>,
         ConcreteObservers<'a>,
     ) {
-        #[cfg(not(test))]
-        let map = unsafe {
-            pub use libafl_targets::{EDGES_MAP, MAX_EDGES_NUM};
-            &mut EDGES_MAP[0..MAX_EDGES_NUM]
-        };
-
-        #[cfg(test)]
-        let map = unsafe {
-            // When testing we should not import libafl_targets, else it conflicts with sancov_dummy
-            pub const EDGES_MAP_SIZE: usize = 65536;
-            pub static mut EDGES_MAP: [u8; EDGES_MAP_SIZE] = [0; EDGES_MAP_SIZE];
-            pub static mut MAX_EDGES_NUM: usize = 0;
-            &mut EDGES_MAP[0..MAX_EDGES_NUM]
-        };
+        let map = edges_map();
 
         let map_feedback = MaxMapFeedback::with_names_tracking(
             MAP_FEEDBACK_NAME,
@@ -384,25 +559,104 @@ where
             false,
         );
 
+        // Rewards transitions of the state machines of the PUTs which have not been seen before
+        let state_graph_feedback =
+            MaxMapFeedback::with_names(STATE_GRAPH_FEEDBACK_NAME, STATE_GRAPH_OBSERVER_NAME);
+
         return {
             let time_observer = TimeObserver::new("time");
             let edges_observer =
                 HitcountsMapObserver::new(unsafe { StdMapObserver::new(EDGES_OBSERVER_NAME, map) });
-            let feedback = feedback_or!(
-                // New maximization map feedback linked to the edges observer and the feedback
-                // state `track_indexes` needed because of
-                // IndexesLenTimeMinimizerCorpusScheduler
-                map_feedback,
-                // Time feedback, this one does not need a feedback state
-                // needed for IndexesLenTimeMinimizerCorpusScheduler
-                TimeFeedback::with_observer(&time_observer)
+            let state_graph_observer = HitcountsMapObserver::new(unsafe {
+                StdMapObserver::new(STATE_GRAPH_OBSERVER_NAME, state_graph_map())
+            });
+            let feedback = feedback_and_fast!(
+                // Mutations which reproduce a stored trace do not bloat the corpus
+                DedupFeedback::new(),
+                feedback_or!(
+                    // Attaches the campaign to new corpus entries, never interesting
+                    CampaignFeedback::new(CampaignConfig::resolved(&self.config)),
+                    // New maximization map feedback linked to the edges observer and the feedback
+                    // state `track_indexes` needed because of
+                    // IndexesLenTimeMinimizerCorpusScheduler
+                    map_feedback,
+                    state_graph_feedback,
+                    // Time feedback, this one does not need a feedback state
+                    // needed for IndexesLenTimeMinimizerCorpusScheduler
+                    TimeFeedback::with_observer(&time_observer)
+                )
             );
-            let observers = tuple_list!(edges_observer, time_observer);
+            let observers = tuple_list!(edges_observer, state_graph_observer, time_observer);
             (feedback, observers)
         };
     }
 }
 
+/// The coverage map which is written by the instrumentation of the PUTs
+pub(super) fn edges_map() -> &'static mut [u8] {
+    #[cfg(not(test))]
+    let map = unsafe {
+        pub use libafl_targets::{EDGES_MAP, EDGES_MAP_SIZE, MAX_EDGES_NUM};
+        // One region per name space, see [`coverage`](super::coverage)
+        let len = MAX_EDGES_NUM * super::coverage::regions(MAX_EDGES_NUM, EDGES_MAP_SIZE);
+        &mut EDGES_MAP[0..len]
+    };
+
+    #[cfg(test)]
+    let map = unsafe {
+        // When testing we should not import libafl_targets, else it conflicts with sancov_dummy
+        pub const EDGES_MAP_SIZE: usize = 65536;
+        pub static mut EDGES_MAP: [u8; EDGES_MAP_SIZE] = [0; EDGES_MAP_SIZE];
+        pub static mut MAX_EDGES_NUM: usize = 0;
+        &mut EDGES_MAP[0..MAX_EDGES_NUM]
+    };
+
+    map
+}
+
+fn with_exporters(
+    mut monitor: StatsMonitor,
+    statsd_address: &Option<String>,
+    prometheus_file: &Option<PathBuf>,
+) -> StatsMonitor {
+    if let Some(address) = statsd_address {
+        monitor = monitor.with_statsd(address.clone());
+    }
+    if let Some(path) = prometheus_file {
+        monitor = monitor.with_prometheus_file(path.clone());
+    }
+    monitor
+}
+
+/// Executes each of the `seeds` twice and warns about the seeds with which the PUT is not
+/// deterministic. Returns the names of these seeds.
+pub fn check_seed_determinism<PB: ProtocolBehavior>(
+    runner: &Runner<PB>,
+    seeds: &[(Trace<PB::Matcher>, &'static str)],
+) -> Vec<&'static str> {
+    let mut nondeterministic = vec![];
+
+    for (trace, name) in seeds {
+        match trace.execute_deterministic(runner) {
+            Err(crate::error::Error::Put(reason))
+                if reason.starts_with("PUT is not deterministic") =>
+            {
+                log::warn!("Seed {}: {}", name, reason);
+                nondeterministic.push(*name);
+            }
+            // Failing seeds are reported by the conformance tests
+            _ => {}
+        }
+    }
+
+    log::info!(
+        "Checked the determinism of {} seeds, {} are not deterministic",
+        seeds.len(),
+        nondeterministic.len()
+    );
+    nondeterministic
+}
+
 /// Starts the fuzzing loop
 pub fn start<PB>(
     put_registry: &PutRegistry<PB>,
@@ -413,21 +667,44 @@ where
     PB: ProtocolBehavior + Clone + 'static,
 {
     let FuzzerConfig {
+        put,
+        agent_puts,
         core_definition,
+        initial_corpus_dir,
         corpus_dir,
         objective_dir,
-        static_seed: _,
+        hangs_dir,
+        buckets_dir,
+        static_seed,
+        rng_seed,
         log_file,
         stats_file,
         broker_port,
+        remote_broker,
         tui,
         no_launcher,
+        fork,
+        incremental,
+        jobs,
+        checkpoint,
+        resume,
+        execution_timeout,
+        hang_deadline,
+        objective_errors,
+        memory_limit,
+        max_knowledge_per_source,
+        symbol_stats,
+        statsd_address,
+        prometheus_file,
+        check_determinism,
         mutation_config:
             MutationConfig {
                 fresh_zoo_after,
                 max_trace_length,
                 min_trace_length,
+                max_trace_size: _,
                 term_constraints,
+                weights: _,
             },
         ..
     } = &config;
@@ -436,15 +713,136 @@ where
     log::info!("Config: {:?}\n\nlog_handle: {:?}", &config, &log_handle);
     log_handle.set_config(config_fuzzing(log_file));
 
+    if *symbol_stats {
+        SYMBOL_EVALUATION.enable();
+    }
+
+    if let Some(seed) = rng_seed {
+        rng::set_master_seed(*seed);
+    }
+
+    check_corpus_signature::<PB::Matcher>(
+        PB::signature(),
+        corpus_dir,
+        &[corpus_dir, objective_dir],
+    )?;
+
+    // Incompatible traces of the initial corpus are skipped instead of refusing to start. Outdated
+    // traces are upgraded into the corpus directory, such that the seeds are left untouched.
+    let initial_files = if initial_corpus_dir.exists() {
+        Some(prepare_initial_corpus::<PB::Matcher>(
+            PB::signature(),
+            initial_corpus_dir,
+            &corpus_dir.join(MIGRATED_DIR),
+        )?)
+    } else {
+        None
+    };
+
+    let new_runner = || {
+        let mapping: Vec<_> = agent_puts
+            .iter()
+            .map(|(agent, factory)| {
+                (
+                    *agent,
+                    PutDescriptor::new(factory.clone(), put.options.clone()),
+                )
+            })
+            .collect();
+        Runner::new(
+            put_registry.clone(),
+            Spawner::new(put_registry.clone())
+                .with_default(put.clone())
+                .with_mapping(&mapping),
+        )
+        .with_knowledge_retention(KnowledgeRetention {
+            max_per_source: *max_knowledge_per_source,
+        })
+    };
+
+    if *check_determinism {
+        check_seed_determinism(&new_runner(), &PB::create_corpus());
+    }
+
     let mut run_client = |state: Option<StdState<Trace<PB::Matcher>, _, _, _>>,
                           event_manager: LlmpRestartingEventManager<_, StdShMemProvider>,
-                          _core_id: CoreId|
+                          core_id: CoreId|
      -> Result<(), Error> {
         log_handle
             .clone()
             .set_config(config_fuzzing_client(log_file));
 
-        let harness_fn = &mut (|input: &_| harness::harness::<PB>(put_registry, input));
+        let checkpoint_file = checkpoint
+            .as_ref()
+            .map(|checkpoint| checkpoint.file(core_id.0));
+        // A restarted client continues from the state in shared memory, which is more recent
+        let state = match (state, &checkpoint_file) {
+            (None, Some(file)) if *resume => {
+                let state = checkpoint::load(file)?;
+                if state.is_some() {
+                    log::info!("Resuming from the checkpoint {}", file.display());
+                }
+                state
+            }
+            (state, _) => state,
+        };
+
+        // The forked executions report their coverage through shared memory
+        let mut shared_map = if *fork {
+            Some(
+                StdShMemProvider::new()?
+                    .new_shmem((edges_map().len() + state_graph_map().len()).max(1))?,
+            )
+        } else {
+            None
+        };
+
+        let asan = install_report_callback();
+        if let Some(memory_limit) = memory_limit {
+            // ASAN reserves too much address space for a cap, it limits allocations itself
+            memory::set_limit(*memory_limit, !asan);
+        }
+
+        let runner = new_runner();
+
+        // Each mutant of a batch reports its coverage through its own slot of the shared memory
+        let parallel = if *jobs > 1 {
+            Some(Rc::new(RefCell::new(harness::ParallelHarness::new(
+                ParallelRunner::new(runner.clone(), *jobs)
+                    .with_timeout(harness::fork_timeout(*execution_timeout)),
+                objective_errors.clone(),
+                StdShMemProvider::new()?
+                    .new_shmem(jobs * (edges_map().len() + state_graph_map().len()).max(1))?,
+            ))))
+        } else {
+            None
+        };
+
+        let mut prefixes = incremental.map(PrefixCache::new);
+        let harness_fn = &mut (|input: &_| {
+            // The mutant was already executed in the last batch
+            if let Some(exit_kind) = parallel
+                .as_ref()
+                .and_then(|parallel| parallel.borrow_mut().take(input, edges_map()))
+            {
+                return exit_kind;
+            }
+
+            match &mut shared_map {
+                Some(shared_map) => harness::fork_harness::<PB>(
+                &runner,
+                input,
+                objective_errors,
+                edges_map(),
+                shared_map.as_mut_slice(),
+                harness::fork_timeout(*execution_timeout),
+                prefixes.as_mut(),
+            )
+            // The trace was not executed, the restarting event manager restarts the client
+            .unwrap_or_else(|err| panic!("Failed to fork the execution of a trace: {}", err)),
+                None => harness::harness::<PB>(&runner, input, objective_errors),
+            }
+        });
 
         let mut builder = RunClientBuilder::new(config.clone(), harness_fn, state, event_manager);
         builder = builder
@@ -455,8 +853,33 @@ where
                 *fresh_zoo_after,
                 PB::signature(),
             ))
+            .with_crash_triage(CrashTriage {
+                buckets_dir: buckets_dir.clone(),
+                context: {
+                    let runner = runner.clone();
+                    Box::new(move |trace| capture_crash_context(&runner, trace))
+                },
+                minimize: {
+                    let runner = runner.clone();
+                    Box::new(move |trace| minimize(trace, |candidate| crashes(&runner, candidate)))
+                },
+                shape: hang_key,
+            })
+            .with_parallel_batch(parallel.clone().map(|parallel| ParallelBatch {
+                size: *jobs,
+                execute: Box::new(move |mutants| {
+                    parallel.borrow_mut().execute_batch(mutants, edges_map())
+                }),
+            }))
             .with_initial_inputs(PB::create_corpus())
-            .with_rand(StdRand::new())
+            .with_initial_files(initial_files.clone())
+            .with_checkpoint_file(checkpoint_file)
+            .with_rand(
+                // Each client derives its own seed from the master seed
+                static_seed
+                    .or_else(|| rng::derived_seed(&format!("client-{}", core_id.0)))
+                    .map_or_else(StdRand::new, StdRand::with_seed),
+            )
             .with_corpus(
                 //InMemoryCorpus::new(),
                 CachedOnDiskCorpus::with_meta_format(
@@ -476,8 +899,20 @@ where
             )
             .with_objective(feedback_or_fast!(
                 // don't execute second if first is conclusive, mimicking https://github.com/AFLplusplus/LibAFL/blob/8445ae54b34a6cea48ae243d40bb1b1b94493898/libafl_sugar/src/inmemory.rs#L164
+                // Attaches the campaign to objectives, never conclusive
+                CampaignFeedback::new(CampaignConfig::resolved(&config)),
+                // ASAN comes first such that its report is attached to crashes
+                AsanFeedback::new(),
+                // Crashes caused by exhausted memory are reported as OOM instead
+                MemoryLimitFeedback::new(),
                 CrashFeedback::new(),
-                TimeoutFeedback::new()
+                // Timeouts are triaged and stored as hangs instead of objectives
+                HangFeedback::new(
+                    runner.clone(),
+                    hangs_dir.clone(),
+                    *execution_timeout,
+                    *hang_deadline,
+                )
             ));
 
         //#[cfg(feature = "sancov")]
@@ -497,14 +932,32 @@ where
             builder = builder
                 .with_feedback(feedback)
                 .with_observers(observer)
-                .with_scheduler(RandScheduler::new());
+                .with_scheduler(DistilledScheduler::new(RandScheduler::new()));
         } // TODO:EVAL investigate using QueueScheduler instead (see https://github.com/AFLplusplus/LibAFL/blob/8445ae54b34a6cea48ae243d40bb1b1b94493898/libafl_sugar/src/inmemory.rs#L190)
 
         builder.run_client()
     };
 
+    if remote_broker.is_some() && *no_launcher {
+        return Err(Error::illegal_argument(
+            "connecting to a remote broker requires the launcher",
+        ));
+    }
+
+    #[cfg(not(feature = "multi-machine"))]
+    if let Some(remote_broker) = remote_broker {
+        log::warn!(
+            "Connecting to the broker at {}, which needs to be built with the multi-machine feature",
+            remote_broker
+        );
+    }
+
     if *no_launcher {
-        let stats_monitor = StatsMonitor::with_raw_output(stats_file.clone());
+        let stats_monitor = with_exporters(
+            StatsMonitor::with_raw_output(stats_file.clone()),
+            statsd_address,
+            prometheus_file,
+        );
 
         let (state, restarting_mgr) =
             setup_restarting_mgr_std(stats_monitor, *broker_port, EventConfig::AlwaysUnique)?;
@@ -530,7 +983,11 @@ where
             .expect("failed to create path to redirect fuzzer clients' stdout");
 
         if *tui {
-            let stats_monitor = StatsMonitor::with_tui_output(stats_file.clone());
+            let stats_monitor = with_exporters(
+                StatsMonitor::with_tui_output(stats_file.clone()),
+                statsd_address,
+                prometheus_file,
+            );
 
             Launcher::builder()
                 .shmem_provider(sh_mem_provider)
@@ -539,11 +996,16 @@ where
                 .run_client(&mut run_client)
                 .cores(&cores)
                 .broker_port(*broker_port)
+                .remote_broker_addr(*remote_broker)
                 .stdout_file(Some(out_file))
                 .build()
                 .launch()
         } else {
-            let stats_monitor = StatsMonitor::with_raw_output(stats_file.clone());
+            let stats_monitor = with_exporters(
+                StatsMonitor::with_raw_output(stats_file.clone()),
+                statsd_address,
+                prometheus_file,
+            );
 
             Launcher::builder()
                 .shmem_provider(sh_mem_provider)
@@ -552,6 +1014,7 @@ where
                 .run_client(&mut run_client)
                 .cores(&cores)
                 .broker_port(*broker_port)
+                .remote_broker_addr(*remote_broker)
                 .stdout_file(Some(out_file))
                 .build()
                 .launch()
@@ -0,0 +1,202 @@
+//! Memory limit of trace executions.
+//!
+//! An execution exceeds the limit if the peak resident set size of the process grew by at least
+//! the limit during the execution. The peak is reset before each execution, see
+//! [`begin_execution`]. Such executions are reported with [`ExitKind::Oom`] instead of as crashes.
+//!
+//! Additionally, the address space of the fuzzer client is capped at its size when the limit is
+//! set plus twice the limit, such that a PUT which allocates without bounds fails to allocate
+//! instead of exhausting the memory of the machine. With ASAN, the address space is not capped,
+//! because ASAN reserves terabytes for its shadow memory. The `malloc_limit_mb` option of ASAN
+//! can be used instead.
+
+use std::fs;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use libafl::prelude::*;
+use libafl_bolts::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::stats_stage::OOM;
+
+/// Exit code of a forked execution which exceeded the memory limit.
+pub const OOM_EXIT_CODE: i32 = 86;
+
+/// The limit in MiB, 0 if executions are not limited
+static LIMIT_MB: AtomicU64 = AtomicU64::new(0);
+
+/// The resident set size in KiB when the current execution began
+static BASELINE_KB: AtomicU64 = AtomicU64::new(0);
+
+/// Limits the memory of the following executions to `limit_mb` MiB.
+pub fn set_limit(limit_mb: u64, cap_address_space: bool) {
+    LIMIT_MB.store(limit_mb, Ordering::SeqCst);
+
+    if !cap_address_space {
+        return;
+    }
+
+    let Some(size_kb) = proc_status_kb("VmSize") else {
+        log::warn!("Failed to read the address space size, it is not capped");
+        return;
+    };
+
+    let cap = size_kb * 1024 + 2 * limit_mb * 1024 * 1024;
+    let limit = libc::rlimit {
+        rlim_cur: cap as libc::rlim_t,
+        rlim_max: cap as libc::rlim_t,
+    };
+    if unsafe { libc::setrlimit(libc::RLIMIT_AS, &limit) } != 0 {
+        log::warn!(
+            "Failed to cap the address space: {}",
+            std::io::Error::last_os_error()
+        );
+    } else {
+        log::info!("Capped the address space at {} MiB", cap / 1024 / 1024);
+    }
+}
+
+pub fn limit_mb() -> Option<u64> {
+    match LIMIT_MB.load(Ordering::SeqCst) {
+        0 => None,
+        limit => Some(limit),
+    }
+}
+
+/// Resets the peak resident set size, such that [`exceeded`] only considers the following
+/// allocations.
+pub fn begin_execution() {
+    if limit_mb().is_none() {
+        return;
+    }
+
+    // Writing 5 to clear_refs resets the peak resident set size of the process
+    let _ = fs::write("/proc/self/clear_refs", "5");
+    BASELINE_KB.store(proc_status_kb("VmRSS").unwrap_or(0), Ordering::SeqCst);
+}
+
+/// Returns the growth of the peak resident set size in MiB since [`begin_execution`] if it
+/// reached the limit.
+pub fn exceeded() -> Option<u64> {
+    let limit_mb = limit_mb()?;
+    let peak_kb = proc_status_kb("VmHWM")?;
+    let growth_mb = peak_kb.saturating_sub(BASELINE_KB.load(Ordering::SeqCst)) / 1024;
+
+    (growth_mb >= limit_mb).then_some(growth_mb)
+}
+
+/// Reads a field of `/proc/self/status` which is given in kB.
+fn proc_status_kb(field: &str) -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    parse_status_kb(&status, field)
+}
+
+fn parse_status_kb(status: &str, field: &str) -> Option<u64> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix(field)?.strip_prefix(':'))
+        .and_then(|value| value.trim().strip_suffix("kB"))
+        .and_then(|value| value.trim().parse().ok())
+}
+
+/// Describes an objective which exceeded the memory limit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OomMetadata {
+    pub limit_mb: u64,
+    /// Growth of the peak resident set size during the execution. `None` if the execution was
+    /// forked, because the parent cannot observe it.
+    pub growth_mb: Option<u64>,
+}
+
+libafl_bolts::impl_serdeany!(OomMetadata);
+
+/// Reports executions which exceeded the memory limit, including crashes which happened after
+/// the limit was reached, e.g. because an allocation failed.
+#[derive(Debug, Default)]
+pub struct MemoryLimitFeedback {
+    oom: Option<OomMetadata>,
+}
+
+impl MemoryLimitFeedback {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Named for MemoryLimitFeedback {
+    fn name(&self) -> &str {
+        "MemoryLimitFeedback"
+    }
+}
+
+impl<S> Feedback<S> for MemoryLimitFeedback
+where
+    S: State,
+{
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &S::Input,
+        _observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = S>,
+        OT: ObserversTuple<S>,
+    {
+        self.oom = None;
+
+        let Some(limit_mb) = limit_mb() else {
+            return Ok(false);
+        };
+
+        let growth_mb = exceeded();
+        if *exit_kind == ExitKind::Oom || (*exit_kind == ExitKind::Crash && growth_mb.is_some()) {
+            log::warn!("Execution exceeded the memory limit of {} MiB", limit_mb);
+            OOM.increment();
+            self.oom = Some(OomMetadata {
+                limit_mb,
+                growth_mb,
+            });
+        }
+
+        Ok(self.oom.is_some())
+    }
+
+    fn append_metadata<OT>(
+        &mut self,
+        _state: &mut S,
+        _observers: &OT,
+        testcase: &mut Testcase<S::Input>,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<S>,
+    {
+        if let Some(oom) = self.oom.take() {
+            testcase.add_metadata(oom);
+        }
+
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &S::Input) -> Result<(), Error> {
+        self.oom = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_status_kb;
+
+    #[test_log::test]
+    fn test_parse_status() {
+        let status =
+            "Name:\tpuffin\nVmPeak:\t  123456 kB\nVmHWM:\t   20480 kB\nVmRSS:\t   10240 kB\n";
+
+        assert_eq!(parse_status_kb(status, "VmHWM"), Some(20480));
+        assert_eq!(parse_status_kb(status, "VmRSS"), Some(10240));
+        assert_eq!(parse_status_kb(status, "VmSize"), None);
+    }
+}
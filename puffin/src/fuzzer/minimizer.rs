@@ -0,0 +1,295 @@
+//! Test case reduction for traces.
+//!
+//! Traces found by the fuzzer often contain many steps and large terms which are not needed to
+//! trigger a crash. The [`minimize`] function repeatedly applies deterministic reductions and keeps
+//! each reduction only if the trace still reproduces the crash:
+//!
+//! * removing prior traces and steps, like the [`SkipMutator`](super::mutations::SkipMutator),
+//! * replacing a sub-term with one of its descendants of the same type, like the
+//!   [`RemoveAndLiftMutator`](super::mutations::RemoveAndLiftMutator).
+
+use std::time::Duration;
+
+use crate::algebra::{Matcher, Term};
+use crate::execution::{run_in_subprocess, ExecutionStatus, Runner, TraceRunner};
+use crate::protocol::ProtocolBehavior;
+use crate::trace::{Action, Trace};
+
+/// Upper bound for the number of passes over the trace.
+const MAX_ROUNDS: usize = 64;
+
+/// Timeout for a single execution of a candidate trace.
+const EXECUTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Executes `trace` in a subprocess and checks whether it crashes. Like in the fuzzing harness,
+/// security violations and crashes of PUTs in other processes are treated as crashes.
+pub fn crashes<PB: ProtocolBehavior>(runner: &Runner<PB>, trace: &Trace<PB::Matcher>) -> bool {
+    let status = run_in_subprocess(
+        || match runner.execute(trace) {
+            Err(err) if err.is_crash() => {
+                log::warn!("{}", err);
+                std::process::abort();
+            }
+            _ => {}
+        },
+        EXECUTION_TIMEOUT,
+    );
+
+    matches!(status, Ok(ExecutionStatus::Crashed))
+}
+
+/// Reduces `trace` as long as `reproduces` still returns `true` for the reduced trace.
+///
+/// The original trace is expected to reproduce. If it does not, it is returned unchanged.
+pub fn minimize<M, F>(trace: &Trace<M>, mut reproduces: F) -> Trace<M>
+where
+    M: Matcher,
+    F: FnMut(&Trace<M>) -> bool,
+{
+    let mut current = trace.clone();
+
+    if !reproduces(&current) {
+        log::warn!("Trace does not reproduce, skipping minimization");
+        return current;
+    }
+
+    for round in 0..MAX_ROUNDS {
+        let reduced = remove_prior_traces(&mut current, &mut reproduces)
+            | remove_steps(&mut current, &mut reproduces)
+            | lift_terms(&mut current, &mut reproduces);
+
+        log::info!(
+            "Minimization round {}: {} steps, {} prior traces",
+            round,
+            current.steps.len(),
+            current.prior_traces.len()
+        );
+
+        if !reduced {
+            break;
+        }
+    }
+
+    current
+}
+
+fn remove_prior_traces<M, F>(trace: &mut Trace<M>, reproduces: &mut F) -> bool
+where
+    M: Matcher,
+    F: FnMut(&Trace<M>) -> bool,
+{
+    let mut reduced = false;
+
+    for index in (0..trace.prior_traces.len()).rev() {
+        let mut candidate = trace.clone();
+        candidate.prior_traces.remove(index);
+
+        if reproduces(&candidate) {
+            *trace = candidate;
+            reduced = true;
+        }
+    }
+
+    reduced
+}
+
+fn remove_steps<M, F>(trace: &mut Trace<M>, reproduces: &mut F) -> bool
+where
+    M: Matcher,
+    F: FnMut(&Trace<M>) -> bool,
+{
+    let mut reduced = false;
+
+    for index in (0..trace.steps.len()).rev() {
+        if trace.steps.len() <= 1 {
+            break;
+        }
+
+        let mut candidate = trace.clone();
+        candidate.steps.remove(index);
+
+        if reproduces(&candidate) {
+            *trace = candidate;
+            reduced = true;
+        }
+    }
+
+    reduced
+}
+
+fn lift_terms<M, F>(trace: &mut Trace<M>, reproduces: &mut F) -> bool
+where
+    M: Matcher,
+    F: FnMut(&Trace<M>) -> bool,
+{
+    let mut reduced = false;
+
+    for step_index in 0..trace.steps.len() {
+        let mut path_index = 0;
+
+        while let Action::Input(input) = &trace.steps[step_index].action {
+            let recipe = &input.recipe;
+            let paths = term_paths(recipe);
+            let Some(path) = paths.get(path_index) else {
+                break;
+            };
+
+            let lifted =
+                lift_candidates(term_at(recipe, path))
+                    .into_iter()
+                    .find_map(|replacement| {
+                        let mut candidate = trace.clone();
+                        if let Action::Input(input) = &mut candidate.steps[step_index].action {
+                            *term_at_mut(&mut input.recipe, path) = replacement;
+                        }
+
+                        if reproduces(&candidate) {
+                            Some(candidate)
+                        } else {
+                            None
+                        }
+                    });
+
+            if let Some(candidate) = lifted {
+                // The sub-terms of the node changed, so its children are visited next
+                *trace = candidate;
+                reduced = true;
+            }
+
+            path_index += 1;
+        }
+    }
+
+    reduced
+}
+
+/// Paths to all nodes of `term` in pre-order.
+fn term_paths<M: Matcher>(term: &Term<M>) -> Vec<Vec<usize>> {
+    fn collect<M: Matcher>(term: &Term<M>, path: &mut Vec<usize>, paths: &mut Vec<Vec<usize>>) {
+        paths.push(path.clone());
+
+        if let Term::Application(_, subterms) = term {
+            for (index, subterm) in subterms.iter().enumerate() {
+                path.push(index);
+                collect(subterm, path, paths);
+                path.pop();
+            }
+        }
+    }
+
+    let mut paths = vec![];
+    collect(term, &mut vec![], &mut paths);
+    paths
+}
+
+fn term_at<'a, M: Matcher>(term: &'a Term<M>, path: &[usize]) -> &'a Term<M> {
+    path.iter().fold(term, |term, index| match term {
+        Term::Application(_, subterms) => &subterms[*index],
+        Term::Variable(_) => term,
+    })
+}
+
+fn term_at_mut<'a, M: Matcher>(term: &'a mut Term<M>, path: &[usize]) -> &'a mut Term<M> {
+    path.iter().fold(term, |term, index| match term {
+        Term::Application(_, subterms) => &mut subterms[*index],
+        Term::Variable(_) => term,
+    })
+}
+
+/// Strict descendants of `term` which have the same type, smallest first.
+fn lift_candidates<M: Matcher>(term: &Term<M>) -> Vec<Term<M>> {
+    let shape = term.get_type_shape();
+
+    let mut candidates: Vec<Term<M>> = term_paths(term)
+        .iter()
+        .skip(1)
+        .map(|path| term_at(term, path))
+        .filter(|descendant| descendant.get_type_shape() == shape)
+        .cloned()
+        .collect();
+
+    candidates.sort_by_key(|candidate| candidate.size());
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::minimize;
+    use crate::agent::AgentName;
+    use crate::algebra::test_signature::*;
+    use crate::algebra::{remove_prefix, AnyMatcher, Term};
+    use crate::term;
+    use crate::trace::{Action, InputAction, OutputAction, Trace};
+
+    fn contains_function(trace: &Trace<AnyMatcher>, name: &str) -> bool {
+        trace.steps.iter().any(|step| match &step.action {
+            Action::Input(input) => input.recipe.into_iter().any(|term| match term {
+                Term::Application(function, _) => remove_prefix(function.name()) == name,
+                Term::Variable(_) => false,
+            }),
+            Action::Output(_)
+            | Action::Expect(_)
+            | Action::Reset(_)
+            | Action::Renegotiate(_)
+            | Action::KeyUpdate(_) => false,
+        })
+    }
+
+    #[test_log::test]
+    fn test_minimize_removes_steps_and_lifts_terms() {
+        let server = AgentName::first();
+
+        let trace = Trace {
+            descriptors: vec![],
+            prior_traces: vec![],
+            steps: vec![
+                InputAction::new_step(server, term! { fn_seq_1 }),
+                InputAction::new_step(
+                    server,
+                    term! {
+                        fn_client_extensions_append(
+                            (fn_client_extensions_append(
+                                fn_client_extensions_new,
+                                fn_ec_point_formats_extension
+                            )),
+                            fn_signature_algorithm_extension
+                        )
+                    },
+                ),
+                OutputAction::new_step(server),
+            ],
+        };
+
+        let minimized = minimize(&trace, |candidate| {
+            contains_function(candidate, "fn_ec_point_formats_extension")
+        });
+
+        assert_eq!(minimized.steps.len(), 1);
+        match &minimized.steps[0].action {
+            Action::Input(input) => assert_eq!(input.recipe.size(), 3),
+            Action::Output(_)
+            | Action::Expect(_)
+            | Action::Reset(_)
+            | Action::Renegotiate(_)
+            | Action::KeyUpdate(_) => panic!("expected an input step"),
+        }
+    }
+
+    #[test_log::test]
+    fn test_minimize_keeps_non_reproducing_trace() {
+        let server = AgentName::first();
+
+        let trace: Trace<AnyMatcher> = Trace {
+            descriptors: vec![],
+            prior_traces: vec![],
+            steps: vec![
+                OutputAction::new_step(server),
+                OutputAction::new_step(server),
+            ],
+        };
+
+        let minimized = minimize(&trace, |_| false);
+
+        assert_eq!(minimized.steps.len(), 2);
+    }
+}
@@ -1,37 +1,75 @@
 //! The fuzzer module setups the fuzzing loop. It also is responsible for gathering feedback from
 //! runs and restarting processes if they crash.
 
-use std::hash::{BuildHasher, Hash, Hasher};
+use std::path::Path;
 
 use chrono::Utc;
 use libafl::inputs::Input;
+use libafl::Error;
+use libafl_bolts::fs::write_file_atomic;
 use libafl_bolts::HasLen;
 
 use crate::trace::Trace;
 
+pub mod campaign;
+mod checkpoint;
+mod cmin;
+pub mod coverage;
+pub mod crash_buckets;
+pub mod dedup;
+mod distillation;
+pub mod hangs;
 pub mod harness;
+pub mod incremental;
 mod libafl_setup;
+pub mod memory;
+mod minimizer;
+mod profile;
 pub mod sanitizer;
+mod signature_check;
 mod stages;
+pub mod state_graph;
 mod stats_monitor;
-mod stats_stage;
+pub(crate) mod stats_stage;
+mod sync;
 pub mod term_zoo;
+pub(crate) mod trace_file;
 // Public for benchmarks
 pub mod mutations;
 
+pub use campaign::CampaignConfig;
+pub use checkpoint::CheckpointConfig;
+pub use cmin::cmin;
 pub use libafl_setup::{start, FuzzerConfig};
+pub use minimizer::{crashes, minimize};
+pub use profile::Profile;
+pub use sync::SyncConfig;
 
-use crate::algebra::Matcher;
+use crate::algebra::{try_deserialize_signature, Matcher};
 
 // LibAFL support
 impl<M: Matcher> Input for Trace<M> {
+    fn to_file<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let signature = try_deserialize_signature()
+            .ok_or_else(|| Error::illegal_state("current signature needs to be set"))?;
+        write_file_atomic(path, &trace_file::encode(self, signature)?)
+    }
+
+    fn from_file<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        trace_file::decode(&std::fs::read(path)?).map(|decoded| decoded.trace)
+    }
+
     fn generate_name(&self, _idx: usize) -> String {
         let now = Utc::now();
-        let mut hasher = ahash::RandomState::with_seeds(0, 0, 0, 0).build_hasher();
-        self.hash(&mut hasher);
         format!(
             "{time}-{hash:016x}.trace",
-            hash = hasher.finish(),
+            hash = sync::input_hash(self),
             time = now.format("%Y%m%d-%H%M%S%3f")
         )
     }
@@ -1,3 +1,6 @@
+use std::fmt;
+use std::str::FromStr;
+
 use libafl::prelude::*;
 use libafl_bolts::prelude::*;
 use util::{Choosable, *};
@@ -6,7 +9,7 @@ use crate::algebra::atoms::Function;
 use crate::algebra::signature::Signature;
 use crate::algebra::{Matcher, Subterms, Term};
 use crate::fuzzer::term_zoo::TermZoo;
-use crate::trace::Trace;
+use crate::trace::{Action, Trace};
 
 pub fn trace_mutations<S, M: Matcher>(
     min_trace_length: usize,
@@ -21,10 +24,12 @@ pub fn trace_mutations<S, M: Matcher>(
        ReplaceMatchMutator<S>,
        RemoveAndLiftMutator<S>,
        GenerateMutator<S, M>,
-       SwapMutator<S>
+       SwapMutator<S>,
+       CrossoverMutator<S>,
+       TruncateMutator<S>
    )
 where
-    S: HasCorpus + HasMetadata + HasMaxSize + HasRand,
+    S: HasCorpus<Input = Trace<M>> + HasMetadata + HasMaxSize + HasRand,
 {
     tuple_list!(
         RepeatMutator::new(max_trace_length),
@@ -35,10 +40,88 @@ where
         GenerateMutator::new(0, fresh_zoo_after, constraints, None, signature), /* Refresh zoo
                                                                                  * after 100000M
                                                                                  * mutations */
-        SwapMutator::new(constraints)
+        SwapMutator::new(constraints),
+        CrossoverMutator::new(constraints),
+        TruncateMutator::new(min_trace_length, max_trace_length)
     )
 }
 
+/// Relative weights with which the mutations of [`trace_mutations`] are scheduled. Mutations with
+/// a weight of 0 are never scheduled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MutationWeights([u32; MutationWeights::NAMES.len()]);
+
+impl MutationWeights {
+    /// Names of the mutations in the order of [`trace_mutations`]
+    pub const NAMES: [&'static str; 9] = [
+        "repeat",
+        "skip",
+        "replace-reuse",
+        "replace-match",
+        "remove-and-lift",
+        "generate",
+        "swap",
+        "crossover",
+        "truncate",
+    ];
+
+    pub fn as_slice(&self) -> &[u32] {
+        &self.0
+    }
+}
+
+impl Default for MutationWeights {
+    fn default() -> Self {
+        Self([1; MutationWeights::NAMES.len()])
+    }
+}
+
+/// Parses weights like `swap=2,generate=0`. Mutations which are not listed keep a weight of 1.
+impl FromStr for MutationWeights {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut weights = Self::default();
+
+        for pair in spec.split(',').filter(|pair| !pair.trim().is_empty()) {
+            let (name, weight) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected <mutation>=<weight>, got {}", pair))?;
+            let index = Self::NAMES
+                .iter()
+                .position(|known| *known == name.trim())
+                .ok_or_else(|| {
+                    format!(
+                        "unknown mutation {}, expected one of {}",
+                        name.trim(),
+                        Self::NAMES.join(", ")
+                    )
+                })?;
+            weights.0[index] = weight
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid weight {} for {}", weight.trim(), name.trim()))?;
+        }
+
+        if weights.0.iter().all(|weight| *weight == 0) {
+            return Err("at least one mutation needs a weight above 0".to_string());
+        }
+
+        Ok(weights)
+    }
+}
+
+impl fmt::Display for MutationWeights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pairs = Self::NAMES
+            .iter()
+            .zip(self.0)
+            .map(|(name, weight)| format!("{}={}", name, weight))
+            .collect::<Vec<_>>();
+        write!(f, "{}", pairs.join(","))
+    }
+}
+
 /// SWAP: Swaps a sub-term with a different sub-term which is part of the trace
 
 /// (such that types match).
@@ -225,10 +308,11 @@ where
         if let Some(mut to_mutate) = choose_term_mut(trace, self.constraints, rand) {
             match &mut to_mutate {
                 Term::Variable(variable) => {
-                    if let Some((shape, dynamic_fn)) = self.signature.functions.choose_filtered(
-                        |(shape, _)| variable.typ == shape.return_type && shape.is_constant(),
-                        rand,
-                    ) {
+                    if let Some((shape, dynamic_fn)) = self
+                        .signature
+                        .constants_returning(&variable.typ)
+                        .choose(rand)
+                    {
                         to_mutate.mutate(Term::Application(
                             Function::new(shape.clone(), dynamic_fn.clone()),
                             Vec::new(),
@@ -239,14 +323,17 @@ where
                     }
                 }
                 Term::Application(func_mut, _) => {
-                    if let Some((shape, dynamic_fn)) = self.signature.functions.choose_filtered(
-                        |(shape, _)| {
-                            func_mut.shape() != shape
-                                && func_mut.shape().return_type == shape.return_type
-                                && func_mut.shape().argument_types == shape.argument_types
-                        },
-                        rand,
-                    ) {
+                    if let Some((shape, dynamic_fn)) = self
+                        .signature
+                        .functions_returning(&func_mut.shape().return_type)
+                        .choose_filtered(
+                            |(shape, _)| {
+                                func_mut.shape() != shape
+                                    && func_mut.shape().argument_types == shape.argument_types
+                            },
+                            rand,
+                        )
+                    {
                         func_mut.change_function(shape.clone(), dynamic_fn.clone());
                         Ok(MutationResult::Mutated)
                     } else {
@@ -296,7 +383,7 @@ where
 
 impl<S, M: Matcher> Mutator<Trace<M>, S> for ReplaceReuseMutator<S>
 where
-    S: HasRand,
+    S: HasRand + HasMaxSize,
 {
     fn mutate(
         &mut self,
@@ -304,6 +391,8 @@ where
         trace: &mut Trace<M>,
         _stage_idx: i32,
     ) -> Result<MutationResult, Error> {
+        let max_size = state.max_size();
+        let trace_size = trace.size();
         let rand = state.rand_mut();
         if let Some(replacement) = choose_term(trace, self.constraints, rand).cloned() {
             if let Some(to_replace) = choose_term_filtered_mut(
@@ -312,6 +401,10 @@ where
                 self.constraints,
                 rand,
             ) {
+                if !fits_max_size(trace_size, to_replace, &replacement, max_size) {
+                    return Ok(MutationResult::Skipped);
+                }
+
                 to_replace.mutate(replacement);
                 return Ok(MutationResult::Mutated);
             }
@@ -404,7 +497,7 @@ where
 }
 impl<S, M: Matcher> Mutator<Trace<M>, S> for RepeatMutator<S>
 where
-    S: HasRand,
+    S: HasRand + HasMaxSize,
 {
     fn mutate(
         &mut self,
@@ -422,6 +515,11 @@ where
         }
         let insert_index = state.rand_mut().between(0, length as u64) as usize;
         let step = state.rand_mut().choose(steps).clone();
+        if let Action::Input(input) = &step.action {
+            if trace.size() + input.recipe.size() > state.max_size() {
+                return Ok(MutationResult::Skipped);
+            }
+        }
         trace.steps.insert(insert_index, step);
         Ok(MutationResult::Mutated)
     }
@@ -435,7 +533,13 @@ where
     }
 }
 
+/// Maximum depth of the terms which are synthesized by the [`GenerateMutator`]
+const GENERATED_TERM_DEPTH: u16 = 4;
+
 /// GENERATE: Generates a previously-unseen term using a term zoo
+///
+/// With a probability of 1/2, or if the zoo contains no term of the required type, a fresh term
+/// is synthesized from the function symbols of the signature which return the required type.
 pub struct GenerateMutator<S, M: Matcher>
 where
     S: HasRand,
@@ -471,7 +575,7 @@ where
 }
 impl<S, M: Matcher> Mutator<Trace<M>, S> for GenerateMutator<S, M>
 where
-    S: HasRand,
+    S: HasRand + HasMaxSize,
 {
     fn mutate(
         &mut self,
@@ -479,6 +583,8 @@ where
         trace: &mut Trace<M>,
         _stage_idx: i32,
     ) -> Result<MutationResult, Error> {
+        let max_size = state.max_size();
+        let trace_size = trace.size();
         let rand = state.rand_mut();
         if let Some(to_mutate) = choose_term_mut(trace, self.constraints, rand) {
             self.mutation_counter += 1;
@@ -488,11 +594,22 @@ where
                 self.zoo
                     .get_or_insert_with(|| TermZoo::generate(self.signature, rand))
             };
-            if let Some(term) = zoo.choose_filtered(
-                |term| to_mutate.get_type_shape() == term.get_type_shape(),
-                rand,
-            ) {
-                to_mutate.mutate(term.clone());
+            let typ = *to_mutate.get_type_shape();
+            let from_zoo = if rand.below(2) == 0 {
+                zoo.choose_filtered(|term| term.get_type_shape() == &typ, rand)
+                    .cloned()
+            } else {
+                None
+            };
+
+            if let Some(term) = from_zoo.or_else(|| {
+                TermZoo::generate_term_of_type(self.signature, &typ, GENERATED_TERM_DEPTH, rand)
+            }) {
+                if !fits_max_size(trace_size, to_mutate, &term, max_size) {
+                    return Ok(MutationResult::Skipped);
+                }
+
+                to_mutate.mutate(term);
                 Ok(MutationResult::Mutated)
             } else {
                 Ok(MutationResult::Skipped)
@@ -511,6 +628,168 @@ where
     }
 }
 
+/// CROSSOVER: Replaces a sub-term with a sub-term of another trace in the corpus (such that types
+/// match).
+///
+/// The other mutations only reuse terms which are already part of the mutated trace. This
+/// mutation is skipped if the recipe would exceed the maximum term size or the trace would exceed
+/// the maximum size afterwards.
+pub struct CrossoverMutator<S>
+where
+    S: HasRand,
+{
+    constraints: TermConstraints,
+    phantom_s: std::marker::PhantomData<S>,
+}
+
+impl<S> CrossoverMutator<S>
+where
+    S: HasRand,
+{
+    #[must_use]
+    pub fn new(constraints: TermConstraints) -> Self {
+        Self {
+            constraints,
+            phantom_s: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<S, M: Matcher> Mutator<Trace<M>, S> for CrossoverMutator<S>
+where
+    S: HasCorpus<Input = Trace<M>> + HasRand + HasMaxSize,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        trace: &mut Trace<M>,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        if state.corpus().count() == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+
+        // We don't want to splice the trace with itself
+        let idx = random_corpus_id!(state.corpus(), state.rand_mut());
+        if state.corpus().current() == &Some(idx) {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let other = {
+            let mut testcase = state.corpus().get(idx)?.borrow_mut();
+            testcase.load_input(state.corpus())?.clone()
+        };
+
+        let max_size = state.max_size();
+        let trace_size = trace.size();
+        let rand = state.rand_mut();
+        let Some(replacement) = choose_term(&other, self.constraints, rand) else {
+            return Ok(MutationResult::Skipped);
+        };
+        let Some(trace_path) = choose_term_path_filtered(
+            trace,
+            |term: &Term<M>| term.get_type_shape() == replacement.get_type_shape(),
+            self.constraints,
+            rand,
+        ) else {
+            return Ok(MutationResult::Skipped);
+        };
+
+        let recipe_size = match &trace.steps[trace_path.0].action {
+            Action::Input(input) => input.recipe.size(),
+            Action::Output(_)
+            | Action::Expect(_)
+            | Action::Reset(_)
+            | Action::Renegotiate(_)
+            | Action::KeyUpdate(_) => return Ok(MutationResult::Skipped),
+        };
+        if let Some(to_replace) = find_term_mut(trace, &trace_path) {
+            if recipe_size - to_replace.size() + replacement.size()
+                >= self.constraints.max_term_size
+                || !fits_max_size(trace_size, to_replace, replacement, max_size)
+            {
+                return Ok(MutationResult::Skipped);
+            }
+
+            to_replace.mutate(replacement.clone());
+            return Ok(MutationResult::Mutated);
+        }
+        Ok(MutationResult::Skipped)
+    }
+}
+
+impl<S> Named for CrossoverMutator<S>
+where
+    S: HasRand,
+{
+    fn name(&self) -> &str {
+        std::any::type_name::<CrossoverMutator<S>>()
+    }
+}
+
+/// TRUNCATE: Removes steps from the end of a trace which has more than the maximum number of
+/// steps or which exceeds the maximum size of the state.
+///
+/// The other mutations only keep traces from growing beyond the limits. This mutation prunes
+/// traces which are already too large, e.g. because they were part of the initial corpus. At
+/// least the minimum number of steps is kept.
+pub struct TruncateMutator<S>
+where
+    S: HasRand,
+{
+    min_trace_length: usize,
+    max_trace_length: usize,
+    phantom_s: std::marker::PhantomData<S>,
+}
+
+impl<S> TruncateMutator<S>
+where
+    S: HasRand,
+{
+    #[must_use]
+    pub fn new(min_trace_length: usize, max_trace_length: usize) -> Self {
+        Self {
+            min_trace_length,
+            max_trace_length,
+            phantom_s: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<S, M: Matcher> Mutator<Trace<M>, S> for TruncateMutator<S>
+where
+    S: HasRand + HasMaxSize,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        trace: &mut Trace<M>,
+        _stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let max_size = state.max_size();
+        let exceeds_limits =
+            |trace: &Trace<M>| trace.steps.len() > self.max_trace_length || trace.size() > max_size;
+
+        if !exceeds_limits(trace) || trace.steps.len() <= self.min_trace_length {
+            return Ok(MutationResult::Skipped);
+        }
+
+        while exceeds_limits(trace) && trace.steps.len() > self.min_trace_length {
+            trace.steps.pop();
+        }
+        Ok(MutationResult::Mutated)
+    }
+}
+
+impl<S> Named for TruncateMutator<S>
+where
+    S: HasRand,
+{
+    fn name(&self) -> &str {
+        std::any::type_name::<TruncateMutator<S>>()
+    }
+}
+
 pub mod util {
     use libafl_bolts::rands::Rand;
 
@@ -540,7 +819,7 @@ pub mod util {
         fn choose(&self, rand: &mut R) -> Option<&T>;
     }
 
-    impl<T, R: Rand> Choosable<T, R> for Vec<T> {
+    impl<T, R: Rand> Choosable<T, R> for [T] {
         fn choose_filtered<P>(&self, filter: P, rand: &mut R) -> Option<&T>
         where
             P: FnMut(&&T) -> bool,
@@ -649,7 +928,11 @@ pub mod util {
                         }
                     }
                 }
-                Action::Output(_) => {
+                Action::Output(_)
+                | Action::Expect(_)
+                | Action::Reset(_)
+                | Action::Renegotiate(_)
+                | Action::KeyUpdate(_) => {
                     // no term -> skip
                 }
             }
@@ -680,6 +963,17 @@ pub mod util {
         }
     }
 
+    /// Whether a trace of size `trace_size` stays within `max_size` if `to_replace` is replaced
+    /// with `replacement`.
+    pub fn fits_max_size<M: Matcher>(
+        trace_size: usize,
+        to_replace: &Term<M>,
+        replacement: &Term<M>,
+        max_size: usize,
+    ) -> bool {
+        trace_size.saturating_sub(to_replace.size()) + replacement.size() <= max_size
+    }
+
     pub fn find_term_mut<'a, M: Matcher>(
         trace: &'a mut Trace<M>,
         trace_path: &TracePath,
@@ -692,7 +986,11 @@ pub mod util {
                 Action::Input(input) => {
                     find_term_by_term_path_mut(&mut input.recipe, &mut term_path.clone())
                 }
-                Action::Output(_) => None,
+                Action::Output(_)
+                | Action::Expect(_)
+                | Action::Reset(_)
+                | Action::Renegotiate(_)
+                | Action::KeyUpdate(_) => None,
             }
         } else {
             None
@@ -762,7 +1060,7 @@ pub mod util {
 mod tests {
     use std::collections::{HashMap, HashSet};
 
-    use libafl::corpus::InMemoryCorpus;
+    use libafl::corpus::{Corpus, InMemoryCorpus, Testcase};
     use libafl::mutators::{MutationResult, Mutator};
     use libafl::state::StdState;
     use libafl_bolts::rands::{RomuDuoJrRand, StdRand};
@@ -772,7 +1070,9 @@ mod tests {
     use crate::algebra::dynamic_function::DescribableFunction;
     use crate::algebra::test_signature::{TestTrace, *};
     use crate::algebra::{AnyMatcher, Term};
-    use crate::trace::{Action, Step};
+    use crate::fuzzer::stages::PuffinScheduledMutator;
+    use crate::term;
+    use crate::trace::{Action, InputAction, Step, Trace};
 
     fn create_state(
     ) -> StdState<TestTrace, InMemoryCorpus<TestTrace>, RomuDuoJrRand, InMemoryCorpus<TestTrace>>
@@ -837,7 +1137,11 @@ mod tests {
                             }
                         }
                     },
-                    Action::Output(_) => {}
+                    Action::Output(_)
+                    | Action::Expect(_)
+                    | Action::Reset(_)
+                    | Action::Renegotiate(_)
+                    | Action::KeyUpdate(_) => {}
                 }
             }
         }
@@ -928,7 +1232,11 @@ mod tests {
             let is_first_not_ch = if let Some(first) = trace.steps.get(0) {
                 match &first.action {
                     Action::Input(input) => Some(input.recipe.name() != fn_client_hello.name()),
-                    Action::Output(_) => None,
+                    Action::Output(_)
+                    | Action::Expect(_)
+                    | Action::Reset(_)
+                    | Action::Renegotiate(_)
+                    | Action::KeyUpdate(_) => None,
                 }
             } else {
                 None
@@ -939,7 +1247,11 @@ mod tests {
                     Action::Input(input) => {
                         Some(input.recipe.name() != fn_client_key_exchange.name())
                     }
-                    Action::Output(_) => None,
+                    Action::Output(_)
+                    | Action::Expect(_)
+                    | Action::Reset(_)
+                    | Action::Renegotiate(_)
+                    | Action::KeyUpdate(_) => None,
                 }
             } else {
                 None
@@ -955,6 +1267,163 @@ mod tests {
         }
     }
 
+    #[test_log::test]
+    fn test_generate_mutator() {
+        let mut state = create_state();
+        let mut mutator =
+            GenerateMutator::new(0, 100000, TermConstraints::default(), None, &TEST_SIGNATURE);
+
+        loop {
+            let mut trace = setup_simple_trace();
+            mutator.mutate(&mut state, &mut trace, 0).unwrap();
+
+            // fn_hmac256 is not part of the seed, but returns the same type as fn_empty_bytes_vec
+            if trace.count_functions_by_name(fn_hmac256.name()) > 0 {
+                break;
+            }
+        }
+    }
+
+    #[test_log::test]
+    fn test_crossover_mutator() {
+        let mut state = create_state();
+        let mut mutator = CrossoverMutator::new(TermConstraints::default());
+
+        let mut trace = setup_simple_trace();
+        assert_eq!(
+            mutator.mutate(&mut state, &mut trace, 0).unwrap(),
+            MutationResult::Skipped
+        );
+
+        let server = AgentName::first();
+        let other: TestTrace = Trace {
+            descriptors: vec![],
+            prior_traces: vec![],
+            steps: vec![InputAction::new_step(
+                server,
+                term! { fn_encrypt12(fn_finished, fn_seq_1) },
+            )],
+        };
+        state.corpus_mut().add(Testcase::new(other)).unwrap();
+
+        loop {
+            let mut trace = setup_simple_trace();
+            let result = mutator.mutate(&mut state, &mut trace, 0).unwrap();
+
+            if let MutationResult::Mutated = result {
+                if trace.count_functions_by_name(fn_seq_1.name()) > 0 {
+                    // fn_seq_0 replaced by fn_seq_1 of the other trace
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test_log::test]
+    fn test_repeat_respects_max_size() {
+        let mut state = create_state();
+        let trace = setup_simple_trace();
+        state.set_max_size(trace.size());
+
+        let mut mutator = RepeatMutator::new(15);
+        for _ in 0..100 {
+            let mut mutated = trace.clone();
+            mutator.mutate(&mut state, &mut mutated, 0).unwrap();
+            assert!(mutated.size() <= trace.size());
+        }
+    }
+
+    #[test_log::test]
+    fn test_truncate_mutator() {
+        let mut state = create_state();
+        let trace = setup_simple_trace();
+        let mut mutator = TruncateMutator::new(1, 15);
+
+        let mut within_limits = trace.clone();
+        assert_eq!(
+            mutator.mutate(&mut state, &mut within_limits, 0).unwrap(),
+            MutationResult::Skipped
+        );
+        assert_eq!(within_limits.steps.len(), trace.steps.len());
+
+        let last_size = match &trace.steps.last().unwrap().action {
+            Action::Input(input) => input.recipe.size(),
+            Action::Output(_)
+            | Action::Expect(_)
+            | Action::Reset(_)
+            | Action::Renegotiate(_)
+            | Action::KeyUpdate(_) => panic!("expected an input step"),
+        };
+        state.set_max_size(trace.size() - last_size);
+        let mut oversized = trace.clone();
+        assert_eq!(
+            mutator.mutate(&mut state, &mut oversized, 0).unwrap(),
+            MutationResult::Mutated
+        );
+        assert_eq!(oversized.steps.len(), trace.steps.len() - 1);
+        assert!(oversized.size() <= state.max_size());
+
+        // the minimum number of steps is kept
+        state.set_max_size(0);
+        let mut oversized = trace.clone();
+        mutator.mutate(&mut state, &mut oversized, 0).unwrap();
+        assert_eq!(oversized.steps.len(), 1);
+    }
+
+    #[test_log::test]
+    fn test_parse_mutation_weights() {
+        let weights: MutationWeights = "swap=3, generate=0".parse().unwrap();
+
+        assert_eq!(weights.as_slice(), &[1, 1, 1, 1, 1, 0, 3, 1, 1]);
+        assert_eq!(weights.to_string().parse::<MutationWeights>(), Ok(weights));
+        assert_eq!(
+            "".parse::<MutationWeights>(),
+            Ok(MutationWeights::default())
+        );
+
+        assert!("fuzz=1".parse::<MutationWeights>().is_err());
+        assert!("swap=-1".parse::<MutationWeights>().is_err());
+        assert!(MutationWeights::NAMES
+            .iter()
+            .map(|name| format!("{}=0", name))
+            .collect::<Vec<_>>()
+            .join(",")
+            .parse::<MutationWeights>()
+            .is_err());
+    }
+
+    #[test_log::test]
+    fn test_weighted_schedule() {
+        let mut state = create_state();
+        let mutator = PuffinScheduledMutator::new(
+            trace_mutations::<_, AnyMatcher>(
+                2,
+                15,
+                TermConstraints::default(),
+                100000,
+                &TEST_SIGNATURE,
+            ),
+            16,
+            "repeat=0,skip=0,replace-reuse=0,replace-match=0,remove-and-lift=0,generate=0,swap=1,crossover=3,truncate=0"
+                .parse()
+                .unwrap(),
+        );
+        let trace = setup_simple_trace();
+
+        let mut counts = [0; MutationWeights::NAMES.len()];
+        for _ in 0..4000 {
+            let id = mutator.schedule(&mut state, &trace);
+            let index = (0..counts.len())
+                .find(|index| MutationId::from(*index) == id)
+                .unwrap();
+            counts[index] += 1;
+        }
+
+        assert_eq!(&counts[..6], &[0; 6]);
+        assert_eq!(counts[8], 0);
+        assert!(counts[7] > 2 * counts[6]);
+    }
+
     #[test_log::test]
     fn test_find_term() {
         let mut rand = StdRand::with_seed(45);
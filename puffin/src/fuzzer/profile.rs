@@ -0,0 +1,118 @@
+//! Named presets for the [`FuzzerConfig`] of a fuzzing campaign.
+//!
+//! A profile only sets defaults. Options which are passed explicitly on the command line take
+//! precedence over the values of the profile.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::libafl_setup::{FuzzerConfig, MutationConfig};
+use super::mutations::util::TermConstraints;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    /// Short run on a single core with small traces, e.g. to check that a PUT works at all
+    Smoke,
+    /// Unbounded run on all cores with the default mutations and periodic corpus distillation.
+    /// The seeds are checked for determinism before fuzzing.
+    Nightly,
+    /// Unbounded run on all cores with larger traces. The results are stored in a new directory
+    /// of `experiments`, like with the `quick-experiment` command.
+    Research,
+}
+
+impl Profile {
+    pub const NAMES: [&'static str; 3] = ["smoke", "nightly", "research"];
+
+    /// Overwrites the fields of `config` which are part of the profile.
+    pub fn apply(&self, config: &mut FuzzerConfig) {
+        match self {
+            Profile::Smoke => {
+                config.core_definition = "0".to_string();
+                config.max_duration = Some(Duration::from_secs(5 * 60));
+                config.distillation_interval = None;
+                config.check_determinism = false;
+                config.mutation_config = MutationConfig {
+                    max_trace_length: 5,
+                    max_trace_size: 500,
+                    term_constraints: TermConstraints {
+                        min_term_size: 0,
+                        max_term_size: 100,
+                    },
+                    ..MutationConfig::default()
+                };
+            }
+            Profile::Nightly => {
+                config.core_definition = "all".to_string();
+                config.max_duration = None;
+                config.distillation_interval = Some(Duration::from_secs(60 * 60));
+                config.check_determinism = true;
+                config.mutation_config = MutationConfig::default();
+            }
+            Profile::Research => {
+                config.core_definition = "all".to_string();
+                config.max_duration = None;
+                config.distillation_interval = None;
+                config.check_determinism = false;
+                config.mutation_config = MutationConfig {
+                    max_trace_length: 30,
+                    max_trace_size: 10000,
+                    term_constraints: TermConstraints {
+                        min_term_size: 0,
+                        max_term_size: 600,
+                    },
+                    ..MutationConfig::default()
+                };
+            }
+        }
+    }
+
+    /// Whether the results of the campaign are stored as an experiment.
+    pub fn is_experiment(&self) -> bool {
+        matches!(self, Profile::Research)
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "smoke" => Ok(Profile::Smoke),
+            "nightly" => Ok(Profile::Nightly),
+            "research" => Ok(Profile::Research),
+            _ => Err(format!(
+                "unknown profile {}, expected one of {}",
+                name,
+                Profile::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Profile::Smoke => "smoke",
+            Profile::Nightly => "nightly",
+            Profile::Research => "research",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Profile;
+
+    #[test_log::test]
+    fn test_profile_names_roundtrip() {
+        for name in Profile::NAMES {
+            let profile: Profile = name.parse().unwrap();
+            assert_eq!(profile.to_string(), name);
+        }
+
+        assert!("weekly".parse::<Profile>().is_err());
+    }
+}
@@ -2,6 +2,12 @@
 
 use std::env;
 use std::ffi::CStr;
+use std::sync::Mutex;
+
+/// The last report of ASAN which has not been taken by [`take_report`] yet.
+static LAST_REPORT: Mutex<Option<String>> = Mutex::new(None);
+
+type ErrorReportCallback = unsafe extern "C" fn(*const libc::c_char);
 
 #[cfg(target_os = "linux")]
 unsafe extern "C" fn iter_libs(
@@ -59,3 +65,45 @@ pub fn asan_info() {
 
     log::info!("ASAN default options: {}", defaults);
 }
+
+unsafe extern "C" fn store_report(report: *const libc::c_char) {
+    if report.is_null() {
+        return;
+    }
+
+    let report = CStr::from_ptr(report).to_string_lossy().into_owned();
+    if let Ok(mut last_report) = LAST_REPORT.lock() {
+        *last_report = Some(report);
+    }
+}
+
+/// Registers a callback with ASAN which stores the reports for [`take_report`].
+///
+/// ASAN calls the callback before it aborts, or before it continues if errors are recoverable.
+/// The callback is looked up at runtime. Returns `false` if the fuzzer is not running with ASAN.
+pub fn install_report_callback() -> bool {
+    let set_callback = unsafe {
+        libc::dlsym(
+            libc::RTLD_DEFAULT,
+            b"__asan_set_error_report_callback\0".as_ptr() as *const libc::c_char,
+        )
+    };
+
+    if set_callback.is_null() {
+        log::info!("ASAN is not available, reports are not captured");
+        return false;
+    }
+
+    let set_callback: unsafe extern "C" fn(Option<ErrorReportCallback>) =
+        unsafe { std::mem::transmute(set_callback) };
+    unsafe { set_callback(Some(store_report)) };
+
+    log::info!("Capturing ASAN reports");
+    true
+}
+
+/// Returns the report of the last error which has been detected by ASAN, if there was one since
+/// the last call.
+pub fn take_report() -> Option<String> {
+    LAST_REPORT.lock().ok().and_then(|mut report| report.take())
+}
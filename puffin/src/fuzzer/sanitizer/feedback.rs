@@ -0,0 +1,163 @@
+//! Objective feedback for errors which are detected by ASAN.
+//!
+//! The [`AsanFeedback`] is interesting whenever ASAN reported an error during the execution, even
+//! if ASAN was configured to recover from errors and the execution did not crash. The report is
+//! attached to the stored testcase as [`AsanReportMetadata`].
+
+use libafl::prelude::*;
+use libafl_bolts::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::asan::take_report;
+
+/// The report of a sanitizer which has been produced by a testcase.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AsanReportMetadata {
+    /// The sanitizer which produced the report, e.g. `AddressSanitizer` or `LeakSanitizer`
+    pub sanitizer: Option<String>,
+    /// The kind of the error, e.g. `heap-buffer-overflow` or `detected memory leaks`
+    pub kind: Option<String>,
+    /// The `SUMMARY` line of the report
+    pub summary: Option<String>,
+    pub report: String,
+}
+
+libafl_bolts::impl_serdeany!(AsanReportMetadata);
+
+impl AsanReportMetadata {
+    /// Extracts the sanitizer and the kind of the error from the `ERROR` line and the summary
+    /// from the `SUMMARY` line of `report`.
+    pub fn parse(report: String) -> Self {
+        let error = report
+            .lines()
+            .find_map(|line| line.split_once("ERROR: ").map(|(_, error)| error.trim()));
+        let (sanitizer, kind) = match error.and_then(|error| error.split_once(": ")) {
+            Some((sanitizer, kind)) => (
+                Some(sanitizer.to_string()),
+                kind.split(" on ").next().map(str::to_string),
+            ),
+            None => (None, None),
+        };
+
+        let summary = report
+            .lines()
+            .find_map(|line| line.strip_prefix("SUMMARY: "))
+            .map(|summary| summary.trim().to_string());
+
+        Self {
+            sanitizer,
+            kind,
+            summary,
+            report,
+        }
+    }
+}
+
+/// Reports an objective if ASAN reported an error during the execution.
+///
+/// The reports are captured by the callback which is installed with
+/// [`install_report_callback`](super::asan::install_report_callback).
+#[derive(Debug, Default)]
+pub struct AsanFeedback {
+    report: Option<AsanReportMetadata>,
+}
+
+impl AsanFeedback {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Named for AsanFeedback {
+    fn name(&self) -> &str {
+        "AsanFeedback"
+    }
+}
+
+impl<S> Feedback<S> for AsanFeedback
+where
+    S: State,
+{
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &S::Input,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = S>,
+        OT: ObserversTuple<S>,
+    {
+        self.report = take_report().map(AsanReportMetadata::parse);
+
+        if let Some(report) = &self.report {
+            log::warn!(
+                "ASAN reported an error: {}",
+                report.summary.as_deref().unwrap_or("no summary")
+            );
+        }
+
+        Ok(self.report.is_some())
+    }
+
+    fn append_metadata<OT>(
+        &mut self,
+        _state: &mut S,
+        _observers: &OT,
+        testcase: &mut Testcase<S::Input>,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<S>,
+    {
+        if let Some(report) = self.report.take() {
+            testcase.add_metadata(report);
+        }
+
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &S::Input) -> Result<(), Error> {
+        self.report = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AsanReportMetadata;
+
+    #[test_log::test]
+    fn test_parse_report() {
+        let report = "\
+=================================================================
+==4711==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000011 at pc 0x55d1 bp 0x7ffc sp 0x7ffc
+READ of size 1 at 0x602000000011 thread T0
+    #0 0x55d1 in tls_process_server_hello ssl/statem/statem_clnt.c:1432
+SUMMARY: AddressSanitizer: heap-buffer-overflow ssl/statem/statem_clnt.c:1432 in tls_process_server_hello
+==4711==ABORTING
+";
+
+        let metadata = AsanReportMetadata::parse(report.to_string());
+
+        assert_eq!(metadata.sanitizer.as_deref(), Some("AddressSanitizer"));
+        assert_eq!(metadata.kind.as_deref(), Some("heap-buffer-overflow"));
+        assert_eq!(
+            metadata.summary.as_deref(),
+            Some("AddressSanitizer: heap-buffer-overflow ssl/statem/statem_clnt.c:1432 in tls_process_server_hello")
+        );
+        assert_eq!(metadata.report, report);
+    }
+
+    #[test_log::test]
+    fn test_parse_leak_report() {
+        let metadata = AsanReportMetadata::parse(
+            "==1==ERROR: LeakSanitizer: detected memory leaks\n\nSUMMARY: AddressSanitizer: 24 byte(s) leaked in 1 allocation(s).\n"
+                .to_string(),
+        );
+
+        assert_eq!(metadata.sanitizer.as_deref(), Some("LeakSanitizer"));
+        assert_eq!(metadata.kind.as_deref(), Some("detected memory leaks"));
+    }
+}
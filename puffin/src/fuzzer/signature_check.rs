@@ -0,0 +1,213 @@
+//! Detects whether the corpus was created with a different [`Signature`] than the one compiled
+//! into the binary.
+//!
+//! Traces reference function symbols by name and type. If symbols are renamed or their types
+//! change, then traces fail to deserialize. Instead of failing deep inside of LibAFL, the
+//! signature used to create a corpus is stored next to it and compared on startup.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::{fmt, fs};
+
+use libafl::inputs::Input;
+use libafl::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::algebra::signature::Signature;
+use crate::algebra::Matcher;
+use crate::trace::Trace;
+
+const MANIFEST_FILE: &str = ".signature.json";
+
+/// The signature which was used to create a corpus.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct SignatureManifest {
+    pub fingerprint: u64,
+    pub symbols: BTreeMap<String, String>,
+}
+
+impl SignatureManifest {
+    pub fn of(signature: &Signature) -> Self {
+        Self {
+            fingerprint: signature.fingerprint(),
+            symbols: signature.symbols(),
+        }
+    }
+}
+
+/// Differences between the signature of a corpus and the signature of the binary.
+#[derive(Debug, Default, PartialEq)]
+pub struct DriftReport {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<(String, String)>,
+    pub affected_traces: Vec<PathBuf>,
+    pub total_traces: usize,
+}
+
+impl DriftReport {
+    pub fn new(stored: &SignatureManifest, current: &SignatureManifest) -> Self {
+        let added = current
+            .symbols
+            .keys()
+            .filter(|name| !stored.symbols.contains_key(*name))
+            .cloned()
+            .collect();
+        let removed = stored
+            .symbols
+            .keys()
+            .filter(|name| !current.symbols.contains_key(*name))
+            .cloned()
+            .collect();
+        let changed = stored
+            .symbols
+            .iter()
+            .filter_map(|(name, old)| match current.symbols.get(name) {
+                Some(new) if new != old => Some((old.clone(), new.clone())),
+                _ => None,
+            })
+            .collect();
+
+        Self {
+            added,
+            removed,
+            changed,
+            ..Self::default()
+        }
+    }
+
+    /// Whether traces of the corpus can no longer be deserialized.
+    pub fn is_breaking(&self) -> bool {
+        !self.affected_traces.is_empty()
+    }
+}
+
+impl fmt::Display for DriftReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "The corpus was created with a different signature than the one of this binary."
+        )?;
+        for name in &self.removed {
+            writeln!(f, "  removed: {}", name)?;
+        }
+        for name in &self.added {
+            writeln!(f, "  added:   {}", name)?;
+        }
+        for (old, new) in &self.changed {
+            writeln!(f, "  changed: {} => {}", old, new)?;
+        }
+        write!(
+            f,
+            "{} of {} traces can not be deserialized anymore.",
+            self.affected_traces.len(),
+            self.total_traces
+        )?;
+        for path in &self.affected_traces {
+            write!(f, "\n  {}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the signature stored in `corpus_dir` with `signature`.
+///
+/// If the corpus has no stored signature yet, then the current one is stored. If the signatures
+/// differ, a report is logged. The stored signature is migrated if all traces in `trace_dirs`
+/// still deserialize, else an error is returned.
+pub fn check_corpus_signature<M: Matcher>(
+    signature: &Signature,
+    corpus_dir: &Path,
+    trace_dirs: &[&Path],
+) -> Result<(), Error> {
+    let current = SignatureManifest::of(signature);
+    let manifest_path = corpus_dir.join(MANIFEST_FILE);
+
+    let stored = if manifest_path.exists() {
+        let content = fs::read_to_string(&manifest_path)?;
+        Some(serde_json::from_str::<SignatureManifest>(&content)?)
+    } else {
+        None
+    };
+
+    if let Some(stored) = stored {
+        if stored.fingerprint == current.fingerprint {
+            return Ok(());
+        }
+
+        let mut report = DriftReport::new(&stored, &current);
+        for dir in trace_dirs.iter().filter(|dir| dir.is_dir()) {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                let is_hidden = path
+                    .file_name()
+                    .map_or(true, |name| name.to_string_lossy().starts_with('.'));
+                if is_hidden || !path.is_file() {
+                    continue;
+                }
+
+                report.total_traces += 1;
+                if Trace::<M>::from_file(&path).is_err() {
+                    report.affected_traces.push(path);
+                }
+            }
+        }
+
+        if report.is_breaking() {
+            log::error!("{}", report);
+            return Err(Error::illegal_state(format!(
+                "Signature of corpus at {:?} is incompatible with this binary",
+                corpus_dir
+            )));
+        }
+
+        log::warn!("{}", report);
+        log::warn!("Migrating the signature of the corpus at {:?}", corpus_dir);
+    }
+
+    fs::create_dir_all(corpus_dir)?;
+    fs::write(&manifest_path, serde_json::to_string_pretty(&current)?)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{DriftReport, SignatureManifest};
+
+    fn manifest(symbols: &[(&str, &str)]) -> SignatureManifest {
+        SignatureManifest {
+            fingerprint: 0,
+            symbols: symbols
+                .iter()
+                .map(|(name, shape)| (name.to_string(), shape.to_string()))
+                .collect::<BTreeMap<_, _>>(),
+        }
+    }
+
+    #[test_log::test]
+    fn test_drift_report_lists_symbol_changes() {
+        let stored = manifest(&[
+            ("fn_a", "fn_a() -> u8"),
+            ("fn_b", "fn_b(u8) -> u8"),
+            ("fn_c", "fn_c() -> u16"),
+        ]);
+        let current = manifest(&[
+            ("fn_a", "fn_a() -> u8"),
+            ("fn_b", "fn_b(u16) -> u8"),
+            ("fn_d", "fn_d() -> u16"),
+        ]);
+
+        let report = DriftReport::new(&stored, &current);
+
+        assert_eq!(report.added, vec!["fn_d".to_string()]);
+        assert_eq!(report.removed, vec!["fn_c".to_string()]);
+        assert_eq!(
+            report.changed,
+            vec![("fn_b(u8) -> u8".to_string(), "fn_b(u16) -> u8".to_string())]
+        );
+        assert!(!report.is_breaking());
+    }
+}
@@ -6,6 +6,9 @@ use libafl::prelude::mutational::MutatedTransform;
 use libafl::prelude::*;
 use libafl_bolts::prelude::*;
 
+use super::mutations::MutationWeights;
+use super::stats_stage::MUTATIONS;
+
 /// The default mutational stage
 #[derive(Clone, Debug)]
 pub struct PuffinMutationalStage<E, EM, I, M, Z> {
@@ -100,9 +103,107 @@ where
     }
 }
 
+/// Executes a batch of mutants at the same time, see [`ParallelMutationalStage`]
+pub struct ParallelBatch<I> {
+    /// How many mutants are executed at the same time
+    pub size: usize,
+    #[allow(clippy::type_complexity)]
+    pub execute: Box<dyn FnMut(&[I])>,
+}
+
+/// A mutational stage which executes the mutants of a corpus entry in batches.
+///
+/// The mutants of a batch are generated first and executed at the same time by the
+/// [`ParallelBatch`], see [`ParallelHarness`](super::harness::ParallelHarness). The fuzzer then
+/// evaluates them one by one like in the [`StdMutationalStage`], while the harness returns the
+/// results of the batch instead of executing them again. Without a batch, the stage equals the
+/// [`StdMutationalStage`].
+pub struct ParallelMutationalStage<E, EM, M, Z>
+where
+    Z: UsesState,
+{
+    mutator: M,
+    batch: Option<ParallelBatch<<Z::State as UsesInput>::Input>>,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(E, EM, Z)>,
+}
+
+impl<E, EM, M, Z> ParallelMutationalStage<E, EM, M, Z>
+where
+    Z: UsesState,
+{
+    pub fn new(mutator: M, batch: Option<ParallelBatch<<Z::State as UsesInput>::Input>>) -> Self {
+        Self {
+            mutator,
+            batch,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<E, EM, M, Z> UsesState for ParallelMutationalStage<E, EM, M, Z>
+where
+    E: UsesState<State = Z::State>,
+    EM: UsesState<State = Z::State>,
+    Z: UsesState,
+{
+    type State = Z::State;
+}
+
+impl<E, EM, M, Z> Stage<E, EM, Z> for ParallelMutationalStage<E, EM, M, Z>
+where
+    E: UsesState<State = Z::State>,
+    EM: UsesState<State = Z::State>,
+    M: Mutator<Z::Input, Z::State>,
+    Z: Evaluator<E, EM>,
+    Z::State: HasCorpus + HasRand,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Z::State,
+        manager: &mut EM,
+        corpus_idx: CorpusId,
+    ) -> Result<(), Error> {
+        let iterations = 1 + state
+            .rand_mut()
+            .below(mutational::DEFAULT_MUTATIONAL_MAX_ITERATIONS);
+        let batch_size = self.batch.as_ref().map_or(1, |batch| batch.size.max(1));
+        let input = state.corpus().cloned_input_for_id(corpus_idx)?;
+
+        let mut i = 0;
+        while i < iterations {
+            let mut mutants = vec![];
+            let mut stage_indices = vec![];
+            while mutants.len() < batch_size && i < iterations {
+                let mut mutant = input.clone();
+                if self.mutator.mutate(state, &mut mutant, i as i32)? == MutationResult::Mutated {
+                    mutants.push(mutant);
+                    stage_indices.push(i as i32);
+                }
+                i += 1;
+            }
+
+            if let Some(batch) = &mut self.batch {
+                (batch.execute)(&mutants);
+            }
+
+            for (stage_idx, mutant) in stage_indices.into_iter().zip(mutants) {
+                let (_, corpus_idx) = fuzzer.evaluate_input(state, executor, manager, mutant)?;
+                self.mutator.post_exec(state, stage_idx, corpus_idx)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
 //-----------------------------
 
 /// A [`Mutator`] that schedules one of the embedded mutations on each call.
+///
+/// The mutations are chosen according to their [`MutationWeights`].
 pub struct PuffinScheduledMutator<I, MT, S>
 where
     I: Input,
@@ -112,6 +213,7 @@ where
     mutations: MT,
     phantom: PhantomData<(I, S)>,
     max_mutations_per_iteration: u64,
+    weights: MutationWeights,
 }
 
 impl<I, MT, S> Debug for PuffinScheduledMutator<I, MT, S>
@@ -185,13 +287,33 @@ where
 {
     /// Compute the number of iterations used to apply stacked mutations
     fn iterations(&self, state: &mut S, _: &I) -> u64 {
-        state.rand_mut().below(self.max_mutations_per_iteration)
+        1 + state.rand_mut().below(self.max_mutations_per_iteration)
     }
 
     /// Get the next mutation to apply
     fn schedule(&self, state: &mut S, _: &I) -> MutationId {
-        debug_assert!(!self.mutations().is_empty());
-        (state.rand_mut().below(self.mutations().len() as u64) as usize).into()
+        self.schedule_index(state).into()
+    }
+
+    /// Applies stacked mutations and records for each whether it changed the input
+    fn scheduled_mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        stage_idx: i32,
+    ) -> Result<MutationResult, Error> {
+        let mut result = MutationResult::Skipped;
+        for _ in 0..self.iterations(state, input) {
+            let index = self.schedule_index(state);
+            let outcome =
+                self.mutations_mut()
+                    .get_and_mutate(index.into(), state, input, stage_idx)?;
+            MUTATIONS.record(index, outcome);
+            if outcome == MutationResult::Mutated {
+                result = MutationResult::Mutated;
+            }
+        }
+        Ok(result)
     }
 }
 
@@ -201,13 +323,36 @@ where
     MT: MutatorsTuple<I, S>,
     S: HasRand,
 {
-    #[allow(dead_code)]
+    /// Chooses the index of the next mutation according to the weights
+    fn schedule_index(&self, state: &mut S) -> usize {
+        debug_assert!(!self.mutations().is_empty());
+        let weights = self.weights.as_slice();
+        let total: u64 = weights.iter().map(|weight| u64::from(*weight)).sum();
+
+        let mut choice = state.rand_mut().below(total);
+        for (index, weight) in weights.iter().enumerate() {
+            if choice < u64::from(*weight) {
+                return index;
+            }
+            choice -= u64::from(*weight);
+        }
+
+        unreachable!("the choice is below the sum of the weights")
+    }
+
     /// Create a new [`PuffinScheduledMutator`] instance specifying mutations
-    pub fn new(mutations: MT, max_mutations_per_iteration: u64) -> Self {
+    pub fn new(mutations: MT, max_mutations_per_iteration: u64, weights: MutationWeights) -> Self {
+        assert_eq!(
+            mutations.len(),
+            weights.as_slice().len(),
+            "each mutation needs a weight"
+        );
+
         PuffinScheduledMutator {
             mutations,
             phantom: PhantomData,
             max_mutations_per_iteration,
+            weights,
         }
     }
 }
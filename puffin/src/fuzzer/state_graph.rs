@@ -0,0 +1,102 @@
+//! Coverage of the state machines of the PUTs.
+//!
+//! The edge coverage mostly rewards new paths through the parsers of a PUT. The state graph
+//! instead approximates the handshake state machine of each agent by the sequence of messages
+//! which the agent sent and received, see
+//! [`AgentCounters::history`](crate::counters::AgentCounters::history). Each pair of consecutive
+//! messages is a transition of the state machine.
+//!
+//! The transitions of an execution are hashed into the [`state_graph_map`], which is observed like
+//! the coverage map of the edges. The map feedback keeps the transitions of all previous
+//! executions, such that testcases which exercise a new transition are added to the corpus.
+
+use std::hash::{BuildHasher, Hash, Hasher};
+
+use crate::agent::AgentName;
+use crate::algebra::Matcher;
+use crate::counters::{Direction, TraceCounters};
+
+pub const STATE_GRAPH_MAP_SIZE: usize = 1 << 16;
+pub const STATE_GRAPH_OBSERVER_NAME: &str = "state_graph_observer";
+pub const STATE_GRAPH_FEEDBACK_NAME: &str = "state_graph";
+
+static mut STATE_GRAPH_MAP: [u8; STATE_GRAPH_MAP_SIZE] = [0; STATE_GRAPH_MAP_SIZE];
+
+/// The map of the transitions which have been taken during the current execution
+pub fn state_graph_map() -> &'static mut [u8] {
+    unsafe { &mut *std::ptr::addr_of_mut!(STATE_GRAPH_MAP) }
+}
+
+/// Records the transitions of all agents of an execution in `map`.
+pub fn record_transitions<M: Matcher>(counters: &TraceCounters<M>, map: &mut [u8]) {
+    for (agent, agent_counters) in counters.iter() {
+        record_history(agent, agent_counters.history(), map);
+    }
+}
+
+/// Records the transitions between the consecutive messages of `history` in `map`. The first
+/// message is a transition from the initial state of the agent.
+///
+/// Like for edges, a transition which is taken several times increments its entry of the map.
+fn record_history<M: Matcher>(
+    agent: AgentName,
+    history: &[(Direction, Option<M>)],
+    map: &mut [u8],
+) {
+    if map.is_empty() {
+        return;
+    }
+
+    let mut previous = None;
+    for message in history {
+        let index = transition_index(agent, previous, message) % map.len();
+        map[index] = map[index].saturating_add(1);
+        previous = Some(message);
+    }
+}
+
+fn transition_index<M: Matcher>(
+    agent: AgentName,
+    from: Option<&(Direction, Option<M>)>,
+    to: &(Direction, Option<M>),
+) -> usize {
+    let mut hasher = ahash::RandomState::with_seeds(0, 0, 0, 0).build_hasher();
+    agent.hash(&mut hasher);
+    from.hash(&mut hasher);
+    to.hash(&mut hasher);
+    hasher.finish() as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::AnyMatcher;
+
+    #[test_log::test]
+    fn test_record_history() {
+        let agent = AgentName::first();
+        let history = [
+            (Direction::Received, Some(AnyMatcher)),
+            (Direction::Sent, Some(AnyMatcher)),
+            (Direction::Sent, Some(AnyMatcher)),
+            (Direction::Sent, Some(AnyMatcher)),
+        ];
+
+        let mut map = vec![0u8; STATE_GRAPH_MAP_SIZE];
+        record_history(agent, &history, &mut map);
+
+        // start -> received, received -> sent, sent -> sent (twice)
+        let mut hits = map
+            .iter()
+            .filter(|hits| **hits > 0)
+            .copied()
+            .collect::<Vec<_>>();
+        hits.sort();
+        assert_eq!(hits, vec![1, 1, 2]);
+
+        // the same transitions of another agent are different transitions
+        let mut other = vec![0u8; STATE_GRAPH_MAP_SIZE];
+        record_history(agent.next(), &history, &mut other);
+        assert_ne!(map, other);
+    }
+}
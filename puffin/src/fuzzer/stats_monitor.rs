@@ -1,9 +1,11 @@
 //! Stats to display both cumulative and per-client stats
 
 use core::time::Duration;
+use std::collections::BTreeMap;
 use std::fmt::Display;
 use std::fs::{File, OpenOptions};
 use std::io::BufWriter;
+use std::net::UdpSocket;
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
@@ -16,7 +18,8 @@ use serde::Serialize;
 use serde_json::Serializer as JSONSerializer;
 
 use crate::fuzzer::libafl_setup::MAP_FEEDBACK_NAME;
-use crate::fuzzer::stats_stage::{RuntimeStats, STATS};
+use crate::fuzzer::mutations::MutationWeights;
+use crate::fuzzer::stats_stage::{RuntimeStats, REPORTED_SYMBOLS, STATS};
 
 trait ClonableMonitor: Monitor + DynClone {}
 impl ClonableMonitor for TuiMonitor {}
@@ -56,6 +59,21 @@ impl StatsMonitor {
         Self { monitor, handlers }
     }
 
+    /// Additionally sends the metrics as gauges to the statsd daemon at `address`
+    pub fn with_statsd(mut self, address: String) -> Self {
+        self.handlers
+            .push(Box::new(StatsdEventHandler::new(address)));
+        self
+    }
+
+    /// Additionally writes the latest metrics to `path` in the Prometheus text format, e.g. for
+    /// the textfile collector of the node exporter
+    pub fn with_prometheus_file(mut self, path: PathBuf) -> Self {
+        self.handlers
+            .push(Box::new(PrometheusEventHandler::new(path)));
+        self
+    }
+
     fn client(&mut self, id: ClientId) -> Statistics {
         let client = self.client_stats_mut_for(id);
 
@@ -109,6 +127,9 @@ impl StatsMonitor {
 
         error_counter.count(client);
 
+        let symbols = SymbolStatistics::most_expensive(client);
+        let mutations = MutationStatistics::new(client);
+
         let corpus_size = client.corpus_size;
         let objective_size = client.objective_size;
 
@@ -120,11 +141,13 @@ impl StatsMonitor {
                 _ => None,
             });
 
-        Statistics::Client(ClientStatistics {
+        Statistics::Client(Box::new(ClientStatistics {
             id: id.0,
             time: SystemTime::now(),
             trace,
             errors: error_counter,
+            symbols,
+            mutations,
             #[cfg(feature = "introspection")]
             intro: introspect_feature,
             coverage,
@@ -132,7 +155,7 @@ impl StatsMonitor {
             objective_size,
             total_execs,
             exec_per_sec: exec_sec as u64,
-        })
+        }))
     }
 
     fn global(&mut self) -> Statistics {
@@ -184,7 +207,7 @@ impl Monitor for StatsMonitor {
 #[serde(tag = "type")]
 enum Statistics {
     #[serde(rename = "client")]
-    Client(ClientStatistics),
+    Client(Box<ClientStatistics>),
     #[serde(rename = "global")]
     Global(GlobalStatistics),
 }
@@ -247,6 +270,9 @@ struct ClientStatistics {
     time: SystemTime,
     errors: ErrorStatistics,
     trace: TraceStatistics,
+    /// The symbols with the longest evaluation time, empty unless symbol stats are enabled
+    symbols: Vec<SymbolStatistics>,
+    mutations: Vec<MutationStatistics>,
     #[cfg(feature = "introspection")]
     intro: IntrospectStatistics,
     coverage: Option<CoverageStatistics>,
@@ -257,6 +283,21 @@ struct ClientStatistics {
     exec_per_sec: u64,
 }
 
+#[derive(Serialize)]
+struct SymbolStatistics {
+    symbol: String,
+    evaluations: u64,
+    micros: u64,
+}
+
+#[derive(Serialize)]
+struct MutationStatistics {
+    mutation: &'static str,
+    applied: u64,
+    /// Applications which changed the trace
+    mutated: u64,
+}
+
 #[derive(Serialize)]
 struct CoverageStatistics {
     hit: u64,
@@ -300,6 +341,8 @@ struct ErrorStatistics {
     ag_error: u64,
     str_error: u64,
     ext_error: u64,
+    /// Executions which exceeded the memory limit
+    oom: u64,
 }
 
 #[derive(Serialize)]
@@ -387,6 +430,7 @@ impl ErrorStatistics {
             ag_error: 0,
             str_error: 0,
             ext_error: 0,
+            oom: 0,
         }
     }
 
@@ -402,6 +446,7 @@ impl ErrorStatistics {
                 RuntimeStats::ExtractionError(c) => {
                     self.ext_error += get_number(client_stats, c.name)
                 }
+                RuntimeStats::Oom(c) => self.oom += get_number(client_stats, c.name),
                 _ => {}
             }
         }
@@ -457,6 +502,129 @@ impl TraceStatistics {
     }
 }
 
+impl SymbolStatistics {
+    /// The client reports only its most expensive symbols, but these can change over time.
+    /// Therefore, the most expensive of all symbols reported so far are selected again.
+    fn most_expensive(user_stats: &ClientStats) -> Vec<SymbolStatistics> {
+        let mut symbols = vec![];
+
+        for stat_definition in &STATS {
+            if let RuntimeStats::SymbolEvaluation(stats) = stat_definition {
+                let prefix = stats.micros_name("");
+                for (name, value) in &user_stats.user_monitor {
+                    let (Some(symbol), UserStatsValue::Number(micros)) =
+                        (name.strip_prefix(&prefix), value.value())
+                    else {
+                        continue;
+                    };
+
+                    symbols.push(SymbolStatistics {
+                        symbol: symbol.to_string(),
+                        evaluations: get_number(user_stats, &stats.evaluations_name(symbol)),
+                        micros: *micros,
+                    });
+                }
+            }
+        }
+
+        symbols.sort_by(|a, b| b.micros.cmp(&a.micros));
+        symbols.truncate(REPORTED_SYMBOLS);
+        symbols
+    }
+}
+
+impl MutationStatistics {
+    fn new(user_stats: &ClientStats) -> Vec<MutationStatistics> {
+        let mut mutations = vec![];
+
+        for stat_definition in &STATS {
+            if let RuntimeStats::Mutations(stats) = stat_definition {
+                for mutation in MutationWeights::NAMES {
+                    mutations.push(MutationStatistics {
+                        mutation,
+                        applied: get_number(user_stats, &stats.applied_name(mutation)),
+                        mutated: get_number(user_stats, &stats.mutated_name(mutation)),
+                    });
+                }
+            }
+        }
+
+        mutations
+    }
+}
+
+/// A single value which is exported to monitoring systems
+struct Metric {
+    name: &'static str,
+    labels: Vec<(&'static str, String)>,
+    value: f64,
+}
+
+impl Metric {
+    fn new(name: &'static str, value: f64) -> Self {
+        Self {
+            name,
+            labels: vec![],
+            value,
+        }
+    }
+
+    fn label(mut self, key: &'static str, value: impl ToString) -> Self {
+        self.labels.push((key, value.to_string()));
+        self
+    }
+}
+
+impl Statistics {
+    fn metrics(&self) -> Vec<Metric> {
+        match self {
+            Statistics::Client(client) => {
+                let mut metrics = vec![
+                    Metric::new("corpus_size", client.corpus_size as f64),
+                    Metric::new("objective_size", client.objective_size as f64),
+                    Metric::new("total_execs", client.total_execs as f64),
+                    Metric::new("exec_per_sec", client.exec_per_sec as f64),
+                    Metric::new("oom", client.errors.oom as f64),
+                ];
+
+                if let Some(CoverageStatistics { hit, max }) = client.coverage {
+                    if max > 0 {
+                        metrics.push(Metric::new("coverage_density", hit as f64 / max as f64));
+                    }
+                }
+
+                for mutation in &client.mutations {
+                    metrics.push(
+                        Metric::new("mutations_applied", mutation.applied as f64)
+                            .label("mutation", mutation.mutation),
+                    );
+                    if mutation.applied > 0 {
+                        metrics.push(
+                            Metric::new(
+                                "mutation_success_rate",
+                                mutation.mutated as f64 / mutation.applied as f64,
+                            )
+                            .label("mutation", mutation.mutation),
+                        );
+                    }
+                }
+
+                metrics
+                    .into_iter()
+                    .map(|metric| metric.label("client", client.id))
+                    .collect()
+            }
+            Statistics::Global(global) => vec![
+                Metric::new("clients", global.clients as f64),
+                Metric::new("corpus_size", global.corpus_size as f64),
+                Metric::new("objective_size", global.objective_size as f64),
+                Metric::new("total_execs", global.total_execs as f64),
+                Metric::new("exec_per_sec", global.exec_per_sec as f64),
+            ],
+        }
+    }
+}
+
 trait EventHandler: DynClone {
     fn process(&mut self, source: ClientId, msg: &str, stats: &Statistics);
 }
@@ -508,3 +676,131 @@ impl EventHandler for JSONEventHandler {
         stats.serialize(&mut self.serializer).unwrap();
     }
 }
+
+/// Sends the metrics as statsd gauges, e.g. `puffin.client.0.exec_per_sec:1234|g`
+struct StatsdEventHandler {
+    address: String,
+    socket: Option<UdpSocket>,
+}
+
+impl StatsdEventHandler {
+    fn new(address: String) -> Self {
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .map_err(|err| log::error!("Failed to open socket for statsd: {}", err))
+            .ok();
+
+        Self { address, socket }
+    }
+
+    fn line(metric: &Metric) -> String {
+        let mut path = vec!["puffin".to_string()];
+        for (key, value) in &metric.labels {
+            path.push(key.to_string());
+            path.push(value.replace(['.', ':', '|'], "_"));
+        }
+        path.push(metric.name.to_string());
+
+        format!("{}:{}|g", path.join("."), metric.value)
+    }
+}
+
+impl Clone for StatsdEventHandler {
+    fn clone(&self) -> Self {
+        Self::new(self.address.clone())
+    }
+}
+
+impl EventHandler for StatsdEventHandler {
+    fn process(&mut self, _source: ClientId, _msg: &str, stats: &Statistics) {
+        let Some(socket) = &self.socket else {
+            return;
+        };
+
+        for metric in stats.metrics() {
+            if let Err(err) = socket.send_to(Self::line(&metric).as_bytes(), &self.address) {
+                log::warn!(
+                    "Failed to send metrics to statsd at {}: {}",
+                    self.address,
+                    err
+                );
+                return;
+            }
+        }
+    }
+}
+
+/// Keeps the latest metrics of each client and rewrites them to a file in the Prometheus text
+/// format after each event
+#[derive(Clone)]
+struct PrometheusEventHandler {
+    output_path: PathBuf,
+    /// Samples by metric name and labels
+    samples: BTreeMap<&'static str, BTreeMap<String, f64>>,
+}
+
+impl PrometheusEventHandler {
+    fn new(output_path: PathBuf) -> Self {
+        Self {
+            output_path,
+            samples: BTreeMap::new(),
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut text = String::new();
+        for (name, samples) in &self.samples {
+            text.push_str(&format!("# TYPE puffin_{} gauge\n", name));
+            for (labels, value) in samples {
+                text.push_str(&format!("puffin_{}{} {}\n", name, labels, value));
+            }
+        }
+        text
+    }
+}
+
+impl EventHandler for PrometheusEventHandler {
+    fn process(&mut self, _source: ClientId, _msg: &str, stats: &Statistics) {
+        for metric in stats.metrics() {
+            let labels = if metric.labels.is_empty() {
+                String::new()
+            } else {
+                let pairs = metric
+                    .labels
+                    .iter()
+                    .map(|(key, value)| format!("{}={:?}", key, value))
+                    .collect::<Vec<_>>();
+                format!("{{{}}}", pairs.join(","))
+            };
+
+            self.samples
+                .entry(metric.name)
+                .or_default()
+                .insert(labels, metric.value);
+        }
+
+        if let Err(err) = write_file_atomic(&self.output_path, self.render().as_bytes()) {
+            log::warn!(
+                "Failed to write metrics to {}: {}",
+                self.output_path.display(),
+                err
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Metric, StatsdEventHandler};
+
+    #[test]
+    fn test_statsd_line() {
+        let metric = Metric::new("mutation_success_rate", 0.5)
+            .label("mutation", "replace-reuse")
+            .label("client", 3);
+
+        assert_eq!(
+            StatsdEventHandler::line(&metric),
+            "puffin.mutation.replace-reuse.client.3.mutation_success_rate:0.5|g"
+        );
+    }
+}
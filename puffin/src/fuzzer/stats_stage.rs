@@ -1,17 +1,27 @@
+use std::collections::BTreeMap;
 use std::marker::PhantomData;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
 
 use libafl::prelude::*;
+
+use crate::fuzzer::mutations::MutationWeights;
+
 pub enum RuntimeStats {
     FnError(&'static Counter),
     TermError(&'static Counter),
     PutError(&'static Counter),
     IOError(&'static Counter),
+    CodecError(&'static Counter),
     AgentError(&'static Counter),
     StreamError(&'static Counter),
     ExtractionError(&'static Counter),
+    Oom(&'static Counter),
     TraceLength(&'static MinMaxMean),
     TermSize(&'static MinMaxMean),
+    SymbolEvaluation(&'static SymbolStats),
+    Mutations(&'static MutationStats),
 }
 
 impl RuntimeStats {
@@ -24,23 +34,29 @@ impl RuntimeStats {
             RuntimeStats::TermError(inner) => inner.fire(consume),
             RuntimeStats::PutError(inner) => inner.fire(consume),
             RuntimeStats::IOError(inner) => inner.fire(consume),
+            RuntimeStats::CodecError(inner) => inner.fire(consume),
             RuntimeStats::AgentError(inner) => inner.fire(consume),
             RuntimeStats::StreamError(inner) => inner.fire(consume),
             RuntimeStats::ExtractionError(inner) => inner.fire(consume),
+            RuntimeStats::Oom(inner) => inner.fire(consume),
             RuntimeStats::TraceLength(inner) => inner.fire(consume),
             RuntimeStats::TermSize(inner) => inner.fire(consume),
+            RuntimeStats::SymbolEvaluation(inner) => inner.fire(consume),
+            RuntimeStats::Mutations(inner) => inner.fire(consume),
         }
     }
 }
 
 // Fn(FnError),
 pub static FN_ERROR: Counter = Counter::new("fn");
-// Term(String),
+// Term(TermError),
 pub static TERM: Counter = Counter::new("term");
 // Put(String),
 pub static PUT: Counter = Counter::new("put");
-// IO(String),
+// IO(IoError),
 pub static IO: Counter = Counter::new("io");
+// Codec(String),
+pub static CODEC: Counter = Counter::new("codec");
 // Agent(String),
 pub static AGENT: Counter = Counter::new("ag");
 // Stream(String),
@@ -48,20 +64,31 @@ pub static STREAM: Counter = Counter::new("str");
 // Extraction(ContentType),
 pub static EXTRACTION: Counter = Counter::new("extr");
 
+/// Executions which exceeded the memory limit, see [`memory`](super::memory)
+pub static OOM: Counter = Counter::new("oom");
+
 pub static TRACE_LENGTH: MinMaxMean = MinMaxMean::new("trace-length");
 
 pub static TERM_SIZE: MinMaxMean = MinMaxMean::new("term-size");
 
-pub static STATS: [RuntimeStats; 9] = [
+pub static SYMBOL_EVALUATION: SymbolStats = SymbolStats::new("sym");
+
+pub static MUTATIONS: MutationStats = MutationStats::new("mut");
+
+pub static STATS: [RuntimeStats; 13] = [
     RuntimeStats::FnError(&FN_ERROR),
     RuntimeStats::TermError(&TERM),
     RuntimeStats::PutError(&PUT),
     RuntimeStats::IOError(&IO),
+    RuntimeStats::CodecError(&CODEC),
     RuntimeStats::AgentError(&AGENT),
     RuntimeStats::StreamError(&STREAM),
     RuntimeStats::ExtractionError(&EXTRACTION),
+    RuntimeStats::Oom(&OOM),
     RuntimeStats::TraceLength(&TRACE_LENGTH),
     RuntimeStats::TermSize(&TERM_SIZE),
+    RuntimeStats::SymbolEvaluation(&SYMBOL_EVALUATION),
+    RuntimeStats::Mutations(&MUTATIONS),
 ];
 
 pub trait Fire: Sync {
@@ -192,6 +219,155 @@ impl Fire for MinMaxMean {
     }
 }
 
+/// How often each mutation of [`MutationWeights::NAMES`] was applied and how often it actually
+/// changed the trace
+pub struct MutationStats {
+    pub name: &'static str,
+    applied: [AtomicUsize; MutationWeights::NAMES.len()],
+    mutated: [AtomicUsize; MutationWeights::NAMES.len()],
+}
+
+impl MutationStats {
+    const fn new(name: &'static str) -> MutationStats {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicUsize = AtomicUsize::new(0);
+
+        Self {
+            name,
+            applied: [ZERO; MutationWeights::NAMES.len()],
+            mutated: [ZERO; MutationWeights::NAMES.len()],
+        }
+    }
+
+    pub fn record(&self, mutation: usize, result: MutationResult) {
+        self.applied[mutation].fetch_add(1, Ordering::SeqCst);
+        if result == MutationResult::Mutated {
+            self.mutated[mutation].fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Name of the user stat which counts the applications of `mutation`
+    pub fn applied_name(&self, mutation: &str) -> String {
+        format!("{}-applied-{}", self.name, mutation)
+    }
+
+    /// Name of the user stat which counts the applications of `mutation` which changed the trace
+    pub fn mutated_name(&self, mutation: &str) -> String {
+        format!("{}-mutated-{}", self.name, mutation)
+    }
+}
+
+impl Fire for MutationStats {
+    fn fire(
+        &self,
+        consume: &mut dyn FnMut(String, UserStats) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        for (index, mutation) in MutationWeights::NAMES.iter().enumerate() {
+            consume(
+                self.applied_name(mutation),
+                UserStats::new(
+                    UserStatsValue::Number(self.applied[index].load(Ordering::SeqCst) as u64),
+                    AggregatorOps::Sum,
+                ),
+            )?;
+            consume(
+                self.mutated_name(mutation),
+                UserStats::new(
+                    UserStatsValue::Number(self.mutated[index].load(Ordering::SeqCst) as u64),
+                    AggregatorOps::Sum,
+                ),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Number of symbols with the longest evaluation time which are reported
+pub const REPORTED_SYMBOLS: usize = 10;
+
+/// How often a symbol was evaluated and how long these evaluations took in total
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SymbolCost {
+    pub evaluations: u64,
+    pub duration: Duration,
+}
+
+/// Evaluation counts and durations per symbol.
+///
+/// Measuring is disabled by default, because it adds a lock and two clock reads to the
+/// evaluation of each function application.
+pub struct SymbolStats {
+    pub name: &'static str,
+    enabled: AtomicBool,
+    symbols: Mutex<BTreeMap<&'static str, SymbolCost>>,
+}
+
+impl SymbolStats {
+    const fn new(name: &'static str) -> SymbolStats {
+        Self {
+            name,
+            enabled: AtomicBool::new(false),
+            symbols: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn enable(&self) {
+        self.enabled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn record(&self, symbol: &'static str, duration: Duration) {
+        let mut symbols = self.symbols.lock().unwrap();
+        let cost = symbols.entry(symbol).or_default();
+        cost.evaluations += 1;
+        cost.duration += duration;
+    }
+
+    /// The `n` symbols with the longest total evaluation time, the most expensive first
+    pub fn most_expensive(&self, n: usize) -> Vec<(&'static str, SymbolCost)> {
+        let symbols = self.symbols.lock().unwrap();
+        let mut costs: Vec<_> = symbols.iter().map(|(name, cost)| (*name, *cost)).collect();
+        costs.sort_by(|(_, a), (_, b)| b.duration.cmp(&a.duration));
+        costs.truncate(n);
+        costs
+    }
+
+    /// Name of the user stat which counts the evaluations of `symbol`
+    pub fn evaluations_name(&self, symbol: &str) -> String {
+        format!("{}-evals-{}", self.name, symbol)
+    }
+
+    /// Name of the user stat which sums the evaluation time of `symbol` in microseconds
+    pub fn micros_name(&self, symbol: &str) -> String {
+        format!("{}-us-{}", self.name, symbol)
+    }
+}
+
+impl Fire for SymbolStats {
+    fn fire(
+        &self,
+        consume: &mut dyn FnMut(String, UserStats) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        for (symbol, cost) in self.most_expensive(REPORTED_SYMBOLS) {
+            consume(
+                self.evaluations_name(symbol),
+                UserStats::new(UserStatsValue::Number(cost.evaluations), AggregatorOps::Sum),
+            )?;
+            consume(
+                self.micros_name(symbol),
+                UserStats::new(
+                    UserStatsValue::Number(cost.duration.as_micros() as u64),
+                    AggregatorOps::Sum,
+                ),
+            )?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct StatsStage<E, EM, Z> {
     #[allow(clippy::type_complexity)]
@@ -265,3 +441,40 @@ where
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{SymbolCost, SymbolStats};
+
+    #[test]
+    fn test_most_expensive_symbols() {
+        let stats = SymbolStats::new("sym");
+        stats.record("fn_cheap", Duration::from_micros(1));
+        stats.record("fn_cheap", Duration::from_micros(1));
+        stats.record("fn_expensive", Duration::from_millis(3));
+        stats.record("fn_medium", Duration::from_micros(50));
+
+        assert_eq!(
+            stats.most_expensive(2),
+            vec![
+                (
+                    "fn_expensive",
+                    SymbolCost {
+                        evaluations: 1,
+                        duration: Duration::from_millis(3)
+                    }
+                ),
+                (
+                    "fn_medium",
+                    SymbolCost {
+                        evaluations: 1,
+                        duration: Duration::from_micros(50)
+                    }
+                ),
+            ]
+        );
+        assert_eq!(stats.most_expensive(10)[2].1.evaluations, 2);
+    }
+}
@@ -0,0 +1,230 @@
+//! Corpus synchronization with other fuzzers through a shared directory.
+//!
+//! The layout follows the sync directories of AFL: each fuzzer owns the subdirectory
+//! `<sync_dir>/<name>/queue` and only writes there. Every `interval` the [`SyncStage`] exports
+//! the new entries of the corpus to its own queue and executes the entries which other fuzzers
+//! added to their queues. Entries are deduplicated by the hash of the trace, such that traces are
+//! neither exported twice nor imported again after another fuzzer exported them.
+
+use std::collections::HashSet;
+use std::fs;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use libafl::prelude::*;
+
+/// Where and how often the corpus is synchronized, see [`SyncStage`]
+#[derive(Clone, Debug)]
+pub struct SyncConfig {
+    pub directory: PathBuf,
+    /// Name of the subdirectory which this fuzzer owns
+    pub name: String,
+    pub interval: Duration,
+}
+
+impl SyncConfig {
+    fn queue(&self) -> PathBuf {
+        self.directory.join(&self.name).join("queue")
+    }
+}
+
+/// The hash by which entries of the sync directory are deduplicated
+pub fn input_hash<I: Hash>(input: &I) -> u64 {
+    let mut hasher = ahash::RandomState::with_seeds(0, 0, 0, 0).build_hasher();
+    input.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A [`Stage`] which periodically exchanges corpus entries with other fuzzers.
+#[derive(Clone, Debug)]
+pub struct SyncStage<E, EM, Z> {
+    config: Option<SyncConfig>,
+    last_run: Instant,
+    /// Hashes of the traces which were exported or imported
+    known: HashSet<u64>,
+    /// Corpus entries which were already considered for the export
+    exported_ids: HashSet<CorpusId>,
+    /// Files of other fuzzers which were already read
+    imported_files: HashSet<PathBuf>,
+    exported: usize,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(E, EM, Z)>,
+}
+
+impl<E, EM, Z> UsesState for SyncStage<E, EM, Z>
+where
+    E: UsesState<State = Z::State>,
+    EM: UsesState<State = Z::State>,
+    Z: UsesState,
+{
+    type State = Z::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for SyncStage<E, EM, Z>
+where
+    E: UsesState<State = Z::State>,
+    EM: UsesState<State = Z::State>,
+    Z: Evaluator<E, EM>,
+    Z::State: HasCorpus,
+    <Z::State as UsesInput>::Input: Hash,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Z::State,
+        manager: &mut EM,
+        _corpus_idx: CorpusId,
+    ) -> Result<(), Error> {
+        let config = if let Some(config) = &self.config {
+            config.clone()
+        } else {
+            return Ok(());
+        };
+
+        if self.last_run.elapsed() < config.interval {
+            return Ok(());
+        }
+        self.last_run = Instant::now();
+
+        let queue = config.queue();
+        fs::create_dir_all(&queue)?;
+
+        let exported = self.export(state, &queue)?;
+
+        let mut imported = 0;
+        for file in self.new_foreign_files(&config)? {
+            let input = match <Z::State as UsesInput>::Input::from_file(&file) {
+                Ok(input) => input,
+                Err(err) => {
+                    log::debug!("Skipping {} during sync: {}", file.display(), err);
+                    continue;
+                }
+            };
+
+            if !self.known.insert(input_hash(&input)) {
+                continue;
+            }
+
+            let (result, _) = fuzzer.evaluate_input(state, executor, manager, input)?;
+            if result != ExecuteInputResult::None {
+                imported += 1;
+            }
+        }
+
+        log::info!(
+            "Synchronized corpus: {} entries exported, {} interesting entries imported",
+            exported,
+            imported
+        );
+
+        Ok(())
+    }
+}
+
+impl<E, EM, Z> SyncStage<E, EM, Z>
+where
+    E: UsesState<State = Z::State>,
+    EM: UsesState<State = Z::State>,
+    Z: UsesState,
+    Z::State: HasCorpus,
+    <Z::State as UsesInput>::Input: Hash,
+{
+    /// Writes the corpus entries which were added since the last export to `queue`
+    fn export(&mut self, state: &Z::State, queue: &Path) -> Result<usize, Error> {
+        let corpus = state.corpus();
+
+        let mut exported = 0;
+        for id in corpus.ids() {
+            if !self.exported_ids.insert(id) {
+                continue;
+            }
+
+            let input = corpus.cloned_input_for_id(id)?;
+            let hash = input_hash(&input);
+            if !self.known.insert(hash) {
+                continue;
+            }
+
+            input.to_file(queue.join(format!("id:{:06},hash:{:016x}", self.exported, hash)))?;
+            self.exported += 1;
+            exported += 1;
+        }
+
+        Ok(exported)
+    }
+}
+
+impl<E, EM, Z> SyncStage<E, EM, Z> {
+    /// Creates a new stage. If `config` is `None`, the stage does nothing.
+    pub fn new(config: Option<SyncConfig>) -> Self {
+        Self {
+            config,
+            last_run: Instant::now(),
+            known: HashSet::new(),
+            exported_ids: HashSet::new(),
+            imported_files: HashSet::new(),
+            exported: 0,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The files in the queues of other fuzzers which were not read yet
+    fn new_foreign_files(&mut self, config: &SyncConfig) -> Result<Vec<PathBuf>, Error> {
+        let mut files = vec![];
+
+        for fuzzer in fs::read_dir(&config.directory)? {
+            let fuzzer = fuzzer?;
+            if fuzzer.file_name() == config.name.as_str() {
+                continue;
+            }
+
+            let Ok(entries) = fs::read_dir(fuzzer.path().join("queue")) else {
+                continue;
+            };
+
+            for entry in entries {
+                let path = entry?.path();
+                if path.is_file() && self.imported_files.insert(path.clone()) {
+                    files.push(path);
+                }
+            }
+        }
+
+        files.sort();
+        Ok(files)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{SyncConfig, SyncStage};
+
+    #[test]
+    fn test_new_foreign_files() {
+        let directory = std::env::temp_dir().join(format!("puffin-sync-{}", std::process::id()));
+        let config = SyncConfig {
+            directory: directory.clone(),
+            name: "own".to_string(),
+            interval: Duration::ZERO,
+        };
+
+        std::fs::create_dir_all(config.queue()).unwrap();
+        std::fs::create_dir_all(directory.join("other").join("queue")).unwrap();
+        std::fs::write(config.queue().join("id:000000"), b"").unwrap();
+        std::fs::write(directory.join("other/queue/id:000000"), b"").unwrap();
+
+        let mut stage = SyncStage::<(), (), ()>::new(Some(config.clone()));
+        assert_eq!(
+            stage.new_foreign_files(&config).unwrap(),
+            vec![directory.join("other/queue/id:000000")]
+        );
+        assert!(stage.new_foreign_files(&config).unwrap().is_empty());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
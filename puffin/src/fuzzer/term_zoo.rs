@@ -1,15 +1,75 @@
 //! Generates a zoo of terms form a [`Signature`]. For each function symbol in the signature
 //! a closed term is generated and added to the zoo.
+//!
+//! Single terms of a given type can also be generated with [`TermZoo::generate_term_of_type`].
+//!
+//! Whole traces can be sampled with [`generate_random_trace`], which is useful to diversify the
+//! initial corpus and for property tests of the evaluation of terms. The probability of each
+//! function symbol can be controlled with [`SymbolWeights`].
+
+use std::collections::HashMap;
 
 use libafl_bolts::rands::Rand;
 
+use crate::agent::{AgentDescriptor, AgentName};
 use crate::algebra::atoms::Function;
+use crate::algebra::dynamic_function::TypeShape;
 use crate::algebra::signature::{FunctionDefinition, Signature};
-use crate::algebra::{Matcher, Term};
+use crate::algebra::{remove_prefix, Matcher, Term};
 use crate::fuzzer::mutations::util::Choosable;
+use crate::protocol::ProtocolBehavior;
+use crate::trace::{InputAction, Trace};
 
 const MAX_DEPTH: u16 = 8; // how deep terms we allow max
 const MAX_TRIES: u16 = 100; // How often we want to try to generate before stopping
+const MAX_STEPS: u64 = 4; // how many input steps a random trace has at most
+
+/// Weights of function symbols when sampling terms. Symbols without a weight have a weight of 1,
+/// and symbols with a weight of 0 are never chosen.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolWeights {
+    weights: HashMap<String, u64>,
+}
+
+impl SymbolWeights {
+    /// Sets the weight of the symbol `name`, which may be given with or without module prefix.
+    pub fn set(&mut self, name: impl Into<String>, weight: u64) {
+        self.weights.insert(name.into(), weight);
+    }
+
+    pub fn weight(&self, name: &str) -> u64 {
+        self.weights
+            .get(name)
+            .or_else(|| self.weights.get(&remove_prefix(name)))
+            .copied()
+            .unwrap_or(1)
+    }
+
+    fn choose<'a, R: Rand>(
+        &self,
+        definitions: &'a [FunctionDefinition],
+        rand: &mut R,
+    ) -> Option<&'a FunctionDefinition> {
+        let total: u64 = definitions
+            .iter()
+            .map(|(shape, _)| self.weight(shape.name))
+            .sum();
+        if total == 0 {
+            return None;
+        }
+
+        let mut index = rand.below(total);
+        definitions.iter().find(|(shape, _)| {
+            let weight = self.weight(shape.name);
+            if index < weight {
+                true
+            } else {
+                index -= weight;
+                false
+            }
+        })
+    }
+}
 
 pub struct TermZoo<M: Matcher> {
     terms: Vec<Term<M>>,
@@ -40,6 +100,63 @@ impl<M: Matcher> TermZoo<M> {
         Self { terms }
     }
 
+    /// Generates a closed term which returns `typ` and has a depth of at most `depth`, by
+    /// sampling function symbols from the `signature`.
+    pub fn generate_term_of_type<R: Rand>(
+        signature: &Signature,
+        typ: &TypeShape,
+        depth: u16,
+        rand: &mut R,
+    ) -> Option<Term<M>> {
+        for _ in 0..MAX_TRIES {
+            let definition = signature.functions_returning(typ).choose(rand)?;
+
+            if let Some(term) = Self::generate_term(signature, definition, depth, rand) {
+                return Some(term);
+            }
+        }
+
+        None
+    }
+
+    /// Like [`TermZoo::generate_term_of_type`], but function symbols are chosen according to
+    /// `weights`. Only constants are chosen at the last level, such that fewer attempts fail.
+    pub fn generate_weighted_term_of_type<R: Rand>(
+        signature: &Signature,
+        typ: &TypeShape,
+        weights: &SymbolWeights,
+        depth: u16,
+        rand: &mut R,
+    ) -> Option<Term<M>> {
+        (0..MAX_TRIES)
+            .find_map(|_| Self::generate_weighted_term(signature, typ, weights, depth, rand))
+    }
+
+    fn generate_weighted_term<R: Rand>(
+        signature: &Signature,
+        typ: &TypeShape,
+        weights: &SymbolWeights,
+        depth: u16,
+        rand: &mut R,
+    ) -> Option<Term<M>> {
+        let (shape, dynamic_fn) = match depth {
+            0 => return None,
+            1 => weights.choose(signature.constants_returning(typ), rand)?,
+            _ => weights.choose(signature.functions_returning(typ), rand)?,
+        };
+
+        let subterms = shape
+            .argument_types
+            .iter()
+            .map(|typ| Self::generate_weighted_term(signature, typ, weights, depth - 1, rand))
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Term::Application(
+            Function::new(shape.clone(), dynamic_fn.clone()),
+            subterms,
+        ))
+    }
+
     fn generate_term<R: Rand>(
         signature: &Signature,
         (shape, dynamic_fn): &FunctionDefinition,
@@ -56,22 +173,16 @@ impl<M: Matcher> TermZoo<M> {
         let mut subterms = Vec::with_capacity(required_types.len());
 
         for typ in required_types {
-            if let Some(possibilities) = signature.functions_by_typ.get(typ) {
-                if let Some(possibility) = possibilities.choose(rand) {
-                    if let Some(subterm) =
-                        Self::generate_term(signature, possibility, depth - 1, rand)
-                    {
-                        subterms.push(subterm)
-                    } else {
-                        // Max depth reached
-                        return None;
-                    }
+            if let Some(possibility) = signature.functions_returning(typ).choose(rand) {
+                if let Some(subterm) = Self::generate_term(signature, possibility, depth - 1, rand)
+                {
+                    subterms.push(subterm)
                 } else {
-                    // No possibilities available
+                    // Max depth reached
                     return None;
                 }
             } else {
-                // Type not available
+                // No possibilities available
                 return None;
             }
         }
@@ -93,3 +204,109 @@ impl<M: Matcher> TermZoo<M> {
         &self.terms
     }
 }
+
+/// Samples a trace in which the attacker sends random messages to a default server.
+///
+/// The trace has between one and a few input steps. Each message is a well-typed term of the
+/// `signature` with a depth of at most `max_depth`. Returns `None` if the signature has no symbols
+/// for the messages of `PB`.
+pub fn generate_random_trace<PB: ProtocolBehavior, R: Rand>(
+    signature: &Signature,
+    max_depth: u16,
+    rand: &mut R,
+) -> Option<Trace<PB::Matcher>> {
+    generate_weighted_random_trace::<PB, R>(signature, &SymbolWeights::default(), max_depth, rand)
+}
+
+/// Like [`generate_random_trace`], but function symbols are chosen according to `weights`.
+pub fn generate_weighted_random_trace<PB: ProtocolBehavior, R: Rand>(
+    signature: &Signature,
+    weights: &SymbolWeights,
+    max_depth: u16,
+    rand: &mut R,
+) -> Option<Trace<PB::Matcher>> {
+    // The types which are accepted as input, see `as_message_flight`
+    let message_types = [
+        TypeShape::of::<PB::OpaqueProtocolMessageFlight>(),
+        TypeShape::of::<PB::ProtocolMessageFlight>(),
+        TypeShape::of::<PB::ProtocolMessage>(),
+        TypeShape::of::<PB::OpaqueProtocolMessage>(),
+    ]
+    .into_iter()
+    .filter(|typ| !signature.functions_returning(typ).is_empty())
+    .collect::<Vec<_>>();
+
+    let agent = AgentName::first();
+    let steps = (0..=rand.below(MAX_STEPS))
+        .map(|_| {
+            let typ = message_types.choose(rand)?;
+            let recipe = TermZoo::<PB::Matcher>::generate_weighted_term_of_type(
+                signature, typ, weights, max_depth, rand,
+            )?;
+            Some(InputAction::new_step(agent, recipe))
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(Trace {
+        descriptors: vec![AgentDescriptor::default()],
+        steps,
+        prior_traces: vec![],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::*;
+    use crate::algebra::dynamic_function::DescribableFunction;
+    use crate::algebra::test_signature::*;
+    use crate::algebra::AnyMatcher;
+
+    fn depth(term: &Term<AnyMatcher>) -> u16 {
+        match term {
+            Term::Variable(_) => 1,
+            Term::Application(_, subterms) => 1 + subterms.iter().map(depth).max().unwrap_or(0),
+        }
+    }
+
+    #[test_log::test]
+    fn test_generate_weighted_term() {
+        let mut rand = StdRand::with_seed(101);
+        let typ = TypeShape::of::<ClientExtensions>();
+
+        for max_depth in 1..5 {
+            let term = TermZoo::<AnyMatcher>::generate_weighted_term_of_type(
+                &TEST_SIGNATURE,
+                &typ,
+                &SymbolWeights::default(),
+                max_depth,
+                &mut rand,
+            )
+            .unwrap();
+            assert!(depth(&term) <= max_depth);
+        }
+
+        let mut weights = SymbolWeights::default();
+        weights.set("fn_client_extensions_append", 0);
+        let term = TermZoo::<AnyMatcher>::generate_weighted_term_of_type(
+            &TEST_SIGNATURE,
+            &typ,
+            &weights,
+            MAX_DEPTH,
+            &mut rand,
+        )
+        .unwrap();
+        assert_eq!(term.name(), fn_client_extensions_new.name());
+
+        weights.set("fn_client_extensions_new", 0);
+        assert!(TermZoo::<AnyMatcher>::generate_weighted_term_of_type(
+            &TEST_SIGNATURE,
+            &typ,
+            &weights,
+            MAX_DEPTH,
+            &mut rand,
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,422 @@
+//! Versioned on-disk format of [`Trace`]s.
+//!
+//! A trace file starts with a header which contains the version of the format and the fingerprint
+//! of the [`Signature`] which was used to write it. The header is followed by the trace encoded
+//! with postcard. Files without a header were written by earlier versions of the fuzzer and
+//! contain only the postcard encoding.
+//!
+//! Version 2 added paths to the queries of variables, see [`crate::query`]. Queries of earlier
+//! versions are read without a path. Version 3 added conditions to
+//! [`ExpectAction`](crate::trace::ExpectAction)s, which are read without conditions from earlier
+//! versions. Version 4 added filters to [`OutputAction`](crate::trace::OutputAction)s. Traces of
+//! earlier versions are read with the types of the [`legacy`] module.
+//!
+//! Traces whose fingerprint differs from the one of the deserialization signature are still
+//! decoded, because most changes of the signature do not affect every trace. If decoding fails,
+//! the error states that the trace is incompatible with the signature.
+//!
+//! When loading the initial corpus, traces which can still be deserialized with the current
+//! signature are upgraded to the current format and written to the corpus directory. All other
+//! traces are skipped.
+//!
+//! Traces of other forks, like upstream tlspuffin, are converted with [`decode_remapped`] and
+//! [`encode_foreign`], which rename the symbols with a [`SymbolMap`].
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use libafl::Error;
+use libafl_bolts::fs::write_file_atomic;
+
+use crate::algebra::remap::{with_symbol_map, SymbolMap};
+use crate::algebra::signature::Signature;
+use crate::algebra::{try_deserialize_signature, Matcher};
+use crate::trace::Trace;
+
+const MAGIC: &[u8; 4] = b"PUFT";
+
+/// Version of the format which is written by [`encode`].
+pub const FORMAT_VERSION: u16 = 4;
+
+/// Directory in the corpus directory to which outdated traces of the initial corpus are upgraded.
+/// It is hidden, like other files of the fuzzer which are not corpus entries.
+pub const MIGRATED_DIR: &str = ".migrated";
+
+/// Version of files without header
+const HEADERLESS_VERSION: u16 = 1;
+
+const HEADER_LEN: usize = MAGIC.len() + 2 + 8;
+
+mod legacy;
+
+/// Deserializes the postcard encoding of a trace in the format `version`.
+fn deserialize_versioned<M: Matcher>(
+    version: u16,
+    bytes: &[u8],
+) -> Result<Trace<M>, postcard::Error> {
+    legacy::from_postcard(version, bytes).unwrap_or_else(|| Trace::deserialize_postcard(bytes))
+}
+
+/// Serializes `trace` with postcard in the format `version`.
+fn serialize_versioned<M: Matcher>(
+    version: u16,
+    trace: &Trace<M>,
+) -> Result<Vec<u8>, postcard::Error> {
+    legacy::to_postcard(version, trace).unwrap_or_else(|| trace.serialize_postcard())
+}
+
+/// Header of a trace file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceHeader {
+    pub version: u16,
+    /// [`Signature::fingerprint`] of the signature the trace was written with
+    pub fingerprint: u64,
+}
+
+impl TraceHeader {
+    fn current(signature: &Signature) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            fingerprint: signature.fingerprint(),
+        }
+    }
+
+    /// Parses the header at the start of `bytes`. Returns `None` if `bytes` have no header.
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < HEADER_LEN || !bytes.starts_with(MAGIC) {
+            return None;
+        }
+
+        let version = u16::from_le_bytes(bytes[4..6].try_into().ok()?);
+        let fingerprint = u64::from_le_bytes(bytes[6..HEADER_LEN].try_into().ok()?);
+
+        Some(Self {
+            version,
+            fingerprint,
+        })
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+        bytes.extend_from_slice(&self.fingerprint.to_le_bytes());
+    }
+}
+
+/// A trace which has been read from a trace file.
+#[derive(Debug)]
+pub struct DecodedTrace<M: Matcher> {
+    pub trace: Trace<M>,
+    /// `None` if the file was written without a header
+    pub header: Option<TraceHeader>,
+}
+
+impl<M: Matcher> DecodedTrace<M> {
+    /// Whether the file should be rewritten with the current format and signature.
+    pub fn is_outdated(&self, signature: &Signature) -> bool {
+        self.header != Some(TraceHeader::current(signature))
+    }
+}
+
+/// Encodes `trace` with the current format.
+pub fn encode<M: Matcher>(trace: &Trace<M>, signature: &Signature) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN);
+    TraceHeader::current(signature).write(&mut bytes);
+    bytes.extend_from_slice(&trace.serialize_postcard()?);
+    Ok(bytes)
+}
+
+/// Decodes a trace which has been written by [`encode`] or by an earlier version without header.
+///
+/// The symbols of the trace are linked against the deserialization signature, see
+/// [`set_deserialize_signature`](crate::algebra::set_deserialize_signature).
+pub fn decode<M: Matcher>(bytes: &[u8]) -> Result<DecodedTrace<M>, Error> {
+    let header = TraceHeader::parse(bytes);
+
+    let payload = match header {
+        Some(header) if header.version > FORMAT_VERSION => {
+            return Err(Error::unsupported(format!(
+                "Trace format version {} is newer than the supported version {}",
+                header.version, FORMAT_VERSION
+            )));
+        }
+        Some(_) => &bytes[HEADER_LEN..],
+        None => bytes,
+    };
+
+    let version = header.map_or(HEADERLESS_VERSION, |header| header.version);
+    let trace = deserialize_versioned(version, payload).map_err(|err| {
+        match (header, try_deserialize_signature()) {
+            (Some(header), Some(signature)) if !signature.is_compatible(header.fingerprint) => {
+                Error::serialize(format!(
+                    "Trace is incompatible with the signature: it was written with signature \
+                         {:016x}, but the current signature is {:016x}: {}",
+                    header.fingerprint,
+                    signature.fingerprint(),
+                    err
+                ))
+            }
+            _ => Error::serialize(format!("Failed to deserialize trace: {}", err)),
+        }
+    })?;
+
+    Ok(DecodedTrace { trace, header })
+}
+
+/// Decodes a trace which has been written with another signature, like the one of another fork. The
+/// symbols are renamed with `map`. Fails with the names of all symbols which could not be mapped
+/// to the current signature.
+pub fn decode_remapped<M: Matcher>(
+    bytes: &[u8],
+    map: &SymbolMap,
+) -> Result<DecodedTrace<M>, Error> {
+    let (decoded, unmapped) = with_symbol_map(map, || decode(bytes));
+
+    if !unmapped.is_empty() {
+        return Err(Error::serialize(format!(
+            "Unmapped symbols: {}",
+            unmapped.join(", ")
+        )));
+    }
+    decoded
+}
+
+/// Encodes `trace` in the format of upstream tlspuffin, which equals the format of files without
+/// header. The symbols are renamed with `map`.
+pub fn encode_foreign<M: Matcher>(trace: &Trace<M>, map: &SymbolMap) -> Result<Vec<u8>, Error> {
+    let (bytes, _) = with_symbol_map(map, || serialize_versioned(HEADERLESS_VERSION, trace));
+    Ok(bytes?)
+}
+
+/// Prepares the traces in `dir` for loading them into the corpus.
+///
+/// Returns the files which can be loaded. Files with an outdated format or signature are upgraded
+/// to a file of the same name in `migrated_dir`, the files in `dir` are not modified. Files which
+/// can not be deserialized are skipped.
+pub fn prepare_initial_corpus<M: Matcher>(
+    signature: &Signature,
+    dir: &Path,
+    migrated_dir: &Path,
+) -> Result<Vec<PathBuf>, Error> {
+    let mut files = vec![];
+    let mut upgraded = 0;
+    let mut skipped = 0;
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_hidden = path
+            .file_name()
+            .map_or(true, |name| name.to_string_lossy().starts_with('.'));
+        if is_hidden || !path.is_file() {
+            continue;
+        }
+
+        let decoded = match decode::<M>(&fs::read(&path)?) {
+            Ok(decoded) => decoded,
+            Err(err) => {
+                log::warn!("Skipping trace {:?}: {}", path, err);
+                skipped += 1;
+                continue;
+            }
+        };
+
+        if decoded.is_outdated(signature) {
+            fs::create_dir_all(migrated_dir)?;
+            let migrated = migrated_dir.join(path.file_name().unwrap());
+            write_file_atomic(&migrated, &encode(&decoded.trace, signature)?)?;
+            upgraded += 1;
+            files.push(migrated);
+        } else {
+            files.push(path);
+        }
+    }
+
+    log::info!(
+        "Found {} traces in {:?}, upgraded {}, skipped {}",
+        files.len(),
+        dir,
+        upgraded,
+        skipped
+    );
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        decode, decode_remapped, encode, encode_foreign, prepare_initial_corpus,
+        serialize_versioned, TraceHeader, FORMAT_VERSION, HEADERLESS_VERSION, MIGRATED_DIR,
+    };
+    use crate::agent::AgentName;
+    use crate::algebra::remap::SymbolMap;
+    use crate::algebra::test_signature::*;
+    use crate::algebra::{set_deserialize_signature, AnyMatcher, Term};
+    use crate::term;
+    use crate::trace::{Action, ExpectAction, InputAction, OutputAction, Trace};
+
+    fn trace() -> TestTrace {
+        let server = AgentName::first();
+
+        Trace {
+            descriptors: vec![],
+            prior_traces: vec![],
+            steps: vec![
+                InputAction::new_step(
+                    server,
+                    term! { fn_client_extensions_append(fn_client_extensions_new, ((server, 0) / "ClientExtensions/ClientExtension[1]")) },
+                ),
+                OutputAction::new_step(server),
+            ],
+        }
+    }
+
+    fn path_lengths(trace: &TestTrace) -> Vec<usize> {
+        trace
+            .steps
+            .iter()
+            .filter_map(|step| match &step.action {
+                Action::Input(input) => Some(&input.recipe),
+                Action::Output(_)
+                | Action::Expect(_)
+                | Action::Reset(_)
+                | Action::Renegotiate(_)
+                | Action::KeyUpdate(_) => None,
+            })
+            .flatten()
+            .filter_map(|term| match term {
+                Term::Variable(variable) => Some(variable.query.path.len()),
+                Term::Application(_, _) => None,
+            })
+            .collect()
+    }
+
+    #[test_log::test]
+    fn test_decode_current_and_legacy_format() {
+        let _ = set_deserialize_signature(&TEST_SIGNATURE);
+        let trace = trace();
+
+        let current = decode::<AnyMatcher>(&encode(&trace, &TEST_SIGNATURE).unwrap()).unwrap();
+        assert!(!current.is_outdated(&TEST_SIGNATURE));
+        assert_eq!(current.trace.steps.len(), 2);
+        assert_eq!(path_lengths(&current.trace), vec![2]);
+
+        let legacy_bytes = serialize_versioned(HEADERLESS_VERSION, &trace).unwrap();
+        let legacy = decode::<AnyMatcher>(&legacy_bytes).unwrap();
+        assert!(legacy.header.is_none());
+        assert!(legacy.is_outdated(&TEST_SIGNATURE));
+        assert_eq!(legacy.trace.steps.len(), 2);
+        assert_eq!(path_lengths(&legacy.trace), vec![0]);
+    }
+
+    #[test_log::test]
+    fn test_decode_expect_conditions() {
+        let _ = set_deserialize_signature(&TEST_SIGNATURE);
+        let mut trace = trace();
+        trace.steps.push(ExpectAction::new_step_with_conditions(
+            AgentName::first(),
+            vec![],
+            true,
+            vec![term! { fn_client_extensions_new }],
+        ));
+
+        let conditions = |trace: &TestTrace| match &trace.steps[2].action {
+            Action::Expect(expect) => expect.conditions.len(),
+            _ => panic!("expected an expectation"),
+        };
+
+        let current = decode::<AnyMatcher>(&encode(&trace, &TEST_SIGNATURE).unwrap()).unwrap();
+        assert_eq!(conditions(&current.trace), 1);
+
+        let legacy_bytes = serialize_versioned(HEADERLESS_VERSION, &trace).unwrap();
+        let legacy = decode::<AnyMatcher>(&legacy_bytes).unwrap();
+        assert_eq!(conditions(&legacy.trace), 0);
+    }
+
+    #[test_log::test]
+    fn test_decode_rejects_newer_format() {
+        let _ = set_deserialize_signature(&TEST_SIGNATURE);
+
+        let mut bytes = vec![];
+        TraceHeader {
+            version: FORMAT_VERSION + 1,
+            fingerprint: TEST_SIGNATURE.fingerprint(),
+        }
+        .write(&mut bytes);
+        bytes.extend_from_slice(&trace().serialize_postcard().unwrap());
+
+        assert!(decode::<AnyMatcher>(&bytes).is_err());
+    }
+
+    #[test_log::test]
+    fn test_decode_reports_incompatible_signature() {
+        let _ = set_deserialize_signature(&TEST_SIGNATURE);
+
+        let mut map = SymbolMap::default();
+        map.insert("fn_client_extensions_new", "fn_removed_extensions_new");
+        let mut bytes = vec![];
+        TraceHeader {
+            version: HEADERLESS_VERSION,
+            fingerprint: TEST_SIGNATURE.fingerprint() ^ 1,
+        }
+        .write(&mut bytes);
+        bytes.extend_from_slice(&encode_foreign(&trace(), &map).unwrap());
+
+        let err = decode::<AnyMatcher>(&bytes).unwrap_err();
+        assert!(err.to_string().contains("incompatible with the signature"));
+    }
+
+    #[test_log::test]
+    fn test_prepare_initial_corpus_keeps_seeds() {
+        let _ = set_deserialize_signature(&TEST_SIGNATURE);
+        let dir = std::env::temp_dir().join(format!("puffin-seeds-{}", std::process::id()));
+        let seeds = dir.join("seeds");
+        let migrated = dir.join("corpus").join(MIGRATED_DIR);
+        std::fs::create_dir_all(&seeds).unwrap();
+
+        let legacy_bytes = serialize_versioned(HEADERLESS_VERSION, &trace()).unwrap();
+        std::fs::write(seeds.join("legacy.trace"), &legacy_bytes).unwrap();
+        std::fs::write(
+            seeds.join("current.trace"),
+            encode(&trace(), &TEST_SIGNATURE).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(seeds.join("broken.trace"), b"broken").unwrap();
+
+        let mut files =
+            prepare_initial_corpus::<AnyMatcher>(&TEST_SIGNATURE, &seeds, &migrated).unwrap();
+        files.sort();
+
+        assert_eq!(
+            files,
+            vec![migrated.join("legacy.trace"), seeds.join("current.trace")]
+        );
+        assert_eq!(
+            std::fs::read(seeds.join("legacy.trace")).unwrap(),
+            legacy_bytes
+        );
+        let upgraded = decode::<AnyMatcher>(&std::fs::read(&files[0]).unwrap()).unwrap();
+        assert!(!upgraded.is_outdated(&TEST_SIGNATURE));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test_log::test]
+    fn test_convert_foreign_trace() {
+        let _ = set_deserialize_signature(&TEST_SIGNATURE);
+        let trace = trace();
+
+        let mut map = SymbolMap::default();
+        map.insert("fn_upstream_extensions_new", "fn_client_extensions_new");
+        let foreign = encode_foreign(&trace, &map.reversed()).unwrap();
+        assert!(decode::<AnyMatcher>(&foreign).is_err());
+
+        let converted = decode_remapped::<AnyMatcher>(&foreign, &map).unwrap();
+        assert!(converted.header.is_none());
+        assert_eq!(converted.trace.steps.len(), 2);
+        assert_eq!(path_lengths(&converted.trace), vec![0]);
+
+        let err = decode_remapped::<AnyMatcher>(&foreign, &SymbolMap::default()).unwrap_err();
+        assert!(err.to_string().contains("fn_upstream_extensions_new"));
+    }
+}
@@ -0,0 +1,367 @@
+//! Traces in the formats of earlier versions, see [`trace_file`](super).
+//!
+//! The types mirror the ones of a [`Trace`] and carry the version `V` of the format in which they
+//! are (de)serialized. Fields which were added after version `V` are neither read nor written and
+//! are empty when the trace is converted to a [`Trace`].
+
+use std::marker::PhantomData;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::agent::{AgentDescriptor, AgentName};
+use crate::algebra::atoms::{Function, Variable};
+use crate::algebra::dynamic_function::TypeShape;
+use crate::algebra::{Matcher, Term};
+use crate::trace::{
+    Action, ExpectAction, InputAction, KeyUpdateAction, OutputAction, Query, RenegotiateAction,
+    ResetAction, Source, Step, Trace,
+};
+
+/// Version which added paths to queries
+const QUERY_PATHS: u16 = 2;
+/// Version which added conditions to expectations
+const EXPECT_CONDITIONS: u16 = 3;
+/// Version which added filters to outputs
+const OUTPUT_FILTERS: u16 = 4;
+
+/// Deserializes a trace which has been encoded with postcard in the format `version`. Returns
+/// `None` if `version` is not one of the earlier versions.
+pub(super) fn from_postcard<M: Matcher>(
+    version: u16,
+    bytes: &[u8],
+) -> Option<Result<Trace<M>, postcard::Error>> {
+    fn read<M: Matcher, const V: u16>(bytes: &[u8]) -> Result<Trace<M>, postcard::Error> {
+        postcard::from_bytes::<VersionedTrace<M, V>>(bytes).map(Trace::from)
+    }
+
+    match version {
+        1 => Some(read::<M, 1>(bytes)),
+        2 => Some(read::<M, 2>(bytes)),
+        3 => Some(read::<M, 3>(bytes)),
+        _ => None,
+    }
+}
+
+/// Encodes `trace` with postcard in the format `version`. Returns `None` if `version` is not one
+/// of the earlier versions.
+pub(super) fn to_postcard<M: Matcher>(
+    version: u16,
+    trace: &Trace<M>,
+) -> Option<Result<Vec<u8>, postcard::Error>> {
+    fn write<M: Matcher, const V: u16>(trace: &Trace<M>) -> Result<Vec<u8>, postcard::Error> {
+        postcard::to_allocvec(&VersionedTrace::<M, V>::from(trace.clone()))
+    }
+
+    match version {
+        1 => Some(write::<M, 1>(trace)),
+        2 => Some(write::<M, 2>(trace)),
+        3 => Some(write::<M, 3>(trace)),
+        _ => None,
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "M: Matcher")]
+struct VersionedTrace<M: Matcher, const V: u16> {
+    descriptors: Vec<AgentDescriptor>,
+    steps: Vec<VersionedStep<M, V>>,
+    prior_traces: Vec<VersionedTrace<M, V>>,
+}
+
+impl<M: Matcher, const V: u16> From<Trace<M>> for VersionedTrace<M, V> {
+    fn from(trace: Trace<M>) -> Self {
+        Self {
+            descriptors: trace.descriptors,
+            steps: trace.steps.into_iter().map(VersionedStep::from).collect(),
+            prior_traces: trace.prior_traces.into_iter().map(Self::from).collect(),
+        }
+    }
+}
+
+impl<M: Matcher, const V: u16> From<VersionedTrace<M, V>> for Trace<M> {
+    fn from(trace: VersionedTrace<M, V>) -> Self {
+        Self {
+            descriptors: trace.descriptors,
+            steps: trace.steps.into_iter().map(Step::from).collect(),
+            prior_traces: trace.prior_traces.into_iter().map(Self::from).collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "M: Matcher")]
+struct VersionedStep<M: Matcher, const V: u16> {
+    agent: AgentName,
+    action: VersionedAction<M, V>,
+}
+
+impl<M: Matcher, const V: u16> From<Step<M>> for VersionedStep<M, V> {
+    fn from(step: Step<M>) -> Self {
+        let action = match step.action {
+            Action::Input(input) => VersionedAction::Input {
+                recipe: input.recipe.into(),
+            },
+            Action::Output(output) => VersionedAction::Output(VersionedOutput(output)),
+            Action::Expect(expect) => VersionedAction::Expect(expect.into()),
+            Action::Reset(reset) => VersionedAction::Reset(reset),
+            Action::Renegotiate(renegotiate) => VersionedAction::Renegotiate(renegotiate),
+            Action::KeyUpdate(key_update) => VersionedAction::KeyUpdate(key_update),
+        };
+
+        Self {
+            agent: step.agent,
+            action,
+        }
+    }
+}
+
+impl<M: Matcher, const V: u16> From<VersionedStep<M, V>> for Step<M> {
+    fn from(step: VersionedStep<M, V>) -> Self {
+        let action = match step.action {
+            VersionedAction::Input { recipe } => Action::Input(InputAction {
+                recipe: recipe.into(),
+            }),
+            VersionedAction::Output(output) => Action::Output(output.0),
+            VersionedAction::Expect(expect) => Action::Expect(expect.into()),
+            VersionedAction::Reset(reset) => Action::Reset(reset),
+            VersionedAction::Renegotiate(renegotiate) => Action::Renegotiate(renegotiate),
+            VersionedAction::KeyUpdate(key_update) => Action::KeyUpdate(key_update),
+        };
+
+        Self {
+            agent: step.agent,
+            action,
+        }
+    }
+}
+
+/// Mirrors [`Action`], whose variants are encoded by their index
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "M: Matcher")]
+enum VersionedAction<M: Matcher, const V: u16> {
+    Input { recipe: VersionedTerm<M, V> },
+    Output(VersionedOutput<M, V>),
+    Expect(VersionedExpect<M, V>),
+    Reset(ResetAction),
+    Renegotiate(RenegotiateAction),
+    KeyUpdate(KeyUpdateAction),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "M: Matcher")]
+enum VersionedTerm<M: Matcher, const V: u16> {
+    Variable(VersionedVariable<M, V>),
+    Application(Function, Vec<VersionedTerm<M, V>>),
+}
+
+impl<M: Matcher, const V: u16> From<Term<M>> for VersionedTerm<M, V> {
+    fn from(term: Term<M>) -> Self {
+        match term {
+            Term::Variable(variable) => Self::Variable(VersionedVariable {
+                unique_id: variable.unique_id,
+                resistant_id: variable.resistant_id,
+                typ: variable.typ,
+                query: VersionedQuery(variable.query),
+            }),
+            Term::Application(function, args) => {
+                Self::Application(function, args.into_iter().map(Self::from).collect())
+            }
+        }
+    }
+}
+
+impl<M: Matcher, const V: u16> From<VersionedTerm<M, V>> for Term<M> {
+    fn from(term: VersionedTerm<M, V>) -> Self {
+        match term {
+            VersionedTerm::Variable(variable) => Self::Variable(Variable {
+                unique_id: variable.unique_id,
+                resistant_id: variable.resistant_id,
+                typ: variable.typ,
+                query: variable.query.0,
+            }),
+            VersionedTerm::Application(function, args) => {
+                Self::Application(function, args.into_iter().map(Self::from).collect())
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "M: Matcher")]
+struct VersionedVariable<M: Matcher, const V: u16> {
+    unique_id: u32,
+    resistant_id: u32,
+    typ: TypeShape,
+    query: VersionedQuery<M, V>,
+}
+
+/// A [`Query`], which is written without its path before version [`QUERY_PATHS`]
+struct VersionedQuery<M, const V: u16>(Query<M>);
+
+#[derive(Serialize, Deserialize)]
+struct LegacyQuery<M> {
+    source: Option<Source>,
+    matcher: Option<M>,
+    counter: u16,
+}
+
+impl<M: Matcher, const V: u16> Serialize for VersionedQuery<M, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if V < QUERY_PATHS {
+            LegacyQuery {
+                source: self.0.source.clone(),
+                matcher: self.0.matcher.clone(),
+                counter: self.0.counter,
+            }
+            .serialize(serializer)
+        } else {
+            self.0.serialize(serializer)
+        }
+    }
+}
+
+impl<'de, M: Matcher, const V: u16> Deserialize<'de> for VersionedQuery<M, V> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if V < QUERY_PATHS {
+            let LegacyQuery {
+                source,
+                matcher,
+                counter,
+            } = LegacyQuery::deserialize(deserializer)?;
+            Ok(Self(Query {
+                source,
+                matcher,
+                counter,
+                path: vec![],
+            }))
+        } else {
+            Query::deserialize(deserializer).map(Self)
+        }
+    }
+}
+
+/// An [`OutputAction`], which is written without its filter before version [`OUTPUT_FILTERS`]
+struct VersionedOutput<M: Matcher, const V: u16>(OutputAction<M>);
+
+#[derive(Serialize, Deserialize)]
+struct LegacyOutput<M> {
+    phantom: PhantomData<M>,
+}
+
+impl<M: Matcher, const V: u16> Serialize for VersionedOutput<M, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if V < OUTPUT_FILTERS {
+            LegacyOutput::<M> {
+                phantom: PhantomData,
+            }
+            .serialize(serializer)
+        } else {
+            self.0.serialize(serializer)
+        }
+    }
+}
+
+impl<'de, M: Matcher, const V: u16> Deserialize<'de> for VersionedOutput<M, V> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if V < OUTPUT_FILTERS {
+            LegacyOutput::<M>::deserialize(deserializer)?;
+            Ok(Self(OutputAction { filter: None }))
+        } else {
+            OutputAction::deserialize(deserializer).map(Self)
+        }
+    }
+}
+
+/// An [`ExpectAction`], which is written without its conditions before version
+/// [`EXPECT_CONDITIONS`]
+struct VersionedExpect<M: Matcher, const V: u16> {
+    matchers: Vec<Option<M>>,
+    present: bool,
+    conditions: Vec<VersionedTerm<M, V>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "M: Matcher")]
+struct LegacyExpect<M: Matcher> {
+    matchers: Vec<Option<M>>,
+    present: bool,
+}
+
+#[derive(Serialize)]
+#[serde(bound = "M: Matcher")]
+struct ExpectRef<'a, M: Matcher, const V: u16> {
+    matchers: &'a [Option<M>],
+    present: bool,
+    conditions: &'a [VersionedTerm<M, V>],
+}
+
+#[derive(Deserialize)]
+#[serde(bound = "M: Matcher")]
+struct ExpectFields<M: Matcher, const V: u16> {
+    matchers: Vec<Option<M>>,
+    present: bool,
+    conditions: Vec<VersionedTerm<M, V>>,
+}
+
+impl<M: Matcher, const V: u16> From<ExpectAction<M>> for VersionedExpect<M, V> {
+    fn from(expect: ExpectAction<M>) -> Self {
+        Self {
+            matchers: expect.matchers,
+            present: expect.present,
+            conditions: expect.conditions.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl<M: Matcher, const V: u16> From<VersionedExpect<M, V>> for ExpectAction<M> {
+    fn from(expect: VersionedExpect<M, V>) -> Self {
+        Self {
+            matchers: expect.matchers,
+            present: expect.present,
+            conditions: expect.conditions.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl<M: Matcher, const V: u16> Serialize for VersionedExpect<M, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if V < EXPECT_CONDITIONS {
+            LegacyExpect {
+                matchers: self.matchers.clone(),
+                present: self.present,
+            }
+            .serialize(serializer)
+        } else {
+            ExpectRef {
+                matchers: &self.matchers,
+                present: self.present,
+                conditions: &self.conditions,
+            }
+            .serialize(serializer)
+        }
+    }
+}
+
+impl<'de, M: Matcher, const V: u16> Deserialize<'de> for VersionedExpect<M, V> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if V < EXPECT_CONDITIONS {
+            let LegacyExpect { matchers, present } = LegacyExpect::deserialize(deserializer)?;
+            Ok(Self {
+                matchers,
+                present,
+                conditions: vec![],
+            })
+        } else {
+            let ExpectFields {
+                matchers,
+                present,
+                conditions,
+            } = ExpectFields::deserialize(deserializer)?;
+            Ok(Self {
+                matchers,
+                present,
+                conditions,
+            })
+        }
+    }
+}
@@ -79,7 +79,11 @@ impl<M: Matcher> Trace<M> {
                     .recipe
                     .dot_subgraph(tree_mode, i, subgraph_name.as_str())
                     .to_string(),
-                Action::Output(_) => format!(
+                Action::Output(_)
+                | Action::Expect(_)
+                | Action::Reset(_)
+                | Action::Renegotiate(_)
+                | Action::KeyUpdate(_) => format!(
                     "subgraph cluster{} \
                     {{ \
                         peripheries=0;\
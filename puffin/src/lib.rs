@@ -2,25 +2,37 @@
 
 pub mod agent;
 pub mod algebra;
+pub mod capture;
 pub mod claims;
 pub mod cli;
 pub mod codec;
+pub mod conformance;
+pub mod counters;
+pub mod debugger;
 pub mod error;
 pub mod execution;
 pub mod experiment;
 pub mod fuzzer;
 pub mod graphviz;
+pub mod libfuzzer;
 pub mod log;
+pub mod process_put;
 pub mod protocol;
 pub mod put;
 pub mod put_registry;
+pub mod query;
+pub mod report;
+pub mod rng;
 pub mod stream;
 pub mod test_utils;
 pub mod trace;
+pub mod trace_diff;
 pub mod trace_helper;
+pub mod trace_text;
+pub mod triage;
 pub mod variable_data;
 
-pub use {libafl, libafl_bolts};
+pub use {libafl, libafl_bolts, paste};
 
 pub const GIT_REF: &str = match option_env!("GIT_REF") {
     Some(env) => env,
@@ -0,0 +1,117 @@
+//! Entry point for external fuzzing engines, such that the harness can be compared against other
+//! engines than the LibAFL loop, e.g. libFuzzer or AFL++ in frida mode.
+//!
+//! [`libfuzzer_target!`](crate::libfuzzer_target) defines `LLVMFuzzerTestOneInput` for a
+//! protocol. The engine passes trace files, see [`trace_file`](crate::fuzzer::trace_file), which
+//! are executed like by the fuzzer. Security violations and bugs of the PUT abort the process,
+//! such that the engine reports them as crashes. Inputs which are no valid trace are rejected.
+
+use std::os::raw::c_int;
+
+use crate::algebra::set_deserialize_signature;
+use crate::execution::Runner;
+use crate::fuzzer::harness::harness;
+use crate::fuzzer::trace_file;
+use crate::protocol::ProtocolBehavior;
+use crate::put_registry::PutRegistry;
+use crate::trace::Spawner;
+
+/// Tells libFuzzer to not add the input to the corpus
+pub const REJECT_INPUT: c_int = -1;
+
+pub struct LibFuzzerTarget<PB: ProtocolBehavior> {
+    runner: Runner<PB>,
+}
+
+impl<PB: ProtocolBehavior + 'static> LibFuzzerTarget<PB> {
+    /// Executes the traces with the default PUT of `registry`.
+    pub fn new(registry: PutRegistry<PB>) -> Self {
+        // Another target of the same protocol might already have set the signature
+        let _ = set_deserialize_signature(PB::signature());
+
+        Self {
+            runner: Runner::new(registry.clone(), Spawner::new(registry)),
+        }
+    }
+
+    /// Executes the trace file `data`. Returns 0, or [`REJECT_INPUT`] if `data` is no trace.
+    pub fn test_one_input(&self, data: &[u8]) -> c_int {
+        match trace_file::decode::<PB::Matcher>(data) {
+            Ok(decoded) => {
+                harness(&self.runner, &decoded.trace, &[]);
+                0
+            }
+            Err(_) => REJECT_INPUT,
+        }
+    }
+}
+
+/// Defines `LLVMFuzzerTestOneInput` for the protocol `$pb`, whose PUTs are registered by
+/// `$registry`:
+///
+/// ```ignore
+/// puffin::libfuzzer_target!(TLSProtocolBehavior, tls_registry());
+/// ```
+#[macro_export]
+macro_rules! libfuzzer_target {
+    ($pb:ty, $registry:expr) => {
+        /// # Safety
+        ///
+        /// `data` points to `size` readable bytes, or is null if `size` is 0.
+        #[no_mangle]
+        pub unsafe extern "C" fn LLVMFuzzerTestOneInput(
+            data: *const u8,
+            size: usize,
+        ) -> ::std::os::raw::c_int {
+            ::std::thread_local! {
+                static TARGET: $crate::libfuzzer::LibFuzzerTarget<$pb> =
+                    $crate::libfuzzer::LibFuzzerTarget::new($registry);
+            }
+
+            let data = if data.is_null() {
+                &[][..]
+            } else {
+                ::std::slice::from_raw_parts(data, size)
+            };
+
+            TARGET.with(|target| target.test_one_input(data))
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LibFuzzerTarget, REJECT_INPUT};
+    use crate::algebra::set_deserialize_signature;
+    use crate::algebra::test_signature::{
+        TestFactory, TestProtocolBehavior, TestTrace, TEST_SIGNATURE,
+    };
+    use crate::execution::Runner;
+    use crate::fuzzer::trace_file;
+    use crate::put_registry::PutRegistry;
+    use crate::trace::{Spawner, Trace};
+
+    #[test_log::test]
+    fn test_one_input_decodes_trace_files() {
+        // The test protocol has no signature of its own
+        let _ = set_deserialize_signature(&TEST_SIGNATURE);
+        let registry = PutRegistry::<TestProtocolBehavior>::new(
+            [("TESTSTUB_RUST_PUT", Box::new(TestFactory) as _)],
+            "TESTSTUB_RUST_PUT",
+        )
+        .unwrap();
+        let target = LibFuzzerTarget {
+            runner: Runner::new(registry.clone(), Spawner::new(registry)),
+        };
+
+        let trace: TestTrace = Trace {
+            descriptors: vec![],
+            prior_traces: vec![],
+            steps: vec![],
+        };
+        let encoded = trace_file::encode(&trace, &TEST_SIGNATURE).unwrap();
+
+        assert_eq!(target.test_one_input(&encoded), 0);
+        assert_eq!(target.test_one_input(&[0xff; 3]), REJECT_INPUT);
+    }
+}
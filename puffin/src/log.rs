@@ -1,16 +1,107 @@
-use std::env;
 use std::path::Path;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::{env, fmt};
 
 use log::LevelFilter;
 use log4rs::append::console::ConsoleAppender;
 use log4rs::append::file::FileAppender;
+use log4rs::config::runtime::ConfigBuilder;
 use log4rs::config::{Appender, Logger, Root};
 use log4rs::encode::pattern::PatternEncoder;
 use log4rs::{self, Config};
 
+static LOG_FILTER: OnceLock<LogFilter> = OnceLock::new();
+
+static TRACE_IDS: AtomicU64 = AtomicU64::new(0);
+
+/// Keys of the mapped diagnostic context which identify the trace, step and agent of an event
+const CONTEXT_KEYS: [&str; 3] = ["trace", "step", "agent"];
+
+/// Log levels of the root logger and of single modules, e.g. `info,puffin::trace=debug`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilter {
+    root: Option<LevelFilter>,
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl FromStr for LogFilter {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let parse_level = |level: &str| {
+            LevelFilter::from_str(level).map_err(|_| format!("invalid log level {}", level))
+        };
+
+        let mut filter = LogFilter {
+            root: None,
+            modules: vec![],
+        };
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((module, level)) => {
+                    filter
+                        .modules
+                        .push((module.to_string(), parse_level(level)?));
+                }
+                None => filter.root = Some(parse_level(directive)?),
+            }
+        }
+
+        Ok(filter)
+    }
+}
+
+/// Sets the filter which the configurations use instead of `RUST_LOG`. It can only be set once.
+pub fn set_log_filter(filter: LogFilter) -> Result<(), LogFilter> {
+    LOG_FILTER.set(filter)
+}
+
+/// Attaches the events which are logged on this thread to a new trace until the returned guard
+/// is dropped. The guard restores the context of an enclosing trace, e.g. when prior traces are
+/// executed.
+pub fn trace_context() -> TraceContextGuard {
+    let previous =
+        CONTEXT_KEYS.map(|key| log_mdc::get(key, |value| value.map(|value| value.to_string())));
+
+    log_mdc::insert(
+        "trace",
+        TRACE_IDS.fetch_add(1, Ordering::Relaxed).to_string(),
+    );
+    log_mdc::remove("step");
+    log_mdc::remove("agent");
+
+    TraceContextGuard { previous }
+}
+
+/// Attaches the events which are logged on this thread to the step `index` of the agent `agent`.
+pub fn step_context(index: usize, agent: impl fmt::Display) {
+    log_mdc::insert("step", index.to_string());
+    log_mdc::insert("agent", agent.to_string());
+}
+
+pub struct TraceContextGuard {
+    previous: [Option<String>; 3],
+}
+
+impl Drop for TraceContextGuard {
+    fn drop(&mut self) {
+        for (key, value) in CONTEXT_KEYS.iter().zip(self.previous.iter_mut()) {
+            match value.take() {
+                Some(value) => {
+                    log_mdc::insert(*key, value);
+                }
+                None => {
+                    log_mdc::remove(*key);
+                }
+            }
+        }
+    }
+}
+
 pub fn config_default() -> log4rs::Config {
-    Config::builder()
+    with_module_loggers(Config::builder(), &[])
         .appender(appender_stderr("stderr"))
         .build(Root::builder().appender("stderr").build(log_level()))
         .unwrap()
@@ -20,14 +111,14 @@ pub fn config_fuzzing<P>(path: P) -> log4rs::Config
 where
     P: AsRef<Path>,
 {
-    Config::builder()
+    with_module_loggers(Config::builder(), &["libafl"])
         .appender(appender_stderr("stderr"))
         .appender(appender_tofile("tofile", path))
         .logger(
             Logger::builder()
                 .appender("tofile")
                 .additive(false)
-                .build("libafl", log_level()),
+                .build("libafl", module_level("libafl")),
         )
         .build(Root::builder().appender("stderr").build(log_level()))
         .unwrap()
@@ -43,13 +134,38 @@ where
         log_level()
     };
 
-    Config::builder()
+    with_module_loggers(Config::builder(), &[])
         .appender(appender_stderr("stderr"))
         .appender(appender_tofile("tofile", path))
         .build(Root::builder().appender("tofile").build(level))
         .unwrap()
 }
 
+/// Adds a logger for every module of the [`LogFilter`] except `excluded`.
+fn with_module_loggers(mut builder: ConfigBuilder, excluded: &[&str]) -> ConfigBuilder {
+    if let Some(filter) = LOG_FILTER.get() {
+        for (module, level) in &filter.modules {
+            if !excluded.contains(&module.as_str()) {
+                builder = builder.logger(Logger::builder().build(module, *level));
+            }
+        }
+    }
+    builder
+}
+
+fn module_level(module: &str) -> LevelFilter {
+    LOG_FILTER
+        .get()
+        .and_then(|filter| {
+            filter
+                .modules
+                .iter()
+                .find(|(name, _)| name == module)
+                .map(|(_, level)| *level)
+        })
+        .unwrap_or_else(log_level)
+}
+
 fn appender_stderr<S>(name: S) -> Appender
 where
     S: AsRef<str>,
@@ -60,7 +176,7 @@ where
             ConsoleAppender::builder()
                 .target(log4rs::append::console::Target::Stderr)
                 .encoder(Box::new(PatternEncoder::new(
-                    "{h({d(%Y-%m-%dT%H:%M:%S%Z)}\t{m}{n})}",
+                    "{h({d(%Y-%m-%dT%H:%M:%S%Z)}\t{X(trace)(-)}/{X(step)(-)}/{X(agent)(-)}\t{m}{n})}",
                 )))
                 .build(),
         ),
@@ -76,7 +192,9 @@ where
         name.as_ref(),
         Box::new(
             FileAppender::builder()
-                .encoder(Box::new(PatternEncoder::new("{d}\t{l}\t{m}{n}")))
+                .encoder(Box::new(PatternEncoder::new(
+                    "{d}\t{l}\t{X(trace)(-)}\t{X(step)(-)}\t{X(agent)(-)}\t{m}{n}",
+                )))
                 .build(log_path)
                 .unwrap(),
         ),
@@ -84,16 +202,54 @@ where
 }
 
 fn log_level() -> LevelFilter {
-    // TODO allow fined-grain configuration of the log level
-    //
-    // At least for:
-    //   - libafl* during fuzzing
-    //   - tlspuffin's packages
-    //   - tlspuffin's packages during fuzzing (libafl client)
-    //
-    // [MM] Maybe also allow package-level control like env_logger?
-    env::var("RUST_LOG")
-        .ok()
-        .and_then(|level| LevelFilter::from_str(&level).ok())
+    LOG_FILTER
+        .get()
+        .and_then(|filter| filter.root)
+        .or_else(|| {
+            env::var("RUST_LOG")
+                .ok()
+                .and_then(|level| LevelFilter::from_str(&level).ok())
+        })
         .unwrap_or(LevelFilter::Info)
 }
+
+#[cfg(test)]
+mod tests {
+    use log::LevelFilter;
+
+    use super::{step_context, trace_context, LogFilter};
+
+    #[test_log::test]
+    fn test_parse_log_filter() {
+        let filter: LogFilter = "warn, puffin::trace=debug,libafl=off".parse().unwrap();
+
+        assert_eq!(filter.root, Some(LevelFilter::Warn));
+        assert_eq!(
+            filter.modules,
+            vec![
+                ("puffin::trace".to_string(), LevelFilter::Debug),
+                ("libafl".to_string(), LevelFilter::Off)
+            ]
+        );
+        assert!("puffin=loud".parse::<LogFilter>().is_err());
+    }
+
+    #[test_log::test]
+    fn test_nested_trace_context_is_restored() {
+        let current = |key: &str| log_mdc::get(key, |value| value.map(str::to_string));
+
+        let outer = trace_context();
+        step_context(3, "1");
+        let trace = current("trace");
+        {
+            let _inner = trace_context();
+            assert_ne!(current("trace"), trace);
+            assert_eq!(current("step"), None);
+        }
+        assert_eq!(current("trace"), trace);
+        assert_eq!(current("step").as_deref(), Some("3"));
+
+        drop(outer);
+        assert_eq!(current("agent"), None);
+    }
+}
@@ -0,0 +1,403 @@
+//! A PUT which runs in another process, such that libraries which can not be linked into the
+//! fuzzer, e.g. closed-source stacks, can be fuzzed through a small harness.
+//!
+//! The harness is either spawned with the PUT options `prog`, `args` and `cwd` and talks over its
+//! stdin and stdout, or it already listens on the unix socket `socket`. Every request of the
+//! fuzzer is answered with exactly one response:
+//!
+//! ```text
+//! request:  u8 command | u32 length (little endian) | payload
+//! response: u8 status  | u32 length (little endian) | payload
+//! ```
+//!
+//! A status of 0 is success, any other status is an error whose payload is a UTF-8 message. The
+//! commands are:
+//!
+//! | command      | request payload                       | response payload                 |
+//! |--------------|---------------------------------------|----------------------------------|
+//! | 0 `init`     | `key=value` lines, see below          | empty                            |
+//! | 1 `inbound`  | bytes sent to the PUT                 | empty                            |
+//! | 2 `outbound` | empty                                 | bytes which the PUT sent         |
+//! | 3 `progress` | empty                                 | u8 successful, UTF-8 state       |
+//! | 4 `reset`    | empty                                 | empty                            |
+//! | 5 `shutdown` | empty                                 | UTF-8 summary, then the PUT exits|
+//!
+//! The payload of `init` contains the lines `agent=<n>`, `role=server|client` and
+//! `version=<version>` followed by all PUT options.
+//!
+//! If a spawned harness is terminated by a signal, e.g. because the stack crashed, the request
+//! fails with [`Error::PutCrash`], which the fuzzer reports like a crash of a linked PUT.
+
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::marker::PhantomData;
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::agent::{AgentDescriptor, AgentName, AgentType};
+use crate::claims::GlobalClaimList;
+use crate::codec::Codec;
+use crate::error::Error;
+use crate::protocol::ProtocolBehavior;
+use crate::put::{Put, PutOptions};
+use crate::put_registry::{Factory, PutKind};
+use crate::stream::Stream;
+use crate::VERSION_STR;
+
+pub const PROCESS_PUT: &str = "process";
+
+/// How long a harness whose connection broke may take to terminate, see [`ProcessPut::request`]
+const EXIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Commands of the fuzzer to the harness
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum Request {
+    Init = 0,
+    Inbound = 1,
+    Outbound = 2,
+    Progress = 3,
+    Reset = 4,
+    Shutdown = 5,
+}
+
+pub fn new_process_factory<PB: ProtocolBehavior>() -> Box<dyn Factory<PB>> {
+    struct ProcessFactory<PB>(PhantomData<fn() -> PB>);
+
+    impl<PB: ProtocolBehavior> Factory<PB> for ProcessFactory<PB> {
+        fn create(
+            &self,
+            agent_descriptor: &AgentDescriptor,
+            _claims: &GlobalClaimList<PB::Claim>,
+            options: &PutOptions,
+        ) -> Result<Box<dyn Put<PB>>, Error> {
+            Ok(Box::new(ProcessPut::<PB>::new(agent_descriptor, options)?))
+        }
+
+        fn kind(&self) -> PutKind {
+            PutKind::CPUT
+        }
+
+        fn name(&self) -> String {
+            PROCESS_PUT.to_string()
+        }
+
+        fn versions(&self) -> Vec<(String, String)> {
+            vec![(
+                "harness".to_string(),
+                format!("{} ({})", PROCESS_PUT, VERSION_STR),
+            )]
+        }
+
+        fn clone_factory(&self) -> Box<dyn Factory<PB>> {
+            Box::new(ProcessFactory::<PB>(PhantomData))
+        }
+    }
+
+    Box::new(ProcessFactory::<PB>(PhantomData))
+}
+
+/// Request and response channel to the harness, see the [module](self) documentation.
+struct Connection {
+    reader: Box<dyn Read>,
+    writer: Box<dyn Write>,
+}
+
+impl Connection {
+    fn request(&mut self, request: Request, payload: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut header = [0; 5];
+        header[0] = request as u8;
+        header[1..].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+        self.writer.write_all(&header)?;
+        self.writer.write_all(payload)?;
+        self.writer.flush()?;
+
+        self.reader.read_exact(&mut header)?;
+        let len = u32::from_le_bytes([header[1], header[2], header[3], header[4]]);
+        let mut response = vec![0; len as usize];
+        self.reader.read_exact(&mut response)?;
+
+        match header[0] {
+            0 => Ok(response),
+            status => Err(Error::Put(format!(
+                "harness failed {:?} with status {}: {}",
+                request,
+                status,
+                String::from_utf8_lossy(&response)
+            ))),
+        }
+    }
+}
+
+pub struct ProcessPut<PB> {
+    agent_descriptor: AgentDescriptor,
+    connection: Connection,
+    process: Option<Child>,
+    state: String,
+    successful: bool,
+    phantom: PhantomData<fn() -> PB>,
+}
+
+impl<PB: ProtocolBehavior> ProcessPut<PB> {
+    fn new(agent_descriptor: &AgentDescriptor, options: &PutOptions) -> Result<Self, Error> {
+        let (connection, process) = if let Some(socket) = options.get_option("socket") {
+            let stream = UnixStream::connect(socket)?;
+            let connection = Connection {
+                reader: Box::new(BufReader::new(stream.try_clone()?)),
+                writer: Box::new(BufWriter::new(stream)),
+            };
+            (connection, None)
+        } else {
+            let prog = options
+                .get_option("prog")
+                .ok_or_else(|| Error::Agent("Unable to find prog or socket".to_string()))?;
+            let mut command = Command::new(prog);
+            if let Some(args) = options.get_option("args") {
+                command.args(args.split_whitespace());
+            }
+            if let Some(cwd) = options.get_option("cwd") {
+                command.current_dir(cwd);
+            }
+            let mut child = command
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()?;
+
+            let connection = Connection {
+                reader: Box::new(BufReader::new(child.stdout.take().unwrap())),
+                writer: Box::new(BufWriter::new(child.stdin.take().unwrap())),
+            };
+            (connection, Some(child))
+        };
+
+        let mut put = Self {
+            agent_descriptor: agent_descriptor.clone(),
+            connection,
+            process,
+            state: String::new(),
+            successful: false,
+            phantom: PhantomData,
+        };
+        put.init(options)?;
+        Ok(put)
+    }
+
+    /// Sends a request to the harness. If the connection broke because the spawned harness was
+    /// terminated by a signal, the crash is reported as [`Error::PutCrash`].
+    fn request(&mut self, request: Request, payload: &[u8]) -> Result<Vec<u8>, Error> {
+        let err = match self.connection.request(request, payload) {
+            Err(err @ Error::IO(_)) => err,
+            result => return result,
+        };
+        let Some(process) = &mut self.process else {
+            return Err(err);
+        };
+
+        let started = Instant::now();
+        while started.elapsed() < EXIT_TIMEOUT {
+            match process.try_wait() {
+                Ok(Some(status)) => {
+                    return match status.signal() {
+                        Some(signal) => Err(Error::PutCrash(format!(
+                            "harness was terminated by signal {} during {:?}",
+                            signal, request
+                        ))),
+                        None => Err(err),
+                    }
+                }
+                Ok(None) => std::thread::sleep(Duration::from_millis(1)),
+                Err(_) => break,
+            }
+        }
+
+        Err(err)
+    }
+
+    fn init(&mut self, options: &PutOptions) -> Result<(), Error> {
+        let descriptor = &self.agent_descriptor;
+        let mut payload = format!(
+            "agent={}\nrole={}\nversion={:?}\n",
+            descriptor.name,
+            match descriptor.typ {
+                AgentType::Server => "server",
+                AgentType::Client => "client",
+            },
+            descriptor.tls_version
+        );
+        for (key, value) in options.iter() {
+            payload.push_str(&format!("{}={}\n", key, value));
+        }
+
+        self.request(Request::Init, payload.as_bytes()).map(|_| ())
+    }
+}
+
+impl<PB: ProtocolBehavior>
+    Stream<
+        PB::Matcher,
+        PB::ProtocolMessage,
+        PB::OpaqueProtocolMessage,
+        PB::OpaqueProtocolMessageFlight,
+    > for ProcessPut<PB>
+{
+    fn add_inbound_flight(&mut self, encoded_flight: &[u8]) {
+        if let Err(err) = self.request(Request::Inbound, encoded_flight) {
+            log::error!("Failed to pass inbound data to the harness: {}", err);
+        }
+    }
+
+    fn take_message_from_outbound(
+        &mut self,
+    ) -> Result<Option<PB::OpaqueProtocolMessageFlight>, Error> {
+        let outbound = self.request(Request::Outbound, &[])?;
+        Ok(PB::OpaqueProtocolMessageFlight::read_bytes(&outbound))
+    }
+}
+
+impl<PB: ProtocolBehavior> Put<PB> for ProcessPut<PB> {
+    fn progress(&mut self) -> Result<(), Error> {
+        let response = self.request(Request::Progress, &[])?;
+        let (successful, state) = response
+            .split_first()
+            .ok_or_else(|| Error::Put("harness sent an empty progress response".to_string()))?;
+        self.successful = *successful != 0;
+        self.state = String::from_utf8_lossy(state).into_owned();
+        Ok(())
+    }
+
+    fn reset(&mut self, new_name: AgentName) -> Result<(), Error> {
+        self.agent_descriptor.name = new_name;
+        self.request(Request::Reset, &[]).map(|_| ())
+    }
+
+    fn descriptor(&self) -> &AgentDescriptor {
+        &self.agent_descriptor
+    }
+
+    fn describe_state(&self) -> &str {
+        &self.state
+    }
+
+    fn is_state_successful(&self) -> bool {
+        self.successful
+    }
+
+    fn shutdown(&mut self) -> String {
+        let summary = match self.request(Request::Shutdown, &[]) {
+            Ok(summary) => String::from_utf8_lossy(&summary).into_owned(),
+            Err(err) => err.to_string(),
+        };
+        if let Some(mut process) = self.process.take() {
+            let _ = process.wait();
+        }
+        summary
+    }
+
+    fn version() -> String
+    where
+        Self: Sized,
+    {
+        format!("{} ({})", PROCESS_PUT, VERSION_STR)
+    }
+}
+
+impl<PB> Drop for ProcessPut<PB> {
+    fn drop(&mut self) {
+        if let Some(mut process) = self.process.take() {
+            let _ = process.kill();
+            let _ = process.wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    use super::ProcessPut;
+    use crate::agent::{AgentDescriptor, AgentName, TLSVersion};
+    use crate::algebra::test_signature::TestProtocolBehavior;
+    use crate::error::Error;
+    use crate::put::{Put, PutOptions};
+
+    /// Answers the requests of one connection like a harness which completes its handshake on the
+    /// first progress
+    fn serve(listener: UnixListener) -> Vec<u8> {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut requests = vec![];
+
+        loop {
+            let mut header = [0; 5];
+            stream.read_exact(&mut header).unwrap();
+            let len = u32::from_le_bytes([header[1], header[2], header[3], header[4]]);
+            let mut payload = vec![0; len as usize];
+            stream.read_exact(&mut payload).unwrap();
+            requests.push(header[0]);
+
+            let response: &[u8] = match header[0] {
+                0 if payload.starts_with(b"agent=1\nrole=server\n") => b"",
+                3 => b"\x01SSLOK",
+                4 => b"",
+                5 => b"bye",
+                _ => {
+                    stream.write_all(&[1, 3, 0, 0, 0]).unwrap();
+                    stream.write_all(b"bad").unwrap();
+                    continue;
+                }
+            };
+            stream.write_all(&[0]).unwrap();
+            stream
+                .write_all(&(response.len() as u32).to_le_bytes())
+                .unwrap();
+            stream.write_all(response).unwrap();
+
+            if header[0] == 5 {
+                return requests;
+            }
+        }
+    }
+
+    #[test_log::test]
+    fn test_process_put_over_socket() {
+        let path = std::env::temp_dir().join(format!("puffin-process-put-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let harness = thread::spawn(move || serve(listener));
+
+        let descriptor = AgentDescriptor::new_server(AgentName::from_index(1), TLSVersion::V1_3);
+        let options = PutOptions::from(vec![("socket", path.to_str().unwrap())]);
+        let mut put = ProcessPut::<TestProtocolBehavior>::new(&descriptor, &options).unwrap();
+
+        assert!(!put.is_state_successful());
+        put.progress().unwrap();
+        assert!(put.is_state_successful());
+        assert_eq!(put.describe_state(), "SSLOK");
+        put.reset(AgentName::from_index(2)).unwrap();
+        assert_eq!(put.descriptor().name, AgentName::from_index(2));
+        assert_eq!(put.shutdown(), "bye");
+
+        assert_eq!(harness.join().unwrap(), vec![0, 3, 4, 5]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test_log::test]
+    fn test_process_put_reports_crashed_harness() {
+        let script =
+            std::env::temp_dir().join(format!("puffin-crashing-{}.sh", std::process::id()));
+        std::fs::write(&script, "kill -SEGV $$\n").unwrap();
+
+        let descriptor = AgentDescriptor::new_server(AgentName::first(), TLSVersion::V1_3);
+        let options = PutOptions::from(vec![("prog", "sh"), ("args", script.to_str().unwrap())]);
+        let result = ProcessPut::<TestProtocolBehavior>::new(&descriptor, &options);
+        let _ = std::fs::remove_file(&script);
+
+        match result {
+            Err(Error::PutCrash(_)) => {}
+            Err(err) => panic!("unexpected error {}", err),
+            Ok(_) => panic!("the harness did not crash"),
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use std::fmt::Debug;
+use std::rc::Rc;
 
 use crate::algebra::signature::Signature;
 use crate::algebra::Matcher;
@@ -22,6 +23,19 @@ pub trait ExtractKnowledge<M: Matcher>: std::fmt::Debug {
     ) -> Result<(), Error>;
 }
 
+/// Shared data yields the knowledge of the data it points to. This allows to store a flight in the
+/// knowledge and elsewhere in the [`TraceContext`](crate::trace::TraceContext) without copying it.
+impl<M: Matcher, T: ExtractKnowledge<M>> ExtractKnowledge<M> for Rc<T> {
+    fn extract_knowledge<'a>(
+        &'a self,
+        knowledges: &mut Vec<Knowledge<'a, M>>,
+        matcher: Option<M>,
+        source: &'a Source,
+    ) -> Result<(), Error> {
+        (**self).extract_knowledge(knowledges, matcher, source)
+    }
+}
+
 /// Store a message flight, a vec of all the messages sent by the PUT between two steps
 pub trait ProtocolMessageFlight<
     Mt: Matcher,
@@ -83,7 +97,7 @@ pub trait ProtocolMessageDeframer<Mt: Matcher> {
 pub trait ProtocolBehavior: 'static {
     type Matcher: Matcher;
     type Claim: Claim;
-    type SecurityViolationPolicy: SecurityViolationPolicy<Self::Claim>;
+    type SecurityViolationPolicy: SecurityViolationPolicy<Self::Claim, Self::Matcher>;
 
     type ProtocolMessage: ProtocolMessage<Self::Matcher, Self::OpaqueProtocolMessage>;
     type OpaqueProtocolMessage: OpaqueProtocolMessage<Self::Matcher>;
@@ -101,4 +115,10 @@ pub trait ProtocolBehavior: 'static {
 
     /// Creates a sane initial seed corpus.
     fn create_corpus() -> Vec<(Trace<Self::Matcher>, &'static str)>;
+
+    /// Reconstructs a trace from the bytes which a client sent over a captured connection, such
+    /// that real-world traffic can be used as seed. The attacker takes the role of the client.
+    fn import_trace(_client_data: &[u8]) -> Result<Trace<Self::Matcher>, String> {
+        Err("Importing traces is not supported by the protocol".to_string())
+    }
 }
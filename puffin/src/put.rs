@@ -25,6 +25,19 @@ impl PutOptions {
             .find(|(found_key, _value)| -> bool { found_key == key })
             .map(|(_key, value)| value.as_str())
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.options
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    /// Returns the option `key` for the agent `agent`. An option `key@agent`, e.g.
+    /// `cipher_list@1`, takes precedence over the option `key`, which applies to all agents.
+    pub fn get_agent_option(&self, agent: AgentName, key: &str) -> Option<&str> {
+        self.get_option(&format!("{}@{}", key, agent))
+            .or_else(|| self.get_option(key))
+    }
 }
 
 impl<S> From<Vec<(S, S)>> for PutOptions
@@ -78,9 +91,27 @@ pub trait Put<PB: ProtocolBehavior>:
     /// Process incoming buffer, internal progress, can fill in the output buffer
     fn progress(&mut self) -> Result<(), Error>;
 
-    /// In-place reset of the state
+    /// In-place reset of the state of the connection. State which outlives a connection, like
+    /// session caches and ticket keys, should be kept, such that a reused agent can resume the
+    /// sessions of prior traces.
     fn reset(&mut self, new_name: AgentName) -> Result<(), Error>;
 
+    /// Initiates a renegotiation of the session, e.g. by sending a HelloRequest as a server. The
+    /// messages are taken from the outbound channel afterwards.
+    fn renegotiate(&mut self) -> Result<(), Error> {
+        Err(Error::Put(
+            "Renegotiation is not supported by this PUT".to_string(),
+        ))
+    }
+
+    /// Updates the traffic keys of the session. If `update_requested` is `true`, the peer is asked
+    /// to update its keys as well.
+    fn key_update(&mut self, _update_requested: bool) -> Result<(), Error> {
+        Err(Error::Put(
+            "Key updates are not supported by this PUT".to_string(),
+        ))
+    }
+
     fn descriptor(&self) -> &AgentDescriptor;
 
     /// Returns a textual representation of the state in which self is
@@ -97,3 +128,23 @@ pub trait Put<PB: ProtocolBehavior>:
     where
         Self: Sized;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::PutOptions;
+    use crate::agent::AgentName;
+
+    #[test_log::test]
+    fn test_get_agent_option() {
+        let first = AgentName::first();
+        let second = first.next();
+        let options = PutOptions::from(vec![
+            ("groups", "X25519"),
+            (&format!("groups@{}", second), "P-384"),
+        ]);
+
+        assert_eq!(options.get_agent_option(first, "groups"), Some("X25519"));
+        assert_eq!(options.get_agent_option(second, "groups"), Some("P-384"));
+        assert_eq!(options.get_agent_option(second, "alpn"), None);
+    }
+}
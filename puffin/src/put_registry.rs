@@ -1,9 +1,11 @@
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::fmt;
 
 use crate::agent::AgentDescriptor;
 use crate::claims::GlobalClaimList;
 use crate::error::Error;
+use crate::fuzzer::coverage;
 use crate::protocol::ProtocolBehavior;
 use crate::put::{Put, PutOptions};
 
@@ -17,6 +19,10 @@ pub const TCP_PUT: &str = "tcp";
 
 /// Registry for [Factories](Factory). An instance of this is usually defined statically and then
 /// used throughout the fuzzer.
+///
+/// Factories are identified by their id, such that several builds of the same library, e.g.
+/// OpenSSL 1.1.1 and 3.0, can be registered side by side. The [`Spawner`](crate::trace::Spawner)
+/// selects the factory of each agent, which allows traces to compare the versions.
 pub struct PutRegistry<PB> {
     factories: HashMap<String, Box<dyn Factory<PB>>>,
     default_put: String,
@@ -42,24 +48,49 @@ impl<PB: ProtocolBehavior> fmt::Debug for PutRegistry<PB> {
 }
 
 impl<PB: ProtocolBehavior> PutRegistry<PB> {
-    pub fn new<SI, I, S>(puts: I, default: S) -> Self
+    /// Registers the `puts` under their ids. Fails if an id is used twice.
+    pub fn new<SI, I, S>(puts: I, default: S) -> Result<Self, Error>
     where
         SI: Into<String>,
         I: IntoIterator<Item = (SI, Box<dyn Factory<PB>>)>,
         S: Into<String>,
     {
-        let result = Self {
-            factories: puts
-                .into_iter()
-                .map(|(id, f)| (Into::<String>::into(id), f))
-                .collect(),
+        let mut result = Self {
+            factories: HashMap::new(),
             default_put: default.into(),
         };
 
+        for (id, factory) in puts {
+            result.register(id, factory)?;
+        }
+
         // check that the default PUT is actually in the registry
         let _ = result.find_by_id(&result.default_put);
 
-        result
+        Ok(result)
+    }
+
+    /// Adds `factory` under `id`. Fails if another factory is already registered under `id`.
+    pub fn register(
+        &mut self,
+        id: impl Into<String>,
+        factory: Box<dyn Factory<PB>>,
+    ) -> Result<(), Error> {
+        match self.factories.entry(id.into()) {
+            Entry::Occupied(entry) => Err(Error::Agent(format!(
+                "PUT {} is registered twice",
+                entry.key()
+            ))),
+            Entry::Vacant(entry) => {
+                entry.insert(factory);
+                Ok(())
+            }
+        }
+    }
+
+    /// The id of the default PUT
+    pub fn default_id(&self) -> &str {
+        &self.default_put
     }
 
     pub fn default(&self) -> &dyn Factory<PB> {
@@ -81,20 +112,22 @@ impl<PB: ProtocolBehavior> PutRegistry<PB> {
 
     pub fn determinism_reseed_all_factories(&self) {
         log::debug!("[RNG] reseed all PUT factories");
-        for (_, factory) in self.factories.iter() {
-            factory.rng_reseed();
+        for (id, factory) in self.factories.iter() {
+            coverage::with_namespace(coverage::namespace(self, id), || factory.rng_reseed());
         }
     }
 }
 
 impl<PB: ProtocolBehavior> Clone for PutRegistry<PB> {
     fn clone(&self) -> Self {
-        Self::new(
-            self.factories
+        Self {
+            factories: self
+                .factories
                 .iter()
-                .map(|(n, f)| (n.clone(), f.clone_factory())),
-            self.default_put.clone(),
-        )
+                .map(|(n, f)| (n.clone(), f.clone_factory()))
+                .collect(),
+            default_put: self.default_put.clone(),
+        }
     }
 }
 
@@ -123,3 +156,38 @@ pub trait Factory<PB: ProtocolBehavior> {
         log::debug!("[RNG] reseed failed ({}): not supported", self.name());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::algebra::test_signature::{TestFactory, TestProtocolBehavior};
+    use crate::put_registry::PutRegistry;
+
+    #[test_log::test]
+    fn test_register_versions_side_by_side() {
+        let mut registry = PutRegistry::<TestProtocolBehavior>::new(
+            [("TESTSTUB_RUST_PUT", Box::new(TestFactory) as _)],
+            "TESTSTUB_RUST_PUT",
+        )
+        .unwrap();
+
+        assert!(registry
+            .register("TESTSTUB_RUST_PUT_V2", Box::new(TestFactory))
+            .is_ok());
+        assert!(registry
+            .register("TESTSTUB_RUST_PUT", Box::new(TestFactory))
+            .is_err());
+
+        assert_eq!(registry.puts().count(), 2);
+        assert!(registry.find_by_id("TESTSTUB_RUST_PUT_V2").is_some());
+        assert_eq!(registry.clone(), registry);
+
+        assert!(PutRegistry::<TestProtocolBehavior>::new(
+            [
+                ("TESTSTUB_RUST_PUT", Box::new(TestFactory) as _),
+                ("TESTSTUB_RUST_PUT", Box::new(TestFactory) as _),
+            ],
+            "TESTSTUB_RUST_PUT",
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,339 @@
+//! Query language for referencing nested knowledge deterministically.
+//!
+//! A [`Query`] without a path selects the knowledge with the highest specificity which has the
+//! requested type. This is ambiguous if a message contains several values of the same type, like
+//! the extensions of a `ServerHello`. A query path such as
+//!
+//! ```text
+//! (0, 2)/ServerHelloPayload/ServerExtension[3]
+//! ```
+//!
+//! instead navigates through the knowledge which was extracted from the messages of an agent:
+//!
+//! * `(0, 2)` is the source and the counter. The source is either the number of an agent or the
+//!   label of a precomputation.
+//! * The first segment selects the knowledge with the type `ServerHelloPayload` whose index among
+//!   all knowledge of this type from the source equals the counter.
+//! * Each further segment `name[index]` selects the `index`-th knowledge with the type `name` which
+//!   has been extracted after the previously selected knowledge from the same message flight. The
+//!   index defaults to 0.
+//!
+//! Types are named without their module prefix, as in the output of `Term`s. Knowledge is
+//! extracted depth-first, so the knowledge following a message is the content of that message.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::agent::AgentName;
+use crate::algebra::{remove_prefix, Matcher};
+use crate::trace::{Query, Source};
+
+/// A segment of a query path, see the [module documentation](self).
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PathSegment {
+    /// Name of the type without module prefix, e.g. `ServerExtension` or `Vec<u8>`
+    pub type_name: String,
+    pub index: u16,
+}
+
+impl PathSegment {
+    /// Whether knowledge of the type `type_name` is selected by this segment.
+    pub fn matches(&self, type_name: &str) -> bool {
+        remove_prefix(type_name) == self.type_name
+    }
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.index == 0 {
+            write!(f, "{}", self.type_name)
+        } else {
+            write!(f, "{}[{}]", self.type_name, self.index)
+        }
+    }
+}
+
+impl FromStr for PathSegment {
+    type Err = String;
+
+    fn from_str(segment: &str) -> Result<Self, Self::Err> {
+        let segment = segment.trim();
+        let (type_name, index) = match segment.strip_suffix(']') {
+            Some(rest) => {
+                let (type_name, index) = rest
+                    .rsplit_once('[')
+                    .ok_or_else(|| format!("missing [ in segment {}", segment))?;
+                let index = index
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid index in segment {}", segment))?;
+                (type_name.trim(), index)
+            }
+            None => (segment, 0),
+        };
+
+        if type_name.is_empty() || type_name.contains(['[', ']']) {
+            return Err(format!("invalid type name in segment {}", segment));
+        }
+
+        Ok(Self {
+            type_name: type_name.to_string(),
+            index,
+        })
+    }
+}
+
+/// Parses a path like `ServerHelloPayload/ServerExtension[3]`.
+///
+/// The first segment must not have an index, as it is selected by the counter of the query.
+pub fn parse_path(path: &str) -> Result<Vec<PathSegment>, String> {
+    let segments = path
+        .split('/')
+        .map(PathSegment::from_str)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if segments[0].index != 0 {
+        return Err(format!(
+            "the first segment {} is selected by the counter and must not have an index",
+            segments[0]
+        ));
+    }
+
+    Ok(segments)
+}
+
+/// Parses a query like `(0, 2)/ServerHelloPayload/ServerExtension[3]`. The path is optional.
+impl<M: Matcher> FromStr for Query<M> {
+    type Err = String;
+
+    fn from_str(query: &str) -> Result<Self, Self::Err> {
+        let query = query.trim();
+        let (head, path) = query
+            .strip_prefix('(')
+            .and_then(|rest| rest.split_once(')'))
+            .ok_or_else(|| format!("expected (source, counter) in query {}", query))?;
+        let (source, counter) = head
+            .split_once(',')
+            .ok_or_else(|| format!("expected (source, counter) in query {}", query))?;
+
+        let source = source.trim();
+        let source = match source.parse::<u8>() {
+            Ok(agent) => Source::Agent(AgentName::from_index(agent)),
+            Err(_) if !source.is_empty() => Source::Label(source.to_string()),
+            Err(_) => return Err(format!("missing source in query {}", query)),
+        };
+        let counter = counter
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid counter in query {}", query))?;
+
+        let path = match path.trim() {
+            "" => vec![],
+            path => parse_path(
+                path.strip_prefix('/')
+                    .ok_or_else(|| format!("expected / after (source, counter) in {}", query))?,
+            )?,
+        };
+
+        Ok(Query {
+            source: Some(source),
+            matcher: None,
+            counter,
+            path,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+    use std::rc::Rc;
+
+    use super::{parse_path, PathSegment};
+    use crate::agent::AgentName;
+    use crate::algebra::atoms::Variable;
+    use crate::algebra::dynamic_function::TypeShape;
+    use crate::algebra::test_signature::TestProtocolBehavior;
+    use crate::algebra::AnyMatcher;
+    use crate::error::Error;
+    use crate::protocol::ExtractKnowledge;
+    use crate::trace::{
+        Knowledge, KnowledgeFilter, KnowledgeRetention, KnowledgeStore, Query, Source,
+    };
+
+    #[derive(Debug, Clone)]
+    struct Record {
+        payloads: Vec<Vec<u8>>,
+    }
+
+    impl ExtractKnowledge<AnyMatcher> for Record {
+        fn extract_knowledge<'a>(
+            &'a self,
+            knowledges: &mut Vec<Knowledge<'a, AnyMatcher>>,
+            matcher: Option<AnyMatcher>,
+            source: &'a Source,
+        ) -> Result<(), Error> {
+            knowledges.push(Knowledge {
+                source,
+                matcher: matcher.clone(),
+                data: self,
+            });
+            for payload in &self.payloads {
+                knowledges.push(Knowledge {
+                    source,
+                    matcher: matcher.clone(),
+                    data: payload,
+                });
+            }
+            Ok(())
+        }
+    }
+
+    #[test_log::test]
+    fn test_parse_query() {
+        let query: Query<AnyMatcher> = "(1, 2)/Record/Vec<u8>[3]".parse().unwrap();
+
+        assert_eq!(query.source, Some(Source::Agent(AgentName::first().next())));
+        assert_eq!(query.counter, 2);
+        assert_eq!(
+            query.path,
+            vec![
+                PathSegment {
+                    type_name: "Record".to_string(),
+                    index: 0
+                },
+                PathSegment {
+                    type_name: "Vec<u8>".to_string(),
+                    index: 3
+                }
+            ]
+        );
+
+        let label: Query<AnyMatcher> = "(resumption, 0)".parse().unwrap();
+        assert_eq!(label.source, Some(Source::Label("resumption".to_string())));
+        assert!(label.path.is_empty());
+
+        assert!(parse_path("Record[1]/Vec<u8>").is_err());
+        assert!("(0, x)/Record".parse::<Query<AnyMatcher>>().is_err());
+        assert!("(0, 0)Record".parse::<Query<AnyMatcher>>().is_err());
+    }
+
+    #[test_log::test]
+    fn test_find_variable_by_path() {
+        let agent = AgentName::first();
+        let mut store = KnowledgeStore::<TestProtocolBehavior>::new();
+        store.add_raw_knowledge(
+            Record {
+                payloads: vec![vec![1], vec![2]],
+            },
+            Source::Agent(agent),
+        );
+        store.add_raw_knowledge(
+            Record {
+                payloads: vec![vec![3], vec![4], vec![5]],
+            },
+            Source::Agent(agent),
+        );
+
+        let find = |query: &str| {
+            store
+                .find_variable(TypeShape::of::<Vec<u8>>(), &query.parse().unwrap())
+                .map(|data| data.boxed_any().downcast::<Vec<u8>>().unwrap()[0])
+        };
+
+        assert_eq!(find("(0, 0)/Record/Vec<u8>[1]"), Some(2));
+        assert_eq!(find("(0, 1)/Record/Vec<u8>[2]"), Some(5));
+        assert_eq!(find("(0, 0)/Vec<u8>"), Some(1));
+        assert_eq!(find("(0, 3)/Vec<u8>"), Some(4));
+        // the path does not leave the flight of the first segment
+        assert_eq!(find("(0, 0)/Record/Vec<u8>[2]"), None);
+        // the last segment must have the requested type
+        assert_eq!(find("(0, 0)/Record"), None);
+        assert_eq!(find("(1, 0)/Record/Vec<u8>"), None);
+    }
+
+    #[test_log::test]
+    fn test_knowledge_retention() {
+        let agent = AgentName::first();
+        let mut store = KnowledgeStore::<TestProtocolBehavior>::new();
+        store.set_retention(KnowledgeRetention {
+            max_per_source: Some(2),
+        });
+        for payload in 0..4 {
+            store.add_raw_knowledge(
+                Record {
+                    payloads: vec![vec![payload]],
+                },
+                Source::Agent(agent),
+            );
+        }
+
+        let find = |store: &KnowledgeStore<_>, query: &str| {
+            store
+                .find_variable(TypeShape::of::<Vec<u8>>(), &query.parse().unwrap())
+                .map(|data| data.boxed_any().downcast::<Vec<u8>>().unwrap()[0])
+        };
+
+        // the first message was used last, the second and third are evicted
+        assert_eq!(find(&store, "(0, 0)/Record/Vec<u8>"), Some(0));
+        assert_eq!(store.collect_garbage(), 2);
+        assert_eq!(find(&store, "(0, 1)/Record/Vec<u8>"), Some(3));
+
+        // pinned knowledge is kept, because eviction would change the counters of the query
+        for payload in 0..3 {
+            store.add_raw_knowledge(
+                Record {
+                    payloads: vec![vec![payload]],
+                },
+                Source::Agent(agent.next()),
+            );
+        }
+        store.pin(&Variable::new(
+            TypeShape::of::<Record>(),
+            "(1, 2)".parse().unwrap(),
+        ));
+        assert_eq!(store.collect_garbage(), 0);
+        assert_eq!(find(&store, "(1, 2)/Record/Vec<u8>"), Some(2));
+
+        store.unpin_all();
+        assert_eq!(store.collect_garbage(), 1);
+        assert_eq!(store.raw_knowledge().len(), 4);
+    }
+
+    #[test_log::test]
+    fn test_find_variable_with_filter() {
+        let agent = AgentName::first();
+        let record = || Record {
+            payloads: vec![vec![1], vec![2]],
+        };
+        let filter = || {
+            Some(KnowledgeFilter {
+                matchers: vec![],
+                types: vec!["Record".to_string()],
+            })
+        };
+        let mut store = KnowledgeStore::<TestProtocolBehavior>::new();
+        store.add_filtered_raw_knowledge(record(), Source::Agent(agent), filter());
+        // shared data yields the knowledge of the record
+        store.add_raw_knowledge(Rc::new(record()), Source::Agent(agent));
+        store.add_filtered_raw_knowledge(record(), Source::Agent(agent), filter());
+
+        let find = |query: &str| {
+            store
+                .find_variable(TypeShape::of::<Vec<u8>>(), &query.parse().unwrap())
+                .map(|data| data.boxed_any().downcast::<Vec<u8>>().unwrap()[0])
+        };
+
+        // the payloads of the filtered records have not been extracted
+        assert_eq!(store.variables().len(), 5);
+        assert_eq!(
+            store.number_matching_message(TypeId::of::<Vec<u8>>(), &None),
+            2
+        );
+        assert_eq!(find("(0, 0)/Vec<u8>"), Some(1));
+        assert_eq!(find("(0, 1)/Record/Vec<u8>"), Some(1));
+        assert_eq!(find("(0, 2)/Vec<u8>"), None);
+    }
+}
@@ -0,0 +1,186 @@
+//! Reports of the state of a [`TraceContext`] after the execution of a trace.
+//!
+//! A [`TraceReport`] contains the knowledge of the attacker, the claims and counters of each agent
+//! and the final status of the execution. It is serialized to JSON with
+//! [`TraceContext::export_report`], such that external triage tools and dashboards do not have to
+//! parse the logs of the fuzzer.
+//!
+//! Values of the knowledge and of the claims are protocol-specific and therefore exported with
+//! their [`Debug`] representation.
+
+use serde::Serialize;
+
+use crate::agent::AgentName;
+use crate::algebra::{remove_prefix, Matcher};
+use crate::claims::{Claim, SecurityViolationPolicy};
+use crate::counters::Direction;
+use crate::protocol::ProtocolBehavior;
+use crate::trace::{ExpectationFailure, Source, TraceContext};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(bound = "M: Matcher")]
+pub struct TraceReport<M: Matcher> {
+    /// The messages which have been added to the knowledge store, in order
+    pub knowledge: Vec<RawKnowledgeReport<M>>,
+    pub agents: Vec<AgentReport<M>>,
+    pub status: StatusReport<M>,
+}
+
+/// A message in the knowledge store together with the knowledge which is extracted from it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(bound = "M: Matcher")]
+pub struct RawKnowledgeReport<M: Matcher> {
+    pub source: Source,
+    pub matcher: Option<M>,
+    pub extracted: Vec<KnowledgeReport<M>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(bound = "M: Matcher")]
+pub struct KnowledgeReport<M: Matcher> {
+    /// Name of the type without module prefix
+    pub type_name: String,
+    pub matcher: Option<M>,
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(bound = "M: Matcher")]
+pub struct AgentReport<M: Matcher> {
+    pub name: AgentName,
+    /// Whether the PUT of the agent reached a successful state, e.g. a finished handshake
+    pub successful: bool,
+    pub claims: Vec<ClaimReport>,
+    pub records_sent: usize,
+    pub records_received: usize,
+    /// The matchers of the messages which have been sent and received by the agent, in order
+    pub history: Vec<(Direction, Option<M>)>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClaimReport {
+    /// Name of the type of the claim without module prefix
+    pub type_name: String,
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(bound = "M: Matcher")]
+pub struct StatusReport<M: Matcher> {
+    /// Whether all agents reached a successful state
+    pub successful: bool,
+    /// The security violation which is reported by the
+    /// [`SecurityViolationPolicy`] of the protocol
+    pub security_violation: Option<&'static str>,
+    pub expectation_failures: Vec<ExpectationFailure<M>>,
+}
+
+impl<PB: ProtocolBehavior> TraceContext<PB> {
+    /// Collects the knowledge, the claims and the status of the execution in a [`TraceReport`].
+    pub fn report(&self) -> TraceReport<PB::Matcher> {
+        let knowledge = self
+            .knowledge_store
+            .raw_knowledge()
+            .iter()
+            .map(|raw| RawKnowledgeReport {
+                source: raw.source.clone(),
+                matcher: raw.matcher.clone(),
+                extracted: raw
+                    .into_iter()
+                    .map(|knowledge| KnowledgeReport {
+                        type_name: remove_prefix(knowledge.data.type_name()),
+                        matcher: knowledge.matcher,
+                        data: format!("{:?}", knowledge.data),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let claims = self.claims().deref_borrow();
+
+        let agents = self
+            .agents()
+            .iter()
+            .map(|agent| {
+                let counters = self.agent_counters(agent.name());
+                AgentReport {
+                    name: agent.name(),
+                    successful: agent.is_state_successful(),
+                    claims: claims
+                        .iter()
+                        .filter(|claim| claim.agent_name() == agent.name())
+                        .map(|claim| ClaimReport {
+                            type_name: remove_prefix(claim.id().name),
+                            data: format!("{:?}", claim),
+                        })
+                        .collect(),
+                    records_sent: counters.map_or(0, |counters| counters.records_sent),
+                    records_received: counters.map_or(0, |counters| counters.records_received),
+                    history: counters.map_or(vec![], |counters| counters.history().to_vec()),
+                }
+            })
+            .collect();
+
+        let security_violation = PB::SecurityViolationPolicy::check_violation(claims.slice())
+            .or_else(|| PB::SecurityViolationPolicy::check_counters(self.counters()))
+            .or_else(|| {
+                PB::SecurityViolationPolicy::check_secrecy(
+                    claims.slice(),
+                    &self.knowledge_store.variables(),
+                )
+            })
+            .or_else(|| {
+                PB::SecurityViolationPolicy::check_exchange(claims.slice(), &self.exchanged())
+            });
+
+        TraceReport {
+            knowledge,
+            agents,
+            status: StatusReport {
+                successful: self.agents_successful(),
+                security_violation,
+                expectation_failures: self.expectation_failures().to_vec(),
+            },
+        }
+    }
+
+    /// Serializes the [`TraceReport`] of the execution to JSON.
+    pub fn export_report(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(&self.report())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::agent::AgentName;
+    use crate::algebra::test_signature::{TestFactory, TestProtocolBehavior};
+    use crate::put_registry::{Factory, PutRegistry};
+    use crate::trace::{Source, Spawner, TraceContext};
+
+    #[test_log::test]
+    fn test_export_report() {
+        fn dummy_factory() -> Box<dyn Factory<TestProtocolBehavior>> {
+            Box::new(TestFactory)
+        }
+
+        let registry =
+            PutRegistry::<TestProtocolBehavior>::new([("teststub", dummy_factory())], "teststub")
+                .unwrap();
+        let mut context = TraceContext::new(Spawner::new(registry));
+        context
+            .knowledge_store
+            .add_raw_knowledge(vec![1u8, 2], Source::Agent(AgentName::first()));
+
+        let report = context.report();
+        assert_eq!(report.knowledge.len(), 1);
+        assert_eq!(report.knowledge[0].extracted[0].type_name, "Vec<u8>");
+        assert_eq!(report.knowledge[0].extracted[0].data, "[1, 2]");
+        assert!(report.agents.is_empty());
+        assert!(report.status.security_violation.is_none());
+
+        let json: serde_json::Value =
+            serde_json::from_str(&context.export_report().unwrap()).unwrap();
+        assert_eq!(json["knowledge"][0]["source"]["Agent"], 0);
+        assert_eq!(json["status"]["successful"], true);
+    }
+}
@@ -0,0 +1,122 @@
+//! The master seed from which all random number generators of a session are seeded.
+//!
+//! Without a master seed, the fuzzer seeds its generators randomly and the PUTs are reseeded with
+//! [`DEFAULT_PUT_SEED`] before each execution. With a master seed, every consumer derives its own
+//! seed from it, see [`derived_seed`], such that whole fuzzing sessions and single replays are
+//! reproducible.
+//!
+//! Function symbols draw their random values from the RNG of the
+//! [`TraceContext`](crate::trace::TraceContext) through [`fill_bytes`]. It is seeded when the
+//! context is created, such that the evaluation of a trace is deterministic even without a master
+//! seed.
+
+use std::cell::RefCell;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::OnceLock;
+
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+
+/// Seed with which the PUTs are reseeded if there is no master seed
+pub const DEFAULT_PUT_SEED: u64 = 42;
+
+static MASTER_SEED: OnceLock<u64> = OnceLock::new();
+
+thread_local! {
+    /// The RNG of the context whose terms are evaluated on this thread
+    static EXECUTION_RNG: RefCell<Option<StdRng>> = const { RefCell::new(None) };
+}
+
+/// Sets the master seed of the session. It can only be set once.
+pub fn set_master_seed(seed: u64) {
+    if MASTER_SEED.set(seed).is_err() && master_seed() != Some(seed) {
+        log::warn!(
+            "Ignoring master seed {}, the session already uses {}",
+            seed,
+            master_seed().unwrap_or_default()
+        );
+    }
+}
+
+pub fn master_seed() -> Option<u64> {
+    MASTER_SEED.get().copied()
+}
+
+/// Derives the seed of the consumer `name` from the master seed, such that consumers do not share
+/// the same stream of random numbers.
+pub fn derived_seed(name: &str) -> Option<u64> {
+    master_seed().map(|seed| derive(seed, name))
+}
+
+fn derive(seed: u64, name: &str) -> u64 {
+    let mut hasher = ahash::RandomState::with_seeds(0, 0, 0, 0).build_hasher();
+    seed.hash(&mut hasher);
+    name.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The seed of the deterministic random number generators of the PUTs
+pub fn put_seed() -> u64 {
+    derived_seed("put").unwrap_or(DEFAULT_PUT_SEED)
+}
+
+/// Creates the RNG of a new execution.
+pub fn execution_rng() -> StdRng {
+    StdRng::seed_from_u64(derived_seed("execution").unwrap_or(DEFAULT_PUT_SEED))
+}
+
+/// Runs `f` such that [`fill_bytes`] draws from `rng`. The state of `rng` advances by what `f`
+/// consumed.
+pub fn with_execution_rng<R>(rng: &RefCell<StdRng>, f: impl FnOnce() -> R) -> R {
+    let previous = EXECUTION_RNG.with(|current| current.replace(Some(rng.borrow().clone())));
+    let result = f();
+    if let Some(state) = EXECUTION_RNG.with(|current| current.replace(previous)) {
+        *rng.borrow_mut() = state;
+    }
+    result
+}
+
+/// Fills `bytes` from the RNG of the current execution, see [`with_execution_rng`]. Returns
+/// `false` outside of an execution, in which case the caller should use its usual source of
+/// randomness.
+pub fn fill_bytes(bytes: &mut [u8]) -> bool {
+    EXECUTION_RNG.with(|current| match current.borrow_mut().as_mut() {
+        Some(rng) => {
+            rng.fill_bytes(bytes);
+            true
+        }
+        None => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::{derive, execution_rng, fill_bytes, with_execution_rng};
+
+    #[test_log::test]
+    fn test_derived_seeds_differ() {
+        assert_eq!(derive(1, "put"), derive(1, "put"));
+        assert_ne!(derive(1, "put"), derive(1, "helpers"));
+        assert_ne!(derive(1, "put"), derive(2, "put"));
+    }
+
+    #[test_log::test]
+    fn test_execution_rng_is_deterministic() {
+        let draw = |rng: &RefCell<_>| {
+            let mut bytes = [0; 8];
+            assert!(with_execution_rng(rng, || fill_bytes(&mut bytes)));
+            bytes
+        };
+
+        let first = RefCell::new(execution_rng());
+        let second = RefCell::new(execution_rng());
+        let bytes = draw(&first);
+
+        assert_eq!(bytes, draw(&second));
+        // The state advances between draws
+        assert_ne!(bytes, draw(&first));
+        assert!(!fill_bytes(&mut [0; 8]));
+    }
+}
@@ -32,7 +32,13 @@ pub trait Stream<
     OF: OpaqueProtocolMessageFlight<Mt, O>,
 >
 {
-    fn add_to_inbound(&mut self, message_flight: &OF);
+    /// Passes the encoding of a whole flight to the PUT in one call, see
+    /// [`Stream::add_to_inbound`].
+    fn add_inbound_flight(&mut self, encoded_flight: &[u8]);
+
+    fn add_to_inbound(&mut self, message_flight: &OF) {
+        self.add_inbound_flight(&message_flight.get_encoding());
+    }
 
     /// Takes a single TLS message from the outbound channel
     fn take_message_from_outbound(&mut self) -> Result<Option<OF>, Error>;
@@ -76,6 +82,10 @@ impl<
         OF: OpaqueProtocolMessageFlight<Mt, O>,
     > Stream<Mt, M, O, OF> for MemoryStream
 {
+    fn add_inbound_flight(&mut self, encoded_flight: &[u8]) {
+        self.inbound.get_mut().extend_from_slice(encoded_flight);
+    }
+
     fn add_to_inbound(&mut self, message_flight: &OF) {
         message_flight.encode(self.inbound.get_mut());
     }
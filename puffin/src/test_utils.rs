@@ -9,7 +9,11 @@ impl<M: Matcher> Trace<M> {
             .iter()
             .map(|step| match &step.action {
                 Action::Input(input) => input.recipe.count_functions_by_name(find_name),
-                Action::Output(_) => 0,
+                Action::Output(_)
+                | Action::Expect(_)
+                | Action::Reset(_)
+                | Action::Renegotiate(_)
+                | Action::KeyUpdate(_) => 0,
             })
             .sum()
     }
@@ -19,7 +23,11 @@ impl<M: Matcher> Trace<M> {
             .iter()
             .flat_map(|step| match &step.action {
                 Action::Input(input) => Some(&input.recipe),
-                Action::Output(_) => None,
+                Action::Output(_)
+                | Action::Expect(_)
+                | Action::Reset(_)
+                | Action::Renegotiate(_)
+                | Action::KeyUpdate(_) => None,
             })
             .map(|term| term.size())
             .sum()
@@ -16,35 +16,51 @@
 
 use core::fmt;
 use std::any::{Any, TypeId};
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
-use std::hash::Hash;
+use std::hash::{BuildHasher, Hash, Hasher};
 use std::marker::PhantomData;
+use std::rc::Rc;
 use std::vec::IntoIter;
 
 use clap::error::Result;
+use itertools::Itertools;
+use rand::rngs::StdRng;
 use serde::{Deserialize, Serialize};
 
 use crate::agent::{Agent, AgentDescriptor, AgentName};
+use crate::algebra::atoms::Variable;
 use crate::algebra::dynamic_function::TypeShape;
 use crate::algebra::error::FnError;
+use crate::algebra::term::ArgumentPool;
 use crate::algebra::{remove_prefix, Matcher, Term};
-use crate::claims::{Claim, GlobalClaimList, SecurityViolationPolicy};
+use crate::capture::Capture;
+use crate::claims::{Claim, GlobalClaimList, KeyLog, SecurityViolationPolicy};
+use crate::codec::Codec;
+use crate::counters::{AgentCounters, Direction, TraceCounters};
 use crate::error::Error;
+use crate::execution::Runner;
+use crate::fuzzer::coverage;
 use crate::protocol::{
     ExtractKnowledge, OpaqueProtocolMessage, OpaqueProtocolMessageFlight, ProtocolBehavior,
     ProtocolMessage, ProtocolMessageFlight,
 };
 use crate::put::PutDescriptor;
 use crate::put_registry::PutRegistry;
+use crate::query::PathSegment;
+use crate::rng;
 use crate::stream::Stream;
 use crate::variable_data::VariableData;
 
-#[derive(Debug, Deserialize, Serialize, Clone, Hash, Eq, PartialEq)]
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Query<M> {
     pub source: Option<Source>,
     pub matcher: Option<M>,
     pub counter: u16, // in case an agent sends multiple messages of the same type
+    /// Path to nested knowledge, see [`crate::query`]. If empty, the knowledge with the highest
+    /// specificity is selected.
+    pub path: Vec<PathSegment>,
 }
 
 impl<M: Matcher> fmt::Display for Query<M> {
@@ -53,7 +69,11 @@ impl<M: Matcher> fmt::Display for Query<M> {
             f,
             "({:?}, {})[{:?}]",
             self.source, self.counter, self.matcher
-        )
+        )?;
+        for segment in &self.path {
+            write!(f, "/{}", segment)?;
+        }
+        Ok(())
     }
 }
 
@@ -91,6 +111,39 @@ pub struct RawKnowledge<M: Matcher> {
     pub source: Source,
     pub matcher: Option<M>,
     pub data: Box<dyn ExtractKnowledge<M>>,
+    /// Restricts the knowledge which is extracted from the data
+    pub filter: Option<KnowledgeFilter<M>>,
+    /// When a query last resolved to this knowledge, see [`KnowledgeStore::collect_garbage`]
+    last_used: Cell<u64>,
+}
+
+/// Selects the knowledge which is extracted from the output of an agent, see [`OutputAction`].
+///
+/// Knowledge is kept if its matcher matches one of the `matchers` and its type is one of the
+/// `types`. An empty list does not restrict the knowledge. Note that [query paths](crate::query)
+/// navigate through the knowledge which is kept.
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+#[serde(bound = "M: Matcher")]
+pub struct KnowledgeFilter<M: Matcher> {
+    pub matchers: Vec<Option<M>>,
+    /// Names of the types without module prefix, e.g. `ServerExtension` or `Vec<u8>`
+    pub types: Vec<String>,
+}
+
+impl<M: Matcher> KnowledgeFilter<M> {
+    pub fn keeps(&self, knowledge: &Knowledge<M>) -> bool {
+        let matches = self.matchers.is_empty()
+            || self
+                .matchers
+                .iter()
+                .any(|matcher| knowledge.matcher.matches(matcher));
+        let has_type = self.types.is_empty()
+            || self
+                .types
+                .contains(&remove_prefix(knowledge.data.type_name()));
+
+        matches && has_type
+    }
 }
 
 impl<M: Matcher> fmt::Display for RawKnowledge<M> {
@@ -105,10 +158,26 @@ impl<'a, M: Matcher> IntoIterator for &'a RawKnowledge<M> {
 
     fn into_iter(self) -> Self::IntoIter {
         let mut knowledges = vec![];
+        self.extract_into(&mut knowledges);
+        knowledges.into_iter()
+    }
+}
+
+impl<M: Matcher> RawKnowledge<M> {
+    /// Appends the knowledge of this message to `knowledges`. The knowledge borrows from the
+    /// message, such that queries over many messages can share a single buffer.
+    pub fn extract_into<'a>(&'a self, knowledges: &mut Vec<Knowledge<'a, M>>) {
+        let start = knowledges.len();
         let _ = self
             .data
-            .extract_knowledge(&mut knowledges, self.matcher.clone(), &self.source);
-        knowledges.into_iter()
+            .extract_knowledge(knowledges, self.matcher.clone(), &self.source);
+        if let Some(filter) = &self.filter {
+            let mut index = 0;
+            knowledges.retain(|knowledge| {
+                index += 1;
+                index <= start || filter.keeps(knowledge)
+            });
+        }
     }
 }
 
@@ -140,29 +209,157 @@ impl<M: Matcher> fmt::Display for Knowledge<'_, M> {
     }
 }
 
+/// Bounds the knowledge of a [`KnowledgeStore`], such that long traces with many outputs do not
+/// accumulate knowledge without bounds, see [`KnowledgeStore::collect_garbage`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KnowledgeRetention {
+    /// Maximum number of messages which are kept per source. `None` keeps all messages.
+    pub max_per_source: Option<usize>,
+}
+
 #[derive(Debug, Default)]
 pub struct KnowledgeStore<PB: ProtocolBehavior> {
     raw_knowledge: Vec<RawKnowledge<PB::Matcher>>,
+    retention: KnowledgeRetention,
+    /// Queries whose knowledge is never evicted
+    pinned: Vec<(TypeShape, Query<PB::Matcher>)>,
+    /// Advances with every added or used message, see [`RawKnowledge::last_used`]
+    clock: Cell<u64>,
 }
 
 impl<PB: ProtocolBehavior> KnowledgeStore<PB> {
     pub fn new() -> Self {
         Self {
             raw_knowledge: vec![],
+            retention: KnowledgeRetention::default(),
+            pinned: vec![],
+            clock: Cell::new(0),
+        }
+    }
+
+    pub fn set_retention(&mut self, retention: KnowledgeRetention) {
+        self.retention = retention;
+    }
+
+    pub fn retention(&self) -> KnowledgeRetention {
+        self.retention
+    }
+
+    /// Keeps the knowledge which `variable` refers to, and the knowledge which determines the
+    /// counter of its query, until [`KnowledgeStore::unpin_all`] is called.
+    pub fn pin(&mut self, variable: &Variable<PB::Matcher>) {
+        self.pinned.push((variable.typ, variable.query.clone()));
+    }
+
+    pub fn unpin_all(&mut self) {
+        self.pinned.clear();
+    }
+
+    /// Evicts the least recently used messages of each source which exceeds
+    /// [`KnowledgeRetention::max_per_source`]. Messages which contain knowledge of the type and
+    /// matcher of a pinned query are kept, such that the counters of the pinned queries still
+    /// select the same knowledge. Returns the number of evicted messages.
+    pub fn collect_garbage(&mut self) -> usize {
+        let Some(max_per_source) = self.retention.max_per_source else {
+            return 0;
+        };
+
+        let mut per_source: HashMap<&Source, Vec<usize>> = HashMap::new();
+        for (index, raw) in self.raw_knowledge.iter().enumerate() {
+            per_source.entry(&raw.source).or_default().push(index);
+        }
+
+        let mut evicted: HashSet<usize> = HashSet::new();
+        for (_, indices) in per_source {
+            if indices.len() <= max_per_source {
+                continue;
+            }
+
+            let mut unpinned: Vec<usize> = indices
+                .iter()
+                .copied()
+                .filter(|index| !self.is_pinned(&self.raw_knowledge[*index]))
+                .collect();
+            unpinned.sort_by_key(|index| self.raw_knowledge[*index].last_used.get());
+            evicted.extend(unpinned.into_iter().take(indices.len() - max_per_source));
         }
+
+        if !evicted.is_empty() {
+            log::trace!("Evicting {} messages from the knowledge", evicted.len());
+            let mut index = 0;
+            self.raw_knowledge.retain(|_| {
+                index += 1;
+                !evicted.contains(&(index - 1))
+            });
+        }
+
+        evicted.len()
+    }
+
+    /// Whether `raw` contains a candidate of a pinned query
+    fn is_pinned(&self, raw: &RawKnowledge<PB::Matcher>) -> bool {
+        self.pinned.iter().any(|(typ, query)| {
+            if query
+                .source
+                .as_ref()
+                .map_or(false, |source| source != &raw.source)
+            {
+                return false;
+            }
+
+            let type_id: TypeId = (*typ).into();
+            raw.into_iter().any(|knowledge| match query.path.first() {
+                Some(first) => first.matches(knowledge.data.type_name()),
+                None => {
+                    knowledge.data.type_id() == type_id && knowledge.matcher.matches(&query.matcher)
+                }
+            })
+        })
+    }
+
+    fn touch(&self, raw: &RawKnowledge<PB::Matcher>) {
+        self.clock.set(self.clock.get() + 1);
+        raw.last_used.set(self.clock.get());
+    }
+
+    /// Returns the messages which have been added to the knowledge, in order
+    pub fn raw_knowledge(&self) -> &[RawKnowledge<PB::Matcher>] {
+        &self.raw_knowledge
+    }
+
+    /// Returns the data of all knowledge which has been extracted from the messages
+    pub fn variables(&self) -> Vec<&dyn VariableData> {
+        self.raw_knowledge
+            .iter()
+            .flatten()
+            .map(|knowledge| knowledge.data)
+            .collect()
     }
 
     pub fn add_raw_knowledge<T: ExtractKnowledge<PB::Matcher> + 'static>(
         &mut self,
         data: T,
         source: Source,
+    ) {
+        self.add_filtered_raw_knowledge(data, source, None)
+    }
+
+    /// Adds `data` of which only the knowledge selected by `filter` is extracted.
+    pub fn add_filtered_raw_knowledge<T: ExtractKnowledge<PB::Matcher> + 'static>(
+        &mut self,
+        data: T,
+        source: Source,
+        filter: Option<KnowledgeFilter<PB::Matcher>>,
     ) {
         log::trace!("Adding raw knowledge for {:?}", &data);
 
+        self.clock.set(self.clock.get() + 1);
         self.raw_knowledge.push(RawKnowledge {
             source,
             matcher: None,
             data: Box::new(data),
+            filter,
+            last_used: Cell::new(self.clock.get()),
         });
     }
 
@@ -172,10 +369,13 @@ impl<PB: ProtocolBehavior> KnowledgeStore<PB> {
         type_id: TypeId,
         tls_message_type: &Option<PB::Matcher>,
     ) -> usize {
-        self.raw_knowledge
+        let mut knowledges = vec![];
+        for raw in self.raw_knowledge.iter().filter(|raw| raw.source == source) {
+            raw.extract_into(&mut knowledges);
+        }
+
+        knowledges
             .iter()
-            .filter(|raw| raw.source == source)
-            .flatten()
             .filter(|knowledge| {
                 knowledge.matcher == *tls_message_type && knowledge.data.type_id() == type_id
             })
@@ -188,9 +388,13 @@ impl<PB: ProtocolBehavior> KnowledgeStore<PB> {
         type_id: TypeId,
         tls_message_type: &Option<PB::Matcher>,
     ) -> usize {
-        self.raw_knowledge
+        let mut knowledges = vec![];
+        for raw in &self.raw_knowledge {
+            raw.extract_into(&mut knowledges);
+        }
+
+        knowledges
             .iter()
-            .flatten()
             .filter(|knowledge| {
                 knowledge.matcher == *tls_message_type && knowledge.data.type_id() == type_id
             })
@@ -206,22 +410,157 @@ impl<PB: ProtocolBehavior> KnowledgeStore<PB> {
     ) -> Option<&(dyn VariableData)> {
         let query_type_id: TypeId = query_type_shape.into();
 
-        let mut possibilities: Vec<Knowledge<PB::Matcher>> = self
+        if !query.path.is_empty() {
+            return self.find_variable_by_path(query_type_id, query);
+        }
+
+        // All messages share one buffer, the knowledge borrows from the messages
+        let mut knowledges = vec![];
+        let mut owners = vec![];
+        for raw in self
             .raw_knowledge
             .iter()
             .filter(|raw| (query.source.is_none() || query.source.as_ref().unwrap() == &raw.source))
-            .flatten()
-            .filter(|knowledge| {
+        {
+            raw.extract_into(&mut knowledges);
+            owners.resize(knowledges.len(), raw);
+        }
+
+        let mut possibilities: Vec<_> = owners
+            .into_iter()
+            .zip(&knowledges)
+            .filter(|(_, knowledge)| {
                 query_type_id == knowledge.data.type_id()
                     && knowledge.matcher.matches(&query.matcher)
             })
             .collect();
 
-        possibilities.sort_by_key(|a| a.specificity());
+        possibilities.sort_by_key(|(_, a)| a.specificity());
 
         possibilities
             .get(query.counter as usize)
-            .map(|possibility| possibility.data)
+            .map(|(raw, possibility)| {
+                self.touch(raw);
+                possibility.data
+            })
+    }
+
+    /// Follows the path of `query` through the knowledge of its source, see [`crate::query`].
+    fn find_variable_by_path(
+        &self,
+        query_type_id: TypeId,
+        query: &Query<PB::Matcher>,
+    ) -> Option<&(dyn VariableData)> {
+        let (first, rest) = query.path.split_first()?;
+        let mut remaining = query.counter as usize;
+        let mut knowledges = vec![];
+
+        for raw in self.raw_knowledge.iter().filter(|raw| {
+            query
+                .source
+                .as_ref()
+                .map_or(true, |source| source == &raw.source)
+        }) {
+            knowledges.clear();
+            raw.extract_into(&mut knowledges);
+            let candidates = knowledges
+                .iter()
+                .positions(|knowledge| first.matches(knowledge.data.type_name()))
+                .collect::<Vec<_>>();
+
+            let Some(&position) = candidates.get(remaining) else {
+                remaining -= candidates.len();
+                continue;
+            };
+
+            let mut selected = position;
+            for segment in rest {
+                selected = knowledges[selected + 1..]
+                    .iter()
+                    .positions(|knowledge| segment.matches(knowledge.data.type_name()))
+                    .nth(segment.index as usize)
+                    .map(|offset| selected + 1 + offset)?;
+            }
+
+            let knowledge = &knowledges[selected];
+            if knowledge.data.type_id() != query_type_id
+                || !knowledge.matcher.matches(&query.matcher)
+            {
+                return None;
+            }
+
+            self.touch(raw);
+            return Some(knowledge.data);
+        }
+
+        None
+    }
+}
+
+/// A hook which is called by the [`Spawner`] with the descriptor of an agent.
+pub type AgentHook = Rc<dyn Fn(&AgentDescriptor) -> Result<(), Error>>;
+
+#[derive(Clone, Default)]
+struct AgentHooks(Vec<AgentHook>);
+
+impl AgentHooks {
+    fn run(&self, descriptor: &AgentDescriptor) -> Result<(), Error> {
+        self.0.iter().try_for_each(|hook| hook(descriptor))
+    }
+}
+
+impl Debug for AgentHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} hooks", self.0.len())
+    }
+}
+
+/// Callbacks into the execution of a trace, which allow analyses such as logging the bytes of all
+/// ClientHellos without changing the execution. All methods do nothing by default.
+///
+/// Hooks are registered per context with [`TraceContext::add_hook`], or for all contexts of a
+/// runner with [`Runner::with_trace_hook`].
+pub trait TraceHook<PB: ProtocolBehavior> {
+    /// Called before the step with the index `index` is executed
+    fn on_step_start(&self, _index: usize, _step: &Step<PB::Matcher>) {}
+
+    /// Called with the encoded flight which `agent` sent, or which the attacker sent to `agent`
+    fn on_message_sent(&self, _agent: AgentName, _direction: Direction, _encoded: &[u8]) {}
+
+    /// Called with each claim which the agents made during a step
+    fn on_claim(&self, _claim: &PB::Claim) {}
+
+    /// Called with the message of a security violation, before the execution is aborted
+    fn on_violation(&self, _message: &'static str) {}
+}
+
+pub(crate) struct TraceHooks<PB: ProtocolBehavior>(Vec<Rc<dyn TraceHook<PB>>>);
+
+impl<PB: ProtocolBehavior> TraceHooks<PB> {
+    pub(crate) fn push(&mut self, hook: Rc<dyn TraceHook<PB>>) {
+        self.0.push(hook);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<PB: ProtocolBehavior> Default for TraceHooks<PB> {
+    fn default() -> Self {
+        Self(vec![])
+    }
+}
+
+impl<PB: ProtocolBehavior> Clone for TraceHooks<PB> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<PB: ProtocolBehavior> Debug for TraceHooks<PB> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} hooks", self.0.len())
     }
 }
 
@@ -230,6 +569,8 @@ pub struct Spawner<PB: ProtocolBehavior> {
     registry: PutRegistry<PB>,
     descriptors: HashMap<AgentName, PutDescriptor>,
     default: PutDescriptor,
+    pre_spawn_hooks: AgentHooks,
+    post_kill_hooks: AgentHooks,
 }
 
 impl<PB: ProtocolBehavior> Spawner<PB> {
@@ -239,6 +580,8 @@ impl<PB: ProtocolBehavior> Spawner<PB> {
             default: registry.default().name().into(),
             registry,
             descriptors: Default::default(),
+            pre_spawn_hooks: Default::default(),
+            post_kill_hooks: Default::default(),
         }
     }
 
@@ -252,6 +595,27 @@ impl<PB: ProtocolBehavior> Spawner<PB> {
         self
     }
 
+    /// Registers a hook which is called before the PUT of an agent is created, for example to set
+    /// environment variables or to start a companion process. An error of the hook is returned as
+    /// spawn failure.
+    pub fn with_pre_spawn_hook(
+        mut self,
+        hook: impl Fn(&AgentDescriptor) -> Result<(), Error> + 'static,
+    ) -> Self {
+        self.pre_spawn_hooks.0.push(Rc::new(hook));
+        self
+    }
+
+    /// Registers a hook which is called after an agent has been dropped, for example to remove
+    /// temporary files. Errors of the hook are logged.
+    pub fn with_post_kill_hook(
+        mut self,
+        hook: impl Fn(&AgentDescriptor) -> Result<(), Error> + 'static,
+    ) -> Self {
+        self.post_kill_hooks.0.push(Rc::new(hook));
+        self
+    }
+
     pub fn spawn(
         &self,
         claims: &GlobalClaimList<PB::Claim>,
@@ -273,8 +637,31 @@ impl<PB: ProtocolBehavior> Spawner<PB> {
                 ))
             })?;
 
-        let put = factory.create(descriptor, claims, &put_descriptor.options)?;
-        Ok(Agent::new(descriptor.clone(), put))
+        self.pre_spawn_hooks.run(descriptor).map_err(|err| {
+            Error::Agent(format!(
+                "pre-spawn hook failed for agent {}: {}",
+                descriptor.name, err
+            ))
+        })?;
+
+        let namespace = coverage::namespace(&self.registry, &put_descriptor.factory);
+        let put = coverage::with_namespace(namespace, || {
+            factory.create(descriptor, claims, &put_descriptor.options)
+        })?;
+        Ok(Agent::new(descriptor.clone(), namespace, put))
+    }
+
+    fn kill(&self, agent: Agent<PB>) {
+        let descriptor = agent.descriptor().clone();
+        drop(agent);
+
+        if let Err(err) = self.post_kill_hooks.run(&descriptor) {
+            log::error!(
+                "post-kill hook failed for agent {}: {}",
+                descriptor.name,
+                err
+            );
+        }
     }
 }
 
@@ -292,6 +679,8 @@ impl<PB: ProtocolBehavior> Clone for Spawner<PB> {
             registry: self.registry.clone(),
             descriptors: self.descriptors.clone(),
             default: self.default.clone(),
+            pre_spawn_hooks: self.pre_spawn_hooks.clone(),
+            post_kill_hooks: self.post_kill_hooks.clone(),
         }
     }
 }
@@ -309,6 +698,17 @@ pub struct TraceContext<PB: ProtocolBehavior> {
     pub knowledge_store: KnowledgeStore<PB>,
     agents: Vec<Agent<PB>>,
     claims: GlobalClaimList<<PB as ProtocolBehavior>::Claim>,
+    counters: TraceCounters<PB::Matcher>,
+    /// Matchers of the messages each agent sent since it received its last input
+    responses: HashMap<AgentName, Vec<Option<PB::Matcher>>>,
+    /// The structured flights which the agents sent and received, in order
+    exchanged: Vec<(AgentName, Direction, Rc<PB::ProtocolMessageFlight>)>,
+    expectation_failures: Vec<ExpectationFailure<PB::Matcher>>,
+    capture: Option<Capture>,
+    /// The RNG of the function symbols, see [`rng`](crate::rng)
+    rng: RefCell<StdRng>,
+    arguments: ArgumentPool,
+    hooks: TraceHooks<PB>,
 
     spawner: Spawner<PB>,
 
@@ -339,6 +739,14 @@ impl<PB: ProtocolBehavior + PartialEq> PartialEq for TraceContext<PB> {
     }
 }
 
+impl<PB: ProtocolBehavior> Drop for TraceContext<PB> {
+    fn drop(&mut self) {
+        for agent in self.agents.drain(..) {
+            self.spawner.kill(agent);
+        }
+    }
+}
+
 impl<PB: ProtocolBehavior> TraceContext<PB> {
     pub fn new(spawner: Spawner<PB>) -> Self {
         // We keep a global list of all claims throughout the execution. Each claim is identified
@@ -349,11 +757,29 @@ impl<PB: ProtocolBehavior> TraceContext<PB> {
             knowledge_store: KnowledgeStore::new(),
             agents: vec![],
             claims,
+            counters: TraceCounters::new(),
+            responses: HashMap::new(),
+            exchanged: vec![],
+            expectation_failures: vec![],
+            capture: None,
+            rng: RefCell::new(rng::execution_rng()),
+            arguments: ArgumentPool::default(),
+            hooks: TraceHooks::default(),
             spawner,
             phantom: Default::default(),
         }
     }
 
+    /// The argument vectors which are reused while evaluating terms in this context
+    pub fn arguments(&self) -> &ArgumentPool {
+        &self.arguments
+    }
+
+    /// Runs `f`, e.g. the evaluation of a function symbol, with the RNG of this execution.
+    pub fn with_rng<R>(&self, f: impl FnOnce() -> R) -> R {
+        rng::with_execution_rng(&self.rng, f)
+    }
+
     pub fn verify_security_violations(&self) -> Result<(), Error> {
         let claims = self.claims.deref_borrow();
         claims.log();
@@ -364,9 +790,94 @@ impl<PB: ProtocolBehavior> TraceContext<PB> {
             // end
             return Err(Error::SecurityClaim(msg));
         }
+        if let Some(msg) = PB::SecurityViolationPolicy::check_counters(&self.counters) {
+            return Err(Error::SecurityClaim(msg));
+        }
+        if let Some(msg) = PB::SecurityViolationPolicy::check_secrecy(
+            claims.slice(),
+            &self.knowledge_store.variables(),
+        ) {
+            return Err(Error::SecurityClaim(msg));
+        }
+        if let Some(msg) =
+            PB::SecurityViolationPolicy::check_exchange(claims.slice(), &self.exchanged())
+        {
+            return Err(Error::SecurityClaim(msg));
+        }
+        if let Some(failure) = self.expectation_failures.first() {
+            log::error!("{}", failure);
+            return Err(Error::SecurityClaim("Output expectation failed"));
+        }
         Ok(())
     }
 
+    /// The structured flights which the agents sent and received, in the order of the execution
+    pub fn exchanged(&self) -> Vec<(AgentName, Direction, &dyn VariableData)> {
+        self.exchanged
+            .iter()
+            .map(|(agent, direction, flight)| (*agent, *direction, &**flight as &dyn VariableData))
+            .collect()
+    }
+
+    /// Returns the [`ExpectAction`]s which did not hold during the execution
+    pub fn expectation_failures(&self) -> &[ExpectationFailure<PB::Matcher>] {
+        &self.expectation_failures
+    }
+
+    /// Calls `hook` during the remaining execution in this context, see [`TraceHook`]
+    pub fn add_hook(&mut self, hook: impl TraceHook<PB> + 'static) {
+        self.hooks.push(Rc::new(hook));
+    }
+
+    pub(crate) fn set_hooks(&mut self, hooks: TraceHooks<PB>) {
+        self.hooks = hooks;
+    }
+
+    fn record_message(&mut self, agent: AgentName, direction: Direction, encoded: &[u8]) {
+        for hook in &self.hooks.0 {
+            hook.on_message_sent(agent, direction, encoded);
+        }
+        if let Some(capture) = &mut self.capture {
+            capture.record(agent, direction, encoded.to_vec());
+        }
+    }
+
+    /// Records the bytes which are exchanged with the agents from now on in `capture`
+    pub fn enable_capture(&mut self, capture: Capture) {
+        self.capture = Some(capture);
+    }
+
+    /// Returns the captured bytes if [`TraceContext::enable_capture`] has been called
+    pub fn capture(&self) -> Option<&Capture> {
+        self.capture.as_ref()
+    }
+
+    /// Stops capturing and returns the captured bytes
+    pub fn take_capture(&mut self) -> Option<Capture> {
+        self.capture.take()
+    }
+
+    /// Writes the secrets of the claims which are made from now on to `key_log`, see
+    /// [`Claim::key_log`]
+    pub fn enable_key_log(&mut self, key_log: KeyLog) {
+        self.claims.deref_borrow_mut().enable_key_log(key_log);
+    }
+
+    /// Returns the claims which have been made by the agents so far
+    pub fn claims(&self) -> &GlobalClaimList<PB::Claim> {
+        &self.claims
+    }
+
+    /// Returns the counters of all agents, which are updated by each [`Step`]
+    pub fn counters(&self) -> &TraceCounters<PB::Matcher> {
+        &self.counters
+    }
+
+    /// Returns the counters of the agent `agent_name`
+    pub fn agent_counters(&self, agent_name: AgentName) -> Option<&AgentCounters<PB::Matcher>> {
+        self.counters.get(agent_name)
+    }
+
     /// Count the number of sub-messages of type `type_id` with the correct source
     pub fn number_matching_message_with_source(
         &self,
@@ -437,6 +948,10 @@ impl<PB: ProtocolBehavior> TraceContext<PB> {
         })
     }
 
+    pub(crate) fn agents(&self) -> &[Agent<PB>] {
+        &self.agents
+    }
+
     pub fn agents_successful(&self) -> bool {
         self.agents.iter().all(|agent| agent.is_state_successful())
     }
@@ -450,31 +965,86 @@ pub struct Trace<M: Matcher> {
     pub prior_traces: Vec<Trace<M>>,
 }
 
+/// Inputs which are compared by a hash of their structure, e.g. to reject duplicates of corpus
+/// entries.
+pub trait StructuralHash {
+    /// The hash is the same in all processes of one build of the fuzzer
+    fn structural_hash(&self) -> u64;
+}
+
+impl<M: Matcher> StructuralHash for Trace<M> {
+    /// Hashes the structure of the trace: the agents, the actions, the function symbols and
+    /// variables of the terms, and the prior traces. The ids which terms get when they are
+    /// created or cloned are ignored, such that a mutated trace which equals another trace has
+    /// the same hash.
+    fn structural_hash(&self) -> u64 {
+        let mut hasher = ahash::RandomState::with_seeds(0, 0, 0, 0).build_hasher();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
 /// A [`Trace`] consists of several [`Step`]s. Each has either a [`OutputAction`] or an
 /// [`InputAction`]. Each [`Step`]s references an [`Agent`] by name. Furthermore, a trace also has a
 /// list of *AgentDescriptors* which act like a blueprint to spawn [`Agent`]s with a corresponding
 /// server or client role and a specific TLs version. Essentially they are an [`Agent`] without a
 /// stream.
 impl<M: Matcher> Trace<M> {
+    /// Spawns an agent for each descriptor.
+    ///
+    /// If a descriptor has [`AgentDescriptor::try_reuse`] set, an agent of a prior trace is reused
+    /// instead. Its connection is reset, but the state of the PUT which outlives a connection, like
+    /// session caches and ticket keys, is kept. This allows to model session resumption with a
+    /// prior trace which establishes the session. An agent with the same name is preferred over
+    /// other compatible agents. Each agent is reused for at most one descriptor, such that a trace
+    /// can have several agents of the same kind, e.g. two servers between which the attacker
+    /// relays messages.
+    ///
+    /// Agents of prior traces which have the name of a descriptor but are not reused are replaced.
     fn spawn_agents<PB: ProtocolBehavior>(&self, ctx: &mut TraceContext<PB>) -> Result<(), Error> {
+        let mut assigned: Vec<AgentName> = vec![];
+
         for descriptor in &self.descriptors {
-            if let Some(reusable) = ctx
-                .agents
-                .iter_mut()
-                .find(|existing| existing.is_reusable_with(descriptor))
-            {
-                // rename if it already exists and we want to reuse
-                reusable.reset(descriptor.name)?;
+            let is_available = |agent: &Agent<PB>| !assigned.contains(&agent.name());
+
+            let reusable = if descriptor.try_reuse {
+                let candidates =
+                    ctx.agents.iter().enumerate().filter(|(_, agent)| {
+                        is_available(agent) && agent.is_reusable_with(descriptor)
+                    });
+                candidates
+                    .clone()
+                    .find(|(_, agent)| agent.name() == descriptor.name)
+                    .or_else(|| candidates.clone().next())
+                    .map(|(index, _)| index)
             } else {
-                // only spawn completely new if not yet existing
-                ctx.spawn(descriptor)?;
+                None
             };
+
+            if let Some(index) = reusable {
+                ctx.agents[index].reset(descriptor.name)?;
+            } else {
+                if let Some(index) = ctx
+                    .agents
+                    .iter()
+                    .position(|agent| is_available(agent) && agent.name() == descriptor.name)
+                {
+                    let replaced = ctx.agents.remove(index);
+                    ctx.spawner.kill(replaced);
+                }
+
+                ctx.spawn(descriptor)?;
+            }
+
+            assigned.push(descriptor.name);
         }
 
         Ok(())
     }
 
-    pub fn execute<PB>(&self, ctx: &mut TraceContext<PB>) -> Result<(), Error>
+    /// Executes the prior traces and spawns the agents of this trace. Afterwards, the steps can be
+    /// executed one by one using [`Step::execute`].
+    pub fn prepare<PB>(&self, ctx: &mut TraceContext<PB>) -> Result<(), Error>
     where
         PB: ProtocolBehavior<Matcher = M>,
     {
@@ -482,18 +1052,122 @@ impl<M: Matcher> Trace<M> {
             trace.execute(ctx)?;
         }
 
-        self.spawn_agents(ctx)?;
+        self.spawn_agents(ctx)
+    }
+
+    pub fn execute<PB>(&self, ctx: &mut TraceContext<PB>) -> Result<(), Error>
+    where
+        PB: ProtocolBehavior<Matcher = M>,
+    {
+        let _context = crate::log::trace_context();
+        self.prepare(ctx)?;
+        self.execute_from(ctx, 0)
+    }
+
+    /// Executes the steps from the index `start` on in a context which already executed the
+    /// preceding steps, see [`Runner::execute_suffixes`].
+    pub fn execute_from<PB>(&self, ctx: &mut TraceContext<PB>, start: usize) -> Result<(), Error>
+    where
+        PB: ProtocolBehavior<Matcher = M>,
+    {
         let steps = &self.steps;
-        for (i, step) in steps.iter().enumerate() {
+        for (i, step) in steps.iter().enumerate().skip(start) {
+            crate::log::step_context(i, step.agent);
             log::debug!("Executing step #{}", i);
+            for hook in &ctx.hooks.0 {
+                hook.on_step_start(i, step);
+            }
+            ctx.claims().deref_borrow_mut().set_step(Some(i));
             step.execute(ctx)?;
 
-            ctx.verify_security_violations()?;
+            if !ctx.hooks.is_empty() {
+                // The hooks are called without borrowing the claims, which the PUTs may extend
+                let claims: Vec<_> = ctx
+                    .claims
+                    .deref_borrow()
+                    .claims_during_steps(i..=i)
+                    .cloned()
+                    .collect();
+                for claim in &claims {
+                    for hook in &ctx.hooks.0 {
+                        hook.on_claim(claim);
+                    }
+                }
+            }
+
+            if let Err(err) = ctx.verify_security_violations() {
+                if let Error::SecurityClaim(message) = err {
+                    for hook in &ctx.hooks.0 {
+                        hook.on_violation(message);
+                    }
+                }
+                return Err(err);
+            }
+
+            if ctx.knowledge_store.retention().max_per_source.is_some() {
+                ctx.knowledge_store.unpin_all();
+                for variable in steps[i + 1..].iter().flat_map(Step::variables) {
+                    ctx.knowledge_store.pin(variable);
+                }
+                ctx.knowledge_store.collect_garbage();
+            }
         }
+        ctx.claims().deref_borrow_mut().set_step(None);
 
         Ok(())
     }
 
+    /// Executes the trace twice, each time with freshly reseeded PUTs, and compares the flights
+    /// which the agents sent.
+    ///
+    /// Returns an [`Error::Put`] if the flights or the results of the executions differ, i.e. if a
+    /// PUT is not deterministic. Otherwise, the result of the second execution is returned.
+    pub fn execute_deterministic<PB>(&self, runner: &Runner<PB>) -> Result<(), Error>
+    where
+        PB: ProtocolBehavior<Matcher = M>,
+    {
+        let execute_captured = || {
+            let mut ctx = runner.new_context();
+            ctx.enable_capture(Capture::new());
+            let result = self.execute(&mut ctx);
+            (ctx.take_capture().unwrap_or_default(), result)
+        };
+
+        let (first, first_result) = execute_captured();
+        let (second, second_result) = execute_captured();
+
+        if let Err(difference) = first.compare_sent(&second) {
+            return Err(Error::Put(format!(
+                "PUT is not deterministic: {}",
+                difference
+            )));
+        }
+
+        if first_result != second_result {
+            return Err(Error::Put(format!(
+                "PUT is not deterministic: the first execution returned {:?}, the second {:?}",
+                first_result, second_result
+            )));
+        }
+
+        second_result
+    }
+
+    /// Sum of the sizes of the recipes of all input steps.
+    pub fn size(&self) -> usize {
+        self.steps
+            .iter()
+            .map(|step| match &step.action {
+                Action::Input(input) => input.recipe.size(),
+                Action::Output(_)
+                | Action::Expect(_)
+                | Action::Reset(_)
+                | Action::Renegotiate(_)
+                | Action::KeyUpdate(_) => 0,
+            })
+            .sum()
+    }
+
     pub fn serialize_postcard(&self) -> Result<Vec<u8>, postcard::Error> {
         postcard::to_allocvec(&self)
     }
@@ -533,24 +1207,47 @@ pub struct Step<M: Matcher> {
 }
 
 impl<M: Matcher> Step<M> {
-    fn execute<PB>(&self, ctx: &mut TraceContext<PB>) -> Result<(), Error>
+    /// The variables which are queried from the knowledge when this step is executed
+    pub fn variables(&self) -> Vec<&Variable<M>> {
+        let terms: Vec<&Term<M>> = match &self.action {
+            Action::Input(input) => vec![&input.recipe],
+            Action::Expect(expect) => expect.conditions.iter().collect(),
+            Action::Output(_)
+            | Action::Reset(_)
+            | Action::Renegotiate(_)
+            | Action::KeyUpdate(_) => vec![],
+        };
+
+        terms
+            .into_iter()
+            .flatten()
+            .filter_map(|term| match term {
+                Term::Variable(variable) => Some(variable),
+                Term::Application(_, _) => None,
+            })
+            .collect()
+    }
+
+    pub fn execute<PB>(&self, ctx: &mut TraceContext<PB>) -> Result<(), Error>
     where
         PB: ProtocolBehavior<Matcher = M>,
     {
         match &self.action {
             Action::Input(input) => input.execute(self.agent, ctx).and_then(|_| {
                 // NOTE force output after each InputAction step
-                (OutputAction {
-                    phantom: Default::default(),
-                })
-                .execute(self.agent, ctx)
+                (OutputAction { filter: None }).execute(self.agent, ctx)
             }),
             Action::Output(output) => output.execute(self.agent, ctx),
+            Action::Expect(expect) => expect.execute(self.agent, ctx),
+            Action::Reset(reset) => reset.execute(self.agent, ctx),
+            Action::Renegotiate(renegotiate) => renegotiate.execute(self.agent, ctx),
+            Action::KeyUpdate(key_update) => key_update.execute(self.agent, ctx),
         }
     }
 }
 
-/// There are two action types [`OutputAction`] and [`InputAction`].
+/// There are two main action types [`OutputAction`] and [`InputAction`]. The [`ExpectAction`] is
+/// an [`OutputAction`] which additionally checks the messages produced by the [`Agent`].
 ///
 /// Both actions drive the internal state machine of an [`Agent`] forward by calling `progress()`.
 /// The [`OutputAction`] first forwards the state machine and then extracts knowledge from the
@@ -560,11 +1257,18 @@ impl<M: Matcher> Step<M> {
 /// by calling `add_to_inbound(...)` and then drives the state machine forward.
 /// Therefore, the difference is that one step *increases* the knowledge of the attacker,
 /// whereas the other action *uses* the available knowledge.
+///
+/// The [`ResetAction`], [`RenegotiateAction`] and [`KeyUpdateAction`] change the session of an
+/// [`Agent`], such that a single trace can cover several sessions.
 #[derive(Serialize, Deserialize, Clone, Debug, Hash)]
 #[serde(bound = "M: Matcher")]
 pub enum Action<M: Matcher> {
     Input(InputAction<M>),
     Output(OutputAction<M>),
+    Expect(ExpectAction<M>),
+    Reset(ResetAction),
+    Renegotiate(RenegotiateAction),
+    KeyUpdate(KeyUpdateAction),
 }
 
 impl<M: Matcher> fmt::Display for Action<M> {
@@ -572,6 +1276,10 @@ impl<M: Matcher> fmt::Display for Action<M> {
         match self {
             Action::Input(input) => write!(f, "{}", input),
             Action::Output(output) => write!(f, "{}", output),
+            Action::Expect(expect) => write!(f, "{}", expect),
+            Action::Reset(reset) => write!(f, "{}", reset),
+            Action::Renegotiate(renegotiate) => write!(f, "{}", renegotiate),
+            Action::KeyUpdate(key_update) => write!(f, "{}", key_update),
         }
     }
 }
@@ -581,17 +1289,29 @@ impl<M: Matcher> fmt::Display for Action<M> {
 /// The [`OutputAction`] first forwards the state machine and then extracts knowledge from the TLS
 /// messages produced by the underlying stream by calling  `take_message_from_outbound(...)`. An
 /// output action is automatically called after each input step.
+///
+/// If a `filter` is set, only the selected knowledge is added to the knowledge store. This keeps
+/// the knowledge small and makes queries of later steps less ambiguous.
 #[derive(Serialize, Deserialize, Clone, Debug, Hash)]
-pub struct OutputAction<M> {
-    phantom: PhantomData<M>,
+#[serde(bound = "M: Matcher")]
+pub struct OutputAction<M: Matcher> {
+    pub filter: Option<KnowledgeFilter<M>>,
 }
 
 impl<M: Matcher> OutputAction<M> {
     pub fn new_step(agent: AgentName) -> Step<M> {
+        Step {
+            agent,
+            action: Action::Output(OutputAction { filter: None }),
+        }
+    }
+
+    /// Creates a step which adds only the knowledge selected by `filter` to the knowledge store.
+    pub fn new_filtered_step(agent: AgentName, filter: KnowledgeFilter<M>) -> Step<M> {
         Step {
             agent,
             action: Action::Output(OutputAction {
-                phantom: Default::default(),
+                filter: Some(filter),
             }),
         }
     }
@@ -606,11 +1326,41 @@ impl<M: Matcher> OutputAction<M> {
         agent.progress()?;
 
         if let Some(opaque_flight) = agent.take_message_from_outbound()? {
-            ctx.knowledge_store
-                .add_raw_knowledge(opaque_flight.clone(), source.clone());
+            if ctx.capture.is_some() || !ctx.hooks.is_empty() {
+                ctx.record_message(agent_name, Direction::Sent, &opaque_flight.get_encoding());
+            }
+
+            let counters = ctx.counters.get_mut_or_default(agent_name);
+            counters.count::<PB>(&opaque_flight, Direction::Sent);
+
+            ctx.knowledge_store.add_filtered_raw_knowledge(
+                opaque_flight.clone(),
+                source.clone(),
+                self.filter.clone(),
+            );
 
             if let Ok(flight) = TryInto::<PB::ProtocolMessageFlight>::try_into(opaque_flight) {
-                ctx.knowledge_store.add_raw_knowledge(flight, source);
+                ctx.counters
+                    .get_mut_or_default(agent_name)
+                    .count::<PB>(&flight, Direction::Sent);
+                // The knowledge borrows from the same flight, it is only copied if a term uses it
+                let flight = Rc::new(flight);
+                ctx.exchanged
+                    .push((agent_name, Direction::Sent, flight.clone()));
+
+                let mut knowledges = vec![];
+                let _ = flight.extract_knowledge(&mut knowledges, None, &source);
+                ctx.responses.entry(agent_name).or_default().extend(
+                    knowledges
+                        .into_iter()
+                        .filter(|knowledge| {
+                            knowledge.data.type_id() == TypeId::of::<PB::ProtocolMessage>()
+                        })
+                        .map(|knowledge| knowledge.matcher),
+                );
+
+                ctx.knowledge_store
+                    .add_filtered_raw_knowledge(flight, source, self.filter.clone());
             }
         }
 
@@ -620,7 +1370,251 @@ impl<M: Matcher> OutputAction<M> {
 
 impl<M: Matcher> fmt::Display for OutputAction<M> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "OutputAction")
+        write!(f, "OutputAction")?;
+        if let Some(filter) = &self.filter {
+            write!(f, ": {:?} {:?}", filter.matchers, filter.types)?;
+        }
+        Ok(())
+    }
+}
+
+/// Advance the [`Agent`]'s state like an [`OutputAction`] and check the produced messages.
+///
+/// The check covers all messages the agent sent since it received its last input, including the
+/// flight taken by the [`OutputAction`] which follows each [`InputAction`]. If `present` is
+/// `true`, these must contain a message which matches one of the `matchers`, e.g. a server must
+/// answer a ClientHello with a ServerHello or an alert. If `present` is `false`, none of these
+/// messages may match.
+///
+/// Properties of the content of the messages are checked by the `conditions`. Each is a term which
+/// must evaluate to `true` after the messages have been added to the knowledge, e.g. a check
+/// whether the extensions of the ServerHello contain a specific extension. This allows to use
+/// traces as conformance tests of a PUT.
+///
+/// A violated expectation is recorded as an [`ExpectationFailure`] and reported as a security
+/// violation, which makes the trace an objective of the fuzzer.
+#[derive(Serialize, Deserialize, Clone, Debug, Hash)]
+#[serde(bound = "M: Matcher")]
+pub struct ExpectAction<M: Matcher> {
+    pub matchers: Vec<Option<M>>,
+    pub present: bool,
+    pub conditions: Vec<Term<M>>,
+}
+
+impl<M: Matcher> ExpectAction<M> {
+    pub fn new_step(agent: AgentName, matchers: Vec<Option<M>>, present: bool) -> Step<M> {
+        Self::new_step_with_conditions(agent, matchers, present, vec![])
+    }
+
+    /// Creates a step which additionally checks that all `conditions` evaluate to `true`.
+    pub fn new_step_with_conditions(
+        agent: AgentName,
+        matchers: Vec<Option<M>>,
+        present: bool,
+        conditions: Vec<Term<M>>,
+    ) -> Step<M> {
+        Step {
+            agent,
+            action: Action::Expect(ExpectAction {
+                matchers,
+                present,
+                conditions,
+            }),
+        }
+    }
+
+    fn execute<PB>(&self, agent_name: AgentName, ctx: &mut TraceContext<PB>) -> Result<(), Error>
+    where
+        PB: ProtocolBehavior<Matcher = M>,
+    {
+        (OutputAction { filter: None }).execute(agent_name, ctx)?;
+
+        let sent = ctx.responses.get(&agent_name).cloned().unwrap_or_default();
+
+        let found = sent.iter().any(|matcher| {
+            self.matchers
+                .iter()
+                .any(|expected| matcher.matches(expected))
+        });
+
+        let failed_conditions = self
+            .conditions
+            .iter()
+            .filter(|condition| !Self::holds(condition, ctx))
+            .map(|condition| condition.to_string())
+            .collect::<Vec<_>>();
+
+        if found != self.present || !failed_conditions.is_empty() {
+            let failure = ExpectationFailure {
+                agent: agent_name,
+                matchers: self.matchers.clone(),
+                present: self.present,
+                sent,
+                failed_conditions,
+            };
+            log::debug!("{}", failure);
+            ctx.expectation_failures.push(failure);
+        }
+
+        Ok(())
+    }
+
+    /// Whether `condition` evaluates to `true`. Conditions which fail to evaluate, e.g. because
+    /// the queried knowledge is missing, do not hold.
+    fn holds<PB>(condition: &Term<M>, ctx: &TraceContext<PB>) -> bool
+    where
+        PB: ProtocolBehavior<Matcher = M>,
+    {
+        match condition.evaluate(ctx) {
+            Ok(value) => value.downcast_ref::<bool>().copied().unwrap_or(false),
+            Err(err) => {
+                log::debug!("Failed to evaluate condition {}: {}", condition, err);
+                false
+            }
+        }
+    }
+}
+
+impl<M: Matcher> fmt::Display for ExpectAction<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let quantifier = if self.present { "one of" } else { "none of" };
+        write!(f, "ExpectAction: {} {:?}", quantifier, self.matchers)?;
+        for condition in &self.conditions {
+            write!(f, ", {}", condition)?;
+        }
+        Ok(())
+    }
+}
+
+/// An [`ExpectAction`] which did not hold during the execution of a trace.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(bound = "M: Matcher")]
+pub struct ExpectationFailure<M: Matcher> {
+    pub agent: AgentName,
+    /// The matchers of the [`ExpectAction`]
+    pub matchers: Vec<Option<M>>,
+    /// Whether a matching message was expected to be present
+    pub present: bool,
+    /// The matchers of the messages which have actually been sent by the agent
+    pub sent: Vec<Option<M>>,
+    /// The conditions of the [`ExpectAction`] which did not hold
+    pub failed_conditions: Vec<String>,
+}
+
+impl<M: Matcher> fmt::Display for ExpectationFailure<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.failed_conditions.is_empty() {
+            return write!(
+                f,
+                "Expectation failed: conditions do not hold for agent {}: {}",
+                self.agent,
+                self.failed_conditions.join(", ")
+            );
+        }
+
+        let verb = if self.present { "none of" } else { "one of" };
+        write!(
+            f,
+            "Expectation failed: agent {} sent {} {:?} (sent: {:?})",
+            self.agent, verb, self.matchers, self.sent
+        )
+    }
+}
+
+/// Reset the connection of the [`Agent`] in-place, see [`Put::reset`](crate::put::Put::reset).
+///
+/// State which outlives a connection, like session caches, is kept, such that the agent can
+/// resume its previous session. A client starts its next handshake with the following
+/// [`OutputAction`].
+#[derive(Serialize, Deserialize, Clone, Debug, Hash)]
+pub struct ResetAction;
+
+impl ResetAction {
+    pub fn new_step<M: Matcher>(agent: AgentName) -> Step<M> {
+        Step {
+            agent,
+            action: Action::Reset(ResetAction),
+        }
+    }
+
+    fn execute<PB>(&self, agent_name: AgentName, ctx: &mut TraceContext<PB>) -> Result<(), Error>
+    where
+        PB: ProtocolBehavior,
+    {
+        ctx.responses.remove(&agent_name);
+        ctx.find_agent_mut(agent_name)?.reset(agent_name)
+    }
+}
+
+impl fmt::Display for ResetAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ResetAction")
+    }
+}
+
+/// Let the [`Agent`] initiate a renegotiation of its session, see
+/// [`Put::renegotiate`](crate::put::Put::renegotiate).
+///
+/// The messages which start the renegotiation, e.g. a HelloRequest of a server, are taken like by
+/// an [`OutputAction`].
+#[derive(Serialize, Deserialize, Clone, Debug, Hash)]
+pub struct RenegotiateAction;
+
+impl RenegotiateAction {
+    pub fn new_step<M: Matcher>(agent: AgentName) -> Step<M> {
+        Step {
+            agent,
+            action: Action::Renegotiate(RenegotiateAction),
+        }
+    }
+
+    fn execute<PB>(&self, agent_name: AgentName, ctx: &mut TraceContext<PB>) -> Result<(), Error>
+    where
+        PB: ProtocolBehavior,
+    {
+        ctx.responses.remove(&agent_name);
+        ctx.find_agent_mut(agent_name)?.renegotiate()?;
+        (OutputAction { filter: None }).execute(agent_name, ctx)
+    }
+}
+
+impl fmt::Display for RenegotiateAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RenegotiateAction")
+    }
+}
+
+/// Let the [`Agent`] update its traffic keys, see [`Put::key_update`](crate::put::Put::key_update).
+///
+/// If `update_requested` is `true`, the peer is asked to update its keys as well. The KeyUpdate
+/// message is taken like by an [`OutputAction`].
+#[derive(Serialize, Deserialize, Clone, Debug, Hash)]
+pub struct KeyUpdateAction {
+    pub update_requested: bool,
+}
+
+impl KeyUpdateAction {
+    pub fn new_step<M: Matcher>(agent: AgentName, update_requested: bool) -> Step<M> {
+        Step {
+            agent,
+            action: Action::KeyUpdate(KeyUpdateAction { update_requested }),
+        }
+    }
+
+    fn execute<PB>(&self, agent_name: AgentName, ctx: &mut TraceContext<PB>) -> Result<(), Error>
+    where
+        PB: ProtocolBehavior,
+    {
+        ctx.responses.remove(&agent_name);
+        ctx.find_agent_mut(agent_name)?
+            .key_update(self.update_requested)?;
+        (OutputAction { filter: None }).execute(agent_name, ctx)
+    }
+}
+
+impl fmt::Display for KeyUpdateAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "KeyUpdateAction: {}", self.update_requested)
     }
 }
 
@@ -650,9 +1644,25 @@ impl<M: Matcher> InputAction<M> {
         PB: ProtocolBehavior<Matcher = M>,
     {
         let message = as_message_flight::<PB>(self.recipe.evaluate(ctx)?)?;
+
+        ctx.responses.remove(&agent_name);
+
+        // The flight is encoded once and crosses into the PUT in a single call
+        let encoded_flight = message.get_encoding();
+
+        ctx.record_message(agent_name, Direction::Received, &encoded_flight);
+
+        let counters = ctx.counters.get_mut_or_default(agent_name);
+        counters.count::<PB>(&message, Direction::Received);
+        if let Ok(flight) = TryInto::<PB::ProtocolMessageFlight>::try_into(message) {
+            counters.count::<PB>(&flight, Direction::Received);
+            ctx.exchanged
+                .push((agent_name, Direction::Received, Rc::new(flight)));
+        }
+
         let agent = ctx.find_agent_mut(agent_name)?;
 
-        agent.add_to_inbound(&message);
+        agent.add_inbound_flight(&encoded_flight);
         agent.progress()
     }
 }
@@ -663,7 +1673,7 @@ impl<M: Matcher> fmt::Display for InputAction<M> {
     }
 }
 
-fn as_message_flight<PB: ProtocolBehavior>(
+pub(crate) fn as_message_flight<PB: ProtocolBehavior>(
     value: Box<dyn Any>,
 ) -> Result<PB::OpaqueProtocolMessageFlight, Error> {
     Err(value)
@@ -0,0 +1,359 @@
+//! Differences between two [`Trace`]s, e.g. between a testcase and its mutation or minimization.
+//!
+//! [`Trace::diff`] aligns the steps of both traces such that as many steps as possible are
+//! unchanged. Removed and added steps between two unchanged steps are paired if they are inputs
+//! to the same agent, in which case only the changed subterms of the recipes are reported. The
+//! [`Display`](fmt::Display) implementation of [`TraceDiff`] renders the edits in the textual
+//! format, see [`trace_text`](crate::trace_text):
+//!
+//! ```text
+//! ~ step 0: input 0
+//!     at [1]:
+//!     - fn_new_random
+//!     + (0, 1)/Random
+//! - step 2: output 0
+//! + step 2: reset 0
+//! ```
+
+use std::fmt;
+use std::hash::{BuildHasher, Hash, Hasher};
+
+use crate::agent::AgentDescriptor;
+use crate::algebra::{Matcher, Term};
+use crate::trace::{Action, Step, Trace};
+use crate::trace_text::descriptor_text;
+
+/// The indexes of the arguments which lead from the root of a term to a subterm
+pub type TermPath = Vec<usize>;
+
+/// A subterm of a recipe which was replaced
+#[derive(Debug, Clone)]
+pub struct TermChange<M: Matcher> {
+    pub path: TermPath,
+    pub old: Term<M>,
+    pub new: Term<M>,
+}
+
+/// An edit which turns one trace into another, see [`Trace::diff`]. Indexes of removed steps
+/// refer to the old trace, indexes of added steps to the new trace.
+#[derive(Debug, Clone)]
+pub enum Edit<M: Matcher> {
+    Descriptors {
+        old: Vec<AgentDescriptor>,
+        new: Vec<AgentDescriptor>,
+    },
+    /// The prior traces at `index` of both traces differ
+    ChangePrior {
+        index: usize,
+        diff: TraceDiff<M>,
+    },
+    RemovePrior {
+        index: usize,
+    },
+    AddPrior {
+        index: usize,
+    },
+    RemoveStep {
+        index: usize,
+        step: Step<M>,
+    },
+    AddStep {
+        index: usize,
+        step: Step<M>,
+    },
+    /// The recipe of an input step changed
+    ChangeStep {
+        old_index: usize,
+        new_index: usize,
+        step: Step<M>,
+        changes: Vec<TermChange<M>>,
+    },
+}
+
+/// The edits which turn one trace into another, see [`Trace::diff`]
+#[derive(Debug, Clone)]
+pub struct TraceDiff<M: Matcher> {
+    pub edits: Vec<Edit<M>>,
+}
+
+impl<M: Matcher> TraceDiff<M> {
+    /// Whether both traces are equal
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    fn render(&self, f: &mut fmt::Formatter<'_>, indent: &str) -> fmt::Result {
+        for edit in &self.edits {
+            match edit {
+                Edit::Descriptors { old, new } => {
+                    writeln!(f, "{}~ agents", indent)?;
+                    for descriptor in old {
+                        writeln!(f, "{}    - {}", indent, descriptor_text(descriptor))?;
+                    }
+                    for descriptor in new {
+                        writeln!(f, "{}    + {}", indent, descriptor_text(descriptor))?;
+                    }
+                }
+                Edit::ChangePrior { index, diff } => {
+                    writeln!(f, "{}~ prior {}", indent, index)?;
+                    diff.render(f, &format!("{}    ", indent))?;
+                }
+                Edit::RemovePrior { index } => writeln!(f, "{}- prior {}", indent, index)?,
+                Edit::AddPrior { index } => writeln!(f, "{}+ prior {}", indent, index)?,
+                Edit::RemoveStep { index, step } => {
+                    writeln!(f, "{}- step {}: {}", indent, index, step.to_text())?
+                }
+                Edit::AddStep { index, step } => {
+                    writeln!(f, "{}+ step {}: {}", indent, index, step.to_text())?
+                }
+                Edit::ChangeStep {
+                    old_index,
+                    new_index,
+                    step,
+                    changes,
+                } => {
+                    if old_index == new_index {
+                        writeln!(f, "{}~ step {}: input {}", indent, old_index, step.agent)?;
+                    } else {
+                        writeln!(
+                            f,
+                            "{}~ step {} -> {}: input {}",
+                            indent, old_index, new_index, step.agent
+                        )?;
+                    }
+                    for change in changes {
+                        writeln!(f, "{}    at {:?}:", indent, change.path)?;
+                        writeln!(f, "{}    - {}", indent, change.old.to_text())?;
+                        writeln!(f, "{}    + {}", indent, change.new.to_text())?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<M: Matcher> fmt::Display for TraceDiff<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.render(f, "")
+    }
+}
+
+impl<M: Matcher> Trace<M> {
+    /// Returns the edits which turn this trace into `other`
+    pub fn diff(&self, other: &Trace<M>) -> TraceDiff<M> {
+        let mut edits = vec![];
+
+        if self.descriptors != other.descriptors {
+            edits.push(Edit::Descriptors {
+                old: self.descriptors.clone(),
+                new: other.descriptors.clone(),
+            });
+        }
+
+        let priors = self.prior_traces.len().max(other.prior_traces.len());
+        for index in 0..priors {
+            match (self.prior_traces.get(index), other.prior_traces.get(index)) {
+                (Some(old), Some(new)) => {
+                    let diff = old.diff(new);
+                    if !diff.is_empty() {
+                        edits.push(Edit::ChangePrior { index, diff });
+                    }
+                }
+                (Some(_), None) => edits.push(Edit::RemovePrior { index }),
+                (None, Some(_)) => edits.push(Edit::AddPrior { index }),
+                (None, None) => unreachable!(),
+            }
+        }
+
+        diff_steps(&self.steps, &other.steps, &mut edits);
+
+        TraceDiff { edits }
+    }
+}
+
+fn step_hash<M: Matcher>(step: &Step<M>) -> u64 {
+    let mut hasher = ahash::RandomState::with_seeds(0, 0, 0, 0).build_hasher();
+    step.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Aligns the steps by their longest common subsequence and pushes the edits of the steps which
+/// are not part of it.
+fn diff_steps<M: Matcher>(old: &[Step<M>], new: &[Step<M>], edits: &mut Vec<Edit<M>>) {
+    let old_hashes: Vec<u64> = old.iter().map(step_hash).collect();
+    let new_hashes: Vec<u64> = new.iter().map(step_hash).collect();
+
+    // common[i][j] is the length of the common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old_hashes[i] == new_hashes[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut removed = vec![];
+    let mut added = vec![];
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old_hashes[i] == new_hashes[j] {
+            pair_steps(old, new, &mut removed, &mut added, edits);
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && common[i + 1][j] >= common[i][j + 1]) {
+            removed.push(i);
+            i += 1;
+        } else {
+            added.push(j);
+            j += 1;
+        }
+    }
+    pair_steps(old, new, &mut removed, &mut added, edits);
+}
+
+/// Pushes the edits of the steps which were removed and added between two unchanged steps.
+/// Inputs to the same agent are paired as changed steps.
+fn pair_steps<M: Matcher>(
+    old: &[Step<M>],
+    new: &[Step<M>],
+    removed: &mut Vec<usize>,
+    added: &mut Vec<usize>,
+    edits: &mut Vec<Edit<M>>,
+) {
+    let mut unpaired = vec![];
+
+    for (position, old_index) in removed.iter().enumerate() {
+        let old_step = &old[*old_index];
+        let paired = added.get(position).and_then(|new_index| {
+            let new_step = &new[*new_index];
+            match (&old_step.action, &new_step.action) {
+                (Action::Input(old_input), Action::Input(new_input))
+                    if old_step.agent == new_step.agent =>
+                {
+                    let mut changes = vec![];
+                    diff_terms(
+                        &old_input.recipe,
+                        &new_input.recipe,
+                        &mut vec![],
+                        &mut changes,
+                    );
+                    Some(Edit::ChangeStep {
+                        old_index: *old_index,
+                        new_index: *new_index,
+                        step: new_step.clone(),
+                        changes,
+                    })
+                }
+                _ => None,
+            }
+        });
+
+        match paired {
+            Some(edit) => edits.push(edit),
+            None => {
+                edits.push(Edit::RemoveStep {
+                    index: *old_index,
+                    step: old_step.clone(),
+                });
+                if let Some(new_index) = added.get(position) {
+                    unpaired.push(*new_index);
+                }
+            }
+        }
+    }
+
+    unpaired.extend(added.iter().skip(removed.len()));
+    for new_index in unpaired {
+        edits.push(Edit::AddStep {
+            index: new_index,
+            step: new[new_index].clone(),
+        });
+    }
+
+    removed.clear();
+    added.clear();
+}
+
+/// Pushes the largest subterms which differ. Applications of the same function are compared
+/// argument by argument.
+fn diff_terms<M: Matcher>(
+    old: &Term<M>,
+    new: &Term<M>,
+    path: &mut TermPath,
+    changes: &mut Vec<TermChange<M>>,
+) {
+    if old == new {
+        return;
+    }
+
+    match (old, new) {
+        (Term::Application(old_function, old_args), Term::Application(new_function, new_args))
+            if old_function == new_function && old_args.len() == new_args.len() =>
+        {
+            for (i, (old_arg, new_arg)) in old_args.iter().zip(new_args).enumerate() {
+                path.push(i);
+                diff_terms(old_arg, new_arg, path, changes);
+                path.pop();
+            }
+        }
+        _ => changes.push(TermChange {
+            path: path.clone(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Edit;
+    use crate::agent::AgentName;
+    use crate::algebra::set_deserialize_signature;
+    use crate::algebra::test_signature::*;
+    use crate::term;
+    use crate::trace::{InputAction, OutputAction, ResetAction};
+
+    #[test_log::test]
+    fn test_diff_reports_changed_subterms_and_steps() {
+        let _ = set_deserialize_signature(&TEST_SIGNATURE);
+        let server = AgentName::first();
+
+        let mut old = setup_simple_trace();
+        old.steps = vec![
+            InputAction::new_step(
+                server,
+                term! { fn_client_extensions_append(fn_client_extensions_new, fn_signature_algorithm_extension) },
+            ),
+            OutputAction::new_step(server),
+        ];
+        let mut new = old.clone();
+        new.steps[0] = InputAction::new_step(
+            server,
+            term! { fn_client_extensions_append(fn_client_extensions_new, fn_ec_point_formats_extension) },
+        );
+        new.steps[1] = ResetAction::new_step(server);
+
+        assert!(old.diff(&old.clone()).is_empty());
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.edits.len(), 3);
+        match &diff.edits[0] {
+            Edit::ChangeStep { changes, .. } => {
+                assert_eq!(changes.len(), 1);
+                assert_eq!(changes[0].path, vec![1]);
+            }
+            edit => panic!("unexpected edit {:?}", edit),
+        }
+        assert!(matches!(diff.edits[1], Edit::RemoveStep { index: 1, .. }));
+        assert!(matches!(diff.edits[2], Edit::AddStep { index: 1, .. }));
+
+        let rendered = diff.to_string();
+        assert!(rendered.contains("fn_ec_point_formats_extension"));
+        assert!(rendered.contains("+ step 1: reset 0"));
+    }
+}
@@ -1,6 +1,6 @@
 use crate::agent::AgentName;
 use crate::algebra::Matcher;
-use crate::trace::Trace;
+use crate::trace::{Action, Step, Trace};
 
 pub trait TraceHelper<A, M>
 where
@@ -31,6 +31,29 @@ where
     }
 }
 
+/// Seeds with more than two agents, e.g. an attacker which relays messages between a client and two
+/// servers.
+impl<M, F> TraceHelper<(AgentName, AgentName, AgentName), M> for F
+where
+    F: Fn(AgentName, AgentName, AgentName) -> Trace<M>,
+    M: Matcher,
+{
+    fn build_named_trace(self) -> (&'static str, Trace<M>) {
+        (self.fn_name(), self.build_trace())
+    }
+
+    fn build_trace(self) -> Trace<M> {
+        let agent_a = AgentName::first();
+        let agent_b = agent_a.next();
+        let agent_c = agent_b.next();
+        (self)(agent_a, agent_b, agent_c)
+    }
+
+    fn fn_name(&self) -> &'static str {
+        std::any::type_name::<F>()
+    }
+}
+
 impl<M, F> TraceHelper<AgentName, M> for F
 where
     F: Fn(AgentName) -> Trace<M>,
@@ -50,3 +73,158 @@ where
         std::any::type_name::<F>()
     }
 }
+
+/// Expands a bounded repetition of [`Step`]s into concrete steps.
+///
+/// `body` is called once per iteration with the index of the iteration. The index can be used in
+/// the queries of the recipes, e.g. `((server, i))`, to refer to the knowledge produced in the
+/// current iteration.
+pub fn repeat_steps<M, F>(count: u16, body: F) -> Vec<Step<M>>
+where
+    M: Matcher,
+    F: Fn(u16) -> Vec<Step<M>>,
+{
+    (0..count).flat_map(body).collect()
+}
+
+/// Expands `count` copies of `steps`. In the `i`-th copy, the counters of all queries in the
+/// recipes are increased by `i`.
+pub fn repeat_steps_shifted<M: Matcher>(count: u16, steps: &[Step<M>]) -> Vec<Step<M>> {
+    repeat_steps(count, |i| {
+        steps
+            .iter()
+            .cloned()
+            .map(|mut step| {
+                if let Action::Input(input) = &mut step.action {
+                    input.recipe.shift_query_counters(i);
+                }
+                step
+            })
+            .collect()
+    })
+}
+
+/// Bounded repetition of steps within a seed trace.
+///
+/// ```ignore
+/// steps: [
+///     vec![OutputAction::new_step(client)],
+///     repeat!(10, |i| {
+///         InputAction::new_step(server, term! { fn_heartbeat((client, i)) }),
+///     }),
+///     // The counters of all queries are increased by the index of the iteration
+///     repeat!(10 => {
+///         InputAction::new_step(server, term! { fn_heartbeat((client, 0)) }),
+///     }),
+/// ]
+/// .concat()
+/// ```
+#[macro_export]
+macro_rules! repeat {
+    ($count:expr, |$index:ident| { $($step:expr),* $(,)? }) => {
+        $crate::trace_helper::repeat_steps($count, |$index: u16| vec![$($step),*])
+    };
+    ($count:expr => { $($step:expr),* $(,)? }) => {
+        $crate::trace_helper::repeat_steps_shifted($count, &[$($step),*])
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::agent::{AgentDescriptor, AgentName, TLSVersion};
+    use crate::algebra::test_signature::*;
+    use crate::algebra::Term;
+    use crate::trace::{Action, InputAction, OutputAction, Step, Trace};
+    use crate::trace_helper::TraceHelper;
+    use crate::{repeat, term};
+
+    fn query_counters(steps: &[Step<crate::algebra::AnyMatcher>]) -> Vec<u16> {
+        steps
+            .iter()
+            .filter_map(|step| match &step.action {
+                Action::Input(input) => Some(&input.recipe),
+                Action::Output(_)
+                | Action::Expect(_)
+                | Action::Reset(_)
+                | Action::Renegotiate(_)
+                | Action::KeyUpdate(_) => None,
+            })
+            .flat_map(|recipe| {
+                recipe
+                    .into_iter()
+                    .filter_map(|term| match term {
+                        Term::Variable(variable) => Some(variable.query.counter),
+                        Term::Application(_, _) => None,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[test_log::test]
+    fn test_repeat_with_index() {
+        let server = AgentName::first();
+
+        let steps: Vec<Step<_>> = repeat!(3, |i| {
+            InputAction::new_step(server, term! { fn_hmac256(fn_hmac256_new_key, ((server, i))) }),
+            OutputAction::new_step(server),
+        });
+
+        assert_eq!(steps.len(), 6);
+        assert_eq!(query_counters(&steps), vec![0, 1, 2]);
+    }
+
+    #[test_log::test]
+    fn test_repeat_shifted() {
+        let server = AgentName::first();
+
+        let steps: Vec<Step<_>> = repeat!(4 => {
+            InputAction::new_step(server, term! { fn_hmac256(fn_hmac256_new_key, ((server, 1))) }),
+        });
+
+        assert_eq!(steps.len(), 4);
+        assert_eq!(query_counters(&steps), vec![1, 2, 3, 4]);
+    }
+
+    fn relay(client: AgentName, server: AgentName, other_server: AgentName) -> TestTrace {
+        Trace {
+            prior_traces: vec![],
+            descriptors: vec![
+                AgentDescriptor::new_client(client, TLSVersion::V1_3),
+                AgentDescriptor::new_server(server, TLSVersion::V1_3),
+                AgentDescriptor::new_server(other_server, TLSVersion::V1_3),
+            ],
+            steps: vec![
+                OutputAction::new_step(client),
+                InputAction::new_step(
+                    server,
+                    term! { fn_hmac256(fn_hmac256_new_key, ((client, 0))) },
+                ),
+                InputAction::new_step(
+                    other_server,
+                    term! { fn_hmac256(fn_hmac256_new_key, ((client, 0))) },
+                ),
+            ],
+        }
+    }
+
+    #[test_log::test]
+    fn test_build_trace_with_three_agents() {
+        let (name, trace) = relay.build_named_trace();
+
+        assert!(name.ends_with("relay"));
+        let names = trace
+            .descriptors
+            .iter()
+            .map(|descriptor| descriptor.name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                AgentName::first(),
+                AgentName::first().next(),
+                AgentName::first().next().next()
+            ]
+        );
+    }
+}
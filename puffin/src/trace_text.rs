@@ -0,0 +1,993 @@
+//! Textual format of [`Trace`]s.
+//!
+//! Trace files are encoded with postcard, see [`Trace::to_file`], which makes it impossible to
+//! craft or tweak a trace by hand. The textual format is line-based, diff-friendly and follows the
+//! syntax of the [`term!`](crate::term) macro. [`Trace::to_text`] writes a trace in this format
+//! and [`Trace::from_str`] parses it:
+//!
+//! ```text
+//! # a server which receives a ClientHello
+//! agent 0 server v1.2 try_reuse
+//!
+//! input 0: fn_client_hello(
+//!     fn_protocol_version12,
+//!     fn_new_random,
+//!     (0, 1)/ClientHelloPayload/SessionId,
+//!     ...
+//! )
+//! output 0
+//! expect 0 present [_, {"Handshake":"ServerHello"}] where fn_has_server_extension(
+//!     (0, 0)[{"Handshake":"ServerHello"}]/Vec<ServerExtension>,
+//!     fn_supported_versions13_server_extension
+//! )
+//! ```
+//!
+//! A trace consists of the following statements:
+//!
+//! * `agent <name> server|client v1.3|v1.2 <flags>` adds an [`AgentDescriptor`]. The flags are
+//!   `try_reuse`, `client_authentication` and `server_authentication`. A flag without a value is
+//!   enabled, `flag=false` disables it. Missing flags have their default value.
+//! * `prior { ... }` adds a prior trace, which consists of statements as well.
+//! * `input <agent>: <term>`, `output <agent>` and `expect <agent> present|absent [<matchers>]` add
+//!   a [`Step`]. Matchers are written as JSON, `_` is no matcher. The conditions of an expectation
+//!   follow as `where <term>, ...`. The knowledge of an output step is restricted with `output
+//!   <agent> filter [<matchers>] [<types>]`.
+//! * `reset <agent>`, `renegotiate <agent>` and `key_update <agent> [requested]` add a [`Step`]
+//!   which changes the session of the agent.
+//! * `repeat <count> { ... }` adds the steps in the braces `count` times, see [`repeat_steps`].
+//!   With `repeat <count> shifted { ... }`, the counters of the queries are increased by the index
+//!   of the iteration, see [`repeat_steps_shifted`]. Written traces always contain the expanded
+//!   steps.
+//!
+//! Terms are either constants `name`, applications `name(arg, ...)` or variables
+//! `(source, counter)[matcher]/Path/Segment[i]: Type`. The source of a variable is the number of an
+//! agent, a label or `_` for none. The matcher and the path, see [`crate::query`], are optional.
+//! The type can be omitted if it equals the type of the argument. Functions and types are named
+//! without their module prefix, unless this is ambiguous in the signature.
+//!
+//! Everything after a `#` until the end of the line is a comment. Parsing requires the signature
+//! which is set with [`set_deserialize_signature`](crate::algebra::set_deserialize_signature).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Write;
+use std::str::FromStr;
+
+use itertools::Itertools;
+
+use crate::agent::{AgentDescriptor, AgentName, AgentType, TLSVersion};
+use crate::algebra::atoms::{Function, Variable};
+use crate::algebra::dynamic_function::TypeShape;
+use crate::algebra::signature::{FunctionDefinition, Signature};
+use crate::algebra::{remove_prefix, try_deserialize_signature, Matcher, Term};
+use crate::query::parse_path;
+use crate::trace::{
+    Action, ExpectAction, InputAction, KeyUpdateAction, KnowledgeFilter, OutputAction, Query,
+    RenegotiateAction, ResetAction, Source, Step, Trace,
+};
+use crate::trace_helper::{repeat_steps, repeat_steps_shifted};
+
+const INDENT: &str = "    ";
+
+/// Characters which end keywords and numbers
+const WORD_END: &[char] = &['#', '{', '}', '(', ')', '[', ']', ',', ':', '='];
+
+/// Characters which end the names of functions
+const NAME_END: &[char] = &['#', '{', '}', '(', ')', ','];
+
+/// Maps the names of the functions and types of a signature without their module prefix to their
+/// full names. Names are only shortened if they are unique.
+struct Names {
+    signature: Option<&'static Signature>,
+    functions: HashMap<String, Vec<&'static str>>,
+    types: HashMap<String, Vec<&'static str>>,
+}
+
+impl Names {
+    fn new(signature: Option<&'static Signature>) -> Self {
+        let mut functions: HashMap<String, Vec<&'static str>> = HashMap::new();
+        let mut types: HashMap<String, Vec<&'static str>> = HashMap::new();
+
+        if let Some(signature) = signature {
+            for name in signature.functions_by_name.keys() {
+                functions
+                    .entry(remove_prefix(name))
+                    .or_default()
+                    .push(*name);
+            }
+            for name in signature.types_by_name.keys() {
+                types.entry(remove_prefix(name)).or_default().push(*name);
+            }
+        }
+
+        Self {
+            signature,
+            functions,
+            types,
+        }
+    }
+
+    fn shorten(names: &HashMap<String, Vec<&'static str>>, name: &str) -> String {
+        let short = remove_prefix(name);
+        match names.get(&short).map(Vec::as_slice) {
+            Some([_]) => short,
+            _ => name.to_string(),
+        }
+    }
+
+    fn resolve<'a>(names: &HashMap<String, Vec<&'static str>>, name: &'a str) -> &'a str {
+        match names.get(name).map(Vec::as_slice) {
+            Some([full]) => full,
+            _ => name,
+        }
+    }
+
+    fn function_name(&self, name: &str) -> String {
+        Self::shorten(&self.functions, name)
+    }
+
+    fn type_name(&self, name: &str) -> String {
+        Self::shorten(&self.types, name)
+    }
+
+    fn function(&self, name: &str) -> Option<&'static FunctionDefinition> {
+        let signature = self.signature?;
+        signature.functions_by_name.get(name).or_else(|| {
+            signature
+                .functions_by_name
+                .get(Self::resolve(&self.functions, name))
+        })
+    }
+
+    fn typ(&self, name: &str) -> Option<TypeShape> {
+        let signature = self.signature?;
+        signature
+            .types_by_name
+            .get(name)
+            .or_else(|| {
+                signature
+                    .types_by_name
+                    .get(Self::resolve(&self.types, name))
+            })
+            .copied()
+    }
+}
+
+impl<M: Matcher> Trace<M> {
+    /// Writes the trace in the textual format, see the [module documentation](crate::trace_text).
+    pub fn to_text(&self) -> String {
+        let names = Names::new(try_deserialize_signature());
+        let mut text = String::new();
+        write_trace(&mut text, self, &names, 0);
+        text
+    }
+}
+
+impl<M: Matcher> Step<M> {
+    /// Writes the statement of the step in the textual format
+    pub fn to_text(&self) -> String {
+        step_text(self, &Names::new(try_deserialize_signature()), 0)
+    }
+}
+
+impl<M: Matcher> Term<M> {
+    /// Writes the term in the textual format
+    pub fn to_text(&self) -> String {
+        term_text(self, None, &Names::new(try_deserialize_signature()), 0)
+    }
+}
+
+/// Parses a trace in the textual format, see the [module documentation](crate::trace_text).
+impl<M: Matcher> FromStr for Trace<M> {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let signature = try_deserialize_signature()
+            .ok_or_else(|| "the signature for deserialization has not been set".to_string())?;
+
+        Parser {
+            text,
+            position: 0,
+            names: Names::new(Some(signature)),
+        }
+        .trace(false)
+    }
+}
+
+fn write_trace<M: Matcher>(text: &mut String, trace: &Trace<M>, names: &Names, depth: usize) {
+    let indent = INDENT.repeat(depth);
+
+    for descriptor in &trace.descriptors {
+        let _ = writeln!(text, "{}{}", indent, descriptor_text(descriptor));
+    }
+
+    for prior_trace in &trace.prior_traces {
+        let _ = writeln!(text, "{}prior {{", indent);
+        write_trace(text, prior_trace, names, depth + 1);
+        let _ = writeln!(text, "{}}}", indent);
+    }
+
+    let has_header = !trace.descriptors.is_empty() || !trace.prior_traces.is_empty();
+    if has_header && !trace.steps.is_empty() {
+        text.push('\n');
+    }
+
+    for step in &trace.steps {
+        let _ = writeln!(text, "{}{}", indent, step_text(step, names, depth));
+    }
+}
+
+/// Writes the statement of `step` at the indentation level `depth`
+fn step_text<M: Matcher>(step: &Step<M>, names: &Names, depth: usize) -> String {
+    match &step.action {
+        Action::Input(input) => format!(
+            "input {}: {}",
+            step.agent,
+            term_text(&input.recipe, None, names, depth)
+        ),
+        Action::Output(output) => match &output.filter {
+            Some(filter) => format!(
+                "output {} filter [{}] [{}]",
+                step.agent,
+                matchers_text(&filter.matchers),
+                filter.types.join(", ")
+            ),
+            None => format!("output {}", step.agent),
+        },
+        Action::Expect(expect) => format!(
+            "expect {} {} [{}]{}",
+            step.agent,
+            if expect.present { "present" } else { "absent" },
+            matchers_text(&expect.matchers),
+            if expect.conditions.is_empty() {
+                String::new()
+            } else {
+                format!(
+                    " where {}",
+                    expect
+                        .conditions
+                        .iter()
+                        .map(|condition| term_text(condition, None, names, depth))
+                        .join(", ")
+                )
+            }
+        ),
+        Action::Reset(_) => format!("reset {}", step.agent),
+        Action::Renegotiate(_) => format!("renegotiate {}", step.agent),
+        Action::KeyUpdate(key_update) => format!(
+            "key_update {}{}",
+            step.agent,
+            if key_update.update_requested {
+                " requested"
+            } else {
+                ""
+            }
+        ),
+    }
+}
+
+fn matchers_text<M: Matcher>(matchers: &[Option<M>]) -> String {
+    matchers
+        .iter()
+        .map(|matcher| match matcher {
+            Some(matcher) => matcher_text(matcher),
+            None => "_".to_string(),
+        })
+        .join(", ")
+}
+
+pub(crate) fn descriptor_text(descriptor: &AgentDescriptor) -> String {
+    let default = AgentDescriptor::default();
+    let mut text = format!(
+        "agent {} {} {}",
+        descriptor.name,
+        match descriptor.typ {
+            AgentType::Server => "server",
+            AgentType::Client => "client",
+        },
+        match descriptor.tls_version {
+            TLSVersion::V1_3 => "v1.3",
+            TLSVersion::V1_2 => "v1.2",
+        }
+    );
+
+    for (flag, value, default) in [
+        ("try_reuse", descriptor.try_reuse, default.try_reuse),
+        (
+            "client_authentication",
+            descriptor.client_authentication,
+            default.client_authentication,
+        ),
+        (
+            "server_authentication",
+            descriptor.server_authentication,
+            default.server_authentication,
+        ),
+    ] {
+        match (value, value == default) {
+            (_, true) => {}
+            (true, false) => text += &format!(" {}", flag),
+            (false, false) => text += &format!(" {}=false", flag),
+        }
+    }
+
+    text
+}
+
+fn matcher_text<M: Matcher>(matcher: &M) -> String {
+    serde_json::to_string(matcher).expect("matchers can be serialized to JSON")
+}
+
+/// Writes `term` at the indentation level `depth`. The type of a variable is written if it differs
+/// from `expected`.
+fn term_text<M: Matcher>(
+    term: &Term<M>,
+    expected: Option<TypeShape>,
+    names: &Names,
+    depth: usize,
+) -> String {
+    match term {
+        Term::Variable(variable) => {
+            let query = &variable.query;
+            let source = match &query.source {
+                Some(Source::Agent(agent)) => agent.to_string(),
+                Some(Source::Label(label)) => label.clone(),
+                None => "_".to_string(),
+            };
+
+            let mut text = format!("({}, {})", source, query.counter);
+            if let Some(matcher) = &query.matcher {
+                let _ = write!(text, "[{}]", matcher_text(matcher));
+            }
+            for segment in &query.path {
+                let _ = write!(text, "/{}", segment);
+            }
+            if expected != Some(variable.typ) {
+                let _ = write!(text, ": {}", names.type_name(variable.typ.name));
+            }
+            text
+        }
+        Term::Application(function, args) => {
+            let name = names.function_name(function.name());
+            if args.is_empty() {
+                return name;
+            }
+
+            let argument_types = &function.shape().argument_types;
+            let args_text = args
+                .iter()
+                .enumerate()
+                .map(|(i, arg)| term_text(arg, argument_types.get(i).copied(), names, depth + 1))
+                .collect_vec();
+
+            let is_leaf = |arg: &Term<M>| match arg {
+                Term::Variable(_) => true,
+                Term::Application(_, args) => args.is_empty(),
+            };
+
+            if args.iter().all(is_leaf) {
+                format!("{}({})", name, args_text.join(", "))
+            } else {
+                let indent = INDENT.repeat(depth + 1);
+                format!(
+                    "{}(\n{}\n{})",
+                    name,
+                    args_text
+                        .iter()
+                        .map(|arg| format!("{}{}", indent, arg))
+                        .join(",\n"),
+                    INDENT.repeat(depth)
+                )
+            }
+        }
+    }
+}
+
+/// Returns the length of the prefix of `text` which ends before the first character for which
+/// `stop` is true and which is not enclosed in brackets. JSON strings are skipped.
+fn scan(text: &str, stop: impl Fn(char) -> bool) -> usize {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, c) in text.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            _ if depth == 0 && stop(c) => return i,
+            '"' => in_string = true,
+            '(' | '[' | '{' | '<' => depth += 1,
+            ')' | ']' | '}' | '>' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    text.len()
+}
+
+/// Recursive descent parser of the textual format.
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+    names: Names,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn error(&self, message: impl fmt::Display) -> String {
+        let line = self.text[..self.position].matches('\n').count() + 1;
+        format!("line {}: {}", line, message)
+    }
+
+    /// Skips whitespace and comments.
+    fn skip(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.position += rest.len() - trimmed.len();
+
+            if !trimmed.starts_with('#') {
+                break;
+            }
+            self.position += trimmed.find('\n').unwrap_or(trimmed.len());
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip();
+        self.rest().chars().next()
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.position += expected.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected {}", expected)))
+        }
+    }
+
+    /// Whether a word follows, as opposed to the end of the input or a bracket, e.g. the `}` which
+    /// closes a block.
+    fn at_word(&mut self) -> bool {
+        self.peek().is_some_and(|c| !WORD_END.contains(&c))
+    }
+
+    /// Reads everything until whitespace or one of `end`.
+    fn word(&mut self, end: &[char]) -> Result<&'a str, String> {
+        self.skip();
+        let rest = self.rest();
+        let length = scan(rest, |c| c.is_whitespace() || end.contains(&c));
+        if length == 0 {
+            return Err(self.error("unexpected end of statement"));
+        }
+
+        self.position += length;
+        Ok(&rest[..length])
+    }
+
+    /// Reads the contents of the brackets `open` and `close`.
+    fn bracketed(&mut self, open: char, close: char) -> Result<&'a str, String> {
+        self.expect(open)?;
+        let rest = self.rest();
+        let length = scan(rest, |c| c == close);
+        if length == rest.len() {
+            return Err(self.error(format!("missing {}", close)));
+        }
+
+        self.position += length + close.len_utf8();
+        Ok(&rest[..length])
+    }
+
+    fn agent(&mut self) -> Result<AgentName, String> {
+        let name = self.word(WORD_END)?;
+        name.parse()
+            .map(AgentName::from_index)
+            .map_err(|_| self.error(format!("invalid agent {}", name)))
+    }
+
+    fn matcher<M: Matcher>(&self, text: &str) -> Result<M, String> {
+        serde_json::from_str(text.trim())
+            .map_err(|err| self.error(format!("invalid matcher {}: {}", text.trim(), err)))
+    }
+
+    /// Parses statements until the end of the input, or until `}` if the trace is `nested`.
+    fn trace<M: Matcher>(&mut self, nested: bool) -> Result<Trace<M>, String> {
+        let mut trace = Trace {
+            descriptors: vec![],
+            steps: vec![],
+            prior_traces: vec![],
+        };
+
+        loop {
+            match self.peek() {
+                None if nested => return Err(self.error("missing }")),
+                None => return Ok(trace),
+                Some('}') if nested => {
+                    self.position += 1;
+                    return Ok(trace);
+                }
+                _ => {}
+            }
+
+            match self.word(WORD_END)? {
+                "agent" => trace.descriptors.push(self.descriptor()?),
+                "prior" => {
+                    self.expect('{')?;
+                    trace.prior_traces.push(self.trace(true)?);
+                }
+                "input" => {
+                    let agent = self.agent()?;
+                    self.expect(':')?;
+                    let recipe = self.term(None)?;
+                    trace.steps.push(InputAction::new_step(agent, recipe));
+                }
+                "output" => trace.steps.push(self.output_step()?),
+                "expect" => trace.steps.push(self.expect_step()?),
+                "reset" => trace.steps.push(ResetAction::new_step(self.agent()?)),
+                "renegotiate" => trace.steps.push(RenegotiateAction::new_step(self.agent()?)),
+                "repeat" => trace.steps.extend(self.repeat()?),
+                "key_update" => {
+                    let agent = self.agent()?;
+                    let position = self.position;
+                    let update_requested = self.at_word() && self.word(WORD_END)? == "requested";
+                    if !update_requested {
+                        self.position = position;
+                    }
+                    trace
+                        .steps
+                        .push(KeyUpdateAction::new_step(agent, update_requested));
+                }
+                statement => return Err(self.error(format!("unknown statement {}", statement))),
+            }
+        }
+    }
+
+    /// Parses the count and the body of a `repeat` statement and expands its steps.
+    fn repeat<M: Matcher>(&mut self) -> Result<Vec<Step<M>>, String> {
+        let count = self.word(WORD_END)?;
+        let count: u16 = count
+            .parse()
+            .map_err(|_| self.error(format!("invalid repetition count {}", count)))?;
+
+        let shifted = self.peek() != Some('{');
+        if shifted {
+            match self.word(WORD_END)? {
+                "shifted" => {}
+                word => return Err(self.error(format!("expected shifted or {{, got {}", word))),
+            }
+        }
+
+        self.expect('{')?;
+        let body: Trace<M> = self.trace(true)?;
+        if !body.descriptors.is_empty() || !body.prior_traces.is_empty() {
+            return Err(self.error("repeat can only contain steps"));
+        }
+
+        Ok(if shifted {
+            repeat_steps_shifted(count, &body.steps)
+        } else {
+            repeat_steps(count, |_| body.steps.clone())
+        })
+    }
+
+    fn descriptor(&mut self) -> Result<AgentDescriptor, String> {
+        let name = self.agent()?;
+        let typ = match self.word(WORD_END)? {
+            "server" => AgentType::Server,
+            "client" => AgentType::Client,
+            typ => return Err(self.error(format!("invalid agent type {}", typ))),
+        };
+        let tls_version = match self.word(WORD_END)? {
+            "v1.3" => TLSVersion::V1_3,
+            "v1.2" => TLSVersion::V1_2,
+            version => return Err(self.error(format!("invalid TLS version {}", version))),
+        };
+
+        let mut descriptor = AgentDescriptor {
+            name,
+            typ,
+            tls_version,
+            ..AgentDescriptor::default()
+        };
+
+        loop {
+            let position = self.position;
+            if matches!(self.peek(), None | Some('}')) {
+                break;
+            }
+
+            let flag = match self.word(WORD_END)? {
+                "try_reuse" => &mut descriptor.try_reuse,
+                "client_authentication" => &mut descriptor.client_authentication,
+                "server_authentication" => &mut descriptor.server_authentication,
+                _ => {
+                    self.position = position;
+                    break;
+                }
+            };
+
+            *flag = if self.eat('=') {
+                match self.word(WORD_END)? {
+                    "true" => true,
+                    "false" => false,
+                    value => return Err(self.error(format!("invalid flag value {}", value))),
+                }
+            } else {
+                true
+            };
+        }
+
+        Ok(descriptor)
+    }
+
+    fn output_step<M: Matcher>(&mut self) -> Result<Step<M>, String> {
+        let agent = self.agent()?;
+
+        let position = self.position;
+        if !self.at_word() || self.word(WORD_END)? != "filter" {
+            self.position = position;
+            return Ok(OutputAction::new_step(agent));
+        }
+
+        let matchers = self.matchers()?;
+        let types = self
+            .bracketed('[', ']')?
+            .split(',')
+            .map(str::trim)
+            .filter(|typ| !typ.is_empty())
+            .map(remove_prefix)
+            .collect();
+
+        Ok(OutputAction::new_filtered_step(
+            agent,
+            KnowledgeFilter { matchers, types },
+        ))
+    }
+
+    /// Parses a list of matchers in brackets.
+    fn matchers<M: Matcher>(&mut self) -> Result<Vec<Option<M>>, String> {
+        let mut list = self.bracketed('[', ']')?;
+        let mut matchers = vec![];
+        while !list.trim().is_empty() {
+            let length = scan(list, |c| c == ',');
+            matchers.push(match list[..length].trim() {
+                "_" => None,
+                matcher => Some(self.matcher(matcher)?),
+            });
+            list = list.get(length + 1..).unwrap_or_default();
+        }
+        Ok(matchers)
+    }
+
+    fn expect_step<M: Matcher>(&mut self) -> Result<Step<M>, String> {
+        let agent = self.agent()?;
+        let present = match self.word(WORD_END)? {
+            "present" => true,
+            "absent" => false,
+            present => {
+                return Err(self.error(format!("expected present or absent, got {}", present)))
+            }
+        };
+
+        let matchers = self.matchers()?;
+
+        let mut conditions = vec![];
+        let position = self.position;
+        if self.at_word() && self.word(WORD_END)? == "where" {
+            loop {
+                conditions.push(self.term(None)?);
+                if !self.eat(',') {
+                    break;
+                }
+            }
+        } else {
+            self.position = position;
+        }
+
+        Ok(ExpectAction::new_step_with_conditions(
+            agent, matchers, present, conditions,
+        ))
+    }
+
+    /// Parses a term. `expected` is the type of the argument which the term is passed to.
+    fn term<M: Matcher>(&mut self, expected: Option<TypeShape>) -> Result<Term<M>, String> {
+        if self.peek() == Some('(') {
+            return self.variable(expected).map(Term::Variable);
+        }
+
+        let name = self.word(NAME_END)?;
+        let (shape, dynamic_fn) = self
+            .names
+            .function(name)
+            .ok_or_else(|| self.error(format!("unknown function {}", name)))?;
+
+        if let Some(expected) = expected {
+            if shape.return_type != expected {
+                return Err(self.error(format!(
+                    "{} returns {} instead of {}",
+                    name,
+                    remove_prefix(shape.return_type.name),
+                    remove_prefix(expected.name)
+                )));
+            }
+        }
+
+        let mut args = vec![];
+        if self.eat('(') {
+            while !self.eat(')') {
+                let argument_type = *shape.argument_types.get(args.len()).ok_or_else(|| {
+                    self.error(format!("too many arguments for function {}", name))
+                })?;
+                args.push(self.term(Some(argument_type))?);
+
+                if !self.eat(',') {
+                    self.expect(')')?;
+                    break;
+                }
+            }
+        }
+
+        if args.len() != shape.argument_types.len() {
+            return Err(self.error(format!(
+                "function {} expects {} arguments, got {}",
+                name,
+                shape.argument_types.len(),
+                args.len()
+            )));
+        }
+
+        Ok(Term::Application(
+            Function::new(shape.clone(), dynamic_fn.clone()),
+            args,
+        ))
+    }
+
+    fn variable<M: Matcher>(&mut self, expected: Option<TypeShape>) -> Result<Variable<M>, String> {
+        let head = self.bracketed('(', ')')?;
+        let mut query: Query<M> = format!("({})", head)
+            .parse()
+            .map_err(|err| self.error(err))?;
+        if query.source == Some(Source::Label("_".to_string())) {
+            query.source = None;
+        }
+
+        if self.peek() == Some('[') {
+            let matcher = self.bracketed('[', ']')?;
+            query.matcher = Some(self.matcher(matcher)?);
+        }
+
+        if self.eat('/') {
+            let path = self.word(&['#', ',', ')', ':'])?;
+            query.path = parse_path(path).map_err(|err| self.error(err))?;
+        }
+
+        let typ = if self.eat(':') {
+            let rest = self.rest();
+            let length = scan(rest, |c| matches!(c, '#' | ',' | ')' | '\n'));
+            let name = rest[..length].trim();
+            self.position += length;
+            self.names
+                .typ(name)
+                .ok_or_else(|| self.error(format!("unknown type {}", name)))?
+        } else {
+            expected.ok_or_else(|| self.error("the type of the variable is missing"))?
+        };
+
+        Ok(Variable::new(typ, query))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::agent::{AgentDescriptor, AgentName, TLSVersion};
+    use crate::algebra::dynamic_function::TypeShape;
+    use crate::algebra::test_signature::*;
+    use crate::algebra::{set_deserialize_signature, AnyMatcher, Term};
+    use crate::term;
+    use crate::trace::{
+        Action, ExpectAction, InputAction, KeyUpdateAction, KnowledgeFilter, OutputAction,
+        RenegotiateAction, ResetAction, Source,
+    };
+
+    #[test_log::test]
+    fn test_roundtrip() {
+        let _ = set_deserialize_signature(&TEST_SIGNATURE);
+        let server = AgentName::first();
+
+        let mut trace = setup_simple_trace();
+        trace.prior_traces.push(setup_simple_trace());
+        trace.descriptors[0].try_reuse = true;
+        trace.descriptors[0].server_authentication = false;
+        trace.steps.extend([
+            OutputAction::new_step(server),
+            OutputAction::new_filtered_step(
+                server,
+                KnowledgeFilter {
+                    matchers: vec![None, Some(AnyMatcher)],
+                    types: vec!["ClientExtension".to_string(), "Vec<u8>".to_string()],
+                },
+            ),
+            InputAction::new_step(
+                server,
+                term! { fn_client_extensions_append(fn_client_extensions_new, ((server, 0) / "ClientExtensions/ClientExtension[1]")) },
+            ),
+            ExpectAction::new_step(server, vec![Some(AnyMatcher), None], false),
+            ExpectAction::new_step_with_conditions(
+                server,
+                vec![],
+                true,
+                vec![
+                    term! { fn_client_extensions_new },
+                    term! { fn_client_extensions_append(fn_client_extensions_new, ((server, 0) / "ClientExtensions/ClientExtension[1]")) },
+                ],
+            ),
+            ResetAction::new_step(server),
+            RenegotiateAction::new_step(server),
+            KeyUpdateAction::new_step(server, true),
+            KeyUpdateAction::new_step(server, false),
+        ]);
+
+        let text = trace.to_text();
+        let parsed = text.parse::<TestTrace>().unwrap();
+
+        assert_eq!(parsed.to_text(), text);
+        assert_eq!(parsed.descriptors, trace.descriptors);
+        assert_eq!(parsed.prior_traces.len(), 1);
+        for (parsed, step) in parsed.steps.iter().zip(&trace.steps) {
+            match (&parsed.action, &step.action) {
+                (Action::Input(parsed), Action::Input(input)) => {
+                    assert_eq!(parsed.recipe, input.recipe)
+                }
+                (Action::Output(parsed), Action::Output(output)) => {
+                    assert_eq!(parsed.filter, output.filter)
+                }
+                (Action::Expect(parsed), Action::Expect(expect)) => {
+                    assert_eq!(parsed.matchers, expect.matchers);
+                    assert_eq!(parsed.present, expect.present);
+                    assert_eq!(parsed.conditions, expect.conditions);
+                }
+                (Action::Reset(_), Action::Reset(_))
+                | (Action::Renegotiate(_), Action::Renegotiate(_)) => {}
+                (Action::KeyUpdate(parsed), Action::KeyUpdate(key_update)) => {
+                    assert_eq!(parsed.update_requested, key_update.update_requested)
+                }
+                _ => panic!("steps differ"),
+            }
+        }
+    }
+
+    #[test_log::test]
+    fn test_parse_hand_written() {
+        let _ = set_deserialize_signature(&TEST_SIGNATURE);
+
+        let trace = "
+            # the server
+            agent 1 client v1.2 client_authentication server_authentication=false
+
+            input 1: fn_encrypt12(
+                fn_finished, # a comment
+                (label, 2)/Vec<u8>/u32[1],
+            )
+            input 1: (_, 0): u32
+            output 1
+        "
+        .parse::<TestTrace>()
+        .unwrap();
+
+        assert_eq!(
+            trace.descriptors,
+            vec![AgentDescriptor {
+                client_authentication: true,
+                server_authentication: false,
+                ..AgentDescriptor::new_client(AgentName::from_index(1), TLSVersion::V1_2)
+            }]
+        );
+        assert_eq!(trace.steps.len(), 3);
+
+        let Action::Input(input) = &trace.steps[0].action else {
+            panic!("expected an input step")
+        };
+        let Term::Application(_, args) = &input.recipe else {
+            panic!("expected an application")
+        };
+        let Term::Variable(variable) = &args[1] else {
+            panic!("expected a variable")
+        };
+        assert_eq!(variable.typ, TypeShape::of::<u32>());
+        assert_eq!(
+            variable.query.source,
+            Some(Source::Label("label".to_string()))
+        );
+        assert_eq!(variable.query.counter, 2);
+        assert_eq!(variable.query.path.len(), 2);
+
+        let Action::Input(input) = &trace.steps[1].action else {
+            panic!("expected an input step")
+        };
+        let Term::Variable(variable) = &input.recipe else {
+            panic!("expected a variable")
+        };
+        assert_eq!(variable.query.source, None);
+    }
+
+    #[test_log::test]
+    fn test_parse_repeat() {
+        let _ = set_deserialize_signature(&TEST_SIGNATURE);
+
+        let trace = "
+            output 0
+            repeat 3 {
+                input 0: (0, 1): u32
+                repeat 2 { output 0 }
+            }
+            repeat 2 shifted {
+                input 0: (0, 1): u32
+            }
+        "
+        .parse::<TestTrace>()
+        .unwrap();
+
+        assert_eq!(trace.steps.len(), 1 + 3 * 3 + 2);
+        let counters = trace
+            .steps
+            .iter()
+            .filter_map(|step| match &step.action {
+                Action::Input(input) => match &input.recipe {
+                    Term::Variable(variable) => Some(variable.query.counter),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(counters, vec![1, 1, 1, 1, 2]);
+        assert!(matches!(trace.steps[3].action, Action::Output(_)));
+
+        let expanded = trace.to_text().parse::<TestTrace>().unwrap();
+        assert_eq!(expanded.to_text(), trace.to_text());
+    }
+
+    #[test_log::test]
+    fn test_parse_errors() {
+        let _ = set_deserialize_signature(&TEST_SIGNATURE);
+
+        for (text, error) in [
+            ("output 0\ninput 0: fn_unknown", "line 2: unknown function"),
+            ("input 0: fn_encrypt12(fn_finished)", "expects 2 arguments"),
+            ("input 0: fn_encrypt12(fn_seq_0, fn_seq_0)", "returns"),
+            ("prior {\noutput 0", "missing }"),
+            ("agent 0 server v1.4", "invalid TLS version"),
+            ("input 0: (0, 0)", "type of the variable is missing"),
+            ("repeat x { output 0 }", "invalid repetition count"),
+            ("repeat 2 twice { output 0 }", "expected shifted"),
+            ("repeat 2 { agent 0 server v1.3 }", "only contain steps"),
+        ] {
+            let result = text.parse::<TestTrace>().map(|_| ());
+            assert!(
+                result.as_ref().is_err_and(|err| err.contains(error)),
+                "{}: {:?}",
+                text,
+                result
+            );
+        }
+    }
+}
@@ -0,0 +1,249 @@
+//! Reports for the crashing traces of a fuzzing campaign.
+//!
+//! [`triage`] replays each trace of an objective directory in a subprocess and writes a report
+//! directory per trace to the output directory:
+//!
+//! * `trace.trace` and `trace.txt`: the trace, in the binary and in the textual format,
+//! * `trace.dot` and, if graphviz is installed, `trace.svg`: the trace as graph,
+//! * `output.log`: everything which the replay wrote to stdout and stderr, like ASAN reports,
+//! * `metadata.json`: the metadata which the fuzzer stored with the objective, if any,
+//! * `README.md`: a summary of the replay.
+//!
+//! An index of all reports is written to `README.md` in the output directory.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use libafl::inputs::Input;
+
+use crate::algebra::Matcher;
+use crate::error::Error;
+use crate::execution::{run_in_subprocess, ExecutionStatus, Runner};
+use crate::fuzzer::sanitizer::feedback::AsanReportMetadata;
+use crate::graphviz::write_graphviz;
+use crate::protocol::ProtocolBehavior;
+use crate::trace::{Trace, TraceContext};
+
+/// Timeout for the replay of a single trace.
+const EXECUTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The outcome of the replay of one trace.
+#[derive(Debug, Clone)]
+pub struct CrashReport {
+    /// Name of the trace file in the objective directory
+    pub name: String,
+    pub status: ExecutionStatus,
+    /// The `SUMMARY` line of the ASAN report, if the replay produced one
+    pub asan_summary: Option<String>,
+    /// The last line of the replay which describes the result of the execution
+    pub result: Option<String>,
+}
+
+/// Replays the traces in `objective_dir` and writes a report for each to `output_dir`.
+pub fn triage<PB: ProtocolBehavior>(
+    runner: &Runner<PB>,
+    objective_dir: &Path,
+    output_dir: &Path,
+) -> Result<Vec<CrashReport>, Box<dyn std::error::Error>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(objective_dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        // LibAFL stores the metadata of an objective and locks in hidden files
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| !name.starts_with('.'))
+        })
+        .collect();
+    paths.sort();
+
+    fs::create_dir_all(output_dir)?;
+
+    let mut reports = vec![];
+    for path in paths {
+        let trace = match Trace::<PB::Matcher>::from_file(&path) {
+            Ok(trace) => trace,
+            Err(err) => {
+                log::warn!("Skipping {}: {}", path.display(), err);
+                continue;
+            }
+        };
+
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let report_dir = output_dir.join(&name);
+        fs::create_dir_all(&report_dir)?;
+
+        let report = triage_trace(runner, &path, &trace, &name, &report_dir)?;
+        log::info!("{}: {:?}", report.name, report.status);
+        reports.push(report);
+    }
+
+    fs::write(output_dir.join("README.md"), index(&reports))?;
+    Ok(reports)
+}
+
+fn triage_trace<PB: ProtocolBehavior>(
+    runner: &Runner<PB>,
+    path: &Path,
+    trace: &Trace<PB::Matcher>,
+    name: &str,
+    report_dir: &Path,
+) -> Result<CrashReport, Box<dyn std::error::Error>> {
+    trace.to_file(report_dir.join("trace.trace"))?;
+    fs::write(report_dir.join("trace.txt"), trace.to_text())?;
+
+    let dot = trace.dot_graph(true);
+    fs::write(report_dir.join("trace.dot"), &dot)?;
+    if let Err(err) = write_graphviz(
+        report_dir.join("trace.svg").to_str().unwrap_or_default(),
+        "svg",
+        &dot,
+    ) {
+        log::warn!("Failed to render {}: {}", name, err);
+    }
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let metadata_path = path.with_file_name(format!(".{}.metadata", file_name));
+    if metadata_path.exists() {
+        fs::copy(&metadata_path, report_dir.join("metadata.json"))?;
+    }
+
+    let log_path = report_dir.join("output.log");
+    let status = replay(runner, trace, &log_path)?;
+
+    let output = String::from_utf8_lossy(&fs::read(&log_path)?).into_owned();
+    let asan_summary = AsanReportMetadata::parse(output.clone()).summary;
+    let result = output
+        .lines()
+        .rev()
+        .find_map(|line| line.strip_prefix("Result: "))
+        .map(str::to_string);
+
+    let report = CrashReport {
+        name: name.to_string(),
+        status,
+        asan_summary,
+        result,
+    };
+    fs::write(report_dir.join("README.md"), summary(&report, trace))?;
+
+    Ok(report)
+}
+
+/// Executes `trace` in a subprocess whose stdout and stderr are redirected to `log_path`.
+fn replay<PB: ProtocolBehavior>(
+    runner: &Runner<PB>,
+    trace: &Trace<PB::Matcher>,
+    log_path: &Path,
+) -> Result<ExecutionStatus, Box<dyn std::error::Error>> {
+    let log = File::create(log_path)?;
+
+    let status = run_in_subprocess(
+        || {
+            unsafe {
+                libc::dup2(log.as_raw_fd(), libc::STDOUT_FILENO);
+                libc::dup2(log.as_raw_fd(), libc::STDERR_FILENO);
+            }
+
+            let mut ctx = runner.new_context();
+            let result = trace.execute(&mut ctx);
+
+            std::process::exit(print_execution_report(&ctx, result));
+        },
+        EXECUTION_TIMEOUT,
+    )?;
+
+    Ok(status)
+}
+
+/// Prints the state of an execution, its claims and expectation failures, and a `Result:` line
+/// which describes `result`. Returns the exit code which the subprocess of the execution exits
+/// with.
+pub fn print_execution_report<PB: ProtocolBehavior>(
+    ctx: &TraceContext<PB>,
+    result: Result<(), Error>,
+) -> i32 {
+    println!("\n{}", ctx);
+
+    println!("\nClaims:");
+    for claim in ctx.claims().deref_borrow().iter() {
+        println!("   {:?}", claim);
+    }
+
+    for failure in ctx.expectation_failures() {
+        println!("\n{}", failure);
+    }
+
+    let code = match result {
+        Ok(_) => {
+            println!("\nResult: success");
+            0
+        }
+        Err(Error::SecurityClaim(msg)) => {
+            println!("\nResult: security violation: {}", msg);
+            1
+        }
+        Err(err) => {
+            println!("\nResult: error: {}", err);
+            1
+        }
+    };
+
+    let _ = std::io::stdout().flush();
+    code
+}
+
+fn summary<M: Matcher>(report: &CrashReport, trace: &Trace<M>) -> String {
+    format!(
+        "# {name}\n\
+         \n\
+         * Status: {status:?}\n\
+         * Result: {result}\n\
+         * ASAN: {asan}\n\
+         * Steps: {steps}\n\
+         \n\
+         The trace is available as [trace.txt](./trace.txt) and [trace.svg](./trace.svg). The \
+         output of the replay is in [output.log](./output.log).\n",
+        name = report.name,
+        status = report.status,
+        result = report.result.as_deref().unwrap_or("none, the PUT crashed"),
+        asan = report.asan_summary.as_deref().unwrap_or("no report"),
+        steps = trace.steps.len(),
+    )
+}
+
+fn index(reports: &[CrashReport]) -> String {
+    let mut index = String::from("# Triage\n\n| Trace | Status | ASAN |\n| --- | --- | --- |\n");
+    for report in reports {
+        index.push_str(&format!(
+            "| [{name}](./{name}/README.md) | {status:?} | {asan} |\n",
+            name = report.name,
+            status = report.status,
+            asan = report.asan_summary.as_deref().unwrap_or("-"),
+        ));
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{index, CrashReport};
+    use crate::execution::ExecutionStatus;
+
+    #[test_log::test]
+    fn test_index() {
+        let reports = vec![CrashReport {
+            name: "20240101-000000000-0123456789abcdef".to_string(),
+            status: ExecutionStatus::Crashed,
+            asan_summary: Some("AddressSanitizer: heap-buffer-overflow".to_string()),
+            result: None,
+        }];
+
+        assert!(index(&reports).ends_with(
+            "| [20240101-000000000-0123456789abcdef](./20240101-000000000-0123456789abcdef/README.md) | Crashed | AddressSanitizer: heap-buffer-overflow |\n"
+        ));
+    }
+}
@@ -190,11 +190,8 @@ pub struct LibSSL {
 impl LibSSL {}
 
 impl Stream<SshQueryMatcher, SshMessage, RawSshMessage, RawSshMessageFlight> for LibSSL {
-    fn add_to_inbound(&mut self, result: &RawSshMessageFlight) {
-        let mut buffer = Vec::new();
-        Codec::encode(result, &mut buffer);
-
-        self.fuzz_stream.write_all(&buffer).unwrap();
+    fn add_inbound_flight(&mut self, encoded_flight: &[u8]) {
+        self.fuzz_stream.write_all(encoded_flight).unwrap();
     }
 
     fn take_message_from_outbound(&mut self) -> Result<Option<RawSshMessageFlight>, Error> {
@@ -1,3 +1,4 @@
+use puffin::process_put::{new_process_factory, PROCESS_PUT};
 use puffin::put_registry::PutRegistry;
 
 use crate::protocol::SshProtocolBehavior;
@@ -6,7 +7,11 @@ pub const LIBSSH_RUST_PUT: &str = "rust-put-libssh";
 
 pub fn ssh_registry() -> PutRegistry<SshProtocolBehavior> {
     PutRegistry::new(
-        [(LIBSSH_RUST_PUT, crate::libssh::new_libssh_factory())],
+        [
+            (LIBSSH_RUST_PUT, crate::libssh::new_libssh_factory()),
+            (PROCESS_PUT, new_process_factory()),
+        ],
         LIBSSH_RUST_PUT,
     )
+    .expect("the PUTs of the binary have distinct names")
 }
@@ -1,10 +1,11 @@
 use puffin::claims::SecurityViolationPolicy;
 
 use crate::claim::SshClaim;
+use crate::query::SshQueryMatcher;
 
 pub struct SshSecurityViolationPolicy;
 
-impl SecurityViolationPolicy<SshClaim> for SshSecurityViolationPolicy {
+impl SecurityViolationPolicy<SshClaim, SshQueryMatcher> for SshSecurityViolationPolicy {
     fn check_violation(_claims: &[SshClaim]) -> Option<&'static str> {
         None
     }
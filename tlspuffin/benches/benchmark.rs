@@ -1,6 +1,6 @@
 use std::any::Any;
 
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
 use puffin::algebra::dynamic_function::make_dynamic;
 use puffin::algebra::error::FnError;
 use puffin::algebra::Term;
@@ -69,47 +69,69 @@ fn benchmark_mutations(c: &mut Criterion) {
     });
 }
 
-fn benchmark_trace(c: &mut Criterion) {
-    let mut group = c.benchmark_group("trace");
-
-    group.bench_function("term clone", |b| {
-        let client_hello: Term<TlsQueryMatcher> = term! {
-              fn_client_hello(
-                fn_protocol_version12,
-                fn_new_random,
-                fn_new_session_id,
-                (fn_append_cipher_suite(
-                    (fn_new_cipher_suites()),
-                    // force TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256
-                    fn_cipher_suite12
-                )),
-                fn_compressions,
+fn client_hello() -> Term<TlsQueryMatcher> {
+    term! {
+          fn_client_hello(
+            fn_protocol_version12,
+            fn_new_random,
+            fn_new_session_id,
+            (fn_append_cipher_suite(
+                (fn_new_cipher_suites()),
+                // force TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256
+                fn_cipher_suite12
+            )),
+            fn_compressions,
+            (fn_client_extensions_append(
                 (fn_client_extensions_append(
                     (fn_client_extensions_append(
                         (fn_client_extensions_append(
                             (fn_client_extensions_append(
                                 (fn_client_extensions_append(
-                                    (fn_client_extensions_append(
-                                        fn_client_extensions_new,
-                                        (fn_support_group_extension(fn_named_group_secp384r1))
-                                    )),
-                                    fn_signature_algorithm_extension
+                                    fn_client_extensions_new,
+                                    (fn_support_group_extension(fn_named_group_secp384r1))
                                 )),
-                                fn_ec_point_formats_extension
+                                fn_signature_algorithm_extension
                             )),
-                            fn_signed_certificate_timestamp_extension
+                            fn_ec_point_formats_extension
                         )),
-                         // Enable Renegotiation
-                        (fn_renegotiation_info_extension(fn_empty_bytes_vec))
+                        fn_signed_certificate_timestamp_extension
                     )),
-                    // Add signature cert extension
-                    fn_signature_algorithm_cert_extension
-                ))
-            )
-        };
+                     // Enable Renegotiation
+                    (fn_renegotiation_info_extension(fn_empty_bytes_vec))
+                )),
+                // Add signature cert extension
+                fn_signature_algorithm_cert_extension
+            ))
+        )
+    }
+}
+
+fn benchmark_trace(c: &mut Criterion) {
+    let mut group = c.benchmark_group("trace");
 
+    group.bench_function("term clone", |b| {
+        let client_hello = client_hello();
         b.iter(|| client_hello.clone())
     });
+
+    let registry = tls_registry();
+    let runner = Runner::new(registry.clone(), Spawner::new(registry));
+
+    group.bench_function("term evaluate", |b| {
+        let client_hello = client_hello();
+        let ctx = runner.new_context();
+        b.iter(|| client_hello.evaluate(&ctx).unwrap())
+    });
+
+    // The argument pool of a new context is empty, such that every argument vector is allocated
+    group.bench_function("term evaluate without argument pool", |b| {
+        let client_hello = client_hello();
+        b.iter_batched_ref(
+            || runner.new_context(),
+            |ctx| client_hello.evaluate(ctx).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
 }
 
 fn benchmark_seeds(c: &mut Criterion) {
@@ -112,6 +112,15 @@ impl Drop for BoringSSL {
 }
 
 impl Stream<TlsQueryMatcher, Message, OpaqueMessage, OpaqueMessageFlight> for BoringSSL {
+    fn add_inbound_flight(&mut self, encoded_flight: &[u8]) {
+        <MemoryStream as Stream<
+            TlsQueryMatcher,
+            Message,
+            OpaqueMessage,
+            OpaqueMessageFlight,
+        >>::add_inbound_flight(self.stream.get_mut(), encoded_flight)
+    }
+
     fn add_to_inbound(&mut self, result: &OpaqueMessageFlight) {
         <MemoryStream as Stream<
             TlsQueryMatcher,
@@ -390,7 +399,7 @@ impl<T> From<Result<T, boring::ssl::Error>> for MaybeError {
                         // MemoryStream
                         MaybeError::Ok
                     }
-                    _ => MaybeError::Err(Error::IO(format!("Unexpected IO Error: {}", io_error))),
+                    _ => MaybeError::Err(Error::IO(io_error.into())),
                 }
             } else if let Some(ssl_error) = ssl_error.ssl_error() {
                 // OpenSSL threw an error, that means that there should be an Alert message in the
@@ -7,6 +7,8 @@ use puffin::claims::Claim;
 use puffin::variable_data::VariableData;
 use smallvec::SmallVec;
 
+use crate::tls::rustls::msgs::enums::CipherSuite;
+
 #[cfg(not(has_instr = "claimer"))]
 pub mod dummy_registration {
     #[no_mangle]
@@ -84,13 +86,20 @@ pub trait Transcript {
 }
 
 #[derive(Debug, Clone)]
-pub struct ClientHello;
+pub struct ClientHello {
+    pub outbound: bool,
+    /// Highest version which is offered by the client
+    pub version: Option<TLSVersion>,
+    pub available_ciphers: SmallVec<[u16; 20]>,
+}
 #[derive(Debug, Clone)]
 pub struct ServerHello;
 #[derive(Debug, Clone)]
 pub struct Certificate;
 #[derive(Debug, Clone)]
-pub struct CertificateVerify;
+pub struct CertificateVerify {
+    pub outbound: bool,
+}
 #[derive(Debug, Clone)]
 pub struct Finished {
     pub outbound: bool,
@@ -100,13 +109,19 @@ pub struct Finished {
     pub session_id: SmallVec<[u8; 32]>,
 
     pub authenticate_peer: bool,
+    /// Whether the agent is a server which requires the client to authenticate
+    pub client_authentication: bool,
     /// DER encoded certificate. DER works, because:
     ///     DER is a subset of BER providing for exactly one way to encode an ASN.1 value.
     ///     (<https://en.wikipedia.org/wiki/X.690#DER_encoding>)
     pub peer_certificate: SmallVec<[u8; 32]>,
 
     pub master_secret: SmallVec<[u8; 32]>,
+    /// Further secrets of the session, which must not be learned by the attacker either
+    pub secrets: SessionSecrets,
 
+    /// Version which has been negotiated in the handshake
+    pub negotiated_version: Option<TLSVersion>,
     pub chosen_cipher: u16,
     pub available_ciphers: SmallVec<[u16; 20]>,
 
@@ -138,6 +153,91 @@ pub struct Finished {
     */
 }
 
+impl Finished {
+    /// Returns the master secret and the further secrets of the session. Secrets which the PUT did
+    /// not report are empty or zero.
+    pub fn all_secrets(&self) -> [&[u8]; 6] {
+        [
+            self.master_secret.as_slice(),
+            self.secrets.handshake_secret.as_slice(),
+            self.secrets.client_app_traffic_secret.as_slice(),
+            self.secrets.server_app_traffic_secret.as_slice(),
+            self.secrets.resumption_master_secret.as_slice(),
+            self.secrets.exporter_master_secret.as_slice(),
+        ]
+    }
+
+    /// Returns the NSS key log entries of the secrets of the session. The handshake traffic
+    /// secrets of TLS 1.3 are not reported by the PUTs, therefore only the application data can
+    /// be decrypted.
+    fn key_log(&self, protocol_version: TLSVersion) -> Vec<String> {
+        if self.client_random.iter().all(|b| *b == 0) {
+            return vec![];
+        }
+
+        let client_random = hex::encode(&self.client_random);
+        let entry = |label: &str, secret: &[u8], length: usize| {
+            let secret = &secret[..length.min(secret.len())];
+            if secret.iter().all(|b| *b == 0) {
+                None
+            } else {
+                Some(format!(
+                    "{} {} {}",
+                    label,
+                    client_random,
+                    hex::encode(secret)
+                ))
+            }
+        };
+
+        match self.negotiated_version.unwrap_or(protocol_version) {
+            TLSVersion::V1_2 => entry("CLIENT_RANDOM", self.master_secret.as_slice(), 48)
+                .into_iter()
+                .collect(),
+            TLSVersion::V1_3 => {
+                // The secrets have the length of the hash of the cipher suite
+                let length =
+                    if self.chosen_cipher == CipherSuite::TLS13_AES_256_GCM_SHA384.get_u16() {
+                        48
+                    } else {
+                        32
+                    };
+
+                [
+                    entry(
+                        "CLIENT_TRAFFIC_SECRET_0",
+                        self.secrets.client_app_traffic_secret.as_slice(),
+                        length,
+                    ),
+                    entry(
+                        "SERVER_TRAFFIC_SECRET_0",
+                        self.secrets.server_app_traffic_secret.as_slice(),
+                        length,
+                    ),
+                    entry(
+                        "EXPORTER_SECRET",
+                        self.secrets.exporter_master_secret.as_slice(),
+                        length,
+                    ),
+                ]
+                .into_iter()
+                .flatten()
+                .collect()
+            }
+        }
+    }
+}
+
+/// The TLS 1.3 secrets of a session besides the master secret
+#[derive(Debug, Clone, Default)]
+pub struct SessionSecrets {
+    pub handshake_secret: SmallVec<[u8; 32]>,
+    pub client_app_traffic_secret: SmallVec<[u8; 32]>,
+    pub server_app_traffic_secret: SmallVec<[u8; 32]>,
+    pub resumption_master_secret: SmallVec<[u8; 32]>,
+    pub exporter_master_secret: SmallVec<[u8; 32]>,
+}
+
 #[derive(Debug, Clone)]
 pub enum ClaimDataTranscript {
     ClientHello(TranscriptClientHello),
@@ -220,6 +320,15 @@ impl Claim for TlsClaim {
             },
         }
     }
+
+    fn key_log(&self) -> Vec<String> {
+        match &self.data {
+            ClaimData::Message(ClaimDataMessage::Finished(finished)) => {
+                finished.key_log(self.protocol_version)
+            }
+            _ => vec![],
+        }
+    }
 }
 
 pub mod claims_helpers {
@@ -227,16 +336,19 @@ pub mod claims_helpers {
     use smallvec::SmallVec;
 
     use crate::claims::{
-        ClaimData, ClaimDataMessage, ClaimDataTranscript, Finished, TlsTranscript,
-        TranscriptCertificate, TranscriptClientFinished, TranscriptClientHello,
-        TranscriptPartialClientHello, TranscriptServerFinished, TranscriptServerHello,
+        CertificateVerify, ClaimData, ClaimDataMessage, ClaimDataTranscript, ClientHello, Finished,
+        SessionSecrets, TlsTranscript, TranscriptCertificate, TranscriptClientFinished,
+        TranscriptClientHello, TranscriptPartialClientHello, TranscriptServerFinished,
+        TranscriptServerHello,
     };
+    use crate::tls::rustls::msgs::enums::CipherSuite;
 
     pub fn to_claim_data(
         protocol_version: TLSVersion,
+        client_authentication: bool,
         claim: security_claims::Claim,
-    ) -> Option<ClaimData> {
-        match claim.typ {
+    ) -> Vec<ClaimData> {
+        let data = match claim.typ {
             // Transcripts
             security_claims::ClaimType::CLAIM_TRANSCRIPT_CH => Some(ClaimData::Transcript(
                 ClaimDataTranscript::ClientHello(TranscriptClientHello(TlsTranscript(
@@ -288,38 +400,68 @@ pub mod claims_helpers {
                     session_id: SmallVec::from_slice(
                         &claim.session_id.data[..claim.session_id.length as usize],
                     ),
-                    authenticate_peer: false,             // FIXME
+                    authenticate_peer: false, // FIXME
+                    client_authentication,
                     peer_certificate: Default::default(), // FIXME
                     master_secret: match protocol_version {
                         TLSVersion::V1_3 => SmallVec::from_slice(&claim.master_secret.secret),
                         TLSVersion::V1_2 => SmallVec::from_slice(&claim.master_secret_12.secret),
                     },
+                    secrets: match protocol_version {
+                        TLSVersion::V1_3 => SessionSecrets {
+                            handshake_secret: SmallVec::from_slice(&claim.handshake_secret.secret),
+                            client_app_traffic_secret: SmallVec::from_slice(
+                                &claim.client_app_traffic_secret.secret,
+                            ),
+                            server_app_traffic_secret: SmallVec::from_slice(
+                                &claim.server_app_traffic_secret.secret,
+                            ),
+                            resumption_master_secret: SmallVec::from_slice(
+                                &claim.resumption_master_secret.secret,
+                            ),
+                            exporter_master_secret: SmallVec::from_slice(
+                                &claim.exporter_master_secret.secret,
+                            ),
+                        },
+                        TLSVersion::V1_2 => SessionSecrets::default(),
+                    },
+                    negotiated_version: to_tls_version(claim.version),
                     chosen_cipher: claim.chosen_cipher.data,
-                    available_ciphers: SmallVec::from_iter(
-                        claim.available_ciphers.ciphers[..claim.available_ciphers.length as usize]
-                            .iter()
-                            .map(|cipher| cipher.data),
-                    ),
+                    available_ciphers: to_ciphers(&claim.available_ciphers),
                     signature_algorithm: claim.signature_algorithm,
                     peer_signature_algorithm: claim.peer_signature_algorithm,
                 })))
             }
-            security_claims::ClaimType::CLAIM_CLIENT_HELLO => None,
+            security_claims::ClaimType::CLAIM_CLIENT_HELLO => Some(ClaimData::Message(
+                ClaimDataMessage::ClientHello(ClientHello {
+                    outbound: claim.write > 0,
+                    version: to_tls_version(claim.version),
+                    available_ciphers: to_ciphers(&claim.available_ciphers),
+                }),
+            )),
             security_claims::ClaimType::CLAIM_CCS => None,
             security_claims::ClaimType::CLAIM_END_OF_EARLY_DATA => None,
             security_claims::ClaimType::CLAIM_CERTIFICATE => None,
             security_claims::ClaimType::CLAIM_KEY_EXCHANGE => None,
             // FIXME it is weird that this returns the correct transcript
             security_claims::ClaimType::CLAIM_CERTIFICATE_VERIFY => {
+                let certificate_verify =
+                    ClaimData::Message(ClaimDataMessage::CertificateVerify(CertificateVerify {
+                        outbound: claim.write > 0,
+                    }));
+
                 if claim.write == 0 {
-                    Some(ClaimData::Transcript(ClaimDataTranscript::ServerFinished(
-                        TranscriptServerFinished(TlsTranscript(
-                            claim.transcript.data,
-                            claim.transcript.length,
+                    return vec![
+                        certificate_verify,
+                        ClaimData::Transcript(ClaimDataTranscript::ServerFinished(
+                            TranscriptServerFinished(TlsTranscript(
+                                claim.transcript.data,
+                                claim.transcript.length,
+                            )),
                         )),
-                    )))
+                    ];
                 } else {
-                    None
+                    Some(certificate_verify)
                 }
             }
             security_claims::ClaimType::CLAIM_KEY_UPDATE => None,
@@ -332,6 +474,23 @@ pub mod claims_helpers {
             security_claims::ClaimType::CLAIM_EARLY_DATA => None,
             security_claims::ClaimType::CLAIM_ENCRYPTED_EXTENSIONS => None,
             _ => None,
+        };
+
+        data.into_iter().collect()
+    }
+
+    fn to_tls_version(version: security_claims::ClaimVersion) -> Option<TLSVersion> {
+        match version.data {
+            security_claims::ClaimTLSVersion::CLAIM_TLS_VERSION_V1_2 => Some(TLSVersion::V1_2),
+            security_claims::ClaimTLSVersion::CLAIM_TLS_VERSION_V1_3 => Some(TLSVersion::V1_3),
+            security_claims::ClaimTLSVersion::CLAIM_TLS_VERSION_UNDEFINED => None,
         }
     }
+
+    fn to_ciphers(ciphers: &security_claims::ClaimCiphers) -> SmallVec<[u16; 20]> {
+        ciphers.ciphers[..ciphers.length as usize]
+            .iter()
+            .map(|cipher| cipher.data)
+            .collect()
+    }
 }
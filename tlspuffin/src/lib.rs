@@ -138,3 +138,7 @@ pub mod tls;
 
 #[cfg(feature = "test-utils")]
 pub mod test_utils;
+
+// Entry point for libFuzzer and AFL++, see [`puffin::libfuzzer`]
+#[cfg(feature = "libfuzzer")]
+puffin::libfuzzer_target!(protocol::TLSProtocolBehavior, put_registry::tls_registry());
@@ -1,16 +1,85 @@
+use std::ffi::c_void;
+
 use foreign_types_openssl::ForeignTypeRef;
-use libc::c_int;
-use openssl::ssl::SslRef;
-use openssl_sys::SSL;
+use libc::{c_int, c_long};
+use openssl::ssl::{SslContextRef, SslRef};
+use openssl_sys::{SSL_CTX_ctrl, SSL, SSL_CTX};
 
 extern "C" {
     fn SSL_clear(ssl: *mut SSL) -> c_int;
+    fn SSL_renegotiate(ssl: *mut SSL) -> c_int;
+    fn SSL_CTX_flush_sessions(ctx: *mut SSL_CTX, tm: c_long);
+    fn put_rng_state(buffer: *mut u8, length: libc::size_t) -> libc::size_t;
 }
 
+const SSL_CTRL_SET_TLSEXT_TICKET_KEYS: c_int = 59;
+
+/// Length of the session ticket keys, i.e. the key name, the HMAC secret and the AES key
+#[cfg(feature = "openssl111-binding")]
+pub const TICKET_KEYS_LEN: usize = 80;
+#[cfg(not(feature = "openssl111-binding"))]
+pub const TICKET_KEYS_LEN: usize = 48;
+
+#[cfg(feature = "openssl111-binding")]
+extern "C" {
+    fn SSL_key_update(ssl: *mut SSL, updatetype: c_int) -> c_int;
+}
+
+#[cfg(feature = "openssl111-binding")]
+const SSL_KEY_UPDATE_NOT_REQUESTED: c_int = 0;
+#[cfg(feature = "openssl111-binding")]
+const SSL_KEY_UPDATE_REQUESTED: c_int = 1;
+
 pub fn clear(ssl: &SslRef) -> u32 {
     unsafe { SSL_clear(ssl.as_ptr()) as u32 }
 }
 
+/// Removes all sessions from the session cache of `ctx`.
+pub fn flush_sessions(ctx: &SslContextRef) {
+    // A time of 0 flushes the sessions regardless of their expiry
+    unsafe { SSL_CTX_flush_sessions(ctx.as_ptr(), 0) }
+}
+
+/// Sets the keys which encrypt and authenticate the session tickets issued by `ctx`. Returns
+/// whether the keys have been set.
+pub fn set_ticket_keys(ctx: &SslContextRef, keys: &[u8; TICKET_KEYS_LEN]) -> bool {
+    unsafe {
+        SSL_CTX_ctrl(
+            ctx.as_ptr(),
+            SSL_CTRL_SET_TLSEXT_TICKET_KEYS,
+            TICKET_KEYS_LEN as c_long,
+            keys.as_ptr() as *mut c_void,
+        ) == 1
+    }
+}
+
+/// Returns the state of the deterministic RNG, which can be restored with
+/// [`rng_reseed_with`](crate::rand::rng_reseed_with). Returns `None` if the default PRNG of
+/// OpenSSL is used.
+pub fn rng_state() -> Option<[u8; 8]> {
+    let mut state = [0; 8];
+    let length = unsafe { put_rng_state(state.as_mut_ptr(), state.len()) };
+    (length == state.len()).then_some(state)
+}
+
+/// Schedules a renegotiation, which is started by the next handshake call. Returns whether the
+/// renegotiation has been scheduled.
+pub fn renegotiate(ssl: &SslRef) -> bool {
+    unsafe { SSL_renegotiate(ssl.as_ptr()) == 1 }
+}
+
+/// Schedules a KeyUpdate message, which is sent by the next handshake call. Returns whether the
+/// update has been scheduled.
+#[cfg(feature = "openssl111-binding")]
+pub fn key_update(ssl: &SslRef, update_requested: bool) -> bool {
+    let updatetype = if update_requested {
+        SSL_KEY_UPDATE_REQUESTED
+    } else {
+        SSL_KEY_UPDATE_NOT_REQUESTED
+    };
+    unsafe { SSL_key_update(ssl.as_ptr(), updatetype) == 1 }
+}
+
 mod version_specific_bindings {
     #[cfg(all(
         any(feature = "openssl101-binding", feature = "openssl102-binding"),
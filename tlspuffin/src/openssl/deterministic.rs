@@ -1,5 +1,12 @@
 #[cfg(test)]
 mod tests {
+    use puffin::agent::{AgentDescriptor, AgentName, TLSVersion};
+    use puffin::claims::GlobalClaimList;
+    use puffin::put::PutOptions;
+
+    use crate::openssl::OpenSSL;
+    use crate::put::TlsPutConfig;
+
     #[test_log::test]
     fn test_openssl_rng_reseed_with_default_seed_has_not_changed() {
         crate::rand::rng_init();
@@ -10,6 +17,32 @@ mod tests {
         assert_eq!(bytes, [183, 96]);
     }
 
+    #[test_log::test]
+    fn test_openssl_context_creation_does_not_consume_randomness() {
+        crate::rand::rng_init();
+        crate::rand::rng_reseed();
+
+        let options = PutOptions::default();
+        let config = TlsPutConfig::new(
+            &AgentDescriptor::new_server(AgentName::first(), TLSVersion::V1_3),
+            &GlobalClaimList::new(),
+            &options,
+        );
+
+        // The first call creates the context, the second one reuses it
+        let mut created = [0; 2];
+        crate::rand::rng_reseed();
+        OpenSSL::context(&config, &options, true).unwrap();
+        openssl::rand::rand_bytes(&mut created).unwrap();
+
+        let mut reused = [0; 2];
+        crate::rand::rng_reseed();
+        OpenSSL::context(&config, &options, true).unwrap();
+        openssl::rand::rand_bytes(&mut reused).unwrap();
+
+        assert_eq!(created, reused);
+    }
+
     #[test_log::test]
     fn test_openssl_rng_reseed_with_same_seed_are_identical() {
         const SEED: [u8; 8] = 789u64.to_le().to_ne_bytes();
@@ -1,7 +1,11 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::io::ErrorKind;
 
 use openssl::error::ErrorStack;
-use openssl::ssl::{Ssl, SslContext, SslContextRef, SslMethod, SslStream, SslVerifyMode};
+use openssl::ssl::{
+    NameType, Ssl, SslContext, SslContextRef, SslMethod, SslSessionRef, SslStream, SslVerifyMode,
+};
 use openssl::x509::store::X509StoreBuilder;
 use openssl::x509::X509;
 use puffin::agent::{AgentDescriptor, AgentName, AgentType};
@@ -13,7 +17,7 @@ use puffin::put_registry::{Factory, PutKind};
 use puffin::stream::{MemoryStream, Stream};
 use puffin::VERSION_STR;
 
-use crate::openssl::util::{set_max_protocol_version, static_rsa_cert};
+use crate::openssl::util::{apply_put_options, set_max_protocol_version, static_rsa_cert};
 use crate::protocol::{OpaqueMessageFlight, TLSProtocolBehavior};
 use crate::put::TlsPutConfig;
 use crate::put_registry::OPENSSL_RUST_PUT;
@@ -25,6 +29,17 @@ mod bindings;
 mod deterministic;
 mod util;
 
+/// Application protocols which the server selects by default, in wire format
+#[cfg(not(feature = "openssl101-binding"))]
+const DEFAULT_ALPN_PROTOCOLS: &[u8] = b"\x02h2\x08http/1.1";
+
+thread_local! {
+    /// The contexts of the agents, which outlive the executions in persistent mode, see
+    /// [`OpenSSL::context`]
+    static CONTEXTS: RefCell<HashMap<(AgentDescriptor, PutOptions), SslContext>> =
+        RefCell::new(HashMap::new());
+}
+
 pub fn new_factory(preset: impl Into<String>) -> Box<dyn Factory<TLSProtocolBehavior>> {
     #[derive(Debug, Clone)]
     struct OpenSSLFactory {
@@ -39,10 +54,18 @@ pub fn new_factory(preset: impl Into<String>) -> Box<dyn Factory<TLSProtocolBeha
             options: &PutOptions,
         ) -> Result<Box<dyn Put<TLSProtocolBehavior>>, Error> {
             let config = TlsPutConfig::new(agent_descriptor, claims, options);
-
-            Ok(Box::new(OpenSSL::new(config).map_err(|err| {
-                Error::Put(format!("Failed to create client/server: {}", err))
-            })?))
+            let persistent = options
+                .get_option("persistent")
+                .map(|value| value.parse().unwrap_or(true))
+                .unwrap_or(true);
+
+            Ok(Box::new(
+                OpenSSL::context(&config, options, persistent)
+                    .and_then(|ctx| OpenSSL::new(config, ctx))
+                    .map_err(|err| {
+                        Error::Put(format!("Failed to create client/server: {}", err))
+                    })?,
+            ))
         }
 
         fn kind(&self) -> PutKind {
@@ -69,6 +92,14 @@ pub fn new_factory(preset: impl Into<String>) -> Box<dyn Factory<TLSProtocolBeha
         fn rng_reseed(&self) {
             log::debug!("[RNG] reseed ({})", self.name());
             crate::rand::rng_reseed();
+
+            // The factory is reseeded before each execution. Sessions of a previous execution
+            // must not be resumed by the persistent contexts.
+            CONTEXTS.with(|contexts| {
+                for ctx in contexts.borrow().values() {
+                    bindings::flush_sessions(ctx);
+                }
+            });
         }
 
         fn clone_factory(&self) -> Box<dyn Factory<TLSProtocolBehavior>> {
@@ -88,6 +119,9 @@ pub struct OpenSSL {
     stream: SslStream<MemoryStream>,
     ctx: SslContext,
     config: TlsPutConfig,
+    /// Whether the server still reads early data, see [`OpenSSL::accepts_early_data`]
+    #[cfg(feature = "openssl111-binding")]
+    reading_early_data: bool,
 }
 
 impl Drop for OpenSSL {
@@ -97,6 +131,15 @@ impl Drop for OpenSSL {
 }
 
 impl Stream<TlsQueryMatcher, Message, OpaqueMessage, OpaqueMessageFlight> for OpenSSL {
+    fn add_inbound_flight(&mut self, encoded_flight: &[u8]) {
+        <MemoryStream as Stream<
+            TlsQueryMatcher,
+            Message,
+            OpaqueMessage,
+            OpaqueMessageFlight,
+        >>::add_inbound_flight(self.stream.get_mut(), encoded_flight)
+    }
+
     fn add_to_inbound(&mut self, result: &OpaqueMessageFlight) {
         <MemoryStream as Stream<
             TlsQueryMatcher,
@@ -116,6 +159,19 @@ impl Stream<TlsQueryMatcher, Message, OpaqueMessage, OpaqueMessageFlight> for Op
 
 impl Put<TLSProtocolBehavior> for OpenSSL {
     fn progress(&mut self) -> Result<(), Error> {
+        #[cfg(feature = "openssl111-binding")]
+        while self.reading_early_data {
+            let mut vec: Vec<u8> = Vec::from([1; 128]);
+            match self.stream.read_early_data(&mut vec) {
+                Ok(0) => self.reading_early_data = false,
+                Ok(_) => continue,
+                Err(err) => {
+                    let maybe_error: MaybeError = Err::<(), _>(err).into();
+                    return maybe_error.into();
+                }
+            }
+        }
+
         let result = if self.is_state_successful() {
             // Trigger another read
             let mut vec: Vec<u8> = Vec::from([1; 128]);
@@ -137,9 +193,21 @@ impl Put<TLSProtocolBehavior> for OpenSSL {
         if self.config.use_clear {
             bindings::clear(self.stream.ssl());
         } else {
-            self.stream = Self::new_stream(&self.ctx, &self.config).map_err(|err| {
-                Error::Put(format!("OpenSSL error during stream creation: {}", err))
-            })?;
+            // Clients keep their session, such that the next handshake resumes it
+            let session = match self.config.descriptor.typ {
+                AgentType::Client => self.stream.ssl().session().map(ToOwned::to_owned),
+                AgentType::Server => None,
+            };
+
+            self.stream =
+                Self::new_stream(&self.ctx, &self.config, session.as_deref()).map_err(|err| {
+                    Error::Put(format!("OpenSSL error during stream creation: {}", err))
+                })?;
+        }
+
+        #[cfg(feature = "openssl111-binding")]
+        {
+            self.reading_early_data = Self::accepts_early_data(&self.config);
         }
 
         self.register_claimer();
@@ -147,6 +215,29 @@ impl Put<TLSProtocolBehavior> for OpenSSL {
         Ok(())
     }
 
+    fn renegotiate(&mut self) -> Result<(), Error> {
+        if !bindings::renegotiate(self.stream.ssl()) {
+            return Err(Error::Put(
+                "OpenSSL refused to renegotiate the session".to_string(),
+            ));
+        }
+
+        let maybe_error: MaybeError = self.stream.do_handshake().into();
+        maybe_error.into()
+    }
+
+    #[cfg(feature = "openssl111-binding")]
+    fn key_update(&mut self, update_requested: bool) -> Result<(), Error> {
+        if !bindings::key_update(self.stream.ssl(), update_requested) {
+            return Err(Error::Put(
+                "OpenSSL refused to update the keys of the session".to_string(),
+            ));
+        }
+
+        let maybe_error: MaybeError = self.stream.do_handshake().into();
+        maybe_error.into()
+    }
+
     fn descriptor(&self) -> &AgentDescriptor {
         &self.config.descriptor
     }
@@ -175,18 +266,63 @@ impl Put<TLSProtocolBehavior> for OpenSSL {
 }
 
 impl OpenSSL {
-    fn new(config: TlsPutConfig) -> Result<OpenSSL, ErrorStack> {
-        let agent_descriptor = &config.descriptor;
-        #[allow(unused_mut)]
-        let mut ctx = match agent_descriptor.typ {
-            AgentType::Server => Self::create_server_ctx(agent_descriptor)?,
-            AgentType::Client => Self::create_client_ctx(agent_descriptor)?,
+    /// Returns the context of the agent described by `config`.
+    ///
+    /// Creating the context, and loading its certificates and keys, is expensive. In persistent
+    /// mode, the context is therefore created once per process for each agent and its options,
+    /// and only the `SSL` object and its memory BIOs are created per execution. The session cache
+    /// of the context is flushed before each execution. Contexts are not shared between agents,
+    /// such that an agent cannot resume the sessions of another.
+    ///
+    /// Creating a context does not advance the RNG, such that executions which create a context
+    /// consume the same random numbers as executions which reuse it. Instead, the session ticket
+    /// keys of the agent are drawn from the RNG whenever the context is used.
+    fn context(
+        config: &TlsPutConfig,
+        options: &PutOptions,
+        persistent: bool,
+    ) -> Result<SslContext, ErrorStack> {
+        let create = || match config.descriptor.typ {
+            AgentType::Server => Self::create_server_ctx(config),
+            AgentType::Client => Self::create_client_ctx(config),
+        };
+
+        if !persistent {
+            return create();
+        }
+
+        let key = (config.descriptor.clone(), options.clone());
+        let ctx = match CONTEXTS.with(|contexts| contexts.borrow().get(&key).cloned()) {
+            Some(ctx) => ctx,
+            None => {
+                let state = bindings::rng_state();
+                let ctx = create();
+                if let Some(state) = state {
+                    crate::rand::rng_reseed_with(&state);
+                }
+
+                let ctx = ctx?;
+                CONTEXTS.with(|contexts| contexts.borrow_mut().insert(key, ctx.clone()));
+                ctx
+            }
         };
 
-        let stream = Self::new_stream(&ctx, &config)?;
+        let mut ticket_keys = [0; bindings::TICKET_KEYS_LEN];
+        openssl::rand::rand_bytes(&mut ticket_keys)?;
+        if !bindings::set_ticket_keys(&ctx, &ticket_keys) {
+            return Err(ErrorStack::get());
+        }
+
+        Ok(ctx)
+    }
+
+    fn new(config: TlsPutConfig, ctx: SslContext) -> Result<OpenSSL, ErrorStack> {
+        let stream = Self::new_stream(&ctx, &config, None)?;
 
         #[allow(unused_mut)]
         let mut openssl = OpenSSL {
+            #[cfg(feature = "openssl111-binding")]
+            reading_early_data: Self::accepts_early_data(&config),
             config,
             ctx,
             stream,
@@ -197,19 +333,35 @@ impl OpenSSL {
         Ok(openssl)
     }
 
+    /// OpenSSL only accepts early data if the server reads it with `SSL_read_early_data` before
+    /// the handshake is continued otherwise. This is done by servers which are configured to
+    /// accept early data, until all early data has been read or it was rejected.
+    #[cfg(feature = "openssl111-binding")]
+    fn accepts_early_data(config: &TlsPutConfig) -> bool {
+        config.descriptor.typ == AgentType::Server
+            && config.max_early_data.map_or(false, |bytes| bytes > 0)
+    }
+
     fn new_stream(
         ctx: &SslContextRef,
         config: &TlsPutConfig,
+        session: Option<&SslSessionRef>,
     ) -> Result<SslStream<MemoryStream>, ErrorStack> {
-        let ssl = match config.descriptor.typ {
+        let mut ssl = match config.descriptor.typ {
             AgentType::Server => Self::create_server(ctx)?,
             AgentType::Client => Self::create_client(ctx)?,
         };
 
+        if let Some(session) = session {
+            // SAFETY: the session has been established with the same context
+            unsafe { ssl.set_session(session)? };
+        }
+
         Ok(SslStream::new(ssl, MemoryStream::new())?)
     }
 
-    fn create_server_ctx(descriptor: &AgentDescriptor) -> Result<SslContext, ErrorStack> {
+    fn create_server_ctx(config: &TlsPutConfig) -> Result<SslContext, ErrorStack> {
+        let descriptor = &config.descriptor;
         let mut ctx_builder = SslContext::builder(SslMethod::tls())?;
 
         let (cert, key) = static_rsa_cert(ALICE_PRIVATE_KEY.0.as_bytes(), ALICE_CERT.0.as_bytes())?;
@@ -248,6 +400,23 @@ impl OpenSSL {
         // Allow EXPORT in server
         ctx_builder.set_cipher_list("ALL:EXPORT:!LOW:!aNULL:!eNULL:!SSLv2")?;
 
+        // Exercise the extension callbacks, like applications which embed OpenSSL do
+        ctx_builder.set_servername_callback(|ssl, _alert| {
+            if let Some(server_name) = ssl.servername_raw(NameType::HOST_NAME) {
+                log::debug!("Client requested server name {:?}", server_name);
+            }
+            Ok(())
+        });
+
+        #[cfg(not(feature = "openssl101-binding"))]
+        ctx_builder.set_alpn_select_callback(|_, client| {
+            openssl::ssl::select_next_proto(DEFAULT_ALPN_PROTOCOLS, client)
+                .ok_or(openssl::ssl::AlpnError::NOACK)
+        });
+
+        // Replaces the default ALPN selection if protocols are configured
+        apply_put_options(&mut ctx_builder, config)?;
+
         Ok(ctx_builder.build())
     }
 
@@ -258,7 +427,8 @@ impl OpenSSL {
         Ok(ssl)
     }
 
-    fn create_client_ctx(descriptor: &AgentDescriptor) -> Result<SslContext, ErrorStack> {
+    fn create_client_ctx(config: &TlsPutConfig) -> Result<SslContext, ErrorStack> {
+        let descriptor = &config.descriptor;
         let mut ctx_builder = SslContext::builder(SslMethod::tls())?;
         // Not sure whether we want this disabled or enabled: https://github.com/tlspuffin/tlspuffin/issues/67
         // The tests become simpler if disabled to maybe that's what we want. Lets leave it default
@@ -293,6 +463,8 @@ impl OpenSSL {
             ctx_builder.set_verify(SslVerifyMode::NONE);
         }
 
+        apply_put_options(&mut ctx_builder, config)?;
+
         Ok(ctx_builder.build())
     }
 
@@ -313,11 +485,17 @@ impl OpenSSL {
             let claims = self.config.claims.clone();
             let protocol_version = self.config.descriptor.tls_version;
             let origin = self.config.descriptor.typ;
+            let client_authentication =
+                origin == AgentType::Server && self.config.descriptor.client_authentication;
 
             security_claims::register_claimer(
                 self.stream.ssl().as_ptr().cast(),
                 move |claim: security_claims::Claim| {
-                    if let Some(data) = claims_helpers::to_claim_data(protocol_version, claim) {
+                    for data in claims_helpers::to_claim_data(
+                        protocol_version,
+                        client_authentication,
+                        claim,
+                    ) {
                         claims
                             .deref_borrow_mut()
                             .claim_sized(crate::claims::TlsClaim {
@@ -355,7 +533,7 @@ impl<T> From<Result<T, openssl::ssl::Error>> for MaybeError {
                         // MemoryStream
                         MaybeError::Ok
                     }
-                    _ => MaybeError::Err(Error::IO(format!("Unexpected IO Error: {}", io_error))),
+                    _ => MaybeError::Err(Error::IO(io_error.into())),
                 }
             } else if let Some(ssl_error) = ssl_error.ssl_error() {
                 // OpenSSL threw an error, that means that there should be an Alert message in the
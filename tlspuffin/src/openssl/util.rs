@@ -4,6 +4,8 @@ use openssl::ssl::SslContextBuilder;
 use openssl::x509::X509;
 use puffin::agent::TLSVersion;
 
+use crate::put::TlsPutConfig;
+
 pub fn static_rsa_cert(key: &[u8], cert: &[u8]) -> Result<(X509, PKey<Private>), ErrorStack> {
     let rsa = openssl::rsa::Rsa::private_key_from_pem(key)?;
     let pkey = PKey::from_rsa(rsa)?;
@@ -33,3 +35,98 @@ pub fn set_max_protocol_version(
 
     Ok(())
 }
+
+/// Restricts the cipher suites, named groups, ALPN protocols and protocol versions which the
+/// context negotiates and sets the number of issued tickets and the accepted amount of early data,
+/// according to the options of `config`.
+/// Options which the bound OpenSSL version does not support are skipped.
+#[allow(unused_variables)]
+pub fn apply_put_options(
+    ctx_builder: &mut SslContextBuilder,
+    config: &TlsPutConfig,
+) -> Result<(), ErrorStack> {
+    if let Some(cipher_list) = &config.cipher_list {
+        ctx_builder.set_cipher_list(cipher_list)?;
+    }
+
+    #[cfg(feature = "openssl111-binding")]
+    if let Some(cipher_suites) = &config.cipher_suites {
+        ctx_builder.set_ciphersuites(cipher_suites)?;
+    }
+
+    #[cfg(any(feature = "openssl111-binding", feature = "libressl333"))]
+    {
+        if let Some(groups) = &config.groups {
+            ctx_builder.set_groups_list(groups)?;
+        }
+
+        if let Some(version) = config.min_version {
+            match ssl_version(version) {
+                Some(version) => ctx_builder.set_min_proto_version(Some(version))?,
+                None => log::warn!("Unsupported minimum protocol version {:?}", version),
+            }
+        }
+
+        if let Some(version) = config.max_version {
+            match ssl_version(version) {
+                Some(version) => ctx_builder.set_max_proto_version(Some(version))?,
+                None => log::warn!("Unsupported maximum protocol version {:?}", version),
+            }
+        }
+    }
+
+    if let Some(num_tickets) = config.num_tickets {
+        // TLS 1.2 servers issue at most one ticket, which can only be disabled
+        if num_tickets == 0 {
+            ctx_builder.set_options(openssl::ssl::SslOptions::NO_TICKET);
+        }
+
+        #[cfg(feature = "openssl111-binding")]
+        ctx_builder.set_num_tickets(num_tickets)?;
+    }
+
+    #[cfg(feature = "openssl111-binding")]
+    if let Some(max_early_data) = config.max_early_data {
+        ctx_builder.set_max_early_data(max_early_data)?;
+    }
+
+    #[cfg(not(feature = "openssl101-binding"))]
+    if !config.alpn.is_empty() {
+        use puffin::agent::AgentType;
+
+        // Protocols are encoded as a list of length-prefixed names
+        let protocols = config
+            .alpn
+            .iter()
+            .flat_map(|protocol| std::iter::once(protocol.len() as u8).chain(protocol.bytes()))
+            .collect::<Vec<u8>>();
+
+        match config.descriptor.typ {
+            AgentType::Client => ctx_builder.set_alpn_protos(&protocols)?,
+            AgentType::Server => ctx_builder.set_alpn_select_callback(move |_, client| {
+                openssl::ssl::select_next_proto(&protocols, client)
+                    .ok_or(openssl::ssl::AlpnError::NOACK)
+            }),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(any(feature = "openssl111-binding", feature = "libressl333"))]
+fn ssl_version(
+    version: crate::tls::rustls::msgs::enums::ProtocolVersion,
+) -> Option<openssl::ssl::SslVersion> {
+    use openssl::ssl::SslVersion;
+
+    use crate::tls::rustls::msgs::enums::ProtocolVersion;
+
+    match version {
+        ProtocolVersion::TLSv1_0 => Some(SslVersion::TLS1),
+        ProtocolVersion::TLSv1_1 => Some(SslVersion::TLS1_1),
+        ProtocolVersion::TLSv1_2 => Some(SslVersion::TLS1_2),
+        #[cfg(feature = "openssl111-binding")]
+        ProtocolVersion::TLSv1_3 => Some(SslVersion::TLS1_3),
+        _ => None,
+    }
+}
@@ -11,13 +11,15 @@ use puffin::trace::{Knowledge, Source, Trace};
 use crate::claims::TlsClaim;
 use crate::debug::{debug_message_with_info, debug_opaque_message_with_info};
 use crate::query::TlsQueryMatcher;
+use crate::tls::import::import_client_data;
 use crate::tls::rustls::msgs::alert::AlertMessagePayload;
 use crate::tls::rustls::msgs::base::Payload;
 use crate::tls::rustls::msgs::ccs::ChangeCipherSpecPayload;
 use crate::tls::rustls::msgs::deframer::MessageDeframer;
 use crate::tls::rustls::msgs::handshake::{
     CertificatePayload, ClientHelloPayload, ECDHEServerKeyExchange, HandshakeMessagePayload,
-    HandshakePayload, NewSessionTicketPayload, ServerHelloPayload, ServerKeyExchangePayload,
+    HandshakePayload, HelloRetryRequest, NewSessionTicketPayload, NewSessionTicketPayloadTLS13,
+    ServerHelloPayload, ServerKeyExchangePayload,
 };
 use crate::tls::rustls::msgs::heartbeat::HeartbeatPayload;
 use crate::tls::rustls::msgs::message::{Message, MessagePayload, OpaqueMessage};
@@ -343,7 +345,10 @@ impl ExtractKnowledge<TlsQueryMatcher> for HandshakePayload {
             data: self,
         });
         match &self {
-            HandshakePayload::HelloRequest | HandshakePayload::HelloRetryRequest(_) => {}
+            HandshakePayload::HelloRequest => {}
+            HandshakePayload::HelloRetryRequest(hrr) => {
+                hrr.extract_knowledge(knowledges, matcher, source)?;
+            }
             HandshakePayload::ClientHello(ch) => {
                 ch.extract_knowledge(knowledges, matcher, source)?;
             }
@@ -363,6 +368,9 @@ impl ExtractKnowledge<TlsQueryMatcher> for HandshakePayload {
             HandshakePayload::NewSessionTicket(ticket) => {
                 ticket.extract_knowledge(knowledges, matcher, source)?;
             }
+            HandshakePayload::NewSessionTicketTLS13(ticket) => {
+                ticket.extract_knowledge(knowledges, matcher, source)?;
+            }
             _ => {
                 log::error!("failed extraction: {self:?}");
                 return Err(Error::Extraction());
@@ -389,6 +397,13 @@ impl ExtractKnowledge<TlsQueryMatcher> for CertificatePayload {
             matcher,
             data: &self.0,
         });
+        for cert in &self.0 {
+            knowledges.push(Knowledge {
+                source,
+                matcher,
+                data: cert,
+            });
+        }
         Ok(())
     }
 }
@@ -548,6 +563,32 @@ impl ExtractKnowledge<TlsQueryMatcher> for NewSessionTicketPayload {
     }
 }
 
+impl ExtractKnowledge<TlsQueryMatcher> for NewSessionTicketPayloadTLS13 {
+    fn extract_knowledge<'a>(
+        &'a self,
+        knowledges: &mut Vec<Knowledge<'a, TlsQueryMatcher>>,
+        matcher: Option<TlsQueryMatcher>,
+        source: &'a Source,
+    ) -> Result<(), Error> {
+        knowledges.push(Knowledge {
+            source,
+            matcher,
+            data: self,
+        });
+        knowledges.push(Knowledge {
+            source,
+            matcher,
+            data: &self.lifetime,
+        });
+        knowledges.push(Knowledge {
+            source,
+            matcher,
+            data: &self.ticket.0,
+        });
+        Ok(())
+    }
+}
+
 impl ExtractKnowledge<TlsQueryMatcher> for ServerHelloPayload {
     fn extract_knowledge<'a>(
         &'a self,
@@ -599,6 +640,42 @@ impl ExtractKnowledge<TlsQueryMatcher> for ServerHelloPayload {
     }
 }
 
+impl ExtractKnowledge<TlsQueryMatcher> for HelloRetryRequest {
+    fn extract_knowledge<'a>(
+        &'a self,
+        knowledges: &mut Vec<Knowledge<'a, TlsQueryMatcher>>,
+        matcher: Option<TlsQueryMatcher>,
+        source: &'a Source,
+    ) -> Result<(), Error> {
+        knowledges.push(Knowledge {
+            source,
+            matcher,
+            data: self,
+        });
+        knowledges.push(Knowledge {
+            source,
+            matcher,
+            data: &self.session_id,
+        });
+        knowledges.push(Knowledge {
+            source,
+            matcher,
+            data: &self.cipher_suite,
+        });
+        knowledges.push(Knowledge {
+            source,
+            matcher,
+            data: &self.extensions,
+        });
+        knowledges.extend(self.extensions.iter().map(|extension| Knowledge {
+            source,
+            matcher,
+            data: extension,
+        }));
+        Ok(())
+    }
+}
+
 impl ProtocolMessageDeframer<TlsQueryMatcher> for MessageDeframer {
     type OpaqueProtocolMessage = OpaqueMessage;
 
@@ -662,4 +739,8 @@ impl ProtocolBehavior for TLSProtocolBehavior {
     fn create_corpus() -> Vec<(Trace<Self::Matcher>, &'static str)> {
         create_corpus()
     }
+
+    fn import_trace(client_data: &[u8]) -> Result<Trace<Self::Matcher>, String> {
+        import_client_data(client_data)
+    }
 }
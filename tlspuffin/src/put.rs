@@ -9,8 +9,23 @@ use puffin::put::PutOptions;
 
 use crate::claims::TlsClaim;
 use crate::protocol::TLSProtocolBehavior;
+use crate::tls::rustls::msgs::enums::ProtocolVersion;
 
 /// Static configuration for creating a new agent state for the PUT
+///
+/// Besides `use_clear`, the [`PutOptions`] can restrict what an agent negotiates. Each option
+/// applies to all agents, unless it is given for a single agent as `key@agent`, see
+/// [`PutOptions::get_agent_option`]:
+///
+/// * `cipher_list`: cipher list for TLS 1.2 and below, in the format of the library
+/// * `cipher_suites`: cipher suites for TLS 1.3, in the format of the library
+/// * `groups`: named groups separated by colons, e.g. `P-384:X25519`
+/// * `alpn`: ALPN protocols separated by commas, e.g. `h2,http/1.1`
+/// * `min_version`, `max_version`: protocol versions from `1.0` to `1.3`
+/// * `max_early_data`: maximum number of bytes of 0-RTT data which a TLS 1.3 server accepts
+///
+/// Only the OpenSSL PUT honors these restrictions so far, and the wolfSSL PUT `max_early_data`.
+/// The other PUTs ignore them.
 #[derive(Clone)]
 pub struct TlsPutConfig {
     pub descriptor: AgentDescriptor,
@@ -18,6 +33,16 @@ pub struct TlsPutConfig {
     pub authenticate_peer: bool,
     pub extract_deferred: Rc<RefCell<Option<TypeShape>>>,
     pub use_clear: bool,
+    pub cipher_list: Option<String>,
+    pub cipher_suites: Option<String>,
+    pub groups: Option<String>,
+    pub alpn: Vec<String>,
+    pub min_version: Option<ProtocolVersion>,
+    pub max_version: Option<ProtocolVersion>,
+    /// Number of tickets which a server issues after a handshake. `0` disables tickets.
+    pub num_tickets: Option<usize>,
+    /// Early data is rejected unless this is set to a positive number of bytes
+    pub max_early_data: Option<u32>,
 }
 
 impl TlsPutConfig {
@@ -31,6 +56,20 @@ impl TlsPutConfig {
             .map(|value| value.parse().unwrap_or(false))
             .unwrap_or(false);
 
+        let name = agent_descriptor.name;
+        let option = |key| options.get_agent_option(name, key).map(ToString::to_string);
+        let version = |key| {
+            options
+                .get_agent_option(name, key)
+                .and_then(|value| match parse_version(value) {
+                    Some(version) => Some(version),
+                    None => {
+                        log::warn!("Ignoring unknown protocol version {}={}", key, value);
+                        None
+                    }
+                })
+        };
+
         TlsPutConfig {
             descriptor: agent_descriptor.clone(),
             claims: claims.clone(),
@@ -40,6 +79,91 @@ impl TlsPutConfig {
                     && agent_descriptor.client_authentication,
             extract_deferred: Rc::new(RefCell::new(None)),
             use_clear,
+            cipher_list: option("cipher_list"),
+            cipher_suites: option("cipher_suites"),
+            groups: option("groups"),
+            alpn: options
+                .get_agent_option(name, "alpn")
+                .map_or(vec![], |alpn| {
+                    alpn.split(',').map(ToString::to_string).collect()
+                }),
+            min_version: version("min_version"),
+            max_version: version("max_version"),
+            num_tickets: options.get_agent_option(name, "num_tickets").and_then(
+                |value| match value.parse() {
+                    Ok(num_tickets) => Some(num_tickets),
+                    Err(_) => {
+                        log::warn!("Ignoring invalid number of tickets num_tickets={}", value);
+                        None
+                    }
+                },
+            ),
+            max_early_data: options
+                .get_agent_option(name, "max_early_data")
+                .and_then(|value| match value.parse() {
+                    Ok(max_early_data) => Some(max_early_data),
+                    Err(_) => {
+                        log::warn!("Ignoring invalid size max_early_data={}", value);
+                        None
+                    }
+                }),
         }
     }
 }
+
+fn parse_version(version: &str) -> Option<ProtocolVersion> {
+    match version {
+        "1.0" => Some(ProtocolVersion::TLSv1_0),
+        "1.1" => Some(ProtocolVersion::TLSv1_1),
+        "1.2" => Some(ProtocolVersion::TLSv1_2),
+        "1.3" => Some(ProtocolVersion::TLSv1_3),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use puffin::agent::{AgentDescriptor, AgentName, TLSVersion};
+    use puffin::claims::GlobalClaimList;
+    use puffin::put::PutOptions;
+
+    use crate::put::TlsPutConfig;
+    use crate::tls::rustls::msgs::enums::ProtocolVersion;
+
+    #[test_log::test]
+    fn test_config_from_options() {
+        let client = AgentName::first();
+        let server = client.next();
+        let options = PutOptions::from(vec![
+            ("cipher_list", "AES128-SHA"),
+            ("alpn", "h2,http/1.1"),
+            ("min_version", "1.1"),
+            ("max_version", "2.0"),
+            (&format!("cipher_list@{}", server), "AES256-SHA"),
+            (&format!("num_tickets@{}", server), "4"),
+            (&format!("max_early_data@{}", server), "16384"),
+        ]);
+
+        let config = TlsPutConfig::new(
+            &AgentDescriptor::new_client(client, TLSVersion::V1_2),
+            &GlobalClaimList::new(),
+            &options,
+        );
+        assert_eq!(config.cipher_list.as_deref(), Some("AES128-SHA"));
+        assert_eq!(config.alpn, vec!["h2", "http/1.1"]);
+        assert_eq!(config.min_version, Some(ProtocolVersion::TLSv1_1));
+        assert_eq!(config.max_version, None);
+        assert_eq!(config.groups, None);
+        assert_eq!(config.num_tickets, None);
+        assert_eq!(config.max_early_data, None);
+
+        let config = TlsPutConfig::new(
+            &AgentDescriptor::new_server(server, TLSVersion::V1_2),
+            &GlobalClaimList::new(),
+            &options,
+        );
+        assert_eq!(config.cipher_list.as_deref(), Some("AES256-SHA"));
+        assert_eq!(config.num_tickets, Some(4));
+        assert_eq!(config.max_early_data, Some(16384));
+    }
+}
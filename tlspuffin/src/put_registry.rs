@@ -30,12 +30,13 @@ pub fn tls_registry() -> PutRegistry<TLSProtocolBehavior> {
         #[cfg(feature = "rust-put")]
         rust_put::new_factory(),
         crate::tcp::new_tcp_factory(),
+        puffin::process_put::new_process_factory(),
     ]
     .map(|f| (f.name(), f));
 
     let default = puts.first().unwrap().0.clone();
 
-    PutRegistry::new(puts, default)
+    PutRegistry::new(puts, default).expect("the PUTs of the binary have distinct names")
 }
 
 #[cfg(test)]
@@ -16,7 +16,8 @@ pub fn rng_reseed_with(buffer: &[u8]) {
     }
 }
 
+/// Reseeds with the seed which is derived from the master seed, or with
+/// [`DEFAULT_PUT_SEED`](puffin::rng::DEFAULT_PUT_SEED) if there is none.
 pub fn rng_reseed() {
-    const DEFAULT_SEED: [u8; 8] = 42u64.to_le().to_ne_bytes();
-    rng_reseed_with(&DEFAULT_SEED);
+    rng_reseed_with(&puffin::rng::put_seed().to_le_bytes());
 }
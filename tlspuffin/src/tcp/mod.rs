@@ -240,9 +240,8 @@ impl TcpPut for TcpServerPut {
 }
 
 impl Stream<TlsQueryMatcher, Message, OpaqueMessage, OpaqueMessageFlight> for TcpServerPut {
-    fn add_to_inbound(&mut self, opaque_flight: &OpaqueMessageFlight) {
-        self.write_to_stream(&opaque_flight.clone().get_encoding())
-            .unwrap();
+    fn add_inbound_flight(&mut self, encoded_flight: &[u8]) {
+        self.write_to_stream(encoded_flight).unwrap();
     }
 
     fn take_message_from_outbound(&mut self) -> Result<Option<OpaqueMessageFlight>, Error> {
@@ -251,9 +250,8 @@ impl Stream<TlsQueryMatcher, Message, OpaqueMessage, OpaqueMessageFlight> for Tc
 }
 
 impl Stream<TlsQueryMatcher, Message, OpaqueMessage, OpaqueMessageFlight> for TcpClientPut {
-    fn add_to_inbound(&mut self, opaque_flight: &OpaqueMessageFlight) {
-        self.write_to_stream(&opaque_flight.clone().get_encoding())
-            .unwrap();
+    fn add_inbound_flight(&mut self, encoded_flight: &[u8]) {
+        self.write_to_stream(encoded_flight).unwrap();
     }
 
     fn take_message_from_outbound(&mut self) -> Result<Option<OpaqueMessageFlight>, Error> {
@@ -0,0 +1,305 @@
+//! Minimal DER decoding and encoding of X.509 certificates.
+//!
+//! Only the structure which is required to manipulate the fields of a certificate is decoded. All
+//! other values are kept as their original encoding, such that re-encoding an unmodified
+//! certificate yields the same bytes.
+
+use puffin::algebra::error::FnError;
+
+use crate::tls::rustls::msgs::enums::SignatureScheme;
+
+pub const TAG_BIT_STRING: u8 = 0x03;
+pub const TAG_OCTET_STRING: u8 = 0x04;
+pub const TAG_NULL: u8 = 0x05;
+pub const TAG_OID: u8 = 0x06;
+pub const TAG_SEQUENCE: u8 = 0x30;
+pub const TAG_VERSION: u8 = 0xa0;
+pub const TAG_EXTENSIONS: u8 = 0xa3;
+
+/// Splits the first value of `input` into its tag, its contents and the remaining input.
+fn split(input: &[u8]) -> Result<(u8, &[u8], &[u8]), FnError> {
+    let malformed = || FnError::Unknown("Malformed DER value".to_string());
+
+    let (&tag, input) = input.split_first().ok_or_else(malformed)?;
+    let (&first, input) = input.split_first().ok_or_else(malformed)?;
+
+    let (length, input) = if first < 0x80 {
+        (first as usize, input)
+    } else {
+        let octets = (first & 0x7f) as usize;
+        if octets == 0 || octets > 4 || input.len() < octets {
+            return Err(malformed());
+        }
+        let (octets, input) = input.split_at(octets);
+        let length = octets
+            .iter()
+            .fold(0usize, |length, octet| (length << 8) | *octet as usize);
+        (length, input)
+    };
+
+    if input.len() < length {
+        return Err(malformed());
+    }
+    let (contents, rest) = input.split_at(length);
+    Ok((tag, contents, rest))
+}
+
+/// Returns the tag and the contents of the single DER value `element`.
+pub fn decode(element: &[u8]) -> Result<(u8, &[u8]), FnError> {
+    let (tag, contents, rest) = split(element)?;
+    if !rest.is_empty() {
+        return Err(FnError::Unknown(
+            "Trailing data after DER value".to_string(),
+        ));
+    }
+    Ok((tag, contents))
+}
+
+/// Splits the contents of a constructed value into its elements. Each element includes its tag
+/// and length.
+pub fn elements(mut contents: &[u8]) -> Result<Vec<&[u8]>, FnError> {
+    let mut elements = Vec::new();
+    while !contents.is_empty() {
+        let (_, _, rest) = split(contents)?;
+        elements.push(&contents[..contents.len() - rest.len()]);
+        contents = rest;
+    }
+    Ok(elements)
+}
+
+pub fn encode(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    let length = contents.len();
+    if length < 0x80 {
+        encoded.push(length as u8);
+    } else {
+        let octets = length.to_be_bytes();
+        let skip = octets.iter().take_while(|octet| **octet == 0).count();
+        encoded.push(0x80 | (octets.len() - skip) as u8);
+        encoded.extend_from_slice(&octets[skip..]);
+    }
+    encoded.extend_from_slice(contents);
+    encoded
+}
+
+/// Encodes a constructed value which consists of `elements`.
+pub fn encode_elements<E: AsRef<[u8]>>(tag: u8, elements: &[E]) -> Vec<u8> {
+    encode(
+        tag,
+        &elements
+            .iter()
+            .flat_map(AsRef::as_ref)
+            .copied()
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Encodes the object identifier `2.999.arc`, which is below the arc reserved for examples and
+/// therefore unknown to every implementation.
+pub fn unknown_oid(arc: u32) -> Vec<u8> {
+    // 2.999 is encoded as 40 * 2 + 999 in base 128
+    let mut contents = vec![0x88, 0x37];
+    let mut groups = vec![(arc & 0x7f) as u8];
+    let mut rest = arc >> 7;
+    while rest > 0 {
+        groups.push(0x80 | (rest & 0x7f) as u8);
+        rest >>= 7;
+    }
+    contents.extend(groups.iter().rev());
+    encode(TAG_OID, &contents)
+}
+
+/// Encodes the `AlgorithmIdentifier` which corresponds to `scheme`. Schemes without an
+/// identifier, like unknown schemes, are encoded as an unknown object identifier.
+pub fn algorithm_identifier(scheme: &SignatureScheme) -> Vec<u8> {
+    const PKCS1: [u8; 8] = [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01];
+    const ECDSA: [u8; 6] = [0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04];
+
+    let (oid, null_parameters) = match scheme {
+        SignatureScheme::RSA_PKCS1_SHA256 => {
+            (encode(TAG_OID, &[&PKCS1[..], &[0x0b]].concat()), true)
+        }
+        SignatureScheme::RSA_PKCS1_SHA384 => {
+            (encode(TAG_OID, &[&PKCS1[..], &[0x0c]].concat()), true)
+        }
+        SignatureScheme::RSA_PKCS1_SHA512 => {
+            (encode(TAG_OID, &[&PKCS1[..], &[0x0d]].concat()), true)
+        }
+        SignatureScheme::RSA_PSS_SHA256
+        | SignatureScheme::RSA_PSS_SHA384
+        | SignatureScheme::RSA_PSS_SHA512 => {
+            (encode(TAG_OID, &[&PKCS1[..], &[0x0a]].concat()), false)
+        }
+        SignatureScheme::ECDSA_NISTP256_SHA256 => (
+            encode(TAG_OID, &[&ECDSA[..], &[0x03, 0x02]].concat()),
+            false,
+        ),
+        SignatureScheme::ECDSA_NISTP384_SHA384 => (
+            encode(TAG_OID, &[&ECDSA[..], &[0x03, 0x03]].concat()),
+            false,
+        ),
+        SignatureScheme::ED25519 => (encode(TAG_OID, &[0x2b, 0x65, 0x70]), false),
+        other => (unknown_oid(other.get_u16() as u32), false),
+    };
+
+    if null_parameters {
+        encode_elements(TAG_SEQUENCE, &[oid, encode(TAG_NULL, &[])])
+    } else {
+        encode(TAG_SEQUENCE, &oid)
+    }
+}
+
+/// A decoded X.509 certificate. The elements include their tag and length.
+pub struct X509Certificate {
+    pub tbs_certificate: Vec<Vec<u8>>,
+    pub signature_algorithm: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl X509Certificate {
+    pub fn decode(der: &[u8]) -> Result<Self, FnError> {
+        let malformed = || FnError::Unknown("Malformed X.509 certificate".to_string());
+
+        let (tag, contents) = decode(der)?;
+        if tag != TAG_SEQUENCE {
+            return Err(malformed());
+        }
+
+        match elements(contents)?.as_slice() {
+            [tbs_certificate, signature_algorithm, signature] => {
+                let (tag, tbs_contents) = decode(tbs_certificate)?;
+                if tag != TAG_SEQUENCE {
+                    return Err(malformed());
+                }
+
+                let certificate = Self {
+                    tbs_certificate: elements(tbs_contents)?.into_iter().map(Vec::from).collect(),
+                    signature_algorithm: signature_algorithm.to_vec(),
+                    signature: signature.to_vec(),
+                };
+
+                if certificate.tbs_certificate.len() < certificate.subject_index() + 2 {
+                    return Err(malformed());
+                }
+                Ok(certificate)
+            }
+            _ => Err(malformed()),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        encode_elements(
+            TAG_SEQUENCE,
+            &[
+                self.encode_tbs_certificate(),
+                self.signature_algorithm.clone(),
+                self.signature.clone(),
+            ],
+        )
+    }
+
+    pub fn encode_tbs_certificate(&self) -> Vec<u8> {
+        encode_elements(TAG_SEQUENCE, &self.tbs_certificate)
+    }
+
+    /// The version is optional and shifts the other fields of the `TBSCertificate`.
+    fn version_offset(&self) -> usize {
+        match self.tbs_certificate.first() {
+            Some(version) if version.first() == Some(&TAG_VERSION) => 1,
+            _ => 0,
+        }
+    }
+
+    pub fn tbs_signature_index(&self) -> usize {
+        self.version_offset() + 1
+    }
+
+    pub fn issuer_index(&self) -> usize {
+        self.version_offset() + 2
+    }
+
+    pub fn subject_index(&self) -> usize {
+        self.version_offset() + 4
+    }
+
+    /// Appends the DER encoded `extension` to the extensions of the certificate. The extensions
+    /// are added if the certificate has none.
+    pub fn append_extension(&mut self, extension: &[u8]) -> Result<(), FnError> {
+        let position = self
+            .tbs_certificate
+            .iter()
+            .position(|element| element.first() == Some(&TAG_EXTENSIONS));
+
+        let mut extensions = match position {
+            Some(position) => {
+                let (_, explicit) = decode(&self.tbs_certificate[position])?;
+                let (_, extensions) = decode(explicit)?;
+                elements(extensions)?
+                    .into_iter()
+                    .map(Vec::from)
+                    .collect::<Vec<_>>()
+            }
+            None => vec![],
+        };
+        extensions.push(extension.to_vec());
+
+        let encoded = encode(TAG_EXTENSIONS, &encode_elements(TAG_SEQUENCE, &extensions));
+        match position {
+            Some(position) => self.tbs_certificate[position] = encoded,
+            None => self.tbs_certificate.push(encoded),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::static_certs::BOB_CERT;
+
+    #[test_log::test]
+    fn test_encode_length() {
+        assert_eq!(
+            encode(TAG_OCTET_STRING, &[1; 3])[..2],
+            [TAG_OCTET_STRING, 3]
+        );
+        assert_eq!(
+            encode(TAG_OCTET_STRING, &[1; 0x80])[..3],
+            [TAG_OCTET_STRING, 0x81, 0x80]
+        );
+        assert_eq!(
+            encode(TAG_OCTET_STRING, &[1; 0x1234])[..4],
+            [TAG_OCTET_STRING, 0x82, 0x12, 0x34]
+        );
+    }
+
+    #[test_log::test]
+    fn test_certificate_roundtrip() {
+        let certificate = X509Certificate::decode(BOB_CERT.1).unwrap();
+        assert_eq!(certificate.encode(), BOB_CERT.1);
+    }
+
+    #[test_log::test]
+    fn test_append_extension() {
+        let mut certificate = X509Certificate::decode(BOB_CERT.1).unwrap();
+        let count = certificate.tbs_certificate.len();
+        let extension = encode_elements(
+            TAG_SEQUENCE,
+            &[unknown_oid(1), encode(TAG_OCTET_STRING, &[0; 300])],
+        );
+
+        certificate.append_extension(&extension).unwrap();
+
+        let certificate = X509Certificate::decode(&certificate.encode()).unwrap();
+        assert_eq!(certificate.tbs_certificate.len(), count);
+        let (_, explicit) = decode(certificate.tbs_certificate.last().unwrap()).unwrap();
+        let (_, extensions) = decode(explicit).unwrap();
+        assert_eq!(elements(extensions).unwrap().last().unwrap(), &extension);
+    }
+
+    #[test_log::test]
+    fn test_unknown_oid() {
+        assert_eq!(unknown_oid(1), [TAG_OID, 3, 0x88, 0x37, 0x01]);
+        assert_eq!(unknown_oid(0x100), [TAG_OID, 4, 0x88, 0x37, 0x82, 0x00]);
+    }
+}
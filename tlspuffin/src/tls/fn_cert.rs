@@ -8,6 +8,7 @@ use ring::signature::{RsaKeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
 use crate::static_certs::{
     ALICE_CERT, ALICE_PRIVATE_KEY, BOB_CERT, BOB_PRIVATE_KEY, EVE_CERT, RANDOM_EC_CERT,
 };
+use crate::tls::der::{self, X509Certificate, TAG_BIT_STRING, TAG_OCTET_STRING, TAG_SEQUENCE};
 use crate::tls::rustls::hash_hs::HandshakeHash;
 use crate::tls::rustls::key::{Certificate, PrivateKey};
 use crate::tls::rustls::msgs::enums::SignatureScheme;
@@ -44,6 +45,10 @@ pub fn fn_random_ec_cert() -> Result<Vec<u8>, FnError> {
     Ok(RANDOM_EC_CERT.1.into())
 }
 
+pub fn fn_certificate_from_der(cert: &Vec<u8>) -> Result<Certificate, FnError> {
+    Ok(Certificate(cert.clone()))
+}
+
 pub fn fn_certificate_entry(cert: &Vec<u8>) -> Result<CertificateEntry, FnError> {
     Ok(CertificateEntry {
         cert: Certificate(cert.clone()),
@@ -64,6 +69,78 @@ pub fn fn_chain_append_certificate_entry(
     Ok(chain)
 }
 
+pub fn fn_truncate_certificate_chain(
+    chain: &Vec<CertificateEntry>,
+    length: &u64,
+) -> Result<Vec<CertificateEntry>, FnError> {
+    Ok(chain.iter().take(*length as usize).cloned().collect())
+}
+
+pub fn fn_truncate_certificates(
+    certs: &Vec<Certificate>,
+    length: &u64,
+) -> Result<Vec<Certificate>, FnError> {
+    Ok(certs.iter().take(*length as usize).cloned().collect())
+}
+
+/// Returns the DER encoding of a certificate, for example of one which was received from a PUT.
+pub fn fn_certificate_der(cert: &Certificate) -> Result<Vec<u8>, FnError> {
+    Ok(cert.0.clone())
+}
+
+/// Replaces the outer signature algorithm of the DER encoded `cert`. The signature algorithm in
+/// the signed part of the certificate is kept, such that the two no longer match.
+pub fn fn_x509_with_signature_algorithm(
+    cert: &Vec<u8>,
+    scheme: &SignatureScheme,
+) -> Result<Vec<u8>, FnError> {
+    let mut certificate = X509Certificate::decode(cert)?;
+    certificate.signature_algorithm = der::algorithm_identifier(scheme);
+    Ok(certificate.encode())
+}
+
+/// Appends the DER encoded X.509 `extension` to the extensions of the DER encoded `cert`.
+pub fn fn_x509_append_extension(cert: &Vec<u8>, extension: &Vec<u8>) -> Result<Vec<u8>, FnError> {
+    let mut certificate = X509Certificate::decode(cert)?;
+    certificate.append_extension(extension)?;
+    Ok(certificate.encode())
+}
+
+/// Non-critical X.509 extension with an unknown identifier and a value of `length` bytes.
+pub fn fn_x509_unknown_extension(length: &u64) -> Result<Vec<u8>, FnError> {
+    Ok(der::encode_elements(
+        TAG_SEQUENCE,
+        &[
+            der::unknown_oid(1),
+            der::encode(TAG_OCTET_STRING, &vec![42; *length as usize]),
+        ],
+    ))
+}
+
+/// Makes the DER encoded `cert` self-signed by copying its subject to its issuer and signing it
+/// with the RSA `private_key`.
+pub fn fn_x509_self_signed(cert: &Vec<u8>, private_key: &Vec<u8>) -> Result<Vec<u8>, FnError> {
+    let scheme = SignatureScheme::RSA_PKCS1_SHA256;
+
+    let mut certificate = X509Certificate::decode(cert)?;
+    let (issuer, subject) = (certificate.issuer_index(), certificate.subject_index());
+    certificate.tbs_certificate[issuer] = certificate.tbs_certificate[subject].clone();
+    let tbs_signature = certificate.tbs_signature_index();
+    certificate.tbs_certificate[tbs_signature] = der::algorithm_identifier(&scheme);
+    certificate.signature_algorithm = der::algorithm_identifier(&scheme);
+
+    // the bit string starts with the number of unused bits
+    let mut signature = vec![0];
+    signature.extend(_fn_rsa_sign(
+        &certificate.encode_tbs_certificate(),
+        private_key,
+        &scheme,
+    )?);
+    certificate.signature = der::encode(TAG_BIT_STRING, &signature);
+
+    Ok(certificate.encode())
+}
+
 pub fn fn_get_context(certificate_request: &Message) -> Result<Vec<u8>, FnError> {
     match certificate_request.payload.clone() {
         MessagePayload::Handshake(payload) => match payload.payload {
@@ -170,6 +247,28 @@ fn _fn_ecdsa_sign(message: &[u8], private_key: &[u8]) -> Result<Vec<u8>, FnError
         .map_err(|_err| FnError::Crypto("Failed to sign using ECDHE key".to_string()))
 }
 
+pub fn fn_rsa_sign12(
+    transcript: &HandshakeHash,
+    private_key: &Vec<u8>,
+    scheme: &SignatureScheme,
+) -> Result<Vec<u8>, FnError> {
+    _fn_rsa_sign(&handshake_messages12(transcript)?, private_key, scheme)
+}
+
+pub fn fn_ecdsa_sign12(
+    transcript: &HandshakeHash,
+    private_key: &Vec<u8>,
+) -> Result<Vec<u8>, FnError> {
+    _fn_ecdsa_sign(&handshake_messages12(transcript)?, private_key)
+}
+
+/// In TLS 1.2 the CertificateVerify signs all handshake messages instead of their hash.
+fn handshake_messages12(transcript: &HandshakeHash) -> Result<Vec<u8>, FnError> {
+    transcript.clone().take_handshake_buf().ok_or_else(|| {
+        FnError::Crypto("Transcript does not buffer the handshake messages".to_string())
+    })
+}
+
 pub fn fn_rsa_pss_signature_algorithm() -> Result<SignatureScheme, FnError> {
     Ok(SignatureScheme::RSA_PSS_SHA256)
 }
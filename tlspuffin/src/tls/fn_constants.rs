@@ -67,10 +67,49 @@ pub fn fn_large_length() -> Result<u64, FnError> {
     Ok(32702) // chosen by experimenting
 }
 
+/// Maximum length of a vector with a one byte length prefix, like a session id
+pub fn fn_max_u8_length() -> Result<u64, FnError> {
+    Ok(u8::MAX as u64)
+}
+
+/// Maximum length of a vector with a two byte length prefix, like a list of extensions
+pub fn fn_max_u16_length() -> Result<u64, FnError> {
+    Ok(u16::MAX as u64)
+}
+
+/// Maximum length of the plaintext of a record (2^14 bytes)
+pub fn fn_max_record_length() -> Result<u64, FnError> {
+    Ok(1 << 14)
+}
+
+/// Exceeds a maximum `length` by one
+pub fn fn_length_plus_one(length: &u64) -> Result<u64, FnError> {
+    Ok(length.saturating_add(1))
+}
+
+pub fn fn_bytes_of_length(length: &u64) -> Result<Vec<u8>, FnError> {
+    Ok(vec![42; *length as usize])
+}
+
 pub fn fn_empty_bytes_vec() -> Result<Vec<u8>, FnError> {
     Ok(vec![])
 }
 
+/// Appends the byte `16 * high + low`, such that arbitrary bytes can be spelled out with the
+/// constants `fn_seq_0` to `fn_seq_15`, e.g. the opaque payloads of an imported handshake.
+pub fn fn_append_byte(bytes: &Vec<u8>, high: &u64, low: &u64) -> Result<Vec<u8>, FnError> {
+    if *high > 15 || *low > 15 {
+        return Err(FnError::Unknown(format!(
+            "Nibbles {} and {} do not form a byte",
+            high, low
+        )));
+    }
+
+    let mut bytes = bytes.clone();
+    bytes.push((high * 16 + low) as u8);
+    Ok(bytes)
+}
+
 pub fn fn_large_bytes_vec() -> Result<Vec<u8>, FnError> {
     Ok(vec![42; 700])
 }
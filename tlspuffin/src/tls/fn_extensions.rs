@@ -7,6 +7,7 @@
 //! In the source code all IDs are available, but implementations are missing.
 
 use puffin::algebra::error::FnError;
+use puffin::codec::Codec;
 use webpki::DnsNameRef;
 
 use crate::nyi_fn;
@@ -33,6 +34,18 @@ pub fn fn_client_extensions_append(
     Ok(new_extensions)
 }
 
+/// Appends `extension` `count` times, for example to fill the list up to its maximum length.
+pub fn fn_client_extensions_repeat(
+    extensions: &Vec<ClientExtension>,
+    extension: &ClientExtension,
+    count: &u64,
+) -> Result<Vec<ClientExtension>, FnError> {
+    let mut new_extensions = extensions.clone();
+    new_extensions.extend(std::iter::repeat(extension).take(*count as usize).cloned());
+
+    Ok(new_extensions)
+}
+
 pub fn fn_server_extensions_new() -> Result<Vec<ServerExtension>, FnError> {
     Ok(vec![])
 }
@@ -47,6 +60,17 @@ pub fn fn_server_extensions_append(
     Ok(new_extensions)
 }
 
+/// Whether `extensions` contain an extension of the same type as `extension`. Used as condition of
+/// [`ExpectAction`](puffin::trace::ExpectAction)s.
+pub fn fn_has_server_extension(
+    extensions: &Vec<ServerExtension>,
+    extension: &ServerExtension,
+) -> Result<bool, FnError> {
+    Ok(extensions
+        .iter()
+        .any(|present| present.get_type() == extension.get_type()))
+}
+
 pub fn fn_hello_retry_extensions_new() -> Result<Vec<HelloRetryExtension>, FnError> {
     Ok(vec![])
 }
@@ -138,6 +162,22 @@ pub fn fn_server_name_extension() -> Result<ClientExtension, FnError> {
 pub fn fn_server_name_server_extension() -> Result<ServerExtension, FnError> {
     Ok(ServerExtension::ServerNameAck)
 }
+/// Requests the server name `host_name`. In contrast to [`fn_server_name_extension`], the name is
+/// not required to be a valid DNS name, such that the servername callback of the PUT sees
+/// arbitrary names.
+pub fn fn_sni_extension(host_name: &Vec<u8>) -> Result<ClientExtension, FnError> {
+    let mut payload = Vec::new();
+    PayloadU16::new(host_name.clone()).encode(&mut payload);
+    Ok(ClientExtension::ServerName(ServerNameRequest(vec![
+        ServerName {
+            typ: ServerNameType::HostName,
+            payload: ServerNamePayload::Unknown(Payload::new(payload)),
+        },
+    ])))
+}
+pub fn fn_sni_host_localhost() -> Result<Vec<u8>, FnError> {
+    Ok(b"localhost".to_vec())
+}
 nyi_fn! {
     /// MaxFragmentLength => 0x0001,
 }
@@ -232,8 +272,24 @@ pub fn fn_signature_algorithm_cert_req_extension() -> Result<CertReqExtension, F
 nyi_fn! {
     /// UseSRTP => 0x000e,
 }
-nyi_fn! {
-    /// Heartbeat => 0x000f,
+/// Heartbeat => 0x000f,
+pub fn fn_heartbeat_extension(mode: &HeartbeatMode) -> Result<ClientExtension, FnError> {
+    Ok(ClientExtension::Unknown(UnknownExtension {
+        typ: ExtensionType::Heartbeat,
+        payload: Payload::new([mode.get_u8()]),
+    }))
+}
+pub fn fn_heartbeat_server_extension(mode: &HeartbeatMode) -> Result<ServerExtension, FnError> {
+    Ok(ServerExtension::Unknown(UnknownExtension {
+        typ: ExtensionType::Heartbeat,
+        payload: Payload::new([mode.get_u8()]),
+    }))
+}
+pub fn fn_heartbeat_mode_peer_allowed_to_send() -> Result<HeartbeatMode, FnError> {
+    Ok(HeartbeatMode::PeerAllowedToSend)
+}
+pub fn fn_heartbeat_mode_peer_not_allowed_to_send() -> Result<HeartbeatMode, FnError> {
+    Ok(HeartbeatMode::PeerNotAllowedToSend)
 }
 /// ALProtocolNegotiation => 0x0010,
 pub fn fn_empty_vec_of_vec() -> Result<Vec<Vec<u8>>, FnError> {
@@ -266,6 +322,16 @@ pub fn fn_al_protocol_server_negotiation(
             .collect(),
     )))
 }
+/// Offers the single application protocol `protocol`.
+pub fn fn_alpn_extension(protocol: &Vec<u8>) -> Result<ClientExtension, FnError> {
+    fn_al_protocol_negotiation(&vec![protocol.clone()])
+}
+pub fn fn_alpn_protocol_h2() -> Result<Vec<u8>, FnError> {
+    Ok(b"h2".to_vec())
+}
+pub fn fn_alpn_protocol_http11() -> Result<Vec<u8>, FnError> {
+    Ok(b"http/1.1".to_vec())
+}
 nyi_fn! {
     /// status_request_v2 => 0x0011
 }
@@ -609,6 +675,12 @@ pub fn fn_transport_parameters_draft_server_extension(
 }
 // Unknown extensions
 
+/// Decodes an extension from its encoding, including the type and the length
+pub fn fn_decode_client_extension(encoding: &Vec<u8>) -> Result<ClientExtension, FnError> {
+    ClientExtension::read_bytes(encoding)
+        .ok_or_else(|| FnError::Unknown("Failed to decode client extension".to_string()))
+}
+
 pub fn fn_unknown_client_extension() -> Result<ClientExtension, FnError> {
     Ok(ClientExtension::Unknown(UnknownExtension {
         typ: ExtensionType::Unknown(0xFFFF),
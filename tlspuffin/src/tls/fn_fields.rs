@@ -3,6 +3,7 @@
 
 use puffin::algebra::error::FnError;
 use puffin::codec::{Codec, Reader};
+use puffin::define_projections;
 
 use crate::tls::key_exchange::tls12_new_secrets;
 use crate::tls::key_schedule::dhe_key_schedule;
@@ -12,7 +13,8 @@ use crate::tls::rustls::msgs::enums::{
     CipherSuite, Compression, ExtensionType, NamedGroup, ProtocolVersion,
 };
 use crate::tls::rustls::msgs::handshake::{
-    ClientExtension, HasServerExtensions, Random, ServerExtension, SessionID,
+    ClientExtension, ClientHelloPayload, HasServerExtensions, HelloRetryRequest, Random,
+    ServerExtension, ServerHelloPayload, SessionID,
 };
 
 pub fn fn_protocol_version13() -> Result<ProtocolVersion, FnError> {
@@ -38,6 +40,16 @@ pub fn fn_empty_session_id() -> Result<SessionID, FnError> {
     Ok(id.unwrap())
 }
 
+/// Session id of `bytes`, which may be up to 255 bytes long instead of the allowed 32 bytes
+pub fn fn_session_id(bytes: &Vec<u8>) -> Result<SessionID, FnError> {
+    SessionID::from_slice(bytes)
+        .ok_or_else(|| FnError::Unknown("Session id is longer than 255 bytes".to_string()))
+}
+
+pub fn fn_max_session_id() -> Result<SessionID, FnError> {
+    fn_session_id(&vec![3; u8::MAX as usize])
+}
+
 pub fn fn_new_random() -> Result<Random, FnError> {
     let random_data: [u8; 32] = [1; 32];
     Ok(Random::from(random_data))
@@ -51,10 +63,65 @@ pub fn fn_compression() -> Result<Compression, FnError> {
     Ok(Compression::Null)
 }
 
+pub fn fn_new_compressions() -> Result<Vec<Compression>, FnError> {
+    Ok(vec![])
+}
+
+pub fn fn_append_compression(
+    compressions: &Vec<Compression>,
+    compression: &Compression,
+) -> Result<Vec<Compression>, FnError> {
+    let mut new_compressions = compressions.clone();
+    new_compressions.push(*compression);
+    Ok(new_compressions)
+}
+
+pub fn fn_compression_deflate() -> Result<Compression, FnError> {
+    Ok(Compression::Deflate)
+}
+
+pub fn fn_compression_lsz() -> Result<Compression, FnError> {
+    Ok(Compression::LSZ)
+}
+
+pub fn fn_decode_compression(encoding: &Vec<u8>) -> Result<Compression, FnError> {
+    Compression::read_bytes(encoding)
+        .ok_or_else(|| FnError::Unknown("Failed to decode compression".to_string()))
+}
+
 pub fn fn_no_key_share() -> Result<Option<Vec<u8>>, FnError> {
     Ok(None)
 }
 
+define_projections!(client_hello: ClientHelloPayload {
+    client_version: ProtocolVersion,
+    random: Random,
+    session_id: SessionID,
+    cipher_suites: Vec<CipherSuite>,
+    compression_methods: Vec<Compression>,
+    extensions: Vec<ClientExtension>,
+});
+
+define_projections!(server_hello: ServerHelloPayload {
+    legacy_version: ProtocolVersion,
+    random: Random,
+    session_id: SessionID,
+    cipher_suite: CipherSuite,
+    compression_method: Compression,
+    extensions: Vec<ServerExtension>,
+});
+
+pub fn fn_get_renegotiation_info(
+    server_extensions: &Vec<ServerExtension>,
+) -> Result<Vec<u8>, FnError> {
+    match server_extensions.find_extension(ExtensionType::RenegotiationInfo) {
+        Some(ServerExtension::RenegotiationInfo(info)) => Ok(info.0.clone()),
+        _ => Err(FnError::Unknown(
+            "RenegotiationInfo extension not found".to_string(),
+        )),
+    }
+}
+
 pub fn fn_get_server_key_share(
     server_extensions: &Vec<ServerExtension>,
 ) -> Result<Option<Vec<u8>>, FnError> {
@@ -69,6 +136,25 @@ pub fn fn_get_server_key_share(
     }
 }
 
+/// Returns the group for which the client has to send a key share in its second ClientHello
+pub fn fn_get_hello_retry_group(
+    hello_retry_request: &HelloRetryRequest,
+) -> Result<NamedGroup, FnError> {
+    hello_retry_request
+        .get_requested_key_share_group()
+        .ok_or(FnError::Unknown("KeyShare extension not found".to_string()))
+}
+
+/// Returns the cookie which the client has to echo in its second ClientHello
+pub fn fn_get_hello_retry_cookie(
+    hello_retry_request: &HelloRetryRequest,
+) -> Result<Vec<u8>, FnError> {
+    hello_retry_request
+        .get_cookie()
+        .map(|cookie| cookie.0.clone())
+        .ok_or(FnError::Unknown("Cookie extension not found".to_string()))
+}
+
 pub fn fn_get_client_key_share(
     client_extensions: &Vec<ClientExtension>,
     group: &NamedGroup,
@@ -178,6 +264,28 @@ pub fn fn_sign_transcript(
     Ok(secrets.client_verify_data(&vh))
 }
 
+/// Like [`fn_sign_transcript`], but computes the verify_data of the Finished of the server.
+pub fn fn_sign_transcript_server(
+    server_random: &Random,
+    server_ecdh_pubkey: &Vec<u8>,
+    transcript: &HandshakeHash,
+    group: &NamedGroup,
+) -> Result<Vec<u8>, FnError> {
+    let secrets = tls12_new_secrets(server_random, server_ecdh_pubkey, group)?;
+
+    let vh = transcript.get_current_hash();
+    Ok(secrets.server_verify_data(&vh))
+}
+
+/// The renegotiation_info of a server during a secure renegotiation contains the verify_data of
+/// both Finished messages of the previous handshake (RFC 5746, Section 3.7).
+pub fn fn_renegotiation_info_data(
+    client_verify_data: &Vec<u8>,
+    server_verify_data: &Vec<u8>,
+) -> Result<Vec<u8>, FnError> {
+    Ok([client_verify_data.as_slice(), server_verify_data.as_slice()].concat())
+}
+
 // ----
 // Cipher Suites
 // ----
@@ -197,6 +305,11 @@ pub fn fn_append_cipher_suite(
     Ok(new)
 }
 
+pub fn fn_decode_cipher_suite(encoding: &Vec<u8>) -> Result<CipherSuite, FnError> {
+    CipherSuite::read_bytes(encoding)
+        .ok_or_else(|| FnError::Unknown("Failed to decode cipher suite".to_string()))
+}
+
 pub fn fn_cipher_suite12() -> Result<CipherSuite, FnError> {
     Ok(
         CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
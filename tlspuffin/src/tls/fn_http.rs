@@ -0,0 +1,167 @@
+#![allow(clippy::ptr_arg)]
+
+//! Payloads of HTTP/1.1 requests which are sent as application data.
+//!
+//! PUTs which are full servers, like nginx linked against the PUT library, parse the application
+//! data after the handshake. The requests are assembled from their parts, such that the fuzzer can
+//! mutate each part separately. The `Content-Length` is a header like any other, which allows to
+//! send requests whose length does not match their body.
+
+use puffin::algebra::error::FnError;
+
+pub fn fn_http_method_get() -> Result<Vec<u8>, FnError> {
+    Ok(b"GET".to_vec())
+}
+
+pub fn fn_http_method_post() -> Result<Vec<u8>, FnError> {
+    Ok(b"POST".to_vec())
+}
+
+pub fn fn_http_path_root() -> Result<Vec<u8>, FnError> {
+    Ok(b"/".to_vec())
+}
+
+pub fn fn_http_header_host() -> Result<Vec<u8>, FnError> {
+    Ok(b"Host".to_vec())
+}
+
+pub fn fn_http_header_content_length() -> Result<Vec<u8>, FnError> {
+    Ok(b"Content-Length".to_vec())
+}
+
+pub fn fn_http_header_transfer_encoding() -> Result<Vec<u8>, FnError> {
+    Ok(b"Transfer-Encoding".to_vec())
+}
+
+pub fn fn_http_localhost() -> Result<Vec<u8>, FnError> {
+    Ok(b"localhost".to_vec())
+}
+
+pub fn fn_http_chunked() -> Result<Vec<u8>, FnError> {
+    Ok(b"chunked".to_vec())
+}
+
+pub fn fn_http_headers_new() -> Result<Vec<u8>, FnError> {
+    Ok(vec![])
+}
+
+pub fn fn_http_headers_append(
+    headers: &Vec<u8>,
+    name: &Vec<u8>,
+    value: &Vec<u8>,
+) -> Result<Vec<u8>, FnError> {
+    Ok([headers, name, &b": "[..], value, &b"\r\n"[..]].concat())
+}
+
+/// The decimal length of `body`, as value of the `Content-Length` header
+pub fn fn_http_content_length(body: &Vec<u8>) -> Result<Vec<u8>, FnError> {
+    Ok(body.len().to_string().into_bytes())
+}
+
+/// Encodes `body` as a single chunk followed by the last chunk
+pub fn fn_http_chunked_body(body: &Vec<u8>) -> Result<Vec<u8>, FnError> {
+    Ok([
+        format!("{:x}\r\n", body.len()).as_bytes(),
+        body,
+        &b"\r\n0\r\n\r\n"[..],
+    ]
+    .concat())
+}
+
+pub fn fn_http_request(
+    method: &Vec<u8>,
+    path: &Vec<u8>,
+    headers: &Vec<u8>,
+    body: &Vec<u8>,
+) -> Result<Vec<u8>, FnError> {
+    Ok([
+        method,
+        &b" "[..],
+        path,
+        &b" HTTP/1.1\r\n"[..],
+        headers,
+        &b"\r\n"[..],
+        body,
+    ]
+    .concat())
+}
+
+/// Replaces the CRLF line endings of `request` by bare LFs, which some parsers accept
+pub fn fn_http_bare_lf(request: &Vec<u8>) -> Result<Vec<u8>, FnError> {
+    let mut bare = Vec::with_capacity(request.len());
+    let mut bytes = request.iter().peekable();
+    while let Some(&byte) = bytes.next() {
+        if byte != b'\r' || bytes.peek() != Some(&&b'\n') {
+            bare.push(byte);
+        }
+    }
+    Ok(bare)
+}
+
+/// Sends `second` in the same record directly after `first`
+pub fn fn_http_pipeline(first: &Vec<u8>, second: &Vec<u8>) -> Result<Vec<u8>, FnError> {
+    Ok([first.as_slice(), second].concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_request() -> Vec<u8> {
+        let headers = fn_http_headers_append(
+            &fn_http_headers_new().unwrap(),
+            &fn_http_header_host().unwrap(),
+            &fn_http_localhost().unwrap(),
+        )
+        .unwrap();
+        fn_http_request(
+            &fn_http_method_get().unwrap(),
+            &fn_http_path_root().unwrap(),
+            &headers,
+            &vec![],
+        )
+        .unwrap()
+    }
+
+    #[test_log::test]
+    fn test_http_request() {
+        assert_eq!(get_request(), b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+
+        let body = b"hello".to_vec();
+        let headers = fn_http_headers_append(
+            &fn_http_headers_new().unwrap(),
+            &fn_http_header_content_length().unwrap(),
+            &fn_http_content_length(&body).unwrap(),
+        )
+        .unwrap();
+        let request = fn_http_request(
+            &fn_http_method_post().unwrap(),
+            &fn_http_path_root().unwrap(),
+            &headers,
+            &body,
+        )
+        .unwrap();
+        assert_eq!(
+            request,
+            b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello"
+        );
+    }
+
+    #[test_log::test]
+    fn test_malformed_http_request() {
+        assert_eq!(
+            fn_http_bare_lf(&get_request()).unwrap(),
+            b"GET / HTTP/1.1\nHost: localhost\n\n"
+        );
+        assert_eq!(
+            fn_http_chunked_body(&vec![0; 26]).unwrap(),
+            [&b"1a\r\n"[..], &[0; 26], &b"\r\n0\r\n\r\n"[..]].concat()
+        );
+        assert_eq!(
+            fn_http_pipeline(&get_request(), &get_request())
+                .unwrap()
+                .len(),
+            2 * get_request().len()
+        );
+    }
+}
@@ -8,6 +8,7 @@
 //! Return type is `Message`
 
 use puffin::algebra::error::FnError;
+use puffin::codec::Reader;
 
 use crate::nyi_fn;
 use crate::tls::rustls::key;
@@ -25,6 +26,12 @@ pub fn fn_opaque_message(message: &OpaqueMessage) -> Result<OpaqueMessage, FnErr
     Ok(message.clone())
 }
 
+/// Decodes a record from its encoding, e.g. an encrypted record of an imported handshake
+pub fn fn_decode_opaque_message(encoding: &Vec<u8>) -> Result<OpaqueMessage, FnError> {
+    OpaqueMessage::read(&mut Reader::init(encoding))
+        .map_err(|err| FnError::Unknown(format!("Failed to decode record: {:?}", err)))
+}
+
 pub fn fn_empty_handshake_message() -> Result<OpaqueMessage, FnError> {
     Ok(OpaqueMessage {
         typ: ContentType::Handshake,
@@ -48,6 +55,160 @@ pub fn fn_alert_close_notify() -> Result<Message, FnError> {
     })
 }
 
+pub fn fn_alert(level: &AlertLevel, description: &AlertDescription) -> Result<Message, FnError> {
+    Ok(Message {
+        version: ProtocolVersion::TLSv1_2,
+        payload: MessagePayload::Alert(AlertMessagePayload {
+            level: *level,
+            description: *description,
+        }),
+    })
+}
+
+pub fn fn_alert_level_warning() -> Result<AlertLevel, FnError> {
+    Ok(AlertLevel::Warning)
+}
+
+pub fn fn_alert_level_fatal() -> Result<AlertLevel, FnError> {
+    Ok(AlertLevel::Fatal)
+}
+
+pub fn fn_alert_description_close_notify() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::CloseNotify)
+}
+
+pub fn fn_alert_description_unexpected_message() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::UnexpectedMessage)
+}
+
+pub fn fn_alert_description_bad_record_mac() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::BadRecordMac)
+}
+
+pub fn fn_alert_description_decryption_failed() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::DecryptionFailed)
+}
+
+pub fn fn_alert_description_record_overflow() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::RecordOverflow)
+}
+
+pub fn fn_alert_description_decompression_failure() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::DecompressionFailure)
+}
+
+pub fn fn_alert_description_handshake_failure() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::HandshakeFailure)
+}
+
+pub fn fn_alert_description_no_certificate() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::NoCertificate)
+}
+
+pub fn fn_alert_description_bad_certificate() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::BadCertificate)
+}
+
+pub fn fn_alert_description_unsupported_certificate() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::UnsupportedCertificate)
+}
+
+pub fn fn_alert_description_certificate_revoked() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::CertificateRevoked)
+}
+
+pub fn fn_alert_description_certificate_expired() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::CertificateExpired)
+}
+
+pub fn fn_alert_description_certificate_unknown() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::CertificateUnknown)
+}
+
+pub fn fn_alert_description_illegal_parameter() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::IllegalParameter)
+}
+
+pub fn fn_alert_description_unknown_ca() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::UnknownCA)
+}
+
+pub fn fn_alert_description_access_denied() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::AccessDenied)
+}
+
+pub fn fn_alert_description_decode_error() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::DecodeError)
+}
+
+pub fn fn_alert_description_decrypt_error() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::DecryptError)
+}
+
+pub fn fn_alert_description_export_restriction() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::ExportRestriction)
+}
+
+pub fn fn_alert_description_protocol_version() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::ProtocolVersion)
+}
+
+pub fn fn_alert_description_insufficient_security() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::InsufficientSecurity)
+}
+
+pub fn fn_alert_description_internal_error() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::InternalError)
+}
+
+pub fn fn_alert_description_inappropriate_fallback() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::InappropriateFallback)
+}
+
+pub fn fn_alert_description_user_canceled() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::UserCanceled)
+}
+
+pub fn fn_alert_description_no_renegotiation() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::NoRenegotiation)
+}
+
+pub fn fn_alert_description_missing_extension() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::MissingExtension)
+}
+
+pub fn fn_alert_description_unsupported_extension() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::UnsupportedExtension)
+}
+
+pub fn fn_alert_description_certificate_unobtainable() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::CertificateUnobtainable)
+}
+
+pub fn fn_alert_description_unrecognised_name() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::UnrecognisedName)
+}
+
+pub fn fn_alert_description_bad_certificate_status_response() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::BadCertificateStatusResponse)
+}
+
+pub fn fn_alert_description_bad_certificate_hash_value() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::BadCertificateHashValue)
+}
+
+pub fn fn_alert_description_unknown_psk_identity() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::UnknownPSKIdentity)
+}
+
+pub fn fn_alert_description_certificate_required() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::CertificateRequired)
+}
+
+pub fn fn_alert_description_no_application_protocol() -> Result<AlertDescription, FnError> {
+    Ok(AlertDescription::NoApplicationProtocol)
+}
+
 // ----
 // CCS Message constructors
 // ----
@@ -58,6 +219,18 @@ pub fn fn_change_cipher_spec() -> Result<Message, FnError> {
         payload: MessagePayload::ChangeCipherSpec(ChangeCipherSpecPayload {}),
     })
 }
+
+/// ChangeCipherSpec record with an arbitrary `payload`. A valid ChangeCipherSpec consists of the
+/// single byte 0x01, which is also the only payload a TLS 1.3 peer may ignore for middlebox
+/// compatibility.
+pub fn fn_opaque_change_cipher_spec(payload: &Vec<u8>) -> Result<OpaqueMessage, FnError> {
+    Ok(OpaqueMessage {
+        typ: ContentType::ChangeCipherSpec,
+        version: ProtocolVersion::TLSv1_2,
+        payload: Payload::new(payload.clone()),
+    })
+}
+
 // ----
 // ApplicationData Message constructors
 // ----
@@ -106,17 +279,43 @@ pub fn fn_application_data(data: &Vec<u8>) -> Result<Message, FnError> {
     ))?)?)
 }*/
 
-pub fn fn_heartbeat_fake_length(payload: &Vec<u8>, fake_length: &u64) -> Result<Message, FnError> {
+/// Heartbeat message of type `typ` whose length field is `length` independently of the actual
+/// length of `payload`.
+pub fn fn_heartbeat_message(
+    typ: &HeartbeatMessageType,
+    payload: &Vec<u8>,
+    length: &u64,
+) -> Result<Message, FnError> {
     Ok(Message {
         version: ProtocolVersion::TLSv1_2,
         payload: MessagePayload::Heartbeat(HeartbeatPayload {
-            typ: HeartbeatMessageType::Request,
+            typ: *typ,
             payload: PayloadU16::new(payload.clone()),
-            fake_length: Some(*fake_length as u16),
+            fake_length: Some(*length as u16),
         }),
     })
 }
 
+pub fn fn_heartbeat_request_type() -> Result<HeartbeatMessageType, FnError> {
+    Ok(HeartbeatMessageType::Request)
+}
+
+pub fn fn_heartbeat_response_type() -> Result<HeartbeatMessageType, FnError> {
+    Ok(HeartbeatMessageType::Response)
+}
+
+pub fn fn_heartbeat_fake_length(payload: &Vec<u8>, fake_length: &u64) -> Result<Message, FnError> {
+    fn_heartbeat_message(&HeartbeatMessageType::Request, payload, fake_length)
+}
+
+pub fn fn_heartbeat_response(payload: &Vec<u8>) -> Result<Message, FnError> {
+    fn_heartbeat_message(
+        &HeartbeatMessageType::Response,
+        payload,
+        &(payload.len() as u64),
+    )
+}
+
 pub fn fn_heartbeat(payload: &Vec<u8>) -> Result<Message, FnError> {
     fn_heartbeat_fake_length(payload, &(payload.len() as u64))
 }
@@ -222,8 +421,15 @@ pub fn fn_new_session_ticket13(
         }),
     })
 }
-nyi_fn! {
-    /// EndOfEarlyData => 0x05,
+/// EndOfEarlyData => 0x05,
+pub fn fn_end_of_early_data() -> Result<Message, FnError> {
+    Ok(Message {
+        version: ProtocolVersion::TLSv1_2,
+        payload: MessagePayload::Handshake(HandshakeMessagePayload {
+            typ: HandshakeType::EndOfEarlyData,
+            payload: HandshakePayload::EndOfEarlyData,
+        }),
+    })
 }
 /// HelloRetryRequest => 0x06,
 pub fn fn_hello_retry_request(
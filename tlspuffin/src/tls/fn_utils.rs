@@ -9,13 +9,13 @@ use crate::protocol::{MessageFlight, OpaqueMessageFlight};
 use crate::tls::key_exchange::{tls12_key_exchange, tls12_new_secrets};
 use crate::tls::key_schedule::*;
 use crate::tls::rustls::conn::Side;
-use crate::tls::rustls::hash_hs::HandshakeHash;
+use crate::tls::rustls::hash_hs::{HandshakeHash, HandshakeHashBuffer};
 use crate::tls::rustls::key::Certificate;
-use crate::tls::rustls::msgs::base::PayloadU8;
+use crate::tls::rustls::msgs::base::{Payload, PayloadU8};
 use crate::tls::rustls::msgs::enums::{HandshakeType, NamedGroup};
 use crate::tls::rustls::msgs::handshake::{
     CertificateEntry, CertificateExtension, CertificateExtensions, HandshakeMessagePayload,
-    HandshakePayload, Random, ServerECDHParams,
+    HandshakePayload, Random, ServerECDHParams, ServerExtension,
 };
 use crate::tls::rustls::msgs::message::{Message, MessagePayload, OpaqueMessage, PlainMessage};
 use crate::tls::rustls::tls12;
@@ -41,6 +41,16 @@ pub fn fn_append_transcript(
     Ok(new_transcript)
 }
 
+/// Replaces the messages of `transcript` with a message_hash message which contains their hash,
+/// as required after a HelloRetryRequest (RFC 8446, Section 4.4.1).
+pub fn fn_hello_retry_rollup_transcript(
+    transcript: &HandshakeHash,
+) -> Result<HandshakeHash, FnError> {
+    let mut new_transcript: HandshakeHash = transcript.clone();
+    new_transcript.rollup_for_hrr();
+    Ok(new_transcript)
+}
+
 pub fn fn_new_flight() -> Result<MessageFlight, FnError> {
     Ok(MessageFlight::new())
 }
@@ -64,6 +74,60 @@ pub fn fn_append_opaque_flight(
     Ok(new_flight)
 }
 
+/// Splits the encoding of `msg` into records whose payloads have at most `size` bytes.
+pub fn fn_fragment(msg: &Message, size: &u64) -> Result<OpaqueMessageFlight, FnError> {
+    if *size == 0 {
+        return Err(FnError::Unknown(
+            "Fragment size must not be zero".to_string(),
+        ));
+    }
+
+    let opaque = PlainMessage::from(msg.clone()).into_unencrypted_opaque();
+    let mut flight = OpaqueMessageFlight::new();
+    for chunk in opaque.payload.0.chunks(*size as usize) {
+        flight.messages.push(OpaqueMessage {
+            typ: opaque.typ,
+            version: opaque.version,
+            payload: Payload::new(chunk),
+        });
+    }
+    Ok(flight)
+}
+
+/// Puts the encodings of all messages of `flight` into a single record.
+pub fn fn_coalesce(flight: &MessageFlight) -> Result<OpaqueMessage, FnError> {
+    coalesce(
+        flight
+            .messages
+            .iter()
+            .map(|msg| PlainMessage::from(msg.clone()).into_unencrypted_opaque()),
+    )
+}
+
+/// Puts the payloads of all records of `flight` into a single record, for example to reassemble
+/// fragments differently.
+pub fn fn_coalesce_opaque(flight: &OpaqueMessageFlight) -> Result<OpaqueMessage, FnError> {
+    coalesce(flight.messages.iter().cloned())
+}
+
+/// The coalesced record has the content type and version of the first record. All records must
+/// have the same content type.
+fn coalesce(mut records: impl Iterator<Item = OpaqueMessage>) -> Result<OpaqueMessage, FnError> {
+    let mut coalesced = records
+        .next()
+        .ok_or_else(|| FnError::Unknown("Unable to coalesce an empty flight".to_string()))?;
+
+    for record in records {
+        if record.typ != coalesced.typ {
+            return Err(FnError::Unknown(
+                "Unable to coalesce records of different content types".to_string(),
+            ));
+        }
+        coalesced.payload.0.extend(record.payload.0);
+    }
+    Ok(coalesced)
+}
+
 /// Decrypt a whole flight of handshake messages and return a Vec of decrypted messages
 pub fn fn_decrypt_handshake_flight(
     flight: &MessageFlight,
@@ -163,6 +227,21 @@ pub fn fn_find_server_ticket(flight: &MessageFlight) -> Result<Message, FnError>
     Err(FnError::Unknown("no server tickets".to_owned()))
 }
 
+/// Finds the `index`-th ticket of `flight`. Servers may issue several tickets after a handshake,
+/// e.g. OpenSSL issues two by default.
+pub fn fn_find_nth_server_ticket(flight: &MessageFlight, index: &u64) -> Result<Message, FnError> {
+    flight
+        .messages
+        .iter()
+        .filter(|msg| match &msg.payload {
+            MessagePayload::Handshake(x) => x.typ == HandshakeType::NewSessionTicket,
+            _ => false,
+        })
+        .nth(*index as usize)
+        .cloned()
+        .ok_or_else(|| FnError::Unknown(format!("no server ticket {}", index)))
+}
+
 pub fn fn_find_server_certificate_request(flight: &MessageFlight) -> Result<Message, FnError> {
     for msg in &flight.messages {
         if let MessagePayload::Handshake(x) = &msg.payload {
@@ -185,6 +264,22 @@ pub fn fn_find_encrypted_extensions(flight: &MessageFlight) -> Result<Message, F
     Err(FnError::Unknown("no encrypted extensions".to_owned()))
 }
 
+/// The extensions of an EncryptedExtensions message, e.g. to check whether the server accepted
+/// early data with [`fn_has_server_extension`](crate::tls::fn_impl::fn_has_server_extension).
+pub fn fn_get_encrypted_extensions(
+    encrypted_extensions: &Message,
+) -> Result<Vec<ServerExtension>, FnError> {
+    match &encrypted_extensions.payload {
+        MessagePayload::Handshake(HandshakeMessagePayload {
+            payload: HandshakePayload::EncryptedExtensions(extensions),
+            ..
+        }) => Ok(extensions.0.clone()),
+        _ => Err(FnError::Unknown(
+            "not an EncryptedExtensions message".to_owned(),
+        )),
+    }
+}
+
 pub fn fn_find_server_certificate_verify(flight: &MessageFlight) -> Result<Message, FnError> {
     for msg in &flight.messages {
         if let MessagePayload::Handshake(x) = &msg.payload {
@@ -260,6 +355,58 @@ pub fn fn_decrypt_application(
     group: &NamedGroup,
     client: &bool,
     sequence: &u64,
+) -> Result<Message, FnError> {
+    decrypt_application(
+        application_data,
+        server_hello_transcript,
+        server_finished_transcript,
+        server_key_share,
+        psk,
+        group,
+        client,
+        0,
+        sequence,
+    )
+}
+
+/// Decrypts an application message of the peer after it updated its keys `generation` times with
+/// KeyUpdate messages. The sequence number starts at zero after each update.
+#[allow(clippy::too_many_arguments)]
+pub fn fn_decrypt_application_updated(
+    application_data: &Message,
+    server_hello_transcript: &HandshakeHash,
+    server_finished_transcript: &HandshakeHash,
+    server_key_share: &Option<Vec<u8>>,
+    psk: &Option<Vec<u8>>,
+    group: &NamedGroup,
+    client: &bool,
+    generation: &u64,
+    sequence: &u64,
+) -> Result<Message, FnError> {
+    decrypt_application(
+        application_data,
+        server_hello_transcript,
+        server_finished_transcript,
+        server_key_share,
+        psk,
+        group,
+        client,
+        *generation,
+        sequence,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn decrypt_application(
+    application_data: &Message,
+    server_hello_transcript: &HandshakeHash,
+    server_finished_transcript: &HandshakeHash,
+    server_key_share: &Option<Vec<u8>>,
+    psk: &Option<Vec<u8>>,
+    group: &NamedGroup,
+    client: &bool,
+    generation: u64,
+    sequence: &u64,
 ) -> Result<Message, FnError> {
     let (suite, key, _) = tls13_application_traffic_secret(
         server_hello_transcript,
@@ -269,6 +416,7 @@ pub fn fn_decrypt_application(
         group,
         !*client,
     )?;
+    let key = tls13_update_traffic_secret(suite, key, generation)?;
     let decrypter = suite
         .tls13()
         .ok_or_else(|| FnError::Crypto("No tls 1.3 suite".to_owned()))?
@@ -293,26 +441,188 @@ pub fn fn_encrypt_handshake(
     group: &NamedGroup,
     client: &bool,
     sequence: &u64,
+) -> Result<OpaqueMessage, FnError> {
+    encrypt_handshake(
+        some_message,
+        0,
+        server_hello,
+        server_key_share,
+        psk,
+        group,
+        client,
+        sequence,
+    )
+}
+
+pub fn fn_encrypt_application(
+    some_message: &Message,
+    server_hello_transcript: &HandshakeHash,
+    server_finished_transcript: &HandshakeHash,
+    server_key_share: &Option<Vec<u8>>,
+    psk: &Option<Vec<u8>>,
+    group: &NamedGroup,
+    sequence: &u64,
+) -> Result<OpaqueMessage, FnError> {
+    encrypt_application(
+        some_message,
+        0,
+        server_hello_transcript,
+        server_finished_transcript,
+        server_key_share,
+        psk,
+        group,
+        true,
+        0,
+        sequence,
+    )
+}
+
+/// Encrypts an application message of the client after it updated its keys `generation` times
+/// with KeyUpdate messages. The sequence number starts at zero after each update.
+#[allow(clippy::too_many_arguments)]
+pub fn fn_encrypt_application_updated(
+    some_message: &Message,
+    server_hello_transcript: &HandshakeHash,
+    server_finished_transcript: &HandshakeHash,
+    server_key_share: &Option<Vec<u8>>,
+    psk: &Option<Vec<u8>>,
+    group: &NamedGroup,
+    generation: &u64,
+    sequence: &u64,
+) -> Result<OpaqueMessage, FnError> {
+    encrypt_application(
+        some_message,
+        0,
+        server_hello_transcript,
+        server_finished_transcript,
+        server_key_share,
+        psk,
+        group,
+        true,
+        *generation,
+        sequence,
+    )
+}
+
+/// Encrypts an application message with the keys of the server, such that an attacker which
+/// impersonates the server can send records after the handshake.
+pub fn fn_encrypt_server_application(
+    some_message: &Message,
+    server_hello_transcript: &HandshakeHash,
+    server_finished_transcript: &HandshakeHash,
+    server_key_share: &Option<Vec<u8>>,
+    psk: &Option<Vec<u8>>,
+    group: &NamedGroup,
+    sequence: &u64,
+) -> Result<OpaqueMessage, FnError> {
+    encrypt_application(
+        some_message,
+        0,
+        server_hello_transcript,
+        server_finished_transcript,
+        server_key_share,
+        psk,
+        group,
+        false,
+        0,
+        sequence,
+    )
+}
+
+/// A TLS 1.3 message which is padded with zeros when it is encrypted.
+#[derive(Debug, Clone)]
+pub struct PaddedMessage {
+    pub message: Message,
+    pub padding: u64,
+}
+
+/// Pads the record of `msg` with `length` zeros when it is encrypted with
+/// [`fn_encrypt_handshake_padded`] or [`fn_encrypt_application_padded`].
+pub fn fn_pad_record(msg: &Message, length: &u64) -> Result<PaddedMessage, FnError> {
+    Ok(PaddedMessage {
+        message: msg.clone(),
+        padding: *length,
+    })
+}
+
+pub fn fn_encrypt_handshake_padded(
+    padded_message: &PaddedMessage,
+    server_hello: &HandshakeHash,
+    server_key_share: &Option<Vec<u8>>,
+    psk: &Option<Vec<u8>>,
+    group: &NamedGroup,
+    client: &bool,
+    sequence: &u64,
+) -> Result<OpaqueMessage, FnError> {
+    encrypt_handshake(
+        &padded_message.message,
+        padded_message.padding as usize,
+        server_hello,
+        server_key_share,
+        psk,
+        group,
+        client,
+        sequence,
+    )
+}
+
+pub fn fn_encrypt_application_padded(
+    padded_message: &PaddedMessage,
+    server_hello_transcript: &HandshakeHash,
+    server_finished_transcript: &HandshakeHash,
+    server_key_share: &Option<Vec<u8>>,
+    psk: &Option<Vec<u8>>,
+    group: &NamedGroup,
+    sequence: &u64,
+) -> Result<OpaqueMessage, FnError> {
+    encrypt_application(
+        &padded_message.message,
+        padded_message.padding as usize,
+        server_hello_transcript,
+        server_finished_transcript,
+        server_key_share,
+        psk,
+        group,
+        true,
+        0,
+        sequence,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn encrypt_handshake(
+    some_message: &Message,
+    padding: usize,
+    server_hello: &HandshakeHash,
+    server_key_share: &Option<Vec<u8>>,
+    psk: &Option<Vec<u8>>,
+    group: &NamedGroup,
+    client: &bool,
+    sequence: &u64,
 ) -> Result<OpaqueMessage, FnError> {
     let (suite, key, _) =
         tls13_handshake_traffic_secret(server_hello, server_key_share, psk, *client, group)?;
     let encrypter = suite
         .tls13()
         .ok_or_else(|| FnError::Crypto("No tls 1.3 suite".to_owned()))?
-        .derive_encrypter(&key);
+        .derive_padding_encrypter(&key, padding);
     let application_data = encrypter
         .encrypt(PlainMessage::from(some_message.clone()).borrow(), *sequence)
         .map_err(|_err| FnError::Crypto("Failed to encrypt it fn_encrypt_handshake".to_string()))?;
     Ok(application_data)
 }
 
-pub fn fn_encrypt_application(
+#[allow(clippy::too_many_arguments)]
+fn encrypt_application(
     some_message: &Message,
+    padding: usize,
     server_hello_transcript: &HandshakeHash,
     server_finished_transcript: &HandshakeHash,
     server_key_share: &Option<Vec<u8>>,
     psk: &Option<Vec<u8>>,
     group: &NamedGroup,
+    client: bool,
+    generation: u64,
     sequence: &u64,
 ) -> Result<OpaqueMessage, FnError> {
     let (suite, key, _) = tls13_application_traffic_secret(
@@ -321,12 +631,13 @@ pub fn fn_encrypt_application(
         server_key_share,
         psk,
         group,
-        true,
+        client,
     )?;
+    let key = tls13_update_traffic_secret(suite, key, generation)?;
     let encrypter = suite
         .tls13()
         .ok_or_else(|| FnError::Crypto("No tls 1.3 suite".to_owned()))?
-        .derive_encrypter(&key);
+        .derive_padding_encrypter(&key, padding);
     let application_data = encrypter
         .encrypt(PlainMessage::from(some_message.clone()).borrow(), *sequence)
         .map_err(|_err| {
@@ -335,6 +646,25 @@ pub fn fn_encrypt_application(
     Ok(application_data)
 }
 
+pub fn fn_encrypt_early_data(
+    some_message: &Message,
+    client_hello_transcript: &HandshakeHash,
+    psk: &Vec<u8>,
+    sequence: &u64,
+) -> Result<OpaqueMessage, FnError> {
+    let (suite, key) = tls13_early_traffic_secret(client_hello_transcript, psk)?;
+    let encrypter = suite
+        .tls13()
+        .ok_or_else(|| FnError::Crypto("No tls 1.3 suite".to_owned()))?
+        .derive_encrypter(&key);
+    let early_data = encrypter
+        .encrypt(PlainMessage::from(some_message.clone()).borrow(), *sequence)
+        .map_err(|_err| {
+            FnError::Crypto("Failed to encrypt it fn_encrypt_early_data".to_string())
+        })?;
+    Ok(early_data)
+}
+
 pub fn fn_derive_psk(
     server_hello: &HandshakeHash,
     server_finished: &HandshakeHash,
@@ -452,6 +782,16 @@ pub fn fn_new_transcript12() -> Result<HandshakeHash, FnError> {
     Ok(transcript)
 }
 
+/// Like [`fn_new_transcript12`], but additionally buffers all handshake messages. The buffer is
+/// signed in the CertificateVerify message of TLS 1.2 client authentication.
+pub fn fn_new_transcript12_client_auth() -> Result<HandshakeHash, FnError> {
+    let suite = &tls12::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256;
+
+    let mut buffer = HandshakeHashBuffer::new();
+    buffer.set_client_auth_enabled();
+    Ok(buffer.start_hash(suite.hash_algorithm()))
+}
+
 pub fn fn_decode_ecdh_pubkey(data: &Vec<u8>) -> Result<Vec<u8>, FnError> {
     let mut rd = Reader::init(data.as_slice());
     let params = ServerECDHParams::read(&mut rd)
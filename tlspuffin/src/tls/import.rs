@@ -0,0 +1,354 @@
+//! Reconstruction of seeds from captured handshakes.
+//!
+//! The records which a real-world client sent are rebuilt from the symbols of the
+//! [signature](crate::tls::TLS_SIGNATURE). ClientHellos are rebuilt field by field, other messages
+//! as a whole. Each value is [inferred](Signature::infer_term) by searching for a term which
+//! evaluates to the captured value. Values for which no term is found, like GREASE cipher suites
+//! and extensions, are decoded from their captured encoding, which is spelled out with
+//! [`fn_append_byte`]. Records which can not be parsed, like encrypted records, are sent as opaque
+//! records. Random values can not be reproduced by the signature and are therefore replaced by the
+//! deterministic ones of the other seeds.
+
+use puffin::agent::{AgentDescriptor, AgentName, TLSVersion};
+use puffin::algebra::signature::Signature;
+use puffin::algebra::Term;
+use puffin::codec::Codec;
+use puffin::protocol::ProtocolMessage;
+use puffin::term;
+use puffin::trace::{Action, InputAction, OutputAction, Step, Trace};
+
+use crate::query::TlsQueryMatcher;
+use crate::tls::fn_impl::*;
+use crate::tls::rustls::msgs::deframer::MessageDeframer;
+use crate::tls::rustls::msgs::enums::{CipherSuite, Compression, NamedGroup, ProtocolVersion};
+use crate::tls::rustls::msgs::handshake::{ClientExtension, ClientHelloPayload, HandshakePayload};
+use crate::tls::rustls::msgs::message::{Message, MessagePayload, OpaqueMessage};
+use crate::tls::TLS_SIGNATURE;
+
+/// Creates a trace in which the attacker sends the records of `client_data` to a server.
+///
+/// The server responds after each ClientHello and after the last record, because a client waits
+/// for the response of the server at these points of a handshake.
+pub fn import_client_data(client_data: &[u8]) -> Result<Trace<TlsQueryMatcher>, String> {
+    let server = AgentName::first();
+    let mut tls_version = None;
+    let mut steps = vec![];
+
+    for record in read_records(client_data)? {
+        let client_hello = match Message::try_from(record.clone()) {
+            Ok(Message {
+                payload: MessagePayload::Handshake(handshake),
+                ..
+            }) => match handshake.payload {
+                HandshakePayload::ClientHello(client_hello) => Some(client_hello),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        let recipe = match &client_hello {
+            Some(client_hello) => {
+                tls_version.get_or_insert_with(|| match client_hello.get_versions_extension() {
+                    Some(versions) if versions.contains(&ProtocolVersion::TLSv1_3) => {
+                        TLSVersion::V1_3
+                    }
+                    _ => TLSVersion::V1_2,
+                });
+                client_hello_term(&TLS_SIGNATURE, client_hello)?
+            }
+            None => record_term(&TLS_SIGNATURE, &record),
+        };
+
+        steps.push(Step {
+            agent: server,
+            action: Action::Input(InputAction { recipe }),
+        });
+        if client_hello.is_some() {
+            steps.push(OutputAction::new_step(server));
+        }
+    }
+
+    let tls_version = tls_version.ok_or_else(|| "No ClientHello found".to_string())?;
+    if !matches!(
+        steps.last(),
+        Some(Step {
+            action: Action::Output(_),
+            ..
+        })
+    ) {
+        steps.push(OutputAction::new_step(server));
+    }
+
+    Ok(Trace {
+        prior_traces: vec![],
+        descriptors: vec![AgentDescriptor::new_server(server, tls_version)],
+        steps,
+    })
+}
+
+fn read_records(client_data: &[u8]) -> Result<Vec<OpaqueMessage>, String> {
+    let mut deframer = MessageDeframer::new();
+    let mut reader = client_data;
+    while deframer
+        .read(&mut reader)
+        .map_err(|err| format!("Failed to read records: {}", err))?
+        > 0
+    {}
+
+    Ok(deframer.frames.into_iter().collect())
+}
+
+/// Finds a term which evaluates to a value with the same encoding as `value`.
+fn infer_encoded<T: Codec + 'static>(
+    signature: &Signature,
+    value: &T,
+) -> Option<Term<TlsQueryMatcher>> {
+    let encoding = value.get_encoding();
+    signature.infer_term(|candidate: &T| candidate.get_encoding() == encoding)
+}
+
+/// Finds a term which evaluates to `value`, or otherwise decodes `value` from its encoding with
+/// the term which `decode` builds from the bytes.
+fn infer_or_decode<T: Codec + std::fmt::Debug + 'static>(
+    signature: &Signature,
+    value: &T,
+    decode: impl FnOnce(Term<TlsQueryMatcher>) -> Term<TlsQueryMatcher>,
+) -> Term<TlsQueryMatcher> {
+    infer_encoded(signature, value).unwrap_or_else(|| {
+        log::debug!("Decoding unknown value {:?} from its encoding", value);
+        decode(bytes_term(&value.get_encoding()))
+    })
+}
+
+/// A message which is inferred as a whole, or otherwise an opaque record.
+fn record_term(signature: &Signature, record: &OpaqueMessage) -> Term<TlsQueryMatcher> {
+    let encoding = record.get_encoding();
+    let message = Message::try_from(record.clone()).ok().and_then(|_| {
+        signature
+            .infer_term(|candidate: &Message| candidate.create_opaque().get_encoding() == encoding)
+    });
+
+    message.unwrap_or_else(|| {
+        let bytes = bytes_term(&encoding);
+        term! { fn_decode_opaque_message((@bytes)) }
+    })
+}
+
+/// A term which spells out `bytes`, see [`fn_append_byte`].
+fn bytes_term(bytes: &[u8]) -> Term<TlsQueryMatcher> {
+    bytes
+        .iter()
+        .fold(term! { fn_empty_bytes_vec }, |bytes, byte| {
+            let high = nibble_term(byte >> 4);
+            let low = nibble_term(byte & 0x0f);
+            term! { fn_append_byte((@bytes), (@high), (@low)) }
+        })
+}
+
+fn nibble_term(nibble: u8) -> Term<TlsQueryMatcher> {
+    match nibble {
+        0 => term! { fn_seq_0 },
+        1 => term! { fn_seq_1 },
+        2 => term! { fn_seq_2 },
+        3 => term! { fn_seq_3 },
+        4 => term! { fn_seq_4 },
+        5 => term! { fn_seq_5 },
+        6 => term! { fn_seq_6 },
+        7 => term! { fn_seq_7 },
+        8 => term! { fn_seq_8 },
+        9 => term! { fn_seq_9 },
+        10 => term! { fn_seq_10 },
+        11 => term! { fn_seq_11 },
+        12 => term! { fn_seq_12 },
+        13 => term! { fn_seq_13 },
+        14 => term! { fn_seq_14 },
+        15 => term! { fn_seq_15 },
+        _ => unreachable!("a nibble is below 16"),
+    }
+}
+
+fn client_hello_term(
+    signature: &Signature,
+    client_hello: &ClientHelloPayload,
+) -> Result<Term<TlsQueryMatcher>, String> {
+    let version = infer_encoded(signature, &client_hello.client_version)
+        .ok_or_else(|| format!("Unknown version {:?}", client_hello.client_version))?;
+
+    let session_id = if client_hello.session_id.is_empty() {
+        term! { fn_empty_session_id }
+    } else {
+        term! { fn_new_session_id }
+    };
+
+    let mut cipher_suites = term! { fn_new_cipher_suites };
+    for suite in &client_hello.cipher_suites {
+        let suite = infer_or_decode::<CipherSuite>(signature, suite, |bytes| {
+            term! { fn_decode_cipher_suite((@bytes)) }
+        });
+        cipher_suites = term! { fn_append_cipher_suite((@cipher_suites), (@suite)) };
+    }
+
+    let mut compressions = term! { fn_new_compressions };
+    for compression in &client_hello.compression_methods {
+        let compression = infer_or_decode::<Compression>(signature, compression, |bytes| {
+            term! { fn_decode_compression((@bytes)) }
+        });
+        compressions = term! { fn_append_compression((@compressions), (@compression)) };
+    }
+
+    let mut extensions = term! { fn_client_extensions_new };
+    for extension in &client_hello.extensions {
+        let extension = extension_term(signature, extension);
+        extensions = term! { fn_client_extensions_append((@extensions), (@extension)) };
+    }
+
+    Ok(term! {
+        fn_client_hello(
+            (@version),
+            fn_new_random,
+            (@session_id),
+            (@cipher_suites),
+            (@compressions),
+            (@extensions)
+        )
+    })
+}
+
+fn extension_term(signature: &Signature, extension: &ClientExtension) -> Term<TlsQueryMatcher> {
+    // The key shares are random, therefore a deterministic key share for the first known
+    // group is sent instead
+    if let ClientExtension::KeyShare(entries) = extension {
+        let key_share = entries.iter().find_map(|entry| {
+            let group = infer_encoded::<NamedGroup>(signature, &entry.group)?;
+            Some(term! { fn_key_share_deterministic_extension((@group)) })
+        });
+        if let Some(key_share) = key_share {
+            return key_share;
+        }
+    }
+
+    infer_or_decode(signature, extension, |bytes| {
+        term! { fn_decode_client_extension((@bytes)) }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use puffin::trace::{Spawner, TraceContext};
+
+    use super::*;
+    use crate::put_registry::tls_registry;
+    use crate::tls::rustls::msgs::base::Payload;
+    use crate::tls::rustls::msgs::enums::{ContentType, ExtensionType, HandshakeType};
+    use crate::tls::rustls::msgs::handshake::{
+        HandshakeMessagePayload, SessionID, UnknownExtension,
+    };
+
+    fn encode_client_hello(client_hello: ClientHelloPayload) -> Vec<u8> {
+        let message = Message {
+            version: ProtocolVersion::TLSv1_2,
+            payload: MessagePayload::Handshake(HandshakeMessagePayload {
+                typ: HandshakeType::ClientHello,
+                payload: HandshakePayload::ClientHello(client_hello),
+            }),
+        };
+        message.create_opaque().get_encoding()
+    }
+
+    fn recipe(step: &Step<TlsQueryMatcher>) -> &Term<TlsQueryMatcher> {
+        match &step.action {
+            Action::Input(input) => &input.recipe,
+            _ => panic!("expected input"),
+        }
+    }
+
+    #[test_log::test]
+    fn test_import_client_hello() {
+        let client_data = encode_client_hello(ClientHelloPayload {
+            client_version: ProtocolVersion::TLSv1_2,
+            random: fn_new_random().unwrap(),
+            session_id: SessionID::empty(),
+            cipher_suites: vec![CipherSuite::TLS13_AES_128_GCM_SHA256],
+            compression_methods: fn_compressions().unwrap(),
+            extensions: vec![
+                fn_key_share_deterministic_extension(&NamedGroup::secp384r1).unwrap(),
+                fn_supported_versions13_extension().unwrap(),
+            ],
+        });
+
+        let trace = import_client_data(&client_data).unwrap();
+        assert_eq!(trace.descriptors[0].tls_version, TLSVersion::V1_3);
+        assert_eq!(trace.steps.len(), 2);
+
+        let expected = term! {
+            fn_client_hello(
+                fn_protocol_version12,
+                fn_new_random,
+                fn_empty_session_id,
+                (fn_append_cipher_suite(
+                    (fn_new_cipher_suites()),
+                    fn_cipher_suite13_aes_128_gcm_sha256
+                )),
+                (fn_append_compression(fn_new_compressions, fn_compression)),
+                (fn_client_extensions_append(
+                    (fn_client_extensions_append(
+                        fn_client_extensions_new,
+                        (fn_key_share_deterministic_extension(fn_named_group_secp384r1))
+                    )),
+                    fn_supported_versions13_extension
+                ))
+            )
+        };
+        assert_eq!(recipe(&trace.steps[0]), &expected);
+
+        assert!(import_client_data(&[0x16, 0x03, 0x01]).is_err());
+    }
+
+    #[test_log::test]
+    fn test_import_roundtrip_with_unknown_values() {
+        let mut client_data = encode_client_hello(ClientHelloPayload {
+            client_version: ProtocolVersion::TLSv1_2,
+            random: fn_new_random().unwrap(),
+            session_id: SessionID::empty(),
+            cipher_suites: vec![
+                CipherSuite::Unknown(0x0a0a), // GREASE
+                CipherSuite::TLS13_AES_128_GCM_SHA256,
+            ],
+            compression_methods: fn_compressions().unwrap(),
+            extensions: vec![
+                ClientExtension::Unknown(UnknownExtension {
+                    typ: ExtensionType::Unknown(0x4a4a), // GREASE
+                    payload: Payload::new([1, 2, 3]),
+                }),
+                fn_supported_versions13_extension().unwrap(),
+            ],
+        });
+        let encrypted = OpaqueMessage {
+            typ: ContentType::ApplicationData,
+            version: ProtocolVersion::TLSv1_2,
+            payload: Payload::new([0xde, 0xad, 0xbe, 0xef]),
+        };
+        client_data.extend(encrypted.get_encoding());
+
+        let trace = import_client_data(&client_data).unwrap();
+        assert_eq!(trace.steps.len(), 4);
+
+        // The rebuilt records evaluate to the captured bytes
+        let ctx = TraceContext::new(Spawner::new(tls_registry()));
+        let client_hello = recipe(&trace.steps[0]).evaluate(&ctx).unwrap();
+        let record = recipe(&trace.steps[2]).evaluate(&ctx).unwrap();
+
+        let mut reencoded = client_hello
+            .downcast_ref::<Message>()
+            .unwrap()
+            .create_opaque()
+            .get_encoding();
+        reencoded.extend(
+            record
+                .downcast_ref::<OpaqueMessage>()
+                .unwrap()
+                .get_encoding(),
+        );
+        assert_eq!(reencoded, client_data);
+    }
+}
@@ -8,8 +8,8 @@ use crate::tls::rustls::key_log::NoKeyLog;
 use crate::tls::rustls::msgs::enums::NamedGroup;
 use crate::tls::rustls::suites::SupportedCipherSuite;
 use crate::tls::rustls::tls13::key_schedule::{
-    KeyScheduleEarly, KeyScheduleHandshake, KeyScheduleHandshakeStart, KeySchedulePreHandshake,
-    KeyScheduleTrafficWithClientFinishedPending,
+    hkdf_expand, KeyScheduleEarly, KeyScheduleHandshake, KeyScheduleHandshakeStart,
+    KeySchedulePreHandshake, KeyScheduleTrafficWithClientFinishedPending,
 };
 
 pub fn tls13_handshake_traffic_secret(
@@ -36,6 +36,27 @@ pub fn tls13_handshake_traffic_secret(
     ))
 }
 
+pub fn tls13_early_traffic_secret(
+    client_hello: &HandshakeHash,
+    psk: &[u8],
+) -> Result<(&'static SupportedCipherSuite, Prk), FnError> {
+    let client_random = &[1u8; 32]; // todo see op_random() https://github.com/tlspuffin/tlspuffin/issues/129
+    let suite = &crate::tls::rustls::tls13::TLS13_AES_128_GCM_SHA256; // todo see op_cipher_suites() https://github.com/tlspuffin/tlspuffin/issues/129
+    let hkdf_algorithm = suite
+        .tls13()
+        .ok_or_else(|| FnError::Crypto("No tls 1.3 suite".to_owned()))?
+        .hkdf_algorithm;
+
+    let early = KeyScheduleEarly::new(hkdf_algorithm, psk);
+    let client_secret = early.client_early_traffic_secret(
+        &client_hello.get_current_hash(),
+        &NoKeyLog {},
+        client_random,
+    );
+
+    Ok((suite, client_secret))
+}
+
 pub fn tls13_application_traffic_secret(
     server_hello: &HandshakeHash,
     server_finished: &HandshakeHash,
@@ -68,6 +89,25 @@ pub fn tls13_application_traffic_secret(
     ))
 }
 
+/// Ratchets the application traffic `secret` forward by `generation` KeyUpdates, see
+/// [RFC 8446, Section 7.2](https://datatracker.ietf.org/doc/html/rfc8446#section-7.2).
+pub fn tls13_update_traffic_secret(
+    suite: &SupportedCipherSuite,
+    secret: Prk,
+    generation: u64,
+) -> Result<Prk, FnError> {
+    let hkdf_algorithm = suite
+        .tls13()
+        .ok_or_else(|| FnError::Crypto("No tls 1.3 suite".to_owned()))?
+        .hkdf_algorithm;
+
+    let mut secret = secret;
+    for _ in 0..generation {
+        secret = hkdf_expand(&secret, hkdf_algorithm, b"traffic upd", &[]);
+    }
+    Ok(secret)
+}
+
 pub fn tls13_derive_psk(
     server_hello: &HandshakeHash,
     server_finished: &HandshakeHash,
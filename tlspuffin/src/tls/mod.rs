@@ -9,11 +9,14 @@ use puffin::algebra::error::FnError;
 use puffin::define_signature;
 use puffin::error::Error;
 
+mod der;
+pub mod import;
 mod key_exchange;
 mod key_schedule;
 
 pub mod rustls;
 pub mod seeds;
+pub mod transcript_policy;
 pub mod violation;
 pub mod vulnerabilities;
 
@@ -24,6 +27,7 @@ pub mod fn_impl {
     pub mod fn_constants;
     pub mod fn_extensions;
     pub mod fn_fields;
+    pub mod fn_http;
     pub mod fn_messages;
     pub mod fn_transcript;
     pub mod fn_utils;
@@ -32,6 +36,7 @@ pub mod fn_impl {
     pub use fn_constants::*;
     pub use fn_extensions::*;
     pub use fn_fields::*;
+    pub use fn_http::*;
     pub use fn_messages::*;
     pub use fn_transcript::*;
     pub use fn_utils::*;
@@ -39,7 +44,11 @@ pub mod fn_impl {
 
 impl From<rustls::error::Error> for Error {
     fn from(error: rustls::error::Error) -> Self {
-        Error::Stream(error.to_string())
+        match error {
+            rustls::error::Error::CorruptMessage
+            | rustls::error::Error::CorruptMessagePayload(_) => Error::Codec(error.to_string()),
+            _ => Error::Stream(error.to_string()),
+        }
     }
 }
 
@@ -81,9 +90,52 @@ define_signature!(
     fn_seq_16
     fn_large_length
     fn_empty_bytes_vec
+    fn_append_byte
     fn_large_bytes_vec
+    fn_max_u8_length
+    fn_max_u16_length
+    fn_max_record_length
+    fn_length_plus_one
+    fn_bytes_of_length
     // messages
     fn_alert_close_notify
+    fn_alert
+    fn_alert_level_warning
+    fn_alert_level_fatal
+    fn_alert_description_close_notify
+    fn_alert_description_unexpected_message
+    fn_alert_description_bad_record_mac
+    fn_alert_description_decryption_failed
+    fn_alert_description_record_overflow
+    fn_alert_description_decompression_failure
+    fn_alert_description_handshake_failure
+    fn_alert_description_no_certificate
+    fn_alert_description_bad_certificate
+    fn_alert_description_unsupported_certificate
+    fn_alert_description_certificate_revoked
+    fn_alert_description_certificate_expired
+    fn_alert_description_certificate_unknown
+    fn_alert_description_illegal_parameter
+    fn_alert_description_unknown_ca
+    fn_alert_description_access_denied
+    fn_alert_description_decode_error
+    fn_alert_description_decrypt_error
+    fn_alert_description_export_restriction
+    fn_alert_description_protocol_version
+    fn_alert_description_insufficient_security
+    fn_alert_description_internal_error
+    fn_alert_description_inappropriate_fallback
+    fn_alert_description_user_canceled
+    fn_alert_description_no_renegotiation
+    fn_alert_description_missing_extension
+    fn_alert_description_unsupported_extension
+    fn_alert_description_certificate_unobtainable
+    fn_alert_description_unrecognised_name
+    fn_alert_description_bad_certificate_status_response
+    fn_alert_description_bad_certificate_hash_value
+    fn_alert_description_unknown_psk_identity
+    fn_alert_description_certificate_required
+    fn_alert_description_no_application_protocol
     fn_application_data
     fn_certificate
     fn_certificate13
@@ -92,13 +144,19 @@ define_signature!(
     fn_certificate_status
     fn_certificate_verify
     fn_change_cipher_spec
+    fn_opaque_change_cipher_spec
     fn_client_hello
     fn_client_key_exchange
     fn_empty_handshake_message
     fn_encrypted_extensions
+    fn_end_of_early_data
     fn_finished
     fn_heartbeat
     fn_heartbeat_fake_length
+    fn_heartbeat_message
+    fn_heartbeat_request_type
+    fn_heartbeat_response_type
+    fn_heartbeat_response
     fn_hello_request
     fn_hello_retry_request
     fn_key_update
@@ -107,14 +165,17 @@ define_signature!(
     fn_new_session_ticket
     fn_new_session_ticket13
     fn_opaque_message
+    fn_decode_opaque_message
     fn_server_hello
     fn_server_hello_done
     fn_server_key_exchange
     // extensions
     fn_client_extensions_new
     fn_client_extensions_append
+    fn_client_extensions_repeat
     fn_server_extensions_new
     fn_server_extensions_append
+    fn_has_server_extension
     fn_hello_retry_extensions_new
     fn_hello_retry_extensions_append
     fn_cert_req_extensions_new
@@ -125,18 +186,27 @@ define_signature!(
     fn_new_session_ticket_extensions_append
     fn_server_name_extension
     fn_server_name_server_extension
+    fn_sni_extension
+    fn_sni_host_localhost
     fn_status_request_extension
     fn_status_request_server_extension
     fn_status_request_certificate_extension
     fn_support_group_extension
     fn_ec_point_formats_extension
     fn_ec_point_formats_server_extension
+    fn_heartbeat_extension
+    fn_heartbeat_server_extension
+    fn_heartbeat_mode_peer_allowed_to_send
+    fn_heartbeat_mode_peer_not_allowed_to_send
     fn_signature_algorithm_extension
     fn_signature_algorithm_cert_req_extension
     fn_empty_vec_of_vec
     fn_append_vec
     fn_al_protocol_negotiation
     fn_al_protocol_server_negotiation
+    fn_alpn_extension
+    fn_alpn_protocol_h2
+    fn_alpn_protocol_http11
     fn_signed_certificate_timestamp_extension
     fn_signed_certificate_timestamp_server_extension
     fn_signed_certificate_timestamp_certificate_extension
@@ -176,6 +246,7 @@ define_signature!(
     fn_renegotiation_info_server_extension
     fn_transport_parameters_draft_extension
     fn_transport_parameters_draft_server_extension
+    fn_decode_client_extension
     fn_unknown_client_extension
     fn_unknown_server_extension
     fn_unknown_hello_retry_extension
@@ -187,18 +258,31 @@ define_signature!(
     fn_protocol_version12
     fn_new_session_id
     fn_empty_session_id
+    fn_session_id
+    fn_max_session_id
     fn_new_random
     fn_compressions
     fn_compression
+    fn_new_compressions
+    fn_append_compression
+    fn_compression_deflate
+    fn_compression_lsz
+    fn_decode_compression
     fn_no_key_share
     fn_get_server_key_share
+    fn_get_renegotiation_info
     fn_get_client_key_share
+    fn_get_hello_retry_group
+    fn_get_hello_retry_cookie
     fn_get_any_client_curve
     fn_verify_data
     fn_verify_data_server
     fn_sign_transcript
+    fn_sign_transcript_server
+    fn_renegotiation_info_data
     fn_new_cipher_suites
     fn_append_cipher_suite
+    fn_decode_cipher_suite
     fn_cipher_suite12
     fn_cipher_suite13_aes_128_gcm_sha256
     fn_cipher_suite13_aes_256_gcm_sha384
@@ -210,22 +294,35 @@ define_signature!(
     fn_append_flight
     fn_new_opaque_flight
     fn_append_opaque_flight
+    fn_fragment
+    fn_coalesce
+    fn_coalesce_opaque
     fn_new_transcript
     fn_append_transcript
+    fn_hello_retry_rollup_transcript
     fn_decrypt_handshake_flight
     fn_decrypt_multiple_handshake_messages
     fn_decrypt_application_flight
     fn_find_server_certificate
     fn_find_server_certificate_request
     fn_find_server_ticket
+    fn_find_nth_server_ticket
     fn_find_server_certificate_verify
     fn_find_encrypted_extensions
+    fn_get_encrypted_extensions
     fn_find_server_finished
     fn_no_psk
     fn_psk
     fn_decrypt_application
+    fn_decrypt_application_updated
     fn_encrypt_handshake
     fn_encrypt_application
+    fn_encrypt_application_updated
+    fn_encrypt_server_application
+    fn_pad_record
+    fn_encrypt_handshake_padded
+    fn_encrypt_application_padded
+    fn_encrypt_early_data
     fn_derive_psk
     fn_derive_binder
     fn_fill_binder
@@ -233,6 +330,7 @@ define_signature!(
     fn_get_ticket_age_add
     fn_get_ticket_nonce
     fn_new_transcript12
+    fn_new_transcript12_client_auth
     fn_decode_ecdh_pubkey
     fn_encode_ec_pubkey12
     fn_new_pubkey12
@@ -257,17 +355,46 @@ define_signature!(
     fn_alice_key
     fn_eve_cert
     fn_random_ec_cert
+    fn_certificate_from_der
     fn_certificate_entry
     fn_empty_certificate_chain
     fn_chain_append_certificate_entry
+    fn_truncate_certificate_chain
+    fn_truncate_certificates
+    fn_certificate_der
+    fn_x509_with_signature_algorithm
+    fn_x509_append_extension
+    fn_x509_unknown_extension
+    fn_x509_self_signed
     fn_get_context
     fn_eve_pkcs1_signature
     fn_rsa_sign_client
     fn_rsa_sign_server
     fn_ecdsa_sign_client
     fn_ecdsa_sign_server
+    fn_rsa_sign12
+    fn_ecdsa_sign12
     fn_rsa_pss_signature_algorithm
     fn_rsa_pkcs1_signature_algorithm
     fn_invalid_signature_algorithm
     fn_ecdsa_signature_algorithm
+    // http
+    fn_http_method_get
+    fn_http_method_post
+    fn_http_path_root
+    fn_http_header_host
+    fn_http_header_content_length
+    fn_http_header_transfer_encoding
+    fn_http_localhost
+    fn_http_chunked
+    fn_http_headers_new
+    fn_http_headers_append
+    fn_http_content_length
+    fn_http_chunked_body
+    fn_http_request
+    fn_http_bare_lf
+    fn_http_pipeline;
+    // projections
+    client_hello_projections
+    server_hello_projections
 );
@@ -92,10 +92,12 @@ impl From<[u8; 32]> for Random {
     }
 }
 
+/// The session id of a legacy session. Peers must not send ids which are longer than 32 bytes,
+/// but the attacker can construct ids up to the maximum encodable length of 255 bytes.
 #[derive(Copy, Clone)]
 pub struct SessionID {
     len: usize,
-    data: [u8; 32],
+    data: [u8; 255],
 }
 
 impl fmt::Debug for SessionID {
@@ -123,7 +125,6 @@ impl PartialEq for SessionID {
 
 impl Codec for SessionID {
     fn encode(&self, bytes: &mut Vec<u8>) {
-        debug_assert!(self.len <= 32);
         bytes.push(self.len as u8);
         bytes.extend_from_slice(&self.data[..self.len]);
     }
@@ -135,27 +136,38 @@ impl Codec for SessionID {
         }
 
         let bytes = r.take(len)?;
-        let mut out = [0u8; 32];
-        out[..len].clone_from_slice(&bytes[..len]);
-
-        Some(Self { data: out, len })
+        Self::from_slice(bytes)
     }
 }
 
 impl SessionID {
     pub fn random() -> Result<Self, rand::GetRandomFailed> {
-        let mut data = [0u8; 32];
-        rand::fill_random(&mut data)?;
+        let mut data = [0u8; 255];
+        rand::fill_random(&mut data[..32])?;
         Ok(Self { data, len: 32 })
     }
 
     pub fn empty() -> Self {
         Self {
-            data: [0u8; 32],
+            data: [0u8; 255],
             len: 0,
         }
     }
 
+    /// Creates a session id of up to 255 bytes, which is longer than peers are allowed to send.
+    pub fn from_slice(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() > 255 {
+            return None;
+        }
+
+        let mut data = [0u8; 255];
+        data[..bytes.len()].clone_from_slice(bytes);
+        Some(Self {
+            data,
+            len: bytes.len(),
+        })
+    }
+
     pub fn len(&self) -> usize {
         self.len
     }
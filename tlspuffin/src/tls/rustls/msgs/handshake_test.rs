@@ -37,6 +37,15 @@ fn rejects_sessionid_with_bad_length() {
     assert_eq!(SessionID::read(&mut rd), None);
 }
 
+#[test_log::test]
+fn encodes_oversized_sessionid() {
+    let id = SessionID::from_slice(&[1; 255]).unwrap();
+    let bytes = id.get_encoding();
+    assert_eq!(bytes.len(), 256);
+    assert_eq!(bytes[0], 255);
+    assert!(SessionID::from_slice(&[1; 256]).is_none());
+}
+
 #[test_log::test]
 fn sessionid_with_different_lengths_are_unequal() {
     let a = SessionID::read(&mut Reader::init(&[1u8, 1])).unwrap();
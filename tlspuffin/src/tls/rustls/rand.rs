@@ -4,8 +4,14 @@ use puffin::codec;
 /// they panic on error.
 use ring::rand::{SecureRandom, SystemRandom};
 
-/// Fill the whole slice with random material.
+/// Fill the whole slice with random material. During the evaluation of a
+/// function symbol, the material comes from the RNG of the execution, see
+/// [`puffin::rng::fill_bytes`], and is therefore reproducible.
 pub fn fill_random(bytes: &mut [u8]) -> Result<(), GetRandomFailed> {
+    if puffin::rng::fill_bytes(bytes) {
+        return Ok(());
+    }
+
     SystemRandom::new().fill(bytes).map_err(|_| GetRandomFailed)
 }
 
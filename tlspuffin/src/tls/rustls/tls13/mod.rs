@@ -68,12 +68,23 @@ pub struct Tls13CipherSuite {
 
 impl Tls13CipherSuite {
     pub fn derive_encrypter(&self, secret: &hkdf::Prk) -> Box<dyn MessageEncrypter> {
+        self.derive_padding_encrypter(secret, 0)
+    }
+
+    /// Derive a `MessageEncrypter` which appends `padding` zero bytes to the plaintext of each
+    /// record (RFC 8446, Section 5.4).
+    pub fn derive_padding_encrypter(
+        &self,
+        secret: &hkdf::Prk,
+        padding: usize,
+    ) -> Box<dyn MessageEncrypter> {
         let key = derive_traffic_key(secret, self.common.aead_algorithm);
         let iv = derive_traffic_iv(secret);
 
         Box::new(Tls13MessageEncrypter {
             enc_key: aead::LessSafeKey::new(key),
             iv,
+            padding,
         })
     }
 
@@ -124,6 +135,7 @@ impl fmt::Debug for Tls13CipherSuite {
 struct Tls13MessageEncrypter {
     enc_key: aead::LessSafeKey,
     iv: Iv,
+    padding: usize,
 }
 
 struct Tls13MessageDecrypter {
@@ -156,10 +168,11 @@ const TLS13_AAD_SIZE: usize = 1 + 2 + 2;
 
 impl MessageEncrypter for Tls13MessageEncrypter {
     fn encrypt(&self, msg: BorrowedPlainMessage, seq: u64) -> Result<OpaqueMessage, Error> {
-        let total_len = msg.payload.len() + 1 + self.enc_key.algorithm().tag_len();
+        let total_len = msg.payload.len() + 1 + self.padding + self.enc_key.algorithm().tag_len();
         let mut payload = Vec::with_capacity(total_len);
         payload.extend_from_slice(msg.payload);
         msg.typ.encode(&mut payload);
+        payload.resize(payload.len() + self.padding, 0);
 
         let nonce = make_nonce(&self.iv, seq);
         let aad = make_tls13_aad(total_len);
@@ -252,6 +252,41 @@ pub fn seed_successful_mitm(client: AgentName, server: AgentName) -> Trace<TlsQu
     }
 }
 
+/// Seed with two sessions which are relayed by the attacker. The ClientHello of the client is sent
+/// to `server` and `other_server`, but only `server` completes the handshake with the client. The
+/// Finished of the client is relayed to `other_server` as well, which must reject it because it
+/// does not match the transcript of its session.
+pub fn seed_cross_session_relay(
+    client: AgentName,
+    server: AgentName,
+    other_server: AgentName,
+) -> Trace<TlsQueryMatcher> {
+    let mut trace = seed_successful(client, server);
+    trace
+        .descriptors
+        .push(AgentDescriptor::new_server(other_server, TLSVersion::V1_3));
+
+    // Client Hello Client -> other server
+    trace.steps.insert(
+        2,
+        InputAction::new_step(
+            other_server,
+            term! {
+                (client, 0)/MessageFlight
+            },
+        ),
+    );
+    // Client Finished of the first session -> other server
+    trace.steps.push(InputAction::new_step(
+        other_server,
+        term! {
+            (client, 1)/MessageFlight
+        },
+    ));
+
+    trace
+}
+
 // TODO: `[RENEGOTIATION_MISMATCH] [ERROR_PARSING_EXTENSION] [PARSE_TLSEXT]` error with BoringSSL
 pub fn seed_successful12_with_tickets(
     client: AgentName,
@@ -752,6 +787,38 @@ pub fn seed_server_attacker_full(client: AgentName) -> Trace<TlsQueryMatcher> {
 
 // TODO: `BAD_SIGNATURE` error with BoringSSL
 pub fn seed_client_attacker_auth(server: AgentName) -> Trace<TlsQueryMatcher> {
+    client_auth_handshake(server, Vec::from)
+}
+
+/// Like [`seed_client_attacker_auth`], but the Certificate, CertificateVerify and Finished
+/// messages are sent as a single flight like an honest client would do.
+pub fn seed_client_auth(server: AgentName) -> Trace<TlsQueryMatcher> {
+    client_auth_handshake(
+        server,
+        |[certificate, certificate_verify, client_finished]| {
+            vec![term! {
+                fn_append_opaque_flight(
+                    (fn_append_opaque_flight(
+                        (fn_append_opaque_flight(
+                            fn_new_opaque_flight,
+                            (@certificate)
+                        )),
+                        (@certificate_verify)
+                    )),
+                    (@client_finished)
+                )
+            }]
+        },
+    )
+}
+
+/// A TLS 1.3 handshake in which the client authenticates with the certificate of Bob.
+/// `client_flights` groups the encrypted Certificate, CertificateVerify and Finished messages
+/// into the inputs which follow the ClientHello.
+fn client_auth_handshake(
+    server: AgentName,
+    client_flights: impl FnOnce([Term<TlsQueryMatcher>; 3]) -> Vec<Term<TlsQueryMatcher>>,
+) -> Trace<TlsQueryMatcher> {
     let client_hello = term! {
           fn_client_hello(
             fn_protocol_version12,
@@ -830,6 +897,25 @@ pub fn seed_client_attacker_auth(server: AgentName) -> Trace<TlsQueryMatcher> {
         )
     };
 
+    let encrypt = |message: Term<TlsQueryMatcher>, sequence: Term<TlsQueryMatcher>| {
+        term! {
+            fn_encrypt_handshake(
+                (@message),
+                (fn_server_hello_transcript(((server, 0)))),
+                (fn_get_server_key_share(((server, 0)))),
+                fn_no_psk,
+                fn_named_group_secp384r1,
+                fn_true,
+                (@sequence)
+            )
+        }
+    };
+    let client_messages = [
+        encrypt(certificate, term! { fn_seq_0 }),
+        encrypt(certificate_verify, term! { fn_seq_1 }),
+        encrypt(client_finished, term! { fn_seq_2 }),
+    ];
+
     Trace {
         prior_traces: vec![],
         descriptors: vec![AgentDescriptor {
@@ -839,64 +925,10 @@ pub fn seed_client_attacker_auth(server: AgentName) -> Trace<TlsQueryMatcher> {
             client_authentication: true,
             ..AgentDescriptor::default()
         }],
-        steps: vec![
-            Step {
-                agent: server,
-                action: Action::Input(InputAction {
-                    recipe: term! {
-                        @client_hello
-                    },
-                }),
-            },
-            Step {
-                agent: server,
-                action: Action::Input(InputAction {
-                    recipe: term! {
-                        fn_encrypt_handshake(
-                            (@certificate),
-                            (fn_server_hello_transcript(((server, 0)))),
-                            (fn_get_server_key_share(((server, 0)))),
-                            fn_no_psk,
-                            fn_named_group_secp384r1,
-                            fn_true,
-                            fn_seq_0  // sequence 0
-                        )
-                    },
-                }),
-            },
-            Step {
-                agent: server,
-                action: Action::Input(InputAction {
-                    recipe: term! {
-                         fn_encrypt_handshake(
-                            (@certificate_verify),
-                            (fn_server_hello_transcript(((server, 0)))),
-                            (fn_get_server_key_share(((server, 0)))),
-                            fn_no_psk,
-                            fn_named_group_secp384r1,
-                            fn_true,
-                            fn_seq_1  // sequence 1
-                        )
-                    },
-                }),
-            },
-            Step {
-                agent: server,
-                action: Action::Input(InputAction {
-                    recipe: term! {
-                        fn_encrypt_handshake(
-                            (@client_finished),
-                            (fn_server_hello_transcript(((server, 0)))),
-                            (fn_get_server_key_share(((server, 0)))),
-                            fn_no_psk,
-                            fn_named_group_secp384r1,
-                            fn_true,
-                            fn_seq_2  // sequence 2
-                        )
-                    },
-                }),
-            },
-        ],
+        steps: std::iter::once(client_hello)
+            .chain(client_flights(client_messages))
+            .map(|recipe| InputAction::new_step(server, recipe))
+            .collect(),
     }
 }
 
@@ -972,6 +1004,212 @@ pub fn seed_client_attacker(server: AgentName) -> Trace<TlsQueryMatcher> {
     }
 }
 
+/// The attacker sends a first ClientHello without a key share, such that the server asks for one
+/// with a HelloRetryRequest. The second ClientHello contains a key share for the requested group.
+pub fn seed_hello_retry_request(server: AgentName) -> Trace<TlsQueryMatcher> {
+    let client_hello = |key_share: Option<Term<TlsQueryMatcher>>| {
+        let extensions = term! {
+            fn_client_extensions_append(
+                (fn_client_extensions_append(
+                    fn_client_extensions_new,
+                    (fn_support_group_extension(fn_named_group_secp384r1))
+                )),
+                fn_signature_algorithm_extension
+            )
+        };
+        let extensions = match key_share {
+            Some(key_share) => term! {
+                fn_client_extensions_append(
+                    (@extensions),
+                    (@key_share)
+                )
+            },
+            None => extensions,
+        };
+
+        term! {
+            fn_client_hello(
+                fn_protocol_version12,
+                fn_new_random,
+                fn_new_session_id,
+                (fn_append_cipher_suite(
+                    (fn_new_cipher_suites()),
+                    fn_cipher_suite13_aes_128_gcm_sha256
+                )),
+                fn_compressions,
+                (fn_client_extensions_append(
+                    (@extensions),
+                    fn_supported_versions13_extension
+                ))
+            )
+        }
+    };
+
+    let first_client_hello = client_hello(None);
+    let second_client_hello = client_hello(Some(term! {
+        fn_key_share_deterministic_extension((fn_get_hello_retry_group(((server, 0)))))
+    }));
+
+    let client_finished = term! {
+        fn_finished(
+            (fn_verify_data(
+                (fn_server_finished_transcript(((server, 0)))),
+                (fn_server_hello_transcript(((server, 0)))),
+                (fn_get_server_key_share(((server, 0)))),
+                fn_no_psk,
+                fn_named_group_secp384r1
+            ))
+        )
+    };
+
+    Trace {
+        prior_traces: vec![],
+        descriptors: vec![AgentDescriptor::new_server(server, TLSVersion::V1_3)],
+        steps: vec![
+            Step {
+                agent: server,
+                action: Action::Input(InputAction {
+                    recipe: first_client_hello,
+                }),
+            },
+            Step {
+                agent: server,
+                action: Action::Input(InputAction {
+                    recipe: second_client_hello,
+                }),
+            },
+            Step {
+                agent: server,
+                action: Action::Input(InputAction {
+                    recipe: term! {
+                        fn_encrypt_handshake(
+                            (@client_finished),
+                            (fn_server_hello_transcript(((server, 0)))),
+                            (fn_get_server_key_share(((server, 0)))),
+                            fn_no_psk,
+                            fn_named_group_secp384r1,
+                            fn_true,
+                            fn_seq_0  // sequence 0
+                        )
+                    },
+                }),
+            },
+            OutputAction::new_step(server),
+        ],
+    }
+}
+
+/// Inserts a step at `position` in which `agent` receives a ChangeCipherSpec.
+pub fn insert_change_cipher_spec(
+    trace: &mut Trace<TlsQueryMatcher>,
+    agent: AgentName,
+    position: usize,
+) {
+    trace.steps.insert(
+        position,
+        Step {
+            agent,
+            action: Action::Input(InputAction {
+                recipe: term! { fn_change_cipher_spec },
+            }),
+        },
+    );
+}
+
+/// The attacker sends a ChangeCipherSpec between its ClientHello and its encrypted Finished, like
+/// a client in middlebox compatibility mode. The server has to ignore it.
+pub fn seed_client_attacker_middlebox_compat(server: AgentName) -> Trace<TlsQueryMatcher> {
+    let mut trace = seed_client_attacker(server);
+    insert_change_cipher_spec(&mut trace, server, 1);
+    trace
+}
+
+/// The attacker sends a ChangeCipherSpec before its ClientHello. A TLS 1.3 server must not accept
+/// a ChangeCipherSpec before the first ClientHello.
+pub fn seed_early_change_cipher_spec(server: AgentName) -> Trace<TlsQueryMatcher> {
+    let mut trace = seed_client_attacker(server);
+    insert_change_cipher_spec(&mut trace, server, 0);
+    trace
+}
+
+/// The attacker splits its ClientHello into records of 16 bytes each.
+pub fn seed_client_attacker_fragmented(server: AgentName) -> Trace<TlsQueryMatcher> {
+    let mut trace = seed_client_attacker(server);
+    if let Action::Input(input) = &mut trace.steps[0].action {
+        let client_hello = input.recipe.clone();
+        input.recipe = term! {
+            fn_fragment(
+                (@client_hello),
+                fn_seq_16
+            )
+        };
+    }
+    trace
+}
+
+/// The attacker requests a server name and offers the application protocol h2, such that the
+/// servername and ALPN selection callbacks of the server are invoked.
+pub fn seed_client_attacker_alpn_sni(server: AgentName) -> Trace<TlsQueryMatcher> {
+    let mut trace = seed_client_attacker(server);
+    if let Action::Input(input) = &mut trace.steps[0].action {
+        if let Term::Application(_, arguments) = &mut input.recipe {
+            let extensions = arguments[5].clone();
+            arguments[5] = term! {
+                fn_client_extensions_append(
+                    (fn_client_extensions_append(
+                        (@extensions),
+                        (fn_sni_extension(fn_sni_host_localhost))
+                    )),
+                    (fn_alpn_extension(fn_alpn_protocol_h2))
+                )
+            };
+        }
+    }
+    trace
+}
+
+/// The attacker closes the connection with a close_notify alert right after the server sent its
+/// ServerHello, and then continues the handshake.
+pub fn seed_close_notify_after_server_hello(server: AgentName) -> Trace<TlsQueryMatcher> {
+    let mut trace = seed_client_attacker(server);
+    trace.steps.insert(
+        1,
+        Step {
+            agent: server,
+            action: Action::Input(InputAction {
+                recipe: term! {
+                    fn_alert(
+                        fn_alert_level_warning,
+                        fn_alert_description_close_notify
+                    )
+                },
+            }),
+        },
+    );
+    trace
+}
+
+/// The attacker sends a warning alert between the ServerHelloDone and the ClientKeyExchange.
+/// Warning alerts do not necessarily abort the handshake in TLS 1.2.
+pub fn seed_warning_alert12(server: AgentName) -> Trace<TlsQueryMatcher> {
+    let mut trace = seed_client_attacker12(server);
+    trace.steps.insert(
+        1,
+        Step {
+            agent: server,
+            action: Action::Input(InputAction {
+                recipe: term! {
+                    fn_alert(
+                        fn_alert_level_warning,
+                        fn_alert_description_user_canceled
+                    )
+                },
+            }),
+        },
+    );
+    trace
+}
+
 pub fn seed_client_attacker12(server: AgentName) -> Trace<TlsQueryMatcher> {
     _seed_client_attacker12(server).0
 }
@@ -1116,74 +1354,445 @@ pub fn _seed_client_attacker12(
     (trace, client_verify_data)
 }
 
-// TODO: `"Unable to find variable (Some(Agent(AgentName(0))), 1)[None]/MessageFlight!"` error with
-// BoringSSL
-pub fn seed_session_resumption_dhe(
-    initial_server: AgentName,
-    server: AgentName,
-) -> Trace<TlsQueryMatcher> {
-    let initial_handshake = seed_client_attacker(initial_server);
-
-    let extensions = term! {
-        fn_decrypt_application_flight(
-            ((initial_server, 1)/MessageFlight), // The first flight of messages sent by the server
-            (fn_server_hello_transcript(((initial_server, 0)))),
-            (fn_server_finished_transcript(((initial_server, 0)))),
-            (fn_get_server_key_share(((initial_server, 0)))),
-            fn_no_psk,
-            fn_named_group_secp384r1,
-            fn_true,
-            fn_seq_0  // sequence 0
-        )
-    };
+/// After a full TLS 1.2 handshake the attacker renegotiates with a ClientHello which is bound to
+/// the previous handshake by the client verify_data in its renegotiation_info (RFC 5746).
+pub fn seed_client_attacker_renegotiation12(server: AgentName) -> Trace<TlsQueryMatcher> {
+    let (trace, client_verify_data) = _seed_client_attacker12(server);
+    _seed_renegotiation12(server, trace, client_verify_data)
+}
 
-    let new_ticket_message = term! {fn_find_server_ticket((@extensions))};
+/// Like [`seed_client_attacker_renegotiation12`], but the renegotiation_info is empty as in an
+/// initial handshake. The server must refuse such an insecure renegotiation.
+pub fn seed_insecure_renegotiation12(server: AgentName) -> Trace<TlsQueryMatcher> {
+    let (trace, _) = _seed_client_attacker12(server);
+    _seed_renegotiation12(server, trace, term! { fn_empty_bytes_vec })
+}
 
-    let client_hello = term! {
+fn _seed_renegotiation12(
+    server: AgentName,
+    mut trace: Trace<TlsQueryMatcher>,
+    renegotiation_info: Term<TlsQueryMatcher>,
+) -> Trace<TlsQueryMatcher> {
+    let renegotiation_client_hello = term! {
           fn_client_hello(
             fn_protocol_version12,
             fn_new_random,
             fn_new_session_id,
             (fn_append_cipher_suite(
                 (fn_new_cipher_suites()),
-                fn_cipher_suite13_aes_128_gcm_sha256
+                fn_cipher_suite12
             )),
             fn_compressions,
             (fn_client_extensions_append(
                 (fn_client_extensions_append(
                     (fn_client_extensions_append(
                         (fn_client_extensions_append(
-                            (fn_client_extensions_append(
-                                (fn_client_extensions_append(
-                                    fn_client_extensions_new,
-                                    (fn_support_group_extension(fn_named_group_secp384r1))
-                                )),
-                                fn_signature_algorithm_extension
-                            )),
-                            fn_supported_versions13_extension
+                            fn_client_extensions_new,
+                            (fn_support_group_extension(fn_named_group_secp384r1))
                         )),
-                        (fn_key_share_deterministic_extension(fn_named_group_secp384r1))
+                        fn_signature_algorithm_extension
                     )),
-                    fn_psk_exchange_mode_dhe_ke_extension
+                    fn_ec_point_formats_extension
                 )),
-                // https://datatracker.ietf.org/doc/html/rfc8446#section-2.2
-                // must be last in client_hello, and initially empty until filled by fn_fill_binder
-                (fn_preshared_keys_extension_empty_binder(
-                    (@new_ticket_message)
-                ))
+                (fn_renegotiation_info_extension((@renegotiation_info)))
             ))
         )
     };
 
-    let psk = term! {
-        fn_derive_psk(
-            (fn_server_hello_transcript(((initial_server, 0)))),
-            (fn_server_finished_transcript(((initial_server, 0)))),
-            (fn_client_finished_transcript(((initial_server, 0)))),
-            (fn_get_server_key_share(((initial_server, 0)[Some(TlsQueryMatcher::Handshake(Some(HandshakeType::ServerHello)))]))),
-            (fn_get_ticket_nonce((@new_ticket_message))),
-            fn_named_group_secp384r1
-        )
+    trace.steps.push(Step {
+        agent: server,
+        action: Action::Input(InputAction {
+            recipe: term! {
+                fn_encrypt12(
+                    (@renegotiation_client_hello),
+                    ((server, 0)),
+                    (fn_decode_ecdh_pubkey(
+                        ((server, 0)[Some(TlsQueryMatcher::Handshake(Some(HandshakeType::ServerKeyExchange)))]/Vec<u8>) // ServerECDHParams
+                    )),
+                    fn_named_group_secp384r1,
+                    fn_true,
+                    fn_seq_1
+                )
+            },
+        }),
+    });
+
+    trace
+}
+
+/// TLS 1.2 handshake in which the attacker authenticates as client using the certificate of Bob.
+pub fn seed_client_auth12(server: AgentName) -> Trace<TlsQueryMatcher> {
+    let client_hello = term! {
+          fn_client_hello(
+            fn_protocol_version12,
+            fn_new_random,
+            fn_new_session_id,
+            (fn_append_cipher_suite(
+                (fn_new_cipher_suites()),
+                // force TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256
+                fn_cipher_suite12
+            )),
+            fn_compressions,
+            (fn_client_extensions_append(
+                (fn_client_extensions_append(
+                    (fn_client_extensions_append(
+                        fn_client_extensions_new,
+                        (fn_support_group_extension(fn_named_group_secp384r1))
+                    )),
+                    fn_signature_algorithm_extension
+                )),
+                fn_ec_point_formats_extension
+            ))
+        )
+    };
+
+    let server_hello_done_transcript = term! {
+        fn_append_transcript(
+            (fn_append_transcript(
+                (fn_append_transcript(
+                    (fn_append_transcript(
+                        (fn_append_transcript(
+                            (fn_append_transcript(
+                                fn_new_transcript12_client_auth,
+                                (@client_hello) // ClientHello
+                            )),
+                            ((server, 0)[Some(TlsQueryMatcher::Handshake(Some(HandshakeType::ServerHello)))]) // plaintext ServerHello
+                        )),
+                        ((server, 0)[Some(TlsQueryMatcher::Handshake(Some(HandshakeType::Certificate)))]) // Certificate
+                    )),
+                    ((server, 0)[Some(TlsQueryMatcher::Handshake(Some(HandshakeType::ServerKeyExchange)))]) // ServerKeyExchange
+                )),
+                ((server, 0)[Some(TlsQueryMatcher::Handshake(Some(HandshakeType::CertificateRequest)))]) // CertificateRequest
+            )),
+            ((server, 0)[Some(TlsQueryMatcher::Handshake(Some(HandshakeType::ServerHelloDone)))]) // ServerHelloDone
+        )
+    };
+
+    let certificate = term! {
+        fn_certificate(
+            (fn_append_certificate(
+                fn_new_certificates,
+                (fn_certificate_from_der(fn_bob_cert))
+            ))
+        )
+    };
+
+    let client_key_exchange = term! {
+        fn_client_key_exchange(
+            (fn_encode_ec_pubkey12(
+                (fn_new_pubkey12(fn_named_group_secp384r1))
+            ))
+        )
+    };
+
+    let client_key_exchange_transcript = term! {
+        fn_append_transcript(
+            (fn_append_transcript(
+                (@server_hello_done_transcript),
+                (@certificate)
+            )),
+            (@client_key_exchange)
+        )
+    };
+
+    let certificate_verify = term! {
+        fn_certificate_verify(
+            fn_rsa_pkcs1_signature_algorithm,
+            (fn_rsa_sign12(
+                (@client_key_exchange_transcript),
+                fn_bob_key,
+                fn_rsa_pkcs1_signature_algorithm
+            ))
+        )
+    };
+
+    let client_verify_data = term! {
+        fn_sign_transcript(
+            ((server, 0)),
+            (fn_decode_ecdh_pubkey(
+                ((server, 0)[Some(TlsQueryMatcher::Handshake(Some(HandshakeType::ServerKeyExchange)))]/Vec<u8>) // ServerECDHParams
+            )),
+            (fn_append_transcript(
+                (@client_key_exchange_transcript),
+                (@certificate_verify)
+            )),
+            fn_named_group_secp384r1
+        )
+    };
+
+    Trace {
+        prior_traces: vec![],
+        descriptors: vec![AgentDescriptor {
+            name: server,
+            tls_version: TLSVersion::V1_2,
+            typ: AgentType::Server,
+            client_authentication: true,
+            ..AgentDescriptor::default()
+        }],
+        steps: vec![
+            Step {
+                agent: server,
+                action: Action::Input(InputAction {
+                    recipe: client_hello,
+                }),
+            },
+            Step {
+                agent: server,
+                action: Action::Input(InputAction {
+                    recipe: certificate,
+                }),
+            },
+            Step {
+                agent: server,
+                action: Action::Input(InputAction {
+                    recipe: client_key_exchange,
+                }),
+            },
+            Step {
+                agent: server,
+                action: Action::Input(InputAction {
+                    recipe: certificate_verify,
+                }),
+            },
+            Step {
+                agent: server,
+                action: Action::Input(InputAction {
+                    recipe: term! { fn_change_cipher_spec },
+                }),
+            },
+            Step {
+                agent: server,
+                action: Action::Input(InputAction {
+                    recipe: term! {
+                        fn_encrypt12(
+                            (fn_finished((@client_verify_data))),
+                            ((server, 0)),
+                            (fn_decode_ecdh_pubkey(
+                                ((server, 0)[Some(TlsQueryMatcher::Handshake(Some(HandshakeType::ServerKeyExchange)))]/Vec<u8>) // ServerECDHParams
+                            )),
+                            fn_named_group_secp384r1,
+                            fn_true,
+                            fn_seq_0
+                        )
+                    },
+                }),
+            },
+        ],
+    }
+}
+
+// TODO: `"Unable to find variable (Some(Agent(AgentName(0))), 1)[None]/MessageFlight!"` error with
+// BoringSSL
+pub fn seed_session_resumption_dhe(
+    initial_server: AgentName,
+    server: AgentName,
+) -> Trace<TlsQueryMatcher> {
+    let initial_handshake = seed_client_attacker(initial_server);
+
+    let extensions = term! {
+        fn_decrypt_application_flight(
+            ((initial_server, 1)/MessageFlight), // The first flight of messages sent by the server
+            (fn_server_hello_transcript(((initial_server, 0)))),
+            (fn_server_finished_transcript(((initial_server, 0)))),
+            (fn_get_server_key_share(((initial_server, 0)))),
+            fn_no_psk,
+            fn_named_group_secp384r1,
+            fn_true,
+            fn_seq_0  // sequence 0
+        )
+    };
+
+    let new_ticket_message = term! {fn_find_server_ticket((@extensions))};
+
+    let client_hello = term! {
+          fn_client_hello(
+            fn_protocol_version12,
+            fn_new_random,
+            fn_new_session_id,
+            (fn_append_cipher_suite(
+                (fn_new_cipher_suites()),
+                fn_cipher_suite13_aes_128_gcm_sha256
+            )),
+            fn_compressions,
+            (fn_client_extensions_append(
+                (fn_client_extensions_append(
+                    (fn_client_extensions_append(
+                        (fn_client_extensions_append(
+                            (fn_client_extensions_append(
+                                (fn_client_extensions_append(
+                                    fn_client_extensions_new,
+                                    (fn_support_group_extension(fn_named_group_secp384r1))
+                                )),
+                                fn_signature_algorithm_extension
+                            )),
+                            fn_supported_versions13_extension
+                        )),
+                        (fn_key_share_deterministic_extension(fn_named_group_secp384r1))
+                    )),
+                    fn_psk_exchange_mode_dhe_ke_extension
+                )),
+                // https://datatracker.ietf.org/doc/html/rfc8446#section-2.2
+                // must be last in client_hello, and initially empty until filled by fn_fill_binder
+                (fn_preshared_keys_extension_empty_binder(
+                    (@new_ticket_message)
+                ))
+            ))
+        )
+    };
+
+    let psk = term! {
+        fn_derive_psk(
+            (fn_server_hello_transcript(((initial_server, 0)))),
+            (fn_server_finished_transcript(((initial_server, 0)))),
+            (fn_client_finished_transcript(((initial_server, 0)))),
+            (fn_get_server_key_share(((initial_server, 0)[Some(TlsQueryMatcher::Handshake(Some(HandshakeType::ServerHello)))]))),
+            (fn_get_ticket_nonce((@new_ticket_message))),
+            fn_named_group_secp384r1
+        )
+    };
+
+    let binder = term! {
+        fn_derive_binder(
+            (@client_hello),
+            (@psk)
+        )
+    };
+
+    let full_client_hello = term! {
+        fn_fill_binder(
+            (@client_hello),
+            (@binder)
+        )
+    };
+
+    let resumption_client_finished = term! {
+        fn_finished(
+            (fn_verify_data(
+                (fn_server_finished_transcript(((server, 0)))),
+                (fn_server_hello_transcript(((server, 0)))),
+                (fn_get_server_key_share(((server, 0)[Some(TlsQueryMatcher::Handshake(Some(HandshakeType::ServerHello)))]))),
+                (fn_psk((@psk))),
+                fn_named_group_secp384r1
+            ))
+        )
+    };
+
+    Trace {
+        prior_traces: vec![initial_handshake],
+        descriptors: vec![AgentDescriptor::new_reusable_server(
+            server,
+            TLSVersion::V1_3,
+        )],
+        steps: vec![
+            Step {
+                agent: server,
+                action: Action::Input(InputAction {
+                    recipe: term! {
+                        @full_client_hello
+                    },
+                }),
+            },
+            Step {
+                agent: server,
+                action: Action::Input(InputAction {
+                    recipe: term! {
+                        fn_encrypt_handshake(
+                            (@resumption_client_finished),
+                            (fn_server_hello_transcript(((server, 0)))),
+                            (fn_get_server_key_share(((server, 0)[Some(TlsQueryMatcher::Handshake(Some(HandshakeType::ServerHello)))]))),
+                            (fn_psk((@psk))),
+                            fn_named_group_secp384r1,
+                            fn_true,
+                            fn_seq_0  // sequence 0
+                        )
+                    },
+                }),
+            },
+        ],
+    }
+}
+
+/// The first ticket which `initial_server` issued after the handshake of [`seed_client_attacker`],
+/// and the PSK which is derived from it.
+fn resumption_ticket_and_psk(
+    initial_server: AgentName,
+) -> (Term<TlsQueryMatcher>, Term<TlsQueryMatcher>) {
+    let extensions = term! {
+        fn_decrypt_application_flight(
+            ((initial_server, 1)/MessageFlight), // The first flight of messages sent by the server
+            (fn_server_hello_transcript(((initial_server, 0)))),
+            (fn_server_finished_transcript(((initial_server, 0)))),
+            (fn_get_server_key_share(((initial_server, 0)))),
+            fn_no_psk,
+            fn_named_group_secp384r1,
+            fn_true,
+            fn_seq_0  // sequence 0
+        )
+    };
+
+    let new_ticket_message = term! {fn_find_server_ticket((@extensions))};
+
+    let psk = term! {
+        fn_derive_psk(
+            (fn_server_hello_transcript(((initial_server, 0)))),
+            (fn_server_finished_transcript(((initial_server, 0)))),
+            (fn_client_finished_transcript(((initial_server, 0)))),
+            (fn_get_server_key_share(((initial_server, 0)[Some(TlsQueryMatcher::Handshake(Some(HandshakeType::ServerHello)))]))),
+            (fn_get_ticket_nonce((@new_ticket_message))),
+            fn_named_group_secp384r1
+        )
+    };
+
+    (new_ticket_message, psk)
+}
+
+/// Resumes a session and sends 0-RTT early data before the server responds to the ClientHello.
+///
+/// The early data and the EndOfEarlyData message are encrypted with the keys derived from the
+/// client_early_traffic_secret, see RFC 8446, Section 4.5. Servers only accept the early data if
+/// they are configured to, e.g. with the PUT option `max_early_data`.
+pub fn seed_0rtt(initial_server: AgentName, server: AgentName) -> Trace<TlsQueryMatcher> {
+    let initial_handshake = seed_client_attacker(initial_server);
+
+    let (new_ticket_message, psk) = resumption_ticket_and_psk(initial_server);
+
+    let client_hello = term! {
+          fn_client_hello(
+            fn_protocol_version12,
+            fn_new_random,
+            fn_new_session_id,
+            (fn_append_cipher_suite(
+                (fn_new_cipher_suites()),
+                fn_cipher_suite13_aes_128_gcm_sha256
+            )),
+            fn_compressions,
+            (fn_client_extensions_append(
+                (fn_client_extensions_append(
+                    (fn_client_extensions_append(
+                        (fn_client_extensions_append(
+                            (fn_client_extensions_append(
+                                (fn_client_extensions_append(
+                                    (fn_client_extensions_append(
+                                        fn_client_extensions_new,
+                                        (fn_support_group_extension(fn_named_group_secp384r1))
+                                    )),
+                                    fn_signature_algorithm_extension
+                                )),
+                                fn_supported_versions13_extension
+                            )),
+                            (fn_key_share_deterministic_extension(fn_named_group_secp384r1))
+                        )),
+                        fn_psk_exchange_mode_dhe_ke_extension
+                    )),
+                    fn_early_data_extension
+                )),
+                // https://datatracker.ietf.org/doc/html/rfc8446#section-2.2
+                // must be last in client_hello, and initially empty until filled by fn_fill_binder
+                (fn_preshared_keys_extension_empty_binder(
+                    (@new_ticket_message)
+                ))
+            ))
+        )
     };
 
     let binder = term! {
@@ -1200,10 +1809,21 @@ pub fn seed_session_resumption_dhe(
         )
     };
 
+    // The early traffic secret is derived from the transcript which only contains the ClientHello
+    let client_hello_transcript = term! {
+        fn_append_transcript(
+            fn_new_transcript,
+            (@full_client_hello)
+        )
+    };
+
     let resumption_client_finished = term! {
         fn_finished(
             (fn_verify_data(
-                (fn_server_finished_transcript(((server, 0)))),
+                (fn_append_transcript(
+                    (fn_server_finished_transcript(((server, 0)))),
+                    fn_end_of_early_data
+                )),
                 (fn_server_hello_transcript(((server, 0)))),
                 (fn_get_server_key_share(((server, 0)[Some(TlsQueryMatcher::Handshake(Some(HandshakeType::ServerHello)))]))),
                 (fn_psk((@psk))),
@@ -1214,7 +1834,10 @@ pub fn seed_session_resumption_dhe(
 
     Trace {
         prior_traces: vec![initial_handshake],
-        descriptors: vec![AgentDescriptor::new_server(server, TLSVersion::V1_3)],
+        descriptors: vec![AgentDescriptor::new_reusable_server(
+            server,
+            TLSVersion::V1_3,
+        )],
         steps: vec![
             Step {
                 agent: server,
@@ -1224,6 +1847,32 @@ pub fn seed_session_resumption_dhe(
                     },
                 }),
             },
+            Step {
+                agent: server,
+                action: Action::Input(InputAction {
+                    recipe: term! {
+                        fn_encrypt_early_data(
+                            (fn_application_data(fn_http_method_get)),
+                            (@client_hello_transcript),
+                            (@psk),
+                            fn_seq_0  // sequence 0
+                        )
+                    },
+                }),
+            },
+            Step {
+                agent: server,
+                action: Action::Input(InputAction {
+                    recipe: term! {
+                        fn_encrypt_early_data(
+                            fn_end_of_early_data,
+                            (@client_hello_transcript),
+                            (@psk),
+                            fn_seq_1  // sequence 1
+                        )
+                    },
+                }),
+            },
             Step {
                 agent: server,
                 action: Action::Input(InputAction {
@@ -1235,7 +1884,7 @@ pub fn seed_session_resumption_dhe(
                             (fn_psk((@psk))),
                             fn_named_group_secp384r1,
                             fn_true,
-                            fn_seq_0  // sequence 0
+                            fn_seq_0  // sequence 0, the EndOfEarlyData used the early traffic keys
                         )
                     },
                 }),
@@ -1342,7 +1991,10 @@ pub fn seed_session_resumption_ke(
 
     Trace {
         prior_traces: vec![initial_handshake],
-        descriptors: vec![AgentDescriptor::new_server(server, TLSVersion::V1_3)],
+        descriptors: vec![AgentDescriptor::new_reusable_server(
+            server,
+            TLSVersion::V1_3,
+        )],
         steps: vec![
             Step {
                 agent: server,
@@ -1376,6 +2028,125 @@ pub fn seed_client_attacker_full(server: AgentName) -> Trace<TlsQueryMatcher> {
     _seed_client_attacker_full(server).0
 }
 
+/// After the handshake, the attacker sends application data, updates its keys with a KeyUpdate
+/// which requests an update of the server as well, and sends application data with the new keys.
+pub fn seed_post_handshake(server: AgentName) -> Trace<TlsQueryMatcher> {
+    let (mut trace, server_hello_transcript, server_finished_transcript, _) =
+        _seed_client_attacker_full(server);
+
+    let encrypt = |message: Term<TlsQueryMatcher>,
+                   generation: Term<TlsQueryMatcher>,
+                   sequence: Term<TlsQueryMatcher>| {
+        Step {
+            agent: server,
+            action: Action::Input(InputAction {
+                recipe: term! {
+                    fn_encrypt_application_updated(
+                        (@message),
+                        (@server_hello_transcript),
+                        (@server_finished_transcript),
+                        (fn_get_server_key_share(((server, 0)))),
+                        fn_no_psk,
+                        fn_named_group_secp384r1,
+                        (@generation),
+                        (@sequence)
+                    )
+                },
+            }),
+        }
+    };
+
+    trace.steps.extend([
+        encrypt(
+            term! { fn_application_data((fn_bytes_of_length(fn_seq_16))) },
+            term! { fn_seq_0 },
+            term! { fn_seq_0 },
+        ),
+        encrypt(
+            term! { fn_key_update },
+            term! { fn_seq_0 },
+            term! { fn_seq_1 },
+        ),
+        encrypt(
+            term! { fn_application_data((fn_bytes_of_length(fn_seq_16))) },
+            term! { fn_seq_1 },
+            term! { fn_seq_0 },
+        ),
+    ]);
+    trace
+}
+
+/// After the handshake, the attacker sends an empty application data record and one with the
+/// maximum length of a plaintext, which exercises the read path of the server.
+pub fn seed_application_data_lengths(server: AgentName) -> Trace<TlsQueryMatcher> {
+    let (mut trace, server_hello_transcript, server_finished_transcript, _) =
+        _seed_client_attacker_full(server);
+
+    let encrypt = |data: Term<TlsQueryMatcher>, sequence: Term<TlsQueryMatcher>| Step {
+        agent: server,
+        action: Action::Input(InputAction {
+            recipe: term! {
+                fn_encrypt_application(
+                    (fn_application_data((@data))),
+                    (@server_hello_transcript),
+                    (@server_finished_transcript),
+                    (fn_get_server_key_share(((server, 0)))),
+                    fn_no_psk,
+                    fn_named_group_secp384r1,
+                    (@sequence)
+                )
+            },
+        }),
+    };
+
+    trace.steps.extend([
+        encrypt(term! { fn_empty_bytes_vec }, term! { fn_seq_0 }),
+        encrypt(
+            term! { fn_bytes_of_length(fn_max_record_length) },
+            term! { fn_seq_1 },
+        ),
+    ]);
+    trace
+}
+
+/// After the handshake, the attacker sends an HTTP GET request. This reaches the HTTP parser of
+/// PUTs which are full servers.
+pub fn seed_http_get(server: AgentName) -> Trace<TlsQueryMatcher> {
+    let (mut trace, server_hello_transcript, server_finished_transcript, _) =
+        _seed_client_attacker_full(server);
+
+    let request = term! {
+        fn_http_request(
+            fn_http_method_get,
+            fn_http_path_root,
+            (fn_http_headers_append(
+                fn_http_headers_new,
+                fn_http_header_host,
+                fn_http_localhost
+            )),
+            fn_empty_bytes_vec
+        )
+    };
+
+    trace.steps.push(Step {
+        agent: server,
+        action: Action::Input(InputAction {
+            recipe: term! {
+                fn_encrypt_application(
+                    (fn_application_data((@request))),
+                    (@server_hello_transcript),
+                    (@server_finished_transcript),
+                    (fn_get_server_key_share(((server, 0)))),
+                    fn_no_psk,
+                    fn_named_group_secp384r1,
+                    fn_seq_0
+                )
+            },
+        }),
+    });
+    trace
+}
+
 /// Seed which contains the whole transcript in the tree. This is rather huge >300 symbols
 pub fn _seed_client_attacker_full(
     server: AgentName,
@@ -1701,7 +2472,10 @@ pub fn seed_session_resumption_dhe_full(
 
     Trace {
         prior_traces: vec![initial_handshake],
-        descriptors: vec![AgentDescriptor::new_server(server, TLSVersion::V1_3)],
+        descriptors: vec![AgentDescriptor::new_reusable_server(
+            server,
+            TLSVersion::V1_3,
+        )],
         steps: vec![
             Step {
                 agent: server,
@@ -1777,15 +2551,30 @@ pub fn create_corpus() -> Vec<(Trace<TlsQueryMatcher>, &'static str)> {
         seed_successful: cfg(feature = "tls13"),
         seed_successful_with_ccs: cfg(feature = "tls13"),
         seed_successful_with_tickets: cfg(feature = "tls13"),
+        seed_cross_session_relay: cfg(feature = "tls13"),
         seed_successful12: cfg(all(feature = "tls12", not(feature = "tls12-session-resumption"))),
         seed_successful12_with_tickets: cfg(all(feature = "tls12", feature = "tls12-session-resumption")),
         // Client Attackers
         seed_client_attacker: cfg(feature = "tls13"),
         seed_client_attacker_full: cfg(feature = "tls13"),
+        seed_post_handshake: cfg(feature = "tls13"),
+        seed_application_data_lengths: cfg(feature = "tls13"),
+        seed_hello_retry_request: cfg(feature = "tls13"),
         seed_client_attacker_auth: cfg(all(feature = "tls13", feature = "client-authentication-transcript-extraction")),
         seed_client_attacker12: cfg(feature = "tls12"),
+        seed_close_notify_after_server_hello: cfg(feature = "tls13"),
+        seed_client_attacker_middlebox_compat: cfg(feature = "tls13"),
+        seed_client_attacker_fragmented: cfg(feature = "tls13"),
+        seed_client_attacker_alpn_sni: cfg(feature = "tls13"),
+        seed_early_change_cipher_spec: cfg(feature = "tls13"),
+        seed_warning_alert12: cfg(feature = "tls12"),
+        seed_client_attacker_renegotiation12: cfg(feature = "tls12"),
+        seed_insecure_renegotiation12: cfg(feature = "tls12"),
+        seed_client_auth: cfg(all(feature = "tls13", feature = "client-authentication-transcript-extraction")),
+        seed_client_auth12: cfg(feature = "tls12"),
         // Session resumption
         seed_session_resumption_dhe: cfg(all(feature = "tls13", feature = "tls13-session-resumption")),
+        seed_0rtt: cfg(all(feature = "tls13", feature = "tls13-session-resumption")),
         seed_session_resumption_ke: cfg(all(feature = "tls13", feature = "tls13-session-resumption")),
         // Server Attackers
         seed_server_attacker_full: cfg(feature = "tls13")
@@ -1808,6 +2597,78 @@ pub mod tests {
         }
     }
 
+    /// Every seed of the corpus succeeds with every linked PUT. PUTs which need options to run are
+    /// skipped, and seeds which a PUT can not handle are listed with the reason. These are the
+    /// seeds whose tests above are disabled for the linked library.
+    #[test_log::test]
+    fn test_seed_conformance() {
+        use puffin::conformance::ConformanceSuite;
+        use puffin::process_put::PROCESS_PUT;
+        use puffin::put_registry::TCP_PUT;
+
+        let registry = tls_registry();
+        let put = registry.default().name();
+
+        #[allow(unused_mut)]
+        let mut suite = ConformanceSuite::new(registry)
+            .skip_put(TCP_PUT)
+            .skip_put(PROCESS_PUT)
+            .expect_failure(
+                &put,
+                "seed_0rtt",
+                "early data is disabled unless the `max_early_data` option is set",
+            );
+
+        #[cfg(feature = "boringssl-binding")]
+        {
+            for seed in [
+                "seed_successful",
+                "seed_successful_with_ccs",
+                "seed_successful_with_tickets",
+                "seed_cross_session_relay",
+                "seed_server_attacker_full",
+            ] {
+                suite = suite.expect_failure(
+                    &put,
+                    seed,
+                    "`[BAD_DECRYPT] [DECRYPTION_FAILED_OR_BAD_RECORD_MAC]` error with BoringSSL",
+                );
+            }
+            for seed in ["seed_successful12", "seed_successful12_with_tickets"] {
+                suite = suite.expect_failure(
+                    &put,
+                    seed,
+                    "`[RENEGOTIATION_MISMATCH] [ERROR_PARSING_EXTENSION] [PARSE_TLSEXT]` error with BoringSSL",
+                );
+            }
+            for seed in [
+                "seed_client_attacker_auth",
+                "seed_client_auth",
+                "seed_client_auth12",
+            ] {
+                suite = suite.expect_failure(&put, seed, "`BAD_SIGNATURE` error with BoringSSL");
+            }
+            for seed in ["seed_session_resumption_dhe", "seed_session_resumption_ke"] {
+                suite = suite.expect_failure(
+                    &put,
+                    seed,
+                    "`Unable to find variable (Some(Agent(AgentName(0))), 1)[None]/MessageFlight!` error with BoringSSL",
+                );
+            }
+        }
+
+        #[cfg(feature = "wolfssl-disable-postauth")]
+        for seed in ["seed_session_resumption_dhe", "seed_session_resumption_ke"] {
+            suite = suite.expect_failure(
+                &put,
+                seed,
+                "WolfSSL sends no tickets without post-handshake messages",
+            );
+        }
+
+        suite.run().assert_conforming();
+    }
+
     #[test_log::test]
     #[cfg(feature = "tls12")]
     fn test_seed_client_attacker12() {
@@ -1831,6 +2692,42 @@ pub mod tests {
         assert!(ctx.agents_successful());
     }
 
+    #[cfg(feature = "tls13")] // require version which supports TLS 1.3
+    #[cfg(feature = "transcript-extraction")] // this depends on extracted transcripts -> claims are required
+    #[test_log::test]
+    fn test_seed_client_attacker_fragmented() {
+        let runner = default_runner_for(tls_registry().default().name());
+        let trace = seed_client_attacker_fragmented.build_trace();
+
+        let ctx = runner.execute(trace).unwrap();
+
+        assert!(ctx.agents_successful());
+    }
+
+    #[cfg(feature = "tls13")] // require version which supports TLS 1.3
+    #[cfg(feature = "transcript-extraction")] // this depends on extracted transcripts -> claims are required
+    #[test_log::test]
+    fn test_seed_client_attacker_alpn_sni() {
+        let runner = default_runner_for(tls_registry().default().name());
+        let trace = seed_client_attacker_alpn_sni.build_trace();
+
+        let ctx = runner.execute(trace).unwrap();
+
+        assert!(ctx.agents_successful());
+    }
+
+    #[cfg(feature = "tls13")] // require version which supports TLS 1.3
+    #[cfg(feature = "transcript-extraction")] // this depends on extracted transcripts -> claims are required
+    #[test_log::test]
+    fn test_seed_hello_retry_request() {
+        let runner = default_runner_for(tls_registry().default().name());
+        let trace = seed_hello_retry_request.build_trace();
+
+        let ctx = runner.execute(trace).unwrap();
+
+        assert!(ctx.agents_successful());
+    }
+
     #[cfg(feature = "tls13")] // require version which supports TLS 1.3
     #[cfg(feature = "client-authentication-transcript-extraction")]
     #[cfg(not(feature = "boringssl-binding"))]
@@ -1844,6 +2741,31 @@ pub mod tests {
         assert!(ctx.agents_successful());
     }
 
+    #[cfg(feature = "tls13")] // require version which supports TLS 1.3
+    #[cfg(feature = "client-authentication-transcript-extraction")]
+    #[cfg(not(feature = "boringssl-binding"))]
+    #[test_log::test]
+    fn test_seed_client_auth() {
+        let runner = default_runner_for(tls_registry().default().name());
+        let trace = seed_client_auth.build_trace();
+
+        let ctx = runner.execute(trace).unwrap();
+
+        assert!(ctx.agents_successful());
+    }
+
+    #[test_log::test]
+    #[cfg(feature = "tls12")]
+    #[cfg(not(feature = "boringssl-binding"))]
+    fn test_seed_client_auth12() {
+        let runner = default_runner_for(tls_registry().default().name());
+        let trace = seed_client_auth12.build_trace();
+
+        let ctx = runner.execute(trace).unwrap();
+
+        assert!(ctx.agents_successful());
+    }
+
     #[cfg(feature = "tls13")] // require version which supports TLS 1.3
     #[test_log::test]
     fn test_seed_client_attacker_full() {
@@ -1855,6 +2777,105 @@ pub mod tests {
         assert!(ctx.agents_successful());
     }
 
+    #[cfg(feature = "tls13")] // require version which supports TLS 1.3
+    #[test_log::test]
+    fn test_seed_post_handshake() {
+        let runner = default_runner_for(tls_registry().default().name());
+        let trace = seed_post_handshake.build_trace();
+
+        let ctx = runner.execute(trace).unwrap();
+
+        assert!(ctx.agents_successful());
+    }
+
+    #[cfg(feature = "tls13")] // require version which supports TLS 1.3
+    #[test_log::test]
+    fn test_seed_application_data_lengths() {
+        let runner = default_runner_for(tls_registry().default().name());
+        let trace = seed_application_data_lengths.build_trace();
+
+        let ctx = runner.execute(trace).unwrap();
+
+        assert!(ctx.agents_successful());
+    }
+
+    #[cfg(feature = "tls13")] // require version which supports TLS 1.3
+    #[test_log::test]
+    fn test_seed_http_get() {
+        let runner = default_runner_for(tls_registry().default().name());
+        let trace = seed_http_get.build_trace();
+
+        let ctx = runner.execute(trace).unwrap();
+
+        assert!(ctx.agents_successful());
+    }
+
+    #[cfg(feature = "tls13")] // require version which supports TLS 1.3
+    #[test_log::test]
+    fn test_expect_server_hello() {
+        use puffin::error::Error;
+        use puffin::trace::ExpectAction;
+
+        let runner = default_runner_for(tls_registry().default().name());
+        let server = AgentName::first();
+        let server_hello = Some(TlsQueryMatcher::Handshake(Some(HandshakeType::ServerHello)));
+
+        let mut trace = seed_client_attacker_full.build_trace();
+        trace.steps.truncate(1);
+        trace
+            .steps
+            .push(ExpectAction::new_step(server, vec![server_hello], true));
+        assert!(runner.execute(&trace).is_ok());
+
+        trace.steps[1] = ExpectAction::new_step(server, vec![server_hello], false);
+        assert!(matches!(
+            runner.execute(&trace),
+            Err(Error::SecurityClaim(_))
+        ));
+    }
+
+    #[cfg(feature = "tls13")] // require version which supports TLS 1.3
+    #[test_log::test]
+    fn test_expect_server_hello_extension() {
+        use puffin::error::Error;
+        use puffin::trace::ExpectAction;
+
+        let runner = default_runner_for(tls_registry().default().name());
+        let server = AgentName::first();
+        let server_hello = Some(TlsQueryMatcher::Handshake(Some(HandshakeType::ServerHello)));
+        let has_extension = |extension: Term<TlsQueryMatcher>| {
+            term! {
+                fn_has_server_extension(
+                    ((server, 0)[Some(TlsQueryMatcher::Handshake(Some(HandshakeType::ServerHello)))]/Vec<ServerExtension>),
+                    (@extension)
+                )
+            }
+        };
+
+        let mut trace = seed_client_attacker_full.build_trace();
+        trace.steps.truncate(1);
+        trace.steps.push(ExpectAction::new_step_with_conditions(
+            server,
+            vec![server_hello],
+            true,
+            vec![has_extension(
+                term! { fn_supported_versions13_server_extension },
+            )],
+        ));
+        assert!(runner.execute(&trace).is_ok());
+
+        trace.steps[1] = ExpectAction::new_step_with_conditions(
+            server,
+            vec![server_hello],
+            true,
+            vec![has_extension(term! { fn_early_data_server_extension })],
+        );
+        assert!(matches!(
+            runner.execute(&trace),
+            Err(Error::SecurityClaim(_))
+        ));
+    }
+
     #[cfg(feature = "tls13")] // require version which supports TLS 1.3
     #[cfg(not(feature = "boringssl-binding"))]
     #[test_log::test]
@@ -1880,6 +2901,21 @@ pub mod tests {
         assert!(ctx.agents_successful());
     }
 
+    #[cfg(all(feature = "tls13", feature = "tls13-session-resumption"))]
+    #[cfg(not(feature = "wolfssl-disable-postauth"))]
+    #[cfg(not(feature = "boringssl-binding"))]
+    #[test_log::test]
+    fn test_seed_session_resumption_dhe_without_reuse() {
+        let runner = default_runner_for(tls_registry().default().name());
+        let mut trace = seed_session_resumption_dhe.build_trace();
+        // A new server does not know the ticket keys of the initial server
+        trace.descriptors[0].try_reuse = false;
+
+        let result = runner.execute(trace);
+
+        assert!(result.map_or(true, |ctx| !ctx.agents_successful()));
+    }
+
     #[cfg(all(feature = "tls13", feature = "tls13-session-resumption"))]
     #[cfg(not(feature = "wolfssl-disable-postauth"))]
     #[cfg(not(feature = "boringssl-binding"))]
@@ -1906,6 +2942,56 @@ pub mod tests {
         assert!(ctx.agents_successful());
     }
 
+    #[cfg(all(feature = "tls13", feature = "tls13-session-resumption"))]
+    #[cfg(any(feature = "openssl111-binding", feature = "wolfssl-binding"))]
+    #[cfg(not(feature = "wolfssl-disable-postauth"))]
+    #[test_log::test]
+    fn test_seed_0rtt() {
+        use puffin::put::PutDescriptor;
+        use puffin::trace::ExpectAction;
+
+        let runner = default_runner_for(PutDescriptor::new(
+            tls_registry().default().name(),
+            vec![("max_early_data", "16384")],
+        ));
+        let initial_server = AgentName::first();
+        let server = initial_server.next();
+        let (_, psk) = resumption_ticket_and_psk(initial_server);
+
+        // The server accepts the early data iff its EncryptedExtensions contain the early_data
+        // extension, see RFC 8446, Section 4.2.10
+        let accepted = term! {
+            fn_has_server_extension(
+                (fn_get_encrypted_extensions(
+                    (fn_find_encrypted_extensions(
+                        (fn_decrypt_handshake_flight(
+                            ((server, 0)/MessageFlight),
+                            (fn_server_hello_transcript(((server, 0)))),
+                            (fn_get_server_key_share(((server, 0)[Some(TlsQueryMatcher::Handshake(Some(HandshakeType::ServerHello)))]))),
+                            (fn_psk((@psk))),
+                            fn_named_group_secp384r1,
+                            fn_true,
+                            fn_seq_0  // sequence 0
+                        ))
+                    ))
+                )),
+                fn_early_data_server_extension
+            )
+        };
+
+        let mut trace = seed_0rtt.build_trace();
+        trace.steps.push(ExpectAction::new_step_with_conditions(
+            server,
+            vec![],
+            false,
+            vec![accepted],
+        ));
+
+        let ctx = runner.execute(trace).unwrap();
+
+        assert!(ctx.agents_successful());
+    }
+
     #[cfg(feature = "tls13")] // require version which supports TLS 1.3
     #[cfg(not(feature = "boringssl-binding"))]
     #[test_log::test]
@@ -1918,6 +3004,43 @@ pub mod tests {
         assert!(ctx.agents_successful());
     }
 
+    #[cfg(feature = "openssl111-binding")]
+    #[test_log::test]
+    fn test_seed_successful_key_update() {
+        use puffin::trace::{ExpectAction, KeyUpdateAction};
+
+        let runner = default_runner_for(tls_registry().default().name());
+        let server = AgentName::first().next();
+
+        let mut trace = seed_successful.build_trace();
+        trace.steps.push(KeyUpdateAction::new_step(server, true));
+        // The KeyUpdate is encrypted
+        trace.steps.push(ExpectAction::new_step(
+            server,
+            vec![Some(TlsQueryMatcher::ApplicationData)],
+            true,
+        ));
+
+        let ctx = runner.execute(trace).unwrap();
+
+        assert!(ctx.agents_successful());
+    }
+
+    #[cfg(feature = "tls13")] // require version which supports TLS 1.3
+    #[cfg(not(feature = "boringssl-binding"))]
+    #[test_log::test]
+    fn test_seed_cross_session_relay() {
+        let runner = default_runner_for(tls_registry().default().name());
+        let trace = seed_cross_session_relay.build_trace();
+        let server = AgentName::first().next();
+        let other_server = server.next();
+
+        let ctx = runner.execute(trace).unwrap();
+
+        assert!(ctx.find_agent(server).unwrap().is_state_successful());
+        assert!(!ctx.find_agent(other_server).unwrap().is_state_successful());
+    }
+
     #[cfg(feature = "tls13")] // require version which supports TLS 1.3
     #[cfg(not(feature = "boringssl-binding"))]
     #[test_log::test]
@@ -2008,7 +3131,11 @@ pub mod tests {
                             terms
                         );
                     }
-                    Action::Output(_) => {}
+                    Action::Output(_)
+                    | Action::Expect(_)
+                    | Action::Reset(_)
+                    | Action::Renegotiate(_)
+                    | Action::KeyUpdate(_) => {}
                 }
             }
         }
@@ -2025,6 +3152,7 @@ pub mod tests {
             seed_successful_client_auth.build_named_trace(),
             seed_successful.build_named_trace(),
             seed_successful_mitm.build_named_trace(),
+            seed_cross_session_relay.build_named_trace(),
             seed_successful12_with_tickets.build_named_trace(),
             seed_successful12.build_named_trace(),
             seed_successful_with_ccs.build_named_trace(),
@@ -2036,6 +3164,15 @@ pub mod tests {
             seed_session_resumption_dhe.build_named_trace(),
             seed_session_resumption_ke.build_named_trace(),
             seed_client_attacker_full.build_named_trace(),
+            seed_hello_retry_request.build_named_trace(),
+            seed_close_notify_after_server_hello.build_named_trace(),
+            seed_warning_alert12.build_named_trace(),
+            seed_client_attacker_renegotiation12.build_named_trace(),
+            seed_insecure_renegotiation12.build_named_trace(),
+            seed_client_attacker_middlebox_compat.build_named_trace(),
+            seed_early_change_cipher_spec.build_named_trace(),
+            seed_client_attacker_fragmented.build_named_trace(),
+            seed_client_attacker_alpn_sni.build_named_trace(),
             // _full can be large: seed_session_resumption_dhe_full.build_named_trace(),
         ] {
             for step in &trace.steps {
@@ -2051,7 +3188,11 @@ pub mod tests {
                             terms
                         );
                     }
-                    Action::Output(_) => {}
+                    Action::Output(_)
+                    | Action::Expect(_)
+                    | Action::Reset(_)
+                    | Action::Renegotiate(_)
+                    | Action::KeyUpdate(_) => {}
                 }
             }
         }
@@ -0,0 +1,203 @@
+//! Verification of the transcripts which the agents claim.
+//!
+//! The transcript hash of a handshake covers all handshake messages which an agent sent and
+//! received. The hashes of the transcripts up to the ClientHello and up to the ServerHello are
+//! recomputed from the flights which were actually exchanged with each agent and compared with
+//! the transcripts which the PUT claims. A mismatch means that the PUT hashed other bytes than it
+//! processed, for example because it re-encoded a message instead of hashing the received one.
+//!
+//! Later transcripts cover encrypted messages and are therefore not recomputed. Handshakes with a
+//! HelloRetryRequest are skipped, because their transcript starts with a synthetic message.
+
+use itertools::Itertools;
+use puffin::agent::AgentName;
+use puffin::codec::Codec;
+use puffin::counters::Direction;
+use puffin::variable_data::VariableData;
+use ring::digest;
+
+use crate::claims::{ClaimData, ClaimDataTranscript, TlsClaim, Transcript};
+use crate::protocol::MessageFlight;
+use crate::tls::rustls::msgs::handshake::{HandshakeMessagePayload, HandshakePayload};
+use crate::tls::rustls::msgs::message::MessagePayload;
+
+/// Compares the ClientHello and ServerHello transcripts which the agents claimed with the
+/// transcripts of the messages which they exchanged.
+pub fn check_transcripts(
+    claims: &[TlsClaim],
+    exchanged: &[(AgentName, Direction, &dyn VariableData)],
+) -> Option<&'static str> {
+    for agent_name in claims.iter().map(|claim| claim.agent_name).unique() {
+        let handshake = handshake_messages(agent_name, exchanged);
+
+        let client_hellos = handshake
+            .iter()
+            .filter(|message| matches!(message.payload, HandshakePayload::ClientHello(_)))
+            .count();
+        let retried = handshake
+            .iter()
+            .any(|message| matches!(message.payload, HandshakePayload::HelloRetryRequest(_)));
+        if client_hellos != 1 || retried {
+            continue;
+        }
+
+        let end_of = |is_end: fn(&HandshakePayload) -> bool| {
+            handshake
+                .iter()
+                .position(|message| is_end(&message.payload))
+        };
+        let client_hello_end =
+            end_of(|payload| matches!(payload, HandshakePayload::ClientHello(_)));
+        let server_hello_end =
+            end_of(|payload| matches!(payload, HandshakePayload::ServerHello(_)));
+
+        let agent_claims = claims
+            .iter()
+            .filter(|claim| claim.agent_name == agent_name)
+            .collect::<Vec<_>>();
+
+        let claimed_client_hello = agent_claims.iter().find_map(|claim| match &claim.data {
+            ClaimData::Transcript(ClaimDataTranscript::ClientHello(transcript)) => {
+                Some(transcript.as_slice())
+            }
+            _ => None,
+        });
+        let claimed_server_hello = agent_claims.iter().find_map(|claim| match &claim.data {
+            ClaimData::Transcript(ClaimDataTranscript::ServerHello(transcript)) => {
+                Some(transcript.as_slice())
+            }
+            _ => None,
+        });
+
+        for (claimed, end) in [
+            (claimed_client_hello, client_hello_end),
+            (claimed_server_hello, server_hello_end),
+        ] {
+            if let (Some(claimed), Some(end)) = (claimed, end) {
+                match transcript_hash(&handshake[..=end], claimed.len()) {
+                    Some(expected) if expected != claimed => {
+                        return Some("Mismatching transcript");
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// The plaintext handshake messages which `agent_name` sent and received, in order
+fn handshake_messages(
+    agent_name: AgentName,
+    exchanged: &[(AgentName, Direction, &dyn VariableData)],
+) -> Vec<HandshakeMessagePayload> {
+    exchanged
+        .iter()
+        .filter(|(agent, _, _)| *agent == agent_name)
+        .filter_map(|(_, _, flight)| flight.boxed_any().downcast::<MessageFlight>().ok())
+        .flat_map(|flight| flight.messages)
+        .filter_map(|message| match message.payload {
+            MessagePayload::Handshake(handshake) => Some(handshake),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Hashes the encoding of `messages` with the algorithm whose output has `length` bytes. Returns
+/// `None` for unknown lengths.
+fn transcript_hash(messages: &[HandshakeMessagePayload], length: usize) -> Option<Vec<u8>> {
+    let algorithm = match length {
+        32 => &digest::SHA256,
+        48 => &digest::SHA384,
+        _ => return None,
+    };
+
+    let mut context = digest::Context::new(algorithm);
+    for message in messages {
+        context.update(&message.get_encoding());
+    }
+    Some(context.finish().as_ref().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use puffin::agent::{AgentName, AgentType, TLSVersion};
+    use puffin::codec::Codec;
+    use puffin::counters::Direction;
+    use puffin::variable_data::VariableData;
+    use ring::digest;
+
+    use super::check_transcripts;
+    use crate::claims::{
+        ClaimData, ClaimDataTranscript, TlsClaim, TlsTranscript, TranscriptClientHello,
+    };
+    use crate::protocol::MessageFlight;
+    use crate::tls::fn_impl::*;
+    use crate::tls::rustls::msgs::enums::{HandshakeType, ProtocolVersion};
+    use crate::tls::rustls::msgs::handshake::{
+        ClientHelloPayload, HandshakeMessagePayload, HandshakePayload, Random, SessionID,
+    };
+    use crate::tls::rustls::msgs::message::{Message, MessagePayload};
+
+    fn client_hello() -> HandshakeMessagePayload {
+        HandshakeMessagePayload {
+            typ: HandshakeType::ClientHello,
+            payload: HandshakePayload::ClientHello(ClientHelloPayload {
+                client_version: ProtocolVersion::TLSv1_2,
+                random: Random::from([7; 32]),
+                session_id: SessionID::empty(),
+                cipher_suites: vec![],
+                compression_methods: fn_compressions().unwrap(),
+                extensions: vec![fn_supported_versions13_extension().unwrap()],
+            }),
+        }
+    }
+
+    fn transcript_claim(agent_name: AgentName, hash: &[u8]) -> TlsClaim {
+        let mut data = [0u8; 64];
+        data[..hash.len()].copy_from_slice(hash);
+        TlsClaim {
+            agent_name,
+            origin: AgentType::Server,
+            protocol_version: TLSVersion::V1_3,
+            data: ClaimData::Transcript(ClaimDataTranscript::ClientHello(TranscriptClientHello(
+                TlsTranscript(data, hash.len() as i32),
+            ))),
+        }
+    }
+
+    #[test_log::test]
+    fn test_transcript_mismatch() {
+        let server = AgentName::first();
+        let flight = MessageFlight {
+            messages: vec![Message {
+                version: ProtocolVersion::TLSv1_2,
+                payload: MessagePayload::Handshake(client_hello()),
+            }],
+        };
+        let exchanged: [(AgentName, Direction, &dyn VariableData); 1] =
+            [(server, Direction::Received, &flight)];
+
+        let hash = digest::digest(&digest::SHA256, &client_hello().get_encoding());
+        assert_eq!(
+            check_transcripts(&[transcript_claim(server, hash.as_ref())], &exchanged),
+            None
+        );
+
+        let other = digest::digest(&digest::SHA256, b"other ClientHello");
+        assert_eq!(
+            check_transcripts(&[transcript_claim(server, other.as_ref())], &exchanged),
+            Some("Mismatching transcript")
+        );
+
+        // Transcripts of agents without exchanged messages are not checked
+        assert_eq!(
+            check_transcripts(
+                &[transcript_claim(server.next(), other.as_ref())],
+                &exchanged
+            ),
+            None
+        );
+    }
+}
@@ -1,14 +1,32 @@
 use itertools::Itertools;
-use puffin::agent::{AgentType, TLSVersion};
+use puffin::agent::{AgentName, AgentType, TLSVersion};
 use puffin::claims::SecurityViolationPolicy;
+use puffin::counters::{Direction, TraceCounters};
+use puffin::variable_data::VariableData;
 
 use crate::claims::{ClaimData, ClaimDataMessage, Finished, TlsClaim};
+use crate::query::TlsQueryMatcher;
 use crate::static_certs::{ALICE_CERT, BOB_CERT};
+use crate::tls::rustls::msgs::base::Payload;
+use crate::tls::rustls::msgs::enums::{CipherSuite, HandshakeType};
+use crate::tls::rustls::msgs::message::OpaqueMessage;
+use crate::tls::transcript_policy::check_transcripts;
+
+/// Shorter secrets are ignored, because they would be found by chance in the knowledge
+const MIN_SECRET_LENGTH: usize = 16;
 
 pub struct TlsSecurityViolationPolicy;
 
-impl SecurityViolationPolicy<TlsClaim> for TlsSecurityViolationPolicy {
+impl SecurityViolationPolicy<TlsClaim, TlsQueryMatcher> for TlsSecurityViolationPolicy {
     fn check_violation(claims: &[TlsClaim]) -> Option<&'static str> {
+        if let Some(violation) = check_client_authentication(claims) {
+            return Some(violation);
+        }
+
+        if let Some(violation) = check_downgrade(claims) {
+            return Some(violation);
+        }
+
         if let Some((claim_a, claim_b)) = find_two_finished_messages(claims) {
             if let Some(((client_claim, client), (server_claim, server))) =
                 get_client_server(claim_a, claim_b)
@@ -119,6 +137,179 @@ impl SecurityViolationPolicy<TlsClaim> for TlsSecurityViolationPolicy {
 
         None
     }
+
+    fn check_counters(counters: &TraceCounters<TlsQueryMatcher>) -> Option<&'static str> {
+        for (_, agent) in counters.iter() {
+            // https://datatracker.ietf.org/doc/html/rfc8446#section-4.1.4
+            let hello_retry_requests = agent.messages_sent(&Some(TlsQueryMatcher::Handshake(
+                Some(HandshakeType::HelloRetryRequest),
+            )));
+            if hello_retry_requests > 1 {
+                return Some("More than one HelloRetryRequest");
+            }
+        }
+
+        None
+    }
+
+    fn check_secrecy(claims: &[TlsClaim], knowledge: &[&dyn VariableData]) -> Option<&'static str> {
+        check_leaked_secrets(claims, knowledge)
+    }
+
+    fn check_exchange(
+        claims: &[TlsClaim],
+        exchanged: &[(AgentName, Direction, &dyn VariableData)],
+    ) -> Option<&'static str> {
+        check_transcripts(claims, exchanged)
+    }
+}
+
+/// Checks whether the attacker learned a secret of a session, like the master secret. Whether the
+/// attacker can derive a secret is approximated by the data which it extracted from the messages
+/// of the agents, i.e. terms which consist of a single variable. A secret is therefore only found
+/// if an agent sent it, for example as plaintext or in a record which was not encrypted.
+pub fn check_leaked_secrets(
+    claims: &[TlsClaim],
+    knowledge: &[&dyn VariableData],
+) -> Option<&'static str> {
+    let secrets = claims
+        .iter()
+        .filter_map(|claim| match &claim.data {
+            ClaimData::Message(ClaimDataMessage::Finished(finished)) => Some(finished),
+            _ => None,
+        })
+        .flat_map(|finished| finished.all_secrets())
+        .map(|secret| {
+            // Secrets are reported in buffers of a fixed size
+            let length = secret.len() - secret.iter().rev().take_while(|b| **b == 0).count();
+            &secret[..length]
+        })
+        .filter(|secret| secret.len() >= MIN_SECRET_LENGTH)
+        .collect::<Vec<_>>();
+
+    if secrets.is_empty() {
+        return None;
+    }
+
+    let leaked = knowledge
+        .iter()
+        .filter_map(|data| knowledge_bytes(*data))
+        .any(|bytes| {
+            secrets
+                .iter()
+                .any(|secret| bytes.windows(secret.len()).any(|window| window == *secret))
+        });
+
+    if leaked {
+        Some("Leaked session secret")
+    } else {
+        None
+    }
+}
+
+/// Returns the bytes of knowledge which carries opaque data
+fn knowledge_bytes(data: &dyn VariableData) -> Option<Vec<u8>> {
+    use std::any::TypeId;
+
+    let type_id = data.type_id();
+    if type_id == TypeId::of::<Vec<u8>>() {
+        data.boxed_any()
+            .downcast::<Vec<u8>>()
+            .ok()
+            .map(|bytes| *bytes)
+    } else if type_id == TypeId::of::<Payload>() {
+        data.boxed_any()
+            .downcast::<Payload>()
+            .ok()
+            .map(|payload| payload.0)
+    } else if type_id == TypeId::of::<OpaqueMessage>() {
+        data.boxed_any()
+            .downcast::<OpaqueMessage>()
+            .ok()
+            .map(|message| message.payload.0)
+    } else {
+        None
+    }
+}
+
+/// Checks that each server which requires client authentication received a CertificateVerify
+/// before it received the Finished of the client. Otherwise the server completed the handshake
+/// with an unauthenticated client.
+pub fn check_client_authentication(claims: &[TlsClaim]) -> Option<&'static str> {
+    let mut verified: Vec<AgentName> = vec![];
+
+    for claim in claims {
+        match &claim.data {
+            ClaimData::Message(ClaimDataMessage::CertificateVerify(certificate_verify))
+                if !certificate_verify.outbound =>
+            {
+                verified.push(claim.agent_name);
+            }
+            ClaimData::Message(ClaimDataMessage::Finished(finished))
+                if !finished.outbound
+                    && finished.client_authentication
+                    && !verified.contains(&claim.agent_name) =>
+            {
+                return Some("Authentication bypass");
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Checks that the handshake between a client and a server was not downgraded by the attacker.
+///
+/// If both agents support TLS 1.3, then the handshake must not complete with TLS 1.2. The chosen
+/// cipher suite must not be an export-grade suite which the client did not offer.
+pub fn check_downgrade(claims: &[TlsClaim]) -> Option<&'static str> {
+    let ((client_claim, client), (server_claim, server)) =
+        find_two_finished_messages(claims).and_then(|(a, b)| get_client_server(a, b))?;
+
+    let client_hello = claims.iter().find_map(|claim| match &claim.data {
+        ClaimData::Message(ClaimDataMessage::ClientHello(client_hello))
+            if client_hello.outbound && claim.agent_name == client_claim.agent_name =>
+        {
+            Some(client_hello)
+        }
+        _ => None,
+    });
+
+    let offered_version = client_hello
+        .and_then(|client_hello| client_hello.version)
+        .unwrap_or(client_claim.protocol_version);
+    let negotiated_version = client.negotiated_version.or(server.negotiated_version);
+
+    if offered_version == TLSVersion::V1_3
+        && server_claim.protocol_version == TLSVersion::V1_3
+        && negotiated_version == Some(TLSVersion::V1_2)
+    {
+        return Some("Protocol version downgrade");
+    }
+
+    let offered_ciphers = match client_hello {
+        Some(client_hello) if !client_hello.available_ciphers.is_empty() => {
+            &client_hello.available_ciphers
+        }
+        _ => &client.available_ciphers,
+    };
+
+    if is_export_cipher(server.chosen_cipher)
+        && !offered_ciphers.is_empty()
+        && !offered_ciphers.contains(&server.chosen_cipher)
+    {
+        return Some("Export cipher suite downgrade");
+    }
+
+    None
+}
+
+fn is_export_cipher(cipher: u16) -> bool {
+    // All export-grade suites are named *_EXPORT_* or *_EXPORT1024_*
+    CipherSuite::from(cipher)
+        .as_str()
+        .map_or(false, |name| name.contains("_EXPORT"))
 }
 
 pub fn find_two_finished_messages(
@@ -163,3 +354,176 @@ pub fn get_client_server<'a, T>(
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use puffin::agent::{AgentName, AgentType, TLSVersion};
+    use puffin::variable_data::VariableData;
+
+    use super::{check_client_authentication, check_downgrade, check_leaked_secrets};
+    use crate::claims::{
+        CertificateVerify, ClaimData, ClaimDataMessage, ClientHello, Finished, TlsClaim,
+    };
+    use crate::tls::rustls::msgs::enums::CipherSuite;
+
+    fn claim(agent_name: AgentName, message: ClaimDataMessage) -> TlsClaim {
+        agent_claim(agent_name, AgentType::Server, message)
+    }
+
+    fn agent_claim(
+        agent_name: AgentName,
+        origin: AgentType,
+        message: ClaimDataMessage,
+    ) -> TlsClaim {
+        TlsClaim {
+            agent_name,
+            origin,
+            protocol_version: TLSVersion::V1_3,
+            data: ClaimData::Message(message),
+        }
+    }
+
+    fn client_finished(client_authentication: bool) -> ClaimDataMessage {
+        ClaimDataMessage::Finished(Finished {
+            outbound: false,
+            client_random: Default::default(),
+            server_random: Default::default(),
+            session_id: Default::default(),
+            authenticate_peer: client_authentication,
+            client_authentication,
+            peer_certificate: Default::default(),
+            master_secret: Default::default(),
+            secrets: Default::default(),
+            negotiated_version: None,
+            chosen_cipher: 0,
+            available_ciphers: Default::default(),
+            signature_algorithm: 0,
+            peer_signature_algorithm: 0,
+        })
+    }
+
+    #[test_log::test]
+    fn test_client_authentication_bypass() {
+        let server = AgentName::first();
+        let certificate_verify =
+            ClaimDataMessage::CertificateVerify(CertificateVerify { outbound: false });
+
+        assert_eq!(
+            check_client_authentication(&[claim(server, client_finished(true))]),
+            Some("Authentication bypass")
+        );
+        assert_eq!(
+            check_client_authentication(&[
+                claim(server, certificate_verify),
+                claim(server, client_finished(true))
+            ]),
+            None
+        );
+        assert_eq!(
+            check_client_authentication(&[claim(server, client_finished(false))]),
+            None
+        );
+    }
+
+    fn handshake(
+        negotiated_version: TLSVersion,
+        chosen_cipher: CipherSuite,
+        offered_ciphers: &[CipherSuite],
+    ) -> Vec<TlsClaim> {
+        let client = AgentName::first();
+        let server = client.next();
+
+        let finished = match client_finished(false) {
+            ClaimDataMessage::Finished(finished) => Finished {
+                negotiated_version: Some(negotiated_version),
+                chosen_cipher: chosen_cipher.get_u16(),
+                ..finished
+            },
+            _ => unreachable!(),
+        };
+
+        vec![
+            agent_claim(
+                client,
+                AgentType::Client,
+                ClaimDataMessage::ClientHello(ClientHello {
+                    outbound: true,
+                    version: Some(TLSVersion::V1_3),
+                    available_ciphers: offered_ciphers.iter().map(|c| c.get_u16()).collect(),
+                }),
+            ),
+            agent_claim(
+                server,
+                AgentType::Server,
+                ClaimDataMessage::Finished(finished.clone()),
+            ),
+            agent_claim(
+                client,
+                AgentType::Client,
+                ClaimDataMessage::Finished(finished),
+            ),
+        ]
+    }
+
+    #[test_log::test]
+    fn test_downgrade() {
+        let offered = [
+            CipherSuite::TLS13_AES_128_GCM_SHA256,
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+        ];
+
+        assert_eq!(
+            check_downgrade(&handshake(
+                TLSVersion::V1_3,
+                CipherSuite::TLS13_AES_128_GCM_SHA256,
+                &offered
+            )),
+            None
+        );
+        assert_eq!(
+            check_downgrade(&handshake(
+                TLSVersion::V1_2,
+                CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+                &offered
+            )),
+            Some("Protocol version downgrade")
+        );
+        assert_eq!(
+            check_downgrade(&handshake(
+                TLSVersion::V1_3,
+                CipherSuite::TLS_RSA_EXPORT_WITH_DES40_CBC_SHA,
+                &offered
+            )),
+            Some("Export cipher suite downgrade")
+        );
+    }
+
+    #[test_log::test]
+    fn test_leaked_secrets() {
+        let server = AgentName::first();
+        let finished = match client_finished(false) {
+            ClaimDataMessage::Finished(finished) => Finished {
+                master_secret: [7u8; 48].into_iter().chain([0u8; 16]).collect(),
+                ..finished
+            },
+            _ => unreachable!(),
+        };
+        let claims = [claim(server, ClaimDataMessage::Finished(finished))];
+
+        let unrelated: Vec<u8> = vec![7u8; 8];
+        assert_eq!(check_leaked_secrets(&claims, &[&unrelated]), None);
+
+        let leaked: Vec<u8> = [1u8, 2].into_iter().chain([7u8; 48]).collect();
+        let knowledge: [&dyn VariableData; 2] = [&unrelated, &leaked];
+        assert_eq!(
+            check_leaked_secrets(&claims, &knowledge),
+            Some("Leaked session secret")
+        );
+
+        // secrets which are not reported are not checked
+        assert_eq!(
+            check_leaked_secrets(&[claim(server, client_finished(false))], &[&vec![0u8; 64]]),
+            None
+        );
+    }
+}
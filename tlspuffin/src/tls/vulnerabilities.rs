@@ -365,6 +365,17 @@ pub fn seed_cve_2021_3449(server: AgentName) -> Trace<TlsQueryMatcher> {
     trace
 }
 
+/// OpenSSL accepted a ChangeCipherSpec before the master secret was established and derived its
+/// keys from an empty master secret. The attacker therefore sends a ChangeCipherSpec directly
+/// after the ClientHello.
+///
+/// <https://nvd.nist.gov/vuln/detail/cve-2014-0224>
+pub fn seed_cve_2014_0224(server: AgentName) -> Trace<TlsQueryMatcher> {
+    let mut trace = seed_client_attacker12(server);
+    insert_change_cipher_spec(&mut trace, server, 1);
+    trace
+}
+
 pub fn seed_heartbleed(client: AgentName, server: AgentName) -> Trace<TlsQueryMatcher> {
     let client_hello = term! {
           fn_client_hello(
@@ -810,7 +821,10 @@ pub fn seed_cve_2022_39173(initial_server: AgentName, server: AgentName) -> Trac
         // Step 1: Prior trace performs an initial TLS 1.3 session with a full handshake and
         // establishes a PSK, including Client Hello number 1 (`CH1`).
         prior_traces: vec![initial_handshake],
-        descriptors: vec![AgentDescriptor::new_server(server, TLSVersion::V1_3)],
+        descriptors: vec![AgentDescriptor::new_reusable_server(
+            server,
+            TLSVersion::V1_3,
+        )],
         steps: vec![
             // Step 2: sends a Client Hello (CH2) with a missing support_group_extension that will
             // make the server enters the state `SERVER_HELLO_RETRY_REQUEST_COMPLETE`
@@ -945,7 +959,10 @@ pub fn seed_cve_2022_39173_full(
 
     Trace {
         prior_traces: vec![initial_handshake],
-        descriptors: vec![AgentDescriptor::new_server(server, TLSVersion::V1_3)],
+        descriptors: vec![AgentDescriptor::new_reusable_server(
+            server,
+            TLSVersion::V1_3,
+        )],
         steps: vec![
             Step {
                 agent: server,
@@ -1085,6 +1102,7 @@ pub mod tests {
             seed_cve_2022_25638.build_named_trace(),
             seed_cve_2022_25640.build_named_trace(),
             seed_cve_2021_3449.build_named_trace(),
+            seed_cve_2014_0224.build_named_trace(),
             seed_heartbleed.build_named_trace(),
             seed_freak.build_named_trace(),
             seed_cve_2022_25640_simple.build_named_trace(),
@@ -1106,7 +1124,11 @@ pub mod tests {
                             terms
                         );
                     }
-                    Action::Output(_) => {}
+                    Action::Output(_)
+                    | Action::Expect(_)
+                    | Action::Reset(_)
+                    | Action::Renegotiate(_)
+                    | Action::KeyUpdate(_) => {}
                 }
             }
         }
@@ -20,8 +20,9 @@ use wolfssl::version::version;
 use wolfssl::x509::X509;
 
 use crate::claims::{
-    ClaimData, ClaimDataMessage, ClaimDataTranscript, Finished, TlsClaim, TranscriptCertificate,
-    TranscriptClientFinished, TranscriptServerFinished, TranscriptServerHello,
+    CertificateVerify, ClaimData, ClaimDataMessage, ClaimDataTranscript, Finished, TlsClaim,
+    TranscriptCertificate, TranscriptClientFinished, TranscriptServerFinished,
+    TranscriptServerHello,
 };
 use crate::protocol::{OpaqueMessageFlight, TLSProtocolBehavior};
 use crate::put::TlsPutConfig;
@@ -109,9 +110,20 @@ pub struct WolfSSL {
     stream: SslStream<MemoryStream>,
     ctx: SslContext,
     config: TlsPutConfig,
+    /// Whether the server still reads early data, see [`WolfSSL::accepts_early_data`]
+    reading_early_data: bool,
 }
 
 impl Stream<TlsQueryMatcher, Message, OpaqueMessage, OpaqueMessageFlight> for WolfSSL {
+    fn add_inbound_flight(&mut self, encoded_flight: &[u8]) {
+        <MemoryStream as Stream<
+            TlsQueryMatcher,
+            Message,
+            OpaqueMessage,
+            OpaqueMessageFlight,
+        >>::add_inbound_flight(self.stream.get_mut(), encoded_flight)
+    }
+
     fn add_to_inbound(&mut self, opaque_flight: &OpaqueMessageFlight) {
         let raw_stream = self.stream.get_mut();
         <MemoryStream as Stream<TlsQueryMatcher, Message, OpaqueMessage, OpaqueMessageFlight>>::add_to_inbound(
@@ -137,7 +149,7 @@ impl WolfSSL {
         let agent_descriptor = &config.descriptor;
         #[allow(unused_mut)]
         let mut ctx = match agent_descriptor.typ {
-            AgentType::Server => Self::create_server_ctx(agent_descriptor)?,
+            AgentType::Server => Self::create_server_ctx(&config)?,
             AgentType::Client => Self::create_client_ctx(agent_descriptor)?,
         };
 
@@ -147,6 +159,7 @@ impl WolfSSL {
         let mut wolfssl = WolfSSL {
             ctx,
             stream,
+            reading_early_data: Self::accepts_early_data(&config),
             config: config.clone(),
         };
 
@@ -154,6 +167,15 @@ impl WolfSSL {
         Ok(wolfssl)
     }
 
+    /// WolfSSL only accepts early data if the server reads it with `wolfSSL_read_early_data`
+    /// instead of continuing the handshake otherwise. This is done by TLS 1.3 servers which are
+    /// configured to accept early data, until there is no early data left to read.
+    fn accepts_early_data(config: &TlsPutConfig) -> bool {
+        config.descriptor.typ == AgentType::Server
+            && config.descriptor.tls_version == TLSVersion::V1_3
+            && config.max_early_data.map_or(false, |bytes| bytes > 0)
+    }
+
     fn new_stream(
         ctx: &mut SslContextRef,
         config: &TlsPutConfig,
@@ -182,6 +204,19 @@ impl WolfSSL {
 
 impl Put<TLSProtocolBehavior> for WolfSSL {
     fn progress(&mut self) -> Result<(), Error> {
+        while self.reading_early_data {
+            let mut vec: Vec<u8> = Vec::from([1; 128]);
+            match self.stream.read_early_data(&mut vec) {
+                Ok(0) => self.reading_early_data = false,
+                Ok(_) => continue,
+                Err(err) => {
+                    self.deferred_transcript_extraction();
+                    let maybe_error: MaybeError = Err::<(), _>(err).into();
+                    return maybe_error.into();
+                }
+            }
+        }
+
         let result = if self.is_state_successful() {
             // Trigger another read
             let mut vec: Vec<u8> = Vec::from([1; 128]);
@@ -206,6 +241,7 @@ impl Put<TLSProtocolBehavior> for WolfSSL {
         } else {
             self.stream = Self::new_stream(&mut self.ctx, &self.config)?;
         }
+        self.reading_early_data = Self::accepts_early_data(&self.config);
 
         self.register_claimer();
 
@@ -290,9 +326,8 @@ impl WolfSSL {
         Ok(ssl)
     }
 
-    pub fn create_server_ctx(
-        descriptor: &AgentDescriptor,
-    ) -> Result<SslContext, WolfSSLErrorStack> {
+    pub fn create_server_ctx(config: &TlsPutConfig) -> Result<SslContext, WolfSSLErrorStack> {
+        let descriptor = &config.descriptor;
         let mut ctx = match descriptor.tls_version {
             TLSVersion::V1_3 => SslContext::new(SslMethod::tls_server_13())?,
             TLSVersion::V1_2 => SslContext::new(SslMethod::tls_server_12())?,
@@ -330,6 +365,13 @@ impl WolfSSL {
         // We expect two tickets like in OpenSSL
         #[cfg(not(feature = "wolfssl430"))]
         ctx.set_num_tickets(2)?;
+
+        if descriptor.tls_version == TLSVersion::V1_3 {
+            if let Some(max_early_data) = config.max_early_data {
+                ctx.set_max_early_data(max_early_data)?;
+            }
+        }
+
         Ok(ctx)
     }
 
@@ -385,11 +427,17 @@ impl WolfSSL {
             let claims = self.config.claims.clone();
             let protocol_version = self.config.descriptor.tls_version;
             let origin = self.config.descriptor.typ;
+            let client_authentication =
+                origin == AgentType::Server && self.config.descriptor.client_authentication;
 
             security_claims::register_claimer(
                 self.stream.ssl().as_ptr().cast(),
                 move |claim: security_claims::Claim| {
-                    if let Some(data) = claims_helpers::to_claim_data(protocol_version, claim) {
+                    for data in claims_helpers::to_claim_data(
+                        protocol_version,
+                        client_authentication,
+                        claim,
+                    ) {
                         claims.deref_borrow_mut().claim_sized(TlsClaim {
                             agent_name,
                             origin,
@@ -417,6 +465,8 @@ impl WolfSSL {
         let claims = config.claims.clone();
         let extract_transcript = config.extract_deferred.clone();
         let authenticate_peer = config.authenticate_peer;
+        let client_authentication =
+            origin == AgentType::Server && config.descriptor.client_authentication;
 
         move |context: &mut SslRef, content_type: i32, first_byte: u8, outbound: bool| {
             let typ = if content_type == 22 {
@@ -434,6 +484,15 @@ impl WolfSSL {
                             Some(TypeShape::of::<TranscriptCertificate>());
                     }
                     HandshakeType::CertificateVerify => {
+                        claims.deref_borrow_mut().claim_sized(TlsClaim {
+                            agent_name,
+                            origin,
+                            protocol_version,
+                            data: ClaimData::Message(ClaimDataMessage::CertificateVerify(
+                                CertificateVerify { outbound },
+                            )),
+                        });
+
                         // Extract ClientHello..ServerFinished..CertificateVerify transcript
                         // at the end of the message flight
                         *extract_transcript.deref().borrow_mut() =
@@ -450,11 +509,14 @@ impl WolfSSL {
                                 server_random: Default::default(), // TODO
                                 session_id: Default::default(),    // TODO
                                 authenticate_peer,
+                                client_authentication,
                                 peer_certificate: context
                                     .get_peer_certificate()
                                     .map(|cert| SmallVec::from_vec(cert))
                                     .unwrap_or_else(|| SmallVec::new()),
                                 master_secret: Default::default(), // TODO
+                                secrets: Default::default(),       // TODO
+                                negotiated_version: None,          // TODO
                                 chosen_cipher: 0,                  // TODO
                                 available_ciphers: Default::default(), // TODO
                                 signature_algorithm: 0,            // TODO
@@ -516,7 +578,7 @@ impl<T> From<Result<T, SslError>> for MaybeError {
                         // MemoryStream
                         MaybeError::Ok
                     }
-                    _ => MaybeError::Err(Error::IO(format!("Unexpected IO Error: {}", io_error))),
+                    _ => MaybeError::Err(Error::IO(io_error.into())),
                 }
             } else if let Some(ssl_error) = ssl_error.ssl_error() {
                 // OpenSSL threw an error, that means that there should be an Alert message in the
@@ -19,3 +19,25 @@ fn test_attacker_full_det_recreate() {
         assert_eq!(ctx_1, ctx_2);
     }
 }
+
+#[test_log::test]
+#[cfg(all(
+    feature = "deterministic",
+    feature = "openssl-binding",
+    feature = "tls12-session-resumption",
+))]
+fn test_openssl_persistent_resumption_det_repeat() {
+    use tlspuffin::test_utils::prelude::*;
+    use tlspuffin::tls::seeds::seed_successful12_with_tickets;
+
+    let runner = default_runner_for(tls_registry().default().name());
+    let trace = seed_successful12_with_tickets.build_trace();
+
+    let ctx_1 = runner.execute(&trace);
+
+    for i in 0..20 {
+        println!("Attempt #{i}...");
+        let ctx_2 = runner.execute(&trace);
+        assert_eq!(ctx_1, ctx_2);
+    }
+}
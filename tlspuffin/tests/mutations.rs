@@ -105,7 +105,11 @@ fn test_mutate_seed_cve_2021_3449() {
                                     }
                                 }
                             },
-                            Action::Output(_) => {}
+                            Action::Output(_)
+                            | Action::Expect(_)
+                            | Action::Reset(_)
+                            | Action::Renegotiate(_)
+                            | Action::KeyUpdate(_) => {}
                         }
                     }
                 }
@@ -134,7 +138,11 @@ fn test_mutate_seed_cve_2021_3449() {
                                     }
                                 }
                             },
-                            Action::Output(_) => {}
+                            Action::Output(_)
+                            | Action::Expect(_)
+                            | Action::Reset(_)
+                            | Action::Renegotiate(_)
+                            | Action::KeyUpdate(_) => {}
                         }
                     }
                 }
@@ -174,7 +182,11 @@ fn test_mutate_seed_cve_2021_3449() {
                                         }
                                     }
                                 },
-                                Action::Output(_) => {}
+                                Action::Output(_)
+                                | Action::Expect(_)
+                                | Action::Reset(_)
+                                | Action::Renegotiate(_)
+                                | Action::KeyUpdate(_) => {}
                             }
                         }
                     }
@@ -206,7 +218,11 @@ fn test_mutate_seed_cve_2021_3449() {
                                     }
                                 }
                             },
-                            Action::Output(_) => {}
+                            Action::Output(_)
+                            | Action::Expect(_)
+                            | Action::Reset(_)
+                            | Action::Renegotiate(_)
+                            | Action::KeyUpdate(_) => {}
                         }
                     }
                 }
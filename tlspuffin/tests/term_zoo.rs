@@ -1,8 +1,11 @@
 use std::collections::HashSet;
 
 use puffin::algebra::dynamic_function::DescribableFunction;
-use puffin::fuzzer::term_zoo::TermZoo;
+use puffin::algebra::Term;
+use puffin::fuzzer::term_zoo::{generate_random_trace, TermZoo};
 use puffin::libafl_bolts::rands::StdRand;
+use puffin::trace::Action;
+use tlspuffin::protocol::TLSProtocolBehavior;
 use tlspuffin::query::TlsQueryMatcher;
 use tlspuffin::tls::fn_impl::*;
 use tlspuffin::tls::TLS_SIGNATURE;
@@ -59,3 +62,29 @@ fn test_term_generation() {
     assert_eq!(difference.count(), 0);
     //println!("{}", graph);
 }
+
+fn depth(term: &Term<TlsQueryMatcher>) -> u16 {
+    match term {
+        Term::Variable(_) => 1,
+        Term::Application(_, subterms) => 1 + subterms.iter().map(depth).max().unwrap_or(0),
+    }
+}
+
+#[test_log::test]
+/// Tests whether random traces consist of well-typed messages of bounded size
+fn test_random_trace_generation() {
+    let mut rand = StdRand::with_seed(101);
+
+    for _ in 0..10 {
+        let trace =
+            generate_random_trace::<TLSProtocolBehavior, _>(&TLS_SIGNATURE, 4, &mut rand).unwrap();
+        assert!(!trace.steps.is_empty());
+
+        for step in &trace.steps {
+            match &step.action {
+                Action::Input(input) => assert!(depth(&input.recipe) <= 4),
+                _ => panic!("expected input"),
+            }
+        }
+    }
+}